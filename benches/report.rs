@@ -0,0 +1,76 @@
+//! Benchmarks of the conversion and report hot paths, over data from `fbar_prep::fixture`. Run with `cargo bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rust_decimal::Decimal;
+
+use fbar_prep::data::UserData;
+use fbar_prep::facts::Facts;
+use fbar_prep::fixture::{self, FixtureSpec};
+use fbar_prep::report::max_balance::max_value;
+use fbar_prep::report::valuation::{BalanceValuation, Valuation};
+use fbar_prep::report::{OpenPeriod, Report};
+use fbar_prep::report_context::ReportContext;
+
+const YEAR: i32 = 2023;
+
+fn context() -> ReportContext {
+    ReportContext::new(Facts::load_facts().expect("bundled facts load"), None)
+}
+
+fn user_data(accounts: usize, years: u32) -> UserData {
+    fixture::generate(&FixtureSpec {
+        providers: 5,
+        accounts,
+        first_year: YEAR - years as i32 + 1,
+        years,
+        ..Default::default()
+    })
+    .expect("fixture generates")
+}
+
+fn rate_lookup(c: &mut Criterion) {
+    let context = context();
+    c.bench_function("rate lookup", |b| {
+        b.iter(|| context.find_exchange_rate(black_box(YEAR), black_box("eur")))
+    });
+}
+
+fn conversion(c: &mut Criterion) {
+    let context = context();
+    let amounts: Vec<(&str, Decimal)> = (0..10_000)
+        .map(|index| {
+            (
+                if index % 2 == 0 { "eur" } else { "gbp" },
+                Decimal::new(index * 1_234, 2),
+            )
+        })
+        .collect();
+    c.bench_function("convert 10,000 amounts", |b| {
+        b.iter(|| context.convert_many(YEAR, black_box(amounts.iter().copied())))
+    });
+}
+
+fn max_balance(c: &mut Criterion) {
+    let context = context();
+    // Ten years of daily balances, of which the report year is a tenth
+    let user_data = user_data(1, 10);
+    let account = &user_data.accounts[0];
+    let period = OpenPeriod::for_account(account, YEAR).expect("account is open");
+    c.bench_function("max balance over 10 years of daily balances", |b| {
+        b.iter(|| {
+            let series = BalanceValuation.value_series(black_box(account), &period, &context)?;
+            max_value(&series)
+        })
+    });
+}
+
+fn full_report(c: &mut Criterion) {
+    let context = context();
+    let user_data = user_data(50, 3);
+    c.bench_function("report for 50 accounts of daily balances", |b| {
+        b.iter(|| Report::generate(YEAR, black_box(&user_data), &context))
+    });
+}
+
+criterion_group!(benches, rate_lookup, conversion, max_balance, full_report);
+criterion_main!(benches);
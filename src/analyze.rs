@@ -0,0 +1,406 @@
+//! Heuristics that look through bank transactions for foreign accounts the user may have forgotten to define: the
+//! accounts money moves to and from, and payroll that suggests a workplace pension.
+
+use anyhow::{bail, Result};
+use chrono::{Datelike, NaiveDate};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::Path;
+
+use crate::data::UserData;
+use crate::parsing::ParseContext;
+use crate::statements::gocardless::{self, GoCardlessImporter};
+use crate::statements::ledger::Transaction;
+use crate::statements::ofx::{self, OfxImporter};
+use crate::statements::StatementImporter;
+
+/// Words in a payment's reference or payer that mark it as pay, in the languages of common expat destinations
+const PAYROLL_WORDS: &[&str] = &[
+    "salary",
+    "payroll",
+    "wages",
+    "gehalt",
+    "lohn",
+    "salaire",
+    "sueldo",
+    "salario",
+    "stipendio",
+    "loon",
+    "nomina",
+];
+
+/// Transfers in this many different months make a counterparty a regular one
+const REGULAR_MONTHS: usize = 3;
+
+/// How likely a suggestion is to be an account that should be in `data.yml`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+impl fmt::Display for Confidence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Confidence::Low => "low",
+            Confidence::Medium => "medium",
+            Confidence::High => "high",
+        })
+    }
+}
+
+/// An account that may be missing from `data.yml`, with the evidence for it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub confidence: Confidence,
+    /// The account or counterparty, as the statement names it
+    pub counterparty: String,
+    pub reasons: Vec<String>,
+    /// Transactions the suggestion is based on
+    pub transactions: usize,
+    pub first: Option<NaiveDate>,
+    pub last: Option<NaiveDate>,
+}
+
+/// A statement's transactions, read for analysis
+#[derive(Debug, Clone, Default)]
+pub struct TransactionStatement {
+    pub source: String,
+    /// The account the statement itself is for, when the format says
+    pub account_id: Option<String>,
+    pub transactions: Vec<Transaction>,
+}
+
+/// Reads the transactions from a GoCardless or OFX export. CSV statements only hold balances, so they can't be
+/// analyzed.
+pub fn read_statement(path: &Path, context: &ParseContext) -> Result<TransactionStatement> {
+    let contents = std::fs::read(path)?;
+    let source = path.display().to_string();
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    if GoCardlessImporter.detect(&file_name, &contents) {
+        Ok(TransactionStatement {
+            transactions: gocardless::parse_transactions(&contents, &source, context)?,
+            source,
+            account_id: None,
+        })
+    } else if OfxImporter.detect(&file_name, &contents) {
+        let statement = String::from_utf8_lossy(&contents);
+        Ok(TransactionStatement {
+            transactions: ofx::parse_transactions(&statement, &source, context)?,
+            account_id: ofx::account_id(&statement).map(str::to_string),
+            source,
+        })
+    } else {
+        bail!(
+            "{} isn't a GoCardless or OFX export; only those list transactions",
+            source
+        )
+    }
+}
+
+/// Suggests accounts the statements point to that `data.yml` doesn't define, most likely first.
+///
+/// Counterparties matching an account's identifiers are known and skipped. The rest are scored on transfers to or from
+/// an account in a filer's name, money moving both ways, regular transfers, and pay coming in, which suggests an
+/// employer that may also pay into a pension the user holds. A statement for an account that isn't defined is
+/// suggested too.
+pub fn analyze(user_data: &UserData, statements: &[TransactionStatement]) -> Vec<Suggestion> {
+    let known: Vec<String> = user_data
+        .accounts
+        .iter()
+        .flat_map(|account| [&account.identifier1, &account.identifier2])
+        .flatten()
+        .map(|identifier| compact(identifier))
+        .collect();
+    let is_known = |account: &str| {
+        let account = compact(account);
+        known.iter().any(|identifier| {
+            *identifier == account || (identifier.len() >= 6 && account.ends_with(identifier))
+        })
+    };
+
+    let mut suggestions = Vec::new();
+    let mut counterparties: BTreeMap<String, Vec<&Transaction>> = BTreeMap::new();
+    for statement in statements {
+        if let Some(account_id) = &statement.account_id {
+            if !is_known(account_id) {
+                suggestions.push(Suggestion {
+                    confidence: Confidence::High,
+                    counterparty: account_id.clone(),
+                    reasons: vec![format!(
+                        "{} is a statement for this account, which isn't defined",
+                        statement.source
+                    )],
+                    transactions: statement.transactions.len(),
+                    first: statement
+                        .transactions
+                        .iter()
+                        .map(|transaction| transaction.date)
+                        .min(),
+                    last: statement
+                        .transactions
+                        .iter()
+                        .map(|transaction| transaction.date)
+                        .max(),
+                });
+            }
+        }
+
+        for transaction in &statement.transactions {
+            if transaction
+                .counterparty_account
+                .as_deref()
+                .is_some_and(is_known)
+            {
+                continue;
+            }
+            let key = match (
+                &transaction.counterparty_account,
+                &transaction.counterparty_name,
+            ) {
+                (Some(account), _) => compact(account),
+                (None, Some(name)) => name.to_lowercase(),
+                (None, None) => continue,
+            };
+            counterparties.entry(key).or_default().push(transaction);
+        }
+    }
+
+    for (key, transactions) in counterparties {
+        if let Some(suggestion) = score(&key, &transactions, user_data) {
+            suggestions.push(suggestion);
+        }
+    }
+
+    suggestions.sort_by(|a, b| {
+        b.confidence
+            .cmp(&a.confidence)
+            .then_with(|| b.transactions.cmp(&a.transactions))
+    });
+    suggestions
+}
+
+fn score(key: &str, transactions: &[&Transaction], user_data: &UserData) -> Option<Suggestion> {
+    let name = transactions
+        .iter()
+        .find_map(|transaction| transaction.counterparty_name.clone());
+    let has_account = transactions
+        .iter()
+        .any(|transaction| transaction.counterparty_account.is_some());
+    let months = |outgoing: bool| -> usize {
+        transactions
+            .iter()
+            .filter(|transaction| transaction.amount.is_sign_negative() == outgoing)
+            .map(|transaction| (transaction.date.year(), transaction.date.month()))
+            .collect::<BTreeSet<_>>()
+            .len()
+    };
+
+    let mut findings = Vec::new();
+    if let Some(name) = &name {
+        if let Some(filer) = user_data
+            .filers
+            .iter()
+            .find(|filer| same_person(&filer.name, name))
+        {
+            findings.push((
+                Confidence::High,
+                format!("transfers with an account in {}'s name", filer.name),
+            ));
+        }
+    }
+    if has_account && months(true) > 0 && months(false) > 0 {
+        findings.push((
+            Confidence::Medium,
+            "money both sent and received".to_string(),
+        ));
+    }
+    if has_account && months(true) >= REGULAR_MONTHS {
+        findings.push((
+            Confidence::Medium,
+            format!("regular transfers out in {} months", months(true)),
+        ));
+    } else if has_account && months(true) == 2 {
+        findings.push((Confidence::Low, "transfers out in 2 months".to_string()));
+    }
+    let paid = transactions
+        .iter()
+        .filter(|transaction| !transaction.amount.is_sign_negative() && is_payroll(transaction))
+        .map(|transaction| (transaction.date.year(), transaction.date.month()))
+        .collect::<BTreeSet<_>>()
+        .len();
+    if paid >= 2 {
+        findings.push((
+            Confidence::Low,
+            format!(
+                "pay received in {} months; employers abroad often pay into a workplace pension too",
+                paid
+            ),
+        ));
+    }
+
+    let confidence = findings.iter().map(|(confidence, _)| *confidence).max()?;
+    let counterparty = match (&name, has_account) {
+        (Some(name), true) => format!("{} ({})", name, key),
+        (Some(name), false) => name.clone(),
+        (None, _) => key.to_string(),
+    };
+    Some(Suggestion {
+        confidence,
+        counterparty,
+        reasons: findings.into_iter().map(|(_, reason)| reason).collect(),
+        transactions: transactions.len(),
+        first: transactions
+            .iter()
+            .map(|transaction| transaction.date)
+            .min(),
+        last: transactions
+            .iter()
+            .map(|transaction| transaction.date)
+            .max(),
+    })
+}
+
+fn is_payroll(transaction: &Transaction) -> bool {
+    [&transaction.description, &transaction.counterparty_name]
+        .into_iter()
+        .flatten()
+        .any(|text| {
+            let text = text.to_lowercase();
+            PAYROLL_WORDS.iter().any(|word| text.contains(word))
+        })
+}
+
+/// Whether every word of the filer's name appears in the counterparty's, e.g. `Jane Doe` in `MRS JANE A DOE`
+fn same_person(filer: &str, counterparty: &str) -> bool {
+    let counterparty: Vec<String> = counterparty
+        .split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .collect();
+    let mut words = filer.split_whitespace().peekable();
+    words.peek().is_some() && words.all(|word| counterparty.contains(&word.to_lowercase()))
+}
+
+/// Identifier without spaces or dashes, uppercased, for comparing
+fn compact(identifier: &str) -> String {
+    identifier
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>()
+        .to_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Account, Filer};
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    fn transaction(date: &str, amount: Decimal, name: &str, account: Option<&str>) -> Transaction {
+        Transaction {
+            date: date.parse().unwrap(),
+            amount,
+            counterparty_name: Some(name.to_string()),
+            counterparty_account: account.map(str::to_string),
+            description: None,
+        }
+    }
+
+    #[test]
+    fn test_analyze() {
+        let user_data = UserData {
+            filers: vec![Filer {
+                name: "Jane Doe".to_string(),
+                handle: "jane".to_string(),
+                tin: None,
+                address: None,
+            }],
+            accounts: vec![Account {
+                handle: "savings".to_string(),
+                identifier1: Some("GB29 NWBK 6016 1331 9268 19".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut salary = transaction("2023-01-28", dec!(3000), "ACME GMBH", None);
+        salary.description = Some("Gehalt Januar".to_string());
+        let mut february_salary = salary.clone();
+        february_salary.date = "2023-02-28".parse().unwrap();
+
+        let statements = vec![TransactionStatement {
+            source: "jan.ofx".to_string(),
+            account_id: Some("DE89370400440532013000".to_string()),
+            transactions: vec![
+                // Known: the savings account
+                transaction(
+                    "2023-01-05",
+                    dec!(-100),
+                    "Savings",
+                    Some("GB29NWBK60161331926819"),
+                ),
+                transaction(
+                    "2023-01-10",
+                    dec!(-500),
+                    "MRS JANE A DOE",
+                    Some("FR1420041010050500013M02606"),
+                ),
+                transaction(
+                    "2023-02-10",
+                    dec!(-500),
+                    "Landlord",
+                    Some("NL91ABNA0417164300"),
+                ),
+                transaction(
+                    "2023-03-10",
+                    dec!(-500),
+                    "Landlord",
+                    Some("NL91ABNA0417164300"),
+                ),
+                transaction(
+                    "2023-04-10",
+                    dec!(-500),
+                    "Landlord",
+                    Some("NL91ABNA0417164300"),
+                ),
+                transaction("2023-04-12", dec!(-20), "Cafe", None),
+                salary,
+                february_salary,
+            ],
+        }];
+
+        let suggestions = analyze(&user_data, &statements);
+        let found: Vec<_> = suggestions
+            .iter()
+            .map(|suggestion| (suggestion.confidence, suggestion.counterparty.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Confidence::High, "DE89370400440532013000"),
+                (
+                    Confidence::High,
+                    "MRS JANE A DOE (FR1420041010050500013M02606)"
+                ),
+                (Confidence::Medium, "Landlord (NL91ABNA0417164300)"),
+                (Confidence::Low, "ACME GMBH"),
+            ]
+        );
+        assert_eq!(
+            suggestions[1].reasons,
+            vec!["transfers with an account in Jane Doe's name"]
+        );
+    }
+
+    #[test]
+    fn test_same_person() {
+        assert!(same_person("Jane Doe", "MRS JANE A DOE"));
+        assert!(!same_person("Jane Doe", "JANE SMITH"));
+        assert!(!same_person("", "JANE SMITH"));
+    }
+}
@@ -0,0 +1,325 @@
+use anyhow::{bail, Result};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use crate::data::identifier::iban_remainder;
+use crate::data::{Address, IdentifierKind, StructuredAddress, UserData};
+use crate::statements::{StatementFile, StatementStore};
+
+/// Replaces names, addresses and identifiers with deterministic fakes, keeping balances, dates, currencies and
+/// rates intact so the anonymized data still reproduces the same report figures.
+///
+/// The same input value always maps to the same fake, so references between providers, accounts and statements
+/// remain consistent.
+pub fn anonymize(data: &UserData) -> UserData {
+    let mut anonymized = data.clone();
+
+    for provider in &mut anonymized.providers {
+        let fake_handle = fake_value("provider", &provider.handle);
+        provider.name = fake_value("Provider", &provider.name);
+        provider.address = fake_address(&provider.address);
+        provider.handle = fake_handle;
+    }
+
+    for filer in &mut anonymized.filers {
+        filer.handle = fake_value("filer", &filer.handle);
+        filer.name = fake_value("Filer", &filer.name);
+        filer.tin = filer.tin.as_ref().map(|tin| fake_value("tin", tin));
+        filer.address = filer.address.as_ref().map(fake_address);
+    }
+
+    for entity in &mut anonymized.entities {
+        entity.handle = fake_value("entity", &entity.handle);
+        entity.name = fake_value("Entity", &entity.name);
+        entity.address = fake_address(&entity.address);
+        entity.tin = entity.tin.as_ref().map(|tin| fake_value("tin", tin));
+    }
+
+    for account in &mut anonymized.accounts {
+        account.handle = fake_value("account", &account.handle);
+        account.provider = fake_value("provider", &account.provider);
+        account.owners = account
+            .owners
+            .iter()
+            .map(|owner| fake_value("filer", owner))
+            .collect();
+        account.entity = account
+            .entity
+            .as_ref()
+            .map(|entity| fake_value("entity", entity));
+        if let Some(authority) = &mut account.signature_authority {
+            authority.owner = fake_value("Owner", &authority.owner);
+            authority.address = fake_address(&authority.address);
+            authority.tin = authority.tin.as_ref().map(|tin| fake_value("tin", tin));
+        }
+        let kinds = [
+            account.primary_identifier_kind(),
+            account.secondary_identifier_kind(),
+        ];
+        for (kind, identifier) in kinds
+            .into_iter()
+            .zip([&mut account.identifier1, &mut account.identifier2])
+        {
+            if let Some(identifier) = identifier {
+                *identifier = fake_identifier(kind, identifier);
+            }
+        }
+
+        for import in &mut account.imports {
+            import.provenance.source_file = format!(
+                "{}.csv",
+                fake_value("statement", &import.provenance.source_file)
+            );
+        }
+    }
+
+    // Spreadsheet IDs lead straight to the originals, and pulling isn't needed to reproduce a report
+    anonymized.google_sheets = None;
+
+    anonymized
+}
+
+/// Loads the data directory at `source` and writes an anonymized copy into `out`
+///
+/// Only `data.yml` and the statement store are copied; tool state such as year snapshots is left behind since it
+/// embeds the original names.
+pub fn write_anonymized(source: &Path, out: &Path) -> Result<()> {
+    let _span = tracing::info_span!("export", path = %out.display()).entered();
+    let out_yaml = out.join("data.yml");
+    if out_yaml.exists() {
+        bail!("{:?} already exists, refusing to overwrite it", out_yaml);
+    }
+
+    let anonymized = anonymize(&UserData::load_from_path(source)?);
+
+    std::fs::create_dir_all(out)?;
+    std::fs::write(out_yaml, serde_yaml::to_string(&anonymized)?)?;
+
+    let store = StatementStore::new(out);
+    for account in anonymized.accounts.iter().filter(|a| !a.imports.is_empty()) {
+        store.save(&StatementFile::new(
+            &account.handle,
+            account.imports.clone(),
+        ))?;
+    }
+
+    Ok(())
+}
+
+fn fake_address(address: &Address) -> Address {
+    match address {
+        Address::Freeform(address) => Address::Freeform(fake_street(address)),
+        // The country is kept since it affects validation and reportability
+        Address::Structured(address) => Address::Structured(StructuredAddress {
+            street: fake_street(&address.street),
+            city: fake_value("City", &address.city),
+            state: address.state.clone(),
+            postal_code: address.postal_code.as_ref().map(|_| "00000".to_string()),
+            country: address.country.clone(),
+        }),
+    }
+}
+
+fn fake_street(value: &str) -> String {
+    let digest = Sha256::digest(value.as_bytes());
+    format!(
+        "{} Example Street",
+        u16::from_be_bytes([digest[0], digest[1]]) % 999 + 1
+    )
+}
+
+/// A fake identifier that's still valid for its kind, so the anonymized data loads cleanly in strict mode
+fn fake_identifier(kind: Option<IdentifierKind>, value: &str) -> String {
+    let digits: String = Sha256::digest(value.as_bytes())
+        .iter()
+        .map(|byte| char::from(b'0' + byte % 10))
+        .collect();
+    // The country part of IBANs and BICs is kept, like provider countries
+    let country = |start: usize| {
+        value
+            .get(start..start + 2)
+            .filter(|code| code.chars().all(|c| c.is_ascii_alphabetic()))
+            .unwrap_or("GB")
+            .to_uppercase()
+    };
+
+    match kind {
+        Some(IdentifierKind::AccountNumber) => digits[..8].to_string(),
+        Some(IdentifierKind::SortCode) => {
+            format!("{}-{}-{}", &digits[..2], &digits[2..4], &digits[4..6])
+        }
+        Some(IdentifierKind::Iban) => {
+            let country = country(0);
+            let bban = format!("EXMP{}", &digits[..14]);
+            let check = 98 - iban_remainder(&format!("{}00{}", country, bban));
+            format!("{}{:02}{}", country, check, bban)
+        }
+        Some(IdentifierKind::Bic) => format!("EXMP{}XX", country(4)),
+        _ => fake_value("id", value),
+    }
+}
+
+fn fake_value(prefix: &str, value: &str) -> String {
+    let digest = hex::encode(Sha256::digest(value.as_bytes()));
+    format!("{}_{}", prefix, &digest[..8])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::facts::Facts;
+    use crate::parsing::ParseContext;
+    use crate::report::Report;
+    use crate::report_context::ReportContext;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn create_test_data_dir(dir: &Path) -> Result<()> {
+        let yaml_content = r#"
+providers:
+  - name: "Jane Doe's Bank"
+    handle: "janes_bank"
+    address: "1 Private Lane, London"
+  - name: "Jane Doe's Other Bank"
+    handle: "janes_other_bank"
+    address:
+      street: "2 Private Lane"
+      city: "Janesville"
+      postal_code: "JD1 1AA"
+      country: "GB"
+
+entities:
+  - name: "Jane Doe Holdings Ltd"
+    handle: "jane_holdings"
+    address: "3 Private Lane, London"
+    tin: "98-7654321"
+    ownership_percent: 100
+
+accounts:
+  - handle: "jane_savings"
+    provider: "janes_bank"
+    currency_code: "GBP"
+    identifier1_name: "Account number"
+    identifier1: "31926819"
+    balances:
+      - date: 2023-03-31
+        amount: 1000.0
+  - handle: "jane_company_account"
+    provider: "janes_other_bank"
+    currency_code: "GBP"
+    identifier1_name: "Account number"
+    identifier1: "41926819"
+    entity: "jane_holdings"
+    balances:
+      - date: 2023-03-31
+        amount: 5000.0
+"#;
+        fs::write(dir.join("data.yml"), yaml_content)?;
+
+        let statement_path = dir.join("jane_doe_june.csv");
+        fs::write(&statement_path, "date,amount\n2023-06-30,2500.0\n")?;
+        StatementStore::new(dir).import(
+            "jane_savings",
+            &statement_path,
+            None,
+            None,
+            &ParseContext::default(),
+        )?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_anonymize_is_deterministic() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        create_test_data_dir(temp_dir.path())?;
+        let data = UserData::load_from_path(temp_dir.path())?;
+
+        let first = anonymize(&data);
+        let second = anonymize(&data);
+
+        assert_eq!(first.providers[0].handle, second.providers[0].handle);
+        assert_eq!(first.accounts[0].handle, second.accounts[0].handle);
+        // Account still points at its (renamed) provider
+        assert_eq!(first.accounts[0].provider, first.providers[0].handle);
+        assert_eq!(
+            first.accounts[1].entity.as_ref(),
+            Some(&first.entities[0].handle)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fake_identifiers_stay_valid() -> Result<()> {
+        let cases = [
+            (IdentifierKind::AccountNumber, "31926819"),
+            (IdentifierKind::SortCode, "12-34-56"),
+            (IdentifierKind::Iban, "CH9300762011623852957"),
+            (IdentifierKind::Bic, "UBSWCHZH80A"),
+        ];
+        for (kind, value) in cases {
+            let fake = fake_identifier(Some(kind), value);
+            assert_ne!(fake, value);
+            assert_eq!(kind.normalize(&fake)?, fake);
+        }
+        assert!(
+            fake_identifier(Some(IdentifierKind::Iban), "CH9300762011623852957").starts_with("CH")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_anonymized() -> Result<()> {
+        let source_dir = TempDir::new()?;
+        let out_dir = TempDir::new()?;
+        create_test_data_dir(source_dir.path())?;
+
+        write_anonymized(source_dir.path(), out_dir.path())?;
+
+        let written = fs::read_to_string(out_dir.path().join("data.yml"))?;
+        assert!(!written.contains("Jane"));
+        assert!(!written.contains("janes_bank"));
+        assert!(!written.contains("Private Lane"));
+        assert!(!written.contains("Janesville"));
+        assert!(!written.contains("JD1"));
+        assert!(written.contains("country: GB"));
+        assert!(!written.contains("31926819"));
+        assert!(!written.contains("98-7654321"));
+
+        let statements = fs::read_dir(out_dir.path().join("statements"))?
+            .map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert!(!statements.contains("jane"));
+
+        // Balances and dates survive, so the report figures are unchanged
+        let context = ReportContext::new(Facts::load_facts().unwrap(), None);
+        let original = Report::generate(
+            2023,
+            &UserData::load_from_path(source_dir.path())?,
+            &context,
+        )?;
+        let anonymized =
+            Report::generate(2023, &UserData::load_from_path(out_dir.path())?, &context)?;
+        assert_eq!(
+            original.accounts[0].max_balance_usd,
+            anonymized.accounts[0].max_balance_usd
+        );
+        assert_eq!(anonymized.provenance[0].records, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_refuses_to_overwrite() -> Result<()> {
+        let source_dir = TempDir::new()?;
+        create_test_data_dir(source_dir.path())?;
+
+        let result = write_anonymized(source_dir.path(), source_dir.path());
+        assert!(result.is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::NaiveDate;
+use std::cell::OnceCell;
+use std::path::{Path, PathBuf};
+
+use crate::crypto::CryptoValuation;
+use crate::data::UserData;
+use crate::facts::{update, Facts};
+use crate::fincen::SchemaVersion;
+use crate::parsing::{DateOrder, ParseContext, ParseMode};
+use crate::progress::Progress;
+use crate::report::ReportCache;
+use crate::report_context::{RateFallback, ReportContext};
+
+/// Settings that apply to every command, usually from the command line's global options
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    /// The data directory holding `data.yml`, statements, rates and tool state
+    pub data_dir: PathBuf,
+    /// Treat problems in the user's data as errors instead of warnings
+    pub strict: bool,
+    /// How to read day/month-ambiguous statement dates
+    pub date_order: Option<DateOrder>,
+    /// Rate source order, overriding `rate_precedence` in `data.yml`
+    pub rate_precedence: Option<Vec<String>>,
+    /// What to do when a currency has no rate for the year; fails unless a fallback is opted into
+    pub rate_fallback: RateFallback,
+    /// Date the filer became a US person, overriding `us_person_since` in `data.yml`
+    pub us_person_since: Option<NaiveDate>,
+    /// FinCEN schema version to write and check the XML against, overriding `fincen_schema_version` in `data.yml`
+    pub fincen_schema_version: Option<SchemaVersion>,
+    /// Reuse per-account maxima cached under `.fbar_prep/cache`
+    pub cache: bool,
+    /// Keep archived accounts in the data, rather than setting them aside
+    pub include_archived: bool,
+    pub progress: Progress,
+}
+
+impl AppConfig {
+    pub fn new(data_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            data_dir: data_dir.into(),
+            strict: false,
+            date_order: None,
+            rate_precedence: None,
+            rate_fallback: RateFallback::None,
+            us_person_since: None,
+            fincen_schema_version: None,
+            cache: true,
+            include_archived: false,
+            progress: Progress::hidden(),
+        }
+    }
+}
+
+/// What commands share: the configuration, and the facts, user data and report context, each loaded the first time
+/// a command asks for it and reused after that.
+///
+/// Commands that don't need the user's data, like importing statements, never load it, so they work on a data
+/// directory whose `data.yml` is incomplete or missing.
+pub struct App {
+    config: AppConfig,
+    parse_context: ParseContext,
+    facts: OnceCell<Facts>,
+    data: OnceCell<(UserData, ReportContext)>,
+}
+
+impl App {
+    pub fn new(config: AppConfig) -> Self {
+        let mode = if config.strict {
+            ParseMode::Strict
+        } else {
+            ParseMode::Lenient
+        };
+        let parse_context = ParseContext::new(mode).with_date_order(config.date_order);
+        Self {
+            config,
+            parse_context,
+            facts: OnceCell::new(),
+            data: OnceCell::new(),
+        }
+    }
+
+    pub fn config(&self) -> &AppConfig {
+        &self.config
+    }
+
+    pub fn data_dir(&self) -> &Path {
+        &self.config.data_dir
+    }
+
+    pub fn progress(&self) -> &Progress {
+        &self.config.progress
+    }
+
+    /// Collects the warnings from everything parsed so far
+    pub fn parse_context(&self) -> &ParseContext {
+        &self.parse_context
+    }
+
+    /// The bundled facts, or the facts update in the data directory once it's been verified
+    pub fn facts(&self) -> Result<&Facts> {
+        if self.facts.get().is_none() {
+            let facts = match update::load_update(self.data_dir(), self.config.strict)
+                .context("loading facts update")?
+            {
+                Some(facts) => facts,
+                None => Facts::load_facts()
+                    .map_err(|err| anyhow!("{}", err))
+                    .context("loading facts data")?,
+            };
+            tracing::info!("Loaded {} years of facts data", facts.years.len());
+            let _ = self.facts.set(facts);
+        }
+        Ok(self.facts.get().expect("facts were just loaded"))
+    }
+
+    /// The user's data and a report context built from it and the facts. Warnings about the data and its rates are
+    /// logged once, when it's first loaded.
+    pub fn data(&self) -> Result<(&UserData, &ReportContext)> {
+        if self.data.get().is_none() {
+            let loaded = self.load(self.facts()?.clone());
+            for diagnostic in self.parse_context.diagnostics() {
+                tracing::warn!("[{}] {}", diagnostic.code, diagnostic.message);
+            }
+            let _ = self.data.set(loaded?);
+        }
+        let (user_data, context) = self.data.get().expect("data was just loaded");
+        Ok((user_data, context))
+    }
+
+    /// The user's data, as loaded by `data`
+    pub fn user_data(&self) -> Result<&UserData> {
+        Ok(self.data()?.0)
+    }
+
+    fn load(&self, facts: Facts) -> Result<(UserData, ReportContext)> {
+        let data_dir = &self.config.data_dir;
+        let mut user_data = UserData::load_with_context(data_dir, &self.parse_context)
+            .context("loading FBAR data")?;
+        if let Some(since) = self.config.us_person_since {
+            user_data.us_person_since = Some(since);
+        }
+        if !self.config.include_archived {
+            user_data.set_aside_archived();
+        }
+        if let Some(version) = self.config.fincen_schema_version {
+            user_data.fincen_schema_version = Some(version);
+        }
+        tracing::debug!("Loaded FBAR data: {:?}", user_data);
+
+        let facts_hash = match facts.content_hash() {
+            Ok(hash) => Some(hash),
+            Err(err) => {
+                tracing::warn!("Not caching report results: {}", err);
+                None
+            }
+        };
+        let mut context = ReportContext::new(facts, user_data.fact_extensions.clone())
+            .with_rate_sets(user_data.rate_sets.clone())
+            .with_rate_fallback(self.config.rate_fallback)
+            .with_progress(self.config.progress.clone())
+            .with_valuation(Box::new(CryptoValuation::new(
+                user_data.price_series.clone(),
+            )));
+        if let Some(facts_hash) = facts_hash.filter(|_| self.config.cache) {
+            context = context.with_cache(ReportCache::open(data_dir, &facts_hash));
+        }
+        if let Some(precedence) = self
+            .config
+            .rate_precedence
+            .clone()
+            .or_else(|| user_data.rate_precedence.clone())
+        {
+            context = context
+                .with_precedence(precedence)
+                .context("configuring rate precedence")?;
+        }
+        let context = context
+            .with_rate_pins(&user_data.rate_pins)
+            .context("configuring rate pins")?;
+        for warning in context.check_rates(&user_data.rate_checks) {
+            tracing::warn!("{}", warning);
+        }
+
+        Ok((user_data, context))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const DATA: &str = r#"
+providers:
+  - name: "Example Bank"
+    handle: "example_bank"
+    address: "1 Bank Street, London"
+accounts:
+  - handle: "example_checking"
+    provider: "example_bank"
+    currency_code: "EUR"
+    balances:
+      - date: 2023-06-30
+        amount: 100.0
+"#;
+
+    #[test]
+    fn test_loads_data_once() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        fs::write(temp_dir.path().join("data.yml"), DATA)?;
+
+        let mut config = AppConfig::new(temp_dir.path());
+        config.cache = false;
+        let app = App::new(config);
+        assert_eq!(app.user_data()?.accounts.len(), 1);
+
+        // Changes after the first load aren't seen by later commands in the same run
+        fs::write(temp_dir.path().join("data.yml"), "providers: []\n")?;
+        assert_eq!(app.user_data()?.accounts.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_unknown_rate_source() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        fs::write(temp_dir.path().join("data.yml"), DATA)?;
+
+        let mut config = AppConfig::new(temp_dir.path());
+        config.rate_precedence = Some(vec!["nowhere".to_string()]);
+        let app = App::new(config);
+        let err = app.data().err().expect("unknown rate source");
+        assert!(format!("{:#}", err).contains("configuring rate precedence"));
+
+        Ok(())
+    }
+}
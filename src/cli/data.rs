@@ -0,0 +1,506 @@
+//! Commands about the data directory as a whole: its status, accounts and health
+
+use anyhow::{bail, Context, Result};
+use chrono::Datelike;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tracing::warn;
+
+use fbar_prep::app::App;
+use fbar_prep::data::migrate as migrations;
+use fbar_prep::diagnostics::{self, codes};
+use fbar_prep::parsing::ParseContext;
+use fbar_prep::report_context::RateSource;
+use fbar_prep::{
+    analyze as analysis, anonymize as anonymizer, doctor as diagnosis, examples, fincen,
+    fixture as fixtures, graph, report, report::overview, schema as schemas,
+};
+
+pub fn status(app: &App) -> Result<ExitCode> {
+    let snapshots = report::SnapshotStore::new(app.data_dir());
+    let (user_data, context) = app.data()?;
+    println!(
+        "{} account(s) with {} provider(s)",
+        user_data.accounts.len(),
+        user_data.providers.len()
+    );
+
+    let years = match snapshots.closed_years() {
+        Ok(years) => years,
+        Err(err) => {
+            warn!("Could not read closed years: {}", err);
+            return Ok(ExitCode::SUCCESS);
+        }
+    };
+    if !years.is_empty() {
+        let listed: Vec<String> = years.iter().map(i32::to_string).collect();
+        println!("Closed years: {}", listed.join(", "));
+    }
+
+    for year in years {
+        let unchanged = snapshots.load(year).and_then(|snapshot| {
+            let report = report::Report::generate(year, user_data, context)?;
+            match snapshot {
+                Some(snapshot) => snapshot.matches(&report),
+                None => Ok(true),
+            }
+        });
+
+        match unchanged {
+            Ok(true) => {}
+            Ok(false) => warn!(
+                "Data for closed year {} has changed since it was closed",
+                year
+            ),
+            Err(err) => warn!("Could not verify closed year {}: {}", year, err),
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn deadlines(app: &App) -> Result<ExitCode> {
+    let (user_data, context) = app.data()?;
+    let snapshots = report::SnapshotStore::new(app.data_dir());
+    let rows = report::deadlines::deadlines(user_data, context, &snapshots)?;
+    if rows.is_empty() {
+        println!("No balance records or filings yet");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let today = chrono::Local::now().date_naive();
+    println!("{:<6}{:<12}{:<12}Status", "Year", "Due", "Extended");
+    for row in &rows {
+        let status = match &row.status {
+            report::FilingStatus::Filed(filed_on) => format!("filed {}", filed_on),
+            report::FilingStatus::Closed(closed_at) => {
+                format!("closed {}", closed_at.date_naive())
+            }
+            report::FilingStatus::Open if !row.appears_unfiled() => "not required".to_string(),
+            report::FilingStatus::Open => {
+                let requirement = if row.required.is_none() {
+                    " (report can't be generated to check it's required)"
+                } else {
+                    ""
+                };
+                let when = if row.is_overdue(today) {
+                    format!("overdue by {} days", (today - row.extended).num_days())
+                } else if today > row.due {
+                    format!("due {} on the extension", row.extended)
+                } else {
+                    format!("due in {} days", (row.due - today).num_days())
+                };
+                format!("UNFILED, {}{}", when, requirement)
+            }
+        };
+        println!(
+            "{:<6}{:<12}{:<12}{}",
+            row.year,
+            row.due.to_string(),
+            row.extended.to_string(),
+            status
+        );
+    }
+
+    Ok(if rows.iter().any(|row| row.is_overdue(today)) {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+pub fn analyze(app: &App, statement_paths: &[PathBuf]) -> Result<ExitCode> {
+    let user_data = app.user_data()?;
+    // Kept apart from the data's context, whose warnings were logged as it loaded
+    let parse_context =
+        ParseContext::new(app.parse_context().mode()).with_date_order(app.config().date_order);
+    let statements = statement_paths
+        .iter()
+        .map(|path| {
+            analysis::read_statement(path, &parse_context)
+                .with_context(|| format!("reading {}", path.display()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for diagnostic in parse_context.diagnostics() {
+        warn!("[{}] {}", diagnostic.code, diagnostic.message);
+    }
+
+    let suggestions = analysis::analyze(user_data, &statements);
+    if suggestions.is_empty() {
+        println!("No signs of accounts missing from data.yml");
+        return Ok(ExitCode::SUCCESS);
+    }
+    for suggestion in &suggestions {
+        let period = match (suggestion.first, suggestion.last) {
+            (Some(first), Some(last)) if first != last => format!(", {} to {}", first, last),
+            (Some(first), _) => format!(", {}", first),
+            _ => String::new(),
+        };
+        println!(
+            "{:<8}{}: {} ({} transaction(s){})",
+            suggestion.confidence,
+            suggestion.counterparty,
+            suggestion.reasons.join("; "),
+            suggestion.transactions,
+            period
+        );
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn summary(app: &App) -> Result<ExitCode> {
+    let (user_data, context) = app.data()?;
+    let rows = report::overview::overview(user_data, context);
+    if rows.is_empty() {
+        println!("No balance records yet");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    println!(
+        "{:<6}{:>10}{:>22}{:>8}{:>11}",
+        "Year", "Accounts", "Aggregate max (USD)", "File?", "Complete"
+    );
+    let mut failed = Vec::new();
+    for row in &rows {
+        let completeness = row
+            .completeness
+            .map_or_else(|| "-".to_string(), |percent| format!("{}%", percent));
+        match &row.figures {
+            Ok(figures) => println!(
+                "{:<6}{:>10}{:>22}{:>8}{:>11}",
+                row.year,
+                figures.accounts,
+                // Filed in whole dollars, rounded up
+                figures.aggregate_max_usd.amount.ceil(),
+                if figures.filing_required() {
+                    "yes"
+                } else {
+                    "no"
+                },
+                completeness
+            ),
+            Err(err) => {
+                println!(
+                    "{:<6}{:>10}{:>22}{:>8}{:>11}",
+                    row.year, "-", "-", "?", completeness
+                );
+                failed.push((row.year, err));
+            }
+        }
+    }
+
+    for (year, err) in &failed {
+        println!("{}: {:#}", year, err);
+    }
+    Ok(if failed.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+pub fn coverage(app: &App) -> Result<ExitCode> {
+    let (user_data, context) = app.data()?;
+    let years = report::coverage::coverage(user_data, context);
+    if years.is_empty() {
+        println!("No balance records yet");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    for year in &years {
+        println!("{}", year.year);
+        if year.currencies.is_empty() {
+            println!("  Only USD, no rates needed");
+        }
+        for currency in &year.currencies {
+            let source = currency
+                .source
+                .as_ref()
+                .map_or_else(|| "MISSING".to_string(), |source| source.to_string());
+            println!(
+                "  {:<6}{:<30}{}",
+                currency.currency_code,
+                source,
+                currency.accounts.join(", ")
+            );
+        }
+    }
+
+    let missing: Vec<_> = years
+        .iter()
+        .flat_map(|year| year.missing().map(move |currency| (year.year, currency)))
+        .collect();
+    if missing.is_empty() {
+        return Ok(ExitCode::SUCCESS);
+    }
+    println!();
+    println!("Add these under fact_extensions in data.yml before reporting:");
+    for (year, currency) in missing {
+        println!("  {} {}", year, currency.currency_code);
+    }
+    Ok(ExitCode::FAILURE)
+}
+
+pub fn rates(app: &App, year: i32) -> Result<ExitCode> {
+    let (user_data, context) = app.data()?;
+    let rates = report::coverage::rates(user_data, context, year);
+    if rates.is_empty() {
+        println!("Only USD in {}, no rates needed", year);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let describe = |rate: &Option<(f64, RateSource)>| match rate {
+        Some((rate, source)) => format!("{} ({})", rate, source),
+        None => "MISSING".to_string(),
+    };
+    println!("{:<6}{:<36}Yearly average (1040)", "", "Year-end (FBAR)");
+    for currency in &rates {
+        println!(
+            "{:<6}{:<36}{}",
+            currency.currency_code,
+            describe(&currency.year_end),
+            describe(&currency.yearly_average)
+        );
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn household_summary(app: &App) -> Result<ExitCode> {
+    let (user_data, context) = app.data()?;
+    if user_data.filers.is_empty() {
+        bail!("No filers in data.yml; list them under `filers` to roll the summary up across them");
+    }
+    let rows = overview::household(user_data, context)?;
+    if rows.is_empty() {
+        println!("No balance records yet");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let print_row = |year: i32, name: &str, figures: &Result<overview::YearFigures>, file: &str| {
+        match figures {
+            Ok(figures) => println!(
+                "{:<6}{:<12}{:>10}{:>22}{:>8}",
+                year,
+                name,
+                figures.accounts,
+                figures.aggregate_max_usd.amount.ceil(),
+                file
+            ),
+            Err(_) => println!("{:<6}{:<12}{:>10}{:>22}{:>8}", year, name, "-", "-", "?"),
+        }
+    };
+    println!(
+        "{:<6}{:<12}{:>10}{:>22}{:>8}",
+        "Year", "Filer", "Accounts", "Aggregate max (USD)", "File?"
+    );
+    let mut failed = Vec::new();
+    for row in &rows {
+        for (handle, figures) in &row.filers {
+            let file = match figures {
+                Ok(figures) if figures.filing_required() => "yes",
+                Ok(_) => "no",
+                Err(err) => {
+                    failed.push((row.year, handle, err));
+                    "?"
+                }
+            };
+            print_row(row.year, handle, figures, file);
+        }
+        // Only filers file, so the household's total has no answer of its own
+        print_row(row.year, "household", &row.household, "-");
+    }
+
+    for (year, handle, err) in &failed {
+        println!("{} {}: {:#}", year, handle, err);
+    }
+    Ok(if failed.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+pub fn list_accounts(app: &App, tags: &[String]) -> Result<ExitCode> {
+    let user_data = app.user_data()?;
+    for account in user_data
+        .accounts
+        .iter()
+        .filter(|account| tags.is_empty() || account.tags.iter().any(|tag| tags.contains(tag)))
+    {
+        let mut line = format!(
+            "{} ({}, {}, {})",
+            account.handle,
+            account.provider,
+            account.kind,
+            account.currency_code.to_uppercase()
+        );
+        if !account.tags.is_empty() {
+            line.push_str(&format!(" [{}]", account.tags.join(", ")));
+        }
+        if let Some(closing_date) = account.closing_date {
+            line.push_str(&format!(" closed {}", closing_date));
+        }
+        if account.exclude_from_fbar {
+            line.push_str(" excluded");
+        }
+        if account.archived {
+            line.push_str(" archived");
+        }
+        println!("{}", line);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The doctor loads the data itself, so it can report a directory that doesn't load
+pub fn doctor(app: &App, year: Option<i32>) -> Result<ExitCode> {
+    let year = year.unwrap_or_else(|| chrono::Local::now().year() - 1);
+    let findings = diagnosis::diagnose(
+        app.data_dir(),
+        app.facts()?,
+        year,
+        app.config().rate_precedence.clone(),
+        app.parse_context(),
+    );
+
+    for finding in &findings {
+        println!("{}", finding);
+    }
+    if findings
+        .iter()
+        .any(|finding| finding.severity == diagnosis::Severity::Problem)
+    {
+        return Ok(ExitCode::FAILURE);
+    } else if findings.is_empty() {
+        println!("No problems found for {}", year);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn migrate(app: &App, dry_run: bool) -> Result<ExitCode> {
+    let result = migrations::migrate(app.data_dir(), dry_run).context("migrating data.yml")?;
+    if result.steps.is_empty() {
+        println!("data.yml is already schema version {}", result.from);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    for step in &result.steps {
+        println!("Version {}: {}", step.to, step.description);
+        for change in &step.changes {
+            println!("  {}", change);
+        }
+    }
+    match &result.backup {
+        Some(backup) => println!(
+            "Upgraded data.yml from schema version {} to {}; the original is in {:?}",
+            result.from, result.to, backup
+        ),
+        None => println!(
+            "Would upgrade data.yml from schema version {} to {}",
+            result.from, result.to
+        ),
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn graph(app: &App, year: Option<i32>) -> Result<ExitCode> {
+    let year = year.unwrap_or_else(|| chrono::Local::now().year() - 1);
+    let (user_data, context) = app.data()?;
+    print!("{}", graph::data_graph(user_data, context, year));
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn anonymize(app: &App, out: &Path) -> Result<ExitCode> {
+    anonymizer::write_anonymized(app.data_dir(), out).context("anonymizing data")?;
+    println!("Wrote anonymized data to {:?}", out);
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn gen_fixture(out: &Path, spec: &fixtures::FixtureSpec) -> Result<ExitCode> {
+    let user_data = fixtures::write_fixture(out, spec).context("generating fixture")?;
+    let records: usize = user_data
+        .accounts
+        .iter()
+        .map(|account| account.balances.len())
+        .sum();
+    println!(
+        "Wrote {} providers, {} accounts and {} balance records to {:?}",
+        user_data.providers.len(),
+        user_data.accounts.len(),
+        records,
+        out
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn example(name: Option<&str>, out: Option<&Path>) -> Result<ExitCode> {
+    let Some(name) = name else {
+        for example in examples::Example::ALL {
+            println!("{:<14} {}", example.name(), example.description());
+        }
+        return Ok(ExitCode::SUCCESS);
+    };
+    let example: examples::Example = name.parse()?;
+    let out = out.map_or_else(|| PathBuf::from(example.name()), Path::to_path_buf);
+    let user_data = examples::write_example(&out, example).context("writing example")?;
+    println!(
+        "Wrote the {} example, with {} accounts, to {:?}; report it with `fbar_prep --data-dir {} report {}`",
+        example,
+        user_data.accounts.len(),
+        out,
+        out.display(),
+        examples::EXAMPLE_YEAR
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn init_from_filing(filing: &Path, out: &Path) -> Result<ExitCode> {
+    let filed = fincen::filed::write_starter(filing, out).context("starting from the filing")?;
+    let user_data = &filed.user_data;
+    println!(
+        "Wrote the {} FBAR's {} account(s) at {} provider(s) to {:?}",
+        filed.year,
+        user_data.accounts.len(),
+        user_data.providers.len(),
+        out
+    );
+    if let Some(count) = filed.summary_accounts {
+        println!(
+            "The filing only gave the number of accounts, {}, so add them to data.yml by hand",
+            count
+        );
+    }
+    if !user_data.accounts.is_empty() {
+        println!(
+            "Each account's currency isn't known (currency_code XXX) and must be set before reporting; then replace \
+             the maximum value filed, recorded in USD, with the account's own balances"
+        );
+    }
+    if !user_data.entities.is_empty() {
+        println!("Check each entity's ownership_percent, which the filing doesn't give and is set to 100");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn schema(file: schemas::SchemaFile) -> Result<ExitCode> {
+    let json = serde_json::to_string_pretty(&schemas::schema(file)).context("writing schema")?;
+    println!("{}", json);
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn explain(code: Option<&str>) -> Result<ExitCode> {
+    let Some(code) = code else {
+        for explanation in codes::EXPLANATIONS {
+            println!("{}  {}", explanation.code, explanation.title);
+        }
+        return Ok(ExitCode::SUCCESS);
+    };
+    match diagnostics::explain(code) {
+        Some(explanation) => {
+            println!("{}", explanation);
+            Ok(ExitCode::SUCCESS)
+        }
+        None => bail!(
+            "Unknown code {:?}; run `fbar_prep explain` to list them",
+            code
+        ),
+    }
+}
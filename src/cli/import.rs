@@ -0,0 +1,359 @@
+//! Commands that bring statements, prices and rates into the data directory. None of them load `data.yml` in
+//! full, so they work before it's complete.
+
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tracing::warn;
+
+use fbar_prep::app::App;
+use fbar_prep::data::{AccountKind, BalanceRecord, UserData};
+use fbar_prep::parsing::ParseContext;
+use fbar_prep::statements::StatementImporter;
+use fbar_prep::{crypto, facts, statements as store};
+
+use super::print_parse_warnings;
+
+pub fn statements(
+    app: &App,
+    account: &str,
+    statement_paths: &[PathBuf],
+    format: Option<&str>,
+    profile: Option<&str>,
+    sheet: Option<String>,
+    dry_run: bool,
+) -> Result<ExitCode> {
+    let profile = store::profile::profile_for(app.data_dir(), account, profile)
+        .context("loading import profile")?;
+    let mut store = store::StatementStore::new(app.data_dir());
+    if let Some(sheet) =
+        sheet.or_else(|| profile.as_ref().and_then(|profile| profile.sheet.clone()))
+    {
+        store = store.with_importers(
+            store::ImporterRegistry::default()
+                .with(Box::new(store::xlsx::XlsxImporter::sheet(sheet))),
+        );
+    }
+    let bar = app.progress().bar(statement_paths.len(), account);
+    let mut kinds = Vec::new();
+
+    for statement in statement_paths {
+        bar.set_message(statement.display().to_string());
+        let result = if dry_run {
+            store.preview(
+                account,
+                statement,
+                format,
+                profile.as_ref(),
+                app.parse_context(),
+            )
+        } else {
+            store.import(
+                account,
+                statement,
+                format,
+                profile.as_ref(),
+                app.parse_context(),
+            )
+        };
+        bar.inc(1);
+        if let Some(kind) = result.as_ref().ok().and_then(|result| result.account_kind) {
+            kinds.push((statement, kind));
+        }
+
+        match result {
+            Ok(result) if dry_run => bar.suspend(|| print_preview(account, &result)),
+            Ok(result) => bar.suspend(|| {
+                println!(
+                    "Imported {} new balance records for {} from {}, skipped {} duplicates (sha256 {})",
+                    result.import.balances.len(),
+                    account,
+                    result.import.provenance.source_file,
+                    result.duplicates.len(),
+                    result.import.provenance.sha256
+                )
+            }),
+            Err(err) => {
+                bar.abandon();
+                print_parse_warnings(app.parse_context());
+                return Err(err.context(format!("importing {:?}", statement)));
+            }
+        }
+    }
+    bar.finish_and_clear();
+    print_parse_warnings(app.parse_context());
+    warn_on_kind_conflicts(app, account, &kinds);
+    if dry_run {
+        println!("Dry run, nothing was written. Rows skipped for unreadable dates are listed in the warnings above");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Imports each OFX or MT940 statement into the account `data.yml` gives its account number to
+pub fn matched(app: &App, statement_paths: &[PathBuf], dry_run: bool) -> Result<ExitCode> {
+    let user_data = app.user_data()?;
+    for statement in statement_paths {
+        let bytes =
+            std::fs::read(statement).with_context(|| format!("reading {}", statement.display()))?;
+        let file_name = statement
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let contents = String::from_utf8_lossy(&bytes);
+        let (format, account_id, bank_id) = if store::ofx::OfxImporter.detect(&file_name, &bytes) {
+            (
+                "ofx",
+                store::ofx::account_id(&contents),
+                store::ofx::bank_id(&contents).map(str::to_string),
+            )
+        } else if store::mt940::Mt940Importer.detect(&file_name, &bytes) {
+            (
+                "mt940",
+                store::mt940::account_id(&contents),
+                store::mt940::bank_id(&contents),
+            )
+        } else {
+            bail!(
+                "{} isn't an OFX or MT940 statement; only those say which account they're for",
+                statement.display()
+            );
+        };
+        let account_id = account_id
+            .with_context(|| format!("{} doesn't give an account number", statement.display()))?;
+        let account = store::matching::match_account(user_data, account_id, bank_id.as_deref())
+            .with_context(|| format!("matching {} to an account", statement.display()))?;
+        println!("{} is for {}", statement.display(), account.handle);
+        statements(
+            app,
+            &account.handle,
+            std::slice::from_ref(statement),
+            Some(format),
+            None,
+            None,
+            dry_run,
+        )?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Warns when the statements, or the account's identifiers and records, suggest a different kind than `data.yml`
+/// declares. The data is read separately and quietly, as it may not be complete yet.
+fn warn_on_kind_conflicts(app: &App, handle: &str, kinds: &[(&PathBuf, AccountKind)]) {
+    let Some(account) = UserData::load_with_context(app.data_dir(), &ParseContext::default())
+        .ok()
+        .and_then(|user_data| {
+            user_data
+                .accounts
+                .into_iter()
+                .find(|account| account.handle == handle)
+        })
+    else {
+        return;
+    };
+
+    for (statement, kind) in kinds {
+        if !account.kind.fits(*kind) {
+            warn!(
+                "{} is a statement for a {} account, but {} is declared as {}; check its `kind` in data.yml",
+                statement.display(),
+                kind,
+                handle,
+                account.kind
+            );
+        }
+    }
+    if let Some(conflict) = account.kind_conflict() {
+        warn!(
+            "{} is declared as {}, but {}, which suggests {}; check its `kind` in data.yml",
+            handle, account.kind, conflict.reason, conflict.kind
+        );
+    }
+}
+
+fn print_preview(account: &str, result: &store::ImportResult) {
+    let provenance = &result.import.provenance;
+    let mut heading = format!(
+        "{} -> {} (read as {}",
+        provenance.source_file, account, result.format
+    );
+    if let Some(profile) = &provenance.profile {
+        heading.push_str(&format!(", profile {}", profile));
+    }
+    println!("{}, sha256 {})", heading, provenance.sha256);
+
+    println!(
+        "  Would add {} balance records{}",
+        result.import.balances.len(),
+        if result.import.balances.is_empty() {
+            ""
+        } else {
+            ":"
+        }
+    );
+    for record in &result.import.balances {
+        println!("    {}", describe_record(record));
+    }
+    if !result.duplicates.is_empty() {
+        println!("  Would skip {} records:", result.duplicates.len());
+        for record in &result.duplicates {
+            println!(
+                "    {}  already imported for the account, or repeated in the statement",
+                describe_record(record)
+            );
+        }
+    }
+}
+
+fn describe_record(record: &BalanceRecord) -> String {
+    match &record.currency {
+        Some(currency) => format!(
+            "{}  {} {}",
+            record.date,
+            record.amount,
+            currency.to_uppercase()
+        ),
+        None => format!("{}  {}", record.date, record.amount),
+    }
+}
+
+/// Pulls the balances of the configured Google Sheets into their accounts' statements
+#[cfg(feature = "google-sheets")]
+pub fn pull_sheets(app: &App, accounts: &[String]) -> Result<ExitCode> {
+    let settings = store::sheets::settings(app.data_dir()).context("loading google_sheets")?;
+    let sources = settings.sources_for(accounts);
+    if sources.is_empty() {
+        anyhow::bail!("No Google Sheets are configured for these accounts; add them under google_sheets in data.yml");
+    }
+    let Some(credentials) = settings.credentials_path(app.data_dir()) else {
+        anyhow::bail!(
+            "No service account key; set google_sheets.credentials in data.yml or {}",
+            store::sheets::CREDENTIALS_ENV
+        );
+    };
+
+    let client = store::sheets::SheetsClient::connect(&credentials)?;
+    let store = store::StatementStore::new(app.data_dir());
+    for source in sources {
+        let profile = store::profile::profile_for(app.data_dir(), &source.account, None)
+            .context("loading import profile")?;
+        let result = store::sheets::pull(
+            &store,
+            &client,
+            source,
+            profile.as_ref(),
+            app.parse_context(),
+        )
+        .with_context(|| format!("pulling {} for {}", source.range, source.account))?;
+        println!(
+            "Pulled {} new balance records for {} from {}, skipped {} duplicates",
+            result.import.balances.len(),
+            source.account,
+            source.range,
+            result.duplicates.len()
+        );
+    }
+    print_parse_warnings(app.parse_context());
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn normalize(app: &App) -> Result<ExitCode> {
+    let result = store::StatementStore::new(app.data_dir()).normalize(app.parse_context());
+    print_parse_warnings(app.parse_context());
+
+    let results = result.context("normalizing statements")?;
+    if results.is_empty() {
+        println!("No statements to normalize");
+    }
+    for result in results {
+        let years: Vec<String> = result.years.iter().map(i32::to_string).collect();
+        println!(
+            "{}: {} balance records in {} ({}), removed {} duplicates and {} empty imports, merged {} repeat imports",
+            result.account,
+            result.records,
+            if years.len() == 1 { "1 file".to_string() } else { format!("{} files", years.len()) },
+            years.join(", "),
+            result.duplicates_removed,
+            result.imports_removed,
+            result.imports_merged
+        );
+        for path in result.files_removed {
+            println!("  Removed {:?}", path);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn compress(app: &App, compressed: bool) -> Result<ExitCode> {
+    let result =
+        store::StatementStore::new(app.data_dir()).set_compressed(compressed, app.parse_context());
+    print_parse_warnings(app.parse_context());
+
+    let changed = result.context(match compressed {
+        true => "compressing statements",
+        false => "expanding statements",
+    })?;
+    for path in &changed {
+        println!("Rewrote {:?}", path);
+    }
+    println!(
+        "{} statement files {}",
+        changed.len(),
+        match compressed {
+            true => "compressed",
+            false => "expanded",
+        }
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn prices(app: &App, asset: &str, prices: &Path, currency: &str) -> Result<ExitCode> {
+    let result = crypto::PriceStore::new(app.data_dir()).import(
+        asset,
+        currency,
+        prices,
+        app.parse_context(),
+    );
+    print_parse_warnings(app.parse_context());
+
+    let series = result.context("importing prices")?;
+    println!(
+        "Stored {} {} prices in {}",
+        series.prices.len(),
+        asset.to_uppercase(),
+        series.currency.to_uppercase()
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Treasury rates are checked against the bundled facts, but don't need the user's data
+pub fn treasury(app: &App, csv: &Path) -> Result<ExitCode> {
+    let result =
+        facts::treasury::import_treasury(app.data_dir(), app.facts()?, csv, app.parse_context());
+    print_parse_warnings(app.parse_context());
+
+    let result = result.context("importing Treasury rates")?;
+    for change in &result.changes {
+        match change.previous {
+            Some(previous) => println!(
+                "{} {}: {} -> {}",
+                change.year,
+                change.currency_code.to_uppercase(),
+                previous,
+                change.rate
+            ),
+            None => println!(
+                "{} {}: {} (new)",
+                change.year,
+                change.currency_code.to_uppercase(),
+                change.rate
+            ),
+        }
+    }
+    println!(
+        "{} rates added or changed, {} unchanged, {} non-year-end rows skipped",
+        result.changes.len(),
+        result.unchanged,
+        result.skipped
+    );
+    Ok(ExitCode::SUCCESS)
+}
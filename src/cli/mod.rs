@@ -0,0 +1,493 @@
+mod data;
+mod import;
+mod year;
+
+use anyhow::Result;
+use clap::{ArgAction, Parser, Subcommand};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use tracing::warn;
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+
+use fbar_prep::app::{App, AppConfig};
+use fbar_prep::{fincen, fixture, output, parsing, progress, report_context, schema};
+
+#[derive(Parser)]
+#[command(about = "Prepare FBAR (FinCEN Form 114) filings from your foreign account records")]
+pub struct Args {
+    /// Data directory holding data.yml, statements and rates
+    #[arg(short, long, global = true, default_value = ".")]
+    data_dir: PathBuf,
+
+    /// Treat unknown keys, unparseable dates and omitted optional fields as errors instead of warnings
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Order in which rate sources are consulted, e.g. `user,ecb,irs`. Overrides `rate_precedence` in data.yml
+    #[arg(long, global = true, value_delimiter = ',')]
+    rate_precedence: Option<Vec<String>>,
+
+    /// When a currency has no rate for the year: `none` fails, `nearest-year` uses the nearest year's rate and notes it
+    /// on the account. Applies to this run only
+    #[arg(long, global = true, default_value = "none")]
+    rate_fallback: report_context::RateFallback,
+
+    /// How to read numeric statement dates like 03/04/2023: `day-first` or `month-first`. ISO dates are always accepted
+    #[arg(long, global = true)]
+    date_order: Option<parsing::DateOrder>,
+
+    /// Log progress to stderr: `-v` for each phase, `-vv` for per-account detail, `-vvv` for everything
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Write logs as JSON lines instead of text
+    #[arg(long, global = true)]
+    log_json: bool,
+
+    /// Don't show progress bars
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// First FBAR after becoming a US person: the date you became one, e.g. 2023-04-15. Values from before it are left
+    /// out of that year's maxima. Overrides `us_person_since` in data.yml
+    #[arg(long, global = true, value_name = "DATE")]
+    first_year: Option<chrono::NaiveDate>,
+
+    /// BSA E-Filing FBAR schema version to write and check the XML against, e.g. the prior one while FinCEN still
+    /// accepts it. Overrides `fincen_schema_version` in data.yml
+    #[arg(long, global = true, value_name = "VERSION")]
+    schema_version: Option<fincen::SchemaVersion>,
+
+    /// Recompute every account instead of reusing maxima cached under `.fbar_prep/cache`
+    #[arg(long, global = true)]
+    no_cache: bool,
+
+    /// Keep accounts marked `archived` in listings, summaries, checks and reports
+    #[arg(long, global = true)]
+    include_archived: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Summarize the data directory and flag closed years whose data has changed since they were closed
+    Status,
+    /// Print a table of each year's reportable accounts, aggregate maximum, whether filing is required and how complete
+    /// the records are
+    Summary {
+        /// Roll the figures up across every filer in data.yml, with each filer's own figures and the household's
+        /// total, counting jointly owned accounts once
+        #[arg(long)]
+        household: bool,
+    },
+    /// List each year's FBAR due dates and flag years that look unfiled: neither given a `filed_on` date nor closed
+    Deadlines,
+    /// List and inspect the accounts in data.yml
+    Accounts {
+        #[command(subcommand)]
+        command: AccountsCommand,
+    },
+    /// Import balance statements (CSV, OFX or MT940) for an account, recording each file's checksum
+    Import {
+        account: String,
+        #[arg(required = true)]
+        statements: Vec<PathBuf>,
+        /// Statement format, e.g. `csv`, `ofx`, `mt940`, `gocardless` or `xlsx`. Detected from the file when omitted
+        #[arg(long)]
+        format: Option<String>,
+        /// Import profile from data.yml to normalize amounts with, overriding the account's `import_profile`
+        #[arg(long)]
+        profile: Option<String>,
+        /// Sheet to read Excel statements from, overriding the import profile's `sheet`
+        #[arg(long)]
+        sheet: Option<String>,
+        /// Show the records each statement would add and skip, without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Import OFX or MT940 statements into the accounts they're for, matched by the account number each gives, and by the
+    /// bank's BIC when the number alone matches more than one account
+    ImportMatched {
+        #[arg(required = true)]
+        statements: Vec<PathBuf>,
+        /// Show the records each statement would add and skip, without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Pull balances kept in Google Sheets, as configured under `google_sheets` in data.yml, into the accounts'
+    /// statements
+    #[cfg(feature = "google-sheets")]
+    PullSheets {
+        /// Accounts to pull; every configured sheet when omitted
+        accounts: Vec<String>,
+    },
+    /// Look through GoCardless or OFX transaction exports for accounts that may be missing from data.yml
+    Analyze {
+        #[arg(required = true)]
+        statements: Vec<PathBuf>,
+    },
+    /// Maintain imported statement files
+    Statements {
+        #[command(subcommand)]
+        command: StatementsCommand,
+    },
+    /// Import a CSV of daily prices (date,price) for a crypto asset, used to value crypto exchange accounts
+    ImportPrices {
+        asset: String,
+        prices: PathBuf,
+        /// Currency the prices are quoted in
+        #[arg(long, default_value = "usd")]
+        currency: String,
+    },
+    /// Maintain exchange rate facts
+    Facts {
+        #[command(subcommand)]
+        command: FactsCommand,
+    },
+    /// Check a year's report against the FinCEN schema's field limits before filing
+    Validate { year: i32 },
+    /// Print the computed report for a year, including the provenance of imported statements
+    Report {
+        year: i32,
+        /// Write the report under this directory instead of printing it, recording it in the directory's manifest
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Where reports go within `--out`, using `{year}`, `{filer}` and `{format}`
+        #[arg(long, default_value = output::DEFAULT_LAYOUT)]
+        layout: output::Layout,
+        /// Output format, `yaml`, `json`, `xml` (FinCEN batch), `pdf` (worksheet), `xlsx` (workbook), `csv` or `html`;
+        /// repeat for several
+        #[arg(long = "format", default_value = "yaml")]
+        formats: Vec<String>,
+        /// Also build a zip of the XML, worksheet, JSON report, audit trail and provenance to archive with your tax
+        /// records, under `--out` or the current directory
+        #[arg(long)]
+        package: bool,
+        /// Only include accounts with this tag; repeat for several
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+    },
+    /// Build a zip of the filing, account details, statements and rates for a year, with an index, to keep for the
+    /// five years records have to be retained
+    Retain {
+        #[arg(long)]
+        year: i32,
+        /// Directory to write the bundle under, recording it in the directory's manifest
+        #[arg(long, default_value = ".")]
+        out: PathBuf,
+        /// Where the bundle goes within `--out`, using `{year}`, `{filer}` and `{format}`
+        #[arg(long, default_value = output::DEFAULT_LAYOUT)]
+        layout: output::Layout,
+        /// Mask a field in the account details, e.g. `identifier1=last4` or `all=full`; repeat for several. Fields are
+        /// `identifier1`, `identifier2` and `tin`; policies `full`, `last4` and `none`.
+        #[arg(long = "mask")]
+        masks: Vec<String>,
+    },
+    /// List the reports generated so far and the closed years' reports, with their IDs and totals
+    History {
+        #[command(subcommand)]
+        command: Option<HistoryCommand>,
+    },
+    /// Recompute a previously generated report and confirm it still matches its inputs
+    Verify { report: PathBuf },
+    /// Redo each account's conversion to USD in f64 and at the exact rate, flagging any that differ from the report's
+    /// figure by more than a cent
+    AuditPrecision { year: i32 },
+    /// Show each name and address the FinCEN XML writes differently from data.yml, transliterated to the characters
+    /// BSA E-Filing accepts
+    Transliterations { year: i32 },
+    /// Freeze the computed report for a year into a snapshot, so later changes to its data are flagged
+    CloseYear { year: i32 },
+    /// Start a new year's prep from last year's accounts, writing empty statement files under `inbox/<to>`
+    Rollover { from: i32, to: i32 },
+    /// Step through a year's accounts, their maxima and warnings, marking each as reviewed in reviews.yml
+    Review {
+        year: i32,
+        /// Name to sign reviews off in; defaults to the first filer's
+        #[arg(long = "by")]
+        reviewer: Option<String>,
+        /// Mark these accounts as reviewed without opening the checklist; repeat for several
+        #[arg(long = "mark")]
+        marks: Vec<String>,
+    },
+    /// Check the data directory's layout, rates and file permissions, suggesting fixes for anything wrong
+    Doctor {
+        /// Year to check exchange rates for; defaults to last year, the one usually being filed
+        year: Option<i32>,
+    },
+    /// Upgrade data.yml to the current schema version, keeping a backup of the original
+    Migrate {
+        /// Show what would change without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Print a Graphviz DOT graph of filers, providers, accounts, statements and rates, highlighting a year's problems
+    Graph {
+        /// Year to check; defaults to last year, the one usually being filed
+        year: Option<i32>,
+    },
+    /// Copy the data directory with names, addresses and identifiers replaced by deterministic fakes
+    Anonymize {
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// For development: generate a synthetic data directory of any size, for benchmarks and tests of the importers
+    /// and report engine
+    GenFixture {
+        #[arg(long)]
+        out: PathBuf,
+        #[arg(long, default_value_t = 3)]
+        providers: usize,
+        #[arg(long, default_value_t = 10)]
+        accounts: usize,
+        /// First year to generate balances for
+        #[arg(long = "from", default_value_t = 2022)]
+        from_year: i32,
+        #[arg(long, default_value_t = 3)]
+        years: u32,
+        /// Write the daily balances as CSV statements under `inbox/<year>` to import, instead of into data.yml
+        #[arg(long)]
+        statements: bool,
+        /// Seed for the generated balances and identifiers
+        #[arg(long, default_value_t = 1)]
+        seed: u64,
+    },
+    /// Write one of the example data directories, e.g. `joint-couple`, or list them when none is given
+    Example {
+        name: Option<String>,
+        /// Directory to write the example to; defaults to one named after it
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Start a data directory from a previously filed FBAR, the XML copy from BSA E-Filing
+    InitFromFiling {
+        filing: PathBuf,
+        /// Directory to write the data directory to
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Print a JSON Schema for `data.yml` or statement files, for editor completion and validation
+    Schema {
+        /// `data` or `statements`
+        #[arg(default_value = "data")]
+        file: schema::SchemaFile,
+    },
+    /// Explain a warning or error code, e.g. FB0012: the rule, why it matters for the FBAR and how to fix it. Lists
+    /// every code when none is given
+    Explain { code: Option<String> },
+}
+
+#[derive(Subcommand)]
+enum AccountsCommand {
+    /// List each account with its provider, kind, currency and tags
+    List {
+        /// Only list accounts with this tag; repeat for several
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum StatementsCommand {
+    /// Rewrite each account's statements as one sorted, deduplicated file per year
+    Normalize,
+    /// Store imported balances as compact series of changes, e.g. for daily balances over many years
+    Compress,
+    /// Store imported balances as lists of records again
+    Expand,
+}
+
+#[derive(Subcommand)]
+enum HistoryCommand {
+    /// Print a report from the history as YAML
+    Show {
+        /// The report's ID, or enough of its start to pick it out
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum FactsCommand {
+    /// Import year-end rates from the Treasury's Reporting Rates of Exchange CSV into `rates/treasury.yml`
+    ImportTreasury { csv: PathBuf },
+    /// List each year's currencies that need converting and whether there's a rate for each
+    Coverage,
+    /// Show the year-end and yearly average rates for each currency the year's accounts are in
+    Rates { year: i32 },
+}
+
+/// Sets up logging and the shared context, then runs the command
+pub fn run(args: Args) -> Result<ExitCode> {
+    init_logging(args.verbose, args.log_json);
+    tracing::info!("Generating FBAR data from {:?}", args.data_dir);
+
+    let app = App::new(AppConfig {
+        data_dir: args.data_dir,
+        strict: args.strict,
+        date_order: args.date_order,
+        rate_precedence: args.rate_precedence,
+        rate_fallback: args.rate_fallback,
+        us_person_since: args.first_year,
+        fincen_schema_version: args.schema_version,
+        cache: !args.no_cache,
+        include_archived: args.include_archived,
+        progress: if args.quiet {
+            progress::Progress::hidden()
+        } else {
+            progress::Progress::new()
+        },
+    });
+
+    match args.command {
+        Command::Status => data::status(&app),
+        Command::Summary { household: false } => data::summary(&app),
+        Command::Summary { household: true } => data::household_summary(&app),
+        Command::Deadlines => data::deadlines(&app),
+        Command::Accounts {
+            command: AccountsCommand::List { tags },
+        } => data::list_accounts(&app, &tags),
+        Command::Import {
+            account,
+            statements,
+            format,
+            profile,
+            sheet,
+            dry_run,
+        } => import::statements(
+            &app,
+            &account,
+            &statements,
+            format.as_deref(),
+            profile.as_deref(),
+            sheet,
+            dry_run,
+        ),
+        Command::ImportMatched {
+            statements,
+            dry_run,
+        } => import::matched(&app, &statements, dry_run),
+        #[cfg(feature = "google-sheets")]
+        Command::PullSheets { accounts } => import::pull_sheets(&app, &accounts),
+        Command::Analyze { statements } => data::analyze(&app, &statements),
+        Command::Statements {
+            command: StatementsCommand::Normalize,
+        } => import::normalize(&app),
+        Command::Statements {
+            command: StatementsCommand::Compress,
+        } => import::compress(&app, true),
+        Command::Statements {
+            command: StatementsCommand::Expand,
+        } => import::compress(&app, false),
+        Command::ImportPrices {
+            asset,
+            prices,
+            currency,
+        } => import::prices(&app, &asset, &prices, &currency),
+        Command::Facts {
+            command: FactsCommand::ImportTreasury { csv },
+        } => import::treasury(&app, &csv),
+        Command::Facts {
+            command: FactsCommand::Coverage,
+        } => data::coverage(&app),
+        Command::Facts {
+            command: FactsCommand::Rates { year },
+        } => data::rates(&app, year),
+        Command::Validate { year } => year::validate(&app, year),
+        Command::Report {
+            year,
+            out,
+            layout,
+            formats,
+            package,
+            tags,
+        } => year::report(
+            &app,
+            year,
+            &year::ReportOptions {
+                out,
+                layout,
+                formats,
+                package,
+                tags,
+            },
+        ),
+        Command::Retain {
+            year,
+            out,
+            layout,
+            masks,
+        } => year::retain(&app, year, &out, &layout, &masks),
+        Command::History { command: None } => year::history(&app),
+        Command::History {
+            command: Some(HistoryCommand::Show { id }),
+        } => year::show_history(&app, &id),
+        Command::Verify { report } => year::verify(&app, &report),
+        Command::AuditPrecision { year } => year::audit_precision(&app, year),
+        Command::Transliterations { year } => year::transliterations(&app, year),
+        Command::CloseYear { year } => year::close_year(&app, year),
+        Command::Rollover { from, to } => year::rollover(&app, from, to),
+        Command::Review {
+            year,
+            reviewer,
+            marks,
+        } => year::review(&app, year, reviewer.as_deref(), &marks),
+        Command::Doctor { year } => data::doctor(&app, year),
+        Command::Migrate { dry_run } => data::migrate(&app, dry_run),
+        Command::Graph { year } => data::graph(&app, year),
+        Command::Anonymize { out } => data::anonymize(&app, &out),
+        Command::GenFixture {
+            out,
+            providers,
+            accounts,
+            from_year,
+            years,
+            statements,
+            seed,
+        } => data::gen_fixture(
+            &out,
+            &fixture::FixtureSpec {
+                providers,
+                accounts,
+                first_year: from_year,
+                years,
+                statements,
+                seed,
+            },
+        ),
+        Command::Example { name, out } => data::example(name.as_deref(), out.as_deref()),
+        Command::InitFromFiling { filing, out } => data::init_from_filing(&filing, &out),
+        Command::Schema { file } => data::schema(file),
+        Command::Explain { code } => data::explain(code.as_deref()),
+    }
+}
+
+fn init_logging(verbose: u8, json: bool) {
+    let level = match verbose {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+    // RUST_LOG still works for finer-grained filtering
+    let filter = EnvFilter::builder()
+        .with_default_directive(level.into())
+        .from_env_lossy();
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_target(false);
+
+    if json {
+        subscriber.json().init();
+    } else {
+        subscriber.without_time().init();
+    }
+}
+
+fn print_parse_warnings(parse_context: &parsing::ParseContext) {
+    for diagnostic in parse_context.diagnostics() {
+        warn!("[{}] {}", diagnostic.code, diagnostic.message);
+    }
+}
@@ -0,0 +1,439 @@
+//! Commands that work on a single year's report
+
+use anyhow::{bail, Context, Result};
+use chrono::SubsecRound;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tracing::warn;
+
+use fbar_prep::app::App;
+use fbar_prep::data::masking::{self, Masking};
+use fbar_prep::data::UserData;
+use fbar_prep::{
+    fincen, output, package, report, retention, review as reviews, rollover as rollovers,
+};
+
+pub struct ReportOptions {
+    pub out: Option<PathBuf>,
+    pub layout: output::Layout,
+    /// Names of the formats to write, resolved against the registry of writers
+    pub formats: Vec<String>,
+    pub package: bool,
+    pub tags: Vec<String>,
+}
+
+pub fn validate(app: &App, year: i32) -> Result<ExitCode> {
+    let snapshots = report::SnapshotStore::new(app.data_dir());
+    let (user_data, context) = app.data()?;
+    let report = report::Report::generate(year, user_data, context)
+        .with_context(|| format!("generating report for {}", year))?;
+    let dropped = warn_on_dropped_accounts(&snapshots, &report, user_data)
+        + warn_on_significant_changes(&snapshots, &report, user_data);
+    warn_on_filing_options(&report, user_data);
+
+    let issues = fincen::validate_report(&report, user_data);
+    for issue in &issues {
+        println!("{}", issue);
+    }
+    if !issues.is_empty() {
+        println!("Run `fbar_prep explain <code>` for what an issue means and how to fix it");
+    }
+
+    if issues
+        .iter()
+        .any(|issue| issue.kind == fincen::IssueKind::Rejected)
+    {
+        return Ok(ExitCode::FAILURE);
+    } else if issues.is_empty() && dropped == 0 {
+        println!(
+            "No problems found for {} against FinCEN schema {}",
+            year,
+            user_data.fincen_schema_version.unwrap_or_default()
+        );
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn report(app: &App, year: i32, options: &ReportOptions) -> Result<ExitCode> {
+    let snapshots = report::SnapshotStore::new(app.data_dir());
+    let registry = output::WriterRegistry::default();
+    let writers = registry.resolve(&options.formats)?;
+    let (user_data, context) = app.data()?;
+    let written = report::Report::generate_tagged(year, user_data, context, &options.tags)
+        .inspect(|report| {
+            warn_on_dropped_accounts(&snapshots, report, user_data);
+            warn_on_significant_changes(&snapshots, report, user_data);
+            warn_on_filing_options(report, user_data);
+            if options.package || writers.iter().any(|writer| writer.is_filing()) {
+                warn_on_unreviewed(app, report);
+                warn_on_transliterations(report, user_data);
+            }
+        })
+        .and_then(|report| write_report(&report, user_data, &writers, options).map(|_| report));
+    let report = written.with_context(|| format!("generating report for {}", year))?;
+    if let Err(err) = report::HistoryStore::new(app.data_dir()).record(&report, chrono::Utc::now())
+    {
+        warn!("Could not record the report in the history: {:#}", err);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Writes the report under `--out` and as a package, or prints it when neither is asked for
+fn write_report(
+    report: &report::Report,
+    user_data: &UserData,
+    writers: &[&dyn output::ReportWriter],
+    options: &ReportOptions,
+) -> Result<()> {
+    let generated_at = chrono::Utc::now().trunc_subsecs(0);
+    let signed_on = generated_at.date_naive();
+    if let Some(out) = &options.out {
+        let _span = tracing::info_span!("export", path = ?out).entered();
+        let artifacts =
+            output::write_report(out, &options.layout, report, user_data, writers, signed_on)?;
+        for artifact in artifacts {
+            println!("Wrote {}", out.join(&artifact.path).display());
+        }
+    }
+    if options.package {
+        let out = options.out.as_deref().unwrap_or(Path::new("."));
+        let _span = tracing::info_span!("package", path = ?out).entered();
+        let artifact =
+            package::write_package(out, &options.layout, report, user_data, generated_at)?;
+        println!("Wrote {}", out.join(&artifact.path).display());
+    }
+    if options.out.is_none() && !options.package {
+        let mut stdout = std::io::stdout();
+        for writer in writers {
+            writer.render(report, user_data, signed_on, &mut stdout)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn transliterations(app: &App, year: i32) -> Result<ExitCode> {
+    let (user_data, context) = app.data()?;
+    let report = report::Report::generate(year, user_data, context)
+        .with_context(|| format!("generating report for {}", year))?;
+    let values = fincen::transliterations(&report, user_data)?;
+    if values.is_empty() {
+        println!("The {} XML writes every value as it is in data.yml", year);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    for value in &values {
+        println!("{} {}", value.subject, value.field);
+        println!("- {}", value.original);
+        if value.is_accepted() {
+            println!("+ {}", value.exported);
+        } else {
+            println!("! {} (can't be transliterated)", value.exported);
+        }
+    }
+    if values.iter().all(|value| value.is_accepted()) {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        println!("Add the values marked `!` under transliteration.replacements in data.yml");
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+pub fn audit_precision(app: &App, year: i32) -> Result<ExitCode> {
+    let (user_data, context) = app.data()?;
+    let report = report::Report::generate(year, user_data, context)
+        .with_context(|| format!("generating report for {}", year))?;
+    let checks = report::precision::precision_checks(&report)
+        .with_context(|| format!("checking the conversions for {}", year))?;
+
+    println!(
+        "{:<24}{:>22}{:>12}{:>18}{:>18}{:>18}",
+        "Account", "Maximum", "Rate", "Filed", "f64", "Exact rate"
+    );
+    for check in &checks {
+        println!(
+            "{:<24}{:>22}{:>12}{:>18}{:>18}{:>18}{}",
+            check.account,
+            check
+                .max_balance
+                .format_to(user_data.minor_units(&check.max_balance.currency)),
+            check.exchange_rate,
+            check.decimal_usd.format(),
+            check.float_usd.format(),
+            check.exact_usd.format(),
+            if check.exceeds_tolerance() {
+                format!("  differs by ${}", check.difference())
+            } else {
+                String::new()
+            }
+        );
+    }
+
+    let differing = checks
+        .iter()
+        .filter(|check| check.exceeds_tolerance())
+        .count();
+    if differing > 0 {
+        println!(
+            "{} of {} conversion(s) for {} differ by more than a cent",
+            differing,
+            checks.len(),
+            year
+        );
+        return Ok(ExitCode::FAILURE);
+    }
+    println!(
+        "All {} conversion(s) for {} agree to the cent",
+        checks.len(),
+        year
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn retain(
+    app: &App,
+    year: i32,
+    out: &Path,
+    layout: &output::Layout,
+    masks: &[String],
+) -> Result<ExitCode> {
+    let mut masking = Masking::default();
+    for rule in masks {
+        masking.apply_rule(rule).context("parsing --mask")?;
+    }
+    let (user_data, context) = app.data()?;
+    let report = report::Report::generate(year, user_data, context)
+        .with_context(|| format!("generating report for {}", year))?;
+    let _span = tracing::info_span!("retain", path = ?out).entered();
+    let generated_at = chrono::Utc::now().trunc_subsecs(0);
+    let artifact = masking::with_masking(masking, || {
+        retention::write_records(out, layout, &report, user_data, context, generated_at)
+    })?;
+    println!("Wrote {}", out.join(&artifact.path).display());
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn history(app: &App) -> Result<ExitCode> {
+    let archived = report::HistoryStore::new(app.data_dir())
+        .list()
+        .context("reading the report history")?;
+    if archived.is_empty() {
+        println!("No reports generated yet");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    println!(
+        "{:<18}{:<6}{:<18}{:>10}{:>20}  Status",
+        "ID", "Year", "Date", "Accounts", "Aggregate max"
+    );
+    for entry in &archived {
+        let tags = entry
+            .report
+            .metadata
+            .as_ref()
+            .map(|metadata| metadata.tags.join(", "))
+            .unwrap_or_default();
+        println!(
+            "{:<18}{:<6}{:<18}{:>10}{:>20}  {}{}",
+            entry.report_id,
+            entry.year,
+            entry.date.format("%Y-%m-%d %H:%M"),
+            entry.report.accounts.len(),
+            entry.report.aggregate_max_usd()?.format(),
+            if entry.closed { "closed" } else { "generated" },
+            if tags.is_empty() {
+                String::new()
+            } else {
+                format!(" (tags: {})", tags)
+            }
+        );
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn show_history(app: &App, id: &str) -> Result<ExitCode> {
+    let archived = report::HistoryStore::new(app.data_dir()).find(id)?;
+    print!("{}", serde_yaml::to_string(&archived.report)?);
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn verify(app: &App, path: &Path) -> Result<ExitCode> {
+    let (user_data, context) = app.data()?;
+    let (stored, differences) = std::fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|contents| Ok(serde_yaml::from_str::<report::Report>(&contents)?))
+        .and_then(|stored| {
+            let differences = report::metadata::verify(&stored, user_data, context)?;
+            Ok((stored, differences))
+        })
+        .with_context(|| format!("verifying {:?}", path))?;
+
+    if !differences.is_empty() {
+        for difference in differences {
+            println!("{}", difference);
+        }
+        return Ok(ExitCode::FAILURE);
+    }
+    println!(
+        "Report {} for {} matches its inputs",
+        stored
+            .metadata
+            .map(|metadata| metadata.report_id)
+            .unwrap_or_default(),
+        stored.year
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn close_year(app: &App, year: i32) -> Result<ExitCode> {
+    let snapshots = report::SnapshotStore::new(app.data_dir());
+    let (user_data, context) = app.data()?;
+    let snapshot = report::Report::generate(year, user_data, context)
+        .and_then(|report| {
+            warn_on_dropped_accounts(&snapshots, &report, user_data);
+            warn_on_significant_changes(&snapshots, &report, user_data);
+            snapshots.close_year(report)
+        })
+        .with_context(|| format!("closing {}", year))?;
+    println!("Closed {} ({})", year, snapshot.content_hash);
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn rollover(app: &App, from: i32, to: i32) -> Result<ExitCode> {
+    let user_data = app.user_data()?;
+    let summary = rollovers::rollover(app.data_dir(), user_data, from, to)
+        .with_context(|| format!("rolling over {} to {}", from, to))?;
+    for path in &summary.created {
+        println!("Created {:?}", path);
+    }
+    for path in &summary.skipped {
+        println!("Skipped {:?} (already exists)", path);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn review(app: &App, year: i32, reviewer: Option<&str>, marks: &[String]) -> Result<ExitCode> {
+    let store = reviews::ReviewStore::new(app.data_dir());
+    let (user_data, context) = app.data()?;
+    let reviewer = reviewer
+        .map(str::to_string)
+        .or_else(|| user_data.filers.first().map(|filer| filer.name.clone()));
+    let state = report::Report::generate(year, user_data, context)
+        .and_then(|report| {
+            let mut state = store.load(year)?;
+            if marks.is_empty() {
+                let app = reviews::ReviewApp::new(report, user_data, state);
+                return match &reviewer {
+                    Some(reviewer) => app.reviewer(reviewer.as_str()),
+                    None => app,
+                }
+                .run(&store);
+            }
+            for handle in marks {
+                let Some(account) = report
+                    .accounts
+                    .iter()
+                    .find(|account| &account.handle == handle)
+                else {
+                    bail!("{} isn't in the {} report", handle, year);
+                };
+                state.mark(account, reviewer.as_deref());
+            }
+            store.save(&state)?;
+            Ok(state)
+        })
+        .with_context(|| format!("reviewing {}", year))?;
+    println!("Reviewed {} account(s) for {}", state.accounts.len(), year);
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Points out accounts going into a filing that nobody has signed off on in `review`
+fn warn_on_unreviewed(app: &App, report: &report::Report) {
+    match reviews::ReviewStore::new(app.data_dir()).load(report.year) {
+        Ok(state) => {
+            let unreviewed = state.unreviewed(report);
+            if !unreviewed.is_empty() {
+                let handles: Vec<&str> = unreviewed
+                    .iter()
+                    .map(|account| account.handle.as_str())
+                    .collect();
+                warn!(
+                    "{} of {} account(s) in the {} filing haven't been reviewed: {}; sign them off with `review {}`",
+                    unreviewed.len(),
+                    report.accounts.len(),
+                    report.year,
+                    handles.join(", "),
+                    report.year
+                );
+            }
+        }
+        Err(err) => warn!("Could not read the reviews for {}: {:#}", report.year, err),
+    }
+}
+
+/// Says how many values the XML writes differently from `data.yml`, so the filer knows to check them
+fn warn_on_transliterations(report: &report::Report, user_data: &UserData) {
+    match fincen::transliterations(report, user_data) {
+        Ok(values) if !values.is_empty() => warn!(
+            "{} name and address value(s) in the {} filing are transliterated for BSA E-Filing; see them with `transliterations {}`",
+            values.len(),
+            report.year,
+            report.year
+        ),
+        Ok(_) => {}
+        Err(err) => warn!("Could not check the transliterations for {}: {:#}", report.year, err),
+    }
+}
+
+/// Points out filing options the report qualifies for, like summarizing 25 or more accounts
+fn warn_on_filing_options(report: &report::Report, user_data: &UserData) {
+    let notices = report::summary::notice(report, user_data)
+        .into_iter()
+        .chain(report::joint::notice(report));
+    for notice in notices {
+        warn!("{}", notice);
+    }
+}
+
+fn warn_on_dropped_accounts(
+    snapshots: &report::SnapshotStore,
+    report: &report::Report,
+    user_data: &UserData,
+) -> usize {
+    match report::continuity::dropped_accounts(snapshots, report, user_data) {
+        Ok(dropped) => {
+            for account in &dropped {
+                warn!("{}", account);
+            }
+            dropped.len()
+        }
+        Err(err) => {
+            warn!(
+                "Could not compare {} with the previous year: {}",
+                report.year, err
+            );
+            0
+        }
+    }
+}
+
+fn warn_on_significant_changes(
+    snapshots: &report::SnapshotStore,
+    report: &report::Report,
+    user_data: &UserData,
+) -> usize {
+    match report::continuity::significant_changes(snapshots, report, &user_data.change_checks) {
+        Ok(changes) => {
+            for change in &changes {
+                warn!("{}", change);
+            }
+            changes.len()
+        }
+        Err(err) => {
+            warn!(
+                "Could not compare {} with the previous year: {}",
+                report.year, err
+            );
+            0
+        }
+    }
+}
@@ -0,0 +1,216 @@
+pub mod prices;
+pub use self::prices::{PricePoint, PriceSeries, PriceStore};
+
+use anyhow::{bail, Result};
+use chrono::NaiveDate;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::data::Account;
+use crate::money::Money;
+use crate::report::{OpenPeriod, Valuation, ValuePoint};
+use crate::report_context::ReportContext;
+
+/// Name of the valuation used by crypto exchange accounts
+pub const CRYPTO_VALUATION: &str = "crypto";
+
+/// Values crypto exchange accounts by pricing their holdings.
+///
+/// The account is valued on each date it has holding records for: every asset's most recent quantity is multiplied
+/// by the asset's most recent price, in the account's currency.
+pub struct CryptoValuation {
+    prices: Vec<PriceSeries>,
+}
+
+impl CryptoValuation {
+    pub fn new(prices: Vec<PriceSeries>) -> Self {
+        Self { prices }
+    }
+
+    fn price(&self, asset: &str, currency: &str, date: NaiveDate) -> Result<Money> {
+        let Some(series) = self
+            .prices
+            .iter()
+            .find(|series| series.asset.eq_ignore_ascii_case(asset))
+        else {
+            bail!("No prices imported for {}", asset.to_uppercase());
+        };
+        if !series.currency.eq_ignore_ascii_case(currency) {
+            bail!(
+                "{} prices are in {} but the account is valued in {}",
+                asset.to_uppercase(),
+                series.currency.to_uppercase(),
+                currency.to_uppercase()
+            );
+        }
+
+        match series.price_on(date) {
+            Some(price) => Ok(price),
+            None => bail!("No {} price on or before {}", asset.to_uppercase(), date),
+        }
+    }
+}
+
+impl Valuation for CryptoValuation {
+    fn name(&self) -> &str {
+        CRYPTO_VALUATION
+    }
+
+    fn value_series(
+        &self,
+        account: &Account,
+        period: &OpenPeriod,
+        _context: &ReportContext,
+    ) -> Result<Vec<ValuePoint>> {
+        let dates: BTreeSet<NaiveDate> = account
+            .holdings
+            .iter()
+            .map(|holding| holding.date)
+            .filter(|date| period.contains(*date))
+            .collect();
+
+        let mut series = Vec::new();
+        for date in dates {
+            // Latest known quantity of each asset as of the date
+            let mut quantities = BTreeMap::new();
+            for holding in account
+                .holdings
+                .iter()
+                .filter(|holding| holding.date <= date)
+            {
+                let asset = holding.asset.to_lowercase();
+                match quantities.get(&asset) {
+                    Some((held_on, _)) if *held_on > holding.date => {}
+                    _ => {
+                        quantities.insert(asset, (holding.date, holding.quantity));
+                    }
+                }
+            }
+
+            let mut value = Money::new(Default::default(), &account.currency_code);
+            for (asset, (_, quantity)) in quantities {
+                let price = self.price(&asset, &account.currency_code, date)?;
+                value = value.checked_add(&Money::new(quantity * price.amount, &price.currency))?;
+            }
+
+            series.push(ValuePoint { date, value });
+        }
+
+        Ok(series)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{AccountKind, HoldingRecord};
+    use crate::facts::Facts;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    fn create_test_context() -> ReportContext {
+        ReportContext::new(Facts::empty(), None)
+    }
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    fn holding(value: &str, asset: &str, quantity: Decimal) -> HoldingRecord {
+        HoldingRecord {
+            date: date(value),
+            asset: asset.to_string(),
+            quantity,
+        }
+    }
+
+    fn create_test_prices() -> Vec<PriceSeries> {
+        let series = |asset: &str, prices: &[(&str, Decimal)]| PriceSeries {
+            asset: asset.to_string(),
+            currency: "usd".to_string(),
+            prices: prices
+                .iter()
+                .map(|(day, price)| PricePoint {
+                    date: date(day),
+                    price: *price,
+                })
+                .collect(),
+        };
+
+        vec![
+            series(
+                "btc",
+                &[("2023-01-31", dec!(23000)), ("2023-06-30", dec!(30000))],
+            ),
+            series(
+                "eth",
+                &[("2023-01-31", dec!(1600)), ("2023-06-30", dec!(1900))],
+            ),
+        ]
+    }
+
+    fn create_test_account() -> Account {
+        Account {
+            handle: "exchange".to_string(),
+            provider: "example_exchange".to_string(),
+            currency_code: "usd".to_string(),
+            kind: AccountKind::CryptoExchange,
+            holdings: vec![
+                holding("2023-01-31", "BTC", dec!(0.5)),
+                holding("2023-01-31", "ETH", dec!(2)),
+                // ETH unchanged since January
+                holding("2023-06-30", "BTC", dec!(0.25)),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_value_series() -> Result<()> {
+        let account = create_test_account();
+        let period = OpenPeriod::for_account(&account, 2023).unwrap();
+
+        let series = CryptoValuation::new(create_test_prices()).value_series(
+            &account,
+            &period,
+            &create_test_context(),
+        )?;
+
+        assert_eq!(
+            series,
+            vec![
+                ValuePoint {
+                    date: date("2023-01-31"),
+                    value: Money::usd(dec!(14700)),
+                },
+                ValuePoint {
+                    date: date("2023-06-30"),
+                    value: Money::usd(dec!(11300)),
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_prices() {
+        let account = create_test_account();
+        let period = OpenPeriod::for_account(&account, 2023).unwrap();
+
+        let result = CryptoValuation::new(create_test_prices()[..1].to_vec()).value_series(
+            &account,
+            &period,
+            &create_test_context(),
+        );
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("No prices imported for ETH"));
+
+        let mut account = create_test_account();
+        account.currency_code = "eur".to_string();
+        assert!(CryptoValuation::new(create_test_prices())
+            .value_series(&account, &period, &create_test_context())
+            .is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::money::Money;
+use crate::parsing::ParseContext;
+
+/// Daily prices for a crypto asset, stored as `prices/<asset>.yml`
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct PriceSeries {
+    /// Asset ticker, stored lowercase like currency codes
+    pub asset: String,
+    /// Currency the prices are quoted in
+    pub currency: String,
+    #[serde(default)]
+    pub prices: Vec<PricePoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct PricePoint {
+    pub date: NaiveDate,
+    /// Price of one unit of the asset
+    pub price: Decimal,
+}
+
+impl PriceSeries {
+    pub fn new(asset: &str, currency: &str) -> Self {
+        Self {
+            asset: asset.to_lowercase(),
+            currency: currency.to_lowercase(),
+            prices: Vec::new(),
+        }
+    }
+
+    /// The most recent price on or before the date
+    pub fn price_on(&self, date: NaiveDate) -> Option<Money> {
+        self.prices
+            .iter()
+            .filter(|point| point.date <= date)
+            .max_by_key(|point| point.date)
+            .map(|point| Money::new(point.price, &self.currency))
+    }
+
+    /// Adds prices to the series, replacing any existing price for the same date
+    pub fn merge(&mut self, prices: Vec<PricePoint>) {
+        for point in prices {
+            self.prices.retain(|existing| existing.date != point.date);
+            self.prices.push(point);
+        }
+        self.prices.sort_by_key(|point| point.date);
+    }
+}
+
+#[derive(Deserialize)]
+struct RawPrice {
+    date: String,
+    price: Decimal,
+}
+
+/// Parses a price series in CSV form, with `date` and `price` columns
+pub fn parse_prices(
+    reader: impl Read,
+    source: &str,
+    context: &ParseContext,
+) -> Result<Vec<PricePoint>> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+
+    let mut prices = Vec::new();
+    for (index, record) in csv_reader.deserialize().enumerate() {
+        // Data rows start on the line after the header
+        let location = format!("{} line {}", source, index + 2);
+        let raw: RawPrice = record.with_context(|| format!("Invalid row in {}", location))?;
+
+        // Rows with bad dates are skipped when lenient
+        if let Some(date) = context.parse_date(&raw.date, &location)? {
+            prices.push(PricePoint {
+                date,
+                price: raw.price,
+            });
+        }
+    }
+
+    Ok(prices)
+}
+
+/// Reads and writes price series under `prices` in the data directory
+pub struct PriceStore {
+    dir: PathBuf,
+}
+
+impl PriceStore {
+    pub fn new(base_path: &Path) -> Self {
+        Self {
+            dir: base_path.join("prices"),
+        }
+    }
+
+    /// Loads every price series in the store, ordered by asset
+    pub fn load_all(&self, context: &ParseContext) -> Result<Vec<PriceSeries>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut series = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("yml") {
+                continue;
+            }
+
+            let contents = std::fs::read_to_string(&path)?;
+            let value: serde_yaml::Value = serde_yaml::from_str(&contents)
+                .with_context(|| format!("Invalid price file {:?}", path))?;
+            series.push(context.deserialize(value, &path.display().to_string())?);
+        }
+        series.sort_by(|a: &PriceSeries, b| a.asset.cmp(&b.asset));
+
+        Ok(series)
+    }
+
+    /// Imports a CSV of daily prices for an asset, merging it into any prices already stored
+    pub fn import(
+        &self,
+        asset: &str,
+        currency: &str,
+        prices_path: &Path,
+        context: &ParseContext,
+    ) -> Result<PriceSeries> {
+        let file = std::fs::File::open(prices_path)
+            .with_context(|| format!("Could not read prices {:?}", prices_path))?;
+        let prices = parse_prices(file, &prices_path.display().to_string(), context)?;
+
+        let path = self.series_path(asset);
+        let mut series = if path.exists() {
+            let contents = std::fs::read_to_string(&path)?;
+            serde_yaml::from_str(&contents)?
+        } else {
+            PriceSeries::new(asset, currency)
+        };
+        if !series.currency.eq_ignore_ascii_case(currency) {
+            bail!(
+                "{} prices are stored in {}, not {}",
+                asset.to_uppercase(),
+                series.currency.to_uppercase(),
+                currency.to_uppercase()
+            );
+        }
+
+        series.merge(prices);
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(&path, serde_yaml::to_string(&series)?)?;
+
+        Ok(series)
+    }
+
+    fn series_path(&self, asset: &str) -> PathBuf {
+        self.dir.join(format!("{}.yml", asset.to_lowercase()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_price_on() {
+        let mut series = PriceSeries::new("BTC", "USD");
+        series.merge(vec![
+            PricePoint {
+                date: date("2023-01-02"),
+                price: dec!(16700),
+            },
+            PricePoint {
+                date: date("2023-01-01"),
+                price: dec!(16500),
+            },
+        ]);
+
+        assert!(series.price_on(date("2022-12-31")).is_none());
+        assert_eq!(
+            series.price_on(date("2023-01-01")),
+            Some(Money::usd(dec!(16500)))
+        );
+        // Weekends and gaps fall back to the last known price
+        assert_eq!(
+            series.price_on(date("2023-01-10")),
+            Some(Money::usd(dec!(16700)))
+        );
+    }
+
+    #[test]
+    fn test_import_merges_prices() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let store = PriceStore::new(temp_dir.path());
+
+        let prices_path = temp_dir.path().join("btc.csv");
+        fs::write(
+            &prices_path,
+            "date,price\n2023-01-01,16500\n2023-01-02,16700\n",
+        )?;
+        store.import("BTC", "usd", &prices_path, &ParseContext::default())?;
+
+        fs::write(
+            &prices_path,
+            "date,price\n2023-01-02,16800\n2023-01-03,16900\n",
+        )?;
+        let series = store.import("btc", "usd", &prices_path, &ParseContext::default())?;
+        assert_eq!(series.prices.len(), 3);
+        assert_eq!(series.prices[1].price, dec!(16800));
+
+        assert_eq!(store.load_all(&ParseContext::default())?, vec![series]);
+
+        // Prices for an asset can't switch currency
+        assert!(store
+            .import("btc", "eur", &prices_path, &ParseContext::default())
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use anyhow::{bail, Result};
+use isocountry::CountryCode;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A provider's address, either as a single free-form line or broken into the parts FinCEN asks for
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Address {
+    Freeform(String),
+    Structured(StructuredAddress),
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct StructuredAddress {
+    pub street: String,
+    pub city: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub postal_code: Option<String>,
+    /// ISO 3166-1 alpha-2 or alpha-3 code
+    pub country: String,
+}
+
+impl Default for Address {
+    fn default() -> Self {
+        Address::Freeform(String::new())
+    }
+}
+
+impl Address {
+    /// The country given in a structured address. Free-form addresses aren't parsed.
+    pub fn country(&self) -> Option<&str> {
+        match self {
+            Address::Freeform(_) => None,
+            Address::Structured(address) => Some(&address.country),
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Address::Freeform(address) => write!(f, "{}", address),
+            Address::Structured(address) => {
+                let parts = [
+                    Some(&address.street),
+                    Some(&address.city),
+                    address.state.as_ref(),
+                    address.postal_code.as_ref(),
+                    Some(&address.country),
+                ];
+                let parts: Vec<&str> = parts.into_iter().flatten().map(String::as_str).collect();
+                write!(f, "{}", parts.join(", "))
+            }
+        }
+    }
+}
+
+impl From<&str> for Address {
+    fn from(address: &str) -> Self {
+        Address::Freeform(address.to_string())
+    }
+}
+
+/// Parses an ISO 3166-1 alpha-2 or alpha-3 country code, case insensitively
+pub fn parse_country_code(code: &str) -> Result<CountryCode> {
+    let code = code.trim();
+    let parsed = match code.len() {
+        2 => CountryCode::for_alpha2_caseless(code).ok(),
+        3 => CountryCode::for_alpha3_caseless(code).ok(),
+        _ => None,
+    };
+
+    match parsed {
+        Some(country) => Ok(country),
+        None => bail!(
+            "{:?} is not an ISO 3166-1 alpha-2 or alpha-3 country code",
+            code
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_both_forms() {
+        let freeform: Address = serde_yaml::from_str("\"1 High St, London\"").unwrap();
+        assert_eq!(freeform, Address::from("1 High St, London"));
+        assert_eq!(freeform.country(), None);
+
+        let structured: Address = serde_yaml::from_str(
+            "street: 1 High St\ncity: London\npostal_code: EC1A 1AA\ncountry: GB\n",
+        )
+        .unwrap();
+        assert_eq!(structured.country(), Some("GB"));
+        assert_eq!(structured.to_string(), "1 High St, London, EC1A 1AA, GB");
+    }
+
+    #[test]
+    fn test_parse_country_code() {
+        assert_eq!(parse_country_code("gb").unwrap(), CountryCode::GBR);
+        assert_eq!(parse_country_code("CHE").unwrap(), CountryCode::CHE);
+        assert!(parse_country_code("XX").is_err());
+        assert!(parse_country_code("Switzerland").is_err());
+    }
+}
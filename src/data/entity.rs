@@ -0,0 +1,72 @@
+use anyhow::{bail, Result};
+use rust_decimal::Decimal;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::Address;
+
+/// A company, partnership or trust through which the filer holds accounts, e.g. a wholly-owned foreign company
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct Entity {
+    pub name: String,
+    pub handle: String,
+    pub address: Address,
+    /// The entity's US taxpayer identification number, if it has one
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "super::masking::tin"
+    )]
+    pub tin: Option<String>,
+    /// Percentage of the entity's stock, voting power, profits or capital the filer owns, directly or indirectly
+    pub ownership_percent: Decimal,
+}
+
+impl Entity {
+    /// Whether the filer owns more than half of the entity, which gives them a financial interest in its accounts.
+    /// Those accounts are reported in Part V; the accounts of entities owned 50% or less aren't the filer's to report.
+    pub fn is_controlled(&self) -> bool {
+        self.ownership_percent > Decimal::from(50)
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self.ownership_percent <= Decimal::ZERO || self.ownership_percent > Decimal::from(100) {
+            bail!(
+                "Entity {} has ownership_percent {}, which must be more than 0 and at most 100",
+                self.handle,
+                self.ownership_percent
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn create_test_entity(ownership_percent: Decimal) -> Entity {
+        Entity {
+            name: "Example Holdings Ltd".to_string(),
+            handle: "holdings".to_string(),
+            address: Address::from("1 Example Street, London"),
+            tin: None,
+            ownership_percent,
+        }
+    }
+
+    #[test]
+    fn test_is_controlled() {
+        assert!(create_test_entity(dec!(100)).is_controlled());
+        assert!(create_test_entity(dec!(50.5)).is_controlled());
+        assert!(!create_test_entity(dec!(50)).is_controlled());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(create_test_entity(dec!(100)).validate().is_ok());
+        assert!(create_test_entity(dec!(0)).validate().is_err());
+        assert!(create_test_entity(dec!(120)).validate().is_err());
+    }
+}
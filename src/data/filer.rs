@@ -0,0 +1,43 @@
+use anyhow::{bail, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{Address, UserData};
+
+/// A person whose accounts are kept in the data directory, e.g. each spouse in a household. The first filer listed
+/// is the one reports are prepared for.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct Filer {
+    pub name: String,
+    pub handle: String,
+    /// US taxpayer identification number, given for joint owners in Part III
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "super::masking::tin"
+    )]
+    pub tin: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<Address>,
+}
+
+impl UserData {
+    /// A copy of the data with reports prepared for another of its filers: they're moved first, and accounts without
+    /// `owners`, which belong to the original first filer, are given them explicitly
+    pub fn as_filer(&self, handle: &str) -> Result<Self> {
+        let Some(index) = self.filers.iter().position(|filer| filer.handle == handle) else {
+            bail!("Unknown filer {}", handle);
+        };
+        let mut data = self.clone();
+        if let Some(primary) = self.primary_filer() {
+            for account in &mut data.accounts {
+                if account.owners.is_empty() {
+                    account.owners.push(primary.handle.clone());
+                }
+            }
+        }
+        let filer = data.filers.remove(index);
+        data.filers.insert(0, filer);
+        Ok(data)
+    }
+}
@@ -0,0 +1,108 @@
+use chrono::NaiveDate;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// How a year's FBAR was, or will be, filed. Only needed when filing late, or to sign the FinCEN XML with the date it
+/// was actually filed.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct Filing {
+    /// Calendar year the filing reports on
+    pub year: i32,
+    /// Date the FBAR was filed, used as its signature date
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filed_on: Option<NaiveDate>,
+    /// Why the FBAR is filed after its deadline
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub late_reason: Option<LateFilingReason>,
+    /// Explanation of the late filing, required when the reason is `other`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<String>,
+}
+
+impl Filing {
+    /// The FBAR's due date: April 15 of the year after the one it reports on
+    pub fn due_date(year: i32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(year + 1, 4, 15)
+    }
+
+    /// The last day a year's FBAR can be filed on time: April 15 of the following year, automatically extended to
+    /// October 15
+    pub fn deadline(year: i32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(year + 1, 10, 15)
+    }
+
+    /// Whether the filing date is after the deadline. Unknown when no filing date is given.
+    pub fn is_late(&self) -> Option<bool> {
+        let deadline = Self::deadline(self.year)?;
+        self.filed_on.map(|filed_on| filed_on > deadline)
+    }
+}
+
+/// The reasons the FBAR form offers for filing late, given in `data.yml` by name or by the form's letter code
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LateFilingReason {
+    #[serde(alias = "A")]
+    ForgotToFile,
+    #[serde(alias = "B")]
+    DidNotKnowRequirement,
+    #[serde(alias = "C")]
+    ThoughtBelowThreshold,
+    #[serde(alias = "D")]
+    DidNotKnowAccountWasForeign,
+    #[serde(alias = "E")]
+    DeclinedToExplain,
+    #[serde(alias = "F")]
+    Other,
+}
+
+impl LateFilingReason {
+    /// The form's letter code for the reason
+    pub fn code(&self) -> &'static str {
+        match self {
+            LateFilingReason::ForgotToFile => "A",
+            LateFilingReason::DidNotKnowRequirement => "B",
+            LateFilingReason::ThoughtBelowThreshold => "C",
+            LateFilingReason::DidNotKnowAccountWasForeign => "D",
+            LateFilingReason::DeclinedToExplain => "E",
+            LateFilingReason::Other => "F",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            LateFilingReason::ForgotToFile => "Forgot to file",
+            LateFilingReason::DidNotKnowRequirement => "Did not know that I had to file",
+            LateFilingReason::ThoughtBelowThreshold => {
+                "Thought account balance was below reporting threshold"
+            }
+            LateFilingReason::DidNotKnowAccountWasForeign => {
+                "Did not know that my account qualified as foreign"
+            }
+            LateFilingReason::DeclinedToExplain => "Decline to provide an explanation",
+            LateFilingReason::Other => "Other",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_late_filing() -> anyhow::Result<()> {
+        let filing: Filing =
+            serde_yaml::from_str("year: 2022\nfiled_on: 2023-11-01\nlate_reason: A\n")?;
+        assert_eq!(filing.late_reason, Some(LateFilingReason::ForgotToFile));
+        assert_eq!(filing.is_late(), Some(true));
+
+        let filing: Filing =
+            serde_yaml::from_str("year: 2022\nfiled_on: 2023-10-15\nlate_reason: other\n")?;
+        assert_eq!(filing.late_reason.map(|reason| reason.code()), Some("F"));
+        assert_eq!(filing.is_late(), Some(false));
+
+        assert!(serde_yaml::from_str::<Filing>("year: 2022\nlate_reason: G\n").is_err());
+
+        Ok(())
+    }
+}
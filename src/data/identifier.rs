@@ -0,0 +1,214 @@
+use anyhow::{bail, Result};
+use isocountry::CountryCode;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// What an account identifier is, which decides how it's normalized and checked
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum IdentifierKind {
+    AccountNumber,
+    /// UK sort code, stored as `12-34-56`
+    SortCode,
+    /// International bank account number, stored without spaces
+    Iban,
+    /// SWIFT/BIC code of the branch holding the account
+    Bic,
+    /// Insurance or pension policy number
+    PolicyNumber,
+    MembershipNumber,
+}
+
+impl IdentifierKind {
+    const ALL: [IdentifierKind; 6] = [
+        IdentifierKind::AccountNumber,
+        IdentifierKind::SortCode,
+        IdentifierKind::Iban,
+        IdentifierKind::Bic,
+        IdentifierKind::PolicyNumber,
+        IdentifierKind::MembershipNumber,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            IdentifierKind::AccountNumber => "Account number",
+            IdentifierKind::SortCode => "Sort code",
+            IdentifierKind::Iban => "IBAN",
+            IdentifierKind::Bic => "BIC",
+            IdentifierKind::PolicyNumber => "Policy number",
+            IdentifierKind::MembershipNumber => "Membership number",
+        }
+    }
+
+    /// Recognizes the free-text `identifier1_name`/`identifier2_name` used before identifier kinds existed, e.g.
+    /// "IBAN", "Sort code" or "account_number". Names that don't match a kind give `None`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let simplified: String = name
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+
+        match simplified.as_str() {
+            "swift" | "swiftcode" | "biccode" => Some(IdentifierKind::Bic),
+            "accountno" => Some(IdentifierKind::AccountNumber),
+            _ => Self::ALL.into_iter().find(|kind| {
+                kind.label()
+                    .chars()
+                    .filter(|c| c.is_ascii_alphanumeric())
+                    .map(|c| c.to_ascii_lowercase())
+                    .eq(simplified.chars())
+            }),
+        }
+    }
+
+    /// Puts an identifier into its canonical form, failing if it can't be one of this kind
+    pub fn normalize(&self, value: &str) -> Result<String> {
+        let compact: String = value
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .collect::<String>()
+            .to_uppercase();
+        if compact.is_empty() {
+            bail!("{} is empty", self.label());
+        }
+
+        match self {
+            IdentifierKind::AccountNumber => {
+                if !compact.chars().all(|c| c.is_ascii_alphanumeric()) {
+                    bail!("account numbers may only contain letters and digits");
+                }
+                Ok(compact)
+            }
+            IdentifierKind::SortCode => {
+                if compact.len() != 6 || !compact.chars().all(|c| c.is_ascii_digit()) {
+                    bail!("sort codes have six digits");
+                }
+                Ok(format!(
+                    "{}-{}-{}",
+                    &compact[..2],
+                    &compact[2..4],
+                    &compact[4..]
+                ))
+            }
+            IdentifierKind::Iban => {
+                let valid_shape = (15..=34).contains(&compact.len())
+                    && compact.chars().all(|c| c.is_ascii_alphanumeric())
+                    && compact[..2].chars().all(|c| c.is_ascii_alphabetic())
+                    && compact[2..4].chars().all(|c| c.is_ascii_digit());
+                if !valid_shape {
+                    bail!(
+                        "IBANs are a country code, two check digits and up to 30 letters or digits"
+                    );
+                }
+                if iban_remainder(&compact) != 1 {
+                    bail!("IBAN check digits don't match");
+                }
+                Ok(compact)
+            }
+            IdentifierKind::Bic => {
+                let valid = matches!(compact.len(), 8 | 11)
+                    && compact.chars().all(|c| c.is_ascii_alphanumeric())
+                    && compact[..6].chars().all(|c| c.is_ascii_alphabetic());
+                if !valid {
+                    bail!("BICs have 8 or 11 characters, starting with a 4-letter bank code and a country code");
+                }
+                if CountryCode::for_alpha2(&compact[4..6]).is_err() {
+                    bail!(
+                        "{} in the BIC is not an ISO 3166-1 country code",
+                        &compact[4..6]
+                    );
+                }
+                Ok(compact)
+            }
+            // Formats vary by provider, so only whitespace is tidied
+            IdentifierKind::PolicyNumber | IdentifierKind::MembershipNumber => {
+                Ok(value.split_whitespace().collect::<Vec<_>>().join(" "))
+            }
+        }
+    }
+}
+
+impl fmt::Display for IdentifierKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.label())
+    }
+}
+
+/// The ISO 13616 mod-97 remainder of a compact, uppercase IBAN; 1 for a valid one
+pub(crate) fn iban_remainder(iban: &str) -> u32 {
+    let (head, tail) = iban.split_at(4.min(iban.len()));
+    tail.chars().chain(head.chars()).fold(0, |remainder, c| {
+        let digit = c.to_digit(36).unwrap_or(0);
+        if digit < 10 {
+            (remainder * 10 + digit) % 97
+        } else {
+            (remainder * 100 + digit) % 97
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(
+            IdentifierKind::from_name("IBAN"),
+            Some(IdentifierKind::Iban)
+        );
+        assert_eq!(
+            IdentifierKind::from_name("Sort code"),
+            Some(IdentifierKind::SortCode)
+        );
+        assert_eq!(
+            IdentifierKind::from_name("account_number"),
+            Some(IdentifierKind::AccountNumber)
+        );
+        assert_eq!(
+            IdentifierKind::from_name("SWIFT"),
+            Some(IdentifierKind::Bic)
+        );
+        assert_eq!(IdentifierKind::from_name("Customer reference"), None);
+    }
+
+    #[test]
+    fn test_normalize() -> Result<()> {
+        assert_eq!(
+            IdentifierKind::Iban.normalize("ch93 0076 2011 6238 5295 7")?,
+            "CH9300762011623852957"
+        );
+        assert_eq!(IdentifierKind::SortCode.normalize("123456")?, "12-34-56");
+        assert_eq!(IdentifierKind::SortCode.normalize("12 34 56")?, "12-34-56");
+        assert_eq!(
+            IdentifierKind::AccountNumber.normalize("3192 6819")?,
+            "31926819"
+        );
+        assert_eq!(IdentifierKind::Bic.normalize("ubswchzh80a")?, "UBSWCHZH80A");
+        assert_eq!(
+            IdentifierKind::PolicyNumber.normalize("  PN/123   456 ")?,
+            "PN/123 456"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_normalize_rejects_invalid() {
+        // One check digit off
+        assert!(IdentifierKind::Iban
+            .normalize("CH94 0076 2011 6238 5295 7")
+            .is_err());
+        assert!(IdentifierKind::Iban.normalize("12345678").is_err());
+        assert!(IdentifierKind::SortCode.normalize("12-34-5").is_err());
+        assert!(IdentifierKind::Bic.normalize("UBS").is_err());
+        // QQ isn't a country
+        assert!(IdentifierKind::Bic.normalize("UBSWQQZH").is_err());
+        assert!(IdentifierKind::AccountNumber
+            .normalize("1234/5678")
+            .is_err());
+        assert!(IdentifierKind::MembershipNumber.normalize("   ").is_err());
+    }
+}
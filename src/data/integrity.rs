@@ -0,0 +1,193 @@
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+
+use super::UserData;
+
+/// Checks that handles are unique and that everything accounts refer to exists, failing with every problem found
+/// rather than just the first, so they can all be fixed in one pass.
+///
+/// Provider and account handles must be unique, and no two accounts with the same provider may share both
+/// identifiers. Accounts without identifiers aren't compared, since there's nothing to tell them apart by.
+pub(crate) fn check_integrity(data: &UserData) -> Result<()> {
+    let mut problems = Vec::new();
+
+    let providers = data
+        .providers
+        .iter()
+        .map(|provider| provider.handle.as_str());
+    for (handle, count) in duplicates(providers) {
+        problems.push(format!(
+            "Provider handle {} is used by {} providers",
+            handle, count
+        ));
+    }
+
+    let accounts = data.accounts.iter().map(|account| account.handle.as_str());
+    for (handle, count) in duplicates(accounts) {
+        problems.push(format!(
+            "Account handle {} is used by {} accounts",
+            handle, count
+        ));
+    }
+
+    let years: Vec<String> = data
+        .filings
+        .iter()
+        .map(|filing| filing.year.to_string())
+        .collect();
+    for (year, count) in duplicates(years.iter().map(String::as_str)) {
+        problems.push(format!("Filing for {} is given {} times", year, count));
+    }
+
+    let mut by_identifiers: BTreeMap<(&str, &str, &str), Vec<&str>> = BTreeMap::new();
+    for account in &data.accounts {
+        if account.identifier1.is_none() && account.identifier2.is_none() {
+            continue;
+        }
+        by_identifiers
+            .entry((
+                account.provider.as_str(),
+                account.identifier1.as_deref().unwrap_or_default(),
+                account.identifier2.as_deref().unwrap_or_default(),
+            ))
+            .or_default()
+            .push(account.handle.as_str());
+    }
+    for ((provider, identifier1, identifier2), handles) in by_identifiers {
+        if handles.len() > 1 {
+            let identifiers: Vec<&str> = [identifier1, identifier2]
+                .into_iter()
+                .filter(|identifier| !identifier.is_empty())
+                .collect();
+            problems.push(format!(
+                "Accounts {} share provider {} and identifiers {}",
+                handles.join(", "),
+                provider,
+                identifiers.join(" / ")
+            ));
+        }
+    }
+
+    for account in &data.accounts {
+        if !data
+            .providers
+            .iter()
+            .any(|provider| provider.handle == account.provider)
+        {
+            problems.push(format!(
+                "Account {} is held with unknown provider {}",
+                account.handle, account.provider
+            ));
+        }
+        for owner in &account.owners {
+            if !data.filers.iter().any(|filer| &filer.handle == owner) {
+                problems.push(format!(
+                    "Account {} is owned by unknown filer {}",
+                    account.handle, owner
+                ));
+            }
+        }
+        if let Some(entity) = &account.entity {
+            if !data.entities.iter().any(|known| &known.handle == entity) {
+                problems.push(format!(
+                    "Account {} is held through unknown entity {}",
+                    account.handle, entity
+                ));
+            }
+        }
+        if let Some(profile) = &account.import_profile {
+            if !data
+                .import_profiles
+                .iter()
+                .any(|known| &known.name == profile)
+            {
+                problems.push(format!(
+                    "Account {} uses unknown import profile {}",
+                    account.handle, profile
+                ));
+            }
+        }
+    }
+
+    match problems.len() {
+        0 => Ok(()),
+        1 => bail!("{}", problems[0]),
+        _ => bail!(
+            "{} problems in data.yml:\n  {}",
+            problems.len(),
+            problems.join("\n  ")
+        ),
+    }
+}
+
+/// Values appearing more than once, with how many times, in order
+fn duplicates<'a>(values: impl Iterator<Item = &'a str>) -> Vec<(&'a str, usize)> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for value in values {
+        *counts.entry(value).or_default() += 1;
+    }
+    counts.into_iter().filter(|(_, count)| *count > 1).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Account, Address, Provider};
+
+    fn provider(handle: &str) -> Provider {
+        Provider {
+            name: "Example Bank".to_string(),
+            handle: handle.to_string(),
+            address: Address::from("1 Bank Street, London"),
+            ..Default::default()
+        }
+    }
+
+    fn account(handle: &str, provider: &str, identifier1: Option<&str>) -> Account {
+        Account {
+            handle: handle.to_string(),
+            provider: provider.to_string(),
+            currency_code: "GBP".to_string(),
+            identifier1: identifier1.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_valid_data() -> Result<()> {
+        let data = UserData {
+            providers: vec![provider("bank")],
+            accounts: vec![
+                account("checking", "bank", Some("12345678")),
+                account("savings", "bank", Some("87654321")),
+                // Accounts without identifiers aren't taken to be the same account
+                account("isa", "bank", None),
+                account("bonds", "bank", None),
+            ],
+            ..Default::default()
+        };
+        check_integrity(&data)
+    }
+
+    #[test]
+    fn test_lists_every_problem() {
+        let data = UserData {
+            providers: vec![provider("bank"), provider("bank")],
+            accounts: vec![
+                account("checking", "bank", Some("12345678")),
+                account("checking", "bank", Some("87654321")),
+                account("savings", "bank", Some("12345678")),
+                account("offshore", "other_bank", None),
+            ],
+            ..Default::default()
+        };
+
+        let message = check_integrity(&data).unwrap_err().to_string();
+        assert!(message.starts_with("4 problems in data.yml"));
+        assert!(message.contains("Provider handle bank is used by 2 providers"));
+        assert!(message.contains("Account handle checking is used by 2 accounts"));
+        assert!(message
+            .contains("Accounts checking, savings share provider bank and identifiers 12345678"));
+        assert!(message.contains("Account offshore is held with unknown provider other_bank"));
+    }
+}
@@ -0,0 +1,185 @@
+//! Infers what kind an account is from its identifiers and records, to catch accounts declared as the wrong kind and so
+//! filed as the wrong type of account in Part II of the form.
+
+use super::{Account, AccountKind, IdentifierKind};
+
+/// Something about an account that points to its kind
+#[derive(Debug, Clone, PartialEq)]
+pub struct KindEvidence {
+    pub kind: AccountKind,
+    /// What points to the kind, e.g. "identifier1 is an IBAN"
+    pub reason: String,
+}
+
+impl AccountKind {
+    /// Whether an account declared as this kind is consistent with evidence of the other. Wallets hold bank balances
+    /// under IBANs, so bank evidence fits them too.
+    pub fn fits(&self, inferred: AccountKind) -> bool {
+        *self == inferred || (*self == AccountKind::Wallet && inferred == AccountKind::Bank)
+    }
+}
+
+impl Account {
+    /// What the account's identifiers and records say about its kind, strongest first: surrender values or a policy
+    /// number for insurance, holdings named by ISIN for securities, other holdings for a crypto exchange, and an IBAN
+    /// or sort code for a bank
+    pub fn kind_evidence(&self) -> Vec<KindEvidence> {
+        let mut evidence = Vec::new();
+        let mut push = |kind, reason: String| evidence.push(KindEvidence { kind, reason });
+
+        if !self.surrender_values.is_empty() {
+            push(
+                AccountKind::Insurance,
+                "it has surrender values".to_string(),
+            );
+        }
+
+        let identifiers = [
+            (
+                "identifier1",
+                self.primary_identifier_kind(),
+                &self.identifier1,
+            ),
+            (
+                "identifier2",
+                self.secondary_identifier_kind(),
+                &self.identifier2,
+            ),
+        ];
+        let mut bank = Vec::new();
+        for (field, kind, identifier) in identifiers {
+            let Some(identifier) = identifier else {
+                continue;
+            };
+            match kind {
+                Some(IdentifierKind::PolicyNumber) => push(
+                    AccountKind::Insurance,
+                    format!("{} is a policy number", field),
+                ),
+                Some(IdentifierKind::Iban) => bank.push(format!("{} is an IBAN", field)),
+                Some(IdentifierKind::SortCode) => bank.push(format!("{} is a sort code", field)),
+                // An identifier of no declared kind that passes the IBAN checks is one
+                None if IdentifierKind::Iban.normalize(identifier).is_ok() => {
+                    bank.push(format!("{} is an IBAN", field))
+                }
+                _ => {}
+            }
+        }
+
+        let isin = self.holdings.iter().find(|holding| is_isin(&holding.asset));
+        if let Some(holding) = isin {
+            push(
+                AccountKind::Securities,
+                format!("it holds securities by ISIN, e.g. {}", holding.asset),
+            );
+        } else if let Some(holding) = self.holdings.first() {
+            push(
+                AccountKind::CryptoExchange,
+                format!("it holds assets by ticker, e.g. {}", holding.asset),
+            );
+        }
+
+        for reason in bank {
+            push(AccountKind::Bank, reason);
+        }
+        evidence
+    }
+
+    /// The strongest evidence against the account's declared kind, if there is any
+    pub fn kind_conflict(&self) -> Option<KindEvidence> {
+        self.kind_evidence()
+            .into_iter()
+            .find(|evidence| !self.kind.fits(evidence.kind))
+    }
+}
+
+/// Whether the value is an International Securities Identification Number: a country code, nine letters or digits,
+/// and a Luhn check digit over the whole, with letters counted as 10 to 35
+pub fn is_isin(value: &str) -> bool {
+    let value = value.trim();
+    let valid_shape = value.len() == 12
+        && value[..2].chars().all(|c| c.is_ascii_uppercase())
+        && value[2..11]
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        && value[11..].chars().all(|c| c.is_ascii_digit());
+    if !valid_shape {
+        return false;
+    }
+
+    let digits: Vec<u32> = value
+        .chars()
+        .filter_map(|c| c.to_digit(36))
+        .flat_map(|digit| {
+            if digit < 10 {
+                vec![digit]
+            } else {
+                vec![digit / 10, digit % 10]
+            }
+        })
+        .collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, digit)| {
+            if index % 2 == 1 {
+                let doubled = digit * 2;
+                doubled / 10 + doubled % 10
+            } else {
+                *digit
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::HoldingRecord;
+    use rust_decimal_macros::dec;
+
+    fn holding(asset: &str) -> HoldingRecord {
+        HoldingRecord {
+            date: "2023-06-30".parse().unwrap(),
+            asset: asset.to_string(),
+            quantity: dec!(10),
+        }
+    }
+
+    #[test]
+    fn test_is_isin() {
+        assert!(is_isin("US0378331005"));
+        assert!(is_isin("IE00B3RBWM25"));
+        assert!(!is_isin("US0378331006"));
+        assert!(!is_isin("BTC"));
+    }
+
+    #[test]
+    fn test_kind_conflict() {
+        let mut account = Account {
+            identifier1: Some("GB29NWBK60161331926819".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(account.kind_conflict(), None);
+
+        // ISIN holdings in an account declared as a bank account
+        account.holdings = vec![holding("IE00B3RBWM25")];
+        let conflict = account.kind_conflict().unwrap();
+        assert_eq!(conflict.kind, AccountKind::Securities);
+        assert!(conflict.reason.contains("IE00B3RBWM25"));
+
+        // The IBAN is still bank evidence against a securities account
+        account.kind = AccountKind::Securities;
+        assert_eq!(
+            account.kind_conflict().map(|conflict| conflict.reason),
+            Some("identifier1 is an IBAN".to_string())
+        );
+
+        // Wallets have IBANs too
+        account.kind = AccountKind::Wallet;
+        account.holdings.clear();
+        assert_eq!(account.kind_conflict(), None);
+    }
+}
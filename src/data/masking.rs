@@ -0,0 +1,224 @@
+//! Masking of account identifiers and TINs when data is serialized, e.g. in a records bundle to be shared. Masking is
+//! a serialization context set by the caller with `with_masking`, so the types serialize as usual everywhere else,
+//! including for input hashes.
+
+use anyhow::{anyhow, bail, Result};
+use serde::Serializer;
+use std::cell::Cell;
+use std::fmt;
+use std::str::FromStr;
+
+thread_local! {
+    static MASKING: Cell<Masking> = Cell::new(Masking::default());
+}
+
+/// How much of a value to hide
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MaskPolicy {
+    /// Written as is
+    #[default]
+    None,
+    /// Every letter and digit but the last four replaced with `*`
+    Last4,
+    /// Every letter and digit replaced with `*`
+    Full,
+}
+
+impl MaskPolicy {
+    /// Masks the value's letters and digits, keeping spaces and punctuation so its shape stays recognizable
+    pub fn apply(&self, value: &str) -> String {
+        let keep = match self {
+            MaskPolicy::None => return value.to_string(),
+            MaskPolicy::Last4 => 4,
+            MaskPolicy::Full => 0,
+        };
+        let masked = value
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .count()
+            .saturating_sub(keep);
+        let mut seen = 0;
+        value
+            .chars()
+            .map(|c| {
+                if !c.is_alphanumeric() {
+                    return c;
+                }
+                seen += 1;
+                if seen <= masked {
+                    '*'
+                } else {
+                    c
+                }
+            })
+            .collect()
+    }
+}
+
+impl FromStr for MaskPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "none" => Ok(MaskPolicy::None),
+            "last4" => Ok(MaskPolicy::Last4),
+            "full" => Ok(MaskPolicy::Full),
+            _ => bail!(
+                "Unknown mask policy {:?}, expected `full`, `last4` or `none`",
+                value
+            ),
+        }
+    }
+}
+
+impl fmt::Display for MaskPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MaskPolicy::None => "none",
+            MaskPolicy::Last4 => "last4",
+            MaskPolicy::Full => "full",
+        })
+    }
+}
+
+/// The policy for each maskable field
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Masking {
+    /// Accounts' primary identifiers
+    pub identifier1: MaskPolicy,
+    /// Accounts' secondary identifiers
+    pub identifier2: MaskPolicy,
+    /// Filers' and entities' taxpayer identification numbers
+    pub tin: MaskPolicy,
+}
+
+impl Masking {
+    /// The same policy for every field
+    pub fn all(policy: MaskPolicy) -> Self {
+        Self {
+            identifier1: policy,
+            identifier2: policy,
+            tin: policy,
+        }
+    }
+
+    /// Applies a rule like `identifier1=last4`, or `all=full` for every field
+    pub fn apply_rule(&mut self, rule: &str) -> Result<()> {
+        let (field, policy) = rule.split_once('=').ok_or_else(|| {
+            anyhow!(
+                "Expected FIELD=POLICY, e.g. identifier1=last4, not {:?}",
+                rule
+            )
+        })?;
+        let policy = policy.parse()?;
+        match field {
+            "identifier1" => self.identifier1 = policy,
+            "identifier2" => self.identifier2 = policy,
+            "tin" => self.tin = policy,
+            "all" => *self = Masking::all(policy),
+            _ => bail!(
+                "Unknown field {:?} to mask, expected `identifier1`, `identifier2`, `tin` or `all`",
+                field
+            ),
+        }
+        Ok(())
+    }
+}
+
+/// Runs `f` with the masking applied to everything serialized on this thread, restoring the previous masking after
+pub fn with_masking<T>(masking: Masking, f: impl FnOnce() -> T) -> T {
+    struct Restore(Masking);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            MASKING.with(|current| current.set(self.0));
+        }
+    }
+
+    let _restore = Restore(MASKING.with(|current| current.replace(masking)));
+    f()
+}
+
+fn serialize_masked<S: Serializer>(
+    value: &Option<String>,
+    policy: MaskPolicy,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serializer.serialize_some(&policy.apply(value)),
+        None => serializer.serialize_none(),
+    }
+}
+
+pub(crate) fn identifier1<S: Serializer>(
+    value: &Option<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serialize_masked(value, MASKING.with(Cell::get).identifier1, serializer)
+}
+
+pub(crate) fn identifier2<S: Serializer>(
+    value: &Option<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serialize_masked(value, MASKING.with(Cell::get).identifier2, serializer)
+}
+
+pub(crate) fn tin<S: Serializer>(value: &Option<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    serialize_masked(value, MASKING.with(Cell::get).tin, serializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Account, Filer};
+
+    #[test]
+    fn test_apply() {
+        let iban = "DE89 3704 0044 0532 0130 00";
+        assert_eq!(MaskPolicy::None.apply(iban), iban);
+        assert_eq!(MaskPolicy::Last4.apply(iban), "**** **** **** **** **30 00");
+        assert_eq!(MaskPolicy::Full.apply("123-45-6789"), "***-**-****");
+        // Too short to hide anything but the last four
+        assert_eq!(MaskPolicy::Last4.apply("123"), "123");
+    }
+
+    #[test]
+    fn test_with_masking() -> Result<()> {
+        let account = Account {
+            handle: "girokonto".to_string(),
+            identifier1: Some("DE89370400440532013000".to_string()),
+            identifier2: Some("COBADEFFXXX".to_string()),
+            ..Default::default()
+        };
+        let filer = Filer {
+            name: "Alex Example".to_string(),
+            handle: "alex".to_string(),
+            tin: Some("123-45-6789".to_string()),
+            address: None,
+        };
+
+        let mut masking = Masking::default();
+        masking.apply_rule("identifier1=last4")?;
+        masking.apply_rule("tin=full")?;
+        let (account_yaml, filer_yaml) = with_masking(masking, || -> Result<_> {
+            Ok((
+                serde_yaml::to_string(&account)?,
+                serde_yaml::to_string(&filer)?,
+            ))
+        })?;
+        assert!(account_yaml.contains("identifier1: '******************3000'"));
+        assert!(account_yaml.contains("identifier2: COBADEFFXXX"));
+        assert!(filer_yaml.contains("tin: '***-**-****'"));
+
+        // Unmasked again outside the context
+        assert!(serde_yaml::to_string(&account)?.contains("identifier1: DE89370400440532013000"));
+
+        assert!(masking.apply_rule("iban=full").is_err());
+        assert!(masking.apply_rule("tin=partial").is_err());
+        masking.apply_rule("all=none")?;
+        assert_eq!(masking, Masking::default());
+
+        Ok(())
+    }
+}
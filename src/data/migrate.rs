@@ -0,0 +1,404 @@
+use anyhow::{bail, Context, Result};
+use serde_yaml::{Mapping, Value};
+use std::path::{Path, PathBuf};
+
+use super::address;
+use super::IdentifierKind;
+
+/// Version of the `data.yml` layout this build writes. Files without a `schema_version` are the version their layout
+/// matches; see [`schema_version`].
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// One step upgrading `data.yml` from the version before `to`
+struct Migration {
+    to: u32,
+    description: &'static str,
+    /// Rewrites the document in place, returning a line for each change made
+    apply: fn(&mut Mapping) -> Vec<String>,
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    to: 2,
+    description: "structured provider addresses and identifier kinds",
+    apply: structure_addresses_and_identifiers,
+}];
+
+/// What migrating a data directory changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationResult {
+    pub from: u32,
+    pub to: u32,
+    /// Each migration applied, with the changes it made
+    pub steps: Vec<MigrationStep>,
+    /// Copy of `data.yml` as it was before migrating, if it was rewritten
+    pub backup: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationStep {
+    pub to: u32,
+    pub description: String,
+    pub changes: Vec<String>,
+}
+
+/// The `schema_version` a parsed `data.yml` declares. A file without one is the version before the first migration
+/// that would change it, or the current version when none would, so hand-written files in the current layout don't
+/// need to declare it.
+pub fn schema_version(value: &Value) -> Result<u32> {
+    match value.get("schema_version") {
+        None | Some(Value::Null) => Ok(layout_version(value)),
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .with_context(|| format!("schema_version {:?} is not a whole number", version)),
+    }
+}
+
+fn layout_version(value: &Value) -> u32 {
+    let Some(document) = value.as_mapping() else {
+        return SCHEMA_VERSION;
+    };
+    let mut upgraded = document.clone();
+    for migration in MIGRATIONS {
+        (migration.apply)(&mut upgraded);
+        if upgraded != *document {
+            return migration.to - 1;
+        }
+    }
+    SCHEMA_VERSION
+}
+
+/// Upgrades the data directory's `data.yml` to the current schema version in place, keeping the original as
+/// `data.yml.v<version>.bak`. With `dry_run`, only reports what would change.
+///
+/// The file is rewritten from its parsed form, so comments and formatting aren't kept; the backup still has them.
+pub fn migrate(base_path: &Path, dry_run: bool) -> Result<MigrationResult> {
+    let path = base_path.join("data.yml");
+    let contents =
+        std::fs::read_to_string(&path).with_context(|| format!("Could not read {:?}", path))?;
+    let mut value: Value =
+        serde_yaml::from_str(&contents).with_context(|| format!("Invalid YAML in {:?}", path))?;
+    let from = schema_version(&value)?;
+    if from > SCHEMA_VERSION {
+        bail!(
+            "data.yml is schema version {}, but this version of fbar_prep only understands up to {}; upgrade fbar_prep",
+            from,
+            SCHEMA_VERSION
+        );
+    }
+
+    let Some(document) = value.as_mapping_mut() else {
+        bail!("data.yml is not a mapping");
+    };
+    let mut result = MigrationResult {
+        from,
+        to: from,
+        steps: Vec::new(),
+        backup: None,
+    };
+    for migration in MIGRATIONS.iter().filter(|migration| migration.to > from) {
+        let changes = (migration.apply)(document);
+        tracing::info!(
+            to = migration.to,
+            changes = changes.len(),
+            "Applied migration"
+        );
+        result.steps.push(MigrationStep {
+            to: migration.to,
+            description: migration.description.to_string(),
+            changes,
+        });
+        result.to = migration.to;
+    }
+
+    if result.steps.is_empty() || dry_run {
+        return Ok(result);
+    }
+
+    // Keep the version first, where it's easy to see
+    let mut migrated = Mapping::new();
+    migrated.insert("schema_version".into(), result.to.into());
+    for (key, value) in std::mem::take(document) {
+        if key.as_str() != Some("schema_version") {
+            migrated.insert(key, value);
+        }
+    }
+
+    let backup = base_path.join(format!("data.yml.v{}.bak", from));
+    std::fs::copy(&path, &backup).with_context(|| format!("Could not back up {:?}", path))?;
+    std::fs::write(&path, serde_yaml::to_string(&migrated)?)
+        .with_context(|| format!("Could not write {:?}", path))?;
+    result.backup = Some(backup);
+
+    Ok(result)
+}
+
+/// Version 2: splits free-form provider addresses into their parts where they can be read reliably, and replaces
+/// free-text identifier names with identifier kinds where they name one
+fn structure_addresses_and_identifiers(document: &mut Mapping) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    if let Some(providers) = document
+        .get_mut("providers")
+        .and_then(Value::as_sequence_mut)
+    {
+        for provider in providers.iter_mut().filter_map(Value::as_mapping_mut) {
+            let handle = provider
+                .get("handle")
+                .and_then(Value::as_str)
+                .unwrap_or("?")
+                .to_string();
+            let Some(freeform) = provider.get("address").and_then(Value::as_str) else {
+                continue;
+            };
+            let country = provider.get("country").and_then(Value::as_str);
+            match structure_address(freeform, country) {
+                Some(structured) => {
+                    changes.push(format!(
+                        "provider {}: split address {:?} into its parts",
+                        handle, freeform
+                    ));
+                    provider.insert("address".into(), structured);
+                    provider.remove("country");
+                }
+                None => changes.push(format!(
+                    "provider {}: left address {:?} as free-form; split it into street, city and country by hand",
+                    handle, freeform
+                )),
+            }
+        }
+    }
+
+    if let Some(accounts) = document
+        .get_mut("accounts")
+        .and_then(Value::as_sequence_mut)
+    {
+        for account in accounts.iter_mut().filter_map(Value::as_mapping_mut) {
+            let handle = account
+                .get("handle")
+                .and_then(Value::as_str)
+                .unwrap_or("?")
+                .to_string();
+            for n in [1, 2] {
+                let kind_key = format!("identifier{}_kind", n);
+                let name_key = format!("identifier{}_name", n);
+                let has_kind = account
+                    .get(kind_key.as_str())
+                    .is_some_and(|kind| !kind.is_null());
+                let Some(name) = account.get(name_key.as_str()).and_then(Value::as_str) else {
+                    continue;
+                };
+                if has_kind {
+                    continue;
+                }
+                match IdentifierKind::from_name(name) {
+                    Some(kind) => {
+                        changes.push(format!(
+                            "account {}: {} {:?} is now {} {}",
+                            handle, name_key, name, kind_key, kind
+                        ));
+                        account.insert(
+                            kind_key.into(),
+                            serde_yaml::to_value(kind).unwrap_or(Value::Null),
+                        );
+                        account.remove(name_key.as_str());
+                    }
+                    None => changes.push(format!(
+                        "account {}: kept {} {:?}, which doesn't name an identifier kind",
+                        handle, name_key, name
+                    )),
+                }
+            }
+        }
+    }
+
+    changes
+}
+
+/// Reads a free-form address written as `street, [postal code] city[, country]`. The country comes from the last
+/// part when it's an ISO code or country name, or else the provider's `country`. Anything that can't be read without
+/// guessing is left alone.
+fn structure_address(freeform: &str, country: Option<&str>) -> Option<Value> {
+    let mut parts: Vec<&str> = freeform
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect();
+
+    let given = country.and_then(|code| address::parse_country_code(code).ok());
+    let last = parts
+        .last()
+        .and_then(|part| address::parse_country_code(part).ok())
+        .or_else(|| {
+            parts
+                .last()
+                .and_then(|part| isocountry::CountryCode::iter().find(|c| c.name() == *part))
+                .copied()
+        });
+    let country = match (given, last) {
+        (Some(given), Some(last)) if given != last => return None,
+        (_, Some(last)) => {
+            parts.pop();
+            last
+        }
+        // Without a recognisable country at the end, only `street, city` can be read without guessing
+        (Some(given), None) if parts.len() == 2 => given,
+        (_, None) => return None,
+    };
+    if parts.len() < 2 {
+        return None;
+    }
+
+    let city_part = parts.pop()?;
+    let (postal_code, city) = match city_part.split_once(' ') {
+        Some((code, city)) if code.chars().any(|c| c.is_ascii_digit()) => {
+            (Some(code.to_string()), city.trim().to_string())
+        }
+        _ => (None, city_part.to_string()),
+    };
+
+    let mut structured = Mapping::new();
+    structured.insert("street".into(), parts.join(", ").into());
+    structured.insert("city".into(), city.into());
+    if let Some(postal_code) = postal_code {
+        structured.insert("postal_code".into(), postal_code.into());
+    }
+    structured.insert("country".into(), country.alpha2().into());
+    Some(Value::Mapping(structured))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Address, UserData};
+    use crate::parsing::ParseContext;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const LEGACY: &str = r#"
+providers:
+  - name: "Example Bank"
+    handle: "example_bank"
+    address: "123 Bank Street, 8001 Zurich, CH"
+  - name: "Other Bank"
+    handle: "other_bank"
+    address: "1 High Street, London"
+    country: "GB"
+  - name: "Vague Bank"
+    handle: "vague_bank"
+    address: "Somewhere in the Alps"
+accounts:
+  - handle: "savings"
+    provider: "example_bank"
+    currency_code: "CHF"
+    identifier1_name: "IBAN"
+    identifier1: "CH93 0076 2011 6238 5295 7"
+    identifier2_name: "Customer reference"
+    identifier2: "ABC-1"
+"#;
+
+    #[test]
+    fn test_migrate() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        fs::write(temp_dir.path().join("data.yml"), LEGACY)?;
+
+        let preview = migrate(temp_dir.path(), true)?;
+        assert_eq!((preview.from, preview.to), (1, 2));
+        assert!(preview.backup.is_none());
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("data.yml"))?,
+            LEGACY
+        );
+
+        let result = migrate(temp_dir.path(), false)?;
+        assert_eq!(result.steps.len(), 1);
+        assert_eq!(result.steps[0].changes.len(), 5);
+        let backup = result.backup.unwrap();
+        assert_eq!(fs::read_to_string(backup)?, LEGACY);
+
+        let data = UserData::load_from_path(temp_dir.path())?;
+        assert_eq!(data.schema_version, Some(SCHEMA_VERSION));
+        let Address::Structured(address) = &data.providers[0].address else {
+            panic!("expected a structured address");
+        };
+        assert_eq!(address.street, "123 Bank Street");
+        assert_eq!(address.city, "Zurich");
+        assert_eq!(address.postal_code.as_deref(), Some("8001"));
+        assert_eq!(address.country, "CH");
+        assert_eq!(data.providers[1].address.country(), Some("GB"));
+        assert!(data.providers[1].country.is_none());
+        assert_eq!(
+            data.providers[2].address,
+            Address::from("Somewhere in the Alps")
+        );
+        assert_eq!(
+            data.accounts[0].identifier1_kind,
+            Some(IdentifierKind::Iban)
+        );
+        assert!(data.accounts[0].identifier1_name.is_none());
+        assert_eq!(
+            data.accounts[0].identifier2_name.as_deref(),
+            Some("Customer reference")
+        );
+
+        // Already current, so nothing changes
+        let again = migrate(temp_dir.path(), false)?;
+        assert!(again.steps.is_empty());
+        assert!(again.backup.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unversioned_file_in_current_layout() -> Result<()> {
+        let current = r#"
+providers:
+  - name: "Example Bank"
+    handle: "example_bank"
+    address:
+      street: "123 Bank Street"
+      city: "Zurich"
+      country: "CH"
+accounts:
+  - handle: "savings"
+    provider: "example_bank"
+    currency_code: "CHF"
+    identifier1_kind: iban
+    identifier1: "CH93 0076 2011 6238 5295 7"
+"#;
+        assert_eq!(
+            schema_version(&serde_yaml::from_str(current)?)?,
+            SCHEMA_VERSION
+        );
+        assert_eq!(schema_version(&serde_yaml::from_str(LEGACY)?)?, 1);
+
+        let temp_dir = TempDir::new()?;
+        fs::write(temp_dir.path().join("data.yml"), current)?;
+        let context = ParseContext::default();
+        UserData::load_with_context(temp_dir.path(), &context)?;
+        assert!(context.warnings().is_empty());
+        let result = migrate(temp_dir.path(), false)?;
+        assert!(result.steps.is_empty());
+
+        fs::write(temp_dir.path().join("data.yml"), LEGACY)?;
+        let context = ParseContext::default();
+        UserData::load_with_context(temp_dir.path(), &context)?;
+        assert!(context
+            .warnings()
+            .iter()
+            .any(|warning| warning.contains("run `fbar_prep migrate`")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_newer_schema_is_refused() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        fs::write(
+            temp_dir.path().join("data.yml"),
+            "schema_version: 99\nproviders: []\n",
+        )?;
+        assert!(migrate(temp_dir.path(), false).is_err());
+        assert!(UserData::load_from_path(temp_dir.path()).is_err());
+        Ok(())
+    }
+}
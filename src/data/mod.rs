@@ -0,0 +1,1540 @@
+pub mod address;
+pub mod entity;
+pub mod filer;
+pub mod filing;
+pub mod identifier;
+mod integrity;
+pub mod kind;
+pub mod masking;
+pub mod migrate;
+pub mod residency;
+pub mod signature_authority;
+pub use self::address::{Address, StructuredAddress};
+pub use self::entity::Entity;
+pub use self::filer::Filer;
+pub use self::filing::{Filing, LateFilingReason};
+pub use self::identifier::IdentifierKind;
+pub use self::kind::KindEvidence;
+pub use self::masking::{MaskPolicy, Masking};
+pub use self::residency::NonresidentPeriod;
+pub use self::signature_authority::SignatureAuthority;
+
+use crate::crypto::{PriceSeries, PriceStore, CRYPTO_VALUATION};
+use crate::diagnostics::{codes, Diagnostic, Diagnostics};
+use crate::facts::{Facts, RateSet};
+use crate::fincen::{SchemaVersion, Transliteration};
+use crate::money;
+use crate::parsing::ParseContext;
+use crate::report::valuation::{BALANCE_VALUATION, SURRENDER_VALUATION, WALLET_VALUATION};
+use crate::report::{ChangeCheckConfig, InterpolationPolicy, ManyAccountsMode, MaximumPolicy};
+use crate::report_context::RateCheckConfig;
+use crate::statements::sheets::GoogleSheets;
+use crate::statements::{ImportProfile, StatementImport, StatementStore};
+use anyhow::{Context, Result};
+use chrono::{Datelike, NaiveDate};
+use isocountry::CountryCode;
+use rust_decimal::Decimal;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// ISO 4217's code for no currency, given to an account whose currency isn't known yet, e.g. one read from a filed
+/// FBAR. The account can't be reported until `currency_code` is set to the currency it's held in.
+pub const UNKNOWN_CURRENCY_CODE: &str = "XXX";
+
+/// Contents of a data directory's `data.yml`
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+pub struct UserData {
+    /// Version of the layout the file is written in, upgraded by `fbar_prep migrate`. Files without one are version 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_version: Option<u32>,
+    /// The people whose accounts these are, the one reports are prepared for first. Only needed when accounts are
+    /// owned by more than one filer.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filers: Vec<Filer>,
+    pub providers: Vec<Provider>,
+    #[serde(default)]
+    pub accounts: Vec<Account>,
+    /// Companies, partnerships and trusts that hold accounts on the filer's behalf
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entities: Vec<Entity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fact_extensions: Option<Facts>,
+    /// Order in which rate sources (`user`, `irs`, or a named rate set) are consulted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_precedence: Option<Vec<String>>,
+    /// Rate sources pinned to currencies by code, e.g. `ILS: broker`. A pinned currency is converted at its source's
+    /// rates alone, whatever `rate_precedence` says.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rate_pins: BTreeMap<String, String>,
+    /// Thresholds for flagging implausible user-provided rates
+    #[serde(default)]
+    pub rate_checks: RateCheckConfig,
+    /// Thresholds for flagging accounts whose maximum moved implausibly from the previous year's
+    #[serde(default, skip_serializing_if = "ChangeCheckConfig::is_default")]
+    pub change_checks: ChangeCheckConfig,
+    /// Whether to file only the number of accounts when there are 25 or more
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub many_accounts: Option<ManyAccountsMode>,
+    /// How to estimate values at the start and end of the year from sparse records; accounts can override it
+    #[serde(default)]
+    pub interpolation: InterpolationPolicy,
+    /// Which maximum to file for accounts whose currency has daily USD rates in the `prices` directory
+    #[serde(default, skip_serializing_if = "MaximumPolicy::is_default")]
+    pub maximum_policy: MaximumPolicy,
+    /// How to normalize amounts as statements are imported, for banks that export minor units or extra precision
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub import_profiles: Vec<ImportProfile>,
+    /// Shared Google Sheets that accounts' balances are kept in, pulled with `pull-sheets`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub google_sheets: Option<GoogleSheets>,
+    /// Date the filer became a US person, e.g. a green card's start date, for their first FBAR. Values from before
+    /// it are left out of that year's maxima.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub us_person_since: Option<NaiveDate>,
+    /// Times the filer was not a US person, for dual-status years. Reports note them on the accounts they affect but
+    /// leave the maxima as they are.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nonresident_periods: Vec<NonresidentPeriod>,
+    /// When each year's FBAR was filed, and why, if it was late
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filings: Vec<Filing>,
+    /// Version of the BSA E-Filing FBAR schema to write and check the XML against; the current one when not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fincen_schema_version: Option<SchemaVersion>,
+    /// How names and addresses are transliterated to the characters BSA E-Filing accepts in the XML
+    #[serde(default, skip_serializing_if = "Transliteration::is_default")]
+    pub transliteration: Transliteration,
+    /// Named rate sets loaded from the `rates` directory
+    #[serde(skip)]
+    pub rate_sets: Vec<RateSet>,
+    /// Crypto asset prices loaded from the `prices` directory
+    #[serde(skip)]
+    pub price_series: Vec<PriceSeries>,
+    /// Archived accounts moved out of `accounts` by `set_aside_archived`, with the positions they had in it
+    #[serde(skip)]
+    pub archived_accounts: Vec<(usize, Account)>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+pub struct Provider {
+    pub name: String,
+    pub handle: String,
+    pub address: Address,
+    /// ISO 3166-1 country code, for when the address doesn't give one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    /// Date the provider was founded, before which none of its accounts can have been opened
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub founded: Option<NaiveDate>,
+    /// Legal name of the US institution the provider is a foreign branch of, e.g. `Citibank, N.A.` for its London
+    /// branch. Accounts at foreign branches of US institutions are reportable, and are filed under the institution's
+    /// name with the branch's own address and country.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub foreign_branch_of: Option<String>,
+    /// SWIFT/BIC code of the institution, e.g. `UBSWCHZHXXX`. Tells accounts with the same number at different
+    /// institutions apart when matching OFX statements to accounts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bic: Option<String>,
+}
+
+impl Provider {
+    /// The name the institution is filed under: the provider's, or for a foreign branch the US institution's followed
+    /// by the branch's in parentheses, e.g. `Citibank, N.A. (London)`, unless the branch's name already includes it
+    pub fn institution_name(&self) -> String {
+        match &self.foreign_branch_of {
+            Some(institution)
+                if !self
+                    .name
+                    .to_lowercase()
+                    .contains(&institution.to_lowercase()) =>
+            {
+                format!("{} ({})", institution, self.name)
+            }
+            _ => self.name.clone(),
+        }
+    }
+
+    /// The provider's country, from the explicit `country` field or else the structured address. Fails if the code
+    /// given isn't a valid ISO 3166-1 code.
+    pub fn country_code(&self) -> Result<Option<CountryCode>> {
+        self.country
+            .as_deref()
+            .or_else(|| self.address.country())
+            .map(address::parse_country_code)
+            .transpose()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountKind {
+    /// A deposit account, valued by its balance records
+    #[default]
+    Bank,
+    /// A foreign crypto exchange or custodial account, valued by pricing its holdings
+    CryptoExchange,
+    /// A multi-currency wallet (e.g. Wise or Revolut) holding balances in several currencies at once, valued by
+    /// summing its sub-balances in USD
+    Wallet,
+    /// A foreign life insurance policy or annuity with a cash surrender value, valued by the surrender values given
+    /// on its statements
+    Insurance,
+    /// A brokerage or custody account holding securities, valued by the total value on its statements
+    Securities,
+}
+
+impl std::fmt::Display for AccountKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AccountKind::Bank => "bank",
+            AccountKind::CryptoExchange => "crypto_exchange",
+            AccountKind::Wallet => "wallet",
+            AccountKind::Insurance => "insurance",
+            AccountKind::Securities => "securities",
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+pub struct Account {
+    pub handle: String,
+    /// Handle of the provider holding the account
+    pub provider: String,
+    /// Currency the account is valued in. For crypto exchange accounts, the currency its asset prices are quoted in;
+    /// for wallets, the currency their total is reported in.
+    pub currency_code: String,
+    #[serde(default)]
+    pub kind: AccountKind,
+    /// Kind of the primary identifier, which decides how it's normalized and checked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identifier1_kind: Option<IdentifierKind>,
+    /// Free-text name of the primary identifier, e.g. "Account number" or "IBAN". Superseded by
+    /// `identifier1_kind`, but still used to infer the kind when it names one.
+    #[serde(default)]
+    pub identifier1_name: Option<String>,
+    #[serde(default, serialize_with = "masking::identifier1")]
+    pub identifier1: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identifier2_kind: Option<IdentifierKind>,
+    /// Free-text name of a secondary identifier, e.g. "Sort code"
+    #[serde(default)]
+    pub identifier2_name: Option<String>,
+    #[serde(default, serialize_with = "masking::identifier2")]
+    pub identifier2: Option<String>,
+    #[serde(default)]
+    pub opening_date: Option<NaiveDate>,
+    /// Date the account was closed, if it no longer exists
+    #[serde(default)]
+    pub closing_date: Option<NaiveDate>,
+    /// Leaves the account out of the FBAR, e.g. a US-based account or one covered by the correspondent account
+    /// exception. Requires an `exclusion_reason`.
+    #[serde(default)]
+    pub exclude_from_fbar: bool,
+    #[serde(default)]
+    pub exclusion_reason: Option<String>,
+    /// Set on an account that's no longer relevant, e.g. one closed years ago. Archived accounts are left out of
+    /// listings, summaries and checks unless `--include-archived` is given. Requires a `closing_date`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub archived: bool,
+    #[serde(default)]
+    pub balances: Vec<BalanceRecord>,
+    /// Quantities of crypto assets held, for crypto exchange accounts
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub holdings: Vec<HoldingRecord>,
+    /// Cash surrender values from the policy's statements, for insurance accounts
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub surrender_values: Vec<SurrenderValueRecord>,
+    /// Name of the valuation that computes the account's values; defaults to the one for the account's kind
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valuation: Option<String>,
+    /// Free-form labels for grouping accounts, e.g. `household` or `kids`; reports can be limited to a tag
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Free-form notes about the account, e.g. its purpose, shown in the report and review
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Handle of the entity the account is held through, when it isn't held directly
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity: Option<String>,
+    /// Set when the filer can sign for the account but has no financial interest in it, e.g. an employer's account;
+    /// it's then reported in Part IV under its owner
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature_authority: Option<SignatureAuthority>,
+    /// Handles of the filers owning the account, e.g. both spouses for a joint account. Defaults to the first filer.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub owners: Vec<String>,
+    /// Interpolation policy for this account, overriding the data directory's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interpolation: Option<InterpolationPolicy>,
+    /// Name of the import profile statements for this account are imported with, unless `import --profile` is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub import_profile: Option<String>,
+    /// Statements imported into the data directory's statement store for this account
+    #[serde(skip)]
+    pub imports: Vec<StatementImport>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default, PartialEq)]
+pub struct BalanceRecord {
+    pub date: NaiveDate,
+    /// Balance in the account's currency, or in `currency` if given
+    pub amount: Decimal,
+    /// Currency of the balance when the statement isn't in the account's currency, e.g. a multi-currency wallet
+    /// reporting a EUR account in GBP. Converted to the account's currency via USD at the year's rates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    /// Set on a balance entered by hand rather than taken from a statement, e.g. a correction confirmed with the bank
+    /// by phone. Requires a `note` saying where it came from.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub manual: bool,
+    /// Where the balance came from or why it was entered, e.g. `phone confirmation from bank`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// The balance's USD equivalent as stated by the provider, e.g. on a statement giving both. Filed in place of the
+    /// converted maximum when this record holds the account's maximum.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usd_value: Option<Decimal>,
+    /// Units of the balance's currency per USD, as stated by the provider. An alternative to `usd_value`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usd_rate: Option<Decimal>,
+}
+
+impl BalanceRecord {
+    /// The balance in USD from the record's own `usd_value` or `usd_rate`, if it gives one
+    pub fn usd_equivalent(&self) -> Result<Option<Decimal>> {
+        match (self.usd_value, self.usd_rate) {
+            (Some(_), Some(_)) => {
+                anyhow::bail!(
+                    "Balance record on {} gives both a usd_value and a usd_rate; use one",
+                    self.date
+                )
+            }
+            (Some(value), None) if value < Decimal::ZERO => {
+                anyhow::bail!(
+                    "Balance record on {} has a negative usd_value {}",
+                    self.date,
+                    value
+                )
+            }
+            (Some(value), None) => Ok(Some(value)),
+            (None, Some(rate)) => match self
+                .amount
+                .checked_div(rate)
+                .filter(|_| rate > Decimal::ZERO)
+            {
+                Some(value) => Ok(Some(value)),
+                None => anyhow::bail!(
+                    "Balance record on {} has an invalid usd_rate {}",
+                    self.date,
+                    rate
+                ),
+            },
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+/// Quantity of a crypto asset held in an account on a date
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct HoldingRecord {
+    pub date: NaiveDate,
+    /// Asset ticker, e.g. BTC
+    pub asset: String,
+    pub quantity: Decimal,
+}
+
+/// Cash surrender value of an insurance policy or annuity on a date, as given on its annual statement
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct SurrenderValueRecord {
+    pub date: NaiveDate,
+    /// What the policy would pay out if surrendered on the date, in the account's currency
+    pub surrender_value: Decimal,
+}
+
+impl Account {
+    /// Whether the account's currency is still to be set, see `UNKNOWN_CURRENCY_CODE`
+    pub fn currency_unknown(&self) -> bool {
+        self.currency_code
+            .eq_ignore_ascii_case(UNKNOWN_CURRENCY_CODE)
+    }
+
+    /// Name of the valuation used for the account: the one it names, or else its kind's default
+    pub fn valuation_name(&self) -> &str {
+        match (&self.valuation, self.kind) {
+            (Some(valuation), _) => valuation,
+            (None, AccountKind::Bank | AccountKind::Securities) => BALANCE_VALUATION,
+            (None, AccountKind::CryptoExchange) => CRYPTO_VALUATION,
+            (None, AccountKind::Wallet) => WALLET_VALUATION,
+            (None, AccountKind::Insurance) => SURRENDER_VALUATION,
+        }
+    }
+
+    /// Dates of every record the account has: balances, imported statements, holdings and surrender values
+    pub fn record_dates(&self) -> impl Iterator<Item = NaiveDate> + '_ {
+        self.balances
+            .iter()
+            .chain(
+                self.imports
+                    .iter()
+                    .flat_map(|import| import.balances.iter()),
+            )
+            .map(|record| record.date)
+            .chain(self.holdings.iter().map(|record| record.date))
+            .chain(self.surrender_values.iter().map(|record| record.date))
+    }
+
+    /// Whether the account has the tag, ignoring case
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|own| own.eq_ignore_ascii_case(tag))
+    }
+
+    /// Kind of the primary identifier, given explicitly or inferred from its free-text name
+    pub fn primary_identifier_kind(&self) -> Option<IdentifierKind> {
+        self.identifier1_kind.or_else(|| {
+            self.identifier1_name
+                .as_deref()
+                .and_then(IdentifierKind::from_name)
+        })
+    }
+
+    /// Kind of the secondary identifier, given explicitly or inferred from its free-text name
+    pub fn secondary_identifier_kind(&self) -> Option<IdentifierKind> {
+        self.identifier2_kind.or_else(|| {
+            self.identifier2_name
+                .as_deref()
+                .and_then(IdentifierKind::from_name)
+        })
+    }
+
+    /// Puts identifiers of a known kind into canonical form. Invalid identifiers are reported and left as given.
+    fn normalize_identifiers(&mut self, location: &str, context: &ParseContext) -> Result<()> {
+        let kinds = (
+            self.primary_identifier_kind(),
+            self.secondary_identifier_kind(),
+        );
+        let identifiers = [
+            ("identifier1", kinds.0, &mut self.identifier1),
+            ("identifier2", kinds.1, &mut self.identifier2),
+        ];
+
+        for (field, kind, value) in identifiers {
+            let (Some(kind), Some(value)) = (kind, value) else {
+                continue;
+            };
+            match kind.normalize(value) {
+                Ok(normalized) => *value = normalized,
+                Err(err) => context.issue_at(
+                    codes::INVALID_IDENTIFIER,
+                    format!("{}.{}", location, field),
+                    format!(
+                        "{:?} in {}.{} is not a valid {}: {}",
+                        value, location, field, kind, err
+                    ),
+                )?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the balance records, both inline and imported, falling within the given calendar year
+    pub fn balances_in_year(&self, year: i32) -> impl Iterator<Item = &BalanceRecord> {
+        self.balances
+            .iter()
+            .chain(
+                self.imports
+                    .iter()
+                    .flat_map(|import| import.balances.iter()),
+            )
+            .filter(move |record| record.date.year() == year)
+    }
+}
+
+impl UserData {
+    /// The filer reports are prepared for, if filers are configured
+    pub fn primary_filer(&self) -> Option<&Filer> {
+        self.filers.first()
+    }
+
+    /// Decimal places amounts in the currency are written to, including currencies defined in `fact_extensions`
+    pub fn minor_units(&self, currency: &str) -> u32 {
+        self.fact_extensions.as_ref().map_or_else(
+            || money::minor_units(currency),
+            |extensions| extensions.minor_units(currency),
+        )
+    }
+
+    /// How the year's FBAR was filed, if given
+    pub fn filing_for(&self, year: i32) -> Option<&Filing> {
+        self.filings.iter().find(|filing| filing.year == year)
+    }
+
+    /// Whether the primary filer owns the account. Accounts without owners belong to the primary filer.
+    pub fn owned_by_primary(&self, account: &Account) -> bool {
+        match self.primary_filer() {
+            Some(filer) if !account.owners.is_empty() => account.owners.contains(&filer.handle),
+            _ => true,
+        }
+    }
+
+    /// The interpolation policy for an account: its own, or else the data directory's
+    pub fn interpolation_for(&self, account: &Account) -> InterpolationPolicy {
+        account.interpolation.unwrap_or(self.interpolation)
+    }
+
+    /// The entity an account is held through, if any. Fails if the account names an unknown entity.
+    pub fn entity_for(&self, account: &Account) -> Result<Option<&Entity>> {
+        let Some(handle) = &account.entity else {
+            return Ok(None);
+        };
+        match self.entities.iter().find(|entity| &entity.handle == handle) {
+            Some(entity) => Ok(Some(entity)),
+            None => anyhow::bail!(
+                "Account {} is held through unknown entity {}",
+                account.handle,
+                handle
+            ),
+        }
+    }
+
+    /// Moves archived accounts out of `accounts`, so listings, summaries and checks skip them. They still count
+    /// towards the input hash, and reports for a year one of them was open in refuse to run without them.
+    pub fn set_aside_archived(&mut self) {
+        let accounts = std::mem::take(&mut self.accounts);
+        for (index, account) in accounts.into_iter().enumerate() {
+            if account.archived {
+                self.archived_accounts.push((index, account));
+            } else {
+                self.accounts.push(account);
+            }
+        }
+    }
+
+    /// Puts the accounts set aside by `set_aside_archived` back where they were
+    fn restore_archived(&mut self) {
+        for (index, account) in std::mem::take(&mut self.archived_accounts) {
+            self.accounts
+                .insert(index.min(self.accounts.len()), account);
+        }
+    }
+
+    /// A copy of the data with only the accounts having at least one of the tags. No tags keeps every account.
+    pub fn with_tags(&self, tags: &[String]) -> Self {
+        let mut data = self.clone();
+        if !tags.is_empty() {
+            data.accounts
+                .retain(|account| tags.iter().any(|tag| account.has_tag(tag)));
+        }
+        data
+    }
+
+    /// The data as YAML with every optional key written out, unset ones included, so the file passes strict parsing
+    pub fn to_complete_yaml(&self) -> Result<String> {
+        let mut value = serde_yaml::to_value(self)?;
+        fill_optional_fields(&mut value)?;
+        Ok(serde_yaml::to_string(&value)?)
+    }
+
+    /// SHA-256 over all the user's inputs: `data.yml`, imported statements, rate sets and prices
+    pub fn content_hash(&self) -> Result<String> {
+        if !self.archived_accounts.is_empty() {
+            let mut data = self.clone();
+            data.restore_archived();
+            return data.content_hash();
+        }
+
+        #[derive(Serialize)]
+        struct Inputs<'a> {
+            data: &'a UserData,
+            imports: Vec<(&'a str, &'a [StatementImport])>,
+            rate_sets: Vec<(&'a str, &'a Facts)>,
+            price_series: &'a [PriceSeries],
+        }
+
+        let inputs = Inputs {
+            data: self,
+            imports: self
+                .accounts
+                .iter()
+                .map(|account| (account.handle.as_str(), account.imports.as_slice()))
+                .collect(),
+            rate_sets: self
+                .rate_sets
+                .iter()
+                .map(|rate_set| (rate_set.name.as_str(), &rate_set.facts))
+                .collect(),
+            price_series: &self.price_series,
+        };
+
+        let serialized = serde_yaml::to_string(&inputs)?;
+        Ok(hex::encode(Sha256::digest(serialized.as_bytes())))
+    }
+
+    /// Loads the data directory, collecting any recoverable problems as warnings
+    pub fn load_from_path(base_path: &Path) -> Result<Self> {
+        Self::load_with_context(base_path, &ParseContext::default())
+    }
+
+    /// Loads the data directory leniently, returning every warning found along with the data, or the data's absence
+    /// and the error that stopped it loading
+    pub fn load_diagnosed(base_path: &Path) -> (Option<Self>, Diagnostics) {
+        let context = ParseContext::default();
+        let loaded = Self::load_with_context(base_path, &context);
+        let mut diagnostics = context.diagnostics();
+        match loaded {
+            Ok(data) => (Some(data), diagnostics),
+            Err(err) => {
+                diagnostics.push(
+                    Diagnostic::error(codes::LOAD_FAILED, format!("{:#}", err))
+                        .at(base_path.display().to_string()),
+                );
+                (None, diagnostics)
+            }
+        }
+    }
+
+    pub fn load_with_context(base_path: &Path, context: &ParseContext) -> Result<Self> {
+        let _span = tracing::info_span!("load", path = %base_path.display()).entered();
+        let yaml_path = base_path.join("data.yml");
+
+        if !yaml_path.exists() {
+            anyhow::bail!("data.yml not found in {:?}", base_path);
+        }
+
+        let contents = std::fs::read_to_string(yaml_path)?;
+        let value: serde_yaml::Value = serde_yaml::from_str(&contents)?;
+        let version = migrate::schema_version(&value)?;
+        if version > migrate::SCHEMA_VERSION {
+            anyhow::bail!(
+                "data.yml is schema version {}, but this version of fbar_prep only understands up to {}; upgrade fbar_prep",
+                version,
+                migrate::SCHEMA_VERSION
+            );
+        }
+        if version < migrate::SCHEMA_VERSION {
+            context.issue_at(
+                codes::OUTDATED_SCHEMA,
+                "data.yml",
+                format!(
+                    "data.yml is schema version {} (current is {}); run `fbar_prep migrate` to upgrade it",
+                    version,
+                    migrate::SCHEMA_VERSION
+                ),
+            )?;
+        }
+        check_optional_fields(&value, context)?;
+        let mut data: UserData = context.deserialize(value, "data.yml")?;
+
+        for entity in &data.entities {
+            entity.validate()?;
+        }
+        for (index, provider) in data.providers.iter_mut().enumerate() {
+            let Some(bic) = &mut provider.bic else {
+                continue;
+            };
+            match IdentifierKind::Bic.normalize(bic) {
+                Ok(normalized) => *bic = normalized,
+                Err(err) => context.issue_at(
+                    codes::INVALID_IDENTIFIER,
+                    format!("data.yml providers[{}].bic", index),
+                    format!(
+                        "{:?} in data.yml providers[{}].bic is not a valid BIC: {}",
+                        bic, index, err
+                    ),
+                )?,
+            }
+        }
+        for period in &data.nonresident_periods {
+            period.validate()?;
+        }
+        if let Some(extensions) = &data.fact_extensions {
+            extensions.validate_years("fact_extensions in data.yml")?;
+            extensions.validate_currencies()?;
+        }
+        for profile in &data.import_profiles {
+            profile.validate()?;
+        }
+
+        for (index, account) in data.accounts.iter_mut().enumerate() {
+            account.normalize_identifiers(&format!("data.yml accounts[{}]", index), context)?;
+
+            let has_reason = account
+                .exclusion_reason
+                .as_ref()
+                .is_some_and(|reason| !reason.trim().is_empty());
+            if account.exclude_from_fbar && !has_reason {
+                anyhow::bail!(
+                    "Account {} is excluded from the FBAR but has no exclusion_reason",
+                    account.handle
+                );
+            }
+            for record in &account.balances {
+                record
+                    .usd_equivalent()
+                    .with_context(|| format!("Account {}", account.handle))?;
+            }
+            if let Some(authority) = &account.signature_authority {
+                authority.validate(&account.handle)?;
+                if account.entity.is_some() || account.owners.len() > 1 {
+                    anyhow::bail!(
+                        "Account {} has signature_authority, so the filer has no financial interest in it, but is \
+                         also held through an entity or jointly owned",
+                        account.handle
+                    );
+                }
+            }
+            if account.archived && account.closing_date.is_none() {
+                anyhow::bail!(
+                    "Account {} is archived but has no closing_date; only closed accounts can be archived",
+                    account.handle
+                );
+            }
+            if let Some(record) = account.balances.iter().find(|record| {
+                record.manual
+                    && record
+                        .note
+                        .as_ref()
+                        .is_none_or(|note| note.trim().is_empty())
+            }) {
+                anyhow::bail!(
+                    "Account {} has a manual balance record on {} without a note saying where it came from",
+                    account.handle,
+                    record.date
+                );
+            }
+        }
+
+        integrity::check_integrity(&data)?;
+
+        for statement_file in StatementStore::new(base_path).load_all(context)? {
+            match data
+                .accounts
+                .iter_mut()
+                .find(|account| account.handle == statement_file.account)
+            {
+                Some(account) => account.imports.extend(statement_file.imports),
+                None => anyhow::bail!(
+                    "Statements found for unknown account {}",
+                    statement_file.account
+                ),
+            }
+        }
+
+        data.rate_sets = RateSet::load_all(base_path, context)?;
+        data.price_series = PriceStore::new(base_path).load_all(context)?;
+
+        tracing::info!(
+            accounts = data.accounts.len(),
+            rate_sets = data.rate_sets.len(),
+            price_series = data.price_series.len(),
+            "Loaded data"
+        );
+
+        Ok(data)
+    }
+}
+
+/// Optional keys strict mode requires in `data.yml`, at the top level and in each provider, account and balance
+/// record, each with the YAML it's written as when unset
+const OPTIONAL_KEYS: &[(&str, &str)] = &[
+    ("schema_version", "~"),
+    ("accounts", "[]"),
+    ("filers", "[]"),
+    ("entities", "[]"),
+    ("fact_extensions", "~"),
+    ("rate_precedence", "~"),
+    ("rate_checks", "{}"),
+    ("many_accounts", "~"),
+    ("interpolation", "none"),
+    ("import_profiles", "[]"),
+    ("us_person_since", "~"),
+    ("filings", "[]"),
+    ("fincen_schema_version", "~"),
+    ("nonresident_periods", "[]"),
+    ("google_sheets", "~"),
+    ("rate_pins", "{}"),
+    ("transliteration", "{}"),
+    ("maximum_policy", "convert_maximum"),
+    ("change_checks", "{}"),
+];
+const OPTIONAL_PROVIDER_KEYS: &[(&str, &str)] = &[
+    ("country", "~"),
+    ("founded", "~"),
+    ("foreign_branch_of", "~"),
+    ("bic", "~"),
+];
+const OPTIONAL_ACCOUNT_KEYS: &[(&str, &str)] = &[
+    ("identifier1_kind", "~"),
+    ("identifier1_name", "~"),
+    ("identifier1", "~"),
+    ("identifier2_kind", "~"),
+    ("identifier2_name", "~"),
+    ("identifier2", "~"),
+    ("opening_date", "~"),
+    ("closing_date", "~"),
+    ("exclude_from_fbar", "false"),
+    ("exclusion_reason", "~"),
+    ("kind", "bank"),
+    ("balances", "[]"),
+    ("holdings", "[]"),
+    ("surrender_values", "[]"),
+    ("valuation", "~"),
+    ("tags", "[]"),
+    ("notes", "~"),
+    ("entity", "~"),
+    ("signature_authority", "~"),
+    ("owners", "[]"),
+    ("interpolation", "~"),
+    ("import_profile", "~"),
+    ("archived", "false"),
+];
+const OPTIONAL_BALANCE_KEYS: &[(&str, &str)] = &[
+    ("currency", "~"),
+    ("manual", "false"),
+    ("note", "~"),
+    ("usd_value", "~"),
+    ("usd_rate", "~"),
+];
+
+fn check_optional_fields(value: &serde_yaml::Value, context: &ParseContext) -> Result<()> {
+    let check = |value, keys: &[(&str, &str)], source: &str| {
+        let keys: Vec<&str> = keys.iter().map(|(key, _)| *key).collect();
+        context.check_optional_keys(value, &keys, source)
+    };
+    check(value, OPTIONAL_KEYS, "data.yml")?;
+
+    if let Some(providers) = value.get("providers").and_then(|p| p.as_sequence()) {
+        for (index, provider) in providers.iter().enumerate() {
+            check(
+                provider,
+                OPTIONAL_PROVIDER_KEYS,
+                &format!("data.yml providers[{}]", index),
+            )?;
+        }
+    }
+
+    if let Some(accounts) = value.get("accounts").and_then(|a| a.as_sequence()) {
+        for (index, account) in accounts.iter().enumerate() {
+            check(
+                account,
+                OPTIONAL_ACCOUNT_KEYS,
+                &format!("data.yml accounts[{}]", index),
+            )?;
+            let balances = account.get("balances").and_then(|b| b.as_sequence());
+            for (record, balance) in balances.into_iter().flatten().enumerate() {
+                check(
+                    balance,
+                    OPTIONAL_BALANCE_KEYS,
+                    &format!("data.yml accounts[{}] balances[{}]", index, record),
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes out every optional key the serialized data leaves out, as its value when unset, so the file passes strict
+/// parsing
+fn fill_optional_fields(value: &mut serde_yaml::Value) -> Result<()> {
+    fn fill(mapping: &mut serde_yaml::Value, keys: &[(&str, &str)]) -> Result<()> {
+        if let Some(mapping) = mapping.as_mapping_mut() {
+            for (key, unset) in keys {
+                if !mapping.contains_key(*key) {
+                    mapping.insert((*key).into(), serde_yaml::from_str(unset)?);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fill(value, OPTIONAL_KEYS)?;
+    if let Some(providers) = value.get_mut("providers").and_then(|p| p.as_sequence_mut()) {
+        for provider in providers {
+            fill(provider, OPTIONAL_PROVIDER_KEYS)?;
+        }
+    }
+    if let Some(accounts) = value.get_mut("accounts").and_then(|a| a.as_sequence_mut()) {
+        for account in accounts {
+            fill(account, OPTIONAL_ACCOUNT_KEYS)?;
+            if let Some(balances) = account
+                .get_mut("balances")
+                .and_then(|b| b.as_sequence_mut())
+            {
+                for balance in balances {
+                    fill(balance, OPTIONAL_BALANCE_KEYS)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn create_test_yaml(dir: &Path) -> std::io::Result<()> {
+        let yaml_content = r#"
+schema_version: 2
+providers:
+  - name: "Example Bank"
+    handle: "example_bank"
+    address: "123 Bank Street, Zurich, Switzerland"
+    country: "CH"
+    founded: 1862-01-01
+    foreign_branch_of: ~
+    bic: "UBSWCHZH80A"
+  - name: "Another Bank"
+    handle: "another_bank"
+    address:
+      street: "456 Finance Ave"
+      city: "Frankfurt"
+      postal_code: "60311"
+      country: "DE"
+    country: ~
+    founded: ~
+    foreign_branch_of: ~
+    bic: ~
+
+accounts:
+  - handle: "example_checking"
+    provider: "example_bank"
+    currency_code: "CHF"
+    identifier1_kind: ~
+    identifier1_name: "IBAN"
+    identifier1: "CH93 0076 2011 6238 5295 7"
+    identifier2_kind: bic
+    identifier2_name: ~
+    identifier2: "ubswchzh80a"
+    opening_date: 2021-04-01
+    closing_date: ~
+    exclude_from_fbar: false
+    exclusion_reason: ~
+    kind: bank
+    valuation: ~
+    holdings: []
+    surrender_values: []
+    tags: ["household"]
+    notes: "Joint account for bills"
+    entity: ~
+    signature_authority: ~
+    owners: []
+    interpolation: ~
+    import_profile: ~
+    archived: false
+    balances:
+      - date: 2022-12-31
+        amount: 900.0
+        currency: ~
+        manual: false
+        note: ~
+        usd_value: ~
+        usd_rate: ~
+      - { date: 2023-03-31, amount: 1000.0, currency: ~, manual: false, note: ~, usd_value: ~, usd_rate: ~ }
+      - { date: 2023-06-30, amount: 1500.5, currency: ~, manual: false, note: ~, usd_value: ~, usd_rate: ~ }
+
+entities: []
+filers: []
+
+rate_precedence: ["user", "irs"]
+many_accounts: ~
+interpolation: none
+import_profiles: []
+us_person_since: ~
+filings: []
+change_checks: {}
+maximum_policy: convert_maximum
+transliteration: { enabled: true }
+rate_pins: {}
+google_sheets: ~
+nonresident_periods: []
+fincen_schema_version: ~
+rate_checks:
+  max_override_deviation: 15.0
+  max_yearly_change: 25.0
+
+fact_extensions:
+  years:
+    - year: 2023
+      exchange_rates:
+        - currency_code: "CHF"
+          rate: 1.08
+        - currency_code: "EUR"
+          rate: 0.91
+    - year: 2022
+      exchange_rates:
+        - currency_code: "CHF"
+          rate: 1.07
+        - currency_code: "EUR"
+          rate: 0.92
+"#;
+        fs::write(dir.join("data.yml"), yaml_content)
+    }
+
+    #[test]
+    fn test_load_valid_data() -> Result<()> {
+        // Create a temporary directory that will be automatically cleaned up
+        let temp_dir = TempDir::new()?;
+        create_test_yaml(temp_dir.path())?;
+
+        // Load and validate the data
+        let data = UserData::load_from_path(temp_dir.path())?;
+
+        // Verify providers
+        assert_eq!(data.providers.len(), 2);
+        assert_eq!(data.providers[0].name, "Example Bank");
+        assert_eq!(data.providers[0].handle, "example_bank");
+        assert_eq!(
+            data.providers[0].address,
+            Address::from("123 Bank Street, Zurich, Switzerland")
+        );
+        assert_eq!(data.providers[0].country_code()?, Some(CountryCode::CHE));
+
+        let structured = &data.providers[1];
+        assert_eq!(structured.address.country(), Some("DE"));
+        assert_eq!(structured.country_code()?, Some(CountryCode::DEU));
+
+        // Verify accounts
+        assert_eq!(data.accounts.len(), 1);
+        assert_eq!(data.accounts[0].handle, "example_checking");
+        assert_eq!(data.accounts[0].provider, "example_bank");
+        assert!(data.accounts[0].has_tag("Household"));
+        assert_eq!(
+            data.accounts[0].notes.as_deref(),
+            Some("Joint account for bills")
+        );
+        assert_eq!(data.with_tags(&["household".to_string()]).accounts.len(), 1);
+        assert!(data.with_tags(&["kids".to_string()]).accounts.is_empty());
+        assert_eq!(data.accounts[0].identifier1_name.as_deref(), Some("IBAN"));
+        // The legacy name still gives the identifier's kind, and both identifiers are normalized
+        assert_eq!(
+            data.accounts[0].primary_identifier_kind(),
+            Some(IdentifierKind::Iban)
+        );
+        assert_eq!(
+            data.accounts[0].identifier1.as_deref(),
+            Some("CH9300762011623852957")
+        );
+        assert_eq!(data.accounts[0].identifier2.as_deref(), Some("UBSWCHZH80A"));
+        assert_eq!(
+            data.accounts[0].opening_date,
+            NaiveDate::from_ymd_opt(2021, 4, 1)
+        );
+        assert_eq!(data.accounts[0].closing_date, None);
+        assert_eq!(data.accounts[0].balances.len(), 3);
+        assert_eq!(
+            data.accounts[0].balances[1].date,
+            NaiveDate::from_ymd_opt(2023, 3, 31).unwrap()
+        );
+
+        assert_eq!(
+            data.rate_precedence,
+            Some(vec!["user".to_string(), "irs".to_string()])
+        );
+        assert!(data.rate_sets.is_empty());
+        assert_eq!(data.rate_checks.max_override_deviation, 15.0);
+
+        // Verify user_fact_overrides
+        let fact_extensions = data.fact_extensions.as_ref().unwrap();
+        assert_eq!(fact_extensions.years.len(), 2);
+
+        let year_2023 = &fact_extensions.years[0];
+        assert_eq!(year_2023.year, 2023);
+        assert_eq!(year_2023.exchange_rates[0].currency_code, "chf");
+        assert_eq!(year_2023.exchange_rates[0].rate, 1.08);
+        assert_eq!(year_2023.exchange_rates[1].currency_code, "eur");
+        assert_eq!(year_2023.exchange_rates[1].rate, 0.91);
+
+        let year_2022 = &fact_extensions.years[1];
+        assert_eq!(year_2022.year, 2022);
+        assert_eq!(year_2022.exchange_rates[0].currency_code, "chf");
+        assert_eq!(year_2022.exchange_rates[0].rate, 1.07);
+        assert_eq!(year_2022.exchange_rates[1].currency_code, "eur");
+        assert_eq!(year_2022.exchange_rates[1].rate, 0.92);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_balances_in_year() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        create_test_yaml(temp_dir.path())?;
+
+        let data = UserData::load_from_path(temp_dir.path())?;
+        let account = &data.accounts[0];
+
+        let amounts: Vec<Decimal> = account.balances_in_year(2023).map(|b| b.amount).collect();
+        assert_eq!(amounts, vec![dec!(1000.0), dec!(1500.5)]);
+        assert_eq!(account.balances_in_year(2021).count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_imported_statements() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        create_test_yaml(temp_dir.path())?;
+
+        let statement_path = temp_dir.path().join("july.csv");
+        fs::write(&statement_path, "date,amount\n2023-07-31,2000.0\n")?;
+        StatementStore::new(temp_dir.path()).import(
+            "example_checking",
+            &statement_path,
+            None,
+            None,
+            &ParseContext::default(),
+        )?;
+
+        let data = UserData::load_from_path(temp_dir.path())?;
+        let account = &data.accounts[0];
+
+        assert_eq!(account.imports.len(), 1);
+        let amounts: Vec<Decimal> = account.balances_in_year(2023).map(|b| b.amount).collect();
+        assert_eq!(amounts, vec![dec!(1000.0), dec!(1500.5), dec!(2000.0)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_statements_for_unknown_account() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        create_test_yaml(temp_dir.path())?;
+
+        let statement_path = temp_dir.path().join("july.csv");
+        fs::write(&statement_path, "date,amount\n2023-07-31,2000.0\n")?;
+        StatementStore::new(temp_dir.path()).import(
+            "mystery_account",
+            &statement_path,
+            None,
+            None,
+            &ParseContext::default(),
+        )?;
+
+        let result = UserData::load_from_path(temp_dir.path());
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("unknown account mystery_account"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict_mode() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        create_test_yaml(temp_dir.path())?;
+
+        // The test data is complete, so strict mode accepts it
+        UserData::load_with_context(temp_dir.path(), &ParseContext::strict())?;
+
+        let contents = fs::read_to_string(temp_dir.path().join("data.yml"))?;
+        fs::write(
+            temp_dir.path().join("data.yml"),
+            contents.replace(
+                "
+    balances:",
+                "
+    balnces:",
+            ),
+        )?;
+
+        let result = UserData::load_with_context(temp_dir.path(), &ParseContext::strict());
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Optional key `balances` omitted"));
+
+        // Lenient mode loads the data but warns about the typo
+        let context = ParseContext::default();
+        let data = UserData::load_with_context(temp_dir.path(), &context)?;
+        assert!(data.accounts[0].balances.is_empty());
+        assert_eq!(
+            context.warnings(),
+            vec![
+                "Unknown key `accounts.0.balnces` in data.yml; did you mean `balances`?"
+                    .to_string()
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict_mode_requires_each_optional_key() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        create_test_yaml(temp_dir.path())?;
+        let contents = fs::read_to_string(temp_dir.path().join("data.yml"))?;
+
+        // Each line left out of the otherwise complete test data, with the key strict mode asks for
+        let omissions = [
+            ("\nfincen_schema_version: ~", "fincen_schema_version"),
+            ("\nnonresident_periods: []", "nonresident_periods"),
+            ("\ngoogle_sheets: ~", "google_sheets"),
+            ("\nrate_pins: {}", "rate_pins"),
+            ("\ntransliteration: { enabled: true }", "transliteration"),
+            ("\nmaximum_policy: convert_maximum", "maximum_policy"),
+            ("\n    archived: false", "archived"),
+            ("\n    signature_authority: ~", "signature_authority"),
+            ("\nchange_checks: {}", "change_checks"),
+            ("\n    country: \"CH\"", "country"),
+            ("\n    founded: 1862-01-01", "founded"),
+            ("\n    foreign_branch_of: ~", "foreign_branch_of"),
+            ("\n    bic: \"UBSWCHZH80A\"", "bic"),
+            ("\n        currency: ~", "currency"),
+            ("\n        manual: false", "manual"),
+            ("\n        note: ~", "note"),
+            ("\n        usd_value: ~", "usd_value"),
+            ("\n        usd_rate: ~", "usd_rate"),
+        ];
+        for (line, key) in omissions {
+            fs::write(
+                temp_dir.path().join("data.yml"),
+                contents.replacen(line, "", 1),
+            )?;
+            let err =
+                UserData::load_with_context(temp_dir.path(), &ParseContext::strict()).unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains(&format!("Optional key `{}` omitted", key)),
+                "{}: {}",
+                key,
+                err
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_complete_yaml_passes_strict_mode() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let data = UserData {
+            providers: vec![Provider {
+                name: "Example Bank".to_string(),
+                handle: "example_bank".to_string(),
+                address: Address::from("1 Bank Street, London"),
+                ..Default::default()
+            }],
+            accounts: vec![Account {
+                handle: "savings".to_string(),
+                provider: "example_bank".to_string(),
+                currency_code: "GBP".to_string(),
+                balances: vec![BalanceRecord {
+                    date: NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(),
+                    amount: dec!(100),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        fs::write(temp_dir.path().join("data.yml"), data.to_complete_yaml()?)?;
+
+        // Every key is written out with the value it has when left out
+        let loaded = UserData::load_with_context(temp_dir.path(), &ParseContext::strict())?;
+        assert_eq!(
+            serde_yaml::to_string(&loaded)?,
+            serde_yaml::to_string(&data)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exclusion_requires_reason() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        create_test_yaml(temp_dir.path())?;
+
+        let contents = fs::read_to_string(temp_dir.path().join("data.yml"))?;
+        fs::write(
+            temp_dir.path().join("data.yml"),
+            contents.replace("exclude_from_fbar: false", "exclude_from_fbar: true"),
+        )?;
+
+        let result = UserData::load_from_path(temp_dir.path());
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("example_checking is excluded from the FBAR but has no exclusion_reason"));
+
+        let contents = fs::read_to_string(temp_dir.path().join("data.yml"))?;
+        fs::write(
+            temp_dir.path().join("data.yml"),
+            contents.replace(
+                "exclusion_reason: ~",
+                "exclusion_reason: \"US-based account\"",
+            ),
+        )?;
+
+        let data = UserData::load_from_path(temp_dir.path())?;
+        assert!(data.accounts[0].exclude_from_fbar);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_manual_record_requires_note() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        create_test_yaml(temp_dir.path())?;
+
+        let contents = fs::read_to_string(temp_dir.path().join("data.yml"))?;
+        fs::write(
+            temp_dir.path().join("data.yml"),
+            contents.replace(
+                "amount: 1000.0, currency: ~, manual: false",
+                "amount: 1000.0, currency: ~, manual: true",
+            ),
+        )?;
+
+        let result = UserData::load_from_path(temp_dir.path());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("example_checking has a manual balance record on 2023-03-31 without a note"));
+
+        let contents = fs::read_to_string(temp_dir.path().join("data.yml"))?;
+        fs::write(
+            temp_dir.path().join("data.yml"),
+            contents.replace(
+                "manual: true, note: ~",
+                "manual: true, note: phone confirmation from bank",
+            ),
+        )?;
+
+        let data = UserData::load_from_path(temp_dir.path())?;
+        let record = &data.accounts[0].balances[1];
+        assert!(record.manual);
+        assert_eq!(record.note.as_deref(), Some("phone confirmation from bank"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_archived_accounts() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        create_test_yaml(temp_dir.path())?;
+
+        let contents = fs::read_to_string(temp_dir.path().join("data.yml"))?;
+        fs::write(
+            temp_dir.path().join("data.yml"),
+            contents.replacen("archived: false", "archived: true", 1),
+        )?;
+        let result = UserData::load_from_path(temp_dir.path());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Account example_checking is archived but has no closing_date"));
+
+        let contents = fs::read_to_string(temp_dir.path().join("data.yml"))?;
+        fs::write(
+            temp_dir.path().join("data.yml"),
+            contents.replacen("closing_date: ~", "closing_date: 2023-12-31", 1),
+        )?;
+        let mut data = UserData::load_from_path(temp_dir.path())?;
+        let hash = data.content_hash()?;
+        let count = data.accounts.len();
+
+        data.set_aside_archived();
+        assert_eq!(data.accounts.len(), count - 1);
+        assert!(data
+            .accounts
+            .iter()
+            .all(|account| account.handle != "example_checking"));
+        assert_eq!(data.archived_accounts[0].1.handle, "example_checking");
+        assert_eq!(data.content_hash()?, hash);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_identifier() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        create_test_yaml(temp_dir.path())?;
+
+        let contents = fs::read_to_string(temp_dir.path().join("data.yml"))?;
+        fs::write(
+            temp_dir.path().join("data.yml"),
+            contents.replace("CH93 0076", "CH94 0076"),
+        )?;
+
+        let result = UserData::load_with_context(temp_dir.path(), &ParseContext::strict());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("data.yml accounts[0].identifier1 is not a valid IBAN"));
+
+        // Lenient mode keeps the identifier as given
+        let context = ParseContext::default();
+        let data = UserData::load_with_context(temp_dir.path(), &context)?;
+        assert_eq!(
+            data.accounts[0].identifier1.as_deref(),
+            Some("CH94 0076 2011 6238 5295 7")
+        );
+        assert_eq!(context.warnings().len(), 1);
+
+        let (data, diagnostics) = UserData::load_diagnosed(temp_dir.path());
+        assert!(data.is_some());
+        let diagnostic = diagnostics.warnings().next().unwrap();
+        assert_eq!(diagnostic.code, codes::INVALID_IDENTIFIER);
+        assert_eq!(
+            diagnostic.location.as_deref(),
+            Some("data.yml accounts[0].identifier1")
+        );
+
+        // Data that can't be loaded at all is an error rather than a failure
+        let (data, diagnostics) = UserData::load_diagnosed(&temp_dir.path().join("missing"));
+        assert!(data.is_none());
+        assert_eq!(
+            diagnostics.errors().next().unwrap().code,
+            codes::LOAD_FAILED
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_entities() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        create_test_yaml(temp_dir.path())?;
+
+        let contents = fs::read_to_string(temp_dir.path().join("data.yml"))?;
+        let with_entity = contents.replace("entity: ~", "entity: holdings").replace(
+            "entities: []",
+            r#"entities:
+  - name: "Example Holdings AG"
+    handle: "holdings"
+    address: "1 Example Street, Zug"
+    ownership_percent: 100"#,
+        );
+        fs::write(temp_dir.path().join("data.yml"), &with_entity)?;
+
+        let data = UserData::load_with_context(temp_dir.path(), &ParseContext::strict())?;
+        let entity = data.entity_for(&data.accounts[0])?.unwrap();
+        assert_eq!(entity.name, "Example Holdings AG");
+        assert_eq!(entity.ownership_percent, dec!(100));
+
+        fs::write(
+            temp_dir.path().join("data.yml"),
+            with_entity.replace("entity: holdings", "entity: unknown"),
+        )?;
+        let result = UserData::load_from_path(temp_dir.path());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("held through unknown entity unknown"));
+
+        fs::write(
+            temp_dir.path().join("data.yml"),
+            with_entity.replace("ownership_percent: 100", "ownership_percent: 0"),
+        )?;
+        assert!(UserData::load_from_path(temp_dir.path()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_signature_authority() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        create_test_yaml(temp_dir.path())?;
+
+        let contents = fs::read_to_string(temp_dir.path().join("data.yml"))?;
+        let with_authority = contents.replace(
+            "signature_authority: ~",
+            r#"signature_authority:
+      owner: "Example Employer AG"
+      address: "1 Example Street, Zug"
+      title: "Treasurer""#,
+        );
+        fs::write(temp_dir.path().join("data.yml"), &with_authority)?;
+
+        let data = UserData::load_from_path(temp_dir.path())?;
+        let authority = data.accounts[0].signature_authority.as_ref().unwrap();
+        assert_eq!(authority.owner, "Example Employer AG");
+        assert_eq!(authority.title, "Treasurer");
+
+        fs::write(
+            temp_dir.path().join("data.yml"),
+            with_authority.replace("entity: ~", "entity: holdings"),
+        )?;
+        let err = UserData::load_from_path(temp_dir.path()).unwrap_err();
+        assert!(err.to_string().contains("no financial interest"), "{}", err);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_filers() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        create_test_yaml(temp_dir.path())?;
+
+        let contents = fs::read_to_string(temp_dir.path().join("data.yml"))?;
+        let with_filers = contents
+            .replace("owners: []", "owners: [alex, sam]")
+            .replace(
+                "filers: []",
+                r#"filers:
+  - name: "Alex Example"
+    handle: "alex"
+  - name: "Sam Example"
+    handle: "sam""#,
+            );
+        fs::write(temp_dir.path().join("data.yml"), &with_filers)?;
+
+        let data = UserData::load_from_path(temp_dir.path())?;
+        assert_eq!(data.primary_filer().unwrap().handle, "alex");
+        assert!(data.owned_by_primary(&data.accounts[0]));
+
+        fs::write(
+            temp_dir.path().join("data.yml"),
+            with_filers.replace("owners: [alex, sam]", "owners: [alex, robin]"),
+        )?;
+        let result = UserData::load_from_path(temp_dir.path());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("owned by unknown filer robin"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_yaml() {
+        // Create an empty temp directory
+        let temp_dir = TempDir::new().unwrap();
+
+        // Attempt to load from directory with no yaml file
+        let result = UserData::load_from_path(temp_dir.path());
+
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("data.yml not found"));
+    }
+
+    #[test]
+    fn test_invalid_yaml() -> std::io::Result<()> {
+        let temp_dir = TempDir::new()?;
+
+        // Create invalid YAML file
+        fs::write(
+            temp_dir.path().join("data.yml"),
+            "invalid: yaml: content: - [",
+        )?;
+
+        let result = UserData::load_from_path(temp_dir.path());
+        assert!(result.is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+use anyhow::{bail, Result};
+use chrono::NaiveDate;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::UserData;
+
+/// A stretch of time during which the filer was not a US person, e.g. after leaving the US partway through a year,
+/// or while a treaty tie-breaker made them a nonresident. Reports note how it bears on each account rather than
+/// changing any figures, as dual-status years are a question for a tax advisor.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct NonresidentPeriod {
+    pub start: NaiveDate,
+    /// Last day as a nonresident; not set while the filer still isn't a US person
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<NaiveDate>,
+    /// Why, e.g. "moved to Germany" or "treaty tie-breaker", repeated in the report's notes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl NonresidentPeriod {
+    /// The part of the period between `start` and `end`, if they overlap
+    pub fn within(&self, start: NaiveDate, end: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+        let from = self.start.max(start);
+        let to = self.end.map_or(end, |own_end| own_end.min(end));
+        (from <= to).then_some((from, to))
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        if let Some(end) = self.end.filter(|end| *end < self.start) {
+            bail!(
+                "Nonresident period starting {} ends on {}, before it starts",
+                self.start,
+                end
+            );
+        }
+        Ok(())
+    }
+}
+
+impl UserData {
+    /// The nonresident periods overlapping `start` to `end`, clipped to it, in order
+    pub fn nonresident_between(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Vec<(NaiveDate, NaiveDate, &NonresidentPeriod)> {
+        let mut overlaps: Vec<_> = self
+            .nonresident_periods
+            .iter()
+            .filter_map(|period| {
+                period
+                    .within(start, end)
+                    .map(|(from, to)| (from, to, period))
+            })
+            .collect();
+        overlaps.sort_by_key(|(from, _, _)| *from);
+        overlaps
+    }
+
+    /// Whether the filer was not a US person on any day from `start` to `end`
+    pub fn nonresident_throughout(&self, start: NaiveDate, end: NaiveDate) -> bool {
+        // The first day not yet known to be covered
+        let mut next = start;
+        for (from, to, _) in self.nonresident_between(start, end) {
+            if from > next {
+                return false;
+            }
+            match to.succ_opt() {
+                Some(after) => next = next.max(after),
+                None => return true,
+            }
+        }
+        next > end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> NaiveDate {
+        value.parse().unwrap()
+    }
+
+    fn period(start: &str, end: Option<&str>) -> NonresidentPeriod {
+        NonresidentPeriod {
+            start: date(start),
+            end: end.map(date),
+            reason: None,
+        }
+    }
+
+    #[test]
+    fn test_nonresident_between() {
+        let user_data = UserData {
+            nonresident_periods: vec![
+                period("2023-07-01", None),
+                period("2022-01-01", Some("2023-02-28")),
+            ],
+            ..Default::default()
+        };
+        let overlaps: Vec<_> = user_data
+            .nonresident_between(date("2023-01-01"), date("2023-12-31"))
+            .into_iter()
+            .map(|(from, to, _)| (from, to))
+            .collect();
+        assert_eq!(
+            overlaps,
+            vec![
+                (date("2023-01-01"), date("2023-02-28")),
+                (date("2023-07-01"), date("2023-12-31"))
+            ]
+        );
+
+        assert!(!user_data.nonresident_throughout(date("2023-01-01"), date("2023-12-31")));
+        assert!(user_data.nonresident_throughout(date("2023-08-01"), date("2023-12-31")));
+        assert!(user_data.nonresident_throughout(date("2022-01-01"), date("2022-12-31")));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(period("2023-07-01", Some("2023-12-31")).validate().is_ok());
+        assert!(period("2023-07-01", Some("2023-06-30")).validate().is_err());
+    }
+}
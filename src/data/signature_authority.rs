@@ -0,0 +1,70 @@
+use anyhow::{bail, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::Address;
+
+/// Signature authority over an account the filer has no financial interest in, e.g. an employer's account they can
+/// sign for. Such accounts are reported in Part IV, with the details of the account's owner.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct SignatureAuthority {
+    /// Name of the account's owner
+    pub owner: String,
+    pub address: Address,
+    /// The owner's US taxpayer identification number, if it has one
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "super::masking::tin"
+    )]
+    pub tin: Option<String>,
+    /// The filer's title with the owner, e.g. `Treasurer`
+    pub title: String,
+}
+
+impl SignatureAuthority {
+    pub(crate) fn validate(&self, account: &str) -> Result<()> {
+        if self.owner.trim().is_empty() {
+            bail!(
+                "Account {} has signature_authority without an owner",
+                account
+            );
+        }
+        if self.title.trim().is_empty() {
+            bail!(
+                "Account {} has signature_authority without the filer's title with {}",
+                account,
+                self.owner
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_authority(owner: &str, title: &str) -> SignatureAuthority {
+        SignatureAuthority {
+            owner: owner.to_string(),
+            address: Address::from("1 Example Street, London"),
+            tin: None,
+            title: title.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(create_test_authority("Example Ltd", "Treasurer")
+            .validate("payroll")
+            .is_ok());
+        assert!(create_test_authority(" ", "Treasurer")
+            .validate("payroll")
+            .is_err());
+        let err = create_test_authority("Example Ltd", "")
+            .validate("payroll")
+            .unwrap_err();
+        assert!(err.to_string().contains("without the filer's title"));
+    }
+}
@@ -0,0 +1,407 @@
+//! Stable codes for every diagnostic, with the rule each one checks, why it matters for the FBAR and how to fix it.
+//!
+//! Codes are never reused or renumbered once released, so they can be searched for and matched on by applications.
+//! A retired check keeps its entry so old output can still be explained.
+
+use std::fmt;
+
+/// A problem in data.yml that doesn't fit a more specific code
+pub const INVALID_DATA: &str = "FB0001";
+pub const UNKNOWN_KEY: &str = "FB0002";
+pub const OMITTED_KEY: &str = "FB0003";
+pub const INVALID_IDENTIFIER: &str = "FB0004";
+pub const OUTDATED_SCHEMA: &str = "FB0005";
+pub const LOAD_FAILED: &str = "FB0006";
+pub const INVALID_DATE: &str = "FB0007";
+pub const TRANSACTION_GAP: &str = "FB0008";
+pub const BALANCE_MISMATCH: &str = "FB0009";
+pub const UNKNOWN_CURRENCY: &str = "FB0010";
+pub const REPORT_FAILED: &str = "FB0011";
+pub const RECORDS_OUTSIDE_OPEN_PERIOD: &str = "FB0012";
+pub const OPENED_AFTER_YEAR: &str = "FB0013";
+pub const CLOSED_BEFORE_OPENED: &str = "FB0014";
+pub const OPENED_BEFORE_FOUNDED: &str = "FB0015";
+pub const VALUE_TOO_LONG: &str = "FB0016";
+pub const US_INSTITUTION: &str = "FB0017";
+pub const MISSING_COUNTRY: &str = "FB0018";
+pub const UNKNOWN_PROVIDER: &str = "FB0019";
+pub const MISSING_ACCOUNT_NUMBER: &str = "FB0020";
+pub const INVALID_MAXIMUM: &str = "FB0021";
+pub const MISSING_JOINT_OWNER_TIN: &str = "FB0022";
+pub const UNKNOWN_JOINT_OWNER: &str = "FB0023";
+pub const MISSING_OWNER_TIN: &str = "FB0024";
+pub const UNKNOWN_ENTITY: &str = "FB0025";
+pub const MISSING_LATE_REASON: &str = "FB0026";
+pub const NEEDLESS_LATE_REASON: &str = "FB0027";
+pub const MISSING_LATE_EXPLANATION: &str = "FB0028";
+pub const LATE_REASON_IN_NARRATIVE: &str = "FB0029";
+pub const ACCOUNT_KIND_MISMATCH: &str = "FB0030";
+pub const NEAREST_YEAR_RATE: &str = "FB0031";
+pub const DUAL_STATUS_YEAR: &str = "FB0032";
+pub const DUPLICATE_FACT: &str = "FB0033";
+pub const UNSUPPORTED_CHARACTERS: &str = "FB0034";
+pub const AMBIGUOUS_BRANCH_COUNTRY: &str = "FB0035";
+pub const UNMARKED_FOREIGN_BRANCH: &str = "FB0036";
+pub const AMBIGUOUS_AMOUNT: &str = "FB0037";
+
+/// What a code means, for `fbar_prep explain`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Explanation {
+    pub code: &'static str,
+    pub title: &'static str,
+    /// What's checked
+    pub rule: &'static str,
+    /// Why it matters for the FBAR
+    pub why: &'static str,
+    pub fix: &'static str,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {}", self.code, self.title)?;
+        writeln!(f)?;
+        writeln!(f, "{}", self.rule)?;
+        writeln!(f)?;
+        writeln!(f, "Why it matters: {}", self.why)?;
+        writeln!(f)?;
+        write!(f, "How to fix it: {}", self.fix)
+    }
+}
+
+pub const EXPLANATIONS: &[Explanation] = &[
+    Explanation {
+        code: INVALID_DATA,
+        title: "problem in the data",
+        rule: "Something in data.yml or an imported file couldn't be used as given and was skipped.",
+        why: "Whatever was skipped doesn't count towards the report, so an account's maximum may be understated.",
+        fix: "Read the message for what was skipped and correct it in the file it names.",
+    },
+    Explanation {
+        code: UNKNOWN_KEY,
+        title: "unknown key",
+        rule: "A key in data.yml or a statement file doesn't match any field fbar_prep knows. Misspelled keys are \
+               reported with the known key they're closest to.",
+        why: "The key's value is ignored, so a typo like `closing_dtae` silently leaves an account open all year, or a \
+              balance out of its maximum.",
+        fix: "Rename the key to the one suggested, or remove it. `fbar_prep schema` prints every key that's accepted.",
+    },
+    Explanation {
+        code: OMITTED_KEY,
+        title: "optional key omitted",
+        rule: "In strict mode, optional keys have to be given explicitly, even when empty.",
+        why: "Strict mode is for checking nothing was left out by accident before filing; a missing key may be a field \
+              that was forgotten rather than deliberately left empty.",
+        fix: "Add the key with its value, or an empty value if it genuinely doesn't apply, or run without --strict.",
+    },
+    Explanation {
+        code: INVALID_IDENTIFIER,
+        title: "invalid account identifier",
+        rule: "An identifier declared as an IBAN or sort code fails that format's checks, e.g. an IBAN's check digits.",
+        why: "The account number is filed in Part II. A mistyped number identifies the wrong account, or none, and \
+              is hard to notice once filed.",
+        fix: "Copy the identifier from a statement, or change `identifier1_kind`/`identifier2_kind` if it's another \
+              kind of number.",
+    },
+    Explanation {
+        code: OUTDATED_SCHEMA,
+        title: "data.yml is an older schema version",
+        rule: "data.yml declares a schema_version older than the one this version of fbar_prep writes, or declares none and still uses an older layout.",
+        why: "Older files still load, but fields added or renamed since may be read with their old meaning or missed.",
+        fix: "Run `fbar_prep migrate`, which upgrades data.yml and keeps a backup of the original.",
+    },
+    Explanation {
+        code: LOAD_FAILED,
+        title: "data directory could not be loaded",
+        rule: "The data directory is missing data.yml, or something in it can't be read at all.",
+        why: "Nothing can be reported until the data loads.",
+        fix: "Read the message for the file and problem, or run `fbar_prep doctor` to check the directory's layout.",
+    },
+    Explanation {
+        code: INVALID_DATE,
+        title: "unparseable or ambiguous date",
+        rule: "A date in an imported statement couldn't be read, or could be read as either day-first or month-first. \
+               The row is skipped.",
+        why: "A skipped row's balance doesn't count towards the maximum, and a date read the wrong way round can move \
+              a balance into another year.",
+        fix: "Pass --date-order day-first or month-first for numeric dates, or correct the row in the statement.",
+    },
+    Explanation {
+        code: TRANSACTION_GAP,
+        title: "gap in imported transactions",
+        rule: "An export of transactions has a long stretch with none, from which balances are reconstructed.",
+        why: "Balances reconstructed across a gap are only right if there really were no transactions; a missing \
+              month of them can hide the year's maximum.",
+        fix: "Check the export covers the whole period, and export the missing stretch if it doesn't.",
+    },
+    Explanation {
+        code: BALANCE_MISMATCH,
+        title: "transactions don't add up to a stated balance",
+        rule: "A balance given in a transaction export differs from the one its transactions add up to.",
+        why: "The reconstructed daily balances, and so the maximum, are wrong somewhere between the two balances.",
+        fix: "Export the transactions again covering the whole period, or import statements with balances instead.",
+    },
+    Explanation {
+        code: UNKNOWN_CURRENCY,
+        title: "unknown Treasury currency",
+        rule: "A row of the Treasury's Reporting Rates of Exchange names a currency fbar_prep doesn't recognise.",
+        why: "Its rate isn't imported, so accounts in that currency can't be converted from the Treasury rates.",
+        fix: "Add the rate to `rates/` by hand if an account needs it; otherwise the row can be ignored.",
+    },
+    Explanation {
+        code: REPORT_FAILED,
+        title: "report could not be computed",
+        rule: "An account, or the year as a whole, couldn't be reported, e.g. for lack of an exchange rate.",
+        why: "Every reportable account has to be on the FBAR, so the report isn't complete until this is fixed.",
+        fix: "Read the message for the cause. Missing rates can be added under `rates/` or given a fallback with \
+              --rate-fallback.",
+    },
+    Explanation {
+        code: RECORDS_OUTSIDE_OPEN_PERIOD,
+        title: "statement date outside account open period",
+        rule: "Records from the year are dated before the account's opening date or after its closing date.",
+        why: "Those records are left out of the account's maximum, which is usually a mistaken opening or closing \
+              date rather than intended, and can understate the value filed.",
+        fix: "Correct the account's `opening_date` or `closing_date`, or the dates of the records.",
+    },
+    Explanation {
+        code: OPENED_AFTER_YEAR,
+        title: "account opened after the year",
+        rule: "An account in the year's report has an opening date after the end of that year.",
+        why: "An account that wasn't open during the year isn't reportable for it; its records or dates are wrong.",
+        fix: "Correct the `opening_date`, or the dates of its records.",
+    },
+    Explanation {
+        code: CLOSED_BEFORE_OPENED,
+        title: "account closed before it was opened",
+        rule: "An account's closing date is before its opening date.",
+        why: "The account's open period decides which records count towards its maximum, so one of the dates is wrong.",
+        fix: "Correct the `opening_date` or `closing_date`.",
+    },
+    Explanation {
+        code: OPENED_BEFORE_FOUNDED,
+        title: "account opened before its provider was founded",
+        rule: "An account's opening date is before the `founded` date of its provider.",
+        why: "It's usually a mistyped year, which moves the account's open period and so the records counted.",
+        fix: "Correct the account's `opening_date` or the provider's `founded` date.",
+    },
+    Explanation {
+        code: VALUE_TOO_LONG,
+        title: "value longer than the FinCEN schema allows",
+        rule: "A name, address, account number or explanation is longer than its field in the BSA E-Filing schema.",
+        why: "BSA E-Filing accepts the filing but cuts the value short, so what's on record may not identify the \
+              institution or account.",
+        fix: "Shorten the value in data.yml, e.g. by abbreviating, so nothing important is cut off.",
+    },
+    Explanation {
+        code: US_INSTITUTION,
+        title: "account at a US institution",
+        rule: "An account's provider is domiciled in the United States or one of its territories.",
+        why: "The FBAR covers foreign financial accounts; accounts at US institutions are generally not reportable.",
+        fix: "Check the provider's country. If the account isn't reportable, set `exclude_from_fbar` with an \
+              `exclusion_reason`.",
+    },
+    Explanation {
+        code: MISSING_COUNTRY,
+        title: "institution country missing or invalid",
+        rule: "An account's provider has no country, or one that isn't an ISO 3166-1 country code.",
+        why: "Part II requires the institution's country, and BSA E-Filing rejects a filing without one.",
+        fix: "Give the provider a `country`, or a structured address with one, as a two-letter code like GB.",
+    },
+    Explanation {
+        code: UNKNOWN_PROVIDER,
+        title: "unknown provider",
+        rule: "An account names a provider handle that isn't in data.yml's providers.",
+        why: "The institution's name and address are filed with each account; without them the filing is rejected.",
+        fix: "Add the provider to data.yml, or correct the account's `provider` handle.",
+    },
+    Explanation {
+        code: MISSING_ACCOUNT_NUMBER,
+        title: "no account number",
+        rule: "An account has no identifier to file as its account number.",
+        why: "Part II requires an account number or other designation, and BSA E-Filing rejects a filing without one.",
+        fix: "Add `identifier1` to the account, e.g. its IBAN or account number from a statement.",
+    },
+    Explanation {
+        code: INVALID_MAXIMUM,
+        title: "maximum value can't be filed",
+        rule: "An account's maximum value is negative or has more digits than the schema's whole-dollar field allows.",
+        why: "BSA E-Filing rejects the filing. A negative maximum is usually a sign error in the balances.",
+        fix: "Check the account's balances for a sign error, or amounts in the wrong currency or units.",
+    },
+    Explanation {
+        code: MISSING_JOINT_OWNER_TIN,
+        title: "no TIN for a joint owner",
+        rule: "A joint account's principal joint owner has no TIN.",
+        why: "Part III asks for the principal joint owner's TIN if known.",
+        fix: "Add the owner's `tin` to their filer entry if you know it; it can be left out if you don't.",
+    },
+    Explanation {
+        code: UNKNOWN_JOINT_OWNER,
+        title: "unknown joint owner",
+        rule: "A joint account names an owner that isn't among data.yml's filers.",
+        why: "Part III needs the joint owner's name and details; without them the filing is rejected.",
+        fix: "Add the owner to data.yml's filers, or correct the account's `owners`.",
+    },
+    Explanation {
+        code: MISSING_OWNER_TIN,
+        title: "no TIN for an entity",
+        rule: "An account held through an entity, reported in Part V, has no TIN for the entity.",
+        why: "Part V asks for the owning entity's US TIN, or its foreign one if it has none.",
+        fix: "Add the entity's `tin` in data.yml.",
+    },
+    Explanation {
+        code: UNKNOWN_ENTITY,
+        title: "unknown entity",
+        rule: "An account names an entity that isn't in data.yml's entities.",
+        why: "Part V needs the owning entity's name and address; without them the filing is rejected.",
+        fix: "Add the entity to data.yml, or correct the account's `entity` handle.",
+    },
+    Explanation {
+        code: MISSING_LATE_REASON,
+        title: "late filing without a reason",
+        rule: "A filing's `filed_on` date is after the deadline, but it has no `late_reason`.",
+        why: "Late FBARs have to give one of the form's reasons for filing late.",
+        fix: "Set the filing's `late_reason`, and an `explanation` if the reason is `other`.",
+    },
+    Explanation {
+        code: NEEDLESS_LATE_REASON,
+        title: "late reason on a filing that isn't late",
+        rule: "A filing has a `late_reason`, but its `filed_on` date is on or before the deadline.",
+        why: "The reason is filed with the FBAR and would say it's late when it isn't.",
+        fix: "Remove the `late_reason`, or correct the `filed_on` date.",
+    },
+    Explanation {
+        code: MISSING_LATE_EXPLANATION,
+        title: "late reason `other` without an explanation",
+        rule: "A filing's `late_reason` is `other`, but it has no `explanation`.",
+        why: "The form requires an explanation when the reason is other, and BSA E-Filing rejects the filing without it.",
+        fix: "Add the filing's `explanation`.",
+    },
+    Explanation {
+        code: LATE_REASON_IN_NARRATIVE,
+        title: "late reason given in the narrative",
+        rule: "The targeted FinCEN schema version has no late filing reason code, so the reason is written into the \
+               narrative instead.",
+        why: "The reason is still filed, but as text, and counts towards the narrative's length limit.",
+        fix: "Nothing, unless the current schema version is accepted; then target it with --schema-version.",
+    },
+    Explanation {
+        code: ACCOUNT_KIND_MISMATCH,
+        title: "account kind doesn't match its identifiers or records",
+        rule: "An account's identifiers or records suggest a different kind than the one declared, e.g. ISIN \
+               holdings in an account declared as a bank account.",
+        why: "The kind is filed as the type of account in Part II, so it should be the right one.",
+        fix: "Set the account's `kind` to the one suggested, or check the identifiers and records if it's right.",
+    },
+    Explanation {
+        code: NEAREST_YEAR_RATE,
+        title: "converted at another year's rate",
+        rule: "There's no exchange rate for the year, so the nearest year's rate was used under --rate-fallback.",
+        why: "Maxima have to be converted at the Treasury's year-end rate; another year's rate may misstate them.",
+        fix: "Add the year's rate under `rates/`, e.g. with `fbar_prep facts import-treasury`, once it's published.",
+    },
+    Explanation {
+        code: DUAL_STATUS_YEAR,
+        title: "not a US person for all of the year",
+        rule: "One of data.yml's `nonresident_periods` falls in the year, so the filer was a US person for only part \
+               of it, or none.",
+        why: "An FBAR is due for years the filer was a US person, and asks for each account's maximum during the \
+              calendar year. How the two combine in a dual-status year isn't settled, so the report leaves the \
+              maxima covering the whole year and notes the nonresident time on each account it affects.",
+        fix: "Nothing to fix in the data. Check the account notes with your tax advisor before filing; for the year \
+              you became a US person, `us_person_since` leaves the earlier values out instead.",
+    },
+    Explanation {
+        code: DUPLICATE_FACT,
+        title: "year or rate given twice in facts",
+        rule: "A facts file (the bundled or updated IRS rates, `fact_extensions` in data.yml, or a rate set under \
+               `rates/`) has two entries for the same year, or two rates for the same currency in one year.",
+        why: "Neither entry can be taken over the other without guessing which is meant, and picking one silently \
+              would change every conversion in the year if it's the wrong one, so the file is refused rather than \
+              merged.",
+        fix: "Merge the year's entries into one, keeping a single rate for each currency.",
+    },
+    Explanation {
+        code: UNSUPPORTED_CHARACTERS,
+        title: "characters BSA E-Filing doesn't accept",
+        rule: "A value written to the XML, like an institution's name or address, has characters other than \
+               printable ASCII after transliteration. Latin letters with diacritics, kana and full-width forms are \
+               transliterated; scripts like kanji, Cyrillic or Hangul aren't.",
+        why: "BSA E-Filing only accepts a restricted character set, so a filing with other characters is refused on \
+              upload.",
+        fix: "Add the text and how to write it under `transliteration.replacements` in data.yml, e.g. \
+              `三菱UFJ銀行: MUFG Bank`, using the romanized name the institution uses itself where it has one. \
+              `fbar_prep transliterations YEAR` lists every value the export changes.",
+    },
+    Explanation {
+        code: AMBIGUOUS_BRANCH_COUNTRY,
+        title: "institution country ambiguous",
+        rule: "A provider's `country` and the country in its structured address differ, or a provider marked with \
+               `foreign_branch_of` is domiciled in the United States.",
+        why: "A foreign branch of a US institution is filed with the branch's address and country. Giving the head \
+              office's instead makes the account look like one at a US institution, which isn't reportable, and \
+              leaves it unclear which country is meant.",
+        fix: "Give the branch's own address and country, with `foreign_branch_of` set to the US institution's name. \
+              If the account is actually held at a US office, remove `foreign_branch_of`.",
+    },
+    Explanation {
+        code: UNMARKED_FOREIGN_BRANCH,
+        title: "possible foreign branch of a US institution",
+        rule: "A provider outside the United States has a name ending like a US national bank's, such as `N.A.` or \
+               `National Association`, without `foreign_branch_of`.",
+        why: "Accounts at foreign branches of US banks are reportable, but are easily mistaken for US accounts or \
+              filed under the branch's local name rather than the institution's.",
+        fix: "If the provider is a foreign branch, set `foreign_branch_of` to the US institution's legal name, and \
+              name the provider after the branch, e.g. `London`.",
+    },
+    Explanation {
+        code: AMBIGUOUS_AMOUNT,
+        title: "ambiguous statement amount",
+        rule: "An imported amount like `1.234` reads as a different number with `.` or `,` as the decimal separator, \
+               and nothing in the statement or its import profile decides which.",
+        why: "A European export read with the wrong separator is off by a factor of 1000, which inflates or hides a \
+              maximum account value without any other sign of a problem.",
+        fix: "Set `decimal_separator` (and `thousands_separator` if it isn't the other of `.` and `,`) in the \
+              account's import profile, then import the statement again.",
+    },
+];
+
+/// The explanation of a code, ignoring case
+pub fn explain(code: &str) -> Option<&'static Explanation> {
+    EXPLANATIONS
+        .iter()
+        .find(|explanation| explanation.code.eq_ignore_ascii_case(code.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_codes_are_unique_and_explained() {
+        let codes: BTreeSet<&str> = EXPLANATIONS
+            .iter()
+            .map(|explanation| explanation.code)
+            .collect();
+        assert_eq!(codes.len(), EXPLANATIONS.len());
+        assert_eq!(
+            codes.last().copied(),
+            Some(AMBIGUOUS_AMOUNT),
+            "the newest code should be explained"
+        );
+        assert!(codes
+            .iter()
+            .all(|code| code.len() == 6 && code.starts_with("FB")));
+
+        let explanation = explain("fb0012").unwrap();
+        assert_eq!(
+            explanation.title,
+            "statement date outside account open period"
+        );
+        assert!(explanation
+            .to_string()
+            .starts_with("FB0012: statement date"));
+        assert!(explain("FB9999").is_none());
+    }
+}
@@ -0,0 +1,426 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::Path;
+
+use crate::crypto::CryptoValuation;
+use crate::data::UserData;
+use crate::facts::Facts;
+use crate::parsing::ParseContext;
+use crate::report::OpenPeriod;
+use crate::report_context::ReportContext;
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Severity {
+    /// Worth fixing, but reports can still be generated
+    Warning,
+    /// Stops reports for the year from being generated, or generated correctly
+    Problem,
+}
+
+/// Something wrong with the data directory, with what to do about it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+    pub fix: String,
+}
+
+impl Finding {
+    fn warning(message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+            fix: fix.into(),
+        }
+    }
+
+    fn problem(message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Problem,
+            message: message.into(),
+            fix: fix.into(),
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Problem => "problem",
+        };
+        write!(f, "{}: {}\n  fix: {}", severity, self.message, self.fix)
+    }
+}
+
+/// Checks that the data directory is laid out as expected, loads cleanly, has exchange rates for every currency
+/// reported in the year, and keeps its sensitive files private. An empty result means all is well.
+pub fn diagnose(
+    base_path: &Path,
+    facts: &Facts,
+    year: i32,
+    rate_precedence: Option<Vec<String>>,
+    context: &ParseContext,
+) -> Vec<Finding> {
+    let mut findings = check_layout(base_path);
+    if findings
+        .iter()
+        .any(|finding| finding.severity == Severity::Problem)
+    {
+        return findings;
+    }
+
+    let loaded = UserData::load_with_context(base_path, context);
+    findings.extend(context.warnings().into_iter().map(|warning| {
+        Finding::warning(
+            warning,
+            "Correct the entry, or leave it as is if it's intended; `--strict` treats these as errors",
+        )
+    }));
+    match loaded {
+        Ok(user_data) => findings.extend(check_rates(&user_data, facts, year, rate_precedence)),
+        Err(err) => findings.push(Finding::problem(
+            format!("The data directory doesn't load: {:#}", err),
+            "Fix data.yml or the file named; `fbar_prep DATA_DIR schema` describes the format",
+        )),
+    }
+
+    findings.extend(check_permissions(base_path));
+    findings
+}
+
+fn check_layout(base_path: &Path) -> Vec<Finding> {
+    if !base_path.is_dir() {
+        return vec![Finding::problem(
+            format!("{:?} is not a directory", base_path),
+            "Pass the path of the directory holding data.yml",
+        )];
+    }
+    if !base_path.join("data.yml").is_file() {
+        return vec![Finding::problem(
+            format!("{:?} has no data.yml", base_path),
+            "Create data.yml listing your providers and accounts; see the readme for an example",
+        )];
+    }
+
+    let mut findings = Vec::new();
+    for dir in ["statements", "rates", "prices"] {
+        let path = base_path.join(dir);
+        if path.exists() && !path.is_dir() {
+            findings.push(Finding::problem(
+                format!("{:?} is not a directory", path),
+                format!("Move it aside; fbar_prep keeps {} in that directory", dir),
+            ));
+            continue;
+        }
+
+        let Ok(entries) = std::fs::read_dir(&path) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let entry_path = entry.path();
+            let is_yaml = entry_path
+                .extension()
+                .is_some_and(|ext| ext == "yml" || ext == "yaml");
+            if !is_yaml {
+                let fix = match dir {
+                    "statements" => {
+                        "Import statements with `fbar_prep DATA_DIR import <account> <file>` and keep the originals elsewhere"
+                    }
+                    "prices" => {
+                        "Import prices with `fbar_prep DATA_DIR import-prices <asset> <file>` and keep the originals elsewhere"
+                    }
+                    _ => "Move it out of the data directory; only .yml files there are loaded",
+                };
+                findings.push(Finding::warning(
+                    format!("{:?} is ignored because it isn't a .yml file", entry_path),
+                    fix,
+                ));
+            }
+        }
+    }
+
+    findings
+}
+
+fn check_rates(
+    user_data: &UserData,
+    facts: &Facts,
+    year: i32,
+    rate_precedence: Option<Vec<String>>,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    if !facts.years.iter().any(|fact| fact.year == year) {
+        findings.push(Finding::warning(
+            format!("The bundled IRS rates don't cover {}", year),
+            "Upgrade fbar_prep, or import the Treasury's year-end rates with `fbar_prep DATA_DIR facts import-treasury <csv>`",
+        ));
+    }
+
+    let context = ReportContext::new(facts.clone(), user_data.fact_extensions.clone())
+        .with_rate_sets(user_data.rate_sets.clone())
+        .with_valuation(Box::new(CryptoValuation::new(
+            user_data.price_series.clone(),
+        )));
+    let context = match rate_precedence.or_else(|| user_data.rate_precedence.clone()) {
+        Some(precedence) => match context.with_precedence(precedence) {
+            Ok(context) => context,
+            Err(err) => {
+                findings.push(Finding::problem(
+                    err.to_string(),
+                    "Name only `user`, `irs` or rate sets in the `rates` directory in rate_precedence",
+                ));
+                return findings;
+            }
+        },
+        None => context,
+    };
+    let context = match context.with_rate_pins(&user_data.rate_pins) {
+        Ok(context) => context,
+        Err(err) => {
+            findings.push(Finding::problem(
+                err.to_string(),
+                "Pin currencies only to `user`, `irs` or rate sets in the `rates` directory in rate_pins",
+            ));
+            return findings;
+        }
+    };
+
+    let mut currencies = BTreeSet::new();
+    for account in &user_data.accounts {
+        if account.exclude_from_fbar || OpenPeriod::for_account(account, year).is_none() {
+            continue;
+        }
+        if let Err(err) = context.valuation_for(account) {
+            findings.push(Finding::problem(
+                err.to_string(),
+                "Remove the account's `valuation`, or register the valuation it names",
+            ));
+        }
+
+        currencies.insert(account.currency_code.to_lowercase());
+        currencies.extend(
+            account
+                .balances_in_year(year)
+                .filter_map(|record| record.currency.as_ref())
+                .map(|currency| currency.to_lowercase()),
+        );
+    }
+
+    for currency in currencies {
+        if context.find_exchange_rate(year, &currency).is_err() {
+            findings.push(Finding::problem(
+                format!("No {} exchange rate for {}", currency.to_uppercase(), year),
+                format!(
+                    "Add it under fact_extensions in data.yml, or import the Treasury's rates for {} with `fbar_prep DATA_DIR facts import-treasury <csv>`",
+                    year
+                ),
+            ));
+        }
+    }
+
+    findings
+}
+
+/// Flags data files and directories that other users on the machine can read, since they hold account numbers and
+/// balances
+#[cfg(unix)]
+fn check_permissions(base_path: &Path) -> Vec<Finding> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut paths = vec![base_path.to_path_buf(), base_path.join("data.yml")];
+    if base_path.join("reviews.yml").is_file() {
+        paths.push(base_path.join("reviews.yml"));
+    }
+    for dir in ["statements", ".fbar_prep"] {
+        let dir = base_path.join(dir);
+        if dir.is_dir() {
+            paths.push(dir);
+        }
+    }
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let mode = std::fs::metadata(&path).ok()?.permissions().mode() & 0o777;
+            if mode & 0o077 == 0 {
+                return None;
+            }
+            let private = if path.is_dir() { "700" } else { "600" };
+            Some(Finding::warning(
+                format!("{:?} is accessible to other users (mode {:o})", path, mode),
+                format!("chmod {} {:?}", private, path),
+            ))
+        })
+        .collect()
+}
+
+#[cfg(not(unix))]
+fn check_permissions(_base_path: &Path) -> Vec<Finding> {
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::facts::{AnnualFact, ExchangeRate};
+    use anyhow::Result;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn create_test_dir(accounts: &str) -> Result<TempDir> {
+        let temp_dir = TempDir::new()?;
+        let yaml_content = format!(
+            r#"
+schema_version: 2
+providers:
+  - name: "Example Bank"
+    handle: "example_bank"
+    address: "1 Example Street, London, UK"
+accounts:
+{}
+"#,
+            accounts
+        );
+        fs::write(temp_dir.path().join("data.yml"), yaml_content)?;
+        Ok(temp_dir)
+    }
+
+    fn facts() -> Facts {
+        Facts {
+            years: vec![AnnualFact {
+                year: 2023,
+                exchange_rates: vec![ExchangeRate::new("GBP".to_string(), 0.8).unwrap()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn make_private(dir: &TempDir) -> Result<()> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o700))?;
+            fs::set_permissions(
+                dir.path().join("data.yml"),
+                fs::Permissions::from_mode(0o600),
+            )?;
+        }
+        Ok(())
+    }
+
+    const GBP_ACCOUNT: &str = r#"
+  - handle: "savings"
+    provider: "example_bank"
+    currency_code: "GBP"
+    identifier1_name: "Account number"
+    identifier1: "12345678"
+    opening_date: "2020-01-01"
+    closing_date: null
+    exclusion_reason: null
+    identifier2_name: null
+    identifier2: null
+    balances:
+      - date: "2023-01-31"
+        amount: 100
+      - date: "2023-02-28"
+        amount: 50
+        currency: "EUR"
+"#;
+
+    #[test]
+    fn test_healthy_directory() -> Result<()> {
+        let dir = create_test_dir(&GBP_ACCOUNT.replace("        currency: \"EUR\"\n", ""))?;
+        make_private(&dir)?;
+
+        let findings = diagnose(dir.path(), &facts(), 2023, None, &ParseContext::default());
+        assert_eq!(findings, vec![]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_rates() -> Result<()> {
+        let dir = create_test_dir(GBP_ACCOUNT)?;
+        make_private(&dir)?;
+
+        let findings = diagnose(dir.path(), &facts(), 2023, None, &ParseContext::default());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Problem);
+        assert_eq!(findings[0].message, "No EUR exchange rate for 2023");
+
+        // A year the bundled facts don't cover at all
+        let findings = diagnose(dir.path(), &facts(), 2024, None, &ParseContext::default());
+        assert_eq!(
+            findings[0].message,
+            "The bundled IRS rates don't cover 2024"
+        );
+        assert_eq!(findings.len(), 2);
+
+        let findings = diagnose(
+            dir.path(),
+            &facts(),
+            2023,
+            Some(vec!["ecb".to_string()]),
+            &ParseContext::default(),
+        );
+        assert!(findings[0].message.contains("Unknown rate source"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unloadable_data() -> Result<()> {
+        let dir = TempDir::new()?;
+        let findings = diagnose(dir.path(), &facts(), 2023, None, &ParseContext::default());
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0].message.ends_with("has no data.yml"));
+
+        let dir = create_test_dir("  - handle: [")?;
+        make_private(&dir)?;
+        let findings = diagnose(dir.path(), &facts(), 2023, None, &ParseContext::default());
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0]
+            .message
+            .starts_with("The data directory doesn't load"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_layout_and_permissions() -> Result<()> {
+        let dir = create_test_dir(&GBP_ACCOUNT.replace("        currency: \"EUR\"\n", ""))?;
+        make_private(&dir)?;
+        fs::create_dir(dir.path().join("statements"))?;
+        fs::write(
+            dir.path().join("statements").join("june.csv"),
+            "date,amount\n",
+        )?;
+
+        let findings = diagnose(dir.path(), &facts(), 2023, None, &ParseContext::default());
+        assert!(findings[0].message.contains("june.csv"));
+        assert!(findings
+            .iter()
+            .all(|finding| finding.severity == Severity::Warning));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(
+                dir.path().join("data.yml"),
+                fs::Permissions::from_mode(0o644),
+            )?;
+            let findings = diagnose(dir.path(), &facts(), 2023, None, &ParseContext::default());
+            let data_yml = findings
+                .iter()
+                .find(|finding| finding.message.contains("data.yml"))
+                .unwrap();
+            assert!(data_yml.message.ends_with("(mode 644)"));
+            assert!(data_yml.fix.starts_with("chmod 600"));
+        }
+
+        Ok(())
+    }
+}
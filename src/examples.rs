@@ -0,0 +1,327 @@
+//! Canonical example data directories, each a common filing situation, for learning the `data.yml` format by
+//! example and as integration fixtures. Unlike `fixture`, which generates data of any size for benchmarking, the
+//! examples are small, fixed and meant to be read.
+
+use anyhow::{bail, Result};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::data::migrate::SCHEMA_VERSION;
+use crate::data::{
+    Account, Address, BalanceRecord, Entity, Filer, IdentifierKind, Provider, SignatureAuthority,
+    StructuredAddress, UserData,
+};
+use crate::fixture::{iban, SplitMix64};
+use crate::report::ManyAccountsMode;
+
+/// The year every example holds balances for
+pub const EXAMPLE_YEAR: i32 = 2023;
+
+/// Accounts in the many-accounts example, enough to file only their number
+const MANY_ACCOUNTS: usize = 26;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Example {
+    /// One filer with a current account, a savings account opened during the year and a euro account abroad
+    SingleFiler,
+    /// Spouses with a joint account and one each of their own
+    JointCouple,
+    /// A filer with 26 accounts, filing only their number
+    ManyAccounts,
+    /// A filer holding accounts through a company they control, and one through a company they don't
+    EntityHeld,
+    /// A filer with an account of their own who can also sign for their employer's account abroad
+    SignatureAuthority,
+}
+
+impl Example {
+    pub const ALL: [Example; 5] = [
+        Example::SingleFiler,
+        Example::JointCouple,
+        Example::ManyAccounts,
+        Example::EntityHeld,
+        Example::SignatureAuthority,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Example::SingleFiler => "single-filer",
+            Example::JointCouple => "joint-couple",
+            Example::ManyAccounts => "many-accounts",
+            Example::EntityHeld => "entity-held",
+            Example::SignatureAuthority => "signature-authority",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Example::SingleFiler => {
+                "One filer with a current account, a savings account opened during the year and a euro account abroad"
+            }
+            Example::JointCouple => "Spouses with a joint account and one each of their own",
+            Example::ManyAccounts => "A filer with 26 accounts, filing only their number",
+            Example::EntityHeld => {
+                "A filer holding accounts through a company they control, and one through a company they don't"
+            }
+            Example::SignatureAuthority => {
+                "A filer with an account of their own who can also sign for their employer's account abroad"
+            }
+        }
+    }
+}
+
+impl fmt::Display for Example {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Example {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match Example::ALL.iter().find(|example| example.name() == name) {
+            Some(example) => Ok(*example),
+            None => bail!(
+                "Unknown example {:?}, expected one of: {}",
+                name,
+                Example::ALL.map(|example| example.name()).join(", ")
+            ),
+        }
+    }
+}
+
+/// Generates the example's data. The same example always generates the same data.
+pub fn generate(example: Example) -> UserData {
+    let mut rng = SplitMix64(example as u64 + 1);
+    let mut user_data = UserData {
+        schema_version: Some(SCHEMA_VERSION),
+        filers: vec![filer("Alex Example", "alex", "123-45-6789")],
+        providers: vec![
+            provider(
+                "Example Bank",
+                "example_bank",
+                "1 High Street",
+                "London",
+                "GB",
+            ),
+            provider(
+                "Beispielbank",
+                "beispielbank",
+                "Hauptstrasse 1",
+                "Berlin",
+                "DE",
+            ),
+        ],
+        ..Default::default()
+    };
+
+    match example {
+        Example::SingleFiler => {
+            user_data.accounts = vec![
+                account("current", "example_bank", "GBP", 3_200, &mut rng),
+                Account {
+                    opening_date: NaiveDate::from_ymd_opt(EXAMPLE_YEAR, 5, 1),
+                    ..account("savings", "example_bank", "GBP", 12_000, &mut rng)
+                },
+                account("girokonto", "beispielbank", "EUR", 8_500, &mut rng),
+            ];
+        }
+        Example::JointCouple => {
+            user_data
+                .filers
+                .push(filer("Sam Example", "sam", "987-65-4321"));
+            user_data.accounts = vec![
+                Account {
+                    owners: vec!["alex".to_string(), "sam".to_string()],
+                    ..account("joint_current", "example_bank", "GBP", 6_400, &mut rng)
+                },
+                account("alex_savings", "example_bank", "GBP", 9_000, &mut rng),
+                Account {
+                    owners: vec!["sam".to_string()],
+                    ..account("sam_girokonto", "beispielbank", "EUR", 4_300, &mut rng)
+                },
+            ];
+        }
+        Example::ManyAccounts => {
+            user_data.many_accounts = Some(ManyAccountsMode::Summary);
+            user_data.accounts = (1..=MANY_ACCOUNTS)
+                .map(|index| {
+                    let (provider, currency) = if index % 2 == 0 {
+                        ("beispielbank", "EUR")
+                    } else {
+                        ("example_bank", "GBP")
+                    };
+                    let base = 500 * index as i64;
+                    account(
+                        &format!("account_{}", index),
+                        provider,
+                        currency,
+                        base,
+                        &mut rng,
+                    )
+                })
+                .collect();
+        }
+        Example::EntityHeld => {
+            user_data.entities = vec![
+                Entity {
+                    name: "Example Holdings Ltd".to_string(),
+                    handle: "holdings".to_string(),
+                    address: structured_address("2 High Street", "London", "GB"),
+                    tin: Some("12-3456789".to_string()),
+                    ownership_percent: Decimal::from(100),
+                },
+                Entity {
+                    name: "Beispiel GmbH".to_string(),
+                    handle: "gmbh".to_string(),
+                    address: structured_address("Hauptstrasse 2", "Berlin", "DE"),
+                    tin: None,
+                    ownership_percent: Decimal::from(30),
+                },
+            ];
+            user_data.accounts = vec![
+                account("current", "example_bank", "GBP", 2_800, &mut rng),
+                Account {
+                    entity: Some("holdings".to_string()),
+                    ..account("holdings_current", "example_bank", "GBP", 48_000, &mut rng)
+                },
+                Account {
+                    entity: Some("gmbh".to_string()),
+                    ..account("gmbh_konto", "beispielbank", "EUR", 150_000, &mut rng)
+                },
+            ];
+        }
+        Example::SignatureAuthority => {
+            user_data.accounts = vec![
+                account("current", "example_bank", "GBP", 4_100, &mut rng),
+                Account {
+                    signature_authority: Some(SignatureAuthority {
+                        owner: "Beispiel AG".to_string(),
+                        address: structured_address("Hauptstrasse 3", "Berlin", "DE"),
+                        tin: None,
+                        title: "Treasurer".to_string(),
+                    }),
+                    ..account("employer_konto", "beispielbank", "EUR", 75_000, &mut rng)
+                },
+            ];
+        }
+    }
+    user_data
+}
+
+/// Writes the example's data directory at `out`, refusing to overwrite one that's already there
+pub fn write_example(out: &Path, example: Example) -> Result<UserData> {
+    let out_yaml = out.join("data.yml");
+    if out_yaml.exists() {
+        bail!("{:?} already exists, refusing to overwrite it", out_yaml);
+    }
+
+    let user_data = generate(example);
+    std::fs::create_dir_all(out)?;
+    std::fs::write(out_yaml, serde_yaml::to_string(&user_data)?)?;
+    Ok(user_data)
+}
+
+fn filer(name: &str, handle: &str, tin: &str) -> Filer {
+    Filer {
+        name: name.to_string(),
+        handle: handle.to_string(),
+        tin: Some(tin.to_string()),
+        address: None,
+    }
+}
+
+fn structured_address(street: &str, city: &str, country: &str) -> Address {
+    Address::Structured(StructuredAddress {
+        street: street.to_string(),
+        city: city.to_string(),
+        state: None,
+        postal_code: None,
+        country: country.to_string(),
+    })
+}
+
+fn provider(name: &str, handle: &str, street: &str, city: &str, country: &str) -> Provider {
+    Provider {
+        name: name.to_string(),
+        handle: handle.to_string(),
+        address: structured_address(street, city, country),
+        ..Default::default()
+    }
+}
+
+/// An account with an IBAN from the provider's country and a balance at the end of every quarter, varying by up
+/// to 10% around `base`
+fn account(
+    handle: &str,
+    provider: &str,
+    currency: &str,
+    base: i64,
+    rng: &mut SplitMix64,
+) -> Account {
+    let country = if provider == "beispielbank" {
+        "DE"
+    } else {
+        "GB"
+    };
+    let balances = [(3, 31), (6, 30), (9, 30), (12, 31)]
+        .into_iter()
+        .map(|(month, day)| {
+            let cents = base * 90 + rng.below(base as u64 * 20 + 1) as i64;
+            BalanceRecord {
+                date: NaiveDate::from_ymd_opt(EXAMPLE_YEAR, month, day).unwrap(),
+                amount: Decimal::new(cents, 2),
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    Account {
+        handle: handle.to_string(),
+        provider: provider.to_string(),
+        currency_code: currency.to_string(),
+        identifier1_kind: Some(IdentifierKind::Iban),
+        identifier1: Some(iban(country, rng)),
+        balances,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::facts::Facts;
+    use crate::parsing::ParseContext;
+    use crate::report::Report;
+    use crate::report_context::ReportContext;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_examples() -> Result<()> {
+        for example in Example::ALL {
+            assert_eq!(example.name().parse::<Example>()?, example);
+
+            let dir = TempDir::new()?;
+            write_example(dir.path(), example)?;
+            assert!(write_example(dir.path(), example).is_err());
+
+            // Loads cleanly and reports
+            let context = ParseContext::default();
+            let user_data = UserData::load_with_context(dir.path(), &context)?;
+            assert_eq!(context.warnings(), Vec::<String>::new(), "{}", example);
+            assert_eq!(
+                serde_yaml::to_string(&user_data)?,
+                serde_yaml::to_string(&generate(example))?
+            );
+            let context = ReportContext::new(Facts::load_facts().unwrap(), None);
+            Report::generate(EXAMPLE_YEAR, &user_data, &context)?;
+        }
+        assert!("signature".parse::<Example>().is_err());
+
+        Ok(())
+    }
+}
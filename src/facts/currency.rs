@@ -0,0 +1,113 @@
+use anyhow::{bail, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::treasury::is_treasury_currency;
+use crate::money;
+
+/// Most decimal places a custom currency can have
+pub const MAX_MINOR_UNITS: u32 = 8;
+
+/// A currency the tool doesn't know of itself, defined in `fact_extensions`, e.g. a discontinued currency, an
+/// informal one, or a fund's units. Its rates are given like any other currency's.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct CurrencyDefinition {
+    /// Three to eight letters or digits, starting with a letter; stored lowercase like other currency codes
+    pub code: String,
+    /// How the currency is described in the report, e.g. "Acme Global Fund units"
+    pub name: String,
+    /// Decimal places amounts in the currency are written and rounded to
+    #[serde(default = "default_minor_units")]
+    #[schemars(range(max = 8))]
+    pub minor_units: u32,
+}
+
+fn default_minor_units() -> u32 {
+    2
+}
+
+impl CurrencyDefinition {
+    pub(crate) fn validate(&self) -> Result<()> {
+        let code = &self.code;
+        let well_formed = (3..=8).contains(&code.len())
+            && code.starts_with(|c: char| c.is_ascii_alphabetic())
+            && code.chars().all(|c| c.is_ascii_alphanumeric());
+        if !well_formed {
+            bail!(
+                "Currency code {:?} in fact_extensions must be 3 to 8 letters or digits, starting with a letter",
+                code
+            );
+        }
+        if money::is_builtin_currency(code) || is_treasury_currency(code) {
+            bail!(
+                "Currency {} in fact_extensions is already known; give only its rates",
+                code.to_uppercase()
+            );
+        }
+        if self.name.trim().is_empty() {
+            bail!(
+                "Currency {} in fact_extensions has no name",
+                code.to_uppercase()
+            );
+        }
+        if self.minor_units > MAX_MINOR_UNITS {
+            bail!(
+                "Currency {} in fact_extensions has {} minor units; at most {} are supported",
+                code.to_uppercase(),
+                self.minor_units,
+                MAX_MINOR_UNITS
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::facts::Facts;
+    use crate::money::{minor_units, Money};
+    use rust_decimal_macros::dec;
+
+    fn definition(code: &str, minor_units: u32) -> CurrencyDefinition {
+        CurrencyDefinition {
+            code: code.to_string(),
+            name: "Acme Global Fund units".to_string(),
+            minor_units,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(definition("acmefund", 4).validate().is_ok());
+        assert!(definition("xx", 2).validate().is_err());
+        assert!(definition("1abc", 2).validate().is_err());
+        assert!(definition("ac-me", 2).validate().is_err());
+        assert!(definition("eur", 2).validate().is_err());
+        assert!(definition("CHF", 2).validate().is_err());
+        assert!(definition("acme", 9).validate().is_err());
+
+        let mut unnamed = definition("acme", 2);
+        unnamed.name = " ".to_string();
+        assert!(unnamed.validate().is_err());
+    }
+
+    #[test]
+    fn test_minor_units() {
+        let mut extensions = Facts::empty();
+        extensions.currencies = vec![definition("ZZFUND", 4)];
+
+        assert_eq!(extensions.minor_units("zzfund"), 4);
+        assert_eq!(extensions.minor_units("jpy"), 0);
+        assert_eq!(extensions.minor_units("eur"), 2);
+        // Only the facts defining the currency know its minor units
+        assert_eq!(Facts::empty().minor_units("zzfund"), 2);
+        assert_eq!(minor_units("zzfund"), 2);
+
+        let units = Money::new(dec!(1234.56789), "zzfund");
+        assert_eq!(
+            units.round_to(extensions.minor_units("zzfund")).amount,
+            dec!(1234.5679)
+        );
+    }
+}
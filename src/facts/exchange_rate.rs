@@ -1,8 +1,15 @@
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+use schemars::JsonSchema;
 use serde::{Deserialize, Deserializer, Serialize};
 
-#[derive(Debug, Serialize, Clone)]
+use crate::money::{minor_units, Money, USD};
+
+#[derive(Debug, Serialize, JsonSchema, Clone)]
 pub struct ExchangeRate {
     pub currency_code: String,
+    /// Units of the currency per USD
+    #[schemars(extend("exclusiveMinimum" = 0))]
     pub rate: f64,
 }
 
@@ -25,16 +32,50 @@ impl ExchangeRate {
         })
     }
 
-    /// Converts an amount from USD to the target currency
-    pub fn convert_from_usd(&self, amount: f64) -> f64 {
-        let result = amount * self.rate;
-        (result * 100.0).round() / 100.0
+    /// Decimal places amounts in the rate's currency are rounded to
+    pub fn minor_units(&self) -> u32 {
+        minor_units(&self.currency_code)
+    }
+
+    /// Converts an amount from USD to the target currency, rounded to its minor units
+    pub fn convert_from_usd(&self, amount: &Money) -> anyhow::Result<Money> {
+        Ok(self.convert_from_usd_unrounded(amount)?.round())
+    }
+
+    /// Converts an amount from USD to the target currency without rounding, for currencies rounded to minor units
+    /// defined elsewhere
+    pub fn convert_from_usd_unrounded(&self, amount: &Money) -> anyhow::Result<Money> {
+        amount.expect_currency(USD)?;
+        let result = amount
+            .amount
+            .checked_mul(self.decimal_rate()?)
+            .ok_or_else(|| {
+                anyhow::anyhow!("{} is too large to convert at {}", amount, self.rate)
+            })?;
+        Ok(Money::new(result, &self.currency_code))
     }
 
-    /// Converts an amount from the target currency to USD
-    pub fn convert_to_usd(&self, amount: f64) -> f64 {
-        let result = amount / self.rate;
-        (result * 100.0).round() / 100.0
+    /// Converts an amount from the target currency to USD, rounded to the cent
+    pub fn convert_to_usd(&self, amount: &Money) -> anyhow::Result<Money> {
+        Ok(self.convert_to_usd_unrounded(amount)?.round())
+    }
+
+    /// Converts an amount from the target currency to USD without rounding, as recorded in the audit trail
+    pub fn convert_to_usd_unrounded(&self, amount: &Money) -> anyhow::Result<Money> {
+        amount.expect_currency(&self.currency_code)?;
+        let result = amount
+            .amount
+            .checked_div(self.decimal_rate()?)
+            .ok_or_else(|| {
+                anyhow::anyhow!("{} is too large to convert at {}", amount, self.rate)
+            })?;
+        Ok(Money::usd(result))
+    }
+
+    fn decimal_rate(&self) -> anyhow::Result<Decimal> {
+        Decimal::from_f64(self.rate)
+            .filter(|rate| *rate > Decimal::ZERO)
+            .ok_or_else(|| anyhow::anyhow!("Invalid exchange rate {}", self.rate))
     }
 }
 
@@ -58,37 +99,85 @@ impl<'de> Deserialize<'de> for ExchangeRate {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use anyhow::Result;
+    use rust_decimal_macros::dec;
+
+    fn usd(amount: Decimal) -> Money {
+        Money::usd(amount)
+    }
+
+    fn eur(amount: Decimal) -> Money {
+        Money::new(amount, "eur")
+    }
 
     #[test]
-    fn test_currency_conversion() {
+    fn test_currency_conversion() -> Result<()> {
         let rate = ExchangeRate {
             currency_code: "EUR".to_string(),
             rate: 0.85, // Example rate: 1 USD = 0.85 EUR
         };
 
         // Test USD to EUR conversion
-        assert_eq!(rate.convert_from_usd(100.0), 85.0); // 100 USD = 85 EUR
-        assert_eq!(rate.convert_from_usd(50.0), 42.5); // 50 USD = 42.5 EUR
+        assert_eq!(rate.convert_from_usd(&usd(dec!(100.0)))?, eur(dec!(85.0))); // 100 USD = 85 EUR
+        assert_eq!(rate.convert_from_usd(&usd(dec!(50.0)))?, eur(dec!(42.5))); // 50 USD = 42.5 EUR
 
         // Test EUR to USD conversion
-        assert_eq!(rate.convert_to_usd(85.0), 100.0); // 85 EUR = 100 USD
-        assert_eq!(rate.convert_to_usd(42.5), 50.0); // 42.5 EUR = 50 USD
+        assert_eq!(rate.convert_to_usd(&eur(dec!(85.0)))?, usd(dec!(100.0))); // 85 EUR = 100 USD
+        assert_eq!(rate.convert_to_usd(&eur(dec!(42.5)))?, usd(dec!(50.0))); // 42.5 EUR = 50 USD
+
+        Ok(())
     }
 
     #[test]
-    fn test_rounding() {
+    fn test_rounding() -> Result<()> {
         let rate = ExchangeRate {
             currency_code: "EUR".to_string(),
             rate: 0.333, // Rate: 1 USD = 0.333 EUR
         };
 
         // Test rounding to 2 decimal places
-        assert_eq!(rate.convert_from_usd(1.00), 0.33); // 1 USD = 0.33 EUR
-        assert_eq!(rate.convert_from_usd(10.00), 3.33); // 10 USD = 3.33 EUR
+        assert_eq!(rate.convert_from_usd(&usd(dec!(1.00)))?, eur(dec!(0.33))); // 1 USD = 0.33 EUR
+        assert_eq!(rate.convert_from_usd(&usd(dec!(10.00)))?, eur(dec!(3.33))); // 10 USD = 3.33 EUR
 
         // Test EUR to USD conversion with rounding
-        assert_eq!(rate.convert_to_usd(0.33), 0.99); // 0.33 EUR ≈ 0.99 USD
-        assert_eq!(rate.convert_to_usd(3.33), 10.00); // 3.33 EUR = 10.00 USD
+        assert_eq!(rate.convert_to_usd(&eur(dec!(0.33)))?, usd(dec!(0.99))); // 0.33 EUR ≈ 0.99 USD
+        assert_eq!(rate.convert_to_usd(&eur(dec!(3.33)))?, usd(dec!(10.00))); // 3.33 EUR = 10.00 USD
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_minor_units() -> Result<()> {
+        let yen = ExchangeRate::new("JPY".to_string(), 151.35).unwrap();
+        assert_eq!(yen.minor_units(), 0);
+        assert_eq!(
+            yen.convert_from_usd(&usd(dec!(10.00)))?,
+            Money::new(dec!(1514), "jpy")
+        );
+
+        let dinar = ExchangeRate::new("KWD".to_string(), 0.307).unwrap();
+        assert_eq!(
+            dinar.convert_from_usd(&usd(dec!(10.00)))?,
+            Money::new(dec!(3.070), "kwd")
+        );
+        assert_eq!(
+            dinar
+                .convert_to_usd_unrounded(&Money::new(dec!(1), "kwd"))?
+                .round(),
+            dinar.convert_to_usd(&Money::new(dec!(1), "kwd"))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrong_currency() {
+        let rate = ExchangeRate::new("EUR".to_string(), 0.85).unwrap();
+
+        // A GBP amount can't be converted with the EUR rate
+        assert!(rate.convert_to_usd(&Money::new(dec!(10), "gbp")).is_err());
+        // Only USD amounts can be converted from USD
+        assert!(rate.convert_from_usd(&eur(dec!(10))).is_err());
     }
 
     #[test]
@@ -103,4 +192,66 @@ mod tests {
         let rate3 = ExchangeRate::new("eUr".to_string(), 0.85).unwrap();
         assert_eq!(rate3.currency_code, "eur");
     }
+
+    mod properties {
+        use super::*;
+        use proptest::prelude::*;
+
+        /// Amounts in cents up to ten billion either way, as statements would give them
+        fn amount() -> impl Strategy<Value = Decimal> {
+            (-1_000_000_000_000i64..1_000_000_000_000).prop_map(|cents| Decimal::new(cents, 2))
+        }
+
+        /// Rates from 0.001 to 10,000 units per USD, with the three decimal places the IRS publishes
+        fn rate() -> impl Strategy<Value = ExchangeRate> {
+            (1u32..=10_000_000).prop_map(|rate| {
+                ExchangeRate::new("EUR".to_string(), rate as f64 / 1000.0).unwrap()
+            })
+        }
+
+        proptest! {
+            #[test]
+            fn round_trip_is_within_rounding(amount in amount(), rate in rate()) {
+                let back = rate.convert_from_usd(&rate.convert_to_usd(&eur(amount)).unwrap()).unwrap();
+
+                // Each step rounds to the cent, and the USD cent is scaled up by the rate on the way back
+                let tolerance = dec!(0.005) * rate.decimal_rate().unwrap() + dec!(0.005);
+                prop_assert!(
+                    (back.amount - amount).abs() <= tolerance,
+                    "{} came back as {} at {}", amount, back, rate.rate
+                );
+            }
+
+            #[test]
+            fn conversion_is_monotonic(a in amount(), b in amount(), rate in rate()) {
+                let (low, high) = if a <= b { (a, b) } else { (b, a) };
+
+                prop_assert!(
+                    rate.convert_to_usd(&eur(low)).unwrap().amount
+                        <= rate.convert_to_usd(&eur(high)).unwrap().amount
+                );
+                prop_assert!(
+                    rate.convert_from_usd(&usd(low)).unwrap().amount
+                        <= rate.convert_from_usd(&usd(high)).unwrap().amount
+                );
+            }
+
+            #[test]
+            fn extreme_values_do_not_panic(
+                parts in (any::<u32>(), any::<u32>(), any::<u32>(), any::<bool>(), 0u32..=28),
+                rate in any::<f64>(),
+            ) {
+                let (lo, mid, hi, negative, scale) = parts;
+                let amount = Decimal::from_parts(lo, mid, hi, negative, scale);
+                let rate = ExchangeRate {
+                    currency_code: "eur".to_string(),
+                    rate,
+                };
+
+                // Overflowing or invalid conversions are errors rather than panics
+                let _ = rate.convert_to_usd(&eur(amount));
+                let _ = rate.convert_from_usd(&usd(amount));
+            }
+        }
+    }
 }
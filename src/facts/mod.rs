@@ -1,34 +1,96 @@
+pub mod currency;
 pub mod exchange_rate;
+pub mod rate_set;
+pub mod treasury;
+pub mod update;
+pub use self::currency::CurrencyDefinition;
 pub use self::exchange_rate::ExchangeRate;
+pub use self::rate_set::RateSet;
 
+use schemars::JsonSchema;
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::diagnostics::codes;
+use crate::money;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
 pub struct Facts {
     pub years: Vec<AnnualFact>,
+    /// Currencies defined by the user rather than known to the tool, only read from `fact_extensions`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub currencies: Vec<CurrencyDefinition>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
 pub struct AnnualFact {
     pub year: i32,
+    /// Year-end rates, which the FBAR is filed at
     pub exchange_rates: Vec<ExchangeRate>,
+    /// Yearly average rates, which income is translated at on Form 1040
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub average_rates: Vec<ExchangeRate>,
+}
+
+impl AnnualFact {
+    /// The year's rates of the kind
+    pub fn rates(&self, kind: RateKind) -> &[ExchangeRate] {
+        match kind {
+            RateKind::YearEnd => &self.exchange_rates,
+            RateKind::YearlyAverage => &self.average_rates,
+        }
+    }
+}
+
+/// Which of a year's rates to convert at
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RateKind {
+    /// The Treasury's year-end rate, as the FBAR is filed at and assets are valued at for Form 8938
+    #[default]
+    YearEnd,
+    /// The yearly average rate, as income is translated at for Form 1040
+    YearlyAverage,
+}
+
+impl fmt::Display for RateKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RateKind::YearEnd => "year-end",
+            RateKind::YearlyAverage => "yearly average",
+        })
+    }
 }
 
 impl Facts {
     pub fn load_facts() -> Result<Facts, Box<dyn std::error::Error>> {
-        // Include the YAML file at compile time
-        let yaml_content = include_str!("../../facts/years.yml");
+        // Included at compile time, and checked against its checksum
+        let yaml_content = update::bundled()?;
 
         // Parse the YAML content
         let facts: Facts = serde_yaml::from_str(yaml_content)?;
+        facts.validate_years("the bundled facts")?;
 
         Ok(facts)
     }
 
+    /// The year-end rate for a currency and year
     pub fn get_exchange_rate(
         &self,
         year: i32,
         currency_code: impl Into<String>,
+    ) -> Option<&ExchangeRate> {
+        self.get_rate(year, currency_code, RateKind::YearEnd)
+    }
+
+    /// The rate of the kind for a currency and year
+    pub fn get_rate(
+        &self,
+        year: i32,
+        currency_code: impl Into<String>,
+        kind: RateKind,
     ) -> Option<&ExchangeRate> {
         let lookup_code = currency_code.into().to_lowercase();
         self.years
@@ -36,15 +98,114 @@ impl Facts {
             .find(|annual_fact| annual_fact.year == year)
             .and_then(|annual_fact| {
                 annual_fact
-                    .exchange_rates
+                    .rates(kind)
                     .iter()
                     .find(|rate| rate.currency_code == lookup_code)
             })
     }
 
+    /// SHA-256 of the facts' serialized form, hex encoded, identifying the dataset
+    pub fn content_hash(&self) -> anyhow::Result<String> {
+        let serialized = serde_yaml::to_string(self)?;
+        Ok(hex::encode(Sha256::digest(serialized.as_bytes())))
+    }
+
+    /// Sets the rate for a currency in a year, replacing any rate already given
+    pub fn set_rate(&mut self, year: i32, rate: ExchangeRate) {
+        let index = match self.years.iter().position(|fact| fact.year == year) {
+            Some(index) => index,
+            None => {
+                self.years.push(AnnualFact {
+                    year,
+                    exchange_rates: Vec::new(),
+                    ..Default::default()
+                });
+                self.years.len() - 1
+            }
+        };
+
+        let rates = &mut self.years[index].exchange_rates;
+        rates.retain(|existing| existing.currency_code != rate.currency_code);
+        rates.push(rate);
+    }
+
+    /// Orders years newest first, as in the bundled facts, and each year's rates by currency, so facts written back
+    /// out serialize the same way whatever order the rates were added in
+    pub fn sort(&mut self) {
+        self.years.sort_by_key(|fact| std::cmp::Reverse(fact.year));
+        for fact in &mut self.years {
+            fact.exchange_rates
+                .sort_by(|a, b| a.currency_code.cmp(&b.currency_code));
+            fact.average_rates
+                .sort_by(|a, b| a.currency_code.cmp(&b.currency_code));
+        }
+    }
+
+    /// Checks that each year appears only once, with at most one rate per currency. Duplicates are refused rather
+    /// than merged, since there's no telling which of two rates is meant. `source` names the facts in the error.
+    pub fn validate_years(&self, source: &str) -> anyhow::Result<()> {
+        let mut years = HashSet::new();
+        for fact in &self.years {
+            if !years.insert(fact.year) {
+                anyhow::bail!(
+                    "{} has two entries for {}; merge them into one (see `fbar_prep explain {}`)",
+                    source,
+                    fact.year,
+                    codes::DUPLICATE_FACT
+                );
+            }
+            for kind in [RateKind::YearEnd, RateKind::YearlyAverage] {
+                let mut currencies = HashSet::new();
+                for rate in fact.rates(kind) {
+                    if !currencies.insert(rate.currency_code.as_str()) {
+                        anyhow::bail!(
+                            "{} has two {} {} rates for {}; keep one (see `fbar_prep explain {}`)",
+                            source,
+                            rate.currency_code.to_uppercase(),
+                            kind,
+                            fact.year,
+                            codes::DUPLICATE_FACT
+                        );
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the currency definitions, each of which must be valid and define a code only once
+    pub fn validate_currencies(&self) -> anyhow::Result<()> {
+        for (index, currency) in self.currencies.iter().enumerate() {
+            currency.validate()?;
+            if self.currencies[..index]
+                .iter()
+                .any(|earlier| earlier.code.eq_ignore_ascii_case(&currency.code))
+            {
+                anyhow::bail!(
+                    "Currency {} is defined more than once in fact_extensions",
+                    currency.code.to_uppercase()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The currency defined with the code, if any
+    pub fn currency(&self, code: &str) -> Option<&CurrencyDefinition> {
+        self.currencies
+            .iter()
+            .find(|currency| currency.code.eq_ignore_ascii_case(code))
+    }
+
+    /// Decimal places amounts in the currency are rounded and written to: a defined currency's own, or the tool's
+    pub fn minor_units(&self, code: &str) -> u32 {
+        self.currency(code)
+            .map_or_else(|| money::minor_units(code), |currency| currency.minor_units)
+    }
+
     /// Creates an empty Facts instance with no exchange rates
     pub fn empty() -> Self {
-        Facts { years: Vec::new() }
+        Self::default()
     }
 }
 
@@ -68,11 +229,16 @@ mod tests {
         // Test exchange rates for 2024
         let gbp = &year_2024.exchange_rates[0];
         assert_eq!(gbp.currency_code, "gbp");
-        assert_eq!(gbp.rate, 0.783);
+        assert_eq!(gbp.rate, 0.797);
 
         let eur = &year_2024.exchange_rates[1];
         assert_eq!(eur.currency_code, "eur");
-        assert_eq!(eur.rate, 0.924);
+        assert_eq!(eur.rate, 0.961);
+
+        // The yearly averages are bundled apart from the year-end rates
+        assert_eq!(year_2024.average_rates.len(), 2);
+        assert_eq!(year_2024.average_rates[0].rate, 0.783);
+        assert_eq!(year_2024.average_rates[1].rate, 0.924);
     }
 
     #[test]
@@ -82,7 +248,14 @@ mod tests {
         // Test existing exchange rate
         let gbp_rate = facts.get_exchange_rate(2024, "gbp").unwrap();
         assert_eq!(gbp_rate.currency_code, "gbp");
-        assert_eq!(gbp_rate.rate, 0.783);
+        assert_eq!(gbp_rate.rate, 0.797);
+        assert_eq!(
+            facts
+                .get_rate(2024, "gbp", RateKind::YearlyAverage)
+                .unwrap()
+                .rate,
+            0.783
+        );
 
         // Test case insensitivity
         let gbp_upper = facts.get_exchange_rate(2024, "GBP").unwrap();
@@ -96,4 +269,59 @@ mod tests {
         // Test non-existent currency
         assert!(facts.get_exchange_rate(2024, "xyz").is_none());
     }
+
+    #[test]
+    fn test_validate_years() {
+        let fact = |year: i32, rates: &[(&str, f64)]| AnnualFact {
+            year,
+            exchange_rates: rates
+                .iter()
+                .map(|(code, rate)| ExchangeRate::new(code.to_string(), *rate).unwrap())
+                .collect(),
+            ..Default::default()
+        };
+        let mut facts = Facts::empty();
+        facts.years = vec![
+            fact(2023, &[("EUR", 0.9), ("GBP", 0.8)]),
+            fact(2022, &[("EUR", 0.95)]),
+        ];
+        assert!(facts.validate_years("fact_extensions").is_ok());
+
+        facts.years.push(fact(2023, &[("CHF", 0.88)]));
+        assert_eq!(
+            facts.validate_years("fact_extensions").unwrap_err().to_string(),
+            "fact_extensions has two entries for 2023; merge them into one (see `fbar_prep explain FB0033`)"
+        );
+
+        facts.years.pop();
+        facts.years[1]
+            .exchange_rates
+            .push(ExchangeRate::new("eur".to_string(), 0.96).unwrap());
+        assert_eq!(
+            facts
+                .validate_years("rate set ecb")
+                .unwrap_err()
+                .to_string(),
+            "rate set ecb has two EUR year-end rates for 2022; keep one (see `fbar_prep explain FB0033`)"
+        );
+    }
+
+    #[test]
+    fn test_validate_currencies() {
+        let definition = |code: &str| CurrencyDefinition {
+            code: code.to_string(),
+            name: "Old drachma".to_string(),
+            minor_units: 2,
+        };
+        let mut extensions = Facts::empty();
+        extensions.currencies = vec![definition("grdold"), definition("xfund")];
+        assert!(extensions.validate_currencies().is_ok());
+
+        extensions.currencies.push(definition("GRDOLD"));
+        let error = extensions.validate_currencies().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Currency GRDOLD is defined more than once in fact_extensions"
+        );
+    }
 }
@@ -0,0 +1,117 @@
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+use super::Facts;
+use crate::parsing::ParseContext;
+
+/// Name under which the embedded IRS year-end rates take part in rate precedence
+pub const IRS_RATES: &str = "irs";
+/// Name under which the user's `fact_extensions` take part in rate precedence
+pub const USER_RATES: &str = "user";
+
+/// An additional, named set of exchange rates, e.g. ECB reference rates or yearly averages.
+///
+/// Rate sets are loaded from `rates/<name>.yml` in the data directory, using the same schema as the facts file.
+#[derive(Debug, Clone)]
+pub struct RateSet {
+    pub name: String,
+    pub facts: Facts,
+}
+
+impl RateSet {
+    /// Loads every rate set in the data directory's `rates` directory, ordered by name
+    pub fn load_all(base_path: &Path, context: &ParseContext) -> Result<Vec<Self>> {
+        let dir = base_path.join("rates");
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut rate_sets = Vec::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("yml") {
+                continue;
+            }
+
+            let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(name) => name.to_lowercase(),
+                None => bail!("Invalid rate set file name {:?}", path),
+            };
+            if name == IRS_RATES || name == USER_RATES {
+                bail!("Rate set name {:?} is reserved ({:?})", name, path);
+            }
+
+            let contents = std::fs::read_to_string(&path)?;
+            let value: serde_yaml::Value = serde_yaml::from_str(&contents)
+                .with_context(|| format!("Invalid rate set file {:?}", path))?;
+            let facts: Facts = context.deserialize(value, &path.display().to_string())?;
+            facts.validate_years(&format!("Rate set {:?}", path))?;
+
+            rate_sets.push(Self { name, facts });
+        }
+        rate_sets.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(rate_sets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const ECB_RATES: &str = r#"
+years:
+  - year: 2023
+    exchange_rates:
+      - currency_code: EUR
+        rate: 0.905
+"#;
+
+    #[test]
+    fn test_load_rate_sets() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        fs::create_dir(temp_dir.path().join("rates"))?;
+        fs::write(temp_dir.path().join("rates/ECB.yml"), ECB_RATES)?;
+        fs::write(temp_dir.path().join("rates/average.yml"), ECB_RATES)?;
+        fs::write(temp_dir.path().join("rates/notes.txt"), "not a rate set")?;
+
+        let rate_sets = RateSet::load_all(temp_dir.path(), &ParseContext::default())?;
+
+        assert_eq!(rate_sets.len(), 2);
+        assert_eq!(rate_sets[0].name, "average");
+        assert_eq!(rate_sets[1].name, "ecb");
+        assert_eq!(
+            rate_sets[1]
+                .facts
+                .get_exchange_rate(2023, "eur")
+                .unwrap()
+                .rate,
+            0.905
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_rates_directory() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        assert!(RateSet::load_all(temp_dir.path(), &ParseContext::default())?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reserved_names() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        fs::create_dir(temp_dir.path().join("rates"))?;
+        fs::write(temp_dir.path().join("rates/irs.yml"), ECB_RATES)?;
+
+        let result = RateSet::load_all(temp_dir.path(), &ParseContext::default());
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("reserved"));
+
+        Ok(())
+    }
+}
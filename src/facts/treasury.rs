@@ -0,0 +1,316 @@
+use anyhow::{Context, Result};
+use chrono::Datelike;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use super::{ExchangeRate, Facts};
+use crate::diagnostics::codes;
+use crate::parsing::ParseContext;
+
+/// Name of the rate set that Treasury rates are imported into, stored as `rates/treasury.yml`
+pub const TREASURY_RATES: &str = "treasury";
+
+/// Treasury's "Country-Currency" descriptions for the currencies we know the ISO 4217 codes of
+const CURRENCY_CODES: &[(&str, &str)] = &[
+    ("argentina-peso", "ars"),
+    ("australia-dollar", "aud"),
+    ("brazil-real", "brl"),
+    ("canada-dollar", "cad"),
+    ("chile-peso", "clp"),
+    ("china-renminbi", "cny"),
+    ("colombia-peso", "cop"),
+    ("czech republic-koruna", "czk"),
+    ("denmark-krone", "dkk"),
+    ("euro zone-euro", "eur"),
+    ("hong kong-dollar", "hkd"),
+    ("hungary-forint", "huf"),
+    ("iceland-krona", "isk"),
+    ("india-rupee", "inr"),
+    ("indonesia-rupiah", "idr"),
+    ("israel-shekel", "ils"),
+    ("japan-yen", "jpy"),
+    ("korea-won", "krw"),
+    ("malaysia-ringgit", "myr"),
+    ("mexico-peso", "mxn"),
+    ("new zealand-dollar", "nzd"),
+    ("norway-krone", "nok"),
+    ("philippines-peso", "php"),
+    ("poland-zloty", "pln"),
+    ("romania-new leu", "ron"),
+    ("russia-ruble", "rub"),
+    ("saudi arabia-riyal", "sar"),
+    ("singapore-dollar", "sgd"),
+    ("south africa-rand", "zar"),
+    ("sweden-krona", "sek"),
+    ("switzerland-franc", "chf"),
+    ("taiwan-dollar", "twd"),
+    ("thailand-baht", "thb"),
+    ("turkey-new lira", "try"),
+    ("united arab emirates-dirham", "aed"),
+    ("united kingdom-pound", "gbp"),
+];
+
+/// A rate the import added or changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateChange {
+    pub year: i32,
+    pub currency_code: String,
+    /// The rate previously imported, or else the bundled IRS rate
+    pub previous: Option<f64>,
+    pub rate: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TreasuryImport {
+    pub changes: Vec<RateChange>,
+    /// Year-end rates already known with the same value
+    pub unchanged: usize,
+    /// Rows for quarters other than the year end, which FBAR doesn't use
+    pub skipped: usize,
+}
+
+/// Parses the Treasury's Reporting Rates of Exchange CSV, as downloaded from Fiscal Data, keeping the December 31
+/// rate for each year. Currencies without a known ISO code are reported and left out.
+pub fn parse_treasury_csv(
+    reader: impl Read,
+    source: &str,
+    context: &ParseContext,
+) -> Result<(Facts, usize)> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+
+    let headers = csv_reader.headers()?.clone();
+    let column = |names: &[&str]| {
+        headers
+            .iter()
+            .position(|header| names.iter().any(|name| header.eq_ignore_ascii_case(name)))
+            .with_context(|| format!("{} has no {:?} column", source, names[0]))
+    };
+    let date_column = column(&["Record Date", "record_date"])?;
+    let currency_column = column(&["Country - Currency Description", "country_currency_desc"])?;
+    let rate_column = column(&["Exchange Rate", "exchange_rate"])?;
+
+    let mut facts = Facts::empty();
+    let mut skipped = 0;
+    for (index, record) in csv_reader.records().enumerate() {
+        let location = format!("{} line {}", source, index + 2);
+        let record = record.with_context(|| format!("Invalid row in {}", location))?;
+        let field = |column: usize| record.get(column).unwrap_or_default();
+
+        let Some(date) = context.parse_date(field(date_column), &location)? else {
+            continue;
+        };
+        if (date.month(), date.day()) != (12, 31) {
+            skipped += 1;
+            continue;
+        }
+
+        let description = field(currency_column);
+        let Some(currency_code) = currency_code(description) else {
+            context.issue_at(
+                codes::UNKNOWN_CURRENCY,
+                &location,
+                format!(
+                    "Unknown Treasury currency {:?} in {}",
+                    description, location
+                ),
+            )?;
+            continue;
+        };
+        let rate = field(rate_column)
+            .parse::<f64>()
+            .map_err(anyhow::Error::from)
+            .and_then(|rate| {
+                ExchangeRate::new(currency_code.to_string(), rate).map_err(anyhow::Error::msg)
+            })
+            .with_context(|| format!("Invalid exchange rate in {}", location))?;
+
+        facts.set_rate(date.year(), rate);
+    }
+
+    Ok((facts, skipped))
+}
+
+/// Imports a Treasury rates CSV into the `treasury` rate set in the data directory. Rates matching the bundled IRS
+/// facts or an earlier import are left out, so only new and corrected rates are written.
+pub fn import_treasury(
+    base_path: &Path,
+    bundled: &Facts,
+    csv_path: &Path,
+    context: &ParseContext,
+) -> Result<TreasuryImport> {
+    let file = std::fs::File::open(csv_path)
+        .with_context(|| format!("Could not read Treasury rates {:?}", csv_path))?;
+    let (imported, skipped) = parse_treasury_csv(file, &csv_path.display().to_string(), context)?;
+
+    let path = rate_set_path(base_path);
+    let mut stored = if path.exists() {
+        let contents = std::fs::read_to_string(&path)?;
+        let stored: Facts = serde_yaml::from_str(&contents)
+            .with_context(|| format!("Invalid rate set file {:?}", path))?;
+        stored.validate_years(&format!("Rate set {:?}", path))?;
+        stored
+    } else {
+        Facts::empty()
+    };
+
+    let mut result = TreasuryImport {
+        skipped,
+        ..Default::default()
+    };
+    for fact in &imported.years {
+        for rate in &fact.exchange_rates {
+            let previous = stored
+                .get_exchange_rate(fact.year, &rate.currency_code)
+                .or_else(|| bundled.get_exchange_rate(fact.year, &rate.currency_code))
+                .map(|previous| previous.rate);
+            if previous == Some(rate.rate) {
+                result.unchanged += 1;
+                continue;
+            }
+
+            result.changes.push(RateChange {
+                year: fact.year,
+                currency_code: rate.currency_code.clone(),
+                previous,
+                rate: rate.rate,
+            });
+            stored.set_rate(fact.year, rate.clone());
+        }
+    }
+
+    if !result.changes.is_empty() {
+        stored.sort();
+        std::fs::create_dir_all(path.parent().expect("rate set path has a parent"))?;
+        std::fs::write(&path, serde_yaml::to_string(&stored)?)?;
+    }
+
+    Ok(result)
+}
+
+fn currency_code(description: &str) -> Option<&'static str> {
+    let description = description.to_lowercase();
+    CURRENCY_CODES
+        .iter()
+        .find(|(known, _)| *known == description)
+        .map(|(_, code)| *code)
+}
+
+/// Whether the code is one of the ISO 4217 currencies the Treasury publishes rates for
+pub(crate) fn is_treasury_currency(code: &str) -> bool {
+    let code = code.to_lowercase();
+    CURRENCY_CODES.iter().any(|(_, known)| *known == code)
+}
+
+fn rate_set_path(base_path: &Path) -> PathBuf {
+    base_path
+        .join("rates")
+        .join(format!("{}.yml", TREASURY_RATES))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::facts::RateSet;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const TREASURY_CSV: &str = "\
+Record Date,Country - Currency Description,Exchange Rate,Effective Date
+2023-12-31,Euro Zone-Euro,0.905,2023-12-31
+2023-12-31,United Kingdom-Pound,0.785,2023-12-31
+2023-12-31,Japan-Yen,141.0,2023-12-31
+2023-09-30,Japan-Yen,149.0,2023-09-30
+2023-12-31,Atlantis-Drachma,1.0,2023-12-31
+";
+
+    fn bundled() -> Facts {
+        let mut facts = Facts::empty();
+        facts.set_rate(2023, ExchangeRate::new("EUR".to_string(), 0.905).unwrap());
+        facts.set_rate(2023, ExchangeRate::new("GBP".to_string(), 0.804).unwrap());
+        facts
+    }
+
+    #[test]
+    fn test_parse_treasury_csv() -> Result<()> {
+        let context = ParseContext::default();
+        let (facts, skipped) = parse_treasury_csv(TREASURY_CSV.as_bytes(), "rates.csv", &context)?;
+
+        assert_eq!(skipped, 1);
+        assert_eq!(facts.get_exchange_rate(2023, "jpy").unwrap().rate, 141.0);
+        assert_eq!(facts.years[0].exchange_rates.len(), 3);
+        assert_eq!(
+            context.warnings(),
+            vec!["Unknown Treasury currency \"Atlantis-Drachma\" in rates.csv line 6".to_string()]
+        );
+
+        assert!(parse_treasury_csv(
+            TREASURY_CSV.as_bytes(),
+            "rates.csv",
+            &ParseContext::strict()
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_deduplicates() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let csv_path = temp_dir.path().join("rates.csv");
+        fs::write(&csv_path, TREASURY_CSV)?;
+
+        let result = import_treasury(
+            temp_dir.path(),
+            &bundled(),
+            &csv_path,
+            &ParseContext::default(),
+        )?;
+        // EUR matches the bundled rate, GBP corrects it and JPY is new
+        assert_eq!(result.unchanged, 1);
+        assert_eq!(
+            result.changes,
+            vec![
+                RateChange {
+                    year: 2023,
+                    currency_code: "gbp".to_string(),
+                    previous: Some(0.804),
+                    rate: 0.785,
+                },
+                RateChange {
+                    year: 2023,
+                    currency_code: "jpy".to_string(),
+                    previous: None,
+                    rate: 141.0,
+                },
+            ]
+        );
+
+        let rate_sets = RateSet::load_all(temp_dir.path(), &ParseContext::default())?;
+        assert_eq!(rate_sets[0].name, TREASURY_RATES);
+        assert!(rate_sets[0].facts.get_exchange_rate(2023, "eur").is_none());
+
+        // Importing the same file again changes nothing
+        let again = import_treasury(
+            temp_dir.path(),
+            &bundled(),
+            &csv_path,
+            &ParseContext::default(),
+        )?;
+        assert!(again.changes.is_empty());
+        assert_eq!(again.unchanged, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_columns() {
+        let result = parse_treasury_csv(
+            "date,rate\n2023-12-31,0.9\n".as_bytes(),
+            "rates.csv",
+            &ParseContext::default(),
+        );
+        assert!(result.unwrap_err().to_string().contains("Record Date"));
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use super::Facts;
+
+/// Facts update downloaded into the data directory, replacing the bundled facts
+pub const UPDATE_FILE: &str = "facts/years.yml";
+/// The update's published SHA-256, in `sha256sum` format, downloaded alongside it
+pub const UPDATE_CHECKSUM_FILE: &str = "facts/years.yml.sha256";
+
+/// The bundled facts and their checksum, embedded at compile time
+const BUNDLED: &str = include_str!("../../facts/years.yml");
+const BUNDLED_CHECKSUM: &str = include_str!("../../facts/years.yml.sha256");
+
+/// The bundled facts, checked against their embedded checksum so a corrupted build can't change every conversion
+pub(super) fn bundled() -> Result<&'static str> {
+    verify(BUNDLED.as_bytes(), BUNDLED_CHECKSUM).context("bundled facts")?;
+    Ok(BUNDLED)
+}
+
+/// Loads a facts update from the data directory, if there is one, after checking it against its published checksum.
+///
+/// An update whose checksum doesn't match is refused. One without a checksum is used with a warning, or refused in
+/// strict mode, since a tampered or corrupted file would silently change every conversion.
+pub fn load_update(base_path: &Path, strict: bool) -> Result<Option<Facts>> {
+    let path = base_path.join(UPDATE_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let contents =
+        std::fs::read(&path).with_context(|| format!("Could not read facts update {:?}", path))?;
+    let checksum_path = base_path.join(UPDATE_CHECKSUM_FILE);
+    if checksum_path.exists() {
+        let checksum = std::fs::read_to_string(&checksum_path)
+            .with_context(|| format!("Could not read {:?}", checksum_path))?;
+        verify(&contents, &checksum).with_context(|| {
+            format!(
+                "Facts update {:?} may be corrupted or tampered with; download it again, or remove it to use the bundled facts",
+                path
+            )
+        })?;
+    } else if strict {
+        bail!(
+            "Facts update {:?} has no published checksum in {:?}",
+            path,
+            checksum_path
+        );
+    } else {
+        tracing::warn!(
+            "Using facts update {:?} without verifying it; save its published SHA-256 as {:?}",
+            path,
+            checksum_path
+        );
+    }
+
+    let facts: Facts = serde_yaml::from_slice(&contents)
+        .with_context(|| format!("Invalid facts update {:?}", path))?;
+    facts.validate_years(&format!("Facts update {:?}", path))?;
+    tracing::info!(
+        "Using facts update {:?} with {} years",
+        path,
+        facts.years.len()
+    );
+    Ok(Some(facts))
+}
+
+/// Checks contents against a checksum given as `sha256sum` prints it: the hex digest, optionally followed by the file
+/// name
+fn verify(contents: &[u8], checksum: &str) -> Result<()> {
+    let Some(expected) = checksum.split_whitespace().next() else {
+        bail!("Checksum file is empty");
+    };
+    let actual = hex::encode(Sha256::digest(contents));
+    if !actual.eq_ignore_ascii_case(expected) {
+        bail!(
+            "SHA-256 is {}, but the published checksum is {}",
+            actual,
+            expected
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const UPDATE: &str = "years:\n  - year: 2025\n    exchange_rates:\n      - currency_code: gbp\n        rate: 0.8\n";
+
+    #[test]
+    fn test_bundled_checksum() -> Result<()> {
+        // Fails when facts/years.yml is edited without updating facts/years.yml.sha256
+        bundled()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_update() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        assert!(load_update(temp_dir.path(), true)?.is_none());
+
+        fs::create_dir(temp_dir.path().join("facts"))?;
+        fs::write(temp_dir.path().join(UPDATE_FILE), UPDATE)?;
+        // Without a checksum the update is only used leniently
+        assert!(load_update(temp_dir.path(), true).is_err());
+        assert!(load_update(temp_dir.path(), false)?.is_some());
+
+        let checksum = hex::encode(Sha256::digest(UPDATE.as_bytes()));
+        fs::write(
+            temp_dir.path().join(UPDATE_CHECKSUM_FILE),
+            format!("{}  years.yml\n", checksum),
+        )?;
+        let facts = load_update(temp_dir.path(), true)?.unwrap();
+        assert_eq!(facts.years[0].year, 2025);
+
+        // A changed rate no longer matches the published checksum
+        fs::write(
+            temp_dir.path().join(UPDATE_FILE),
+            UPDATE.replace("0.8", "0.9"),
+        )?;
+        let message = format!("{:#}", load_update(temp_dir.path(), false).unwrap_err());
+        assert!(message.contains("may be corrupted or tampered with"));
+        assert!(message.contains(&format!("the published checksum is {}", checksum)));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,671 @@
+//! Reads a previously filed FBAR back into a starter data directory, so a long-time filer can begin from their last
+//! filing rather than retyping every account. The filing gives each account's maximum value in USD, not its balances
+//! or even its currency, so each account starts out with an unknown currency, which has to be set before the account
+//! can be reported. Its maximum is kept as a manual USD record at the end of the year, with the filed figure as the
+//! record's `usd_value`, to be replaced with the account's own records.
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::NaiveDate;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use rust_decimal::Decimal;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::data::migrate::SCHEMA_VERSION;
+use crate::data::{
+    Account, AccountKind, Address, BalanceRecord, Entity, Filer, Filing, LateFilingReason,
+    Provider, StructuredAddress, UserData, UNKNOWN_CURRENCY_CODE,
+};
+
+/// Activity party type codes, as written by `fbar_xml`
+const FOREIGN_ACCOUNT_FILER: &str = "15";
+const FINANCIAL_INSTITUTION: &str = "41";
+const PRINCIPAL_JOINT_OWNER: &str = "42";
+const CONSOLIDATED_OWNER: &str = "44";
+/// Account type codes
+const SECURITIES_ACCOUNT: &str = "142";
+const OTHER_ACCOUNT: &str = "143";
+/// How insurance and annuity accounts, filed as other accounts, are described
+const INSURANCE_ACCOUNT_TYPE: &str = "Life insurance or annuity";
+
+const LATE_FILING_REASONS: [LateFilingReason; 6] = [
+    LateFilingReason::ForgotToFile,
+    LateFilingReason::DidNotKnowRequirement,
+    LateFilingReason::ThoughtBelowThreshold,
+    LateFilingReason::DidNotKnowAccountWasForeign,
+    LateFilingReason::DeclinedToExplain,
+    LateFilingReason::Other,
+];
+
+/// What was read from a filed FBAR
+#[derive(Debug)]
+pub struct FiledFbar {
+    pub year: i32,
+    /// The filer, providers, accounts and owners from the filing, with the filing itself under `filings`
+    pub user_data: UserData,
+    /// The number of accounts, when the filing only gave that rather than listing them
+    pub summary_accounts: Option<u32>,
+}
+
+/// Writes a starter data directory at `out` from the filed FBAR at `source`, refusing to overwrite one that's
+/// already there
+pub fn write_starter(source: &Path, out: &Path) -> Result<FiledFbar> {
+    let out_yaml = out.join("data.yml");
+    if out_yaml.exists() {
+        bail!("{:?} already exists, refusing to overwrite it", out_yaml);
+    }
+
+    let contents = std::fs::read(source).with_context(|| format!("reading {:?}", source))?;
+    if contents.starts_with(b"%PDF") {
+        bail!(
+            "{:?} is a PDF; reading the fillable PDF's form data isn't supported, so use the XML copy of the filing \
+             from BSA E-Filing instead",
+            source
+        );
+    }
+    let xml = String::from_utf8(contents).with_context(|| format!("{:?} isn't UTF-8", source))?;
+    let filed = read_filed_xml(&xml).with_context(|| format!("reading {:?}", source))?;
+
+    std::fs::create_dir_all(out)?;
+    std::fs::write(out_yaml, filed.user_data.to_complete_yaml()?)?;
+    Ok(filed)
+}
+
+/// Reads a BSA E-Filing FBAR batch, as `fbar_xml` writes and BSA E-Filing gives back as the copy of a filing
+pub fn read_filed_xml(xml: &str) -> Result<FiledFbar> {
+    let root = Element::parse(xml)?;
+    if root.name != "EFilingBatchXML" {
+        bail!(
+            "Not a BSA E-Filing batch: the root element is {}",
+            root.name
+        );
+    }
+    if let Some(form) = root.text("FormTypeCode").filter(|form| *form != "FBARX") {
+        bail!("Not an FBAR: the batch is of form type {}", form);
+    }
+    let activity = root
+        .child("Activity")
+        .ok_or_else(|| anyhow!("The batch has no Activity"))?;
+
+    let year_text = activity
+        .child("ForeignAccountActivity")
+        .and_then(|foreign| foreign.text("ReportCalendarYearText"))
+        .ok_or_else(|| anyhow!("The filing has no ReportCalendarYearText"))?;
+    // Four digits, as the schema requires, which also keeps the year's dates in range
+    let year: i32 = year_text
+        .parse()
+        .ok()
+        .filter(|year| (1000..=9999).contains(year))
+        .ok_or_else(|| {
+            anyhow!(
+                "Invalid Activity/ForeignAccountActivity/ReportCalendarYearText {:?}: not a four-digit year",
+                year_text
+            )
+        })?;
+    let filed_on = activity
+        .text("ApprovalOfficialSignatureDateText")
+        .map(|date| NaiveDate::parse_from_str(date, "%Y%m%d"))
+        .transpose()
+        .context("Invalid ApprovalOfficialSignatureDateText")?;
+    let mut user_data = UserData {
+        schema_version: Some(SCHEMA_VERSION),
+        filings: vec![filing(activity, year, filed_on)],
+        ..Default::default()
+    };
+
+    let mut summary_accounts = None;
+    if let Some(party) = activity
+        .children("Party")
+        .find(|party| party.text("ActivityPartyTypeCode") == Some(FOREIGN_ACCOUNT_FILER))
+    {
+        let name = party_name(party).unwrap_or_default();
+        user_data.filers.push(Filer {
+            handle: unique_handle(&name, "filer", &user_data.filers, |filer| &filer.handle),
+            name,
+            tin: party_tin(party),
+            address: party
+                .child("Address")
+                .map(address)
+                .map(|(address, _)| address),
+        });
+        if party.text("FilerFinancialInterest25ForeignAccountIndicator") == Some("Y") {
+            summary_accounts = party
+                .text("FinancialInterest25ForeignAccountCount")
+                .map(|count| count.parse())
+                .transpose()
+                .context("Invalid FinancialInterest25ForeignAccountCount")?;
+        }
+    }
+
+    for (index, element) in activity.children("Account").enumerate() {
+        let account = account(element, year, &mut user_data)
+            .with_context(|| format!("reading account {}", index + 1))?;
+        user_data.accounts.push(account);
+    }
+
+    Ok(FiledFbar {
+        year,
+        user_data,
+        summary_accounts,
+    })
+}
+
+/// The filing of the year, with its late filing reason and explanation
+fn filing(activity: &Element, year: i32, filed_on: Option<NaiveDate>) -> Filing {
+    let mut late_reason = activity
+        .child("ForeignAccountActivity")
+        .and_then(|foreign| foreign.text("LateFilingReasonCode"))
+        .and_then(|code| {
+            LATE_FILING_REASONS
+                .into_iter()
+                .find(|reason| reason.code() == code)
+        });
+    let mut explanation = activity
+        .child("ActivityNarrativeInformation")
+        .and_then(|narrative| narrative.text("ActivityNarrativeText"))
+        .map(str::to_string);
+
+    // Schemas without a reason code lead the narrative with the reason, as `SchemaVersion::narrative` writes it
+    if let Some(narrative) = explanation.clone() {
+        for reason in LATE_FILING_REASONS {
+            let prefix = format!(
+                "Late filing reason {}: {}.",
+                reason.code(),
+                reason.description()
+            );
+            if let Some(rest) = narrative.strip_prefix(&prefix) {
+                late_reason = late_reason.or(Some(reason));
+                explanation = Some(rest.trim().to_string()).filter(|rest| !rest.is_empty());
+            }
+        }
+    }
+
+    Filing {
+        year,
+        filed_on,
+        late_reason,
+        explanation,
+    }
+}
+
+/// The account, adding its provider and any joint owner or entity to the data if they aren't there yet
+fn account(element: &Element, year: i32, user_data: &mut UserData) -> Result<Account> {
+    let max_value = element
+        .text("AccountMaximumValueAmountText")
+        .map(Decimal::from_str)
+        .transpose()
+        .context("Invalid AccountMaximumValueAmountText")?;
+    let other_type = element.text("OtherAccountTypeText");
+    let kind = match (element.text("AccountTypeCode"), other_type) {
+        (Some(SECURITIES_ACCOUNT), _) => AccountKind::Securities,
+        (Some(OTHER_ACCOUNT), Some(INSURANCE_ACCOUNT_TYPE)) => AccountKind::Insurance,
+        _ => AccountKind::Bank,
+    };
+
+    let mut account = Account {
+        currency_code: UNKNOWN_CURRENCY_CODE.to_string(),
+        kind,
+        identifier1: element.text("AccountNumberText").map(str::to_string),
+        notes: other_type
+            .filter(|_| kind != AccountKind::Insurance)
+            .map(|other| format!("Filed as another type of account: {}", other)),
+        ..Default::default()
+    };
+
+    if let Some(max_value) = max_value {
+        let date = NaiveDate::from_ymd_opt(year, 12, 31)
+            .ok_or_else(|| anyhow!("{} is out of range for a date", year))?;
+        match kind {
+            // Surrender values are in the policy's own currency, so the USD figure can only be noted
+            AccountKind::Insurance => {
+                account.notes = Some(format!(
+                    "Maximum value filed for {}: {} USD; add the policy's surrender values in its own currency",
+                    year, max_value
+                ))
+            }
+            _ => account.balances.push(BalanceRecord {
+                date,
+                amount: max_value,
+                currency: Some("USD".to_string()),
+                manual: true,
+                note: Some(format!(
+                    "Maximum value filed for {}; replace with the account's own records",
+                    year
+                )),
+                usd_value: Some(max_value),
+                ..Default::default()
+            }),
+        }
+    }
+
+    for party in element.children("Party") {
+        let name = party_name(party).unwrap_or_default();
+        match party.text("ActivityPartyTypeCode") {
+            Some(FINANCIAL_INSTITUTION) => {
+                let (address, country) = party
+                    .child("Address")
+                    .map(address)
+                    .unwrap_or((Address::Freeform(String::new()), None));
+                let country = country.filter(|_| matches!(address, Address::Freeform(_)));
+                let existing = user_data.providers.iter().find(|provider| {
+                    provider.name == name
+                        && provider.address == address
+                        && provider.country == country
+                });
+                account.provider = match existing {
+                    Some(provider) => provider.handle.clone(),
+                    None => {
+                        let handle =
+                            unique_handle(&name, "provider", &user_data.providers, |provider| {
+                                &provider.handle
+                            });
+                        user_data.providers.push(Provider {
+                            name,
+                            handle: handle.clone(),
+                            address,
+                            country,
+                            ..Default::default()
+                        });
+                        handle
+                    }
+                };
+            }
+            Some(PRINCIPAL_JOINT_OWNER) => {
+                let handle = match user_data
+                    .filers
+                    .iter()
+                    .skip(1)
+                    .find(|filer| filer.name == name)
+                {
+                    Some(filer) => filer.handle.clone(),
+                    None => {
+                        let handle =
+                            unique_handle(&name, "joint_owner", &user_data.filers, |filer| {
+                                &filer.handle
+                            });
+                        user_data.filers.push(Filer {
+                            name,
+                            handle: handle.clone(),
+                            tin: party_tin(party),
+                            address: party
+                                .child("Address")
+                                .map(address)
+                                .map(|(address, _)| address),
+                        });
+                        handle
+                    }
+                };
+                account.owners = user_data
+                    .primary_filer()
+                    .map(|filer| filer.handle.clone())
+                    .into_iter()
+                    .chain([handle])
+                    .collect();
+            }
+            Some(CONSOLIDATED_OWNER) => {
+                let handle = match user_data.entities.iter().find(|entity| entity.name == name) {
+                    Some(entity) => entity.handle.clone(),
+                    None => {
+                        let handle =
+                            unique_handle(&name, "entity", &user_data.entities, |entity| {
+                                &entity.handle
+                            });
+                        user_data.entities.push(Entity {
+                            name,
+                            handle: handle.clone(),
+                            address: party
+                                .child("Address")
+                                .map(address)
+                                .map_or(Address::Freeform(String::new()), |(address, _)| address),
+                            tin: party_tin(party),
+                            // Consolidated accounts are those of entities the filer owns more than half of, but the
+                            // filing doesn't say how much more
+                            ownership_percent: Decimal::ONE_HUNDRED,
+                        });
+                        handle
+                    }
+                };
+                account.entity = Some(handle);
+            }
+            _ => {}
+        }
+    }
+
+    let number = account.identifier1.as_deref().unwrap_or_default();
+    let digits: String = number.chars().filter(char::is_ascii_alphanumeric).collect();
+    let base = match digits.len() {
+        0 => account.provider.clone(),
+        length => format!(
+            "{}_{}",
+            account.provider,
+            &digits[length.saturating_sub(4)..]
+        ),
+    };
+    account.handle = unique_handle(&base, "account", &user_data.accounts, |account| {
+        &account.handle
+    });
+    Ok(account)
+}
+
+fn party_name(party: &Element) -> Option<String> {
+    let name = party.child("PartyName")?;
+    if let Some(full) = name.text("RawPartyFullName") {
+        return Some(full.to_string());
+    }
+    let last = name.text("RawEntityIndividualLastName")?;
+    Some(match name.text("RawIndividualFirstName") {
+        Some(first) => format!("{} {}", first, last),
+        None => last.to_string(),
+    })
+}
+
+fn party_tin(party: &Element) -> Option<String> {
+    party
+        .child("PartyIdentification")
+        .and_then(|identification| identification.text("PartyIdentificationNumberText"))
+        .map(str::to_string)
+}
+
+/// The address, structured when it has a city and country, with the country code it gives
+fn address(element: &Element) -> (Address, Option<String>) {
+    let street = element.text("RawStreetAddress1Text").unwrap_or_default();
+    let country = element.text("RawCountryCodeText").map(str::to_string);
+    let address = match (element.text("RawCityText"), &country) {
+        (Some(city), Some(country)) => Address::Structured(StructuredAddress {
+            street: street.to_string(),
+            city: city.to_string(),
+            state: element.text("RawStateCodeText").map(str::to_string),
+            postal_code: element.text("RawZIPCode").map(str::to_string),
+            country: country.clone(),
+        }),
+        _ => Address::Freeform(street.to_string()),
+    };
+    (address, country)
+}
+
+/// A handle made from the name, e.g. `example_sons_bank` for "Example & Sons Bank", numbered if it's already taken
+fn unique_handle<T>(
+    name: &str,
+    fallback: &str,
+    existing: &[T],
+    handle: impl Fn(&T) -> &String,
+) -> String {
+    let mut base = String::new();
+    for c in name.to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() {
+            base.push(c);
+        } else if !base.is_empty() && !base.ends_with('_') {
+            base.push('_');
+        }
+    }
+    let base = match base.trim_end_matches('_') {
+        "" => fallback.to_string(),
+        base => base.to_string(),
+    };
+
+    let taken = |candidate: &str| existing.iter().any(|item| handle(item) == candidate);
+    let mut candidate = base.clone();
+    let mut number = 1;
+    while taken(&candidate) {
+        number += 1;
+        candidate = format!("{}_{}", base, number);
+    }
+    candidate
+}
+
+/// An element of the batch with its namespace prefix dropped, its text, and its child elements
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    text: String,
+    children: Vec<Element>,
+}
+
+impl Element {
+    fn parse(xml: &str) -> Result<Self> {
+        let mut reader = Reader::from_str(xml);
+        reader.trim_text(true);
+        // The elements being read, the innermost last
+        let mut open: Vec<Element> = Vec::new();
+        loop {
+            let event = reader
+                .read_event()
+                .with_context(|| format!("Invalid XML at byte {}", reader.buffer_position()))?;
+            match event {
+                Event::Start(start) => open.push(Element {
+                    name: String::from_utf8_lossy(start.local_name().as_ref()).into_owned(),
+                    ..Default::default()
+                }),
+                Event::Empty(empty) => {
+                    let element = Element {
+                        name: String::from_utf8_lossy(empty.local_name().as_ref()).into_owned(),
+                        ..Default::default()
+                    };
+                    match open.last_mut() {
+                        Some(parent) => parent.children.push(element),
+                        None => return Ok(element),
+                    }
+                }
+                Event::Text(text) => {
+                    if let Some(element) = open.last_mut() {
+                        element.text.push_str(&text.unescape()?);
+                    }
+                }
+                Event::CData(data) => {
+                    if let Some(element) = open.last_mut() {
+                        element
+                            .text
+                            .push_str(&String::from_utf8_lossy(&data.into_inner()));
+                    }
+                }
+                Event::End(_) => {
+                    let element = open.pop().expect("the reader checks elements are balanced");
+                    match open.last_mut() {
+                        Some(parent) => parent.children.push(element),
+                        None => return Ok(element),
+                    }
+                }
+                Event::Eof => bail!("The XML has no root element"),
+                _ => {}
+            }
+        }
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    /// The trimmed text of the named child, unless it's empty
+    fn text(&self, name: &str) -> Option<&str> {
+        self.child(name)
+            .map(|child| child.text.trim())
+            .filter(|text| !text.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{App, AppConfig};
+    use crate::examples::{self, Example, EXAMPLE_YEAR};
+    use crate::fincen::fbar_xml;
+    use crate::report::Report;
+    use tempfile::TempDir;
+
+    /// Exports an example's report as the filed copy would be
+    fn filed_example(example: Example) -> Result<(String, Report)> {
+        let dir = TempDir::new()?;
+        examples::write_example(dir.path(), example)?;
+        let mut config = AppConfig::new(dir.path().to_path_buf());
+        config.cache = false;
+        let app = App::new(config);
+        let (user_data, context) = app.data()?;
+        let report = Report::generate(EXAMPLE_YEAR, user_data, context)?;
+        let xml = fbar_xml(
+            &report,
+            user_data,
+            NaiveDate::from_ymd_opt(2024, 3, 15).unwrap(),
+        )?;
+        Ok((xml, report))
+    }
+
+    #[test]
+    fn test_read_filed_xml() -> Result<()> {
+        let (xml, report) = filed_example(Example::JointCouple)?;
+        let filed = read_filed_xml(&xml)?;
+        assert_eq!(filed.year, EXAMPLE_YEAR);
+        assert_eq!(filed.summary_accounts, None);
+        assert_eq!(
+            filed.user_data.filings[0].filed_on,
+            NaiveDate::from_ymd_opt(2024, 3, 15)
+        );
+
+        let user_data = &filed.user_data;
+        assert_eq!(user_data.filers.len(), 2);
+        assert_eq!(user_data.filers[0].name, "Alex Example");
+        assert_eq!(user_data.filers[0].tin.as_deref(), Some("123456789"));
+        assert_eq!(user_data.accounts.len(), report.accounts.len());
+        // Each provider is listed once, however many accounts it holds
+        let mut providers: Vec<&str> = user_data
+            .providers
+            .iter()
+            .map(|provider| provider.name.as_str())
+            .collect();
+        providers.dedup();
+        assert_eq!(providers.len(), user_data.providers.len());
+        assert!(user_data
+            .accounts
+            .iter()
+            .any(|account| account.owners.len() == 2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_starter() -> Result<()> {
+        for example in [
+            Example::SingleFiler,
+            Example::JointCouple,
+            Example::EntityHeld,
+        ] {
+            let (xml, report) = filed_example(example)?;
+            let dir = TempDir::new()?;
+            let source = dir.path().join("filed.xml");
+            std::fs::write(&source, &xml)?;
+            let out = dir.path().join("data");
+            write_starter(&source, &out)?;
+            assert!(write_starter(&source, &out).is_err());
+
+            // The starter directory loads, even in strict mode, but can't be reported until each account's currency
+            // is set
+            let mut config = AppConfig::new(out);
+            config.cache = false;
+            config.strict = true;
+            let app = App::new(config);
+            let (user_data, context) = app.data()?;
+            let error = Report::generate(EXAMPLE_YEAR, user_data, context).unwrap_err();
+            assert!(
+                error.to_string().contains("has no currency yet"),
+                "{}",
+                error
+            );
+
+            // Set to USD, it reports the maxima that were filed
+            let mut user_data = user_data.clone();
+            for account in &mut user_data.accounts {
+                assert!(account.currency_unknown());
+                assert_eq!(account.balances[0].currency.as_deref(), Some("USD"));
+                assert_eq!(
+                    account.balances[0].usd_value,
+                    Some(account.balances[0].amount)
+                );
+                account.currency_code = "USD".to_string();
+            }
+            let starter = Report::generate(EXAMPLE_YEAR, &user_data, context)?;
+            let maxima = |report: &Report| {
+                let mut maxima: Vec<Decimal> = report
+                    .accounts
+                    .iter()
+                    .map(|account| account.max_balance_usd.amount.ceil())
+                    .collect();
+                maxima.sort();
+                maxima
+            };
+            assert_eq!(maxima(&starter), maxima(&report), "{}", example);
+        }
+
+        let dir = TempDir::new()?;
+        let pdf = dir.path().join("filed.pdf");
+        std::fs::write(&pdf, "%PDF-1.7\n")?;
+        let error = write_starter(&pdf, &dir.path().join("data")).unwrap_err();
+        assert!(error.to_string().contains("is a PDF"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_summary_filing() -> Result<()> {
+        let (xml, _) = filed_example(Example::ManyAccounts)?;
+        let filed = read_filed_xml(&xml)?;
+        assert_eq!(filed.summary_accounts, Some(26));
+        assert!(filed.user_data.accounts.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_late_filing_narrative() -> Result<()> {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<fc2:EFilingBatchXML xmlns:fc2="www.fincen.gov/base" ActivityCount="1">
+  <fc2:FormTypeCode>FBARX</fc2:FormTypeCode>
+  <fc2:Activity SeqNum="1">
+    <fc2:ApprovalOfficialSignatureDateText>20250201</fc2:ApprovalOfficialSignatureDateText>
+    <fc2:ForeignAccountActivity SeqNum="2">
+      <fc2:ReportCalendarYearText>2023</fc2:ReportCalendarYearText>
+    </fc2:ForeignAccountActivity>
+    <fc2:ActivityNarrativeInformation SeqNum="3">
+      <fc2:ActivityNarrativeSequenceNumber>1</fc2:ActivityNarrativeSequenceNumber>
+      <fc2:ActivityNarrativeText>Late filing reason A: Forgot to file. Moved abroad &amp; missed it.</fc2:ActivityNarrativeText>
+    </fc2:ActivityNarrativeInformation>
+  </fc2:Activity>
+</fc2:EFilingBatchXML>
+"#;
+        let filed = read_filed_xml(xml)?;
+        assert_eq!(
+            filed.user_data.filings,
+            vec![Filing {
+                year: 2023,
+                filed_on: NaiveDate::from_ymd_opt(2025, 2, 1),
+                late_reason: Some(LateFilingReason::ForgotToFile),
+                explanation: Some("Moved abroad & missed it.".to_string()),
+            }]
+        );
+
+        let error = read_filed_xml(&xml.replace(">2023<", ">9999999<")).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Activity/ForeignAccountActivity/ReportCalendarYearText \"9999999\""));
+        assert!(read_filed_xml(&xml.replace(">2023<", ">20x3<")).is_err());
+
+        assert!(read_filed_xml("<html></html>").is_err());
+        assert!(read_filed_xml("<fc2:EFilingBatchXML>").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unique_handle() {
+        let taken = vec!["example_sons_bank".to_string()];
+        assert_eq!(
+            unique_handle("Example & Sons Bank", "provider", &taken, |handle| handle),
+            "example_sons_bank_2"
+        );
+        assert_eq!(
+            unique_handle("三菱", "provider", &taken, |handle| handle),
+            "provider"
+        );
+    }
+}
@@ -0,0 +1,55 @@
+pub mod filed;
+pub mod schema;
+pub mod transliterate;
+pub mod validation;
+pub mod worksheet;
+pub mod xml;
+pub use self::schema::SchemaVersion;
+pub use self::transliterate::{Transliterated, Transliteration};
+pub use self::validation::{validate_report, FieldIssue, IssueKind};
+pub use self::worksheet::worksheet_pdf;
+pub use self::xml::{fbar_xml, transliterations};
+
+use crate::data::{Account, IdentifierKind};
+
+/// Joins an account's identifiers into the single "account number or other designation" field FinCEN expects,
+/// e.g. a sort code followed by an account number. BICs identify the bank rather than the account, so are left out.
+pub fn account_number(account: &Account) -> Option<String> {
+    let identifiers: Vec<&str> = [
+        (account.secondary_identifier_kind(), &account.identifier2),
+        (account.primary_identifier_kind(), &account.identifier1),
+    ]
+    .into_iter()
+    .filter(|(kind, _)| *kind != Some(IdentifierKind::Bic))
+    .filter_map(|(_, identifier)| identifier.as_deref())
+    .map(|identifier| identifier.trim())
+    .filter(|identifier| !identifier.is_empty())
+    .collect();
+
+    (!identifiers.is_empty()).then(|| identifiers.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_number() {
+        let mut account = Account {
+            identifier1: Some("12345678".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(account_number(&account).as_deref(), Some("12345678"));
+
+        account.identifier2 = Some("12-34-56".to_string());
+        assert_eq!(
+            account_number(&account).as_deref(),
+            Some("12-34-56 12345678")
+        );
+
+        account.identifier2_kind = Some(IdentifierKind::Bic);
+        assert_eq!(account_number(&account).as_deref(), Some("12345678"));
+
+        assert_eq!(account_number(&Account::default()), None);
+    }
+}
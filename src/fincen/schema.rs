@@ -0,0 +1,107 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::data::Filing;
+
+/// Versions of the BSA E-Filing FBAR batch schema the XML can be written and checked against. FinCEN revises the
+/// schema from time to time and accepts the prior version for a while after, so a filing can target either.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SchemaVersion {
+    /// The prior version, without a coded late filing reason
+    #[serde(rename = "1.1")]
+    V1_1,
+    /// The current version
+    #[default]
+    #[serde(rename = "1.2")]
+    V1_2,
+}
+
+impl SchemaVersion {
+    pub const ALL: [SchemaVersion; 2] = [SchemaVersion::V1_1, SchemaVersion::V1_2];
+
+    /// Whether the schema has a `LateFilingReasonCode` element. Earlier versions only take the reason as narrative.
+    pub fn has_late_filing_reason_code(&self) -> bool {
+        match self {
+            SchemaVersion::V1_1 => false,
+            SchemaVersion::V1_2 => true,
+        }
+    }
+
+    /// The narrative filed with the FBAR: the late filing explanation, preceded by the reason when the schema has no
+    /// code for it
+    pub fn narrative(&self, filing: &Filing) -> Option<String> {
+        let reason = filing
+            .late_reason
+            .filter(|_| !self.has_late_filing_reason_code())
+            .map(|reason| {
+                format!(
+                    "Late filing reason {}: {}.",
+                    reason.code(),
+                    reason.description()
+                )
+            });
+        match (reason, filing.explanation.as_deref()) {
+            (Some(reason), Some(explanation)) => Some(format!("{} {}", reason, explanation)),
+            (Some(reason), None) => Some(reason),
+            (None, explanation) => explanation.map(str::to_string),
+        }
+    }
+}
+
+impl fmt::Display for SchemaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SchemaVersion::V1_1 => "1.1",
+            SchemaVersion::V1_2 => "1.2",
+        })
+    }
+}
+
+impl FromStr for SchemaVersion {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|version| version.to_string() == value)
+            .ok_or_else(|| {
+                format!(
+                    "unknown FinCEN schema version {:?}, expected one of {}",
+                    value,
+                    Self::ALL.map(|version| version.to_string()).join(", ")
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::LateFilingReason;
+
+    #[test]
+    fn test_narrative() {
+        let filing = Filing {
+            year: 2022,
+            filed_on: None,
+            late_reason: Some(LateFilingReason::ForgotToFile),
+            explanation: Some("Moved abroad that year.".to_string()),
+        };
+        assert_eq!(
+            SchemaVersion::V1_2.narrative(&filing).as_deref(),
+            Some("Moved abroad that year.")
+        );
+        assert_eq!(
+            SchemaVersion::V1_1.narrative(&filing).as_deref(),
+            Some("Late filing reason A: Forgot to file. Moved abroad that year.")
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("1.1".parse(), Ok(SchemaVersion::V1_1));
+        assert!("2.0".parse::<SchemaVersion>().is_err());
+    }
+}
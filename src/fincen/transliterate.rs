@@ -0,0 +1,331 @@
+//! Transliteration of names and addresses to the printable ASCII BSA E-Filing accepts, e.g. `Müller` to `Mueller`
+//! and `ゆうちょ` to `yuucho`. Latin letters with diacritics, typographic punctuation, full-width forms and kana are
+//! transliterated; anything else, like kanji, needs an entry in `replacements` or it's flagged by `validate`.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct Transliteration {
+    /// Whether to transliterate at all; when off, values are written to the XML as they are in `data.yml`
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    /// Text to replace before the built-in transliteration, longest first, e.g. a bank's name in kanji with its
+    /// romanized name: `三菱UFJ銀行: MUFG Bank`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub replacements: BTreeMap<String, String>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl Default for Transliteration {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            replacements: BTreeMap::new(),
+        }
+    }
+}
+
+/// A value the XML export changed from what's in `data.yml`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transliterated {
+    /// Handle of the account the value belongs to, or `filer` for the filer's own details
+    pub subject: String,
+    /// XML element the value is written to
+    pub field: &'static str,
+    pub original: String,
+    pub exported: String,
+}
+
+impl Transliterated {
+    /// Whether BSA E-Filing accepts the exported value, or it still has characters the transliteration couldn't
+    /// handle
+    pub fn is_accepted(&self) -> bool {
+        is_accepted(&self.exported)
+    }
+}
+
+/// Whether every character is printable ASCII
+pub fn is_accepted(text: &str) -> bool {
+    text.chars().all(|c| matches!(c, ' '..='~'))
+}
+
+impl Transliteration {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The text with the replacements and built-in transliteration applied, or as it is when disabled
+    pub fn apply(&self, text: &str) -> String {
+        if !self.enabled {
+            return text.to_string();
+        }
+
+        let mut replacements: Vec<(&String, &String)> = self.replacements.iter().collect();
+        replacements.sort_by_key(|(from, _)| std::cmp::Reverse(from.chars().count()));
+        let mut text = text.to_string();
+        for (from, to) in replacements {
+            text = text.replace(from.as_str(), to);
+        }
+        if is_accepted(&text) {
+            return text;
+        }
+
+        let chars: Vec<char> = text.chars().map(to_hiragana).collect();
+        let mut output = String::with_capacity(text.len());
+        // A small tsu doubles the consonant that follows it
+        let mut sokuon = false;
+        let mut index = 0;
+        while index < chars.len() {
+            let c = chars[index];
+            index += 1;
+            if c == 'っ' {
+                sokuon = true;
+                continue;
+            }
+            let Some(syllable) = kana(c) else {
+                sokuon = false;
+                match latin(c) {
+                    Some(replacement) => output.push_str(replacement),
+                    None => output.push(full_width(c)),
+                }
+                continue;
+            };
+
+            let mut syllable = syllable.to_string();
+            // A consonant and i followed by a small ya, yu or yo make one syllable: き + ょ is kyo, し + ゃ sha
+            if let Some(vowel) = chars.get(index).copied().and_then(small_y) {
+                if syllable.len() > 1 && syllable.ends_with('i') {
+                    syllable.pop();
+                    if !(syllable.ends_with("sh") || syllable.ends_with("ch") || syllable == "j") {
+                        syllable.push('y');
+                    }
+                    syllable.push(vowel);
+                    index += 1;
+                }
+            }
+            if std::mem::take(&mut sokuon) {
+                match syllable.chars().next() {
+                    Some('c') => output.push('t'),
+                    Some(first) if !"aiueon".contains(first) => output.push(first),
+                    _ => {}
+                }
+            }
+            output.push_str(&syllable);
+        }
+        output
+    }
+}
+
+/// Katakana as the matching hiragana, so one table romanizes both
+fn to_hiragana(c: char) -> char {
+    match c {
+        '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+/// The vowel of a small ya, yu or yo
+fn small_y(c: char) -> Option<char> {
+    match c {
+        'ゃ' => Some('a'),
+        'ゅ' => Some('u'),
+        'ょ' => Some('o'),
+        _ => None,
+    }
+}
+
+/// Hepburn romanization of a hiragana, without macrons
+fn kana(c: char) -> Option<&'static str> {
+    Some(match c {
+        'あ' | 'ぁ' => "a",
+        'い' | 'ぃ' | 'ゐ' => "i",
+        'う' | 'ぅ' => "u",
+        'え' | 'ぇ' | 'ゑ' => "e",
+        'お' | 'ぉ' | 'を' => "o",
+        'か' => "ka",
+        'き' => "ki",
+        'く' => "ku",
+        'け' => "ke",
+        'こ' => "ko",
+        'が' => "ga",
+        'ぎ' => "gi",
+        'ぐ' => "gu",
+        'げ' => "ge",
+        'ご' => "go",
+        'さ' => "sa",
+        'し' => "shi",
+        'す' => "su",
+        'せ' => "se",
+        'そ' => "so",
+        'ざ' => "za",
+        'じ' | 'ぢ' => "ji",
+        'ず' | 'づ' => "zu",
+        'ぜ' => "ze",
+        'ぞ' => "zo",
+        'た' => "ta",
+        'ち' => "chi",
+        'つ' => "tsu",
+        'て' => "te",
+        'と' => "to",
+        'だ' => "da",
+        'で' => "de",
+        'ど' => "do",
+        'な' => "na",
+        'に' => "ni",
+        'ぬ' => "nu",
+        'ね' => "ne",
+        'の' => "no",
+        'は' => "ha",
+        'ひ' => "hi",
+        'ふ' => "fu",
+        'へ' => "he",
+        'ほ' => "ho",
+        'ば' => "ba",
+        'び' => "bi",
+        'ぶ' => "bu",
+        'べ' => "be",
+        'ぼ' => "bo",
+        'ぱ' => "pa",
+        'ぴ' => "pi",
+        'ぷ' => "pu",
+        'ぺ' => "pe",
+        'ぽ' => "po",
+        'ま' => "ma",
+        'み' => "mi",
+        'む' => "mu",
+        'め' => "me",
+        'も' => "mo",
+        'や' | 'ゃ' => "ya",
+        'ゆ' | 'ゅ' => "yu",
+        'よ' | 'ょ' => "yo",
+        'ら' => "ra",
+        'り' => "ri",
+        'る' => "ru",
+        'れ' => "re",
+        'ろ' => "ro",
+        'わ' | 'ゎ' => "wa",
+        'ん' => "n",
+        'ゔ' => "vu",
+        // The long vowel mark is left out, as macrons would be
+        'ー' => "",
+        '・' => " ",
+        _ => return None,
+    })
+}
+
+/// Latin letters with diacritics and typographic punctuation, as the ASCII they're usually written as
+fn latin(c: char) -> Option<&'static str> {
+    Some(match c {
+        'ä' => "ae",
+        'ö' => "oe",
+        'ü' => "ue",
+        'Ä' => "Ae",
+        'Ö' => "Oe",
+        'Ü' => "Ue",
+        'ß' => "ss",
+        'ẞ' => "SS",
+        'æ' => "ae",
+        'Æ' => "AE",
+        'œ' => "oe",
+        'Œ' => "OE",
+        'ø' => "o",
+        'Ø' => "O",
+        'þ' => "th",
+        'Þ' => "Th",
+        'ð' => "d",
+        'Ð' => "D",
+        'à' | 'á' | 'â' | 'ã' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'À' | 'Á' | 'Â' | 'Ã' | 'Å' | 'Ā' | 'Ă' | 'Ą' => "A",
+        'ç' | 'ć' | 'č' => "c",
+        'Ç' | 'Ć' | 'Č' => "C",
+        'ď' | 'đ' => "d",
+        'Ď' | 'Đ' => "D",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => "e",
+        'È' | 'É' | 'Ê' | 'Ë' | 'Ē' | 'Ė' | 'Ę' | 'Ě' => "E",
+        'ğ' => "g",
+        'Ğ' => "G",
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'į' | 'ı' => "i",
+        'Ì' | 'Í' | 'Î' | 'Ï' | 'Ī' | 'Į' | 'İ' => "I",
+        'ł' | 'ľ' | 'ĺ' => "l",
+        'Ł' | 'Ľ' | 'Ĺ' => "L",
+        'ñ' | 'ń' | 'ň' => "n",
+        'Ñ' | 'Ń' | 'Ň' => "N",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ō' | 'ő' => "o",
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ō' | 'Ő' => "O",
+        'ř' => "r",
+        'Ř' => "R",
+        'ś' | 'š' | 'ş' | 'ș' => "s",
+        'Ś' | 'Š' | 'Ş' | 'Ș' => "S",
+        'ť' | 'ţ' | 'ț' => "t",
+        'Ť' | 'Ţ' | 'Ț' => "T",
+        'ù' | 'ú' | 'û' | 'ū' | 'ů' | 'ű' | 'ų' => "u",
+        'Ù' | 'Ú' | 'Û' | 'Ū' | 'Ů' | 'Ű' | 'Ų' => "U",
+        'ý' | 'ÿ' => "y",
+        'Ý' | 'Ÿ' => "Y",
+        'ź' | 'ż' | 'ž' => "z",
+        'Ź' | 'Ż' | 'Ž' => "Z",
+        '‘' | '’' | '‚' | '′' => "'",
+        '“' | '”' | '„' | '«' | '»' => "\"",
+        '‐' | '‑' | '–' | '—' => "-",
+        '…' => "...",
+        '\u{00A0}' | '\u{3000}' => " ",
+        '、' => ",",
+        '。' => ".",
+        _ => return None,
+    })
+}
+
+/// Full-width letters, digits and punctuation as their ASCII forms, anything else as it is
+fn full_width(c: char) -> char {
+    match c {
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let transliteration = Transliteration::default();
+        assert_eq!(
+            transliteration.apply("Münchner Straße 12"),
+            "Muenchner Strasse 12"
+        );
+        assert_eq!(
+            transliteration.apply("Crédit Agricole, 12 rue de l’Église"),
+            "Credit Agricole, 12 rue de l'Eglise"
+        );
+        assert_eq!(transliteration.apply("Øresund Bank"), "Oresund Bank");
+        assert_eq!(transliteration.apply("ゆうちょ"), "yuucho");
+        assert_eq!(transliteration.apply("マッチ・センター"), "matchi senta");
+        assert_eq!(transliteration.apply("ＵＦＪ　１"), "UFJ 1");
+
+        // Kanji have no built-in transliteration
+        let bank = transliteration.apply("三菱UFJ銀行");
+        assert_eq!(bank, "三菱UFJ銀行");
+        assert!(!is_accepted(&bank));
+
+        let transliteration = Transliteration {
+            enabled: true,
+            replacements: BTreeMap::from([
+                ("銀行".to_string(), " Bank".to_string()),
+                ("三菱UFJ銀行".to_string(), "MUFG Bank".to_string()),
+            ]),
+        };
+        assert_eq!(transliteration.apply("三菱UFJ銀行"), "MUFG Bank");
+
+        let transliteration = Transliteration {
+            enabled: false,
+            ..Default::default()
+        };
+        assert_eq!(transliteration.apply("Müller"), "Müller");
+    }
+}
@@ -0,0 +1,1007 @@
+use chrono::{Datelike, NaiveDate};
+use isocountry::CountryCode;
+use rust_decimal::Decimal;
+use std::fmt;
+
+use super::SchemaVersion;
+use crate::data::address::parse_country_code;
+use crate::data::{Account, Address, Filing, LateFilingReason, Provider, UserData};
+use crate::diagnostics::{codes, Diagnostic, Severity};
+use crate::report::{AccountReport, Report};
+use crate::report_context::RateSource;
+
+/// Field limits imposed by the BSA E-Filing FBAR schema
+pub const MAX_INSTITUTION_NAME_LENGTH: usize = 150;
+pub const MAX_ADDRESS_LENGTH: usize = 100;
+pub const MAX_CITY_LENGTH: usize = 50;
+pub const MAX_POSTAL_CODE_LENGTH: usize = 9;
+pub const MAX_ACCOUNT_NUMBER_LENGTH: usize = 40;
+/// Name of the owner of a Part IV or Part V account
+pub const MAX_OWNER_NAME_LENGTH: usize = 150;
+/// Maximum account values are filed in whole dollars
+pub const MAX_ACCOUNT_VALUE_DIGITS: usize = 15;
+/// Explanation of a late filing
+pub const MAX_LATE_FILING_EXPLANATION_LENGTH: usize = 750;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IssueKind {
+    /// BSA E-Filing will accept the value but cut it short
+    Truncated,
+    /// BSA E-Filing will refuse the filing
+    Rejected,
+    /// Accepted as-is, but worth a second look before filing
+    Warning,
+}
+
+/// A value that won't survive upload to BSA E-Filing intact
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldIssue {
+    /// Stable code from `diagnostics::codes`, explained by `fbar_prep explain`
+    pub code: &'static str,
+    pub account: String,
+    pub field: &'static str,
+    pub kind: IssueKind,
+    pub message: String,
+}
+
+impl fmt::Display for FieldIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            IssueKind::Truncated => "will be truncated",
+            IssueKind::Rejected => "will be rejected",
+            IssueKind::Warning => "warning",
+        };
+        write!(
+            f,
+            "{} {} {} ({}): {}",
+            self.code, self.account, self.field, kind, self.message
+        )
+    }
+}
+
+impl From<&FieldIssue> for Diagnostic {
+    /// Values BSA E-Filing would reject are errors; truncated values and everything else to check are warnings
+    fn from(issue: &FieldIssue) -> Self {
+        let severity = match issue.kind {
+            IssueKind::Rejected => Severity::Error,
+            IssueKind::Truncated | IssueKind::Warning => Severity::Warning,
+        };
+        Diagnostic::new(
+            severity,
+            issue.code,
+            format!("{}: {}", issue.field, issue.message),
+        )
+        .at(issue.account.clone())
+    }
+}
+
+/// Pre-export pass over a report, flagging values that exceed the limits of the FinCEN schema version `data.yml`
+/// targets
+pub fn validate_report(report: &Report, user_data: &UserData) -> Vec<FieldIssue> {
+    let _span = tracing::info_span!("validate", year = report.year).entered();
+    let mut issues = Vec::new();
+    let version = user_data.fincen_schema_version.unwrap_or_default();
+
+    if let Some(filing) = user_data.filing_for(report.year) {
+        validate_filing(filing, version, &mut issues);
+    }
+    validate_residency(report.year, user_data, &mut issues);
+
+    // Dates decide which records count towards the maxima, so they're checked even when only the count is filed
+    for account_report in &report.accounts {
+        if let Some(account) = user_data
+            .accounts
+            .iter()
+            .find(|account| account.handle == account_report.handle)
+        {
+            let provider = user_data
+                .providers
+                .iter()
+                .find(|provider| provider.handle == account.provider);
+            validate_dates(report.year, account, provider, &mut issues);
+            if let Some(conflict) = account.kind_conflict() {
+                issues.push(FieldIssue {
+                    code: codes::ACCOUNT_KIND_MISMATCH,
+                    account: account.handle.clone(),
+                    field: "account type",
+                    kind: IssueKind::Warning,
+                    message: format!(
+                        "declared as {}, but {}, which suggests {}; check `kind`",
+                        account.kind, conflict.reason, conflict.kind
+                    ),
+                });
+            }
+        }
+        if let RateSource::NearestYear { year, .. } = &account_report.rate_source {
+            issues.push(FieldIssue {
+                code: codes::NEAREST_YEAR_RATE,
+                account: account_report.handle.clone(),
+                field: "exchange rate",
+                kind: IssueKind::Warning,
+                message: format!(
+                    "converted at the {} rate for {}, as there's none for {}",
+                    account_report.currency_code.to_uppercase(),
+                    year,
+                    report.year
+                ),
+            });
+        }
+    }
+
+    // The filer's name and address are uploaded even in summary mode
+    validate_characters(report, user_data, &mut issues);
+
+    // Only the number of accounts is filed in summary mode, so none of their fields are uploaded
+    if report.summary.is_some() {
+        tracing::info!("Report files only the number of accounts");
+        return issues;
+    }
+
+    for account_report in &report.accounts {
+        let mut issue = |code, field, kind, message| {
+            issues.push(FieldIssue {
+                code,
+                account: account_report.handle.clone(),
+                field,
+                kind,
+                message,
+            })
+        };
+
+        match user_data
+            .providers
+            .iter()
+            .find(|provider| provider.handle == account_report.provider)
+        {
+            Some(provider) => {
+                if let Some(message) =
+                    check_length(&provider.institution_name(), MAX_INSTITUTION_NAME_LENGTH)
+                {
+                    issue(
+                        codes::VALUE_TOO_LONG,
+                        "institution name",
+                        IssueKind::Truncated,
+                        message,
+                    );
+                }
+                for (field, value, limit) in address_fields(provider) {
+                    if let Some(message) = check_length(value, limit) {
+                        issue(codes::VALUE_TOO_LONG, field, IssueKind::Truncated, message);
+                    }
+                }
+
+                match provider.country_code() {
+                    Ok(Some(country))
+                        if US_COUNTRY_CODES.contains(&country)
+                            && provider.foreign_branch_of.is_some() =>
+                    {
+                        issue(
+                            codes::AMBIGUOUS_BRANCH_COUNTRY,
+                            "institution country",
+                            IssueKind::Warning,
+                            format!(
+                                "provider is a foreign branch of {} but is domiciled in {}; give the branch's own \
+                                 address, or remove `foreign_branch_of` if the account is held in the US",
+                                provider.foreign_branch_of.as_deref().unwrap_or_default(),
+                                country.alpha2()
+                            ),
+                        )
+                    }
+                    Ok(Some(country)) if US_COUNTRY_CODES.contains(&country) => issue(
+                        codes::US_INSTITUTION,
+                        "institution country",
+                        IssueKind::Warning,
+                        format!(
+                            "provider is domiciled in {}; accounts at US institutions are generally not reportable",
+                            country.alpha2()
+                        ),
+                    ),
+                    Ok(Some(_)) => {
+                        if provider.foreign_branch_of.is_none() && is_us_national_bank(&provider.name) {
+                            issue(
+                                codes::UNMARKED_FOREIGN_BRANCH,
+                                "institution name",
+                                IssueKind::Warning,
+                                format!(
+                                    "{:?} looks like a US national bank; if the provider is its foreign branch, set \
+                                     `foreign_branch_of` to the bank's name",
+                                    provider.name
+                                ),
+                            );
+                        }
+                    }
+                    Ok(None) => issue(
+                        codes::MISSING_COUNTRY,
+                        "institution country",
+                        IssueKind::Rejected,
+                        "no country given; add `country` or a structured address".to_string(),
+                    ),
+                    Err(err) => issue(
+                        codes::MISSING_COUNTRY,
+                        "institution country",
+                        IssueKind::Rejected,
+                        err.to_string(),
+                    ),
+                }
+
+                let address_country = provider
+                    .address
+                    .country()
+                    .and_then(|code| parse_country_code(code).ok());
+                if let (Ok(Some(country)), Some(address_country)) =
+                    (provider.country_code(), address_country)
+                {
+                    if country != address_country {
+                        issue(
+                            codes::AMBIGUOUS_BRANCH_COUNTRY,
+                            "institution country",
+                            IssueKind::Warning,
+                            format!(
+                                "`country` is {} but the address is in {}; {} is filed. For a foreign branch of a \
+                                 US institution, give the branch's address rather than the head office's",
+                                country.alpha2(),
+                                address_country.alpha2(),
+                                country.alpha2()
+                            ),
+                        );
+                    }
+                }
+            }
+            None => issue(
+                codes::UNKNOWN_PROVIDER,
+                "institution name",
+                IssueKind::Rejected,
+                format!("unknown provider {:?}", account_report.provider),
+            ),
+        }
+
+        let account_number = user_data
+            .accounts
+            .iter()
+            .find(|account| account.handle == account_report.handle)
+            .and_then(super::account_number);
+        match account_number {
+            Some(account_number) => {
+                if let Some(message) = check_length(&account_number, MAX_ACCOUNT_NUMBER_LENGTH) {
+                    issue(
+                        codes::VALUE_TOO_LONG,
+                        "account number",
+                        IssueKind::Truncated,
+                        message,
+                    );
+                }
+            }
+            None => issue(
+                codes::MISSING_ACCOUNT_NUMBER,
+                "account number",
+                IssueKind::Rejected,
+                "no account identifier given".to_string(),
+            ),
+        }
+
+        if let Some(message) = check_value(account_report) {
+            issue(
+                codes::INVALID_MAXIMUM,
+                "maximum account value",
+                IssueKind::Rejected,
+                message,
+            );
+        }
+
+        if let Some(handle) = &account_report.joint_owner {
+            match report.joint_owners.iter().find(|owner| &owner.handle == handle) {
+                Some(owner) if owner.tin.is_none() => issue(
+                    codes::MISSING_JOINT_OWNER_TIN,
+                    "joint owner TIN",
+                    IssueKind::Warning,
+                    format!(
+                        "no TIN given for {}; Part III asks for the principal joint owner's TIN if known",
+                        owner.name
+                    ),
+                ),
+                Some(_) => {}
+                None => issue(
+                    codes::UNKNOWN_JOINT_OWNER,
+                    "joint owner name",
+                    IssueKind::Rejected,
+                    format!("unknown filer {:?}", handle),
+                ),
+            }
+        }
+
+        if let Some(authority) = &account_report.signature_authority {
+            if let Some(message) = check_length(&authority.owner, MAX_OWNER_NAME_LENGTH) {
+                issue(
+                    codes::VALUE_TOO_LONG,
+                    "owner name",
+                    IssueKind::Truncated,
+                    message,
+                );
+            }
+        }
+
+        if let Some(handle) = &account_report.entity {
+            match report.entities.iter().find(|owner| &owner.handle == handle) {
+                Some(owner) => {
+                    if let Some(message) = check_length(&owner.name, MAX_OWNER_NAME_LENGTH) {
+                        issue(
+                            codes::VALUE_TOO_LONG,
+                            "owner name",
+                            IssueKind::Truncated,
+                            message,
+                        );
+                    }
+                    if owner.tin.is_none() {
+                        issue(
+                            codes::MISSING_OWNER_TIN,
+                            "owner TIN",
+                            IssueKind::Warning,
+                            format!(
+                                "no TIN given for {}; Part V asks for the owner's US TIN, or its foreign one if it \
+                                 has none",
+                                owner.name
+                            ),
+                        );
+                    }
+                }
+                None => issue(
+                    codes::UNKNOWN_ENTITY,
+                    "owner name",
+                    IssueKind::Rejected,
+                    format!("unknown entity {:?}", handle),
+                ),
+            }
+        }
+    }
+
+    tracing::info!(issues = issues.len(), "Validated report");
+    issues
+}
+
+/// Values that still have characters BSA E-Filing doesn't accept once transliterated, like kanji
+fn validate_characters(report: &Report, user_data: &UserData, issues: &mut Vec<FieldIssue>) {
+    let Ok(values) = super::transliterations(report, user_data) else {
+        // Writing the XML fails the same way, with the reason
+        return;
+    };
+    for value in values.into_iter().filter(|value| !value.is_accepted()) {
+        issues.push(FieldIssue {
+            code: codes::UNSUPPORTED_CHARACTERS,
+            account: value.subject,
+            field: value.field,
+            kind: IssueKind::Rejected,
+            message: format!(
+                "{:?} has characters that can't be transliterated; add a `transliteration.replacements` entry for them",
+                value.exported
+            ),
+        });
+    }
+}
+
+/// Late filings need one of the form's reasons, and an explanation when the reason is `other`
+fn validate_filing(filing: &Filing, version: SchemaVersion, issues: &mut Vec<FieldIssue>) {
+    let mut issue = |code, field, kind, message| {
+        issues.push(FieldIssue {
+            code,
+            account: "filing".to_string(),
+            field,
+            kind,
+            message,
+        })
+    };
+
+    let deadline = Filing::deadline(filing.year);
+    match (filing.filed_on.zip(deadline), filing.late_reason) {
+        (Some((filed_on, deadline)), None) if filed_on > deadline => issue(
+            codes::MISSING_LATE_REASON,
+            "late filing reason",
+            IssueKind::Rejected,
+            format!(
+                "filed on {}, after the {} deadline, without a late_reason",
+                filed_on, deadline
+            ),
+        ),
+        (Some((filed_on, deadline)), Some(reason)) if filed_on <= deadline => issue(
+            codes::NEEDLESS_LATE_REASON,
+            "late filing reason",
+            IssueKind::Warning,
+            format!("{} is given, but the filing isn't late", reason.code()),
+        ),
+        _ => {}
+    }
+
+    if filing.explanation.is_none() && filing.late_reason == Some(LateFilingReason::Other) {
+        issue(
+            codes::MISSING_LATE_EXPLANATION,
+            "late filing explanation",
+            IssueKind::Rejected,
+            "an explanation is required when the late_reason is other".to_string(),
+        );
+    }
+    // Schemas without a reason code take the reason in the narrative, which then counts towards its length
+    if let Some(narrative) = version.narrative(filing) {
+        if let Some(message) = check_length(&narrative, MAX_LATE_FILING_EXPLANATION_LENGTH) {
+            issue(
+                codes::VALUE_TOO_LONG,
+                "late filing explanation",
+                IssueKind::Truncated,
+                message,
+            );
+        }
+    }
+    if let Some(reason) = filing.late_reason {
+        if !version.has_late_filing_reason_code() {
+            issue(
+                codes::LATE_REASON_IN_NARRATIVE,
+                "late filing reason",
+                IssueKind::Warning,
+                format!(
+                    "schema {} has no late filing reason code, so {} is given in the narrative instead",
+                    version,
+                    reason.code()
+                ),
+            );
+        }
+    }
+}
+
+/// Years in which the filer wasn't a US person throughout are flagged, as the report still covers the whole year
+fn validate_residency(year: i32, user_data: &UserData, issues: &mut Vec<FieldIssue>) {
+    let (Some(start), Some(end)) = (
+        NaiveDate::from_ymd_opt(year, 1, 1),
+        NaiveDate::from_ymd_opt(year, 12, 31),
+    ) else {
+        return;
+    };
+    let message = if user_data.nonresident_throughout(start, end) {
+        format!(
+            "not a US person at any time in {}; an FBAR is only due for years the filer was one",
+            year
+        )
+    } else {
+        let periods: Vec<String> = user_data
+            .nonresident_between(start, end)
+            .into_iter()
+            .map(|(from, to, _)| format!("{} to {}", from, to))
+            .collect();
+        if periods.is_empty() {
+            return;
+        }
+        format!(
+            "not a US person from {}; the maxima still cover that time, see the account notes",
+            periods.join(" and ")
+        )
+    };
+    issues.push(FieldIssue {
+        code: codes::DUAL_STATUS_YEAR,
+        account: "filing".to_string(),
+        field: "US person status",
+        kind: IssueKind::Warning,
+        message,
+    });
+}
+
+/// Opening dates have to be plausible: not after the reporting year, not after the closing date, and not before the
+/// provider was founded. Records from the year dated outside the account's open interval are left out of its
+/// maximum, which is usually a mistaken date rather than intended.
+fn validate_dates(
+    year: i32,
+    account: &Account,
+    provider: Option<&Provider>,
+    issues: &mut Vec<FieldIssue>,
+) {
+    let mut issue = |code, field, message| {
+        issues.push(FieldIssue {
+            code,
+            account: account.handle.clone(),
+            field,
+            kind: IssueKind::Warning,
+            message,
+        })
+    };
+
+    if let Some(opened) = account.opening_date {
+        if opened.year() > year {
+            issue(
+                codes::OPENED_AFTER_YEAR,
+                "opening date",
+                format!(
+                    "opened {}, after the end of {}; the account shouldn't be in this year's report",
+                    opened, year
+                ),
+            );
+        }
+        if let Some(closed) = account.closing_date.filter(|closed| *closed < opened) {
+            issue(
+                codes::CLOSED_BEFORE_OPENED,
+                "closing date",
+                format!("closed {}, before it was opened on {}", closed, opened),
+            );
+        }
+        if let Some(founded) = provider
+            .and_then(|provider| provider.founded)
+            .filter(|founded| opened < *founded)
+        {
+            issue(
+                codes::OPENED_BEFORE_FOUNDED,
+                "opening date",
+                format!(
+                    "opened {}, before its provider was founded on {}",
+                    opened, founded
+                ),
+            );
+        }
+    }
+
+    let outside: Vec<NaiveDate> = account
+        .record_dates()
+        .filter(|date| date.year() == year)
+        .filter(|date| {
+            account.opening_date.is_some_and(|opened| *date < opened)
+                || account.closing_date.is_some_and(|closed| *date > closed)
+        })
+        .collect();
+    if let Some(first) = outside.iter().min() {
+        let interval: Vec<String> = [
+            account
+                .opening_date
+                .map(|opened| format!("opened {}", opened)),
+            account
+                .closing_date
+                .map(|closed| format!("closed {}", closed)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        issue(
+            codes::RECORDS_OUTSIDE_OPEN_PERIOD,
+            "statement dates",
+            format!(
+                "{} record(s) from {} fall outside the account's open interval ({}), the first on {}; they're left \
+                 out of its maximum",
+                outside.len(),
+                year,
+                interval.join(", "),
+                first
+            ),
+        );
+    }
+}
+
+/// The United States and its territories, which FBAR doesn't treat as foreign
+/// Whether the name ends the way US national banks' legal names do, like `Citibank, N.A.`
+fn is_us_national_bank(name: &str) -> bool {
+    let name = name.trim_end().to_lowercase();
+    name.ends_with("n.a.") || name.ends_with(" na") || name.ends_with("national association")
+}
+
+const US_COUNTRY_CODES: [CountryCode; 7] = [
+    CountryCode::USA,
+    CountryCode::PRI,
+    CountryCode::GUM,
+    CountryCode::VIR,
+    CountryCode::ASM,
+    CountryCode::MNP,
+    CountryCode::UMI,
+];
+
+fn address_fields(provider: &Provider) -> Vec<(&'static str, &str, usize)> {
+    match &provider.address {
+        Address::Freeform(address) => vec![("institution address", address, MAX_ADDRESS_LENGTH)],
+        Address::Structured(address) => {
+            let mut fields = vec![
+                (
+                    "institution address",
+                    address.street.as_str(),
+                    MAX_ADDRESS_LENGTH,
+                ),
+                ("institution city", address.city.as_str(), MAX_CITY_LENGTH),
+            ];
+            if let Some(postal_code) = &address.postal_code {
+                fields.push((
+                    "institution postal code",
+                    postal_code,
+                    MAX_POSTAL_CODE_LENGTH,
+                ));
+            }
+            fields
+        }
+    }
+}
+
+fn check_length(value: &str, limit: usize) -> Option<String> {
+    let length = value.chars().count();
+    (length > limit).then(|| format!("{} characters, limit is {}", length, limit))
+}
+
+fn check_value(account_report: &AccountReport) -> Option<String> {
+    // FBAR values are rounded up to the next whole dollar
+    let whole_dollars = match account_report.filed_value() {
+        Ok(whole_dollars) => whole_dollars,
+        Err(err) => return Some(err.to_string()),
+    };
+    let digits = whole_dollars.abs().trunc().to_string().len();
+
+    if whole_dollars < Decimal::ZERO {
+        Some(format!("negative value {}", whole_dollars))
+    } else if digits > MAX_ACCOUNT_VALUE_DIGITS {
+        Some(format!(
+            "{} digits, limit is {}",
+            digits, MAX_ACCOUNT_VALUE_DIGITS
+        ))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Account, SignatureAuthority, StructuredAddress};
+    use crate::money::Money;
+    use crate::report::{AccountsSummary, EntityOwner, JointOwner};
+    use crate::report_context::RateSource;
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    fn create_test_user_data(provider_name: &str, identifier1: Option<&str>) -> UserData {
+        UserData {
+            providers: vec![Provider {
+                name: provider_name.to_string(),
+                handle: "example_bank".to_string(),
+                address: Address::from("1 Bahnhofstrasse, Zurich"),
+                country: Some("CH".to_string()),
+                ..Default::default()
+            }],
+            accounts: vec![Account {
+                handle: "savings".to_string(),
+                provider: "example_bank".to_string(),
+                currency_code: "chf".to_string(),
+                identifier1: identifier1.map(str::to_string),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn create_test_report(max_balance_usd: Decimal) -> Report {
+        Report {
+            year: 2023,
+            metadata: None,
+            summary: None,
+            accounts: vec![AccountReport {
+                handle: "savings".to_string(),
+                provider: "example_bank".to_string(),
+                currency_code: "chf".to_string(),
+                max_balance: Money::new(max_balance_usd, "chf"),
+                max_balance_usd: Money::usd(max_balance_usd),
+                max_balance_date: None,
+                rate_source: RateSource::IrsProvided,
+                rate_pinned: false,
+                exchange_rate: Some(1.0),
+                usd_price: None,
+                other_maximum: None,
+                open_period: None,
+                notes: vec![],
+                entity: None,
+                signature_authority: None,
+                joint_owner: None,
+                interpolation: None,
+            }],
+            overridden_rates: Vec::new(),
+            provenance: vec![],
+            excluded: vec![],
+            entities: vec![],
+            joint_owners: vec![],
+            spousal_filing: None,
+        }
+    }
+
+    #[test]
+    fn test_valid_report() {
+        let user_data = create_test_user_data("Example Bank", Some("CH93 0076 2011 6238 5295 7"));
+        let issues = validate_report(&create_test_report(dec!(1000.50)), &user_data);
+
+        assert!(issues.is_empty());
+    }
+
+    #[test]
+    fn test_truncated_fields() {
+        let long_name = "Bank ".repeat(40);
+        let long_number = "1".repeat(41);
+        let user_data = create_test_user_data(&long_name, Some(&long_number));
+
+        let issues = validate_report(&create_test_report(dec!(1000)), &user_data);
+
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].field, "institution name");
+        assert_eq!(issues[0].kind, IssueKind::Truncated);
+        assert_eq!(issues[1].field, "account number");
+        assert_eq!(
+            issues[1].to_string(),
+            "FB0016 savings account number (will be truncated): 41 characters, limit is 40"
+        );
+    }
+
+    #[test]
+    fn test_provider_country() {
+        let mut user_data = create_test_user_data("Example Bank", Some("12345678"));
+        let report = create_test_report(dec!(1000));
+
+        user_data.providers[0].country = None;
+        let issues = validate_report(&report, &user_data);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].field, "institution country");
+        assert_eq!(issues[0].kind, IssueKind::Rejected);
+
+        user_data.providers[0].country = Some("Switzerland".to_string());
+        let issues = validate_report(&report, &user_data);
+        assert!(issues[0].message.contains("not an ISO 3166-1"));
+
+        user_data.providers[0].address = Address::Structured(StructuredAddress {
+            street: "1 Main St".to_string(),
+            city: "Springfield".to_string(),
+            state: Some("IL".to_string()),
+            postal_code: Some("627010000".to_string()),
+            country: "USA".to_string(),
+        });
+        user_data.providers[0].country = None;
+        let issues = validate_report(&report, &user_data);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, IssueKind::Warning);
+        assert!(issues[0].message.contains("domiciled in US"));
+    }
+
+    #[test]
+    fn test_foreign_branch() {
+        let mut user_data = create_test_user_data("Citibank, N.A.", Some("12345678"));
+        let report = create_test_report(dec!(1000));
+
+        // A US national bank's name on a provider outside the US
+        let issues = validate_report(&report, &user_data);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].code, codes::UNMARKED_FOREIGN_BRANCH);
+
+        user_data.providers[0].name = "London".to_string();
+        user_data.providers[0].foreign_branch_of = Some("Citibank, N.A.".to_string());
+        assert_eq!(
+            user_data.providers[0].institution_name(),
+            "Citibank, N.A. (London)"
+        );
+        assert!(validate_report(&report, &user_data).is_empty());
+
+        // The head office's address given for the branch
+        user_data.providers[0].address = Address::Structured(StructuredAddress {
+            street: "388 Greenwich St".to_string(),
+            city: "New York".to_string(),
+            state: Some("NY".to_string()),
+            postal_code: Some("10013".to_string()),
+            country: "US".to_string(),
+        });
+        let issues = validate_report(&report, &user_data);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].code, codes::AMBIGUOUS_BRANCH_COUNTRY);
+        assert!(issues[0]
+            .message
+            .contains("`country` is CH but the address is in US"));
+
+        user_data.providers[0].country = None;
+        let issues = validate_report(&report, &user_data);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].code, codes::AMBIGUOUS_BRANCH_COUNTRY);
+        assert!(issues[0]
+            .message
+            .contains("foreign branch of Citibank, N.A."));
+    }
+
+    #[test]
+    fn test_rejected_fields() {
+        let user_data = create_test_user_data("Example Bank", None);
+
+        let issues = validate_report(&create_test_report(dec!(1_000_000_000_000_000)), &user_data);
+
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].field, "account number");
+        assert_eq!(issues[0].kind, IssueKind::Rejected);
+        assert_eq!(issues[1].field, "maximum account value");
+        assert!(issues[1].message.contains("16 digits"));
+    }
+
+    #[test]
+    fn test_unsupported_characters() {
+        let mut user_data = create_test_user_data("Zürcher Kantonalbank", Some("12345678"));
+        let report = create_test_report(dec!(1000));
+        // Transliterated to Zuercher Kantonalbank
+        assert!(validate_report(&report, &user_data).is_empty());
+
+        user_data.providers[0].name = "みずほ銀行".to_string();
+        let issues = validate_report(&report, &user_data);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].code, codes::UNSUPPORTED_CHARACTERS);
+        assert_eq!(issues[0].field, "RawPartyFullName");
+        assert_eq!(issues[0].kind, IssueKind::Rejected);
+        assert!(issues[0].message.contains("\"mizuho銀行\""));
+
+        user_data
+            .transliteration
+            .replacements
+            .insert("みずほ銀行".to_string(), "Mizuho Bank".to_string());
+        assert!(validate_report(&report, &user_data).is_empty());
+    }
+
+    #[test]
+    fn test_entity_owner() {
+        let user_data = create_test_user_data("Example Bank", Some("12345678"));
+        let mut report = create_test_report(dec!(1000));
+        report.accounts[0].entity = Some("holdings".to_string());
+
+        let issues = validate_report(&report, &user_data);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].field, "owner name");
+        assert_eq!(issues[0].kind, IssueKind::Rejected);
+
+        report.entities = vec![EntityOwner {
+            handle: "holdings".to_string(),
+            name: "Example Holdings AG".to_string(),
+            address: Address::from("1 Example Street, Zug"),
+            tin: None,
+            ownership_percent: dec!(100),
+        }];
+        let issues = validate_report(&report, &user_data);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].field, "owner TIN");
+        assert_eq!(issues[0].kind, IssueKind::Warning);
+
+        report.entities[0].tin = Some("CHE-123.456.789".to_string());
+        assert!(validate_report(&report, &user_data).is_empty());
+    }
+
+    #[test]
+    fn test_signature_authority_owner() {
+        let user_data = create_test_user_data("Example Bank", Some("12345678"));
+        let mut report = create_test_report(dec!(1000));
+        report.accounts[0].signature_authority = Some(SignatureAuthority {
+            owner: "Example Employer AG".to_string(),
+            address: Address::from("1 Example Street, Zug"),
+            tin: None,
+            title: "Treasurer".to_string(),
+        });
+        assert!(validate_report(&report, &user_data).is_empty());
+
+        report.accounts[0]
+            .signature_authority
+            .as_mut()
+            .unwrap()
+            .owner = "A".repeat(151);
+        let issues = validate_report(&report, &user_data);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].field, "owner name");
+        assert_eq!(issues[0].kind, IssueKind::Truncated);
+    }
+
+    #[test]
+    fn test_summary_report() {
+        let user_data = create_test_user_data("Example Bank", None);
+        let mut report = create_test_report(dec!(1000));
+        assert_eq!(validate_report(&report, &user_data).len(), 1);
+
+        report.summary = Some(AccountsSummary {
+            accounts: 25,
+            separately_owned: 25,
+            held_through_entities: 0,
+        });
+        assert!(validate_report(&report, &user_data).is_empty());
+    }
+
+    #[test]
+    fn test_joint_owner() {
+        let user_data = create_test_user_data("Example Bank", Some("12345678"));
+        let mut report = create_test_report(dec!(1000));
+        report.accounts[0].joint_owner = Some("sam".to_string());
+        report.joint_owners = vec![JointOwner {
+            handle: "sam".to_string(),
+            name: "Sam Example".to_string(),
+            tin: None,
+            address: None,
+        }];
+
+        let issues = validate_report(&report, &user_data);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].field, "joint owner TIN");
+        assert_eq!(issues[0].kind, IssueKind::Warning);
+
+        report.joint_owners[0].tin = Some("123-45-6789".to_string());
+        assert!(validate_report(&report, &user_data).is_empty());
+    }
+
+    #[test]
+    fn test_late_filing() {
+        let mut user_data = create_test_user_data("Example Bank", Some("12345678"));
+        let report = create_test_report(dec!(1000));
+        user_data.filings = vec![Filing {
+            year: 2023,
+            filed_on: NaiveDate::from_ymd_opt(2024, 11, 1),
+            late_reason: None,
+            explanation: None,
+        }];
+
+        let issues = validate_report(&report, &user_data);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].field, "late filing reason");
+        assert_eq!(issues[0].kind, IssueKind::Rejected);
+
+        user_data.filings[0].late_reason = Some(LateFilingReason::Other);
+        let issues = validate_report(&report, &user_data);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].field, "late filing explanation");
+        assert_eq!(issues[0].kind, IssueKind::Rejected);
+
+        user_data.filings[0].explanation = Some("Moved abroad".to_string());
+        assert!(validate_report(&report, &user_data).is_empty());
+
+        // The prior schema takes the reason in the narrative, which has to fit with the explanation
+        user_data.fincen_schema_version = Some(SchemaVersion::V1_1);
+        user_data.filings[0].explanation = Some("x".repeat(740));
+        let issues = validate_report(&report, &user_data);
+        let kinds: Vec<_> = issues
+            .iter()
+            .map(|issue| (issue.field, issue.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("late filing explanation", IssueKind::Truncated),
+                ("late filing reason", IssueKind::Warning),
+            ]
+        );
+        user_data.fincen_schema_version = None;
+        user_data.filings[0].explanation = Some("Moved abroad".to_string());
+
+        // A reason given for a filing made on time is probably a mistake
+        user_data.filings[0].filed_on = NaiveDate::from_ymd_opt(2024, 4, 15);
+        let issues = validate_report(&report, &user_data);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, IssueKind::Warning);
+    }
+
+    #[test]
+    fn test_account_dates() {
+        let date = |value: &str| value.parse::<NaiveDate>().unwrap();
+        let mut user_data = create_test_user_data("Example Bank", Some("12345678"));
+        let report = create_test_report(dec!(1000));
+        user_data.providers[0].founded = Some(date("1990-01-01"));
+        user_data.accounts[0].opening_date = Some(date("2023-03-01"));
+        user_data.accounts[0].balances = vec![crate::data::BalanceRecord {
+            date: date("2023-06-30"),
+            amount: dec!(1000),
+            ..Default::default()
+        }];
+        assert!(validate_report(&report, &user_data).is_empty());
+
+        user_data.accounts[0].opening_date = Some(date("1985-06-01"));
+        let issues = validate_report(&report, &user_data);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].field, "opening date");
+        assert!(issues[0]
+            .message
+            .contains("before its provider was founded"));
+
+        user_data.accounts[0].opening_date = Some(date("2024-02-01"));
+        let issues = validate_report(&report, &user_data);
+        assert_eq!(issues.len(), 2);
+        assert!(issues[0].message.contains("after the end of 2023"));
+        assert_eq!(issues[1].field, "statement dates");
+        assert_eq!(
+            issues[1].message,
+            "1 record(s) from 2023 fall outside the account's open interval (opened 2024-02-01), the first on \
+             2023-06-30; they're left out of its maximum"
+        );
+
+        user_data.accounts[0].opening_date = Some(date("2023-03-01"));
+        user_data.accounts[0].closing_date = Some(date("2023-01-31"));
+        let issues = validate_report(&report, &user_data);
+        assert_eq!(issues[0].field, "closing date");
+        assert_eq!(issues[0].kind, IssueKind::Warning);
+        assert_eq!(issues[1].field, "statement dates");
+    }
+}
@@ -0,0 +1,299 @@
+use anyhow::Result;
+
+use crate::data::{AccountKind, Provider, UserData};
+use crate::money::format::format_conversion;
+use crate::money::Money;
+use crate::report::Report;
+
+/// Lines per page of the worksheet
+const LINES_PER_PAGE: usize = 60;
+const FONT_SIZE: usize = 9;
+const LINE_HEIGHT: usize = 12;
+/// US Letter, in points
+const PAGE_WIDTH: usize = 612;
+const PAGE_HEIGHT: usize = 792;
+const MARGIN: usize = 40;
+
+/// A plain-text worksheet of what to enter on the FBAR for each account, for checking the filing by hand or keying
+/// it into the BSA E-Filing form
+pub fn worksheet_lines(report: &Report, user_data: &UserData) -> Result<Vec<String>> {
+    let mut lines = vec![format!("FBAR worksheet for {}", report.year), String::new()];
+    if let Some(filer) = user_data.primary_filer() {
+        lines.push(format!("Filer: {}", filer.name));
+    }
+    if let Some(metadata) = &report.metadata {
+        lines.push(format!("Report ID: {}", metadata.report_id));
+    }
+    if let Some(filing) = user_data.filing_for(report.year) {
+        if let Some(filed_on) = filing.filed_on {
+            lines.push(format!("Filed on: {}", filed_on));
+        }
+        if let Some(reason) = filing.late_reason {
+            lines.push(format!(
+                "Filed late: {} ({})",
+                reason.code(),
+                reason.description()
+            ));
+        }
+        if let Some(explanation) = &filing.explanation {
+            lines.push(format!("Explanation: {}", explanation));
+        }
+    }
+    if let Some(summary) = &report.summary {
+        lines.push(format!(
+            "Filing the number of accounts only: {} ({} separately owned, {} held through entities)",
+            summary.accounts, summary.separately_owned, summary.held_through_entities
+        ));
+    }
+
+    for (index, account_report) in report.accounts.iter().enumerate() {
+        let account = user_data
+            .accounts
+            .iter()
+            .find(|account| account.handle == account_report.handle);
+        let provider = user_data
+            .providers
+            .iter()
+            .find(|provider| provider.handle == account_report.provider);
+
+        lines.push(String::new());
+        lines.push(format!(
+            "{}. {} ({})",
+            index + 1,
+            account_report.handle,
+            account_report.part()
+        ));
+        lines.push(format!(
+            "   Institution: {}",
+            provider
+                .map(Provider::institution_name)
+                .unwrap_or_else(|| account_report.provider.clone())
+        ));
+        if let Some(provider) = provider {
+            lines.push(format!("   Address: {}", provider.address));
+        }
+        lines.push(format!(
+            "   Account number: {}",
+            account
+                .and_then(super::account_number)
+                .unwrap_or_else(|| "(none)".to_string())
+        ));
+        match account.map(|account| account.kind) {
+            Some(AccountKind::Insurance) => {
+                lines.push("   Account type: Other (life insurance or annuity)".to_string())
+            }
+            Some(AccountKind::Securities) => lines.push("   Account type: Securities".to_string()),
+            _ => {}
+        }
+        lines.push(format!(
+            "   Maximum value: {}, filed as {}",
+            format_conversion(
+                &account_report.max_balance,
+                user_data.minor_units(&account_report.currency_code),
+                &account_report.max_balance_usd,
+                account_report.exchange_rate
+            ),
+            Money::usd(account_report.filed_value()?)
+                .format()
+                .trim_end_matches(".00")
+        ));
+        if let Some(date) = account_report.max_balance_date {
+            lines.push(format!("   Maximum reached on: {}", date));
+        }
+        if let Some(owner) = &account_report.joint_owner {
+            lines.push(format!("   Joint owner: {}", owner));
+        }
+        if let Some(entity) = &account_report.entity {
+            lines.push(format!("   Held through: {}", entity));
+        }
+        if let Some(authority) = &account_report.signature_authority {
+            lines.push(format!(
+                "   Owner: {}, {}; filer's title: {}",
+                authority.owner, authority.address, authority.title
+            ));
+        }
+        for note in &account_report.notes {
+            lines.push(format!("   - {}", note));
+        }
+    }
+
+    if !report.overridden_rates.is_empty() {
+        lines.push(String::new());
+        lines.push("Rates used instead of the IRS rate:".to_string());
+        for rate in &report.overridden_rates {
+            lines.push(format!(
+                "   {} {} per USD ({}) instead of {}, changing the maxima of {} by {}",
+                rate.rate,
+                rate.currency_code.to_uppercase(),
+                rate.source,
+                rate.irs_rate,
+                rate.accounts.join(", "),
+                rate.usd_delta.format()
+            ));
+        }
+    }
+
+    if !report.excluded.is_empty() {
+        lines.push(String::new());
+        lines.push("Not reported:".to_string());
+        for excluded in &report.excluded {
+            lines.push(format!("   {}: {}", excluded.handle, excluded.reason));
+        }
+    }
+
+    Ok(lines)
+}
+
+/// The worksheet as a PDF, in a monospaced font
+pub fn worksheet_pdf(report: &Report, user_data: &UserData) -> Result<Vec<u8>> {
+    Ok(text_pdf(&worksheet_lines(report, user_data)?))
+}
+
+/// Lays lines of text out on as many pages as they need. Long lines are wrapped. The font is WinAnsi-encoded, so
+/// currency symbols like £, € and ¥ and accented Latin letters print; anything else is replaced with `?`.
+fn text_pdf(lines: &[String]) -> Vec<u8> {
+    // Courier's glyphs are 0.6em wide
+    let columns = (PAGE_WIDTH - 2 * MARGIN) * 10 / (FONT_SIZE * 6);
+    let wrapped: Vec<String> = lines
+        .iter()
+        .flat_map(|line| {
+            let characters: Vec<char> = line
+                .chars()
+                .map(|character| {
+                    if win_ansi(character).is_some() {
+                        character
+                    } else {
+                        '?'
+                    }
+                })
+                .collect();
+            if characters.is_empty() {
+                return vec![String::new()];
+            }
+            characters
+                .chunks(columns)
+                .map(|chunk| chunk.iter().collect())
+                .collect()
+        })
+        .collect();
+    let pages: Vec<&[String]> = if wrapped.is_empty() {
+        vec![&[]]
+    } else {
+        wrapped.chunks(LINES_PER_PAGE).collect()
+    };
+
+    // Objects: 1 catalog, 2 page tree, 3 font, then a page and its content stream per page
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..pages.len())
+                .map(|index| format!("{} 0 R", 4 + 2 * index))
+                .collect::<Vec<_>>()
+                .join(" "),
+            pages.len()
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+    ];
+    for (index, page) in pages.iter().enumerate() {
+        let mut content = format!(
+            "BT /F1 {} Tf {} TL {} {} Td\n",
+            FONT_SIZE,
+            LINE_HEIGHT,
+            MARGIN,
+            PAGE_HEIGHT - MARGIN
+        );
+        for line in page.iter() {
+            content.push_str(&format!("({}) '\n", escape(line)));
+        }
+        content.push_str("ET");
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R >> >> \
+             /Contents {} 0 R >>",
+            PAGE_WIDTH,
+            PAGE_HEIGHT,
+            5 + 2 * index
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}\nendstream",
+            content.len(),
+            content
+        ));
+    }
+
+    let mut pdf = "%PDF-1.4\n".to_string();
+    let mut offsets = Vec::new();
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.push_str(&format!("{} 0 obj\n{}\nendobj\n", index + 1, object));
+    }
+    let xref = pdf.len();
+    pdf.push_str(&format!(
+        "xref\n0 {}\n0000000000 65535 f \n",
+        objects.len() + 1
+    ));
+    for offset in offsets {
+        pdf.push_str(&format!("{:010} 00000 n \n", offset));
+    }
+    pdf.push_str(&format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    ));
+    pdf.into_bytes()
+}
+
+/// Writes a line as a PDF string: ASCII as is, other WinAnsi characters as octal escapes
+fn escape(line: &str) -> String {
+    let mut escaped = String::new();
+    for character in line.chars() {
+        match (character, win_ansi(character)) {
+            ('\\' | '(' | ')', _) => {
+                escaped.push('\\');
+                escaped.push(character);
+            }
+            (_, Some(byte)) if byte.is_ascii() => escaped.push(character),
+            (_, Some(byte)) => escaped.push_str(&format!("\\{:03o}", byte)),
+            (_, None) => escaped.push('?'),
+        }
+    }
+    escaped
+}
+
+/// The character's code in WinAnsiEncoding, for the characters the worksheet can contain: ASCII, Latin-1 letters and
+/// symbols (which keep their codes), and the euro sign
+fn win_ansi(character: char) -> Option<u8> {
+    match character {
+        '€' => Some(0x80),
+        ' '..='~' | '\u{a0}'..='\u{ff}' => u8::try_from(character).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_pdf() {
+        let mut lines: Vec<String> = (0..70).map(|index| format!("line {}", index)).collect();
+        lines.push("Café (joint)".to_string());
+        lines.push("£5 €6 ₹7".to_string());
+        let pdf = String::from_utf8(text_pdf(&lines)).unwrap();
+
+        assert!(pdf.starts_with("%PDF-1.4\n"));
+        assert!(pdf.contains("/Count 2"));
+        assert!(pdf.contains("(Caf\\351 \\(joint\\)) '"));
+        assert!(pdf.contains("(\\2435 \\2006 ?7) '"));
+        assert!(pdf.ends_with("%%EOF\n"));
+
+        // Every cross-reference entry points at its object
+        let xref = pdf.rfind("xref\n").unwrap();
+        let entries = pdf[xref..].lines().skip(3).take(7);
+        for (index, entry) in entries.enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(&format!("{} 0 obj", index + 1)));
+        }
+    }
+}
@@ -0,0 +1,585 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use super::transliterate::{self, Transliterated, Transliteration};
+use crate::data::{AccountKind, Address, UserData};
+use crate::report::Report;
+
+/// Namespace of the BSA E-Filing batch schema
+const NAMESPACE: &str = "www.fincen.gov/base";
+/// Form type of an FBAR (FinCEN Form 114) batch
+const FORM_TYPE: &str = "FBARX";
+
+/// Activity party type codes from the BSA E-Filing FBAR XML user guide
+const FOREIGN_ACCOUNT_FILER: &str = "15";
+const FINANCIAL_INSTITUTION: &str = "41";
+const PRINCIPAL_JOINT_OWNER: &str = "42";
+const SIGNATURE_AUTHORITY_OWNER: &str = "43";
+const CONSOLIDATED_OWNER: &str = "44";
+/// Party account association codes, one per part of the form an account is reported in
+const SEPARATELY_OWNED: &str = "5";
+const JOINTLY_OWNED: &str = "6";
+const NO_FINANCIAL_INTEREST: &str = "7";
+const CONSOLIDATED: &str = "8";
+/// Account type codes
+const BANK_ACCOUNT: &str = "141";
+const SECURITIES_ACCOUNT: &str = "142";
+const OTHER_ACCOUNT: &str = "143";
+/// How insurance and annuity accounts, reported as other accounts, are described
+const INSURANCE_ACCOUNT_TYPE: &str = "Life insurance or annuity";
+/// Party identification type code for an SSN or ITIN
+const SSN_ITIN: &str = "1";
+
+/// Renders the report as a BSA E-Filing FBAR batch with a single filing, signed on the given date unless the year's
+/// `filings` entry gives the date it was filed.
+///
+/// Account values are rounded up to whole dollars, as FinCEN asks. In summary mode only the number of accounts is
+/// given. The batch follows the schema version `data.yml` targets, by default the current one. Names and addresses
+/// are transliterated as `data.yml` configures. The batch still needs to be checked, signed and uploaded through
+/// BSA E-Filing.
+pub fn fbar_xml(report: &Report, user_data: &UserData, signed_on: NaiveDate) -> Result<String> {
+    Ok(render(report, user_data, signed_on)?.finish())
+}
+
+/// The values the XML export changes from how they're written in `data.yml`, in document order
+pub fn transliterations(report: &Report, user_data: &UserData) -> Result<Vec<Transliterated>> {
+    // The signing date is never transliterated, so any will do
+    Ok(render(report, user_data, NaiveDate::default())?.transliterated)
+}
+
+fn render(report: &Report, user_data: &UserData, signed_on: NaiveDate) -> Result<XmlWriter> {
+    let mut xml = XmlWriter::new(user_data.transliteration.clone());
+    xml.raw("<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+    xml.open_with(
+        "EFilingBatchXML",
+        &[
+            ("xmlns:fc2", NAMESPACE.to_string()),
+            ("ActivityCount", "1".to_string()),
+        ],
+    );
+    xml.element("FormTypeCode", FORM_TYPE);
+    let version = user_data.fincen_schema_version.unwrap_or_default();
+    let filing = user_data.filing_for(report.year);
+    let signed_on = filing
+        .and_then(|filing| filing.filed_on)
+        .unwrap_or(signed_on);
+    xml.open_sequenced("Activity");
+    xml.element(
+        "ApprovalOfficialSignatureDateText",
+        &signed_on.format("%Y%m%d").to_string(),
+    );
+
+    xml.open_sequenced("Party");
+    xml.element("ActivityPartyTypeCode", FOREIGN_ACCOUNT_FILER);
+    xml.element(
+        "FilerFinancialInterest25ForeignAccountIndicator",
+        if report.summary.is_some() { "Y" } else { "N" },
+    );
+    if let Some(summary) = &report.summary {
+        xml.element(
+            "FinancialInterest25ForeignAccountCount",
+            &summary.accounts.to_string(),
+        );
+    }
+    xml.element("FilerTypeIndividualIndicator", "Y");
+    if let Some(filer) = user_data.primary_filer() {
+        individual_name(&mut xml, &filer.name);
+        if let Some(address) = &filer.address {
+            address_element(&mut xml, address, None);
+        }
+        if let Some(tin) = &filer.tin {
+            identification(&mut xml, tin);
+        }
+    }
+    xml.close("Party");
+
+    // In summary mode only the accounts the filer has signature authority over are still itemized
+    let itemized = report.accounts.iter().filter(|account_report| {
+        report.summary.is_none() || account_report.signature_authority.is_some()
+    });
+    for account_report in itemized {
+        let account = user_data
+            .accounts
+            .iter()
+            .find(|account| account.handle == account_report.handle);
+        let provider = user_data
+            .providers
+            .iter()
+            .find(|provider| provider.handle == account_report.provider);
+
+        xml.subject = account_report.handle.clone();
+        xml.open_sequenced("Account");
+        xml.element(
+            "AccountMaximumValueAmountText",
+            &account_report.filed_value()?.to_string(),
+        );
+        if let Some(account_number) = account.and_then(super::account_number) {
+            xml.element("AccountNumberText", &account_number);
+        }
+        match account.map(|account| account.kind) {
+            Some(AccountKind::Insurance) => {
+                xml.element("AccountTypeCode", OTHER_ACCOUNT);
+                xml.element("OtherAccountTypeText", INSURANCE_ACCOUNT_TYPE);
+            }
+            Some(AccountKind::Securities) => xml.element("AccountTypeCode", SECURITIES_ACCOUNT),
+            _ => xml.element("AccountTypeCode", BANK_ACCOUNT),
+        }
+
+        if let Some(provider) = provider {
+            xml.open_sequenced("Party");
+            xml.element("ActivityPartyTypeCode", FINANCIAL_INSTITUTION);
+            entity_name(&mut xml, &provider.institution_name());
+            let country = provider.country_code().ok().flatten();
+            address_element(
+                &mut xml,
+                &provider.address,
+                country.map(|country| country.alpha2()),
+            );
+            xml.close("Party");
+        }
+
+        let association = if let Some(handle) = &account_report.entity {
+            if let Some(owner) = report.entities.iter().find(|owner| &owner.handle == handle) {
+                xml.open_sequenced("Party");
+                xml.element("ActivityPartyTypeCode", CONSOLIDATED_OWNER);
+                entity_name(&mut xml, &owner.name);
+                address_element(&mut xml, &owner.address, None);
+                if let Some(tin) = &owner.tin {
+                    identification(&mut xml, tin);
+                }
+                xml.close("Party");
+            }
+            CONSOLIDATED
+        } else if let Some(authority) = &account_report.signature_authority {
+            xml.open_sequenced("Party");
+            xml.element("ActivityPartyTypeCode", SIGNATURE_AUTHORITY_OWNER);
+            entity_name(&mut xml, &authority.owner);
+            address_element(&mut xml, &authority.address, None);
+            if let Some(tin) = &authority.tin {
+                identification(&mut xml, tin);
+            }
+            xml.open_sequenced("PartyOccupationBusiness");
+            xml.element("OccupationBusinessText", &authority.title);
+            xml.close("PartyOccupationBusiness");
+            xml.close("Party");
+            NO_FINANCIAL_INTEREST
+        } else if let Some(handle) = &account_report.joint_owner {
+            if let Some(owner) = report
+                .joint_owners
+                .iter()
+                .find(|owner| &owner.handle == handle)
+            {
+                xml.open_sequenced("Party");
+                xml.element("ActivityPartyTypeCode", PRINCIPAL_JOINT_OWNER);
+                individual_name(&mut xml, &owner.name);
+                if let Some(address) = &owner.address {
+                    address_element(&mut xml, address, None);
+                }
+                if let Some(tin) = &owner.tin {
+                    identification(&mut xml, tin);
+                }
+                xml.close("Party");
+            }
+            JOINTLY_OWNED
+        } else {
+            SEPARATELY_OWNED
+        };
+        xml.open_sequenced("PartyAccountAssociation");
+        xml.element("PartyAccountAssociationTypeCode", association);
+        xml.close("PartyAccountAssociation");
+        xml.close("Account");
+    }
+
+    xml.open_sequenced("ForeignAccountActivity");
+    xml.element("ReportCalendarYearText", &report.year.to_string());
+    if let Some(reason) = filing
+        .and_then(|filing| filing.late_reason)
+        .filter(|_| version.has_late_filing_reason_code())
+    {
+        xml.element("LateFilingReasonCode", reason.code());
+    }
+    xml.close("ForeignAccountActivity");
+    if let Some(narrative) = filing.and_then(|filing| version.narrative(filing)) {
+        xml.open_sequenced("ActivityNarrativeInformation");
+        xml.element("ActivityNarrativeSequenceNumber", "1");
+        xml.element("ActivityNarrativeText", &narrative);
+        xml.close("ActivityNarrativeInformation");
+    }
+    xml.close("Activity");
+    xml.close("EFilingBatchXML");
+
+    Ok(xml)
+}
+
+/// FBAR values are rounded up to the next whole dollar
+pub fn whole_dollars(amount: Decimal) -> Decimal {
+    amount.ceil()
+}
+
+/// Splits a name on its last space into given names and a family name
+fn individual_name(xml: &mut XmlWriter, name: &str) {
+    let (first, last) = name.trim().rsplit_once(' ').unwrap_or(("", name.trim()));
+    xml.open_sequenced("PartyName");
+    xml.element("PartyNameTypeCode", "L");
+    xml.element("RawEntityIndividualLastName", last);
+    if !first.is_empty() {
+        xml.element("RawIndividualFirstName", first.trim());
+    }
+    xml.close("PartyName");
+}
+
+fn entity_name(xml: &mut XmlWriter, name: &str) {
+    xml.open_sequenced("PartyName");
+    xml.element("PartyNameTypeCode", "L");
+    xml.element("RawPartyFullName", name);
+    xml.close("PartyName");
+}
+
+fn address_element(xml: &mut XmlWriter, address: &Address, country: Option<&str>) {
+    xml.open_sequenced("Address");
+    match address {
+        Address::Freeform(address) => {
+            if let Some(country) = country {
+                xml.element("RawCountryCodeText", country);
+            }
+            xml.element("RawStreetAddress1Text", address);
+        }
+        Address::Structured(address) => {
+            xml.element("RawCityText", &address.city);
+            xml.element("RawCountryCodeText", country.unwrap_or(&address.country));
+            if let Some(state) = &address.state {
+                xml.element("RawStateCodeText", state);
+            }
+            xml.element("RawStreetAddress1Text", &address.street);
+            if let Some(postal_code) = &address.postal_code {
+                xml.element("RawZIPCode", postal_code);
+            }
+        }
+    }
+    xml.close("Address");
+}
+
+fn identification(xml: &mut XmlWriter, tin: &str) {
+    xml.open_sequenced("PartyIdentification");
+    xml.element(
+        "PartyIdentificationNumberText",
+        &tin.chars().filter(char::is_ascii_digit).collect::<String>(),
+    );
+    xml.element("PartyIdentificationTypeCode", SSN_ITIN);
+    xml.close("PartyIdentification");
+}
+
+/// Writes indented `fc2:` elements, numbering the elements that need a `SeqNum` in document order and
+/// transliterating their text
+struct XmlWriter {
+    output: String,
+    depth: usize,
+    sequence: usize,
+    transliteration: Transliteration,
+    /// What the elements being written belong to, for the record of transliterated values
+    subject: String,
+    transliterated: Vec<Transliterated>,
+}
+
+impl XmlWriter {
+    fn new(transliteration: Transliteration) -> Self {
+        Self {
+            output: String::new(),
+            depth: 0,
+            sequence: 0,
+            transliteration,
+            subject: "filer".to_string(),
+            transliterated: Vec::new(),
+        }
+    }
+
+    fn raw(&mut self, line: &str) {
+        self.output.push_str(&"  ".repeat(self.depth));
+        self.output.push_str(line);
+        self.output.push('\n');
+    }
+
+    fn open_with(&mut self, name: &str, attributes: &[(&str, String)]) {
+        let attributes: String = attributes
+            .iter()
+            .map(|(key, value)| format!(" {}=\"{}\"", key, escape(value)))
+            .collect();
+        self.raw(&format!("<fc2:{}{}>", name, attributes));
+        self.depth += 1;
+    }
+
+    fn open_sequenced(&mut self, name: &str) {
+        self.sequence += 1;
+        let sequence = self.sequence.to_string();
+        self.open_with(name, &[("SeqNum", sequence)]);
+    }
+
+    fn close(&mut self, name: &str) {
+        self.depth -= 1;
+        self.raw(&format!("</fc2:{}>", name));
+    }
+
+    fn element(&mut self, name: &'static str, text: &str) {
+        let exported = self.transliteration.apply(text);
+        self.raw(&format!("<fc2:{0}>{1}</fc2:{0}>", name, escape(&exported)));
+        if exported != text || !transliterate::is_accepted(&exported) {
+            self.transliterated.push(Transliterated {
+                subject: self.subject.clone(),
+                field: name,
+                original: text.to_string(),
+                exported,
+            });
+        }
+    }
+
+    fn finish(self) -> String {
+        self.output
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{
+        Account, BalanceRecord, Filer, Filing, LateFilingReason, Provider, StructuredAddress,
+    };
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use crate::fincen::SchemaVersion;
+    use crate::report_context::ReportContext;
+    use rust_decimal_macros::dec;
+
+    fn create_test_user_data() -> UserData {
+        UserData {
+            filers: vec![Filer {
+                name: "Alex Q Example".to_string(),
+                handle: "alex".to_string(),
+                tin: Some("123-45-6789".to_string()),
+                address: None,
+            }],
+            providers: vec![Provider {
+                name: "Example & Sons Bank".to_string(),
+                handle: "example_bank".to_string(),
+                address: Address::Structured(StructuredAddress {
+                    street: "1 Bank Street".to_string(),
+                    city: "London".to_string(),
+                    state: None,
+                    postal_code: Some("EC2N".to_string()),
+                    country: "GB".to_string(),
+                }),
+                ..Default::default()
+            }],
+            accounts: vec![Account {
+                handle: "example_checking".to_string(),
+                provider: "example_bank".to_string(),
+                currency_code: "GBP".to_string(),
+                identifier1: Some("12345678".to_string()),
+                balances: vec![BalanceRecord {
+                    date: NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(),
+                    amount: dec!(1000.10),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fbar_xml() -> Result<()> {
+        let user_data = create_test_user_data();
+        let context = ReportContext::new(
+            Facts {
+                years: vec![AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![ExchangeRate::new("GBP".to_string(), 0.80).unwrap()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            None,
+        );
+        let report = Report::generate(2023, &user_data, &context)?;
+
+        let xml = fbar_xml(
+            &report,
+            &user_data,
+            NaiveDate::from_ymd_opt(2024, 3, 15).unwrap(),
+        )?;
+        assert!(xml.contains("<fc2:ApprovalOfficialSignatureDateText>20240315<"));
+        assert!(xml.contains("<fc2:RawEntityIndividualLastName>Example<"));
+        assert!(xml.contains("<fc2:RawIndividualFirstName>Alex Q<"));
+        assert!(xml.contains("<fc2:PartyIdentificationNumberText>123456789<"));
+        // 1000.10 GBP at 0.80 per USD is 1250.125 USD, rounded up
+        assert!(xml.contains("<fc2:AccountMaximumValueAmountText>1251<"));
+        assert!(xml.contains("<fc2:RawPartyFullName>Example &amp; Sons Bank<"));
+        assert!(xml.contains("<fc2:AccountTypeCode>141<"));
+        assert!(xml.contains("<fc2:PartyAccountAssociationTypeCode>5<"));
+        assert!(xml.contains("<fc2:ReportCalendarYearText>2023<"));
+
+        let sequences: Vec<&str> = xml.matches("SeqNum=\"").collect();
+        assert_eq!(sequences.len(), 10);
+        assert!(xml.trim_end().ends_with("</fc2:EFilingBatchXML>"));
+        assert!(!xml.contains("LateFilingReasonCode"));
+
+        let mut user_data = user_data;
+        user_data.accounts[0].kind = AccountKind::Securities;
+        let xml = fbar_xml(
+            &report,
+            &user_data,
+            NaiveDate::from_ymd_opt(2024, 3, 15).unwrap(),
+        )?;
+        assert!(xml.contains("<fc2:AccountTypeCode>142<"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rounds_up_unrounded_maximum() -> Result<()> {
+        let mut user_data = create_test_user_data();
+        user_data.accounts[0].balances[0].amount = dec!(1000.0032);
+        let context = ReportContext::new(
+            Facts {
+                years: vec![AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![ExchangeRate::new("GBP".to_string(), 0.80).unwrap()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            None,
+        );
+        let report = Report::generate(2023, &user_data, &context)?;
+        // 1250.004 USD shows as 1250.00, but is still more than 1250
+        assert_eq!(report.accounts[0].max_balance_usd.amount, dec!(1250.00));
+        assert_eq!(report.accounts[0].filed_value()?, dec!(1251));
+
+        let xml = fbar_xml(
+            &report,
+            &user_data,
+            NaiveDate::from_ymd_opt(2024, 3, 15).unwrap(),
+        )?;
+        assert!(xml.contains("<fc2:AccountMaximumValueAmountText>1251<"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_transliterations() -> Result<()> {
+        let mut user_data = create_test_user_data();
+        user_data.providers[0].name = "Münchner Bank".to_string();
+        user_data.providers[0].address = Address::Freeform("三菱ビル 1".to_string());
+        let context = ReportContext::new(
+            Facts {
+                years: vec![AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![ExchangeRate::new("GBP".to_string(), 0.80).unwrap()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            None,
+        );
+        let report = Report::generate(2023, &user_data, &context)?;
+
+        let xml = fbar_xml(
+            &report,
+            &user_data,
+            NaiveDate::from_ymd_opt(2024, 3, 15).unwrap(),
+        )?;
+        assert!(xml.contains("<fc2:RawPartyFullName>Muenchner Bank<"));
+        assert!(xml.contains("<fc2:RawStreetAddress1Text>三菱biru 1<"));
+
+        let values = transliterations(&report, &user_data)?;
+        assert_eq!(
+            values,
+            vec![
+                Transliterated {
+                    subject: "example_checking".to_string(),
+                    field: "RawPartyFullName",
+                    original: "Münchner Bank".to_string(),
+                    exported: "Muenchner Bank".to_string(),
+                },
+                Transliterated {
+                    subject: "example_checking".to_string(),
+                    field: "RawStreetAddress1Text",
+                    original: "三菱ビル 1".to_string(),
+                    exported: "三菱biru 1".to_string(),
+                },
+            ]
+        );
+        assert!(!values[1].is_accepted());
+
+        user_data
+            .transliteration
+            .replacements
+            .insert("三菱".to_string(), "Mitsubishi ".to_string());
+        let values = transliterations(&report, &user_data)?;
+        assert_eq!(values[1].exported, "Mitsubishi biru 1");
+        assert!(values.iter().all(Transliterated::is_accepted));
+
+        user_data.transliteration.enabled = false;
+        let xml = fbar_xml(
+            &report,
+            &user_data,
+            NaiveDate::from_ymd_opt(2024, 3, 15).unwrap(),
+        )?;
+        assert!(xml.contains("<fc2:RawPartyFullName>Münchner Bank<"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_late_filing() -> Result<()> {
+        let mut user_data = create_test_user_data();
+        user_data.filings = vec![Filing {
+            year: 2023,
+            filed_on: NaiveDate::from_ymd_opt(2025, 2, 1),
+            late_reason: Some(LateFilingReason::Other),
+            explanation: Some("Moved abroad & missed the deadline".to_string()),
+        }];
+        let context = ReportContext::new(
+            Facts {
+                years: vec![AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![ExchangeRate::new("GBP".to_string(), 0.80).unwrap()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            None,
+        );
+        let report = Report::generate(2023, &user_data, &context)?;
+
+        let xml = fbar_xml(
+            &report,
+            &user_data,
+            NaiveDate::from_ymd_opt(2025, 3, 15).unwrap(),
+        )?;
+        assert!(xml.contains("<fc2:ApprovalOfficialSignatureDateText>20250201<"));
+        assert!(xml.contains("<fc2:LateFilingReasonCode>F<"));
+        assert!(xml.contains("<fc2:ActivityNarrativeText>Moved abroad &amp; missed the deadline<"));
+
+        // The prior schema has no reason code, so the reason leads the narrative
+        user_data.fincen_schema_version = Some(SchemaVersion::V1_1);
+        let xml = fbar_xml(
+            &report,
+            &user_data,
+            NaiveDate::from_ymd_opt(2025, 3, 15).unwrap(),
+        )?;
+        assert!(!xml.contains("LateFilingReasonCode"));
+        assert!(xml.contains(
+            "<fc2:ActivityNarrativeText>Late filing reason F: Other. Moved abroad &amp; missed the deadline<"
+        ));
+
+        Ok(())
+    }
+}
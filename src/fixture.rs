@@ -0,0 +1,255 @@
+//! Synthetic data directories of any size, for benchmarking the report engine and exercising importers with more
+//! data than the hand-written fixtures under `tests/fixtures` hold.
+
+use anyhow::{bail, Result};
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::Decimal;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::data::identifier::iban_remainder;
+use crate::data::migrate::SCHEMA_VERSION;
+use crate::data::{
+    Account, Address, BalanceRecord, IdentifierKind, Provider, StructuredAddress, UserData,
+};
+use crate::rollover::inbox_dir;
+
+/// Currencies the bundled exchange rate facts cover, so every generated account can be reported
+const CURRENCIES: [&str; 2] = ["EUR", "GBP"];
+/// Countries providers are given in turn
+const COUNTRIES: [&str; 3] = ["GB", "DE", "FR"];
+
+/// The size and shape of a generated data directory
+#[derive(Debug, Clone)]
+pub struct FixtureSpec {
+    pub providers: usize,
+    /// Accounts, shared out between the providers in turn
+    pub accounts: usize,
+    pub first_year: i32,
+    pub years: u32,
+    /// Write the balances as CSV statements under `inbox/<year>` to import, instead of into `data.yml`
+    pub statements: bool,
+    /// Seed for the balances and identifiers; the same spec always generates the same data
+    pub seed: u64,
+}
+
+impl Default for FixtureSpec {
+    fn default() -> Self {
+        Self {
+            providers: 3,
+            accounts: 10,
+            first_year: 2022,
+            years: 3,
+            statements: false,
+            seed: 1,
+        }
+    }
+}
+
+/// Generates the data: providers with structured addresses, accounts with valid IBANs alternating between EUR and
+/// GBP, and a balance for every day of every year following a random walk
+pub fn generate(spec: &FixtureSpec) -> Result<UserData> {
+    if spec.providers == 0 && spec.accounts > 0 {
+        bail!("Accounts need at least one provider");
+    }
+    let Some(start) = NaiveDate::from_ymd_opt(spec.first_year, 1, 1) else {
+        bail!("Invalid first year {}", spec.first_year);
+    };
+    let Some(end) = NaiveDate::from_ymd_opt(spec.first_year + spec.years as i32 - 1, 12, 31) else {
+        bail!("Invalid number of years {}", spec.years);
+    };
+
+    let mut rng = SplitMix64(spec.seed);
+    let providers: Vec<Provider> = (1..=spec.providers)
+        .map(|index| Provider {
+            name: format!("Example Bank {}", index),
+            handle: format!("bank_{}", index),
+            address: Address::Structured(StructuredAddress {
+                street: format!("{} Example Street", index),
+                city: "Example City".to_string(),
+                state: None,
+                postal_code: Some(format!("{:05}", index)),
+                country: COUNTRIES[(index - 1) % COUNTRIES.len()].to_string(),
+            }),
+            ..Default::default()
+        })
+        .collect();
+
+    let accounts = (0..spec.accounts)
+        .map(|index| {
+            let provider = &providers[index % providers.len()];
+            // The provider's country, as it's the one holding the account
+            let country = COUNTRIES[index % providers.len() % COUNTRIES.len()];
+            Account {
+                handle: format!("account_{}", index + 1),
+                provider: provider.handle.clone(),
+                currency_code: CURRENCIES[index % CURRENCIES.len()].to_string(),
+                identifier1_kind: Some(IdentifierKind::Iban),
+                identifier1: Some(iban(country, &mut rng)),
+                balances: random_walk(start, end, &mut rng),
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    Ok(UserData {
+        schema_version: Some(SCHEMA_VERSION),
+        providers,
+        accounts,
+        ..Default::default()
+    })
+}
+
+/// Generates a data directory at `out`, refusing to overwrite one that's already there. With `statements`, the
+/// balances go into a CSV per account and year under `inbox/<year>` and `data.yml` has none.
+pub fn write_fixture(out: &Path, spec: &FixtureSpec) -> Result<UserData> {
+    let _span = tracing::info_span!("fixture", path = %out.display()).entered();
+    let out_yaml = out.join("data.yml");
+    if out_yaml.exists() {
+        bail!("{:?} already exists, refusing to overwrite it", out_yaml);
+    }
+
+    let mut user_data = generate(spec)?;
+    std::fs::create_dir_all(out)?;
+    if spec.statements {
+        for account in &mut user_data.accounts {
+            for year in spec.first_year..spec.first_year + spec.years as i32 {
+                let mut csv = "date,amount\n".to_string();
+                for record in account
+                    .balances
+                    .iter()
+                    .filter(|record| record.date.year() == year)
+                {
+                    writeln!(csv, "{},{}", record.date, record.amount)?;
+                }
+                let dir = inbox_dir(out, year);
+                std::fs::create_dir_all(&dir)?;
+                std::fs::write(dir.join(format!("{}.csv", account.handle)), csv)?;
+            }
+        }
+    }
+
+    let mut written = user_data.clone();
+    if spec.statements {
+        for account in &mut written.accounts {
+            account.balances.clear();
+        }
+    }
+    std::fs::write(out_yaml, serde_yaml::to_string(&written)?)?;
+    Ok(user_data)
+}
+
+/// A day-by-day balance starting between 1,000 and 50,000 that moves by up to 2% a day and never goes negative
+fn random_walk(start: NaiveDate, end: NaiveDate, rng: &mut SplitMix64) -> Vec<BalanceRecord> {
+    let mut cents = 100_000 + rng.below(4_900_000) as i64;
+    start
+        .iter_days()
+        .take_while(|date| *date <= end)
+        .map(|date| {
+            let step = cents / 50;
+            if step > 0 {
+                cents += rng.below(2 * step as u64 + 1) as i64 - step;
+            }
+            cents = cents.max(0);
+            BalanceRecord {
+                date,
+                amount: Decimal::new(cents, 2),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// A random IBAN for the country with valid check digits
+pub(crate) fn iban(country: &str, rng: &mut SplitMix64) -> String {
+    let bban: String = (0..18)
+        .map(|_| char::from(b'0' + rng.below(10) as u8))
+        .collect();
+    let check = 98 - iban_remainder(&format!("{}00{}", country, bban));
+    format!("{}{:02}{}", country, check, bban)
+}
+
+/// A small, fast generator, so fixtures are reproducible from a seed without another dependency
+pub(crate) struct SplitMix64(pub(crate) u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub(crate) fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::facts::Facts;
+    use crate::parsing::ParseContext;
+    use crate::report::Report;
+    use crate::report_context::ReportContext;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_write_fixture() -> Result<()> {
+        let dir = TempDir::new()?;
+        let spec = FixtureSpec {
+            providers: 2,
+            accounts: 3,
+            first_year: 2023,
+            years: 1,
+            ..Default::default()
+        };
+        let generated = write_fixture(dir.path(), &spec)?;
+        assert_eq!(generated.accounts[0].balances.len(), 365);
+
+        // Loads with valid identifiers and reports every account
+        let context = ParseContext::default();
+        let user_data = UserData::load_with_context(dir.path(), &context)?;
+        assert!(!context
+            .warnings()
+            .iter()
+            .any(|warning| warning.contains("identifier")));
+        assert_eq!(user_data.accounts.len(), 3);
+        let context = ReportContext::new(Facts::load_facts().unwrap(), None);
+        let report = Report::generate(2023, &user_data, &context)?;
+        assert_eq!(report.accounts.len(), 3);
+
+        // The same seed gives the same data
+        assert_eq!(
+            generate(&spec)?.accounts[2].identifier1,
+            user_data.accounts[2].identifier1
+        );
+        assert!(write_fixture(dir.path(), &spec).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_statements() -> Result<()> {
+        let dir = TempDir::new()?;
+        let spec = FixtureSpec {
+            providers: 1,
+            accounts: 1,
+            first_year: 2023,
+            years: 2,
+            statements: true,
+            ..Default::default()
+        };
+        write_fixture(dir.path(), &spec)?;
+
+        let user_data = UserData::load_from_path(dir.path())?;
+        assert!(user_data.accounts[0].balances.is_empty());
+        let csv = std::fs::read_to_string(dir.path().join("inbox/2024/account_1.csv"))?;
+        // A header and every day of the leap year
+        assert_eq!(csv.lines().count(), 367);
+        assert!(csv.lines().nth(1).unwrap().starts_with("2024-01-01,"));
+
+        Ok(())
+    }
+}
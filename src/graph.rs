@@ -0,0 +1,280 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::data::UserData;
+use crate::fincen::{validate_report, IssueKind};
+use crate::report::{describe_rate, OpenPeriod, Report};
+use crate::report_context::ReportContext;
+
+/// Fill colour of nodes with a problem that stops the year being filed as-is
+const PROBLEM_COLOR: &str = "#f4b6b6";
+/// Fill colour of nodes with issues worth a look
+const WARNING_COLOR: &str = "#f9e3a3";
+
+/// Renders the data directory as a Graphviz DOT graph: filers and entities to the accounts they own, providers to
+/// the accounts they hold, and accounts to their imported statements and the year's exchange rates.
+///
+/// Problems found for the year are highlighted in red (will be rejected, or missing rates and valuations) or amber
+/// (truncated fields and warnings) and listed in the node's tooltip. Excluded accounts are dashed. Problems that
+/// belong to no node, like a report that can't be generated, are listed in the graph's label.
+pub fn data_graph(user_data: &UserData, context: &ReportContext, year: i32) -> String {
+    let mut problems: BTreeMap<String, Vec<(IssueKind, String)>> = BTreeMap::new();
+    let mut general: Vec<String> = Vec::new();
+
+    match Report::generate(year, user_data, context) {
+        Ok(report) => {
+            for issue in validate_report(&report, user_data) {
+                problems
+                    .entry(format!("account:{}", issue.account))
+                    .or_default()
+                    .push((issue.kind, format!("{}: {}", issue.field, issue.message)));
+            }
+        }
+        Err(err) => general.push(format!("Report for {} failed: {:#}", year, err)),
+    }
+
+    let mut currencies = BTreeSet::new();
+    for account in &user_data.accounts {
+        if account.exclude_from_fbar || OpenPeriod::for_account(account, year).is_none() {
+            continue;
+        }
+        currencies.insert(account.currency_code.to_lowercase());
+        if let Err(err) = context.valuation_for(account) {
+            problems
+                .entry(format!("account:{}", account.handle))
+                .or_default()
+                .push((IssueKind::Rejected, err.to_string()));
+        }
+    }
+
+    // Issues with the filing itself rather than an account
+    for (_, message) in problems.remove("account:filing").unwrap_or_default() {
+        general.push(format!("Filing: {}", message));
+    }
+
+    let mut dot = String::new();
+    writeln!(dot, "digraph fbar_prep {{").unwrap();
+    writeln!(dot, "  rankdir=LR;").unwrap();
+    writeln!(dot, "  node [fontname=\"Helvetica\", fontsize=10];").unwrap();
+    let mut label = format!("Data for {}", year);
+    for problem in &general {
+        label.push('\n');
+        label.push_str(problem);
+    }
+    writeln!(dot, "  label={};", quote(&label)).unwrap();
+
+    for filer in &user_data.filers {
+        node(
+            &mut dot,
+            &format!("filer:{}", filer.handle),
+            &format!("{}\nfiler", filer.name),
+            "ellipse",
+            &[],
+        );
+    }
+    for entity in &user_data.entities {
+        node(
+            &mut dot,
+            &format!("entity:{}", entity.handle),
+            &format!("{}\nentity", entity.name),
+            "hexagon",
+            &[],
+        );
+    }
+    for provider in &user_data.providers {
+        node(
+            &mut dot,
+            &format!("provider:{}", provider.handle),
+            &format!("{}\nprovider", provider.name),
+            "house",
+            &[],
+        );
+    }
+
+    for account in &user_data.accounts {
+        let id = format!("account:{}", account.handle);
+        let issues = problems.remove(&id).unwrap_or_default();
+        let label = format!(
+            "{}\n{} {}",
+            account.handle,
+            account.kind,
+            account.currency_code.to_uppercase()
+        );
+        node(&mut dot, &id, &label, "box", &issues);
+        if account.exclude_from_fbar {
+            let style = if issues.is_empty() {
+                "dashed"
+            } else {
+                "dashed,filled"
+            };
+            writeln!(dot, "  {} [style={}];", quote(&id), quote(style)).unwrap();
+        }
+
+        edge(&mut dot, &format!("provider:{}", account.provider), &id);
+        if let Some(entity) = &account.entity {
+            edge(&mut dot, &format!("entity:{}", entity), &id);
+        } else if account.owners.is_empty() {
+            if let Some(filer) = user_data.primary_filer() {
+                edge(&mut dot, &format!("filer:{}", filer.handle), &id);
+            }
+        }
+        for owner in &account.owners {
+            edge(&mut dot, &format!("filer:{}", owner), &id);
+        }
+
+        for (index, import) in account.imports.iter().enumerate() {
+            let statement = format!("statement:{}:{}", account.handle, index);
+            node(
+                &mut dot,
+                &statement,
+                &format!(
+                    "{}\n{} records",
+                    import.provenance.source_file,
+                    import.balances.len()
+                ),
+                "note",
+                &[],
+            );
+            edge(&mut dot, &id, &statement);
+        }
+
+        let currency = account.currency_code.to_lowercase();
+        if currencies.contains(&currency) {
+            edge(&mut dot, &id, &format!("rate:{}", currency));
+        }
+    }
+
+    for currency in currencies {
+        let id = format!("rate:{}", currency);
+        match context.find_exchange_rate(year, &currency) {
+            Ok(rate) => node(
+                &mut dot,
+                &id,
+                &format!(
+                    "{} {}\n{}",
+                    currency.to_uppercase(),
+                    year,
+                    describe_rate(&rate)
+                ),
+                "diamond",
+                &[],
+            ),
+            Err(err) => node(
+                &mut dot,
+                &id,
+                &format!("{} {}\nno rate", currency.to_uppercase(), year),
+                "diamond",
+                &[(IssueKind::Rejected, err.to_string())],
+            ),
+        }
+    }
+
+    writeln!(dot, "}}").unwrap();
+    dot
+}
+
+fn node(dot: &mut String, id: &str, label: &str, shape: &str, issues: &[(IssueKind, String)]) {
+    let mut attributes = format!("shape={}, label={}", shape, quote(label));
+    if !issues.is_empty() {
+        let color = if issues.iter().any(|(kind, _)| *kind == IssueKind::Rejected) {
+            PROBLEM_COLOR
+        } else {
+            WARNING_COLOR
+        };
+        let messages: Vec<&str> = issues.iter().map(|(_, message)| message.as_str()).collect();
+        attributes.push_str(&format!(
+            ", style=filled, fillcolor={}, tooltip={}",
+            quote(color),
+            quote(&messages.join("\n"))
+        ));
+    }
+    writeln!(dot, "  {} [{}];", quote(id), attributes).unwrap();
+}
+
+fn edge(dot: &mut String, from: &str, to: &str) {
+    writeln!(dot, "  {} -> {};", quote(from), quote(to)).unwrap();
+}
+
+/// A DOT double-quoted string, with newlines as centred line breaks
+fn quote(value: &str) -> String {
+    format!(
+        "\"{}\"",
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Account, Address, BalanceRecord, Filer, Provider};
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_data_graph() {
+        let user_data = UserData {
+            filers: vec![Filer {
+                name: "Alex Example".to_string(),
+                handle: "alex".to_string(),
+                tin: None,
+                address: None,
+            }],
+            providers: vec![Provider {
+                name: "Example \"Big\" Bank".to_string(),
+                handle: "bank".to_string(),
+                address: Address::from("1 Bank Street, London"),
+                country: Some("GB".to_string()),
+                ..Default::default()
+            }],
+            accounts: vec![
+                Account {
+                    handle: "checking".to_string(),
+                    provider: "bank".to_string(),
+                    currency_code: "GBP".to_string(),
+                    ..Default::default()
+                },
+                Account {
+                    handle: "savings".to_string(),
+                    provider: "bank".to_string(),
+                    currency_code: "CHF".to_string(),
+                    balances: vec![BalanceRecord {
+                        date: NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(),
+                        amount: dec!(1000),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let context = ReportContext::new(
+            Facts {
+                years: vec![AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![ExchangeRate::new("GBP".to_string(), 0.80).unwrap()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            None,
+        );
+
+        let dot = data_graph(&user_data, &context, 2023);
+        assert!(dot.starts_with("digraph fbar_prep {"));
+        assert!(dot.contains(
+            "\"provider:bank\" [shape=house, label=\"Example \\\"Big\\\" Bank\\nprovider\"];"
+        ));
+        assert!(dot.contains("\"filer:alex\" -> \"account:checking\";"));
+        assert!(dot.contains("\"account:checking\" -> \"rate:gbp\";"));
+        // Without a CHF rate the report fails, and the missing rate is highlighted
+        assert!(dot.contains("Report for 2023 failed"));
+        assert!(dot.contains(&format!(
+            "\"rate:chf\" [shape=diamond, label=\"CHF 2023\\nno rate\", style=filled, fillcolor=\"{}\"",
+            PROBLEM_COLOR
+        )));
+    }
+}
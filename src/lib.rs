@@ -0,0 +1,25 @@
+pub mod analyze;
+pub mod anonymize;
+pub mod app;
+pub mod crypto;
+pub mod data;
+pub mod diagnostics;
+pub mod doctor;
+pub mod examples;
+pub mod facts;
+pub mod fincen;
+pub mod fixture;
+pub mod graph;
+pub mod money;
+pub mod output;
+pub mod package;
+pub mod parsing;
+pub mod progress;
+pub mod report;
+pub mod report_context;
+pub mod retention;
+pub mod review;
+pub mod rollover;
+pub mod schema;
+pub mod statements;
+pub mod workbook;
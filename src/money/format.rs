@@ -0,0 +1,142 @@
+//! Writing amounts the way people read them: with the currency's symbol, grouped thousands, and as many decimal
+//! places as the currency has minor units.
+
+use rust_decimal::Decimal;
+
+use super::{minor_units, Money};
+
+/// Symbols that only stand for one currency. Currencies sharing a symbol, like the many dollars, get a prefix
+/// (`CA$`, `A$`) or else are written with their code.
+const SYMBOLS: &[(&str, &str)] = &[
+    ("usd", "$"),
+    ("eur", "€"),
+    ("gbp", "£"),
+    ("jpy", "¥"),
+    ("cny", "CN¥"),
+    ("inr", "₹"),
+    ("krw", "₩"),
+    ("ils", "₪"),
+    ("php", "₱"),
+    ("thb", "฿"),
+    ("try", "₺"),
+    ("vnd", "₫"),
+    ("ngn", "₦"),
+    ("uah", "₴"),
+    ("cad", "CA$"),
+    ("aud", "A$"),
+    ("nzd", "NZ$"),
+    ("hkd", "HK$"),
+    ("sgd", "S$"),
+    ("mxn", "MX$"),
+    ("brl", "R$"),
+];
+
+/// The currency's symbol, if it has one of its own
+pub fn symbol(currency: &str) -> Option<&'static str> {
+    let currency = currency.to_lowercase();
+    SYMBOLS
+        .iter()
+        .find(|(code, _)| *code == currency)
+        .map(|(_, symbol)| *symbol)
+}
+
+impl Money {
+    /// The amount as it's usually written, e.g. `£1,234.56`, `¥123,457` or `BHD 1,234.568`
+    pub fn format(&self) -> String {
+        self.format_to(minor_units(&self.currency))
+    }
+
+    /// The amount written with the given number of decimal places, for currencies defined in `fact_extensions`
+    pub fn format_to(&self, minor_units: u32) -> String {
+        let rounded = self.round_to(minor_units).amount;
+        let digits = group_thousands(rounded.abs(), minor_units);
+        let sign = if rounded.is_sign_negative() && !rounded.is_zero() {
+            "-"
+        } else {
+            ""
+        };
+        match symbol(&self.currency) {
+            Some(symbol) => format!("{}{}{}", sign, symbol, digits),
+            None => format!("{}{} {}", sign, self.currency.to_uppercase(), digits),
+        }
+    }
+}
+
+/// A maximum converted to USD, with the rate used, e.g. `£8,000.00 at 0.8 GBP per USD = $10,000.00`. USD amounts,
+/// which aren't converted, are written once. The amount is written to `minor_units` decimal places.
+pub fn format_conversion(
+    amount: &Money,
+    minor_units: u32,
+    usd: &Money,
+    rate: Option<f64>,
+) -> String {
+    match rate {
+        Some(rate) if !amount.is_currency(super::USD) => format!(
+            "{} at {} {} per USD = {}",
+            amount.format_to(minor_units),
+            rate,
+            amount.currency.to_uppercase(),
+            usd.format()
+        ),
+        _ => usd.format(),
+    }
+}
+
+fn group_thousands(amount: Decimal, decimal_places: u32) -> String {
+    let written = format!("{:.*}", decimal_places as usize, amount);
+    let (whole, fraction) = match written.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (written.as_str(), None),
+    };
+
+    let mut grouped = String::new();
+    for (index, digit) in whole.chars().enumerate() {
+        if index > 0 && (whole.len() - index) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    if let Some(fraction) = fraction {
+        grouped.push('.');
+        grouped.push_str(fraction);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(Money::new(dec!(1234.565), "gbp").format(), "£1,234.57");
+        assert_eq!(Money::new(dec!(123456.5), "JPY").format(), "¥123,457");
+        assert_eq!(Money::new(dec!(1234.5675), "bhd").format(), "BHD 1,234.568");
+        assert_eq!(Money::new(dec!(-50), "chf").format(), "-CHF 50.00");
+        assert_eq!(Money::usd(dec!(1000000)).format(), "$1,000,000.00");
+        assert_eq!(Money::usd(dec!(-0.001)).format(), "$0.00");
+        assert_eq!(Money::new(dec!(12), "cad").format(), "CA$12.00");
+        assert_eq!(
+            Money::new(dec!(1234.56789), "zzfund").format_to(4),
+            "ZZFUND 1,234.5679"
+        );
+    }
+
+    #[test]
+    fn test_format_conversion() {
+        assert_eq!(
+            format_conversion(
+                &Money::new(dec!(8000), "gbp"),
+                2,
+                &Money::usd(dec!(10000)),
+                Some(0.8)
+            ),
+            "£8,000.00 at 0.8 GBP per USD = $10,000.00"
+        );
+        assert_eq!(
+            format_conversion(&Money::usd(dec!(5)), 2, &Money::usd(dec!(5)), Some(1.0)),
+            "$5.00"
+        );
+    }
+}
@@ -0,0 +1,204 @@
+pub mod format;
+
+use anyhow::{bail, Result};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub const USD: &str = "usd";
+
+/// Currencies without the usual two decimal places: those ISO 4217 lists with other minor units, and the forint,
+/// whose fillér were withdrawn in 1999 though ISO 4217 still gives it two
+const MINOR_UNITS: &[(&str, u32)] = &[
+    ("bif", 0),
+    ("clp", 0),
+    ("djf", 0),
+    ("gnf", 0),
+    ("huf", 0),
+    ("isk", 0),
+    ("jpy", 0),
+    ("kmf", 0),
+    ("krw", 0),
+    ("pyg", 0),
+    ("rwf", 0),
+    ("ugx", 0),
+    ("uyi", 0),
+    ("vnd", 0),
+    ("vuv", 0),
+    ("xaf", 0),
+    ("xof", 0),
+    ("xpf", 0),
+    ("bhd", 3),
+    ("iqd", 3),
+    ("jod", 3),
+    ("kwd", 3),
+    ("lyd", 3),
+    ("omr", 3),
+    ("tnd", 3),
+    ("clf", 4),
+    ("uyw", 4),
+];
+
+/// Whether the tool knows the currency without it being defined: USD, or one with its own minor units or symbol
+pub fn is_builtin_currency(currency: &str) -> bool {
+    let currency = currency.to_lowercase();
+    currency == USD
+        || MINOR_UNITS.iter().any(|(code, _)| *code == currency)
+        || format::symbol(&currency).is_some()
+}
+
+/// Decimal places the currency is written and rounded to, 2 unless the table says otherwise. Currencies defined in
+/// `fact_extensions` have their own, looked up with `Facts::minor_units`.
+pub fn minor_units(currency: &str) -> u32 {
+    let currency = currency.to_lowercase();
+    MINOR_UNITS
+        .iter()
+        .find(|(code, _)| *code == currency)
+        .map_or(2, |(_, units)| *units)
+}
+
+/// An amount tagged with the currency it is denominated in.
+///
+/// Amounts are only ever combined or converted after checking their currencies, so a GBP balance can't be added to
+/// a EUR one or converted with the wrong currency's rate. Currency codes are stored lowercase, like exchange rates.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Money {
+    pub amount: Decimal,
+    pub currency: String,
+}
+
+impl Money {
+    pub fn new(amount: Decimal, currency: impl Into<String>) -> Self {
+        Self {
+            amount,
+            currency: currency.into().to_lowercase(),
+        }
+    }
+
+    pub fn usd(amount: Decimal) -> Self {
+        Self::new(amount, USD)
+    }
+
+    pub fn is_currency(&self, currency: &str) -> bool {
+        self.currency.eq_ignore_ascii_case(currency)
+    }
+
+    /// Fails unless the amount is in the given currency
+    pub fn expect_currency(&self, currency: &str) -> Result<()> {
+        if !self.is_currency(currency) {
+            bail!(
+                "Expected an amount in {} but got {}",
+                currency.to_uppercase(),
+                self
+            );
+        }
+        Ok(())
+    }
+
+    pub fn checked_add(&self, other: &Money) -> Result<Money> {
+        other.expect_currency(&self.currency)?;
+        Ok(Money::new(self.amount + other.amount, &self.currency))
+    }
+
+    pub fn checked_sub(&self, other: &Money) -> Result<Money> {
+        other.expect_currency(&self.currency)?;
+        Ok(Money::new(self.amount - other.amount, &self.currency))
+    }
+
+    /// Returns the larger of two amounts in the same currency
+    pub fn checked_max(self, other: Money) -> Result<Money> {
+        other.expect_currency(&self.currency)?;
+        Ok(if other.amount > self.amount {
+            other
+        } else {
+            self
+        })
+    }
+
+    /// Decimal places amounts in the currency have, e.g. 2 for cents, 0 for yen or 3 for Kuwaiti fils
+    pub fn minor_units(&self) -> u32 {
+        minor_units(&self.currency)
+    }
+
+    /// Rounds to the currency's minor units, with halves rounded away from zero
+    pub fn round(&self) -> Money {
+        self.round_to(self.minor_units())
+    }
+
+    /// Rounds to the given number of decimal places, for currencies defined in `fact_extensions`
+    pub fn round_to(&self, minor_units: u32) -> Money {
+        Money::new(
+            self.amount
+                .round_dp_with_strategy(minor_units, RoundingStrategy::MidpointAwayFromZero),
+            &self.currency,
+        )
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.*} {}",
+            self.minor_units() as usize,
+            self.amount,
+            self.currency.to_uppercase()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_currency_is_normalized() {
+        let money = Money::new(dec!(10), "GBP");
+        assert_eq!(money.currency, "gbp");
+        assert!(money.is_currency("GbP"));
+        assert_eq!(money.to_string(), "10.00 GBP");
+    }
+
+    #[test]
+    fn test_add_same_currency() -> Result<()> {
+        let total = Money::new(dec!(10.50), "eur").checked_add(&Money::new(dec!(4.25), "EUR"))?;
+        assert_eq!(total, Money::new(dec!(14.75), "eur"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_mixed_currencies() {
+        let result = Money::new(dec!(10), "gbp").checked_add(&Money::new(dec!(10), "eur"));
+
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Expected an amount in GBP but got 10.00 EUR"));
+    }
+
+    #[test]
+    fn test_max() -> Result<()> {
+        let max = Money::new(dec!(10), "gbp").checked_max(Money::new(dec!(12), "gbp"))?;
+        assert_eq!(max.amount, dec!(12));
+
+        assert!(Money::new(dec!(10), "gbp")
+            .checked_max(Money::usd(dec!(12)))
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_round() {
+        assert_eq!(Money::usd(dec!(1.004)).round().amount, dec!(1.00));
+        assert_eq!(Money::usd(dec!(1.005)).round().amount, dec!(1.01));
+        assert_eq!(Money::usd(dec!(-1.005)).round().amount, dec!(-1.01));
+        assert_eq!(Money::new(dec!(1234.5), "jpy").round().amount, dec!(1235));
+        assert_eq!(Money::new(dec!(1.2345), "kwd").round().amount, dec!(1.235));
+        assert_eq!(Money::new(dec!(99.5), "huf").round().amount, dec!(100));
+        assert_eq!(Money::new(dec!(1500), "JPY").to_string(), "1500 JPY");
+    }
+}
@@ -0,0 +1,424 @@
+mod table;
+mod writer;
+pub use self::table::{report_csv, report_html};
+pub use self::writer::{ReportWriter, WriterRegistry};
+
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use crate::data::UserData;
+use crate::report::{Report, ReportMetadata};
+
+/// Layout used when `--layout` isn't given
+pub const DEFAULT_LAYOUT: &str = "{year}/{filer}/{format}";
+/// Index of the artifacts written to an output directory, at its root
+pub const MANIFEST_FILE: &str = "manifest.yml";
+/// Stands in for `{filer}` when no filers are configured
+pub const DEFAULT_FILER: &str = "default";
+
+const PLACEHOLDERS: [&str; 3] = ["{year}", "{filer}", "{format}"];
+
+/// The built-in formats a report can be written in
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    Yaml,
+    Json,
+    /// A BSA E-Filing FBAR batch
+    Xml,
+    /// A worksheet for checking the filing by hand
+    Pdf,
+    /// An Excel workbook for accountants, with a summary sheet and a sheet per account
+    Xlsx,
+    /// A row per account, with the workbook summary's columns
+    Csv,
+    /// A page with the year's totals and a table of its accounts
+    Html,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 7] = [
+        OutputFormat::Yaml,
+        OutputFormat::Json,
+        OutputFormat::Xml,
+        OutputFormat::Pdf,
+        OutputFormat::Xlsx,
+        OutputFormat::Csv,
+        OutputFormat::Html,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Json => "json",
+            OutputFormat::Xml => "xml",
+            OutputFormat::Pdf => "pdf",
+            OutputFormat::Xlsx => "xlsx",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Html => "html",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Yaml => "yml",
+            OutputFormat::Json => "json",
+            OutputFormat::Xml => "xml",
+            OutputFormat::Pdf => "pdf",
+            OutputFormat::Xlsx => "xlsx",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Html => "html",
+        }
+    }
+
+    /// Whether the format is one the FBAR is filed from: the BSA batch, or the worksheet for filing by hand
+    pub fn is_filing(&self) -> bool {
+        matches!(self, OutputFormat::Xml | OutputFormat::Pdf)
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "yaml" | "yml" => Ok(OutputFormat::Yaml),
+            "json" => Ok(OutputFormat::Json),
+            "xml" => Ok(OutputFormat::Xml),
+            "pdf" => Ok(OutputFormat::Pdf),
+            "xlsx" => Ok(OutputFormat::Xlsx),
+            "csv" => Ok(OutputFormat::Csv),
+            "html" => Ok(OutputFormat::Html),
+            _ => Err(format!(
+                "unknown format {:?}, expected `yaml`, `json`, `xml`, `pdf`, `xlsx`, `csv` or `html`",
+                value
+            )),
+        }
+    }
+}
+
+/// Where artifacts go within an output directory: a relative path template using `{year}`, `{filer}` and
+/// `{format}`, e.g. `{year}/{filer}/{format}`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    template: String,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            template: DEFAULT_LAYOUT.to_string(),
+        }
+    }
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let mut remainder = template.to_string();
+        for placeholder in PLACEHOLDERS {
+            remainder = remainder.replace(placeholder, "");
+        }
+        if remainder.contains(['{', '}']) {
+            return Err(format!(
+                "layout {:?} has an unknown placeholder, expected {}",
+                template,
+                PLACEHOLDERS.join(", ")
+            ));
+        }
+        if !Path::new(template)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(format!(
+                "layout {:?} must be a relative path within the output directory",
+                template
+            ));
+        }
+        Ok(Self {
+            template: template.to_string(),
+        })
+    }
+}
+
+impl Layout {
+    /// The directory, relative to the output directory, that a year's artifacts in a format are written to
+    pub fn directory(&self, year: i32, filer: &str, format: &str) -> PathBuf {
+        PathBuf::from(
+            self.template
+                .replace("{year}", &year.to_string())
+                .replace("{filer}", filer)
+                .replace("{format}", format),
+        )
+    }
+}
+
+/// An artifact written to an output directory
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Artifact {
+    /// Path relative to the output directory, with `/` separators
+    pub path: String,
+    pub year: i32,
+    pub filer: String,
+    /// Name of the format, e.g. `yaml`, or `package` for a filing package
+    pub format: String,
+    /// Tags the report was limited to, if any
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// ID of the report in the artifact, from its metadata
+    pub report_id: String,
+    /// SHA-256 of the artifact's contents, hex encoded
+    pub sha256: String,
+}
+
+/// The output directory's index of artifacts, kept across runs and sorted by path so scripts can find each year's
+/// outputs without walking the directory
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Manifest {
+    pub artifacts: Vec<Artifact>,
+}
+
+impl Manifest {
+    /// Loads the output directory's manifest, or an empty one if there isn't one yet
+    pub fn load(out: &Path) -> Result<Self> {
+        let path = out.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        serde_yaml::from_str(&contents).with_context(|| format!("Invalid manifest {:?}", path))
+    }
+
+    pub fn save(&self, out: &Path) -> Result<()> {
+        std::fs::write(out.join(MANIFEST_FILE), serde_yaml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Adds an artifact, replacing any previous one at the same path
+    pub fn record(&mut self, artifact: Artifact) {
+        self.artifacts
+            .retain(|existing| existing.path != artifact.path);
+        self.artifacts.push(artifact);
+        self.artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+}
+
+/// The value of `{filer}` for the user's data: the primary filer's handle
+pub fn filer_handle(user_data: &UserData) -> &str {
+    user_data
+        .primary_filer()
+        .map(|filer| filer.handle.as_str())
+        .unwrap_or(DEFAULT_FILER)
+}
+
+/// Writes the report with each writer to its place in the layout under `out`, and records the artifacts in the
+/// directory's manifest
+pub fn write_report(
+    out: &Path,
+    layout: &Layout,
+    report: &Report,
+    user_data: &UserData,
+    writers: &[&dyn ReportWriter],
+    signed_on: NaiveDate,
+) -> Result<Vec<Artifact>> {
+    let metadata = report_metadata(report)?;
+    if writers.is_empty() {
+        bail!("No output formats given");
+    }
+    let filer = filer_handle(user_data);
+    let mut manifest = Manifest::load(out)?;
+
+    let mut file_name = "report".to_string();
+    for tag in &metadata.tags {
+        file_name.push('-');
+        file_name.push_str(tag);
+    }
+
+    let mut artifacts = Vec::new();
+    for writer in writers {
+        let relative = layout
+            .directory(report.year, filer, writer.name())
+            .join(format!("{}.{}", file_name, writer.extension()));
+        let contents = writer.render_to_vec(report, user_data, signed_on)?;
+        let artifact = write_artifact(out, relative, &contents, report, filer, writer.name())?;
+        manifest.record(artifact.clone());
+        artifacts.push(artifact);
+    }
+
+    manifest.save(out)?;
+    Ok(artifacts)
+}
+
+pub(crate) fn report_metadata(report: &Report) -> Result<&ReportMetadata> {
+    report
+        .metadata
+        .as_ref()
+        .context("Report has no metadata to record in the manifest")
+}
+
+/// Writes an artifact's contents at a path relative to the output directory, describing it for the manifest
+pub(crate) fn write_artifact(
+    out: &Path,
+    relative: PathBuf,
+    contents: &[u8],
+    report: &Report,
+    filer: &str,
+    format: &str,
+) -> Result<Artifact> {
+    let metadata = report_metadata(report)?;
+    let path = out.join(&relative);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {:?}", parent))?;
+    }
+    std::fs::write(&path, contents).with_context(|| format!("Failed to write {:?}", path))?;
+
+    Ok(Artifact {
+        path: relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+        year: report.year,
+        filer: filer.to_string(),
+        format: format.to_string(),
+        tags: metadata.tags.clone(),
+        report_id: metadata.report_id.clone(),
+        sha256: hex::encode(Sha256::digest(contents)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Account, BalanceRecord};
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use crate::report_context::ReportContext;
+    use rust_decimal_macros::dec;
+    use tempfile::TempDir;
+
+    fn create_test_report(tags: &[String]) -> Result<Report> {
+        let user_data = UserData {
+            accounts: vec![Account {
+                handle: "example_checking".to_string(),
+                provider: "example_bank".to_string(),
+                currency_code: "EUR".to_string(),
+                tags: vec!["household".to_string()],
+                balances: vec![BalanceRecord {
+                    date: NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(),
+                    amount: dec!(100),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let context = ReportContext::new(
+            Facts {
+                years: vec![AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![ExchangeRate::new("EUR".to_string(), 0.80).unwrap()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            None,
+        );
+        Report::generate_tagged(2023, &user_data, &context, tags)
+    }
+
+    fn signed_on() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()
+    }
+
+    #[test]
+    fn test_layout() {
+        let layout = Layout::default();
+        assert_eq!(
+            layout.directory(2023, "alex", "json"),
+            PathBuf::from("2023/alex/json")
+        );
+
+        let layout: Layout = "fbar-{year}".parse().unwrap();
+        assert_eq!(
+            layout.directory(2023, "alex", "yaml"),
+            PathBuf::from("fbar-2023")
+        );
+
+        assert!("{year}/{account}".parse::<Layout>().is_err());
+        assert!("/tmp/{year}".parse::<Layout>().is_err());
+        assert!("../{year}".parse::<Layout>().is_err());
+    }
+
+    #[test]
+    fn test_write_report() -> Result<()> {
+        let dir = TempDir::new()?;
+        let report = create_test_report(&[])?;
+
+        let artifacts = write_report(
+            dir.path(),
+            &Layout::default(),
+            &report,
+            &UserData::default(),
+            &[&OutputFormat::Yaml, &OutputFormat::Json, &OutputFormat::Pdf],
+            signed_on(),
+        )?;
+        assert_eq!(artifacts.len(), 3);
+        assert!(dir.path().join("2023/default/pdf/report.pdf").exists());
+
+        let yaml = std::fs::read_to_string(dir.path().join("2023/default/yaml/report.yml"))?;
+        assert_eq!(serde_yaml::from_str::<Report>(&yaml)?, report);
+        let json = std::fs::read_to_string(dir.path().join("2023/default/json/report.json"))?;
+        assert_eq!(serde_json::from_str::<Report>(&json)?, report);
+
+        // Writing again, and a tagged report alongside, keeps one entry per artifact
+        write_report(
+            dir.path(),
+            &Layout::default(),
+            &report,
+            &UserData::default(),
+            &[&OutputFormat::Yaml],
+            signed_on(),
+        )?;
+        let tagged = create_test_report(&["household".to_string()])?;
+        write_report(
+            dir.path(),
+            &Layout::default(),
+            &tagged,
+            &UserData::default(),
+            &[&OutputFormat::Yaml],
+            signed_on(),
+        )?;
+
+        let manifest = Manifest::load(dir.path())?;
+        let paths: Vec<&str> = manifest
+            .artifacts
+            .iter()
+            .map(|artifact| artifact.path.as_str())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "2023/default/json/report.json",
+                "2023/default/pdf/report.pdf",
+                "2023/default/yaml/report-household.yml",
+                "2023/default/yaml/report.yml",
+            ]
+        );
+        assert_eq!(
+            manifest.artifacts[3].sha256,
+            hex::encode(Sha256::digest(yaml.as_bytes()))
+        );
+        assert_eq!(
+            manifest.artifacts[3].report_id,
+            report.metadata.unwrap().report_id
+        );
+
+        Ok(())
+    }
+}
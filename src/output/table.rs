@@ -0,0 +1,244 @@
+//! The report as a table with a row per account, the same columns as the workbook's summary sheet: as CSV for
+//! spreadsheets and scripts, and as a standalone HTML page for reading in a browser or printing.
+
+use anyhow::Result;
+
+use crate::data::{Provider, UserData};
+use crate::report::overview::FILING_THRESHOLD_USD;
+use crate::report::{AccountReport, Report};
+
+const COLUMNS: [&str; 13] = [
+    "Account",
+    "Institution",
+    "Part",
+    "Currency",
+    "Maximum value",
+    "Rate (per USD)",
+    "Maximum value (USD)",
+    "Filed as (USD)",
+    "Maximum on",
+    "Rate source",
+    "Counted from",
+    "Counted to",
+    "Notes",
+];
+
+/// Renders the report's accounts as CSV, a header row then a row per account. Notes are on separate lines of the
+/// last column.
+pub fn report_csv(report: &Report, user_data: &UserData) -> Result<Vec<u8>> {
+    let mut writer = ::csv::Writer::from_writer(Vec::new());
+    writer.write_record(COLUMNS)?;
+    for account_report in &report.accounts {
+        let mut row = row(account_report, user_data)?;
+        row.push(account_report.notes.join("\n"));
+        writer.write_record(row)?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+/// Renders the report as an HTML page: the year's totals, a table of its accounts with their notes, and the
+/// accounts left out of the FBAR
+pub fn report_html(report: &Report, user_data: &UserData) -> Result<String> {
+    let aggregate = report.aggregate_max_usd()?;
+    let title = format!("FBAR summary for {}", report.year);
+    let mut html = vec![
+        "<!DOCTYPE html>".to_string(),
+        "<html>".to_string(),
+        "<head>".to_string(),
+        "<meta charset=\"utf-8\">".to_string(),
+        format!("<title>{}</title>", escape(&title)),
+        "</head>".to_string(),
+        "<body>".to_string(),
+        format!("<h1>{}</h1>", escape(&title)),
+        "<dl>".to_string(),
+    ];
+    let mut term = |term: &str, definition: &str| {
+        html.push(format!(
+            "<dt>{}</dt><dd>{}</dd>",
+            escape(term),
+            escape(definition)
+        ))
+    };
+    if let Some(filer) = user_data.primary_filer() {
+        term("Filer", &filer.name);
+    }
+    if let Some(metadata) = &report.metadata {
+        term("Report ID", &metadata.report_id);
+    }
+    term("Accounts", &report.accounts.len().to_string());
+    term("Aggregate maximum (USD)", &aggregate.amount.to_string());
+    term(
+        "FBAR required",
+        if aggregate.amount > FILING_THRESHOLD_USD {
+            "Yes"
+        } else {
+            "No"
+        },
+    );
+    html.push("</dl>".to_string());
+
+    html.push("<table>".to_string());
+    html.push(format!(
+        "<tr>{}</tr>",
+        COLUMNS
+            .iter()
+            .map(|column| format!("<th>{}</th>", escape(column)))
+            .collect::<String>()
+    ));
+    for account_report in &report.accounts {
+        let cells: String = row(account_report, user_data)?
+            .iter()
+            .map(|cell| format!("<td>{}</td>", escape(cell)))
+            .collect();
+        let notes = match account_report.notes.is_empty() {
+            true => String::new(),
+            false => format!(
+                "<ul>{}</ul>",
+                account_report
+                    .notes
+                    .iter()
+                    .map(|note| format!("<li>{}</li>", escape(note)))
+                    .collect::<String>()
+            ),
+        };
+        html.push(format!("<tr>{}<td>{}</td></tr>", cells, notes));
+    }
+    html.push("</table>".to_string());
+
+    if !report.excluded.is_empty() {
+        html.push("<h2>Left out of the FBAR</h2>".to_string());
+        html.push("<table>".to_string());
+        html.push("<tr><th>Account</th><th>Institution</th><th>Reason</th></tr>".to_string());
+        for excluded in &report.excluded {
+            html.push(format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&excluded.handle),
+                escape(&excluded.provider),
+                escape(&excluded.reason)
+            ));
+        }
+        html.push("</table>".to_string());
+    }
+
+    html.push("</body>".to_string());
+    html.push("</html>".to_string());
+    Ok(html.join("\n") + "\n")
+}
+
+/// The account's cells, up to but not including its notes
+fn row(account_report: &AccountReport, user_data: &UserData) -> Result<Vec<String>> {
+    let (from, to) = match account_report.open_period {
+        Some(period) => (period.start.to_string(), period.end.to_string()),
+        None => (String::new(), String::new()),
+    };
+    Ok(vec![
+        account_report.handle.clone(),
+        user_data
+            .providers
+            .iter()
+            .find(|provider| provider.handle == account_report.provider)
+            .map_or_else(
+                || account_report.provider.clone(),
+                Provider::institution_name,
+            ),
+        account_report.part().to_string(),
+        account_report.currency_code.to_uppercase(),
+        account_report.max_balance.amount.to_string(),
+        account_report
+            .exchange_rate
+            .map(|rate| rate.to_string())
+            .unwrap_or_default(),
+        account_report.max_balance_usd.amount.to_string(),
+        account_report.filed_value()?.to_string(),
+        account_report
+            .max_balance_date
+            .map(|date| date.to_string())
+            .unwrap_or_default(),
+        account_report.rate_source.to_string(),
+        from,
+        to,
+    ])
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Account, BalanceRecord};
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use crate::report_context::ReportContext;
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    fn create_test_report() -> Result<(Report, UserData)> {
+        let user_data = UserData {
+            providers: vec![Provider {
+                name: "Smith & Sons <Bank>".to_string(),
+                handle: "example_bank".to_string(),
+                ..Default::default()
+            }],
+            accounts: vec![Account {
+                handle: "savings".to_string(),
+                provider: "example_bank".to_string(),
+                currency_code: "EUR".to_string(),
+                notes: Some("Rainy day fund, \"untouched\"".to_string()),
+                balances: vec![BalanceRecord {
+                    date: NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(),
+                    amount: dec!(1000),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let context = ReportContext::new(
+            Facts {
+                years: vec![AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![ExchangeRate::new("EUR".to_string(), 0.8).unwrap()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            None,
+        );
+        let report = Report::generate(2023, &user_data, &context)?;
+        Ok((report, user_data))
+    }
+
+    #[test]
+    fn test_report_csv() -> Result<()> {
+        let (report, user_data) = create_test_report()?;
+        let csv = String::from_utf8(report_csv(&report, &user_data)?)?;
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some(COLUMNS.join(",").as_str()));
+        assert!(
+            lines.next().unwrap().starts_with(
+                "savings,Smith & Sons <Bank>,Part II,EUR,1000,0.8,1250,1250,2023-06-30,IRS rate,"
+            ),
+            "{}",
+            csv
+        );
+        assert!(csv.contains("\"Rainy day fund, \"\"untouched\"\"\""));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_html() -> Result<()> {
+        let (report, user_data) = create_test_report()?;
+        let html = report_html(&report, &user_data)?;
+        assert!(html.contains("<h1>FBAR summary for 2023</h1>"));
+        assert!(html.contains("<dt>Aggregate maximum (USD)</dt><dd>1250</dd>"));
+        assert!(html.contains("<td>Smith &amp; Sons &lt;Bank&gt;</td>"));
+        assert!(html.contains("<li>Rainy day fund, &quot;untouched&quot;</li>"));
+
+        Ok(())
+    }
+}
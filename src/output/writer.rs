@@ -0,0 +1,234 @@
+use anyhow::{bail, Result};
+use chrono::NaiveDate;
+use std::io::Write;
+
+use super::OutputFormat;
+use crate::data::UserData;
+use crate::fincen;
+use crate::report::Report;
+use crate::workbook;
+
+/// Renders a report in some output format.
+///
+/// Implement this in your own crate to support another format, and add it to a `WriterRegistry` to make it
+/// available to `write_report`.
+pub trait ReportWriter {
+    /// Short name used to pick the writer with `--format`, and as `{format}` in the output layout, e.g. `json`
+    fn name(&self) -> &str;
+
+    /// Extension of the files the writer produces, without the dot
+    fn extension(&self) -> &str;
+
+    /// Whether the FBAR is filed from the format, so writing it should warn about accounts that haven't been reviewed
+    fn is_filing(&self) -> bool {
+        false
+    }
+
+    /// Writes the report. A filing is signed on the given date.
+    fn render(
+        &self,
+        report: &Report,
+        user_data: &UserData,
+        signed_on: NaiveDate,
+        writer: &mut dyn Write,
+    ) -> Result<()>;
+
+    /// The report rendered to bytes
+    fn render_to_vec(
+        &self,
+        report: &Report,
+        user_data: &UserData,
+        signed_on: NaiveDate,
+    ) -> Result<Vec<u8>> {
+        let mut contents = Vec::new();
+        self.render(report, user_data, signed_on, &mut contents)?;
+        Ok(contents)
+    }
+}
+
+impl ReportWriter for OutputFormat {
+    fn name(&self) -> &str {
+        OutputFormat::name(self)
+    }
+
+    fn extension(&self) -> &str {
+        OutputFormat::extension(self)
+    }
+
+    fn is_filing(&self) -> bool {
+        OutputFormat::is_filing(self)
+    }
+
+    fn render(
+        &self,
+        report: &Report,
+        user_data: &UserData,
+        signed_on: NaiveDate,
+        writer: &mut dyn Write,
+    ) -> Result<()> {
+        match self {
+            OutputFormat::Yaml => serde_yaml::to_writer(writer, report)?,
+            OutputFormat::Json => {
+                serde_json::to_writer_pretty(&mut *writer, report)?;
+                writer.write_all(b"\n")?;
+            }
+            OutputFormat::Xml => {
+                writer.write_all(fincen::fbar_xml(report, user_data, signed_on)?.as_bytes())?
+            }
+            OutputFormat::Pdf => writer.write_all(&fincen::worksheet_pdf(report, user_data)?)?,
+            OutputFormat::Xlsx => {
+                writer.write_all(&workbook::report_workbook(report, user_data)?)?
+            }
+            OutputFormat::Csv => writer.write_all(&super::report_csv(report, user_data)?)?,
+            OutputFormat::Html => {
+                writer.write_all(super::report_html(report, user_data)?.as_bytes())?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The formats available to `report`. The default registry holds the built-in YAML, JSON, XML, PDF, Excel, CSV and
+/// HTML writers.
+pub struct WriterRegistry {
+    writers: Vec<Box<dyn ReportWriter>>,
+}
+
+impl Default for WriterRegistry {
+    fn default() -> Self {
+        OutputFormat::ALL
+            .into_iter()
+            .fold(Self::empty(), |registry, format| {
+                registry.with(Box::new(format))
+            })
+    }
+}
+
+impl WriterRegistry {
+    pub fn empty() -> Self {
+        Self {
+            writers: Vec::new(),
+        }
+    }
+
+    /// Adds a writer. Writers registered later take priority, so a custom writer can replace a built-in one of the
+    /// same name.
+    pub fn with(mut self, writer: Box<dyn ReportWriter>) -> Self {
+        self.writers.push(writer);
+        self
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.writers.iter().map(|writer| writer.name()).collect()
+    }
+
+    /// The writer with the given name, or failing that the given extension, so `yml` picks the YAML writer
+    pub fn get(&self, name: &str) -> Option<&dyn ReportWriter> {
+        self.writers
+            .iter()
+            .rev()
+            .find(|writer| writer.name() == name)
+            .or_else(|| {
+                self.writers
+                    .iter()
+                    .rev()
+                    .find(|writer| writer.extension() == name)
+            })
+            .map(|writer| writer.as_ref())
+    }
+
+    /// The writers for each of the named formats, in order
+    pub fn resolve(&self, names: &[String]) -> Result<Vec<&dyn ReportWriter>> {
+        names
+            .iter()
+            .map(|name| match self.get(name) {
+                Some(writer) => Ok(writer),
+                None => bail!(
+                    "Unknown output format {:?} (available: {})",
+                    name,
+                    self.names().join(", ")
+                ),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Account, BalanceRecord};
+    use crate::facts::Facts;
+    use crate::report_context::ReportContext;
+    use rust_decimal_macros::dec;
+
+    /// Writes a line per account
+    struct ListWriter;
+
+    impl ReportWriter for ListWriter {
+        fn name(&self) -> &str {
+            "list"
+        }
+
+        fn extension(&self) -> &str {
+            "txt"
+        }
+
+        fn render(
+            &self,
+            report: &Report,
+            _user_data: &UserData,
+            _signed_on: NaiveDate,
+            writer: &mut dyn Write,
+        ) -> Result<()> {
+            for account in &report.accounts {
+                writeln!(writer, "{}", account.handle)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_default_writers() -> Result<()> {
+        let registry = WriterRegistry::default();
+        assert_eq!(
+            registry.names(),
+            vec!["yaml", "json", "xml", "pdf", "xlsx", "csv", "html"]
+        );
+
+        let writers = registry.resolve(&["yml".to_string(), "pdf".to_string()])?;
+        assert_eq!(writers[0].name(), "yaml");
+        assert!(writers[1].is_filing());
+        assert!(registry.resolve(&["list".to_string()]).is_err());
+
+        let registry = registry.with(Box::new(ListWriter));
+        let writer = registry.get("list").unwrap();
+        let user_data = UserData {
+            accounts: vec![Account {
+                handle: "brokerage".to_string(),
+                currency_code: "USD".to_string(),
+                balances: vec![BalanceRecord {
+                    date: NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(),
+                    amount: dec!(100),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let context = ReportContext::new(
+            Facts {
+                years: Vec::new(),
+                ..Default::default()
+            },
+            None,
+        );
+        let report = Report::generate(2023, &user_data, &context)?;
+        assert_eq!(
+            writer.render_to_vec(&report, &user_data, NaiveDate::MIN)?,
+            b"brokerage\n"
+        );
+        assert!(!writer.is_filing());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,396 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, NaiveDate, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+
+use crate::data::UserData;
+use crate::fincen::{self, FieldIssue};
+use crate::money::Money;
+use crate::output::{self, Artifact, Layout, Manifest, OutputFormat, ReportWriter};
+use crate::report::{AccountReport, ExcludedAccount, ProvenanceEntry, Report, ReportMetadata};
+
+/// Name packages are recorded under in the output directory's manifest and layout
+pub const PACKAGE_FORMAT: &str = "package";
+/// Name of the package's own manifest, the last file in the archive
+pub const PACKAGE_MANIFEST: &str = "manifest.yml";
+
+/// Index of a filing package's contents, stamped with when it was built and each file's hash
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PackageManifest {
+    pub year: i32,
+    pub report_id: String,
+    pub generated_at: DateTime<Utc>,
+    pub files: Vec<PackageFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PackageFile {
+    pub name: String,
+    /// SHA-256 of the file, hex encoded
+    pub sha256: String,
+}
+
+/// How each figure in the report was arrived at, for the records kept with the filing
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AuditTrail {
+    pub year: i32,
+    pub metadata: ReportMetadata,
+    pub accounts: Vec<AccountReport>,
+    /// Each account's conversion to USD before and after rounding
+    #[serde(default)]
+    pub conversions: Vec<ConversionAudit>,
+    pub excluded: Vec<ExcludedAccount>,
+    /// Balances from the year entered by hand rather than imported, kept apart so corrections can be told from
+    /// statement data
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub manual_records: Vec<ManualRecord>,
+    /// Problems `validate` finds with the report, as they stood when the package was built
+    pub issues: Vec<String>,
+}
+
+/// An account's maximum value converted to USD, with the exact result the rounded figure came from
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ConversionAudit {
+    pub account: String,
+    /// The maximum value in the account's currency
+    pub max_balance: Money,
+    /// When the account first held its maximum value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_balance_date: Option<NaiveDate>,
+    /// Units of the account's currency per USD, absent for amounts already in USD
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exchange_rate: Option<f64>,
+    /// The maximum value in USD before rounding to the cent
+    pub unrounded_usd: Money,
+    pub max_balance_usd: Money,
+}
+
+impl ConversionAudit {
+    fn new(account: &AccountReport) -> Result<Self> {
+        let unrounded_usd = account.unrounded_max_balance_usd()?;
+        Ok(Self {
+            account: account.handle.clone(),
+            max_balance: account.max_balance.clone(),
+            max_balance_date: account.max_balance_date,
+            exchange_rate: account.exchange_rate,
+            unrounded_usd,
+            max_balance_usd: account.max_balance_usd.clone(),
+        })
+    }
+}
+
+/// A balance entered by hand in `data.yml`, with where it came from
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ManualRecord {
+    pub account: String,
+    pub date: NaiveDate,
+    pub balance: Money,
+    pub note: String,
+}
+
+impl ManualRecord {
+    /// The manual balance records from the year of the reported accounts
+    fn for_report(report: &Report, user_data: &UserData) -> Vec<Self> {
+        report
+            .accounts
+            .iter()
+            .filter_map(|account_report| {
+                user_data
+                    .accounts
+                    .iter()
+                    .find(|account| account.handle == account_report.handle)
+            })
+            .flat_map(|account| {
+                account
+                    .balances_in_year(report.year)
+                    .filter(|record| record.manual)
+                    .map(move |record| ManualRecord {
+                        account: account.handle.clone(),
+                        date: record.date,
+                        balance: Money::new(
+                            record.amount,
+                            record
+                                .currency
+                                .as_ref()
+                                .unwrap_or(&account.currency_code)
+                                .to_lowercase(),
+                        ),
+                        note: record.note.clone().unwrap_or_default(),
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Where the report's inputs came from: the hashes of the data and facts, and each imported statement
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ProvenanceManifest {
+    pub year: i32,
+    pub input_hash: String,
+    pub facts_hash: String,
+    pub statements: Vec<ProvenanceEntry>,
+}
+
+/// The files of a filing package, in archive order, ending with its manifest
+pub fn package_files(
+    report: &Report,
+    user_data: &UserData,
+    generated_at: DateTime<Utc>,
+) -> Result<Vec<(String, Vec<u8>)>> {
+    let metadata = output::report_metadata(report)?;
+    let signed_on = generated_at.date_naive();
+    let year = report.year;
+
+    let audit_trail = AuditTrail {
+        year,
+        metadata: metadata.clone(),
+        accounts: report.accounts.clone(),
+        conversions: report
+            .accounts
+            .iter()
+            .map(ConversionAudit::new)
+            .collect::<Result<_>>()?,
+        excluded: report.excluded.clone(),
+        manual_records: ManualRecord::for_report(report, user_data),
+        issues: fincen::validate_report(report, user_data)
+            .iter()
+            .map(FieldIssue::to_string)
+            .collect(),
+    };
+    let provenance = ProvenanceManifest {
+        year,
+        input_hash: metadata.input_hash.clone(),
+        facts_hash: metadata.facts_hash.clone(),
+        statements: report.provenance.clone(),
+    };
+
+    let mut files = vec![
+        (
+            format!("fbar-{}.xml", year),
+            OutputFormat::Xml.render_to_vec(report, user_data, signed_on)?,
+        ),
+        (
+            format!("worksheet-{}.pdf", year),
+            OutputFormat::Pdf.render_to_vec(report, user_data, signed_on)?,
+        ),
+        (
+            format!("report-{}.json", year),
+            OutputFormat::Json.render_to_vec(report, user_data, signed_on)?,
+        ),
+        (
+            format!("audit-trail-{}.yml", year),
+            serde_yaml::to_string(&audit_trail)?.into_bytes(),
+        ),
+        (
+            format!("provenance-{}.yml", year),
+            serde_yaml::to_string(&provenance)?.into_bytes(),
+        ),
+    ];
+
+    let manifest = PackageManifest {
+        year,
+        report_id: metadata.report_id.clone(),
+        generated_at,
+        files: files
+            .iter()
+            .map(|(name, contents)| PackageFile {
+                name: name.clone(),
+                sha256: hex::encode(Sha256::digest(contents)),
+            })
+            .collect(),
+    };
+    files.push((
+        PACKAGE_MANIFEST.to_string(),
+        serde_yaml::to_string(&manifest)?.into_bytes(),
+    ));
+
+    Ok(files)
+}
+
+/// Builds the filing package for a report, a zip of the FinCEN XML, PDF worksheet, JSON report, audit trail and
+/// provenance manifest, and writes it to its place in the layout under `out`. The archive is named after the time
+/// it was built and the hash of its manifest, and recorded in the output directory's manifest.
+pub fn write_package(
+    out: &Path,
+    layout: &Layout,
+    report: &Report,
+    user_data: &UserData,
+    generated_at: DateTime<Utc>,
+) -> Result<Artifact> {
+    let files = package_files(report, user_data, generated_at)?;
+    let (_, manifest) = files.last().context("Package has no manifest")?;
+    let stamp = hex::encode(Sha256::digest(manifest));
+
+    let archive = zip_files(&files, generated_at)?;
+
+    let filer = output::filer_handle(user_data);
+    let relative = layout
+        .directory(report.year, filer, PACKAGE_FORMAT)
+        .join(format!(
+            "fbar-{}-{}-{}.zip",
+            report.year,
+            generated_at.format("%Y%m%dT%H%M%SZ"),
+            &stamp[..12]
+        ));
+    let artifact = output::write_artifact(out, relative, &archive, report, filer, PACKAGE_FORMAT)?;
+
+    let mut manifest = Manifest::load(out)?;
+    manifest.record(artifact.clone());
+    manifest.save(out)?;
+    Ok(artifact)
+}
+
+/// Zips the files in order. Entries carry the build time rather than the time they were zipped, so the archive's
+/// bytes depend only on its contents.
+pub(crate) fn zip_files(
+    files: &[(String, Vec<u8>)],
+    generated_at: DateTime<Utc>,
+) -> Result<Vec<u8>> {
+    let modified = zip::DateTime::from_date_and_time(
+        generated_at.year() as u16,
+        generated_at.month() as u8,
+        generated_at.day() as u8,
+        generated_at.hour() as u8,
+        generated_at.minute() as u8,
+        generated_at.second() as u8,
+    )
+    .context("Package timestamp is out of the range zip files can hold")?;
+    let options = SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .last_modified_time(modified);
+
+    let mut archive = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, contents) in files {
+        archive.start_file(name.as_str(), options)?;
+        archive.write_all(contents)?;
+    }
+    Ok(archive.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Account, BalanceRecord};
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use crate::report_context::ReportContext;
+    use chrono::{NaiveDate, TimeZone};
+    use rust_decimal_macros::dec;
+    use std::io::Read;
+    use tempfile::TempDir;
+
+    fn create_test_report() -> Result<(UserData, Report)> {
+        let user_data = UserData {
+            accounts: vec![Account {
+                handle: "example_checking".to_string(),
+                provider: "example_bank".to_string(),
+                currency_code: "EUR".to_string(),
+                identifier1: Some("12345678".to_string()),
+                balances: vec![BalanceRecord {
+                    date: NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(),
+                    amount: dec!(100.01),
+                    manual: true,
+                    note: Some("phone confirmation from bank".to_string()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let context = ReportContext::new(
+            Facts {
+                years: vec![AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![ExchangeRate::new("EUR".to_string(), 0.80).unwrap()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            None,
+        );
+        let report = Report::generate(2023, &user_data, &context)?;
+        Ok((user_data, report))
+    }
+
+    #[test]
+    fn test_write_package() -> Result<()> {
+        let dir = TempDir::new()?;
+        let (user_data, report) = create_test_report()?;
+        let generated_at = Utc.with_ymd_and_hms(2024, 3, 15, 9, 30, 0).unwrap();
+
+        let artifact = write_package(
+            dir.path(),
+            &Layout::default(),
+            &report,
+            &user_data,
+            generated_at,
+        )?;
+        assert!(artifact
+            .path
+            .starts_with("2023/default/package/fbar-2023-20240315T093000Z-"));
+        assert_eq!(
+            Manifest::load(dir.path())?.artifacts,
+            vec![artifact.clone()]
+        );
+
+        let file = std::fs::File::open(dir.path().join(&artifact.path))?;
+        let mut archive = zip::ZipArchive::new(file)?;
+        let names: Vec<&str> = archive.file_names().collect();
+        assert_eq!(names.len(), 6);
+
+        let mut contents = String::new();
+        archive
+            .by_name(PACKAGE_MANIFEST)?
+            .read_to_string(&mut contents)?;
+        let manifest: PackageManifest = serde_yaml::from_str(&contents)?;
+        assert_eq!(manifest.generated_at, generated_at);
+        assert_eq!(
+            manifest.report_id,
+            report.metadata.as_ref().unwrap().report_id
+        );
+
+        contents.clear();
+        archive
+            .by_name("audit-trail-2023.yml")?
+            .read_to_string(&mut contents)?;
+        let audit_trail: AuditTrail = serde_yaml::from_str(&contents)?;
+        assert_eq!(
+            audit_trail.conversions[0].unrounded_usd,
+            Money::usd(dec!(125.0125))
+        );
+        assert_eq!(
+            audit_trail.conversions[0].max_balance_usd,
+            Money::usd(dec!(125.01))
+        );
+        assert_eq!(
+            audit_trail.manual_records,
+            vec![ManualRecord {
+                account: "example_checking".to_string(),
+                date: NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(),
+                balance: Money::new(dec!(100.01), "eur"),
+                note: "phone confirmation from bank".to_string(),
+            }]
+        );
+
+        // Every file listed matches its hash
+        for file in &manifest.files {
+            let mut contents = Vec::new();
+            archive.by_name(&file.name)?.read_to_end(&mut contents)?;
+            assert_eq!(hex::encode(Sha256::digest(&contents)), file.sha256);
+        }
+
+        // Building again from the same report gives the same archive
+        let again = write_package(
+            dir.path(),
+            &Layout::default(),
+            &report,
+            &user_data,
+            generated_at,
+        )?;
+        assert_eq!(again, artifact);
+
+        Ok(())
+    }
+}
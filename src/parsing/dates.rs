@@ -0,0 +1,176 @@
+use chrono::NaiveDate;
+use std::fmt;
+use std::str::FromStr;
+
+/// How to read numeric dates like `03/04/2023`, where the day and month could be either way round
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateOrder {
+    /// `DD/MM/YYYY`, as used by most non-US banks
+    DayFirst,
+    /// `MM/DD/YYYY`, as used by US banks
+    MonthFirst,
+}
+
+impl FromStr for DateOrder {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "day-first" | "dmy" => Ok(Self::DayFirst),
+            "month-first" | "mdy" => Ok(Self::MonthFirst),
+            _ => Err(format!(
+                "unknown date order {:?}, expected `day-first` or `month-first`",
+                value
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DateError {
+    /// Not a date in any supported format
+    Unrecognized,
+    /// Valid either way round, and no date order was given to decide
+    Ambiguous {
+        day_first: NaiveDate,
+        month_first: NaiveDate,
+    },
+}
+
+impl fmt::Display for DateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unrecognized => write!(f, "unrecognized date"),
+            Self::Ambiguous {
+                day_first,
+                month_first,
+            } => write!(
+                f,
+                "ambiguous date: {} if day first, {} if month first; pass a date order to decide",
+                day_first, month_first
+            ),
+        }
+    }
+}
+
+/// Parses a statement date.
+///
+/// ISO dates (`2023-01-31`) are always accepted, including timestamps with a time and timezone, whose date is taken
+/// as written rather than shifted to another timezone. Numeric dates separated by `/`, `.` or `-` are read in the
+/// given order; without one they are only accepted when a part greater than 12 makes the order obvious.
+pub fn parse_date(value: &str, order: Option<DateOrder>) -> Result<NaiveDate, DateError> {
+    let value = value.trim();
+
+    // The date part of an ISO timestamp, e.g. 2023-01-31T23:30:00-05:00
+    let iso_date = value.get(..10).filter(|_| {
+        value.len() == 10 || value[10..].starts_with('T') || value[10..].starts_with(' ')
+    });
+    if let Some(date) = iso_date.and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()) {
+        return Ok(date);
+    }
+
+    let parts: Vec<&str> = value.split(['/', '.', '-']).collect();
+    let [first, second, year] = parts[..] else {
+        return Err(DateError::Unrecognized);
+    };
+    let (Ok(first), Ok(second), Ok(year)) = (
+        first.parse::<u32>(),
+        second.parse::<u32>(),
+        year.parse::<i32>(),
+    ) else {
+        return Err(DateError::Unrecognized);
+    };
+    if year < 1000 {
+        return Err(DateError::Unrecognized);
+    }
+
+    let day_first = NaiveDate::from_ymd_opt(year, second, first);
+    let month_first = NaiveDate::from_ymd_opt(year, first, second);
+
+    match (order, day_first, month_first) {
+        (Some(DateOrder::DayFirst), Some(date), _) => Ok(date),
+        (Some(DateOrder::MonthFirst), _, Some(date)) => Ok(date),
+        (Some(_), _, _) => Err(DateError::Unrecognized),
+        (None, Some(day_first), Some(month_first)) if day_first != month_first => {
+            Err(DateError::Ambiguous {
+                day_first,
+                month_first,
+            })
+        }
+        (None, Some(date), _) | (None, None, Some(date)) => Ok(date),
+        (None, None, None) => Err(DateError::Unrecognized),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_iso_dates() {
+        assert_eq!(parse_date("2023-01-31", None), Ok(date(2023, 1, 31)));
+        assert_eq!(
+            parse_date("2023-01-31T23:30:00-05:00", Some(DateOrder::DayFirst)),
+            Ok(date(2023, 1, 31))
+        );
+        assert_eq!(
+            parse_date(" 2023-01-31 10:00:00 ", None),
+            Ok(date(2023, 1, 31))
+        );
+        assert_eq!(
+            parse_date("2023-01-31garbage", None),
+            Err(DateError::Unrecognized)
+        );
+    }
+
+    #[test]
+    fn test_unambiguous_numeric_dates() {
+        assert_eq!(parse_date("31/01/2023", None), Ok(date(2023, 1, 31)));
+        assert_eq!(parse_date("01/31/2023", None), Ok(date(2023, 1, 31)));
+        assert_eq!(parse_date("31.01.2023", None), Ok(date(2023, 1, 31)));
+        // Same either way round
+        assert_eq!(parse_date("05/05/2023", None), Ok(date(2023, 5, 5)));
+    }
+
+    #[test]
+    fn test_ambiguous_dates() {
+        assert_eq!(
+            parse_date("03/04/2023", None),
+            Err(DateError::Ambiguous {
+                day_first: date(2023, 4, 3),
+                month_first: date(2023, 3, 4),
+            })
+        );
+        assert_eq!(
+            parse_date("03/04/2023", Some(DateOrder::DayFirst)),
+            Ok(date(2023, 4, 3))
+        );
+        assert_eq!(
+            parse_date("03/04/2023", Some(DateOrder::MonthFirst)),
+            Ok(date(2023, 3, 4))
+        );
+    }
+
+    #[test]
+    fn test_invalid_dates() {
+        for value in ["", "not-a-date", "31/01/23", "13/13/2023", "1/2/3/2023"] {
+            assert_eq!(parse_date(value, None), Err(DateError::Unrecognized));
+        }
+        // Contradicts the given order
+        assert_eq!(
+            parse_date("01/31/2023", Some(DateOrder::DayFirst)),
+            Err(DateError::Unrecognized)
+        );
+    }
+
+    #[test]
+    fn test_date_order_from_str() {
+        assert_eq!("day-first".parse(), Ok(DateOrder::DayFirst));
+        assert_eq!("mdy".parse(), Ok(DateOrder::MonthFirst));
+        assert!("ymd".parse::<DateOrder>().is_err());
+    }
+}
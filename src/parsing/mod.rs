@@ -0,0 +1,437 @@
+pub mod dates;
+pub mod numbers;
+mod suggest;
+pub use self::dates::{DateError, DateOrder};
+pub use self::numbers::{NumberError, NumberFormat};
+
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use std::cell::RefCell;
+
+use crate::diagnostics::{codes, Diagnostic, Diagnostics};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ParseMode {
+    /// Problems in the user's data are collected as warnings and parsing carries on
+    #[default]
+    Lenient,
+    /// Any problem in the user's data is a hard error
+    Strict,
+}
+
+/// Options and collected warnings shared by everything that deserializes user-provided files.
+///
+/// Loaders report recoverable problems (unknown keys, unparseable statement dates) through `issue`, which either
+/// fails or records a warning diagnostic depending on the mode. Strict mode additionally requires optional fields to be given.
+#[derive(Debug, Default)]
+pub struct ParseContext {
+    mode: ParseMode,
+    /// How to read day/month-ambiguous statement dates
+    date_order: Option<DateOrder>,
+    /// How to read statement amounts whose decimal separator could be either `.` or `,`
+    number_format: Option<NumberFormat>,
+    diagnostics: RefCell<Diagnostics>,
+}
+
+impl ParseContext {
+    pub fn new(mode: ParseMode) -> Self {
+        Self {
+            mode,
+            date_order: None,
+            number_format: None,
+            diagnostics: RefCell::new(Diagnostics::default()),
+        }
+    }
+
+    pub fn with_date_order(mut self, date_order: Option<DateOrder>) -> Self {
+        self.date_order = date_order;
+        self
+    }
+
+    pub fn with_number_format(mut self, number_format: Option<NumberFormat>) -> Self {
+        self.number_format = number_format;
+        self
+    }
+
+    pub fn strict() -> Self {
+        Self::new(ParseMode::Strict)
+    }
+
+    pub fn mode(&self) -> ParseMode {
+        self.mode
+    }
+
+    /// A context with the same options and no diagnostics yet
+    fn fork(&self) -> Self {
+        Self {
+            mode: self.mode,
+            date_order: self.date_order,
+            number_format: self.number_format,
+            diagnostics: RefCell::new(Diagnostics::default()),
+        }
+    }
+
+    /// Runs the parse with amounts read in the given format, e.g. an import profile's, rather than the context's.
+    /// Issues it reports are collected into this context.
+    pub fn with_amounts_in<R>(
+        &self,
+        number_format: Option<NumberFormat>,
+        parse: impl FnOnce(&ParseContext) -> R,
+    ) -> R {
+        let Some(number_format) = number_format else {
+            return parse(self);
+        };
+        let fork = Self {
+            number_format: Some(number_format),
+            ..self.fork()
+        };
+        let result = parse(&fork);
+        let mut collected = self.diagnostics.borrow_mut();
+        for diagnostic in fork.diagnostics.into_inner().iter() {
+            collected.push(diagnostic.clone());
+        }
+        result
+    }
+
+    /// Parses the items, e.g. files, on up to one thread per core, keeping the results in the items' order. Each
+    /// thread reports issues to its own fork of the context, and their warnings are collected back into this one in
+    /// the items' order, as if they'd been parsed one by one.
+    pub fn parse_parallel<T: Send, R: Send>(
+        &self,
+        items: Vec<T>,
+        parse: impl Fn(T, &ParseContext) -> R + Sync,
+    ) -> Vec<R> {
+        let threads = std::thread::available_parallelism()
+            .map_or(1, usize::from)
+            .min(items.len());
+        if threads <= 1 {
+            return items.into_iter().map(|item| parse(item, self)).collect();
+        }
+
+        let chunk_size = items.len().div_ceil(threads);
+        let mut items = items.into_iter().peekable();
+        let mut chunks: Vec<Vec<T>> = Vec::new();
+        while items.peek().is_some() {
+            chunks.push(items.by_ref().take(chunk_size).collect());
+        }
+
+        let parse = &parse;
+        let parsed: Vec<(Vec<R>, Diagnostics)> = std::thread::scope(|scope| {
+            let handles: Vec<_> = chunks
+                .into_iter()
+                .map(|chunk| {
+                    let fork = self.fork();
+                    scope.spawn(move || {
+                        let results = chunk.into_iter().map(|item| parse(item, &fork)).collect();
+                        (results, fork.diagnostics.into_inner())
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect()
+        });
+
+        let mut collected = self.diagnostics.borrow_mut();
+        parsed
+            .into_iter()
+            .flat_map(|(results, diagnostics)| {
+                for diagnostic in diagnostics.iter() {
+                    collected.push(diagnostic.clone());
+                }
+                results
+            })
+            .collect()
+    }
+
+    /// Reports a problem with the data: an error in strict mode, a warning otherwise
+    pub fn issue(&self, message: impl Into<String>) -> Result<()> {
+        self.issue_diagnostic(Diagnostic::warning(codes::INVALID_DATA, message))
+    }
+
+    /// Reports a problem with its own code and location, e.g. `unknown-key` in `data.yml`
+    pub fn issue_at(
+        &self,
+        code: &'static str,
+        location: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Result<()> {
+        self.issue_diagnostic(Diagnostic::warning(code, message).at(location))
+    }
+
+    fn issue_diagnostic(&self, diagnostic: Diagnostic) -> Result<()> {
+        match self.mode {
+            ParseMode::Strict => bail!(diagnostic.message),
+            ParseMode::Lenient => {
+                self.diagnostics.borrow_mut().push(diagnostic);
+                Ok(())
+            }
+        }
+    }
+
+    /// Parses a statement date using the context's date order. Unparseable and ambiguous dates are reported as
+    /// issues naming `location` (e.g. the file and line), and give `None` when the context is lenient.
+    pub fn parse_date(&self, value: &str, location: &str) -> Result<Option<NaiveDate>> {
+        match dates::parse_date(value, self.date_order) {
+            Ok(date) => Ok(Some(date)),
+            Err(err) => {
+                self.issue_at(
+                    codes::INVALID_DATE,
+                    location,
+                    format!("{:?} in {}: {}", value, location, err),
+                )?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// The format to read a file's amounts in: the context's, or else the one the amounts themselves decide, if any
+    pub fn number_format_for<'a>(
+        &self,
+        amounts: impl IntoIterator<Item = &'a str>,
+    ) -> Option<NumberFormat> {
+        self.number_format.or_else(|| NumberFormat::detect(amounts))
+    }
+
+    /// Parses a statement amount in the format, from `number_format_for`. Ambiguous amounts are reported as issues
+    /// naming `location` and read with a `.` decimal separator; unrecognized ones fail.
+    pub fn parse_amount(
+        &self,
+        value: &str,
+        format: Option<NumberFormat>,
+        location: &str,
+    ) -> Result<Decimal> {
+        match numbers::parse_amount(value, format) {
+            Ok(amount) => Ok(amount),
+            Err(NumberError::Ambiguous { point, comma }) => {
+                self.issue_at(
+                    codes::AMBIGUOUS_AMOUNT,
+                    location,
+                    format!(
+                        "{:?} in {}: {}",
+                        value,
+                        location,
+                        NumberError::Ambiguous { point, comma }
+                    ),
+                )?;
+                Ok(point)
+            }
+            Err(NumberError::Unrecognized) => bail!("Invalid amount {:?} in {}", value, location),
+        }
+    }
+
+    /// Returns the messages of the warnings collected so far
+    pub fn warnings(&self) -> Vec<String> {
+        self.diagnostics
+            .borrow()
+            .iter()
+            .map(|diagnostic| diagnostic.message.clone())
+            .collect()
+    }
+
+    /// Returns the diagnostics collected so far, with their codes and locations
+    pub fn diagnostics(&self) -> Diagnostics {
+        self.diagnostics.borrow().clone()
+    }
+
+    /// Deserializes a YAML value, reporting any keys that don't map onto `T`. Misspelled keys come with the known key
+    /// they're closest to, found from `T`'s JSON Schema, since a typo like `closing_dtae` otherwise loses the value.
+    pub fn deserialize<T: DeserializeOwned + JsonSchema>(
+        &self,
+        value: serde_yaml::Value,
+        source: &str,
+    ) -> Result<T> {
+        let mut unknown_keys = Vec::new();
+        let parsed: T = serde_ignored::deserialize(value, |path| {
+            let (parent, key) = suggest::split_path(&path);
+            unknown_keys.push((path.to_string(), parent, key));
+        })
+        .with_context(|| format!("Invalid YAML in {}", source))?;
+        if unknown_keys.is_empty() {
+            return Ok(parsed);
+        }
+
+        let schema = schemars::schema_for!(T);
+        for (path, parent, key) in unknown_keys {
+            let suggestion =
+                key.and_then(|key| suggest::did_you_mean(schema.as_value(), &parent, &key));
+            match suggestion {
+                Some(known) => self.issue_at(
+                    codes::UNKNOWN_KEY,
+                    source,
+                    format!(
+                        "Unknown key `{}` in {}; did you mean `{}`?",
+                        path, source, known
+                    ),
+                )?,
+                None => self.issue_at(
+                    codes::UNKNOWN_KEY,
+                    source,
+                    format!("Unknown key `{}` in {}", path, source),
+                )?,
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Fails if any of the optional `keys` are missing from a YAML mapping in strict mode.
+    ///
+    /// Omitting optional keys is normal, so lenient mode doesn't warn about it.
+    pub fn check_optional_keys(
+        &self,
+        value: &serde_yaml::Value,
+        keys: &[&str],
+        source: &str,
+    ) -> Result<()> {
+        if self.mode == ParseMode::Lenient {
+            return Ok(());
+        }
+
+        for key in keys {
+            if value.get(*key).is_none() {
+                self.issue_at(
+                    codes::OMITTED_KEY,
+                    source,
+                    format!("Optional key `{}` omitted in {}", key, source),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, JsonSchema)]
+    struct Example {
+        name: String,
+        #[serde(default)]
+        tags: Vec<String>,
+    }
+
+    fn parse_value(yaml: &str) -> serde_yaml::Value {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_lenient_collects_warnings() -> Result<()> {
+        let context = ParseContext::default();
+
+        let example: Example =
+            context.deserialize(parse_value("name: a\nnmae: b\n"), "example.yml")?;
+        assert_eq!(example.name, "a");
+        assert!(example.tags.is_empty());
+
+        assert_eq!(
+            context.warnings(),
+            vec!["Unknown key `nmae` in example.yml; did you mean `name`?".to_string()]
+        );
+        let diagnostics = context.diagnostics();
+        let diagnostic = diagnostics.iter().next().unwrap();
+        assert_eq!(diagnostic.code, codes::UNKNOWN_KEY);
+        assert_eq!(diagnostic.location.as_deref(), Some("example.yml"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict_rejects_unknown_keys() {
+        let context = ParseContext::strict();
+
+        let result: Result<Example> =
+            context.deserialize(parse_value("name: a\nnmae: b\n"), "example.yml");
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown key `nmae`"));
+    }
+
+    #[test]
+    fn test_parse_parallel() -> Result<()> {
+        let context = ParseContext::default();
+        let values: Vec<String> = (1..=40)
+            .map(|day| match day % 10 {
+                0 => format!("not a date {}", day),
+                _ => format!("2023-01-{:02}", day % 28 + 1),
+            })
+            .collect();
+
+        let parsed = context.parse_parallel(values.clone(), |value, context| {
+            context.parse_date(&value, &value)
+        });
+        assert_eq!(parsed.len(), 40);
+        assert_eq!(
+            parsed[0].as_ref().unwrap(),
+            &NaiveDate::from_ymd_opt(2023, 1, 2)
+        );
+        assert_eq!(parsed[9].as_ref().unwrap(), &None);
+
+        // Warnings come back in the values' order
+        let warnings = context.warnings();
+        assert_eq!(warnings.len(), 4);
+        for (warning, day) in warnings.iter().zip([10, 20, 30, 40]) {
+            assert!(warning.starts_with(&format!("\"not a date {}\"", day)));
+        }
+
+        let strict = ParseContext::strict();
+        let parsed =
+            strict.parse_parallel(values, |value, context| context.parse_date(&value, &value));
+        assert!(parsed[9].is_err());
+        assert!(parsed[8].is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_date() -> Result<()> {
+        let lenient = ParseContext::default();
+        assert!(lenient
+            .parse_date("03/04/2023", "jan.csv line 2")?
+            .is_none());
+        assert!(
+            lenient.warnings()[0].starts_with("\"03/04/2023\" in jan.csv line 2: ambiguous date")
+        );
+
+        let day_first = ParseContext::default().with_date_order(Some(DateOrder::DayFirst));
+        assert_eq!(
+            day_first.parse_date("03/04/2023", "jan.csv line 2")?,
+            NaiveDate::from_ymd_opt(2023, 4, 3)
+        );
+
+        assert!(ParseContext::strict()
+            .parse_date("03/04/2023", "jan.csv line 2")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_optional_keys() -> Result<()> {
+        let value = parse_value("name: a\n");
+
+        let lenient = ParseContext::default();
+        lenient.check_optional_keys(&value, &["tags"], "example.yml")?;
+        assert!(lenient.warnings().is_empty());
+
+        let strict = ParseContext::strict();
+        assert!(strict
+            .check_optional_keys(&value, &["tags"], "example.yml")
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+use rust_decimal::Decimal;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// How to read amounts like `1.234,56`, where `.` and `,` could each be the decimal or the thousands separator
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq)]
+pub struct NumberFormat {
+    pub decimal_separator: char,
+    /// Separator between groups of three digits, e.g. `.` in `1.234,56`. Spaces and apostrophes between groups are
+    /// always allowed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thousands_separator: Option<char>,
+}
+
+impl NumberFormat {
+    /// `1,234.56`, as used in the US and UK
+    pub const POINT: Self = Self {
+        decimal_separator: '.',
+        thousands_separator: Some(','),
+    };
+    /// `1.234,56`, as used in most of continental Europe
+    pub const COMMA: Self = Self {
+        decimal_separator: ',',
+        thousands_separator: Some('.'),
+    };
+
+    /// The format with the given separators. The one not given is the other of `.` and `,`. Fails unless the decimal
+    /// separator is `.` or `,` and differs from the thousands separator.
+    pub fn new(
+        decimal_separator: Option<char>,
+        thousands_separator: Option<char>,
+    ) -> Result<Self, String> {
+        let decimal_separator = match (decimal_separator, thousands_separator) {
+            (Some(decimal), _) => decimal,
+            (None, Some('.')) => ',',
+            (None, _) => '.',
+        };
+        if !matches!(decimal_separator, '.' | ',') {
+            return Err(format!(
+                "decimal separator {:?} isn't `.` or `,`",
+                decimal_separator
+            ));
+        }
+        let thousands_separator = thousands_separator.or(match decimal_separator {
+            '.' => Some(','),
+            _ => Some('.'),
+        });
+        if thousands_separator == Some(decimal_separator) {
+            return Err(format!(
+                "{:?} can't be both the decimal and the thousands separator",
+                decimal_separator
+            ));
+        }
+        Ok(Self {
+            decimal_separator,
+            thousands_separator,
+        })
+    }
+
+    /// The format the amounts are written in, when one of them can only be read one way and none contradicts it,
+    /// e.g. `12,50` decides on a `,` decimal separator for a file that also has `1.234`
+    pub fn detect<'a>(values: impl IntoIterator<Item = &'a str>) -> Option<Self> {
+        let mut detected = None;
+        for value in values {
+            let decided = match (read(value, &Self::POINT), read(value, &Self::COMMA)) {
+                (Some(point), Some(comma)) if point != comma => continue,
+                (Some(_), Some(_)) | (None, None) => continue,
+                (Some(_), None) => Self::POINT,
+                (None, Some(_)) => Self::COMMA,
+            };
+            match detected {
+                Some(format) if format != decided => return None,
+                _ => detected = Some(decided),
+            }
+        }
+        detected
+    }
+}
+
+impl fmt::Display for NumberFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` decimal separator", self.decimal_separator)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NumberError {
+    /// Not a number in the format, or in either format when none was given
+    Unrecognized,
+    /// Valid with either separator as the decimal one, and no format was given to decide
+    Ambiguous { point: Decimal, comma: Decimal },
+}
+
+impl fmt::Display for NumberError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unrecognized => write!(f, "unrecognized amount"),
+            Self::Ambiguous { point, comma } => write!(
+                f,
+                "ambiguous amount: {} if `.` is the decimal separator, {} if `,` is; set decimal_separator in \
+                 the account's import profile to decide",
+                point, comma
+            ),
+        }
+    }
+}
+
+/// Parses a statement amount.
+///
+/// Amounts are read in the given format. Without one, an amount is read whichever way it can be, and is ambiguous
+/// when it can be read both ways with different results, like `1.234` or `1,234`. Spaces and apostrophes between
+/// groups of digits are skipped, as in `1 234,56` or `1'234.56`.
+pub fn parse_amount(value: &str, format: Option<NumberFormat>) -> Result<Decimal, NumberError> {
+    if let Some(format) = format {
+        return read(value, &format).ok_or(NumberError::Unrecognized);
+    }
+    match (
+        read(value, &NumberFormat::POINT),
+        read(value, &NumberFormat::COMMA),
+    ) {
+        (Some(point), Some(comma)) if point != comma => {
+            Err(NumberError::Ambiguous { point, comma })
+        }
+        (Some(amount), _) | (None, Some(amount)) => Ok(amount),
+        (None, None) => Err(NumberError::Unrecognized),
+    }
+}
+
+/// Reads an amount in the format, checking any thousands separators split the whole number into groups of three
+fn read(value: &str, format: &NumberFormat) -> Option<Decimal> {
+    let value = value.trim();
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let (whole, fraction) = match digits.split_once(format.decimal_separator) {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (digits, None),
+    };
+
+    let is_separator =
+        |c: char| Some(c) == format.thousands_separator || c.is_whitespace() || c == '\'';
+    let groups: Vec<&str> = whole.split(is_separator).collect();
+    let grouped = groups.len() > 1;
+    let valid_groups = groups.iter().enumerate().all(|(index, group)| {
+        let digits = group.chars().all(|c| c.is_ascii_digit());
+        match (grouped, index) {
+            (false, _) => digits && !group.is_empty(),
+            // Grouped numbers don't start with a zero, so `0,123` can only be a fraction
+            (true, 0) => digits && (1..=3).contains(&group.len()) && !group.starts_with('0'),
+            (true, _) => digits && group.len() == 3,
+        }
+    });
+    let valid_fraction = fraction.is_none_or(|fraction| {
+        !fraction.is_empty() && fraction.chars().all(|c| c.is_ascii_digit())
+    });
+    if !valid_groups || !valid_fraction {
+        return None;
+    }
+
+    let number = match fraction {
+        Some(fraction) => format!("{}.{}", groups.concat(), fraction),
+        None => groups.concat(),
+    };
+    let amount = Decimal::from_str(&number).ok()?;
+    Some(if negative { -amount } else { amount })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_unambiguous_amounts() {
+        assert_eq!(parse_amount("1234", None), Ok(dec!(1234)));
+        assert_eq!(parse_amount("-10.5", None), Ok(dec!(-10.5)));
+        assert_eq!(parse_amount("1.234,56", None), Ok(dec!(1234.56)));
+        assert_eq!(parse_amount("1,234.56", None), Ok(dec!(1234.56)));
+        assert_eq!(parse_amount("12,50", None), Ok(dec!(12.50)));
+        assert_eq!(parse_amount("1.234.567", None), Ok(dec!(1234567)));
+        assert_eq!(parse_amount("1 234,56", None), Ok(dec!(1234.56)));
+        assert_eq!(parse_amount("1'234.56", None), Ok(dec!(1234.56)));
+        assert_eq!(parse_amount("0,123", None), Ok(dec!(0.123)));
+        assert_eq!(parse_amount("abc", None), Err(NumberError::Unrecognized));
+        assert_eq!(parse_amount("1.23.4", None), Err(NumberError::Unrecognized));
+    }
+
+    #[test]
+    fn test_ambiguous_amounts() {
+        assert_eq!(
+            parse_amount("1.234", None),
+            Err(NumberError::Ambiguous {
+                point: dec!(1.234),
+                comma: dec!(1234)
+            })
+        );
+        assert_eq!(
+            parse_amount("1.234", Some(NumberFormat::COMMA)),
+            Ok(dec!(1234))
+        );
+        assert_eq!(
+            parse_amount("1,234", Some(NumberFormat::POINT)),
+            Ok(dec!(1234))
+        );
+        // The wrong format is an error, not a silently different amount
+        assert_eq!(
+            parse_amount("1,234.56", Some(NumberFormat::COMMA)),
+            Err(NumberError::Unrecognized)
+        );
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            NumberFormat::detect(["1.234", "12,50"]),
+            Some(NumberFormat::COMMA)
+        );
+        assert_eq!(
+            NumberFormat::detect(["1,234", "1,234.50"]),
+            Some(NumberFormat::POINT)
+        );
+        assert_eq!(NumberFormat::detect(["1.234", "100"]), None);
+        // Contradictory amounts decide nothing
+        assert_eq!(NumberFormat::detect(["12,50", "10.5"]), None);
+    }
+
+    #[test]
+    fn test_new() {
+        assert_eq!(NumberFormat::new(Some(','), None), Ok(NumberFormat::COMMA));
+        assert_eq!(NumberFormat::new(None, Some('.')), Ok(NumberFormat::COMMA));
+        assert_eq!(
+            NumberFormat::new(Some(','), Some(' ')),
+            Ok(NumberFormat {
+                decimal_separator: ',',
+                thousands_separator: Some(' ')
+            })
+        );
+        assert!(NumberFormat::new(Some(';'), None).is_err());
+        assert!(NumberFormat::new(Some('.'), Some('.')).is_err());
+    }
+}
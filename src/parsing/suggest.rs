@@ -0,0 +1,166 @@
+use serde_json::Value;
+
+/// One step from a parent value to a child: a mapping key or a sequence element
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Step {
+    Key(String),
+    Index,
+}
+
+/// Flattens the path `serde_ignored` gives for an unknown key into the steps to its parent and the key itself.
+/// Options and newtypes don't appear in the file, so they're skipped.
+pub(super) fn split_path(path: &serde_ignored::Path) -> (Vec<Step>, Option<String>) {
+    fn steps(path: &serde_ignored::Path, out: &mut Vec<Step>) {
+        match path {
+            serde_ignored::Path::Root => {}
+            serde_ignored::Path::Seq { parent, .. } => {
+                steps(parent, out);
+                out.push(Step::Index);
+            }
+            serde_ignored::Path::Map { parent, key } => {
+                steps(parent, out);
+                out.push(Step::Key(key.clone()));
+            }
+            serde_ignored::Path::Some { parent }
+            | serde_ignored::Path::NewtypeStruct { parent }
+            | serde_ignored::Path::NewtypeVariant { parent } => steps(parent, out),
+        }
+    }
+
+    let mut out = Vec::new();
+    steps(path, &mut out);
+    match out.pop() {
+        Some(Step::Key(key)) => (out, Some(key)),
+        Some(step) => {
+            out.push(step);
+            (out, None)
+        }
+        None => (out, None),
+    }
+}
+
+/// The known key closest to a misspelled one, found by walking the JSON Schema of the type being loaded down to the
+/// key's parent. Only keys a couple of edits away are suggested, so unrelated keys aren't offered as fixes.
+pub(super) fn did_you_mean(schema: &Value, parent: &[Step], key: &str) -> Option<String> {
+    let mut nodes = vec![schema];
+    for step in parent {
+        nodes = nodes
+            .into_iter()
+            .flat_map(|node| variants(schema, node))
+            .filter_map(|node| match step {
+                Step::Key(key) => node.get("properties").and_then(|p| p.get(key)),
+                Step::Index => node.get("items"),
+            })
+            .collect();
+    }
+
+    let threshold = (key.chars().count() / 3).max(1);
+    nodes
+        .into_iter()
+        .flat_map(|node| variants(schema, node))
+        .filter_map(|node| node.get("properties").and_then(Value::as_object))
+        .flat_map(|properties| properties.keys())
+        .map(|known| {
+            (
+                edit_distance(&key.to_lowercase(), &known.to_lowercase()),
+                known,
+            )
+        })
+        .filter(|(distance, _)| *distance <= threshold)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known)| known.clone())
+}
+
+/// A schema node with its `$ref` resolved and its `anyOf`/`oneOf`/`allOf` alternatives expanded, since options,
+/// enums and flattened structs all put their properties in those
+fn variants<'a>(root: &'a Value, node: &'a Value) -> Vec<&'a Value> {
+    let node = match node
+        .get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|reference| reference.strip_prefix("#/$defs/"))
+    {
+        Some(name) => match root.get("$defs").and_then(|defs| defs.get(name)) {
+            Some(definition) => definition,
+            None => return Vec::new(),
+        },
+        None => node,
+    };
+
+    let mut found = vec![node];
+    for combinator in ["anyOf", "oneOf", "allOf"] {
+        if let Some(alternatives) = node.get(combinator).and_then(Value::as_array) {
+            for alternative in alternatives {
+                found.extend(variants(root, alternative));
+            }
+        }
+    }
+    found
+}
+
+/// Edit distance between two strings, counting a swap of adjacent characters as one edit since that's the commonest
+/// typo (optimal string alignment distance)
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::UserData;
+
+    #[test]
+    fn test_did_you_mean() {
+        let schema = schemars::schema_for!(UserData);
+        let schema = schema.as_value();
+        let account = [Step::Key("accounts".to_string()), Step::Index];
+
+        assert_eq!(
+            did_you_mean(schema, &account, "closing_dtae").as_deref(),
+            Some("closing_date")
+        );
+        assert_eq!(
+            did_you_mean(schema, &[], "acounts").as_deref(),
+            Some("accounts")
+        );
+        // Through an untagged enum's structured variant
+        let address = [
+            Step::Key("providers".to_string()),
+            Step::Index,
+            Step::Key("address".to_string()),
+        ];
+        assert_eq!(
+            did_you_mean(schema, &address, "postal_cdoe").as_deref(),
+            Some("postal_code")
+        );
+        assert_eq!(did_you_mean(schema, &account, "favourite_colour"), None);
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("closing_dtae", "closing_date"), 1);
+        assert_eq!(edit_distance("balnces", "balances"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+}
@@ -0,0 +1,42 @@
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+/// Progress bars for the slower phases, drawn on stderr. Nothing is drawn when hidden or when stderr isn't a
+/// terminal, so output can still be piped and logged cleanly.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    bars: MultiProgress,
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Self {
+            bars: MultiProgress::with_draw_target(ProgressDrawTarget::stderr()),
+        }
+    }
+
+    pub fn hidden() -> Self {
+        Self {
+            bars: MultiProgress::with_draw_target(ProgressDrawTarget::hidden()),
+        }
+    }
+
+    /// Adds a bar counting `len` items, labelled with the phase it tracks. The bar's message names the file or
+    /// account being worked on.
+    pub fn bar(&self, len: usize, label: &str) -> ProgressBar {
+        let bar = self.bars.add(ProgressBar::new(len as u64));
+        bar.set_style(
+            ProgressStyle::with_template("{prefix:>8} [{bar:30}] {pos}/{len} {wide_msg}")
+                .expect("progress template is valid")
+                .progress_chars("=> "),
+        );
+        bar.set_prefix(label.to_string());
+        bar
+    }
+}
+
+impl Default for Progress {
+    /// Hidden, so library callers only see bars when they ask for them
+    fn default() -> Self {
+        Self::hidden()
+    }
+}
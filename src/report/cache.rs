@@ -0,0 +1,260 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::max_balance::{Maximum, PolicyMaximum};
+use crate::data::{Account, UserData};
+use crate::report_context::ReportContext;
+use crate::statements::StatementImport;
+
+/// An account's computed maximum for a year, with the notes describing how it was derived
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct CachedMaximum {
+    pub maximum: Option<Maximum>,
+    /// The maximum of the values converted at daily rates, when the account's currency has a price series in USD
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub converted: Option<PolicyMaximum>,
+    pub notes: Vec<String>,
+    /// Dates of the values estimated by interpolation rather than recorded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub estimated: Vec<NaiveDate>,
+}
+
+/// Caches each account's computed maximum under `.fbar_prep/cache` in the data directory, so re-running a report
+/// after editing one account only recomputes that account.
+///
+/// Entries are keyed by a hash of the account, its imported statements, the year, and everything else the maximum
+/// depends on (rates, prices, settings and the tool's version), so any change to those misses the cache. Entries
+/// live in a directory per bundled facts hash, and directories for other facts are removed when the cache is opened.
+/// Entries are written to a temporary file and renamed into place, so concurrent runs never read a partial entry.
+pub struct ReportCache {
+    dir: PathBuf,
+}
+
+/// Distinguishes temporary files written by threads of the same process
+static WRITES: AtomicUsize = AtomicUsize::new(0);
+
+impl ReportCache {
+    /// Opens the cache for the bundled facts with the given hash, clearing out entries computed with other facts
+    pub fn open(base_path: &Path, facts_hash: &str) -> Self {
+        let root = base_path.join(".fbar_prep").join("cache");
+        let dir = root.join(facts_hash);
+
+        if let Ok(entries) = std::fs::read_dir(&root) {
+            for entry in entries.flatten() {
+                if entry.path() != dir {
+                    // Another run may be clearing the same directory; whichever finishes first wins
+                    let _ = std::fs::remove_dir_all(entry.path());
+                }
+            }
+        }
+
+        Self { dir }
+    }
+
+    /// Key for an account's maximum in a year. `shared` is the hash of the inputs common to every account, from
+    /// `shared_inputs_hash`.
+    pub fn key(account: &Account, year: i32, shared: &str) -> Result<String> {
+        #[derive(Serialize)]
+        struct Inputs<'a> {
+            shared: &'a str,
+            year: i32,
+            account: &'a Account,
+            imports: &'a [StatementImport],
+        }
+
+        let serialized = serde_yaml::to_string(&Inputs {
+            shared,
+            year,
+            account,
+            imports: &account.imports,
+        })?;
+        Ok(hex::encode(Sha256::digest(serialized.as_bytes())))
+    }
+
+    /// Hash of the inputs besides the account itself that an account's maximum depends on: the rates the context
+    /// consults, the data directory's settings, rate sets and prices, and the tool's version
+    pub fn shared_inputs_hash(user_data: &UserData, context: &ReportContext) -> Result<String> {
+        let settings = UserData {
+            accounts: Vec::new(),
+            archived_accounts: Vec::new(),
+            ..user_data.clone()
+        };
+        let serialized = format!(
+            "{}:{}:{}",
+            env!("CARGO_PKG_VERSION"),
+            context.inputs_hash()?,
+            settings.content_hash()?
+        );
+        Ok(hex::encode(Sha256::digest(serialized.as_bytes())))
+    }
+
+    /// The cached maximum for a key. Unreadable entries are treated as missing and recomputed.
+    pub fn get(&self, key: &str) -> Option<CachedMaximum> {
+        let contents = std::fs::read_to_string(self.entry_path(key)).ok()?;
+        serde_yaml::from_str(&contents).ok()
+    }
+
+    pub fn put(&self, key: &str, maximum: &CachedMaximum) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let temporary = self.dir.join(format!(
+            ".{}.{}.{}.tmp",
+            key,
+            std::process::id(),
+            WRITES.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&temporary, serde_yaml::to_string(maximum)?)?;
+        std::fs::rename(&temporary, self.entry_path(key))?;
+        Ok(())
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.yml", key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::BalanceRecord;
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use crate::money::Money;
+    use crate::report::Report;
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+    use tempfile::TempDir;
+
+    fn create_test_user_data() -> UserData {
+        let account = |handle: &str| Account {
+            handle: handle.to_string(),
+            provider: "example_bank".to_string(),
+            currency_code: "EUR".to_string(),
+            balances: vec![BalanceRecord {
+                date: NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(),
+                amount: dec!(100),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        UserData {
+            accounts: vec![account("example_checking"), account("example_savings")],
+            ..Default::default()
+        }
+    }
+
+    fn create_test_facts(rate: f64) -> Facts {
+        Facts {
+            years: vec![AnnualFact {
+                year: 2023,
+                exchange_rates: vec![ExchangeRate::new("EUR".to_string(), rate).unwrap()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn create_test_context(dir: &Path, rate: f64) -> Result<ReportContext> {
+        let facts = create_test_facts(rate);
+        let cache = ReportCache::open(dir, &facts.content_hash()?);
+        Ok(ReportContext::new(facts, None).with_cache(cache))
+    }
+
+    fn entries(dir: &Path) -> Result<usize> {
+        let mut count = 0;
+        for facts_dir in std::fs::read_dir(dir.join(".fbar_prep").join("cache"))? {
+            count += std::fs::read_dir(facts_dir?.path())?.count();
+        }
+        Ok(count)
+    }
+
+    #[test]
+    fn test_cached_report_matches() -> Result<()> {
+        let dir = TempDir::new()?;
+        let mut user_data = create_test_user_data();
+        let uncached = Report::generate(
+            2023,
+            &user_data,
+            &ReportContext::new(create_test_facts(0.80), None),
+        )?;
+
+        let context = create_test_context(dir.path(), 0.80)?;
+        assert_eq!(Report::generate(2023, &user_data, &context)?, uncached);
+        assert_eq!(entries(dir.path())?, 2);
+        // The second run is served from the cache
+        let shared = ReportCache::shared_inputs_hash(&user_data, &context)?;
+        let key = ReportCache::key(&user_data.accounts[1], 2023, &shared)?;
+        context.cache().unwrap().put(
+            &key,
+            &CachedMaximum {
+                maximum: Some(Maximum {
+                    value: Money::new(dec!(42), "eur"),
+                    date: NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(),
+                }),
+                ..Default::default()
+            },
+        )?;
+        let report = Report::generate(2023, &user_data, &context)?;
+        assert_eq!(report.accounts[1].max_balance.amount, dec!(42));
+        assert_eq!(entries(dir.path())?, 2);
+        context.cache().unwrap().put(
+            &key,
+            &CachedMaximum {
+                maximum: Some(Maximum {
+                    value: uncached.accounts[1].max_balance.clone(),
+                    date: uncached.accounts[1].max_balance_date.unwrap(),
+                }),
+                ..Default::default()
+            },
+        )?;
+
+        // Editing one account only adds an entry for that account
+        user_data.accounts[0].balances[0].amount = dec!(250);
+        let report = Report::generate(2023, &user_data, &context)?;
+        assert_eq!(report.accounts[0].max_balance.amount, dec!(250));
+        assert_eq!(report.accounts[1], uncached.accounts[1]);
+        assert_eq!(entries(dir.path())?, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_facts_change_clears_cache() -> Result<()> {
+        let dir = TempDir::new()?;
+        let user_data = create_test_user_data();
+
+        Report::generate(2023, &user_data, &create_test_context(dir.path(), 0.80)?)?;
+        assert_eq!(entries(dir.path())?, 2);
+
+        let context = create_test_context(dir.path(), 0.90)?;
+        assert_eq!(entries(dir.path())?, 0);
+        Report::generate(2023, &user_data, &context)?;
+        assert_eq!(entries(dir.path())?, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unreadable_entry_is_a_miss() -> Result<()> {
+        let dir = TempDir::new()?;
+        let cache = ReportCache::open(dir.path(), "facts");
+        let maximum = CachedMaximum {
+            maximum: Some(Maximum {
+                value: Money::new(dec!(100), "eur"),
+                date: NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(),
+            }),
+            ..Default::default()
+        };
+
+        cache.put("key", &maximum)?;
+        assert_eq!(cache.get("key"), Some(maximum));
+
+        std::fs::write(cache.entry_path("key"), "not: [valid")?;
+        assert_eq!(cache.get("key"), None);
+        assert_eq!(cache.get("missing"), None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,403 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::{Report, SnapshotStore};
+use crate::data::UserData;
+use crate::money::Money;
+
+/// An account reported for the previous year that has no figures this year, although it wasn't closed before the
+/// year began. Dropping an account between years is an easy mistake to make and a costly one to file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DroppedAccount {
+    pub handle: String,
+    pub provider: String,
+    pub prior_year: i32,
+    pub prior_max_balance_usd: Money,
+    /// Whether the account is still in `data.yml`, rather than removed from it
+    pub in_data: bool,
+}
+
+impl fmt::Display for DroppedAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Account {} at {} was reported for {} (maximum {}) but is missing from {}: ",
+            self.handle,
+            self.provider,
+            self.prior_year,
+            self.prior_max_balance_usd.format(),
+            self.prior_year + 1
+        )?;
+        if self.in_data {
+            write!(
+                f,
+                "add its balances for {}, or set its closing_date if it was closed",
+                self.prior_year + 1
+            )
+        } else {
+            write!(f, "it has been removed from data.yml")
+        }
+    }
+}
+
+/// Accounts in the previous year's closed snapshot that are missing from the report, other than those closed before
+/// the report's year or listed in its `excluded` appendix. Nothing is flagged when the previous year hasn't been closed.
+pub fn dropped_accounts(
+    snapshots: &SnapshotStore,
+    report: &Report,
+    user_data: &UserData,
+) -> Result<Vec<DroppedAccount>> {
+    Ok(match snapshots.load(report.year - 1)? {
+        Some(snapshot) => compare(&snapshot.report, report, user_data),
+        None => Vec::new(),
+    })
+}
+
+fn compare(prior: &Report, report: &Report, user_data: &UserData) -> Vec<DroppedAccount> {
+    let year_start = NaiveDate::from_ymd_opt(report.year, 1, 1);
+    let tags = report
+        .metadata
+        .as_ref()
+        .map(|metadata| metadata.tags.as_slice())
+        .unwrap_or_default();
+
+    prior
+        .accounts
+        .iter()
+        .filter(|prior_account| {
+            let handle = &prior_account.handle;
+            !report
+                .accounts
+                .iter()
+                .any(|account| &account.handle == handle)
+                && !report
+                    .excluded
+                    .iter()
+                    .any(|account| &account.handle == handle)
+        })
+        .filter_map(|prior_account| {
+            let account = user_data
+                .accounts
+                .iter()
+                .find(|account| account.handle == prior_account.handle);
+            match account {
+                Some(account) => {
+                    let closed = account
+                        .closing_date
+                        .is_some_and(|closed| Some(closed) < year_start);
+                    let tagged_out =
+                        !tags.is_empty() && !tags.iter().any(|tag| account.has_tag(tag));
+                    if closed || account.exclude_from_fbar || tagged_out {
+                        return None;
+                    }
+                }
+                // A report limited to tags can't tell whether a removed account had them
+                None if !tags.is_empty() => return None,
+                None => {}
+            }
+
+            Some(DroppedAccount {
+                handle: prior_account.handle.clone(),
+                provider: prior_account.provider.clone(),
+                prior_year: prior.year,
+                prior_max_balance_usd: prior_account.max_balance_usd.clone(),
+                in_data: account.is_some(),
+            })
+        })
+        .collect()
+}
+
+/// Thresholds for flagging accounts whose maximum moved implausibly from the previous year's
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+#[serde(default)]
+pub struct ChangeCheckConfig {
+    /// How far an account's maximum may move from the previous year's, as a percentage of the smaller of the two,
+    /// so that a fall to a hundredth is flagged like a hundredfold rise
+    pub max_yearly_change: f64,
+    /// Accounts whose maxima are both below this many USD aren't checked for their change
+    pub min_balance_usd: Decimal,
+    /// USD amounts an account's maximum is flagged for newly reaching, e.g. the $10,000 FBAR threshold
+    pub thresholds: Vec<Decimal>,
+}
+
+impl Default for ChangeCheckConfig {
+    fn default() -> Self {
+        Self {
+            max_yearly_change: 300.0,
+            min_balance_usd: Decimal::from(1000),
+            thresholds: vec![Decimal::from(10000), Decimal::from(50000)],
+        }
+    }
+}
+
+impl ChangeCheckConfig {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// An account whose maximum changed from the previous year's by enough to be worth a second look, as an import error
+/// like a misread decimal separator inflates a balance a hundredfold
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignificantChange {
+    pub handle: String,
+    pub prior_year: i32,
+    pub prior_max_balance_usd: Money,
+    pub max_balance_usd: Money,
+    /// The threshold newly reached, when the change crossed one rather than exceeding `max_yearly_change`
+    pub threshold: Option<Money>,
+}
+
+impl fmt::Display for SignificantChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Account {}'s maximum went from {} in {} to {} in {}, ",
+            self.handle,
+            self.prior_max_balance_usd.format(),
+            self.prior_year,
+            self.max_balance_usd.format(),
+            self.prior_year + 1
+        )?;
+        match &self.threshold {
+            Some(threshold) => write!(f, "reaching {} for the first time", threshold.format())?,
+            None => write!(
+                f,
+                "{:.0}% {}",
+                change_percent(&self.prior_max_balance_usd, &self.max_balance_usd)
+                    .unwrap_or_default(),
+                match self.max_balance_usd.amount > self.prior_max_balance_usd.amount {
+                    true => "more",
+                    false => "less",
+                }
+            )?,
+        }
+        write!(f, "; check its balances were imported correctly")
+    }
+}
+
+/// Accounts whose maximum moved from the previous year's closed snapshot by more than the configured change, or newly
+/// reached one of the thresholds. Nothing is flagged when the previous year hasn't been closed.
+pub fn significant_changes(
+    snapshots: &SnapshotStore,
+    report: &Report,
+    config: &ChangeCheckConfig,
+) -> Result<Vec<SignificantChange>> {
+    Ok(match snapshots.load(report.year - 1)? {
+        Some(snapshot) => compare_maxima(&snapshot.report, report, config),
+        None => Vec::new(),
+    })
+}
+
+fn compare_maxima(
+    prior: &Report,
+    report: &Report,
+    config: &ChangeCheckConfig,
+) -> Vec<SignificantChange> {
+    report
+        .accounts
+        .iter()
+        .filter_map(|account| {
+            let prior_account = prior
+                .accounts
+                .iter()
+                .find(|prior_account| prior_account.handle == account.handle)?;
+            let (before, after) = (&prior_account.max_balance_usd, &account.max_balance_usd);
+            let threshold = config
+                .thresholds
+                .iter()
+                .filter(|threshold| before.amount < **threshold && after.amount >= **threshold)
+                .max()
+                .map(|threshold| Money::usd(*threshold));
+            let checked = before.amount.max(after.amount) >= config.min_balance_usd;
+            let changed = checked
+                && change_percent(before, after)
+                    .is_some_and(|percent| percent > config.max_yearly_change);
+            (changed || threshold.is_some()).then(|| SignificantChange {
+                handle: account.handle.clone(),
+                prior_year: prior.year,
+                prior_max_balance_usd: before.clone(),
+                max_balance_usd: after.clone(),
+                threshold: threshold.filter(|_| !changed),
+            })
+        })
+        .collect()
+}
+
+/// The change between two amounts as a percentage of the smaller, unless it's zero
+fn change_percent(before: &Money, after: &Money) -> Option<f64> {
+    let smaller = before.amount.min(after.amount);
+    let difference = (after.amount - before.amount).abs();
+    (smaller > Decimal::ZERO)
+        .then(|| difference / smaller * Decimal::ONE_HUNDRED)
+        .and_then(|percent| percent.to_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Account, BalanceRecord};
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use crate::report_context::ReportContext;
+    use rust_decimal_macros::dec;
+    use tempfile::TempDir;
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    fn create_test_account(handle: &str, balance_dates: &[&str]) -> Account {
+        Account {
+            handle: handle.to_string(),
+            provider: "example_bank".to_string(),
+            currency_code: "EUR".to_string(),
+            balances: balance_dates
+                .iter()
+                .map(|balance_date| BalanceRecord {
+                    date: date(balance_date),
+                    amount: dec!(1000),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn create_test_context() -> ReportContext {
+        let exchange_rates = vec![ExchangeRate::new("EUR".to_string(), 0.8).unwrap()];
+        ReportContext::new(
+            Facts {
+                years: vec![
+                    AnnualFact {
+                        year: 2022,
+                        exchange_rates: exchange_rates.clone(),
+                        ..Default::default()
+                    },
+                    AnnualFact {
+                        year: 2023,
+                        exchange_rates,
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            },
+            None,
+        )
+    }
+
+    #[test]
+    fn test_dropped_accounts() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let snapshots = SnapshotStore::new(temp_dir.path());
+        let context = create_test_context();
+
+        let mut user_data = UserData {
+            accounts: vec![
+                create_test_account("current", &["2022-06-30", "2023-06-30"]),
+                create_test_account("forgotten", &["2022-06-30"]),
+                create_test_account("closed", &["2022-06-30"]),
+                create_test_account("excluded", &["2022-06-30"]),
+                create_test_account("removed", &["2022-06-30"]),
+            ],
+            ..Default::default()
+        };
+        // 2021 wasn't closed, so there's nothing to compare against
+        let report = Report::generate(2022, &user_data, &context)?;
+        assert!(dropped_accounts(&snapshots, &report, &user_data)?.is_empty());
+        snapshots.close_year(report)?;
+
+        // Only the current account has balances for 2023
+        let report = Report::generate(2023, &user_data, &context)?;
+        assert_eq!(dropped_accounts(&snapshots, &report, &user_data)?.len(), 4);
+
+        user_data.accounts[2].closing_date = Some(date("2022-12-31"));
+        user_data.accounts[3].exclude_from_fbar = true;
+        user_data.accounts[3].exclusion_reason = Some("US-based".to_string());
+        user_data.accounts.pop();
+        let report = Report::generate(2023, &user_data, &context)?;
+        let dropped = dropped_accounts(&snapshots, &report, &user_data)?;
+
+        assert_eq!(
+            dropped
+                .iter()
+                .map(|account| (account.handle.as_str(), account.in_data))
+                .collect::<Vec<_>>(),
+            vec![("forgotten", true), ("removed", false)]
+        );
+        assert_eq!(
+            dropped[0].to_string(),
+            "Account forgotten at example_bank was reported for 2022 (maximum $1,250.00) but is missing \
+             from 2023: add its balances for 2023, or set its closing_date if it was closed"
+        );
+
+        // Reports limited to a tag only look at accounts with it
+        let report = Report::generate_tagged(2023, &user_data, &context, &["kids".to_string()])?;
+        assert!(dropped_accounts(&snapshots, &report, &user_data)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_significant_changes() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let snapshots = SnapshotStore::new(temp_dir.path());
+        let context = create_test_context();
+
+        let account = |handle: &str, before: Decimal, after: Decimal| {
+            let mut account = create_test_account(handle, &["2022-06-30", "2023-06-30"]);
+            account.balances[0].amount = before;
+            account.balances[1].amount = after;
+            account
+        };
+        let user_data = UserData {
+            accounts: vec![
+                account("steady", dec!(1000), dec!(1100)),
+                // A misread decimal separator
+                account("inflated", dec!(1000), dec!(100000)),
+                account("growing", dec!(7000), dec!(9000)),
+                account("small", dec!(10), dec!(500)),
+            ],
+            ..Default::default()
+        };
+        let config = ChangeCheckConfig::default();
+        let report = Report::generate(2022, &user_data, &context)?;
+        assert!(significant_changes(&snapshots, &report, &config)?.is_empty());
+        snapshots.close_year(report)?;
+
+        let report = Report::generate(2023, &user_data, &context)?;
+        let changes = significant_changes(&snapshots, &report, &config)?;
+        assert_eq!(
+            changes
+                .iter()
+                .map(|change| change.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "Account growing's maximum went from $8,750.00 in 2022 to $11,250.00 in 2023, reaching $10,000.00 \
+                 for the first time; check its balances were imported correctly",
+                "Account inflated's maximum went from $1,250.00 in 2022 to $125,000.00 in 2023, 9900% more; check \
+                 its balances were imported correctly",
+            ]
+        );
+
+        let config = ChangeCheckConfig {
+            max_yearly_change: 20.0,
+            thresholds: Vec::new(),
+            ..Default::default()
+        };
+        let changes = significant_changes(&snapshots, &report, &config)?;
+        assert_eq!(
+            changes
+                .iter()
+                .map(|change| change.handle.as_str())
+                .collect::<Vec<_>>(),
+            vec!["growing", "inflated"]
+        );
+
+        Ok(())
+    }
+}
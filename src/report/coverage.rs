@@ -0,0 +1,285 @@
+use std::collections::BTreeMap;
+
+use super::overview::{reportable, years};
+use super::OpenPeriod;
+use crate::data::UserData;
+use crate::facts::RateKind;
+use crate::report_context::{RateSource, ReportContext};
+
+const USD: &str = "usd";
+
+/// The currencies a year's report has to convert, and where each one's rate would come from
+#[derive(Debug)]
+pub struct YearCoverage {
+    pub year: i32,
+    /// By currency code, uppercase
+    pub currencies: Vec<CurrencyCoverage>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CurrencyCoverage {
+    pub currency_code: String,
+    /// Handles of the accounts held in the currency, or with records in it, during the year
+    pub accounts: Vec<String>,
+    /// Where the rate would come from, or `None` when no source has one and the report would fail
+    pub source: Option<RateSource>,
+}
+
+impl YearCoverage {
+    /// The currencies without a rate, which need one in `fact_extensions` before the year can be reported
+    pub fn missing(&self) -> impl Iterator<Item = &CurrencyCoverage> {
+        self.currencies
+            .iter()
+            .filter(|currency| currency.source.is_none())
+    }
+}
+
+/// Lists, for every year the data has records for, oldest first, the currencies the year's accounts need converted
+/// from and whether there's a rate for each. USD needs no rate, so isn't listed.
+pub fn coverage(user_data: &UserData, context: &ReportContext) -> Vec<YearCoverage> {
+    let _span = tracing::info_span!("coverage").entered();
+    years(user_data)
+        .into_iter()
+        .map(|year| YearCoverage {
+            year,
+            currencies: currencies_in_year(user_data, year)
+                .into_iter()
+                .map(|(currency, accounts)| CurrencyCoverage {
+                    source: context
+                        .find_exchange_rate(year, &currency)
+                        .ok()
+                        .map(|converter| converter.source().clone()),
+                    currency_code: currency.to_uppercase(),
+                    accounts,
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// A currency's year-end and yearly average rates for a year, each with where it came from
+#[derive(Debug, PartialEq)]
+pub struct CurrencyRates {
+    /// Uppercase
+    pub currency_code: String,
+    pub year_end: Option<(f64, RateSource)>,
+    pub yearly_average: Option<(f64, RateSource)>,
+}
+
+/// Both kinds of rate for each currency the year's accounts need converted from, by currency code, for filers who
+/// translate income on Form 1040 at the yearly average alongside filing the FBAR at the year-end rate
+pub fn rates(user_data: &UserData, context: &ReportContext, year: i32) -> Vec<CurrencyRates> {
+    let rate = |currency: &str, kind| {
+        context
+            .find_rate(year, currency, kind)
+            .ok()
+            .map(|converter| (converter.rate, converter.source().clone()))
+    };
+    currencies_in_year(user_data, year)
+        .into_keys()
+        .map(|currency| CurrencyRates {
+            year_end: rate(&currency, RateKind::YearEnd),
+            yearly_average: rate(&currency, RateKind::YearlyAverage),
+            currency_code: currency.to_uppercase(),
+        })
+        .collect()
+}
+
+/// Handles of the accounts open in the year, by each lowercase currency other than USD they're held in or have
+/// records in during it
+fn currencies_in_year(user_data: &UserData, year: i32) -> BTreeMap<String, Vec<String>> {
+    let mut accounts: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for account in reportable(user_data) {
+        if OpenPeriod::for_account(account, year).is_none() {
+            continue;
+        }
+        let mut currencies = vec![account.currency_code.to_lowercase()];
+        currencies.extend(
+            account
+                .balances_in_year(year)
+                .filter_map(|record| record.currency.as_ref())
+                .map(|currency| currency.to_lowercase()),
+        );
+        currencies.sort();
+        currencies.dedup();
+        for currency in currencies.into_iter().filter(|currency| currency != USD) {
+            accounts
+                .entry(currency)
+                .or_default()
+                .push(account.handle.clone());
+        }
+    }
+    accounts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Account, BalanceRecord};
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use crate::money::Money;
+    use anyhow::Result;
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_coverage() -> Result<()> {
+        let record = |date: &str, currency: Option<&str>| BalanceRecord {
+            date: date.parse().unwrap(),
+            amount: dec!(100),
+            currency: currency.map(str::to_string),
+            ..Default::default()
+        };
+        let user_data = UserData {
+            accounts: vec![
+                Account {
+                    handle: "girokonto".to_string(),
+                    currency_code: "EUR".to_string(),
+                    balances: vec![
+                        record("2022-12-31", None),
+                        record("2023-06-30", Some("CHF")),
+                    ],
+                    ..Default::default()
+                },
+                Account {
+                    handle: "brokerage".to_string(),
+                    currency_code: "USD".to_string(),
+                    balances: vec![record("2023-06-30", None)],
+                    ..Default::default()
+                },
+                Account {
+                    handle: "closed".to_string(),
+                    currency_code: "ILS".to_string(),
+                    closing_date: NaiveDate::from_ymd_opt(2022, 12, 31),
+                    balances: vec![record("2022-06-30", None)],
+                    ..Default::default()
+                },
+                Account {
+                    handle: "excluded".to_string(),
+                    currency_code: "JPY".to_string(),
+                    exclude_from_fbar: true,
+                    balances: vec![record("2023-06-30", None)],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let facts = Facts {
+            years: vec![AnnualFact {
+                year: 2023,
+                exchange_rates: vec![ExchangeRate::new("EUR".to_string(), 0.92).unwrap()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let years = coverage(&user_data, &ReportContext::new(facts, None));
+        assert_eq!(
+            years.iter().map(|year| year.year).collect::<Vec<_>>(),
+            vec![2022, 2023]
+        );
+
+        // Nothing has a 2022 rate
+        assert_eq!(
+            years[0]
+                .missing()
+                .map(|currency| currency.currency_code.as_str())
+                .collect::<Vec<_>>(),
+            vec!["EUR", "ILS"]
+        );
+
+        // The closed and excluded accounts' currencies aren't needed, nor USD
+        assert_eq!(
+            years[1].currencies,
+            vec![
+                CurrencyCoverage {
+                    currency_code: "CHF".to_string(),
+                    accounts: vec!["girokonto".to_string()],
+                    source: None,
+                },
+                CurrencyCoverage {
+                    currency_code: "EUR".to_string(),
+                    accounts: vec!["girokonto".to_string()],
+                    source: Some(RateSource::IrsProvided),
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rates() -> Result<()> {
+        let user_data = UserData {
+            accounts: vec![Account {
+                handle: "girokonto".to_string(),
+                currency_code: "EUR".to_string(),
+                balances: vec![BalanceRecord {
+                    date: "2023-06-30".parse()?,
+                    amount: dec!(100),
+                    currency: Some("CHF".to_string()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let facts = Facts {
+            years: vec![AnnualFact {
+                year: 2023,
+                exchange_rates: vec![ExchangeRate::new("EUR".to_string(), 0.905).unwrap()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let extensions = Facts {
+            years: vec![AnnualFact {
+                year: 2023,
+                exchange_rates: Vec::new(),
+                average_rates: vec![ExchangeRate::new("EUR".to_string(), 0.924).unwrap()],
+            }],
+            ..Default::default()
+        };
+        let context = ReportContext::new(facts, extensions);
+
+        assert_eq!(
+            rates(&user_data, &context, 2023),
+            vec![
+                CurrencyRates {
+                    currency_code: "CHF".to_string(),
+                    year_end: None,
+                    yearly_average: None,
+                },
+                CurrencyRates {
+                    currency_code: "EUR".to_string(),
+                    year_end: Some((0.905, RateSource::IrsProvided)),
+                    yearly_average: Some((0.924, RateSource::UserProvided)),
+                },
+            ]
+        );
+
+        // The same amount converts differently at each
+        let amount = Money::new(dec!(905), "EUR");
+        assert_eq!(
+            context
+                .convert_to_usd_with(2023, &amount, RateKind::YearEnd)?
+                .amount,
+            dec!(1000)
+        );
+        assert_eq!(
+            context
+                .convert_to_usd_with(2023, &amount, RateKind::YearlyAverage)?
+                .amount,
+            dec!(979.44)
+        );
+        assert_eq!(
+            context
+                .convert_to_usd_with(2023, &Money::new(dec!(1), "CHF"), RateKind::YearlyAverage)
+                .unwrap_err()
+                .to_string(),
+            "No yearly average exchange rate found for chf in year 2023"
+        );
+
+        Ok(())
+    }
+}
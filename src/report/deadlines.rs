@@ -0,0 +1,177 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::BTreeSet;
+
+use super::overview;
+use super::SnapshotStore;
+use crate::data::{Filing, UserData};
+use crate::report_context::ReportContext;
+
+/// A year's FBAR due dates, and whether it looks filed
+#[derive(Debug)]
+pub struct YearDeadline {
+    pub year: i32,
+    /// April 15 of the following year
+    pub due: NaiveDate,
+    /// October 15 of the following year, the automatic extension
+    pub extended: NaiveDate,
+    pub status: FilingStatus,
+    /// Whether the aggregate maximum exceeds the filing threshold; `None` when the year's report can't be generated
+    pub required: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilingStatus {
+    /// `data.yml` gives the date it was filed on
+    Filed(NaiveDate),
+    /// The year was closed, which is usually done once it's filed
+    Closed(DateTime<Utc>),
+    Open,
+}
+
+impl YearDeadline {
+    /// Open, and either required or not known not to be
+    pub fn appears_unfiled(&self) -> bool {
+        self.status == FilingStatus::Open && self.required != Some(false)
+    }
+
+    /// Unfiled after its extended deadline
+    pub fn is_overdue(&self, today: NaiveDate) -> bool {
+        self.appears_unfiled() && today > self.extended
+    }
+}
+
+/// The due dates of every year the data has records or a filing for, or that was closed, oldest first, with which of
+/// them look filed. A year counts as filed when `data.yml` gives a `filed_on` date for it or it has been closed.
+pub fn deadlines(
+    user_data: &UserData,
+    context: &ReportContext,
+    snapshots: &SnapshotStore,
+) -> Result<Vec<YearDeadline>> {
+    let overview = overview::overview(user_data, context);
+    let closed = snapshots.closed_years()?;
+    let years: BTreeSet<i32> = overview
+        .iter()
+        .map(|row| row.year)
+        .chain(user_data.filings.iter().map(|filing| filing.year))
+        .chain(closed.iter().copied())
+        .collect();
+
+    years
+        .into_iter()
+        .map(|year| {
+            let status = match user_data
+                .filing_for(year)
+                .and_then(|filing| filing.filed_on)
+            {
+                Some(filed_on) => FilingStatus::Filed(filed_on),
+                None if closed.contains(&year) => match snapshots.load(year)? {
+                    Some(snapshot) => FilingStatus::Closed(snapshot.closed_at),
+                    None => FilingStatus::Open,
+                },
+                None => FilingStatus::Open,
+            };
+            let required =
+                overview
+                    .iter()
+                    .find(|row| row.year == year)
+                    .map_or(Some(false), |row| {
+                        row.figures
+                            .as_ref()
+                            .ok()
+                            .map(|figures| figures.filing_required())
+                    });
+
+            Ok(YearDeadline {
+                year,
+                due: Filing::due_date(year).ok_or_else(|| anyhow!("No due date for {}", year))?,
+                extended: Filing::deadline(year)
+                    .ok_or_else(|| anyhow!("No deadline for {}", year))?,
+                status,
+                required,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Account, BalanceRecord};
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use crate::report::Report;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use tempfile::TempDir;
+
+    fn balance(date: &str, amount: Decimal) -> BalanceRecord {
+        BalanceRecord {
+            date: date.parse().unwrap(),
+            amount,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_deadlines() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let snapshots = SnapshotStore::new(temp_dir.path());
+        let context = ReportContext::new(
+            Facts {
+                years: [2021, 2022, 2023]
+                    .into_iter()
+                    .map(|year| AnnualFact {
+                        year,
+                        exchange_rates: vec![ExchangeRate::new("GBP".to_string(), 0.80).unwrap()],
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            },
+            None,
+        );
+        let user_data = UserData {
+            accounts: vec![Account {
+                handle: "savings".to_string(),
+                currency_code: "GBP".to_string(),
+                balances: vec![
+                    balance("2021-06-30", dec!(20000)),
+                    balance("2022-06-30", dec!(20000)),
+                    balance("2023-06-30", dec!(20000)),
+                    balance("2024-06-30", dec!(100)),
+                ],
+                ..Default::default()
+            }],
+            filings: vec![Filing {
+                year: 2021,
+                filed_on: "2022-04-01".parse().ok(),
+                late_reason: None,
+                explanation: None,
+            }],
+            ..Default::default()
+        };
+        snapshots.close_year(Report::generate(2022, &user_data, &context)?)?;
+
+        let rows = deadlines(&user_data, &context, &snapshots)?;
+        assert_eq!(
+            rows.iter().map(|row| row.year).collect::<Vec<_>>(),
+            vec![2021, 2022, 2023, 2024]
+        );
+        assert_eq!(rows[0].due, "2022-04-15".parse::<NaiveDate>()?);
+        assert_eq!(rows[0].extended, "2022-10-15".parse::<NaiveDate>()?);
+        assert_eq!(rows[0].status, FilingStatus::Filed("2022-04-01".parse()?));
+        assert!(matches!(rows[1].status, FilingStatus::Closed(_)));
+        assert!(!rows[1].appears_unfiled());
+
+        // Over the threshold and neither filed nor closed
+        assert!(rows[2].appears_unfiled());
+        assert!(rows[2].is_overdue("2024-10-16".parse()?));
+        assert!(!rows[2].is_overdue("2024-10-15".parse()?));
+
+        // No 2024 rate, so whether it's required isn't known
+        assert_eq!(rows[3].required, None);
+        assert!(rows[3].appears_unfiled());
+
+        Ok(())
+    }
+}
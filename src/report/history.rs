@@ -0,0 +1,203 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::{Report, SnapshotStore};
+
+/// A generated report kept in the data directory's history
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HistoryEntry {
+    pub report_id: String,
+    /// When a report with this ID was first generated
+    pub generated_at: DateTime<Utc>,
+    pub report: Report,
+}
+
+/// A report from the history or a closed year, as listed by `history`
+#[derive(Debug, Clone)]
+pub struct ArchivedReport {
+    pub report_id: String,
+    pub year: i32,
+    /// When it was first generated, or when its year was closed
+    pub date: DateTime<Utc>,
+    /// Whether it's the frozen report for a closed year
+    pub closed: bool,
+    pub report: Report,
+}
+
+/// Keeps a copy of every report generated under `.fbar_prep/history` in the data directory, one file per report ID,
+/// so earlier years' figures can be looked up without regenerating them from data that may have changed since
+pub struct HistoryStore {
+    dir: PathBuf,
+    snapshots: SnapshotStore,
+}
+
+impl HistoryStore {
+    pub fn new(base_path: &Path) -> Self {
+        Self {
+            dir: base_path.join(".fbar_prep").join("history"),
+            snapshots: SnapshotStore::new(base_path),
+        }
+    }
+
+    /// Records a generated report. Report IDs are derived from the inputs, so regenerating an unchanged report keeps
+    /// the entry from its first generation.
+    pub fn record(&self, report: &Report, generated_at: DateTime<Utc>) -> Result<()> {
+        let report_id = crate::output::report_metadata(report)?.report_id.clone();
+        let path = self.dir.join(format!("{}.yml", report_id));
+        if path.exists() {
+            return Ok(());
+        }
+
+        let entry = HistoryEntry {
+            report_id,
+            generated_at,
+            report: report.clone(),
+        };
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(&path, serde_yaml::to_string(&entry)?)
+            .with_context(|| format!("Failed to write {:?}", path))?;
+        Ok(())
+    }
+
+    /// Every generated report and closed year, by year and then date. A generated report that was later frozen by
+    /// closing its year is listed once, as closed.
+    pub fn list(&self) -> Result<Vec<ArchivedReport>> {
+        let mut archived = Vec::new();
+        for year in self.snapshots.closed_years()? {
+            if let Some(snapshot) = self.snapshots.load(year)? {
+                let report_id = match &snapshot.report.metadata {
+                    Some(metadata) => metadata.report_id.clone(),
+                    None => snapshot.content_hash[..16].to_string(),
+                };
+                archived.push(ArchivedReport {
+                    report_id,
+                    year,
+                    date: snapshot.closed_at,
+                    closed: true,
+                    report: snapshot.report,
+                });
+            }
+        }
+
+        if self.dir.exists() {
+            for dir_entry in std::fs::read_dir(&self.dir)? {
+                let path = dir_entry?.path();
+                if path.extension().and_then(|ext| ext.to_str()) != Some("yml") {
+                    continue;
+                }
+                let contents = std::fs::read_to_string(&path)?;
+                let entry: HistoryEntry = serde_yaml::from_str(&contents)
+                    .with_context(|| format!("Invalid YAML in {:?}", path))?;
+                if archived
+                    .iter()
+                    .any(|existing| existing.report_id == entry.report_id)
+                {
+                    continue;
+                }
+                archived.push(ArchivedReport {
+                    report_id: entry.report_id,
+                    year: entry.report.year,
+                    date: entry.generated_at,
+                    closed: false,
+                    report: entry.report,
+                });
+            }
+        }
+
+        archived.sort_by(|a, b| a.year.cmp(&b.year).then_with(|| a.date.cmp(&b.date)));
+        Ok(archived)
+    }
+
+    /// The archived report whose ID starts with `id`, which has to pick out exactly one
+    pub fn find(&self, id: &str) -> Result<ArchivedReport> {
+        let mut matching: Vec<ArchivedReport> = self
+            .list()?
+            .into_iter()
+            .filter(|archived| archived.report_id.starts_with(id))
+            .collect();
+        match matching.len() {
+            0 => bail!("No report in the history has an ID starting with {:?}", id),
+            1 => Ok(matching.remove(0)),
+            count => bail!(
+                "{} reports have IDs starting with {:?}; give more of the ID",
+                count,
+                id
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Account, BalanceRecord, UserData};
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use crate::report_context::ReportContext;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use tempfile::TempDir;
+
+    fn user_data(amount: Decimal) -> UserData {
+        UserData {
+            accounts: vec![Account {
+                handle: "savings".to_string(),
+                currency_code: "GBP".to_string(),
+                balances: vec![BalanceRecord {
+                    date: NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(),
+                    amount,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_history() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let store = HistoryStore::new(temp_dir.path());
+        let context = ReportContext::new(
+            Facts {
+                years: vec![AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![ExchangeRate::new("GBP".to_string(), 0.80).unwrap()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            None,
+        );
+        assert!(store.list()?.is_empty());
+
+        let first = Report::generate(2023, &user_data(dec!(800)), &context)?;
+        let first_at = Utc::now();
+        store.record(&first, first_at)?;
+        // Regenerating the same report keeps its first date
+        store.record(&first, first_at + chrono::Duration::hours(1))?;
+        let second = Report::generate(2023, &user_data(dec!(900)), &context)?;
+        store.record(&second, first_at + chrono::Duration::days(1))?;
+
+        let listed = store.list()?;
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].date, first_at);
+        assert!(!listed[0].closed);
+
+        // Closing the year freezes the second report, which is then listed once
+        SnapshotStore::new(temp_dir.path()).close_year(second.clone())?;
+        let listed = store.list()?;
+        assert_eq!(listed.len(), 2);
+        assert!(listed.iter().any(|archived| archived.closed));
+
+        let second_id = &second.metadata.as_ref().unwrap().report_id;
+        let found = store.find(&second_id[..8])?;
+        assert_eq!(found.report, second);
+        assert!(store.find("").is_err());
+        assert!(store.find("not-an-id").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,374 @@
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::Decimal;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{OpenPeriod, Valuation, ValuePoint};
+use crate::data::Account;
+use crate::money::Money;
+use crate::report_context::ReportContext;
+
+/// How to estimate an account's value at the edges of the year when its records are sparse, e.g. monthly or
+/// quarterly statements.
+///
+/// Between records the maximum is unaffected: holding a value or drawing a line between two records never exceeds
+/// them. What changes is the start of the year, where the last record of the previous year may still have been the
+/// balance, and with `linear`, the end of the year, where the line towards the next year's first record ends.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InterpolationPolicy {
+    /// Only the recorded values count
+    #[default]
+    None,
+    /// A value holds until the next record, so the previous year's last record counts at the start of the year
+    StepHold,
+    /// Values change linearly between records, so the start and end of the year are estimated from the records
+    /// either side
+    Linear,
+}
+
+impl InterpolationPolicy {
+    fn describe(&self) -> &'static str {
+        match self {
+            InterpolationPolicy::None => "no interpolation",
+            InterpolationPolicy::StepHold => "step-hold interpolation",
+            InterpolationPolicy::Linear => "linear interpolation",
+        }
+    }
+}
+
+/// The account's last value before the open period and, for linear interpolation, its first value after it. Only
+/// looks outside the period while the account was open.
+pub fn neighbours(
+    valuation: &dyn Valuation,
+    account: &Account,
+    period: &OpenPeriod,
+    context: &ReportContext,
+    policy: InterpolationPolicy,
+) -> Result<(Option<ValuePoint>, Option<ValuePoint>)> {
+    if policy == InterpolationPolicy::None {
+        return Ok((None, None));
+    }
+    let year = period.start.year();
+
+    // The latest value before the period: from earlier in the year when the period starts late, e.g. in a first
+    // year filing, or else from the year before
+    let earlier = OpenPeriod::for_account(account, year).and_then(|open| {
+        let end = period.start.pred_opt()?;
+        (open.start <= end).then_some(OpenPeriod { end, ..open })
+    });
+    let mut before = None;
+    for earlier in earlier
+        .into_iter()
+        .chain(OpenPeriod::for_account(account, year - 1))
+    {
+        before = valuation
+            .value_series(account, &earlier, context)?
+            .into_iter()
+            .max_by_key(|point| point.date);
+        if before.is_some() {
+            break;
+        }
+    }
+    let after = match OpenPeriod::for_account(account, year + 1) {
+        Some(next) if policy == InterpolationPolicy::Linear => valuation
+            .value_series(account, &next, context)?
+            .into_iter()
+            .min_by_key(|point| point.date),
+        _ => None,
+    };
+
+    Ok((before, after))
+}
+
+/// Adds the values the policy estimates at the start and end of the open period to a series sorted by date, with a
+/// note describing each estimate. The neighbouring values must be in the series' currency.
+pub fn interpolate(
+    mut series: Vec<ValuePoint>,
+    before: Option<ValuePoint>,
+    after: Option<ValuePoint>,
+    period: &OpenPeriod,
+    policy: InterpolationPolicy,
+) -> Result<(Vec<ValuePoint>, Vec<String>)> {
+    let mut notes = Vec::new();
+    if policy == InterpolationPolicy::None {
+        return Ok((series, notes));
+    }
+
+    let recorded_at_start = series
+        .first()
+        .is_some_and(|point| point.date == period.start);
+    if let (Some(before), false) = (&before, recorded_at_start) {
+        let next = series.first().or(after.as_ref());
+        let value = match (policy, next) {
+            (InterpolationPolicy::Linear, Some(next)) => linear(before, next, period.start)?,
+            _ => before.value.clone(),
+        };
+        notes.push(format!(
+            "Value on {} of {} estimated by {} from {} on {}",
+            period.start,
+            value,
+            policy.describe(),
+            before.value,
+            before.date
+        ));
+        series.insert(
+            0,
+            ValuePoint {
+                date: period.start,
+                value,
+            },
+        );
+    }
+
+    let recorded_at_end = series.last().is_some_and(|point| point.date == period.end);
+    let after = after.filter(|_| policy == InterpolationPolicy::Linear);
+    if let (Some(after), false) = (&after, recorded_at_end) {
+        if let Some(previous) = series.last() {
+            let value = linear(previous, after, period.end)?;
+            notes.push(format!(
+                "Value on {} of {} estimated by {} towards {} on {}",
+                period.end,
+                value,
+                policy.describe(),
+                after.value,
+                after.date
+            ));
+            series.push(ValuePoint {
+                date: period.end,
+                value,
+            });
+        }
+    }
+
+    Ok((series, notes))
+}
+
+/// The value on a date between two points, on the line joining them, rounded to the currency's minor units
+fn linear(from: &ValuePoint, to: &ValuePoint, date: NaiveDate) -> Result<Money> {
+    let total_days = (to.date - from.date).num_days();
+    if total_days == 0 {
+        return Ok(from.value.clone());
+    }
+    let elapsed = Decimal::from((date - from.date).num_days()) / Decimal::from(total_days);
+    let change = to.value.checked_sub(&from.value)?;
+
+    Ok(Money::new(
+        from.value.amount + change.amount * elapsed,
+        from.value.currency.clone(),
+    )
+    .round())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{BalanceRecord, UserData};
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use crate::report::Report;
+    use rust_decimal_macros::dec;
+
+    fn point(date: &str, amount: Decimal) -> ValuePoint {
+        ValuePoint {
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            value: Money::new(amount, "eur"),
+        }
+    }
+
+    fn full_year() -> OpenPeriod {
+        OpenPeriod {
+            start: NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+            end: NaiveDate::from_ymd_opt(2023, 12, 31).unwrap(),
+        }
+    }
+
+    fn amounts(series: &[ValuePoint]) -> Vec<(String, Decimal)> {
+        series
+            .iter()
+            .map(|point| (point.date.to_string(), point.value.amount))
+            .collect()
+    }
+
+    #[test]
+    fn test_no_interpolation() -> Result<()> {
+        let series = vec![point("2023-03-31", dec!(100))];
+        let (interpolated, notes) = interpolate(
+            series.clone(),
+            Some(point("2022-12-31", dec!(900))),
+            None,
+            &full_year(),
+            InterpolationPolicy::None,
+        )?;
+
+        assert_eq!(interpolated, series);
+        assert!(notes.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_hold() -> Result<()> {
+        let (interpolated, notes) = interpolate(
+            vec![point("2023-03-31", dec!(100))],
+            Some(point("2022-12-31", dec!(900))),
+            Some(point("2024-01-31", dec!(5000))),
+            &full_year(),
+            InterpolationPolicy::StepHold,
+        )?;
+
+        assert_eq!(
+            amounts(&interpolated),
+            vec![
+                ("2023-01-01".to_string(), dec!(900)),
+                ("2023-03-31".to_string(), dec!(100)),
+            ]
+        );
+        assert_eq!(
+            notes,
+            vec![
+                "Value on 2023-01-01 of 900.00 EUR estimated by step-hold interpolation from 900.00 EUR on 2022-12-31"
+                    .to_string()
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_linear() -> Result<()> {
+        let (interpolated, notes) = interpolate(
+            vec![point("2023-01-02", dec!(100))],
+            Some(point("2022-12-31", dec!(900))),
+            Some(point("2024-01-01", dec!(465))),
+            &full_year(),
+            InterpolationPolicy::Linear,
+        )?;
+
+        // Halfway from 900 to 100, and 364 of 365 days from 100 towards 465
+        assert_eq!(
+            amounts(&interpolated),
+            vec![
+                ("2023-01-01".to_string(), dec!(500)),
+                ("2023-01-02".to_string(), dec!(100)),
+                ("2023-12-31".to_string(), dec!(464)),
+            ]
+        );
+        assert_eq!(notes.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_recorded_bounds_are_kept() -> Result<()> {
+        let series = vec![
+            point("2023-01-01", dec!(100)),
+            point("2023-12-31", dec!(200)),
+        ];
+        let (interpolated, notes) = interpolate(
+            series.clone(),
+            Some(point("2022-12-31", dec!(900))),
+            Some(point("2024-01-01", dec!(900))),
+            &full_year(),
+            InterpolationPolicy::Linear,
+        )?;
+
+        assert_eq!(interpolated, series);
+        assert!(notes.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_records_policy() -> Result<()> {
+        let balance = |date: &str, amount: Decimal| BalanceRecord {
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            amount,
+            ..Default::default()
+        };
+        let mut user_data = UserData {
+            accounts: vec![Account {
+                handle: "quarterly_savings".to_string(),
+                provider: "example_bank".to_string(),
+                currency_code: "EUR".to_string(),
+                balances: vec![
+                    balance("2022-12-31", dec!(2000)),
+                    balance("2023-03-31", dec!(100)),
+                    balance("2023-06-30", dec!(200)),
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let context = ReportContext::new(
+            Facts {
+                years: vec![AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![ExchangeRate::new("EUR".to_string(), 0.80).unwrap()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            None,
+        );
+
+        let report = Report::generate(2023, &user_data, &context)?;
+        assert_eq!(report.accounts[0].max_balance.amount, dec!(200));
+        assert_eq!(report.accounts[0].interpolation, None);
+
+        user_data.interpolation = InterpolationPolicy::StepHold;
+        let report = Report::generate(2023, &user_data, &context)?;
+        assert_eq!(report.accounts[0].max_balance.amount, dec!(2000));
+        assert_eq!(
+            report.accounts[0].interpolation,
+            Some(InterpolationPolicy::StepHold)
+        );
+        assert!(report.accounts[0]
+            .notes
+            .iter()
+            .any(|note| note.starts_with("Value on 2023-01-01 of 2000.00 EUR estimated")));
+
+        // An account's own policy overrides the data directory's
+        user_data.accounts[0].interpolation = Some(InterpolationPolicy::None);
+        let report = Report::generate(2023, &user_data, &context)?;
+        assert_eq!(report.accounts[0].max_balance.amount, dec!(200));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_linear_without_earlier_record() -> Result<()> {
+        let balance = |date: &str, amount: Decimal| BalanceRecord {
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            amount,
+            ..Default::default()
+        };
+        let user_data = UserData {
+            accounts: vec![Account {
+                handle: "opened_midyear".to_string(),
+                provider: "example_bank".to_string(),
+                currency_code: "EUR".to_string(),
+                balances: vec![
+                    balance("2023-06-30", dec!(100)),
+                    balance("2024-01-31", dec!(100000)),
+                ],
+                ..Default::default()
+            }],
+            interpolation: InterpolationPolicy::Linear,
+            ..Default::default()
+        };
+        let context = ReportContext::new(
+            Facts {
+                years: vec![AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![ExchangeRate::new("EUR".to_string(), 0.80).unwrap()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            None,
+        );
+
+        // With nothing before the year, the record after it still gives the estimate for December 31: 184 of 215
+        // days from 100 towards 100000
+        let report = Report::generate(2023, &user_data, &context)?;
+        assert_eq!(report.accounts[0].max_balance.amount, dec!(85595.81));
+        Ok(())
+    }
+}
@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+
+use super::{OpenPeriod, Report};
+use crate::data::{Account, Address, UserData};
+
+/// The joint owner details Part III asks for alongside each jointly owned account
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct JointOwner {
+    pub handle: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tin: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<Address>,
+}
+
+/// Two filers who jointly own every reportable account. One may report all of them, with the other as joint owner
+/// in Part III, and the other needn't file a separate FBAR; both sign FinCEN Form 114a.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SpousalFiling {
+    pub filer: String,
+    pub spouse: String,
+}
+
+/// The principal joint owner of an account owned by the primary filer and another filer
+pub fn joint_owner(account: &Account, user_data: &UserData) -> Option<String> {
+    let primary = user_data.primary_filer()?;
+    if !account.owners.contains(&primary.handle) {
+        return None;
+    }
+    account
+        .owners
+        .iter()
+        .find(|owner| **owner != primary.handle)
+        .cloned()
+}
+
+/// Details of the filers named as joint owners in the report
+pub fn joint_owners(report: &Report, user_data: &UserData) -> Vec<JointOwner> {
+    user_data
+        .filers
+        .iter()
+        .filter(|filer| {
+            report
+                .accounts
+                .iter()
+                .any(|account| account.joint_owner.as_ref() == Some(&filer.handle))
+        })
+        .map(|filer| JointOwner {
+            handle: filer.handle.clone(),
+            name: filer.name.clone(),
+            tin: filer.tin.clone(),
+            address: filer.address.clone(),
+        })
+        .collect()
+}
+
+/// Whether exactly two filers are configured and they jointly own every account reportable for the year
+pub fn spousal_filing(year: i32, user_data: &UserData) -> Option<SpousalFiling> {
+    let [filer, spouse] = user_data.filers.as_slice() else {
+        return None;
+    };
+
+    let mut reportable = user_data
+        .accounts
+        .iter()
+        .filter(|account| {
+            !account.exclude_from_fbar && OpenPeriod::for_account(account, year).is_some()
+        })
+        .peekable();
+    reportable.peek()?;
+    reportable
+        .all(|account| {
+            account.owners.contains(&filer.handle) && account.owners.contains(&spouse.handle)
+        })
+        .then(|| SpousalFiling {
+            filer: filer.handle.clone(),
+            spouse: spouse.handle.clone(),
+        })
+}
+
+/// Suggests the single-filer option when the report finds every account jointly owned
+pub fn notice(report: &Report) -> Option<String> {
+    let spousal = report.spousal_filing.as_ref()?;
+    Some(format!(
+        "Every account for {} is jointly owned by {} and {}: {} can report them all, listing {} as joint owner in \
+         Part III, and {} needn't file separately if both sign FinCEN Form 114a",
+        report.year,
+        spousal.filer,
+        spousal.spouse,
+        spousal.filer,
+        spousal.spouse,
+        spousal.spouse
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{BalanceRecord, Filer};
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use crate::report_context::ReportContext;
+    use anyhow::Result;
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    fn create_test_account(handle: &str, owners: &[&str]) -> Account {
+        Account {
+            handle: handle.to_string(),
+            provider: "example_bank".to_string(),
+            currency_code: "EUR".to_string(),
+            owners: owners.iter().map(|owner| owner.to_string()).collect(),
+            balances: vec![BalanceRecord {
+                date: NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(),
+                amount: dec!(100),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn create_test_user_data(accounts: Vec<Account>) -> UserData {
+        let filer = |name: &str, handle: &str| Filer {
+            name: name.to_string(),
+            handle: handle.to_string(),
+            tin: Some("123-45-6789".to_string()),
+            address: None,
+        };
+        UserData {
+            filers: vec![filer("Alex Example", "alex"), filer("Sam Example", "sam")],
+            accounts,
+            ..Default::default()
+        }
+    }
+
+    fn create_test_context() -> ReportContext {
+        ReportContext::new(
+            Facts {
+                years: vec![AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![ExchangeRate::new("EUR".to_string(), 0.80).unwrap()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            None,
+        )
+    }
+
+    #[test]
+    fn test_fully_joint() -> Result<()> {
+        let user_data = create_test_user_data(vec![
+            create_test_account("joint_savings", &["alex", "sam"]),
+            create_test_account("joint_checking", &["sam", "alex"]),
+        ]);
+
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        assert!(report
+            .accounts
+            .iter()
+            .all(|account| account.joint_owner.as_deref() == Some("sam")));
+        assert_eq!(report.joint_owners.len(), 1);
+        assert_eq!(report.joint_owners[0].name, "Sam Example");
+        assert_eq!(
+            report.spousal_filing,
+            Some(SpousalFiling {
+                filer: "alex".to_string(),
+                spouse: "sam".to_string(),
+            })
+        );
+        assert!(notice(&report)
+            .unwrap()
+            .starts_with("Every account for 2023 is jointly owned by alex and sam"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_partly_joint() -> Result<()> {
+        let user_data = create_test_user_data(vec![
+            create_test_account("joint_savings", &["alex", "sam"]),
+            create_test_account("alex_savings", &[]),
+            create_test_account("sam_savings", &["sam"]),
+        ]);
+
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        let joint_owners: Vec<(&str, Option<&str>)> = report
+            .accounts
+            .iter()
+            .map(|account| (account.handle.as_str(), account.joint_owner.as_deref()))
+            .collect();
+        assert_eq!(
+            joint_owners,
+            vec![("alex_savings", None), ("joint_savings", Some("sam"))]
+        );
+        // The spouse's own account is theirs to report
+        assert_eq!(report.excluded[0].handle, "sam_savings");
+        assert_eq!(report.spousal_filing, None);
+        assert_eq!(notice(&report), None);
+
+        Ok(())
+    }
+}
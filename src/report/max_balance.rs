@@ -0,0 +1,336 @@
+use anyhow::{anyhow, Result};
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::ValuePoint;
+use crate::crypto::PriceSeries;
+use crate::data::Account;
+use crate::money::Money;
+
+/// The part of a reporting year during which an account was open
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct OpenPeriod {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl OpenPeriod {
+    /// Intersects the account's open interval with the calendar year. Returns `None` if the account was not open
+    /// at any point during the year.
+    pub fn for_account(account: &Account, year: i32) -> Option<Self> {
+        let year_start = NaiveDate::from_ymd_opt(year, 1, 1)?;
+        let year_end = NaiveDate::from_ymd_opt(year, 12, 31)?;
+
+        let start = account
+            .opening_date
+            .map_or(year_start, |opened| opened.max(year_start));
+        let end = account
+            .closing_date
+            .map_or(year_end, |closed| closed.min(year_end));
+
+        if start > end {
+            return None;
+        }
+
+        Some(Self { start, end })
+    }
+
+    /// The part of the period from `date` on, or `None` if the period ended before it
+    pub fn starting_from(self, date: NaiveDate) -> Option<Self> {
+        let start = self.start.max(date);
+        (start <= self.end).then_some(Self { start, ..self })
+    }
+
+    pub fn is_full_year(&self) -> bool {
+        (self.start.month(), self.start.day()) == (1, 1)
+            && (self.end.month(), self.end.day()) == (12, 31)
+    }
+
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.start <= date && date <= self.end
+    }
+}
+
+/// An account's maximum value during the year, and when it was reached
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Maximum {
+    /// In the account's currency
+    pub value: Money,
+    /// The earliest date the account held the value, which may be an estimated value's date under interpolation
+    pub date: NaiveDate,
+}
+
+/// Maximum of a value series, with the date of its first occurrence. Returns `None` for an empty series, and fails
+/// if the values aren't all in the same currency.
+pub fn max_value(series: &[ValuePoint]) -> Result<Option<Maximum>> {
+    series.iter().try_fold(None, |max: Option<Maximum>, point| {
+        // Ties keep the earlier point, so the date is when the maximum was first reached
+        let larger = match &max {
+            Some(max) => {
+                max.value.clone().checked_max(point.value.clone())?.amount > max.value.amount
+            }
+            None => true,
+        };
+        Ok(if larger {
+            Some(Maximum {
+                value: point.value.clone(),
+                date: point.date,
+            })
+        } else {
+            max
+        })
+    })
+}
+
+/// Which maximum is filed for an account with daily USD rates imported for its currency. Whatever the policy, the
+/// report shows the maximum under the other one alongside.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MaximumPolicy {
+    /// The maximum balance in the account's currency, converted at the year's rate. With one rate for the year, this
+    /// is also the maximum in USD.
+    #[default]
+    ConvertMaximum,
+    /// The maximum of the account's values each converted at the daily rate on its date, which can peak on another
+    /// date than the balance does
+    MaxOfConverted,
+}
+
+impl MaximumPolicy {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// An account's maximum under one policy
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PolicyMaximum {
+    pub policy: MaximumPolicy,
+    /// In the account's currency
+    pub max_balance: Money,
+    pub max_balance_usd: Money,
+    pub date: NaiveDate,
+    /// Units of the account's currency per USD the maximum was converted at
+    pub exchange_rate: f64,
+    /// USD per unit of the account's currency, as given by the daily rates, when the maximum was converted at one.
+    /// Kept as given, since `exchange_rate` is only its reciprocal to float precision.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usd_price: Option<Decimal>,
+}
+
+/// Maximum of a value series converted to USD at the daily rates in `rates`, a price series of the series' currency
+/// in USD. Each value is converted at the most recent rate on or before its date; fails if a value has none.
+pub fn max_of_converted(
+    series: &[ValuePoint],
+    rates: &PriceSeries,
+) -> Result<Option<PolicyMaximum>> {
+    let mut max: Option<PolicyMaximum> = None;
+    for point in series {
+        let price = rates.price_on(point.date).ok_or_else(|| {
+            anyhow!(
+                "No daily {} rate on or before {}",
+                rates.asset.to_uppercase(),
+                point.date
+            )
+        })?;
+        let usd = Money::usd(point.value.amount * price.amount).round();
+        if max
+            .as_ref()
+            .is_some_and(|max| usd.amount <= max.max_balance_usd.amount)
+        {
+            continue;
+        }
+        let exchange_rate = Decimal::ONE
+            .checked_div(price.amount)
+            .and_then(|rate| rate.to_f64())
+            .ok_or_else(|| {
+                anyhow!(
+                    "Invalid daily {} rate {} on {}",
+                    rates.asset.to_uppercase(),
+                    price.amount,
+                    point.date
+                )
+            })?;
+        max = Some(PolicyMaximum {
+            policy: MaximumPolicy::MaxOfConverted,
+            max_balance: point.value.clone(),
+            max_balance_usd: usd,
+            date: point.date,
+            exchange_rate,
+            usd_price: Some(price.amount),
+        });
+    }
+    Ok(max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::PricePoint;
+    use crate::data::BalanceRecord;
+    use crate::facts::Facts;
+    use crate::report::valuation::{BalanceValuation, Valuation};
+    use crate::report_context::ReportContext;
+    use rust_decimal_macros::dec;
+
+    fn max_balance(account: &Account, period: &OpenPeriod) -> Option<Money> {
+        let context = ReportContext::new(Facts::empty(), None);
+        max_value(
+            &BalanceValuation
+                .value_series(account, period, &context)
+                .unwrap(),
+        )
+        .unwrap()
+        .map(|maximum| maximum.value)
+    }
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    fn create_test_account(opening_date: Option<&str>, closing_date: Option<&str>) -> Account {
+        Account {
+            handle: "savings".to_string(),
+            provider: "example_bank".to_string(),
+            currency_code: "EUR".to_string(),
+            opening_date: opening_date.map(date),
+            closing_date: closing_date.map(date),
+            balances: vec![
+                BalanceRecord {
+                    date: date("2023-01-31"),
+                    amount: dec!(5000),
+                    ..Default::default()
+                },
+                BalanceRecord {
+                    date: date("2023-06-30"),
+                    amount: dec!(1000),
+                    ..Default::default()
+                },
+                BalanceRecord {
+                    date: date("2023-11-30"),
+                    amount: dec!(3000),
+                    ..Default::default()
+                },
+            ],
+            imports: vec![],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_full_year() {
+        let account = create_test_account(Some("2020-05-01"), None);
+        let period = OpenPeriod::for_account(&account, 2023).unwrap();
+
+        assert!(period.is_full_year());
+        assert_eq!(
+            max_balance(&account, &period),
+            Some(Money::new(dec!(5000), "eur"))
+        );
+    }
+
+    #[test]
+    fn test_opened_mid_year() {
+        let account = create_test_account(Some("2023-03-15"), None);
+        let period = OpenPeriod::for_account(&account, 2023).unwrap();
+
+        assert!(!period.is_full_year());
+        assert_eq!(period.start, date("2023-03-15"));
+        assert_eq!(period.end, date("2023-12-31"));
+        // The January record predates the account opening
+        assert_eq!(
+            max_balance(&account, &period),
+            Some(Money::new(dec!(3000), "eur"))
+        );
+    }
+
+    #[test]
+    fn test_closed_mid_year() {
+        let account = create_test_account(None, Some("2023-07-31"));
+        let period = OpenPeriod::for_account(&account, 2023).unwrap();
+
+        assert_eq!(period.start, date("2023-01-01"));
+        assert_eq!(period.end, date("2023-07-31"));
+        assert_eq!(
+            max_balance(&account, &period),
+            Some(Money::new(dec!(5000), "eur"))
+        );
+    }
+
+    #[test]
+    fn test_mixed_currency_series() {
+        let point = |currency: &str| ValuePoint {
+            date: date("2023-01-31"),
+            value: Money::new(dec!(1), currency),
+        };
+
+        assert!(max_value(&[]).unwrap().is_none());
+        assert!(max_value(&[point("eur"), point("gbp")]).is_err());
+    }
+
+    #[test]
+    fn test_max_value_date() {
+        let point = |day: &str, amount| ValuePoint {
+            date: date(day),
+            value: Money::new(amount, "eur"),
+        };
+
+        // The first date the maximum was reached
+        let maximum = max_value(&[
+            point("2023-01-31", dec!(100)),
+            point("2023-02-28", dec!(300)),
+            point("2023-03-31", dec!(300)),
+            point("2023-04-30", dec!(200)),
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(maximum.value, Money::new(dec!(300), "eur"));
+        assert_eq!(maximum.date, date("2023-02-28"));
+    }
+
+    #[test]
+    fn test_max_of_converted() {
+        let point = |day: &str, amount| ValuePoint {
+            date: date(day),
+            value: Money::new(amount, "eur"),
+        };
+        let series = [
+            point("2023-01-31", dec!(1000)),
+            point("2023-06-30", dec!(900)),
+        ];
+        let mut rates = PriceSeries::new("eur", "usd");
+        rates.merge(vec![
+            PricePoint {
+                date: date("2023-01-01"),
+                price: dec!(1.05),
+            },
+            PricePoint {
+                date: date("2023-06-01"),
+                price: dec!(1.25),
+            },
+        ]);
+
+        // 900 EUR at 1.25 is worth more than 1000 EUR at 1.05
+        let maximum = max_of_converted(&series, &rates).unwrap().unwrap();
+        assert_eq!(maximum.max_balance, Money::new(dec!(900), "eur"));
+        assert_eq!(maximum.max_balance_usd, Money::usd(dec!(1125)));
+        assert_eq!(maximum.date, date("2023-06-30"));
+        assert_eq!(maximum.exchange_rate, 0.8);
+        assert_eq!(maximum.usd_price, Some(dec!(1.25)));
+
+        // Values before the first rate can't be converted
+        assert!(max_of_converted(&[point("2022-12-31", dec!(1))], &rates).is_err());
+    }
+
+    #[test]
+    fn test_not_open_during_year() {
+        let account = create_test_account(Some("2024-01-10"), None);
+        assert!(OpenPeriod::for_account(&account, 2023).is_none());
+
+        let account = create_test_account(None, Some("2022-12-31"));
+        assert!(OpenPeriod::for_account(&account, 2023).is_none());
+    }
+}
@@ -0,0 +1,208 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::snapshot::content_hash;
+use super::Report;
+use crate::data::UserData;
+use crate::report_context::ReportContext;
+
+/// Version of this crate, stamped on every report
+pub const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Everything needed to tell whether a report can be reproduced from the current inputs
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReportMetadata {
+    /// Deterministic ID derived from the fields below and the report's figures. Regenerating the report from the
+    /// same inputs with the same version gives the same ID.
+    pub report_id: String,
+    pub crate_version: String,
+    /// SHA-256 of the bundled IRS exchange rate facts
+    pub facts_hash: String,
+    /// SHA-256 of the user's data, imported statements, rate sets and prices
+    pub input_hash: String,
+    /// Tags the report was limited to, if any
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl ReportMetadata {
+    pub fn new(report: &Report, user_data: &UserData, context: &ReportContext) -> Result<Self> {
+        let facts_hash = context.facts_hash()?;
+        let input_hash = user_data.content_hash()?;
+        let report_id = hex::encode(Sha256::digest(format!(
+            "{}:{}:{}:{}:{}",
+            report.year,
+            CRATE_VERSION,
+            facts_hash,
+            input_hash,
+            content_hash(report)?
+        )))[..16]
+            .to_string();
+
+        Ok(Self {
+            report_id,
+            crate_version: CRATE_VERSION.to_string(),
+            facts_hash,
+            input_hash,
+            tags: Vec::new(),
+        })
+    }
+
+    pub fn with_tags(mut self, tags: &[String]) -> Self {
+        self.tags = tags.to_vec();
+        self
+    }
+}
+
+/// Recomputes a previously generated report from the current inputs and lists how it differs. An empty list means
+/// the report is reproduced exactly.
+pub fn verify(
+    stored: &Report,
+    user_data: &UserData,
+    context: &ReportContext,
+) -> Result<Vec<String>> {
+    let Some(stored_metadata) = &stored.metadata else {
+        return Ok(vec![
+            "Report has no metadata; it was generated by an older version".to_string(),
+        ]);
+    };
+    let recomputed =
+        Report::generate_tagged(stored.year, user_data, context, &stored_metadata.tags)?;
+    let metadata = recomputed
+        .metadata
+        .as_ref()
+        .expect("generated reports always have metadata");
+
+    let mut differences = Vec::new();
+    if stored_metadata.crate_version != metadata.crate_version {
+        differences.push(format!(
+            "Generated by version {}, now running {}",
+            stored_metadata.crate_version, metadata.crate_version
+        ));
+    }
+    if stored_metadata.facts_hash != metadata.facts_hash {
+        differences.push("Bundled exchange rate facts have changed".to_string());
+    }
+    if stored_metadata.input_hash != metadata.input_hash {
+        differences.push("Input data has changed".to_string());
+    }
+    if content_hash(stored)? != content_hash(&recomputed)? {
+        differences.push("Recomputed figures differ from the report's".to_string());
+    }
+    if differences.is_empty() && stored_metadata.report_id != metadata.report_id {
+        differences.push(format!(
+            "Report ID {} does not match recomputed ID {}",
+            stored_metadata.report_id, metadata.report_id
+        ));
+    }
+
+    Ok(differences)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Account, BalanceRecord};
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    fn create_test_context() -> ReportContext {
+        ReportContext::new(
+            Facts {
+                years: vec![AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![ExchangeRate::new("EUR".to_string(), 0.80).unwrap()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            None,
+        )
+    }
+
+    fn create_test_user_data() -> UserData {
+        UserData {
+            accounts: vec![Account {
+                handle: "savings".to_string(),
+                provider: "example_bank".to_string(),
+                currency_code: "EUR".to_string(),
+                balances: vec![BalanceRecord {
+                    date: NaiveDate::from_ymd_opt(2023, 1, 31).unwrap(),
+                    amount: dec!(800),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_report_id_is_deterministic() -> Result<()> {
+        let first = Report::generate(2023, &create_test_user_data(), &create_test_context())?;
+        let second = Report::generate(2023, &create_test_user_data(), &create_test_context())?;
+
+        let metadata = first.metadata.as_ref().unwrap();
+        assert_eq!(metadata.report_id.len(), 16);
+        assert_eq!(metadata.crate_version, CRATE_VERSION);
+        assert_eq!(first.metadata, second.metadata);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify() -> Result<()> {
+        let user_data = create_test_user_data();
+        let stored = Report::generate(2023, &user_data, &create_test_context())?;
+
+        assert!(verify(&stored, &user_data, &create_test_context())?.is_empty());
+
+        let mut changed = create_test_user_data();
+        changed.accounts[0].balances[0].amount = dec!(900);
+        assert_eq!(
+            verify(&stored, &changed, &create_test_context())?,
+            vec![
+                "Input data has changed".to_string(),
+                "Recomputed figures differ from the report's".to_string(),
+            ]
+        );
+
+        // Data for other accounts or years changes the input hash, but not the figures
+        let mut unrelated = create_test_user_data();
+        unrelated.accounts[0].balances.push(BalanceRecord {
+            date: NaiveDate::from_ymd_opt(2022, 1, 31).unwrap(),
+            amount: dec!(5),
+            ..Default::default()
+        });
+        assert_eq!(
+            verify(&stored, &unrelated, &create_test_context())?,
+            vec!["Input data has changed".to_string()]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_tagged_report() -> Result<()> {
+        let mut user_data = create_test_user_data();
+        user_data.accounts[0].tags = vec!["household".to_string()];
+        user_data.accounts.push(Account {
+            handle: "kids_savings".to_string(),
+            ..user_data.accounts[0].clone()
+        });
+        user_data.accounts[1].tags = vec!["kids".to_string()];
+
+        let tags = vec!["household".to_string()];
+        let stored = Report::generate_tagged(2023, &user_data, &create_test_context(), &tags)?;
+        assert_eq!(stored.accounts.len(), 1);
+        assert_eq!(stored.metadata.as_ref().unwrap().tags, tags);
+
+        // Changes to accounts outside the tag don't affect the report
+        user_data.accounts[1].balances[0].amount = dec!(900);
+        assert!(verify(&stored, &user_data, &create_test_context())?.is_empty());
+
+        Ok(())
+    }
+}
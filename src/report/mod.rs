@@ -0,0 +1,1802 @@
+pub mod cache;
+pub mod continuity;
+pub mod coverage;
+pub mod deadlines;
+pub mod history;
+pub mod interpolation;
+pub mod joint;
+pub mod max_balance;
+pub mod metadata;
+mod ordering;
+pub mod overrides;
+pub mod overview;
+pub mod precision;
+pub mod snapshot;
+pub mod summary;
+pub mod valuation;
+pub use self::cache::ReportCache;
+pub use self::continuity::{ChangeCheckConfig, DroppedAccount, SignificantChange};
+pub use self::deadlines::{FilingStatus, YearDeadline};
+pub use self::history::{ArchivedReport, HistoryStore};
+pub use self::interpolation::InterpolationPolicy;
+pub use self::joint::{JointOwner, SpousalFiling};
+pub use self::max_balance::{Maximum, MaximumPolicy, OpenPeriod, PolicyMaximum};
+pub use self::metadata::ReportMetadata;
+pub use self::overrides::RateOverride;
+pub use self::overview::{YearFigures, YearOverview};
+pub use self::precision::PrecisionCheck;
+pub use self::snapshot::{SnapshotStore, YearSnapshot};
+pub use self::summary::{AccountsSummary, ManyAccountsMode};
+pub use self::valuation::{
+    BalanceValuation, SurrenderValuation, Valuation, ValuePoint, WalletValuation,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::crypto::PriceSeries;
+use crate::data::{Account, AccountKind, Address, BalanceRecord, SignatureAuthority, UserData};
+use crate::diagnostics::{codes, Diagnostic, Diagnostics};
+use crate::facts::ExchangeRate;
+use crate::fincen::validate_report;
+use crate::money::{Money, USD};
+use crate::report_context::{Converter, RateSource, ReportContext};
+use crate::statements::Provenance;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Report {
+    pub year: i32,
+    /// Version and input hashes the report was generated from. Not part of the report's content hash.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<ReportMetadata>,
+    /// Set when the filer has 25 or more accounts and chose to file only their number. The accounts are still
+    /// listed, as the records the filer must keep.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<AccountsSummary>,
+    pub accounts: Vec<AccountReport>,
+    /// Exchange rates used in place of the IRS rate, with the difference each made to the maximum values
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub overridden_rates: Vec<RateOverride>,
+    /// Imported statements that contributed balance records to this report
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub provenance: Vec<ProvenanceEntry>,
+    /// Appendix of accounts open during the year that were deliberately left out of the FBAR
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excluded: Vec<ExcludedAccount>,
+    /// Owners of the accounts reported in Part V, through whom the filer has a financial interest in them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entities: Vec<EntityOwner>,
+    /// Joint owners of the accounts reported in Part III
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub joint_owners: Vec<JointOwner>,
+    /// Set when the two configured filers jointly own every account, so one can file for both
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spousal_filing: Option<SpousalFiling>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AccountReport {
+    pub handle: String,
+    pub provider: String,
+    pub currency_code: String,
+    /// Maximum balance during the year, in the account's currency
+    pub max_balance: Money,
+    /// Maximum balance during the year, converted to USD
+    pub max_balance_usd: Money,
+    /// The first date the account held its maximum balance. Not set in reports from before dates were recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_balance_date: Option<NaiveDate>,
+    /// Which rate source supplied the exchange rate used for the conversion
+    pub rate_source: RateSource,
+    /// Set when the source was pinned to the account's currency in `rate_pins`, rather than being the first in
+    /// precedence with a rate
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub rate_pinned: bool,
+    /// Units of the account's currency per USD the maximum was converted at; not set for USD accounts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exchange_rate: Option<f64>,
+    /// USD per unit of the account's currency, when the maximum was converted at a daily rate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usd_price: Option<Decimal>,
+    /// The maximum under the other `MaximumPolicy`, for comparison with the one filed; only set when the account's
+    /// currency has daily USD rates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub other_maximum: Option<PolicyMaximum>,
+    /// Set when only part of the year counts, because the account was only open for part of it or the filer became a
+    /// US person during it; the maximum only covers this period
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_period: Option<OpenPeriod>,
+    /// Notes for the filer: the account's own notes, then how its figures were derived
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<String>,
+    /// Handle of the entity the account is held through. Such accounts are reported in Part V rather than Part II.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity: Option<String>,
+    /// The account's owner and the filer's title with them, when the filer only has signature authority over the
+    /// account. Such accounts are reported in Part IV.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature_authority: Option<SignatureAuthority>,
+    /// Handle of the filer the account is jointly owned with. Such accounts are reported in Part III.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub joint_owner: Option<String>,
+    /// Interpolation policy used to estimate values at the edges of the year, when not `none`. The estimates are
+    /// described in the notes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interpolation: Option<InterpolationPolicy>,
+}
+
+impl AccountReport {
+    /// The maximum value in USD before rounding to the cent: the maximum converted again at the rate used, or the
+    /// USD value itself when there's no rate or it came from the record. A daily rate is applied as the USD price it
+    /// was given as, since its reciprocal in `exchange_rate` is only accurate to float precision.
+    pub fn unrounded_max_balance_usd(&self) -> Result<Money> {
+        match (self.exchange_rate, &self.rate_source, self.usd_price) {
+            (_, RateSource::DailyRate { .. }, Some(price)) => {
+                Ok(Money::usd(self.max_balance.amount * price))
+            }
+            (None, _, _)
+            | (_, RateSource::RecordProvided { .. } | RateSource::DailyRate { .. }, _) => {
+                Ok(self.max_balance_usd.clone())
+            }
+            (Some(rate), _, _) => ExchangeRate::new(self.currency_code.clone(), rate)
+                .map_err(|err| anyhow!("{}: {}", self.handle, err))?
+                .convert_to_usd_unrounded(&self.max_balance),
+        }
+    }
+
+    /// The part of the FBAR the account is reported in
+    pub fn part(&self) -> &'static str {
+        if self.entity.is_some() {
+            "Part V"
+        } else if self.signature_authority.is_some() {
+            "Part IV"
+        } else if self.joint_owner.is_some() {
+            "Part III"
+        } else {
+            "Part II"
+        }
+    }
+
+    /// The value filed for the account: the unrounded maximum in USD, rounded up to the next whole dollar, so a
+    /// maximum of 1250.004 is filed as 1251 rather than rounding to 1250.00 first
+    pub fn filed_value(&self) -> Result<Decimal> {
+        Ok(crate::fincen::xml::whole_dollars(
+            self.unrounded_max_balance_usd()?.amount,
+        ))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ProvenanceEntry {
+    pub account: String,
+    #[serde(flatten)]
+    pub provenance: Provenance,
+    /// Number of the statement's records falling within the report year
+    pub records: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ExcludedAccount {
+    pub handle: String,
+    pub provider: String,
+    pub reason: String,
+}
+
+/// The corporate owner details Part V asks for alongside each account held through an entity
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EntityOwner {
+    pub handle: String,
+    pub name: String,
+    pub address: Address,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tin: Option<String>,
+    pub ownership_percent: Decimal,
+}
+
+impl Report {
+    /// Computes the report for a single year from the user's data
+    ///
+    /// Accounts that were not open during the year, or have no balance records during the part of it they were open,
+    /// are left out of the report. Accounts excluded from the FBAR are listed with their reasons instead.
+    pub fn generate(year: i32, user_data: &UserData, context: &ReportContext) -> Result<Self> {
+        Self::generate_tagged(year, user_data, context, &[])
+    }
+
+    /// Computes the report like `generate`, collecting problems instead of stopping at the first: the report's
+    /// validation issues when it can be computed, and otherwise every account that can't be reported and why
+    pub fn diagnose(
+        year: i32,
+        user_data: &UserData,
+        context: &ReportContext,
+    ) -> (Option<Self>, Diagnostics) {
+        let err = match Self::generate(year, user_data, context) {
+            Ok(report) => {
+                let diagnostics = validate_report(&report, user_data)
+                    .iter()
+                    .map(Diagnostic::from)
+                    .collect();
+                return (Some(report), diagnostics);
+            }
+            Err(err) => err,
+        };
+
+        // Each account on its own, to find every one that fails rather than only the first
+        let mut diagnostics: Diagnostics = user_data
+            .accounts
+            .iter()
+            .filter_map(|account| {
+                let alone = UserData {
+                    accounts: vec![account.clone()],
+                    ..user_data.clone()
+                };
+                Self::generate(year, &alone, context).err().map(|err| {
+                    Diagnostic::error(codes::REPORT_FAILED, format!("{:#}", err))
+                        .at(account.handle.clone())
+                })
+            })
+            .collect();
+        // A problem with the year or the filer rather than any one account
+        if diagnostics.is_empty() {
+            diagnostics.push(Diagnostic::error(
+                codes::REPORT_FAILED,
+                format!("{:#}", err),
+            ));
+        }
+        (None, diagnostics)
+    }
+
+    /// Computes the report for only the accounts with at least one of the tags. The tags are recorded in the report's
+    /// metadata so it can be verified later.
+    pub fn generate_tagged(
+        year: i32,
+        user_data: &UserData,
+        context: &ReportContext,
+        tags: &[String],
+    ) -> Result<Self> {
+        let _span = tracing::info_span!("report", year).entered();
+        let user_data = &user_data.with_tags(tags);
+        let residency = match user_data.us_person_since {
+            Some(since) if since.year() > year => bail!(
+                "The filer became a US person on {}, after {}; no FBAR is due for {}",
+                since,
+                year,
+                year
+            ),
+            Some(since) if since.year() == year && since.ordinal() > 1 => Some(since),
+            _ => None,
+        };
+        if let Some((_, account)) = user_data
+            .archived_accounts
+            .iter()
+            .find(|(_, account)| OpenPeriod::for_account(account, year).is_some())
+        {
+            bail!(
+                "Account {} is archived but was open in {}; pass --include-archived to report the year",
+                account.handle,
+                year
+            );
+        }
+        let mut accounts = Vec::new();
+        let mut provenance = Vec::new();
+        let mut excluded = Vec::new();
+        let shared = match context.cache() {
+            Some(_) => ReportCache::shared_inputs_hash(user_data, context)?,
+            None => String::new(),
+        };
+
+        let progress = context
+            .progress()
+            .bar(user_data.accounts.len(), &year.to_string());
+        for account in &user_data.accounts {
+            progress.set_message(account.handle.clone());
+            progress.inc(1);
+            let open_period = OpenPeriod::for_account(account, year);
+            let period = match residency {
+                Some(since) => open_period.and_then(|period| period.starting_from(since)),
+                None => open_period,
+            };
+            let entity = user_data.entity_for(account)?;
+
+            if account.exclude_from_fbar {
+                if period.is_some() {
+                    excluded.push(ExcludedAccount {
+                        handle: account.handle.clone(),
+                        provider: account.provider.clone(),
+                        reason: account.exclusion_reason.clone().unwrap_or_default(),
+                    });
+                }
+                continue;
+            }
+            if !user_data.owned_by_primary(account) {
+                if period.is_some() {
+                    excluded.push(ExcludedAccount {
+                        handle: account.handle.clone(),
+                        provider: account.provider.clone(),
+                        reason: format!(
+                            "Owned by {} without the filer; reported on their own FBAR",
+                            account.owners.join(" and ")
+                        ),
+                    });
+                }
+                continue;
+            }
+            if let Some(entity) = entity.filter(|entity| !entity.is_controlled()) {
+                if period.is_some() {
+                    excluded.push(ExcludedAccount {
+                        handle: account.handle.clone(),
+                        provider: account.provider.clone(),
+                        reason: format!(
+                            "Held through {}, of which the filer owns {}%; only more than 50% ownership gives a \
+                             financial interest in its accounts",
+                            entity.name, entity.ownership_percent
+                        ),
+                    });
+                }
+                continue;
+            }
+
+            if let (Some(since), Some(_), None) = (residency, open_period, period) {
+                excluded.push(ExcludedAccount {
+                    handle: account.handle.clone(),
+                    provider: account.provider.clone(),
+                    reason: format!("Closed before the filer became a US person on {}", since),
+                });
+                continue;
+            }
+
+            if period.is_some() && account.currency_unknown() {
+                bail!(
+                    "Account {} has no currency yet: set its `currency_code` to the currency it's held in",
+                    account.handle
+                );
+            }
+
+            let (maximum, daily_maximum, derivation_notes, estimated) = match &period {
+                Some(period) => {
+                    let computed = account_maximum(account, period, user_data, context, &shared)?;
+                    (
+                        computed.maximum,
+                        computed.converted,
+                        computed.notes,
+                        computed.estimated,
+                    )
+                }
+                None => (None, None, Vec::new(), Vec::new()),
+            };
+
+            if let (
+                Some(period),
+                Some(Maximum {
+                    value: max_balance,
+                    date: max_balance_date,
+                }),
+            ) = (period, maximum)
+            {
+                let partial_period = (!period.is_full_year()).then_some(period);
+                let mut notes: Vec<String> = account.notes.iter().cloned().collect();
+                if let Some(since) = residency.filter(|since| period.start == *since) {
+                    notes.push(format!(
+                        "First-year filing: the filer became a US person on {}, so values before then are left \
+                         out of the maximum",
+                        since
+                    ));
+                }
+                notes.extend(nonresident_notes(user_data, &period));
+                notes.extend(
+                    open_period
+                        .filter(|open| !open.is_full_year())
+                        .map(open_period_notes)
+                        .unwrap_or_default(),
+                );
+                notes.extend(derivation_notes);
+                notes.extend(manual_records_note(account, &period));
+                if account.kind == AccountKind::CryptoExchange {
+                    notes.push(
+                        "Crypto exchange account valued from imported asset prices. Accounts holding only virtual \
+                         currency are not currently reportable; it is included voluntarily."
+                            .to_string(),
+                    );
+                }
+                if account.kind == AccountKind::Insurance {
+                    notes.push(
+                        "Foreign life insurance policy or annuity, reported at its cash surrender value".to_string(),
+                    );
+                }
+                if account.valuation_name() == valuation::WALLET_VALUATION {
+                    notes.push(wallet_note(account, &period, context)?);
+                }
+                if let Some(entity) = entity {
+                    notes.push(format!(
+                        "Reported in Part V: held through {}, of which the filer owns {}%",
+                        entity.name, entity.ownership_percent
+                    ));
+                }
+                if let Some(authority) = &account.signature_authority {
+                    notes.push(format!(
+                        "Reported in Part IV: the filer can sign for it as {} of {}, but has no financial interest in it",
+                        authority.title, authority.owner
+                    ));
+                }
+                let _span = tracing::debug_span!("convert", account = %account.handle).entered();
+                let rate = context.find_exchange_rate(year, &account.currency_code)?;
+                let max_balance_usd = rate.convert_to_usd(&max_balance)?;
+                if let Some(custom) = context.currency(&account.currency_code) {
+                    notes.push(format!(
+                        "{} is {}, a currency defined in fact_extensions, written to {} decimal places",
+                        custom.code.to_uppercase(),
+                        custom.name,
+                        custom.minor_units
+                    ));
+                }
+                if rate.is_pinned() {
+                    notes.push(format!(
+                        "Converted at the {}, pinned to {} in rate_pins",
+                        rate.source(),
+                        account.currency_code.to_uppercase()
+                    ));
+                }
+                if let RateSource::NearestYear {
+                    year: rate_year,
+                    source,
+                } = rate.source()
+                {
+                    notes.push(format!(
+                        "No {currency} rate for {}: converted at {} {currency} per USD, the {} for {}, under the \
+                         nearest-year rate fallback. Check it before filing.",
+                        year,
+                        rate.rate,
+                        source,
+                        rate_year,
+                        currency = account.currency_code.to_uppercase(),
+                    ));
+                }
+                tracing::debug!(
+                    %max_balance,
+                    %max_balance_usd,
+                    rate = rate.rate,
+                    source = ?rate.source(),
+                    "Converted maximum"
+                );
+
+                let converted_maximum = PolicyMaximum {
+                    policy: MaximumPolicy::ConvertMaximum,
+                    max_balance,
+                    max_balance_usd,
+                    date: max_balance_date,
+                    exchange_rate: rate.rate,
+                    usd_price: None,
+                };
+                let (mut filed, other_maximum) = match (user_data.maximum_policy, daily_maximum) {
+                    (MaximumPolicy::MaxOfConverted, Some(daily)) => {
+                        (daily, Some(converted_maximum))
+                    }
+                    (_, daily) => (converted_maximum, daily),
+                };
+                notes.extend(maximum_policy_notes(
+                    account,
+                    user_data.maximum_policy,
+                    &filed,
+                    other_maximum.as_ref(),
+                    rate.source(),
+                ));
+                let daily = filed.policy == MaximumPolicy::MaxOfConverted;
+                let record_provided = match rate.source() {
+                    RateSource::NotConverted => false,
+                    _ => record_provided(account, &mut filed, &estimated, &mut notes)?,
+                };
+
+                accounts.push(AccountReport {
+                    handle: account.handle.clone(),
+                    provider: account.provider.clone(),
+                    currency_code: account.currency_code.to_lowercase(),
+                    max_balance_usd: filed.max_balance_usd,
+                    max_balance: filed.max_balance,
+                    max_balance_date: Some(filed.date),
+                    rate_source: match (record_provided, daily) {
+                        (true, _) => RateSource::RecordProvided { date: filed.date },
+                        (false, true) => RateSource::DailyRate { date: filed.date },
+                        (false, false) => rate.source().clone(),
+                    },
+                    rate_pinned: rate.is_pinned() && !daily && !record_provided,
+                    exchange_rate: Some(filed.exchange_rate)
+                        .filter(|_| *rate.source() != RateSource::NotConverted),
+                    usd_price: filed.usd_price,
+                    other_maximum,
+                    open_period: partial_period,
+                    notes,
+                    entity: account.entity.clone(),
+                    signature_authority: account.signature_authority.clone(),
+                    joint_owner: joint::joint_owner(account, user_data),
+                    interpolation: Some(user_data.interpolation_for(account))
+                        .filter(|policy| *policy != InterpolationPolicy::None),
+                });
+            }
+
+            for import in &account.imports {
+                let records = import
+                    .balances
+                    .iter()
+                    .filter(|record| record.date.year() == year)
+                    .count();
+
+                if records > 0 {
+                    provenance.push(ProvenanceEntry {
+                        account: account.handle.clone(),
+                        provenance: import.provenance.clone(),
+                        records,
+                    });
+                }
+            }
+        }
+
+        progress.finish_and_clear();
+
+        let entities = user_data
+            .entities
+            .iter()
+            .filter(|entity| {
+                accounts
+                    .iter()
+                    .any(|account| account.entity.as_ref() == Some(&entity.handle))
+            })
+            .map(|entity| EntityOwner {
+                handle: entity.handle.clone(),
+                name: entity.name.clone(),
+                address: entity.address.clone(),
+                tin: entity.tin.clone(),
+                ownership_percent: entity.ownership_percent,
+            })
+            .collect();
+
+        let overridden_rates = overrides::rate_overrides(year, &accounts, context)?;
+        let mut report = Self {
+            year,
+            metadata: None,
+            summary: None,
+            accounts,
+            overridden_rates,
+            provenance,
+            excluded,
+            entities,
+            joint_owners: Vec::new(),
+            spousal_filing: joint::spousal_filing(year, user_data),
+        };
+        report.joint_owners = joint::joint_owners(&report, user_data);
+        report.sort();
+        if user_data.many_accounts == Some(ManyAccountsMode::Summary) {
+            report.summary =
+                Some(AccountsSummary::for_report(&report)).filter(AccountsSummary::qualifies);
+        }
+        report.metadata = Some(ReportMetadata::new(&report, user_data, context)?.with_tags(tags));
+
+        Ok(report)
+    }
+}
+
+/// The account's maximum value over its open period and when it was reached, and the maximum of its values at daily
+/// rates if it has them, with notes on how values were converted and estimated. Served from the context's cache when
+/// the account's inputs haven't changed.
+fn account_maximum(
+    account: &Account,
+    period: &OpenPeriod,
+    user_data: &UserData,
+    context: &ReportContext,
+    shared: &str,
+) -> Result<cache::CachedMaximum> {
+    let year = period.start.year();
+    let key = match context.cache() {
+        Some(cache) => {
+            let key = ReportCache::key(account, year, shared)?;
+            if let Some(cached) = cache.get(&key) {
+                tracing::debug!(account = %account.handle, "Using cached maximum");
+                return Ok(cached);
+            }
+            Some(key)
+        }
+        None => None,
+    };
+
+    let policy = user_data.interpolation_for(account);
+    let valuation = context.valuation_for(account)?;
+    let series = valuation.value_series(account, period, context)?;
+    let (mut series, mut notes) = convert_to_account_currency(series, account, year, context)?;
+
+    let (before, after) = interpolation::neighbours(valuation, account, period, context, policy)?;
+    let neighbours = before.iter().chain(after.iter()).cloned().collect();
+    let (neighbours, _) = convert_to_account_currency(neighbours, account, year, context)?;
+    let mut neighbours = neighbours.into_iter();
+    let before = before.is_some().then(|| neighbours.next()).flatten();
+    let after = after.is_some().then(|| neighbours.next()).flatten();
+    series.sort_by_key(|point| point.date);
+    let recorded: Vec<NaiveDate> = series.iter().map(|point| point.date).collect();
+    let (series, interpolation_notes) =
+        interpolation::interpolate(series, before, after, period, policy)?;
+    notes.extend(interpolation_notes);
+    let estimated = series
+        .iter()
+        .map(|point| point.date)
+        .filter(|date| !recorded.contains(date))
+        .collect();
+
+    let maximum = max_balance::max_value(&series)
+        .with_context(|| format!("Invalid values for account {}", account.handle))?;
+    let converted = match daily_rates(account, user_data) {
+        Some(rates) => max_balance::max_of_converted(&series, rates).with_context(|| {
+            format!(
+                "Can't convert account {}'s values at daily rates",
+                account.handle
+            )
+        })?,
+        None => None,
+    };
+
+    let computed = cache::CachedMaximum {
+        maximum,
+        converted,
+        notes,
+        estimated,
+    };
+    if let (Some(cache), Some(key)) = (context.cache(), key) {
+        cache.put(&key, &computed)?;
+    }
+    Ok(computed)
+}
+
+/// The daily USD rates for the account's currency: a price series for the currency, quoted in USD, in the `prices`
+/// directory
+fn daily_rates<'a>(account: &Account, user_data: &'a UserData) -> Option<&'a PriceSeries> {
+    let currency = account.currency_code.to_lowercase();
+    if currency == USD {
+        return None;
+    }
+    user_data
+        .price_series
+        .iter()
+        .find(|series| series.asset == currency && series.currency == USD)
+}
+
+/// Notes comparing the maximum filed with the one under the other maximum policy, or explaining why there's none
+fn maximum_policy_notes(
+    account: &Account,
+    policy: MaximumPolicy,
+    filed: &PolicyMaximum,
+    other: Option<&PolicyMaximum>,
+    source: &RateSource,
+) -> Option<String> {
+    match (filed.policy, other) {
+        (MaximumPolicy::MaxOfConverted, Some(other)) => Some(format!(
+            "Filed at the maximum of its values converted at daily rates, {} ({}) on {}, under the max_of_converted \
+             maximum policy; its maximum balance, {} on {}, is {} at the {}",
+            filed.max_balance_usd,
+            filed.max_balance,
+            filed.date,
+            other.max_balance,
+            other.date,
+            other.max_balance_usd,
+            source
+        )),
+        (_, Some(other)) => Some(format!(
+            "Converted at daily rates, its values peak at {} ({}) on {}, against the {} filed for its maximum \
+             balance under the convert_maximum maximum policy",
+            other.max_balance_usd, other.max_balance, other.date, filed.max_balance_usd
+        )),
+        (_, None) if policy == MaximumPolicy::MaxOfConverted && *source != RateSource::NotConverted => {
+            Some(format!(
+                "No daily USD rates for {} in the prices directory, so its maximum balance is converted at the {} \
+                 despite the max_of_converted maximum policy",
+                account.currency_code.to_uppercase(),
+                source
+            ))
+        }
+        (_, None) => None,
+    }
+}
+
+/// Files the USD value the provider gave for the maximum in place of the converted value, noting the value it
+/// replaces. Only a recorded maximum qualifies, never one estimated by interpolation: for an account valued by its
+/// balances, a record on the date of the maximum for exactly its amount, in the account's currency; for a wallet,
+/// records on that date for each of its sub-balances, every one with a USD value, which are summed.
+fn record_provided(
+    account: &Account,
+    filed: &mut PolicyMaximum,
+    estimated: &[NaiveDate],
+    notes: &mut Vec<String>,
+) -> Result<bool> {
+    if estimated.contains(&filed.date) {
+        return Ok(false);
+    }
+    let usd = match account.valuation_name() {
+        valuation::BALANCE_VALUATION => balance_usd_value(account, &filed.max_balance, filed.date)?,
+        valuation::WALLET_VALUATION => wallet_usd_value(account, filed.date)?,
+        _ => None,
+    };
+    let Some(usd) = usd else {
+        return Ok(false);
+    };
+    let exchange_rate = filed
+        .max_balance
+        .amount
+        .checked_div(usd)
+        .and_then(|rate| rate.to_f64())
+        .ok_or_else(|| anyhow!("Invalid USD value {} on the record for {}", usd, filed.date))?;
+    let usd = Money::usd(usd).round();
+    notes.push(format!(
+        "Filed at {}, the USD value given on its balance record for {}, rather than the {} converted",
+        usd, filed.date, filed.max_balance_usd
+    ));
+    filed.max_balance_usd = usd;
+    filed.exchange_rate = exchange_rate;
+    filed.usd_price = None;
+    Ok(true)
+}
+
+/// The USD value on the record of the balance on the date, if one gives it
+fn balance_usd_value(
+    account: &Account,
+    balance: &Money,
+    date: NaiveDate,
+) -> Result<Option<Decimal>> {
+    for record in account.balances_in_year(date.year()).filter(|record| {
+        record.date == date
+            && record.amount == balance.amount
+            && balance.is_currency(record.currency.as_deref().unwrap_or(&account.currency_code))
+    }) {
+        if let Some(usd) = record.usd_equivalent()? {
+            return Ok(Some(usd));
+        }
+    }
+    Ok(None)
+}
+
+/// The wallet's value on the date in USD, summed from the USD values of its sub-balances, if every currency with a
+/// balance then was recorded on the date with one
+fn wallet_usd_value(account: &Account, date: NaiveDate) -> Result<Option<Decimal>> {
+    let mut records: Vec<_> = account
+        .balances_in_year(date.year())
+        .filter(|record| record.date <= date)
+        .collect();
+    records.sort_by_key(|record| record.date);
+    // Each currency's latest sub-balance, as the wallet valuation carries them forward
+    let mut sub_balances: BTreeMap<String, &BalanceRecord> = BTreeMap::new();
+    for record in records {
+        let currency = record
+            .currency
+            .as_deref()
+            .unwrap_or(&account.currency_code)
+            .to_lowercase();
+        sub_balances.insert(currency, record);
+    }
+
+    let mut total = Decimal::ZERO;
+    for record in sub_balances.values() {
+        if record.amount.is_zero() {
+            continue;
+        }
+        match record.usd_equivalent()? {
+            Some(usd) if record.date == date => total += usd,
+            _ => return Ok(None),
+        }
+    }
+    Ok((!sub_balances.is_empty()).then_some(total))
+}
+
+/// Converts values recorded in another currency into the account's, via USD at the year's rates, with a note
+/// describing each currency's conversion
+fn convert_to_account_currency(
+    series: Vec<ValuePoint>,
+    account: &Account,
+    year: i32,
+    context: &ReportContext,
+) -> Result<(Vec<ValuePoint>, Vec<String>)> {
+    let account_currency = account.currency_code.to_lowercase();
+    // Counts of converted values per currency, in the order first seen
+    let mut converted: Vec<(String, usize)> = Vec::new();
+
+    // Values in other currencies go to USD in one batch, sharing rate lookups, then on to the account's currency
+    let in_usd = context.convert_many(
+        year,
+        series
+            .iter()
+            .filter(|point| point.value.currency != account_currency)
+            .map(|point| (&point.value.currency, point.value.amount)),
+    );
+    let mut in_usd = in_usd.into_iter();
+    let to = context.find_exchange_rate(year, &account_currency);
+
+    let series = series
+        .into_iter()
+        .map(|point| {
+            if point.value.currency == account_currency {
+                return Ok(point);
+            }
+
+            let usd = in_usd
+                .next()
+                .expect("a USD value was converted for every value in another currency")?;
+            let value = to
+                .as_ref()
+                .map_err(|err| anyhow!("{}", err))?
+                .convert_from_usd_unrounded(&usd)?
+                .round_to(context.minor_units(&account_currency));
+
+            match converted
+                .iter_mut()
+                .find(|(currency, _)| *currency == point.value.currency)
+            {
+                Some((_, count)) => *count += 1,
+                None => converted.push((point.value.currency.clone(), 1)),
+            }
+            Ok(ValuePoint {
+                date: point.date,
+                value,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let notes = converted
+        .into_iter()
+        .map(|(currency, count)| {
+            let from = context.find_exchange_rate(year, &currency)?;
+            let to = context.find_exchange_rate(year, &account_currency)?;
+            let via = if account_currency == USD {
+                String::new()
+            } else {
+                format!(" then {}", describe_rate(&to))
+            };
+
+            Ok(format!(
+                "{} balance record(s) in {} converted to {} at {}{}",
+                count,
+                currency.to_uppercase(),
+                account_currency.to_uppercase(),
+                describe_rate(&from),
+                via
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok((series, notes))
+}
+
+/// Describes how a wallet's sub-balances were combined, naming each currency's rate
+fn wallet_note(account: &Account, period: &OpenPeriod, context: &ReportContext) -> Result<String> {
+    let mut currencies: Vec<String> = account
+        .balances_in_year(period.start.year())
+        .filter(|record| period.contains(record.date))
+        .map(|record| {
+            record
+                .currency
+                .as_deref()
+                .unwrap_or(&account.currency_code)
+                .to_lowercase()
+        })
+        .collect();
+    currencies.retain(|currency| currency != USD);
+    currencies.sort();
+    currencies.dedup();
+
+    let rates = currencies
+        .iter()
+        .map(|currency| {
+            Ok(describe_rate(
+                &context.find_exchange_rate(period.start.year(), currency)?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut note =
+        "Multi-currency wallet: the latest sub-balance in each currency was converted to USD and \
+                    summed on each date"
+            .to_string();
+    if !rates.is_empty() {
+        note.push_str(&format!(", at {}", rates.join(", ")));
+    }
+    Ok(note)
+}
+
+/// The rate and where it came from, e.g. `0.8 GBP per USD (IRS rate)`
+pub(crate) fn describe_rate(converter: &Converter) -> String {
+    format!(
+        "{} {} per USD ({})",
+        converter.rate,
+        converter.currency_code.to_uppercase(),
+        converter.source()
+    )
+}
+
+/// Notes on the times during the period the filer wasn't a US person. The maximum is left covering them, as FinCEN
+/// asks for the maximum during the calendar year and doesn't address dual-status years.
+fn nonresident_notes(user_data: &UserData, period: &OpenPeriod) -> Vec<String> {
+    let year = period.start.year();
+    if user_data.nonresident_throughout(period.start, period.end) {
+        return vec![format!(
+            "The filer was not a US person at any time the account was open in {}. It's reported as given, but an \
+             FBAR only covers accounts held while a US person; check with your tax advisor whether it belongs on \
+             this one.",
+            year
+        )];
+    }
+    user_data
+        .nonresident_between(period.start, period.end)
+        .into_iter()
+        .map(|(from, to, nonresident)| {
+            let reason = nonresident
+                .reason
+                .as_ref()
+                .map(|reason| format!(" ({})", reason))
+                .unwrap_or_default();
+            format!(
+                "Dual-status year: the filer was not a US person from {} to {}{}. The maximum still covers that \
+                 time, as FinCEN asks for the maximum during the calendar year; check with your tax advisor whether \
+                 it should.",
+                from, to, reason
+            )
+        })
+        .collect()
+}
+
+/// A note listing the balances over the period that were entered by hand, with where each came from
+fn manual_records_note(account: &Account, period: &OpenPeriod) -> Option<String> {
+    let records: Vec<String> = account
+        .balances
+        .iter()
+        .filter(|record| record.manual && (period.start..=period.end).contains(&record.date))
+        .map(|record| {
+            let currency = record.currency.as_ref().unwrap_or(&account.currency_code);
+            format!(
+                "{} on {} ({})",
+                Money::new(record.amount, currency.to_lowercase()),
+                record.date,
+                record.note.as_deref().unwrap_or_default()
+            )
+        })
+        .collect();
+    (!records.is_empty()).then(|| {
+        format!(
+            "Balances entered by hand rather than imported: {}",
+            records.join("; ")
+        )
+    })
+}
+
+fn open_period_notes(period: OpenPeriod) -> Vec<String> {
+    vec![format!(
+        "Account was only open from {} to {}; the maximum value is computed over that period. \
+         The account must still be reported for the year.",
+        period.start, period.end
+    )]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Account, BalanceRecord, Entity, NonresidentPeriod, SurrenderValueRecord};
+    use crate::facts::{AnnualFact, CurrencyDefinition, ExchangeRate, Facts};
+    use crate::statements::StatementImport;
+    use chrono::{NaiveDate, Utc};
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use std::collections::BTreeMap;
+
+    fn create_test_context_facts() -> Facts {
+        Facts {
+            years: vec![AnnualFact {
+                year: 2023,
+                exchange_rates: vec![ExchangeRate::new("EUR".to_string(), 0.80).unwrap()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn create_test_context() -> ReportContext {
+        ReportContext::new(create_test_context_facts(), None)
+    }
+
+    fn balance(date: &str, amount: Decimal) -> BalanceRecord {
+        BalanceRecord {
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            amount,
+            ..Default::default()
+        }
+    }
+
+    fn account_report<'a>(report: &'a Report, handle: &str) -> &'a AccountReport {
+        report
+            .accounts
+            .iter()
+            .find(|account| account.handle == handle)
+            .unwrap()
+    }
+
+    fn create_test_user_data() -> UserData {
+        UserData {
+            accounts: vec![
+                Account {
+                    handle: "savings".to_string(),
+                    provider: "example_bank".to_string(),
+                    currency_code: "EUR".to_string(),
+                    opening_date: None,
+                    closing_date: None,
+                    balances: vec![
+                        balance("2022-12-31", dec!(9000)),
+                        balance("2023-01-31", dec!(800)),
+                        balance("2023-02-28", dec!(1600)),
+                        balance("2023-03-31", dec!(400)),
+                    ],
+                    imports: vec![StatementImport {
+                        provenance: Provenance {
+                            source_file: "april.csv".to_string(),
+                            imported_at: Utc::now(),
+                            sha256: "abc123".to_string(),
+                            profile: None,
+                        },
+                        balances: vec![
+                            balance("2023-04-30", dec!(1200)),
+                            balance("2024-01-31", dec!(5)),
+                        ],
+                    }],
+                    ..Default::default()
+                },
+                Account {
+                    handle: "dormant".to_string(),
+                    provider: "example_bank".to_string(),
+                    currency_code: "EUR".to_string(),
+                    opening_date: None,
+                    closing_date: None,
+                    balances: vec![balance("2021-06-30", dec!(100))],
+                    imports: vec![],
+                    ..Default::default()
+                },
+                Account {
+                    handle: "closed".to_string(),
+                    provider: "example_bank".to_string(),
+                    currency_code: "EUR".to_string(),
+                    opening_date: None,
+                    closing_date: NaiveDate::from_ymd_opt(2023, 5, 31),
+                    balances: vec![
+                        balance("2023-03-31", dec!(400)),
+                        // Recorded after the closing date, so ignored
+                        balance("2023-08-31", dec!(9999)),
+                    ],
+                    imports: vec![],
+                    ..Default::default()
+                },
+                Account {
+                    handle: "us_brokerage".to_string(),
+                    provider: "us_bank".to_string(),
+                    currency_code: "EUR".to_string(),
+                    exclude_from_fbar: true,
+                    exclusion_reason: Some("US-based account".to_string()),
+                    balances: vec![balance("2023-03-31", dec!(50000))],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_generate_report() -> Result<()> {
+        let report = Report::generate(2023, &create_test_user_data(), &create_test_context())?;
+
+        assert_eq!(report.year, 2023);
+        // The dormant account has no records in 2023
+        assert_eq!(report.accounts.len(), 2);
+
+        let savings = account_report(&report, "savings");
+        assert_eq!(savings.handle, "savings");
+        assert_eq!(savings.currency_code, "eur");
+        assert_eq!(savings.max_balance, Money::new(dec!(1600), "eur"));
+        assert_eq!(savings.max_balance_usd, Money::usd(dec!(2000)));
+        assert_eq!(
+            savings.max_balance_date,
+            NaiveDate::from_ymd_opt(2023, 2, 28)
+        );
+        assert_eq!(savings.rate_source, RateSource::IrsProvided);
+        assert_eq!(savings.open_period, None);
+        assert!(savings.notes.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_records_in_another_currency() -> Result<()> {
+        let facts = Facts {
+            years: vec![AnnualFact {
+                year: 2023,
+                exchange_rates: vec![
+                    ExchangeRate::new("EUR".to_string(), 0.80).unwrap(),
+                    ExchangeRate::new("GBP".to_string(), 0.50).unwrap(),
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut user_data = create_test_user_data();
+        user_data.accounts[0].balances.push(BalanceRecord {
+            currency: Some("gbp".to_string()),
+            ..balance("2023-07-31", dec!(1500))
+        });
+
+        let report = Report::generate(2023, &user_data, &ReportContext::new(facts, None))?;
+
+        // 1500 GBP is 3000 USD, or 2400 EUR
+        let savings = account_report(&report, "savings");
+        assert_eq!(savings.max_balance, Money::new(dec!(2400), "eur"));
+        assert_eq!(savings.max_balance_usd, Money::usd(dec!(3000)));
+        assert_eq!(
+            savings.notes,
+            vec![
+                "1 balance record(s) in GBP converted to EUR at 0.5 GBP per USD (IRS rate) then 0.8 EUR per USD \
+                 (IRS rate)"
+                    .to_string()
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_currency() -> Result<()> {
+        let extensions = Facts {
+            years: vec![AnnualFact {
+                year: 2023,
+                exchange_rates: vec![ExchangeRate::new("GLDOZ".to_string(), 0.0005).unwrap()],
+                ..Default::default()
+            }],
+            currencies: vec![CurrencyDefinition {
+                code: "gldoz".to_string(),
+                name: "troy ounces of gold".to_string(),
+                minor_units: 3,
+            }],
+        };
+        let context = ReportContext::new(create_test_context_facts(), extensions);
+        let mut user_data = create_test_user_data();
+        user_data.accounts[0].currency_code = "GLDOZ".to_string();
+        user_data.accounts[0].imports.clear();
+        user_data.accounts[0].balances = vec![balance("2023-06-30", dec!(1.23456))];
+
+        let report = Report::generate(2023, &user_data, &context)?;
+
+        let savings = account_report(&report, "savings");
+        assert_eq!(savings.max_balance.amount, dec!(1.23456));
+        assert_eq!(savings.max_balance_usd, Money::usd(dec!(2469.12)));
+        assert_eq!(
+            savings.max_balance.format_to(context.minor_units("gldoz")),
+            "GLDOZ 1.235"
+        );
+        // The definition belongs to this context alone
+        assert_eq!(create_test_context().minor_units("gldoz"), 2);
+        assert!(savings.notes.contains(
+            &"GLDOZ is troy ounces of gold, a currency defined in fact_extensions, written to 3 decimal places"
+                .to_string()
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pinned_rate() -> Result<()> {
+        let extensions = Facts {
+            years: vec![AnnualFact {
+                year: 2023,
+                exchange_rates: vec![ExchangeRate::new("EUR".to_string(), 0.50).unwrap()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let pins = BTreeMap::from([("eur".to_string(), "irs".to_string())]);
+        let context =
+            ReportContext::new(create_test_context_facts(), extensions).with_rate_pins(&pins)?;
+
+        // The user's rate would win by precedence, but EUR is pinned to the IRS rate
+        let report = Report::generate(2023, &create_test_user_data(), &context)?;
+        let savings = account_report(&report, "savings");
+        assert_eq!(savings.rate_source, RateSource::IrsProvided);
+        assert_eq!(savings.exchange_rate, Some(0.80));
+        assert!(savings.rate_pinned);
+        assert!(savings
+            .notes
+            .contains(&"Converted at the IRS rate, pinned to EUR in rate_pins".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_archived_account_open_in_year() -> Result<()> {
+        let mut user_data = create_test_user_data();
+        let savings = user_data
+            .accounts
+            .iter_mut()
+            .find(|account| account.handle == "savings")
+            .unwrap();
+        savings.archived = true;
+        savings.closing_date = Some(NaiveDate::from_ymd_opt(2023, 6, 30).unwrap());
+        user_data.set_aside_archived();
+
+        let err = Report::generate(2023, &user_data, &create_test_context()).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Account savings is archived but was open in 2023"));
+
+        // A year after it closed reports without it
+        let report = Report::generate(2024, &user_data, &create_test_context())?;
+        assert!(report
+            .accounts
+            .iter()
+            .all(|account| account.handle != "savings"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_provided_usd_value() -> Result<()> {
+        let mut user_data = create_test_user_data();
+        user_data.accounts[0].balances[2].usd_value = Some(dec!(1750));
+
+        // The record holding the maximum gives its own USD value, filed instead of 2000 USD at the IRS rate
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        let savings = account_report(&report, "savings");
+        let february = NaiveDate::from_ymd_opt(2023, 2, 28).unwrap();
+        assert_eq!(savings.max_balance, Money::new(dec!(1600), "eur"));
+        assert_eq!(savings.max_balance_usd, Money::usd(dec!(1750)));
+        assert_eq!(
+            savings.rate_source,
+            RateSource::RecordProvided { date: february }
+        );
+        assert!(!savings.rate_pinned);
+        assert!(savings
+            .notes
+            .iter()
+            .any(|note| note.contains("rather than the 2000")));
+
+        user_data.accounts[0].balances[2].usd_value = None;
+        user_data.accounts[0].balances[2].usd_rate = Some(dec!(0.5));
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        let savings = account_report(&report, "savings");
+        assert_eq!(savings.max_balance_usd, Money::usd(dec!(3200)));
+        assert_eq!(savings.exchange_rate, Some(0.5));
+
+        // Only one of the two may be given
+        user_data.accounts[0].balances[2].usd_value = Some(dec!(1750));
+        assert!(Report::generate(2023, &user_data, &create_test_context()).is_err());
+
+        // A value on a record other than the maximum's is left alone
+        let mut user_data = create_test_user_data();
+        user_data.accounts[0].balances[1].usd_value = Some(dec!(5000));
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        let savings = account_report(&report, "savings");
+        assert_eq!(savings.max_balance_usd, Money::usd(dec!(2000)));
+        assert_eq!(savings.rate_source, RateSource::IrsProvided);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_provided_needs_the_maximum_record() -> Result<()> {
+        // Another record on the date of the maximum gives a USD value for a different amount
+        let mut user_data = create_test_user_data();
+        user_data.accounts[0].balances.push(BalanceRecord {
+            usd_value: Some(dec!(6)),
+            ..balance("2023-02-28", dec!(5))
+        });
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        let savings = account_report(&report, "savings");
+        assert_eq!(savings.max_balance, Money::new(dec!(1600), "eur"));
+        assert_eq!(savings.max_balance_usd, Money::usd(dec!(2000)));
+        assert_eq!(savings.rate_source, RateSource::IrsProvided);
+
+        // Or for the same amount in another currency
+        let mut user_data = create_test_user_data();
+        user_data.accounts[0].balances.push(BalanceRecord {
+            currency: Some("usd".to_string()),
+            usd_value: Some(dec!(1600)),
+            ..balance("2023-02-28", dec!(1600))
+        });
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        assert_eq!(
+            account_report(&report, "savings").rate_source,
+            RateSource::IrsProvided
+        );
+
+        // An estimated maximum has no record of its own, even when the record it was estimated from gives one
+        let mut user_data = create_test_user_data();
+        user_data.accounts[0].interpolation = Some(InterpolationPolicy::StepHold);
+        user_data.accounts[0].balances[0].usd_value = Some(dec!(11000));
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        let savings = account_report(&report, "savings");
+        assert_eq!(savings.max_balance, Money::new(dec!(9000), "eur"));
+        assert_eq!(savings.max_balance_usd, Money::usd(dec!(11250)));
+        assert_eq!(savings.rate_source, RateSource::IrsProvided);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_provided_wallet() -> Result<()> {
+        let extensions = Facts {
+            years: vec![AnnualFact {
+                year: 2023,
+                exchange_rates: vec![ExchangeRate::new("GBP".to_string(), 0.50).unwrap()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let context = ReportContext::new(create_test_context_facts(), extensions);
+        let mut user_data = create_test_user_data();
+        user_data.accounts[0].kind = AccountKind::Wallet;
+        user_data.accounts[0].imports.clear();
+        user_data.accounts[0].balances = vec![
+            balance("2023-06-30", dec!(10000)),
+            BalanceRecord {
+                currency: Some("gbp".to_string()),
+                usd_value: Some(dec!(12.5)),
+                ..balance("2023-06-30", dec!(10))
+            },
+        ];
+
+        // Only the GBP sub-balance gives a USD value, so the wallet's is converted
+        let report = Report::generate(2023, &user_data, &context)?;
+        let wallet = account_report(&report, "savings");
+        assert_eq!(wallet.max_balance, Money::new(dec!(10016), "eur"));
+        assert_eq!(wallet.max_balance_usd, Money::usd(dec!(12520)));
+        assert_eq!(wallet.rate_source, RateSource::IrsProvided);
+
+        // With both, their sum is filed
+        user_data.accounts[0].balances[0].usd_value = Some(dec!(12400));
+        let report = Report::generate(2023, &user_data, &context)?;
+        let wallet = account_report(&report, "savings");
+        assert_eq!(wallet.max_balance_usd, Money::usd(dec!(12412.50)));
+        assert_eq!(
+            wallet.rate_source,
+            RateSource::RecordProvided {
+                date: NaiveDate::from_ymd_opt(2023, 6, 30).unwrap()
+            }
+        );
+
+        // A sub-balance carried forward from an earlier date isn't valued on the date of the maximum
+        user_data.accounts[0].balances[0].date = NaiveDate::from_ymd_opt(2023, 5, 31).unwrap();
+        let report = Report::generate(2023, &user_data, &context)?;
+        let wallet = account_report(&report, "savings");
+        assert_eq!(wallet.max_balance_usd, Money::usd(dec!(12520)));
+        assert_eq!(wallet.rate_source, RateSource::IrsProvided);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_daily_rate_filed_value() -> Result<()> {
+        // 1/1.001 isn't exact as a float, and converting back through it gives 1001.0000000000001
+        let mut rates = PriceSeries::new("eur", "usd");
+        rates.merge(vec![crate::crypto::PricePoint {
+            date: NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+            price: dec!(1.001),
+        }]);
+        let mut user_data = UserData {
+            price_series: vec![rates],
+            maximum_policy: MaximumPolicy::MaxOfConverted,
+            ..create_test_user_data()
+        };
+        user_data.accounts[0].imports.clear();
+        user_data.accounts[0].balances = vec![balance("2023-06-30", dec!(1000))];
+
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        let savings = account_report(&report, "savings");
+        assert!(matches!(savings.rate_source, RateSource::DailyRate { .. }));
+        assert_eq!(savings.unrounded_max_balance_usd()?, Money::usd(dec!(1001)));
+        assert_eq!(savings.filed_value()?, dec!(1001));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_maximum_policy() -> Result<()> {
+        let mut rates = PriceSeries::new("eur", "usd");
+        rates.merge(vec![
+            crate::crypto::PricePoint {
+                date: NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+                price: dec!(1),
+            },
+            crate::crypto::PricePoint {
+                date: NaiveDate::from_ymd_opt(2023, 4, 1).unwrap(),
+                price: dec!(2),
+            },
+        ]);
+        let mut user_data = UserData {
+            price_series: vec![rates],
+            ..create_test_user_data()
+        };
+
+        // By default the maximum balance of 1600 EUR is filed at the year's rate, with the 1200 EUR worth 2400 USD at
+        // April's rate shown alongside
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        let savings = account_report(&report, "savings");
+        assert_eq!(savings.max_balance_usd, Money::usd(dec!(2000)));
+        assert_eq!(savings.rate_source, RateSource::IrsProvided);
+        let other = savings.other_maximum.as_ref().unwrap();
+        assert_eq!(other.policy, MaximumPolicy::MaxOfConverted);
+        assert_eq!(other.max_balance, Money::new(dec!(1200), "eur"));
+        assert_eq!(other.max_balance_usd, Money::usd(dec!(2400)));
+
+        user_data.maximum_policy = MaximumPolicy::MaxOfConverted;
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        let savings = account_report(&report, "savings");
+        let april = NaiveDate::from_ymd_opt(2023, 4, 30).unwrap();
+        assert_eq!(savings.max_balance, Money::new(dec!(1200), "eur"));
+        assert_eq!(savings.max_balance_usd, Money::usd(dec!(2400)));
+        assert_eq!(savings.max_balance_date, Some(april));
+        assert_eq!(savings.rate_source, RateSource::DailyRate { date: april });
+        assert_eq!(savings.exchange_rate, Some(0.5));
+        let other = savings.other_maximum.as_ref().unwrap();
+        assert_eq!(other.policy, MaximumPolicy::ConvertMaximum);
+        assert_eq!(other.max_balance_usd, Money::usd(dec!(2000)));
+        // Daily rates aren't a replacement for the IRS rate
+        assert!(report.overridden_rates.is_empty());
+
+        // Without daily rates the maximum balance is converted at the year's rate
+        user_data.price_series.clear();
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        let savings = account_report(&report, "savings");
+        assert_eq!(savings.max_balance_usd, Money::usd(dec!(2000)));
+        assert_eq!(savings.other_maximum, None);
+        assert_eq!(
+            savings.notes,
+            vec![
+                "No daily USD rates for EUR in the prices directory, so its maximum balance is converted at the IRS \
+                 rate despite the max_of_converted maximum policy"
+                    .to_string()
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wallet_account() -> Result<()> {
+        let mut user_data = create_test_user_data();
+        user_data.accounts[0].kind = AccountKind::Wallet;
+        user_data.accounts[0].currency_code = "USD".to_string();
+        for record in &mut user_data.accounts[0].balances {
+            record.currency = Some("eur".to_string());
+        }
+
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+
+        let wallet = account_report(&report, "savings");
+        assert_eq!(wallet.max_balance, Money::usd(dec!(2000)));
+        assert_eq!(
+            wallet.notes,
+            vec![
+                "Multi-currency wallet: the latest sub-balance in each currency was converted to USD and summed on \
+                 each date, at 0.8 EUR per USD (IRS rate)"
+                    .to_string()
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_insurance_account() -> Result<()> {
+        let mut user_data = create_test_user_data();
+        user_data.accounts.push(Account {
+            handle: "pension_policy".to_string(),
+            provider: "example_insurer".to_string(),
+            currency_code: "EUR".to_string(),
+            kind: AccountKind::Insurance,
+            // Balances aren't the reportable value of a policy, so they're ignored
+            balances: vec![balance("2023-06-30", dec!(50000))],
+            surrender_values: vec![
+                SurrenderValueRecord {
+                    date: NaiveDate::from_ymd_opt(2022, 12, 31).unwrap(),
+                    surrender_value: dec!(11000),
+                },
+                SurrenderValueRecord {
+                    date: NaiveDate::from_ymd_opt(2023, 12, 31).unwrap(),
+                    surrender_value: dec!(12000),
+                },
+            ],
+            ..Default::default()
+        });
+
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+
+        let policy = report
+            .accounts
+            .iter()
+            .find(|account| account.handle == "pension_policy")
+            .unwrap();
+        assert_eq!(policy.max_balance, Money::new(dec!(12000), "eur"));
+        assert_eq!(
+            policy.notes,
+            vec![
+                "Foreign life insurance policy or annuity, reported at its cash surrender value"
+                    .to_string()
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_account_notes_and_tags() -> Result<()> {
+        let mut user_data = create_test_user_data();
+        user_data.accounts[0].notes = Some("Emergency fund".to_string());
+        user_data.accounts[0].tags = vec!["household".to_string()];
+
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        assert_eq!(
+            account_report(&report, "savings").notes,
+            vec!["Emergency fund".to_string()]
+        );
+
+        user_data.accounts[0].balances[2].manual = true;
+        user_data.accounts[0].balances[2].note = Some("phone confirmation from bank".to_string());
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        assert_eq!(
+            account_report(&report, "savings").notes[1],
+            "Balances entered by hand rather than imported: 1600.00 EUR on 2023-02-28 (phone confirmation from bank)"
+        );
+
+        let tagged = Report::generate_tagged(
+            2023,
+            &user_data,
+            &create_test_context(),
+            &["household".to_string()],
+        )?;
+        let handles: Vec<&str> = tagged.accounts.iter().map(|a| a.handle.as_str()).collect();
+        assert_eq!(handles, vec!["savings"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_first_year_filing() -> Result<()> {
+        let mut user_data = create_test_user_data();
+        user_data.us_person_since = NaiveDate::from_ymd_opt(2023, 3, 1);
+
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        let savings = account_report(&report, "savings");
+        // February's 1600 was before the filer became a US person
+        assert_eq!(savings.max_balance, Money::new(dec!(1200), "eur"));
+        assert_eq!(
+            savings.open_period.unwrap().start,
+            NaiveDate::from_ymd_opt(2023, 3, 1).unwrap()
+        );
+        assert_eq!(
+            savings.notes,
+            vec![
+                "First-year filing: the filer became a US person on 2023-03-01, so values before then are left out \
+                 of the maximum"
+                    .to_string()
+            ]
+        );
+
+        // Accounts closed before then aren't reportable, and step-hold carries the last value before the date
+        user_data.us_person_since = NaiveDate::from_ymd_opt(2023, 6, 15);
+        user_data.interpolation = InterpolationPolicy::StepHold;
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        assert_eq!(report.accounts.len(), 1);
+        assert_eq!(
+            account_report(&report, "savings").max_balance,
+            Money::new(dec!(1200), "eur")
+        );
+        assert!(report
+            .excluded
+            .iter()
+            .any(|excluded| excluded.handle == "closed"
+                && excluded.reason == "Closed before the filer became a US person on 2023-06-15"));
+
+        // Nothing is due for years before then
+        assert!(Report::generate(2022, &user_data, &create_test_context()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dual_status_year() -> Result<()> {
+        let mut user_data = create_test_user_data();
+        user_data.nonresident_periods = vec![NonresidentPeriod {
+            start: NaiveDate::from_ymd_opt(2022, 9, 1).unwrap(),
+            end: NaiveDate::from_ymd_opt(2023, 2, 28),
+            reason: Some("moved to Germany".to_string()),
+        }];
+
+        // February's 1600 still counts; the nonresident time is only noted
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        let savings = account_report(&report, "savings");
+        assert_eq!(savings.max_balance, Money::new(dec!(1600), "eur"));
+        assert!(savings.notes[0].starts_with(
+            "Dual-status year: the filer was not a US person from 2023-01-01 to 2023-02-28 (moved to Germany)."
+        ));
+        let issues = validate_report(&report, &user_data);
+        assert!(issues
+            .iter()
+            .any(|issue| issue.code == codes::DUAL_STATUS_YEAR));
+
+        // Left for good, before the closed account closed
+        user_data.nonresident_periods[0].end = None;
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        assert!(account_report(&report, "closed").notes[0].starts_with(
+            "The filer was not a US person at any time the account was open in 2023."
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_account_closed_mid_year() -> Result<()> {
+        let report = Report::generate(2023, &create_test_user_data(), &create_test_context())?;
+
+        let closed = account_report(&report, "closed");
+        assert_eq!(closed.handle, "closed");
+        assert_eq!(closed.max_balance, Money::new(dec!(400), "eur"));
+
+        let period = closed.open_period.unwrap();
+        assert_eq!(period.start, NaiveDate::from_ymd_opt(2023, 1, 1).unwrap());
+        assert_eq!(period.end, NaiveDate::from_ymd_opt(2023, 5, 31).unwrap());
+
+        assert_eq!(closed.notes.len(), 1);
+        assert!(closed.notes[0].contains("from 2023-01-01 to 2023-05-31"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_excluded_accounts() -> Result<()> {
+        let report = Report::generate(2023, &create_test_user_data(), &create_test_context())?;
+
+        assert!(report.accounts.iter().all(|a| a.handle != "us_brokerage"));
+        assert_eq!(
+            report.excluded,
+            vec![ExcludedAccount {
+                handle: "us_brokerage".to_string(),
+                provider: "us_bank".to_string(),
+                reason: "US-based account".to_string(),
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_entity_accounts() -> Result<()> {
+        let mut user_data = create_test_user_data();
+        user_data.entities = vec![Entity {
+            name: "Example Holdings Ltd".to_string(),
+            handle: "holdings".to_string(),
+            address: Address::from("1 Example Street, London"),
+            tin: Some("12-3456789".to_string()),
+            ownership_percent: dec!(100),
+        }];
+        user_data.accounts[0].entity = Some("holdings".to_string());
+
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        let savings = account_report(&report, "savings");
+        assert_eq!(savings.entity.as_deref(), Some("holdings"));
+        assert_eq!(
+            savings.notes,
+            vec!["Reported in Part V: held through Example Holdings Ltd, of which the filer owns 100%".to_string()]
+        );
+        assert_eq!(report.entities.len(), 1);
+        assert_eq!(report.entities[0].tin.as_deref(), Some("12-3456789"));
+
+        // Without a controlling interest, the entity's accounts aren't the filer's to report
+        user_data.entities[0].ownership_percent = dec!(40);
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        assert!(report.accounts.iter().all(|a| a.handle != "savings"));
+        assert!(report.entities.is_empty());
+        assert!(report.excluded[0]
+            .reason
+            .starts_with("Held through Example Holdings Ltd, of which the filer owns 40%"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_provenance() -> Result<()> {
+        let report = Report::generate(2023, &create_test_user_data(), &create_test_context())?;
+
+        assert_eq!(report.provenance.len(), 1);
+        assert_eq!(report.provenance[0].account, "savings");
+        assert_eq!(report.provenance[0].provenance.source_file, "april.csv");
+        assert_eq!(report.provenance[0].provenance.sha256, "abc123");
+        // Only the April record falls within 2023
+        assert_eq!(report.provenance[0].records, 1);
+
+        Ok(())
+    }
+
+    /// Values every account at a flat 5000 on the last day of its open period
+    struct FlatValuation;
+
+    impl Valuation for FlatValuation {
+        fn name(&self) -> &str {
+            "flat"
+        }
+
+        fn value_series(
+            &self,
+            account: &Account,
+            period: &OpenPeriod,
+            _context: &ReportContext,
+        ) -> Result<Vec<ValuePoint>> {
+            Ok(vec![ValuePoint {
+                date: period.end,
+                value: Money::new(dec!(5000), &account.currency_code),
+            }])
+        }
+    }
+
+    #[test]
+    fn test_custom_valuation() -> Result<()> {
+        let mut user_data = create_test_user_data();
+        user_data.accounts[0].valuation = Some("flat".to_string());
+
+        let result = Report::generate(2023, &user_data, &create_test_context());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown valuation \"flat\" for account savings"));
+
+        let context = create_test_context().with_valuation(Box::new(FlatValuation));
+        let report = Report::generate(2023, &user_data, &context)?;
+        assert_eq!(
+            account_report(&report, "savings").max_balance,
+            Money::new(dec!(5000), "eur")
+        );
+        // Accounts without a valuation still use their balance records
+        assert_eq!(
+            account_report(&report, "closed").max_balance,
+            Money::new(dec!(400), "eur")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_report_missing_rate() {
+        let result = Report::generate(2021, &create_test_user_data(), &create_test_context());
+
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("No exchange rate found"));
+    }
+
+    #[test]
+    fn test_diagnose() {
+        let context = create_test_context();
+        let mut user_data = create_test_user_data();
+        let (report, diagnostics) = Report::diagnose(2023, &user_data, &context);
+        assert!(report.is_some());
+        // The test data has no providers, which BSA E-Filing would reject
+        assert!(diagnostics
+            .errors()
+            .any(|diagnostic| diagnostic.code == codes::UNKNOWN_PROVIDER));
+
+        // Every account without a rate is reported, not only the first
+        for (handle, currency_code) in [("swiss", "CHF"), ("japanese", "JPY")] {
+            user_data.accounts.push(Account {
+                handle: handle.to_string(),
+                provider: "example_bank".to_string(),
+                currency_code: currency_code.to_string(),
+                balances: vec![balance("2023-03-31", dec!(100))],
+                ..Default::default()
+            });
+        }
+        let (report, diagnostics) = Report::diagnose(2023, &user_data, &context);
+        assert!(report.is_none());
+        let locations: Vec<_> = diagnostics
+            .errors()
+            .map(|diagnostic| diagnostic.location.as_deref())
+            .collect();
+        assert_eq!(locations, vec![Some("swiss"), Some("japanese")]);
+    }
+}
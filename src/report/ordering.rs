@@ -0,0 +1,100 @@
+use super::Report;
+
+impl Report {
+    /// Puts every list in the report in a canonical order, so the same inputs always serialize the same way and
+    /// diffs between runs only show real changes: accounts, excluded accounts, entities and joint owners by handle,
+    /// overridden rates by currency, and provenance by account, then import time and file.
+    ///
+    /// Notes keep the order they were written in, since later notes build on earlier ones.
+    pub fn sort(&mut self) {
+        self.accounts.sort_by(|a, b| a.handle.cmp(&b.handle));
+        self.excluded.sort_by(|a, b| a.handle.cmp(&b.handle));
+        self.entities.sort_by(|a, b| a.handle.cmp(&b.handle));
+        self.joint_owners.sort_by(|a, b| a.handle.cmp(&b.handle));
+        self.overridden_rates
+            .sort_by(|a, b| a.currency_code.cmp(&b.currency_code));
+        for rate in &mut self.overridden_rates {
+            rate.accounts.sort();
+        }
+        self.provenance.sort_by(|a, b| {
+            a.account
+                .cmp(&b.account)
+                .then_with(|| a.provenance.imported_at.cmp(&b.provenance.imported_at))
+                .then_with(|| a.provenance.source_file.cmp(&b.provenance.source_file))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{Account, BalanceRecord, UserData};
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use crate::report::Report;
+    use crate::report_context::ReportContext;
+    use anyhow::Result;
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    fn account(handle: &str) -> Account {
+        Account {
+            handle: handle.to_string(),
+            currency_code: "GBP".to_string(),
+            balances: vec![BalanceRecord {
+                date: NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(),
+                amount: dec!(100),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_order_is_independent_of_data_order() -> Result<()> {
+        let context = ReportContext::new(
+            Facts {
+                years: vec![AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![ExchangeRate::new("GBP".to_string(), 0.80).unwrap()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            None,
+        );
+        let mut excluded = account("bonds");
+        excluded.exclude_from_fbar = true;
+        excluded.exclusion_reason = Some("US-based".to_string());
+        let mut also_excluded = account("annuity");
+        also_excluded.exclude_from_fbar = true;
+        also_excluded.exclusion_reason = Some("US-based".to_string());
+
+        let user_data = UserData {
+            accounts: vec![
+                account("savings"),
+                excluded,
+                account("checking"),
+                also_excluded,
+            ],
+            ..Default::default()
+        };
+        let report = Report::generate(2023, &user_data, &context)?;
+        let handles: Vec<&str> = report
+            .accounts
+            .iter()
+            .map(|account| account.handle.as_str())
+            .collect();
+        assert_eq!(handles, vec!["checking", "savings"]);
+        assert_eq!(report.excluded[0].handle, "annuity");
+
+        let mut reversed = user_data.clone();
+        reversed.accounts.reverse();
+        let other = Report::generate(2023, &reversed, &context)?;
+        assert_eq!(
+            serde_yaml::to_string(&other.accounts)?,
+            serde_yaml::to_string(&report.accounts)?
+        );
+        assert_eq!(other.excluded, report.excluded);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::Result;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::AccountReport;
+use crate::money::Money;
+use crate::report_context::{RateSource, ReportContext};
+
+/// An exchange rate used in place of the IRS rate for the year, with what it changed
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RateOverride {
+    pub currency_code: String,
+    /// The rate the accounts were converted at
+    pub rate: f64,
+    pub source: RateSource,
+    /// The IRS rate it took the place of
+    pub irs_rate: f64,
+    /// Accounts converted at the rate
+    pub accounts: Vec<String>,
+    /// The accounts' maximum values in USD at the rate used, less their maximum values at the IRS rate
+    pub usd_delta: Money,
+}
+
+/// Lists every rate from a source other than the IRS that was used where the IRS has a rate of its own, with the
+/// difference it made to the maximum values of the accounts converted at it
+pub(crate) fn rate_overrides(
+    year: i32,
+    accounts: &[AccountReport],
+    context: &ReportContext,
+) -> Result<Vec<RateOverride>> {
+    let mut by_currency: BTreeMap<&str, Vec<&AccountReport>> = BTreeMap::new();
+    for account in accounts {
+        if !matches!(
+            account.rate_source,
+            RateSource::IrsProvided
+                | RateSource::NotConverted
+                | RateSource::DailyRate { .. }
+                | RateSource::RecordProvided { .. }
+        ) {
+            by_currency
+                .entry(account.currency_code.as_str())
+                .or_default()
+                .push(account);
+        }
+    }
+
+    let mut overrides = Vec::new();
+    for (currency, accounts) in by_currency {
+        let Some(irs_rate) = context.irs_rate(year, currency) else {
+            continue;
+        };
+        let rate = context.find_exchange_rate(year, currency)?;
+
+        let mut usd_delta = Money::usd(Decimal::ZERO);
+        for account in &accounts {
+            let at_irs_rate = irs_rate.convert_to_usd(&account.max_balance)?;
+            usd_delta =
+                usd_delta.checked_add(&account.max_balance_usd.checked_sub(&at_irs_rate)?)?;
+        }
+
+        overrides.push(RateOverride {
+            currency_code: currency.to_string(),
+            rate: rate.rate,
+            source: rate.source().clone(),
+            irs_rate: irs_rate.rate,
+            accounts: accounts
+                .iter()
+                .map(|account| account.handle.clone())
+                .collect(),
+            usd_delta,
+        });
+    }
+
+    Ok(overrides)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Account, BalanceRecord, UserData};
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use crate::report::Report;
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    fn facts(rate: f64) -> Facts {
+        Facts {
+            years: vec![AnnualFact {
+                year: 2023,
+                exchange_rates: vec![ExchangeRate::new("EUR".to_string(), rate).unwrap()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn account(handle: &str, amount: Decimal) -> Account {
+        Account {
+            handle: handle.to_string(),
+            currency_code: "EUR".to_string(),
+            balances: vec![BalanceRecord {
+                date: NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(),
+                amount,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_rate_overrides() -> Result<()> {
+        let user_data = UserData {
+            accounts: vec![
+                account("savings", dec!(800)),
+                account("checking", dec!(400)),
+            ],
+            ..Default::default()
+        };
+
+        let report = Report::generate(2023, &user_data, &ReportContext::new(facts(0.80), None))?;
+        assert!(report.overridden_rates.is_empty());
+
+        let context = ReportContext::new(facts(0.80), facts(0.50));
+        let report = Report::generate(2023, &user_data, &context)?;
+        assert_eq!(
+            report.overridden_rates,
+            vec![RateOverride {
+                currency_code: "eur".to_string(),
+                rate: 0.50,
+                source: RateSource::UserProvided,
+                irs_rate: 0.80,
+                accounts: vec!["checking".to_string(), "savings".to_string()],
+                // 2400 USD at the user's rate, against 1500 USD at the IRS rate
+                usd_delta: Money::usd(dec!(900)),
+            }]
+        );
+
+        Ok(())
+    }
+}
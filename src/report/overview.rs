@@ -0,0 +1,331 @@
+use anyhow::Result;
+use chrono::Datelike;
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{OpenPeriod, Report};
+use crate::data::{Account, AccountKind, UserData};
+use crate::money::Money;
+use crate::report_context::ReportContext;
+
+/// An FBAR is required when the aggregate of the maximum values of the filer's foreign accounts exceeds this
+pub const FILING_THRESHOLD_USD: Decimal = Decimal::from_parts(10_000, 0, 0, false, 0);
+
+/// One row of the at-a-glance summary of the data directory
+#[derive(Debug)]
+pub struct YearOverview {
+    pub year: i32,
+    /// The year's figures, or why its report couldn't be generated
+    pub figures: Result<YearFigures>,
+    /// Share of the months reportable accounts were open in that have a record, as a whole percentage. `None` when no
+    /// reportable account was open during the year.
+    pub completeness: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct YearFigures {
+    /// Accounts in the report
+    pub accounts: usize,
+    /// Sum of the accounts' maximum values
+    pub aggregate_max_usd: Money,
+}
+
+impl YearFigures {
+    pub fn filing_required(&self) -> bool {
+        self.aggregate_max_usd.amount > FILING_THRESHOLD_USD
+    }
+}
+
+/// One year of the household roll-up across every configured filer
+#[derive(Debug)]
+pub struct HouseholdYear {
+    pub year: i32,
+    /// Each filer's handle and figures, as their own report gives them, in the order the filers are listed
+    pub filers: Vec<(String, Result<YearFigures>)>,
+    /// Every account any filer reports, counted once even when jointly owned: the family's total foreign exposure.
+    /// An error when any filer's report fails.
+    pub household: Result<YearFigures>,
+}
+
+/// Summarizes every year the data has records for, oldest first: how many accounts would be reported, their
+/// aggregate maximum, whether that requires filing, and how complete the records are
+pub fn overview(user_data: &UserData, context: &ReportContext) -> Vec<YearOverview> {
+    let _span = tracing::info_span!("overview").entered();
+    years(user_data)
+        .into_iter()
+        .map(|year| YearOverview {
+            year,
+            figures: figures(year, user_data, context),
+            completeness: completeness(year, user_data),
+        })
+        .collect()
+}
+
+/// Rolls the summary up across every configured filer, with each filer's own figures alongside. Each filer's report
+/// is generated as it would be for them, so nothing about the per-filer reports changes.
+pub fn household(user_data: &UserData, context: &ReportContext) -> Result<Vec<HouseholdYear>> {
+    let _span = tracing::info_span!("household").entered();
+    let filers = user_data
+        .filers
+        .iter()
+        .map(|filer| Ok((filer.handle.clone(), user_data.as_filer(&filer.handle)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(years(user_data)
+        .into_iter()
+        .map(|year| {
+            let reports: Vec<(String, Result<Report>)> = filers
+                .iter()
+                .map(|(handle, data)| (handle.clone(), Report::generate(year, data, context)))
+                .collect();
+
+            let mut accounts = BTreeMap::new();
+            let mut failed = None;
+            for (handle, report) in &reports {
+                match report {
+                    Ok(report) => accounts.extend(
+                        report
+                            .accounts
+                            .iter()
+                            .map(|account| (account.handle.as_str(), &account.max_balance_usd)),
+                    ),
+                    Err(err) => {
+                        failed.get_or_insert_with(|| {
+                            format!("{}'s report failed: {:#}", handle, err)
+                        });
+                    }
+                }
+            }
+            let household = match failed {
+                Some(message) => Err(anyhow::anyhow!(message)),
+                None => accounts
+                    .values()
+                    .try_fold(Money::usd(Decimal::ZERO), |total, max| {
+                        total.checked_add(max)
+                    })
+                    .map(|aggregate_max_usd| YearFigures {
+                        accounts: accounts.len(),
+                        aggregate_max_usd,
+                    }),
+            };
+
+            HouseholdYear {
+                year,
+                filers: reports
+                    .into_iter()
+                    .map(|(handle, report)| {
+                        (handle, report.and_then(|report| year_figures(&report)))
+                    })
+                    .collect(),
+                household,
+            }
+        })
+        .collect())
+}
+
+pub(super) fn years(user_data: &UserData) -> BTreeSet<i32> {
+    reportable(user_data)
+        .flat_map(Account::record_dates)
+        .map(|date| date.year())
+        .collect()
+}
+
+pub(super) fn reportable(user_data: &UserData) -> impl Iterator<Item = &Account> {
+    user_data
+        .accounts
+        .iter()
+        .filter(|account| !account.exclude_from_fbar)
+}
+
+impl Report {
+    /// Sum of the reported accounts' maximum values, which decides whether an FBAR is required
+    pub fn aggregate_max_usd(&self) -> Result<Money> {
+        let mut aggregate = Money::usd(Decimal::ZERO);
+        for account in &self.accounts {
+            aggregate = aggregate.checked_add(&account.max_balance_usd)?;
+        }
+        Ok(aggregate)
+    }
+}
+
+fn figures(year: i32, user_data: &UserData, context: &ReportContext) -> Result<YearFigures> {
+    year_figures(&Report::generate(year, user_data, context)?)
+}
+
+fn year_figures(report: &Report) -> Result<YearFigures> {
+    Ok(YearFigures {
+        accounts: report.accounts.len(),
+        aggregate_max_usd: report.aggregate_max_usd()?,
+    })
+}
+
+/// Counts each month of an account's open period with a record as complete. Insurance policies only have annual
+/// statements, so one record in the period covers the year.
+fn completeness(year: i32, user_data: &UserData) -> Option<u32> {
+    let mut expected = 0;
+    let mut covered = 0;
+    for account in reportable(user_data) {
+        let Some(period) = OpenPeriod::for_account(account, year) else {
+            continue;
+        };
+        let months: BTreeSet<u32> = account
+            .record_dates()
+            .filter(|date| period.start <= *date && *date <= period.end)
+            .map(|date| date.month())
+            .collect();
+
+        if account.kind == AccountKind::Insurance {
+            expected += 1;
+            covered += u32::from(!months.is_empty());
+        } else {
+            expected += period.end.month() - period.start.month() + 1;
+            covered += months.len() as u32;
+        }
+    }
+
+    (expected > 0).then(|| covered * 100 / expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{BalanceRecord, Filer};
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    fn account(handle: &str, balances: &[(&str, Decimal)]) -> Account {
+        Account {
+            handle: handle.to_string(),
+            currency_code: "GBP".to_string(),
+            balances: balances
+                .iter()
+                .map(|(date, amount)| BalanceRecord {
+                    date: date.parse().unwrap(),
+                    amount: *amount,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_overview() -> Result<()> {
+        let context = ReportContext::new(
+            Facts {
+                years: vec![AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![ExchangeRate::new("GBP".to_string(), 0.80).unwrap()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            None,
+        );
+        let mut closed = account(
+            "savings",
+            &[("2022-12-31", dec!(3000)), ("2023-02-15", dec!(4000))],
+        );
+        closed.closing_date = NaiveDate::from_ymd_opt(2023, 3, 31);
+        let user_data = UserData {
+            accounts: vec![
+                account(
+                    "checking",
+                    &[
+                        ("2023-01-31", dec!(6000)),
+                        ("2023-02-28", dec!(7000)),
+                        ("2023-12-31", dec!(5000)),
+                    ],
+                ),
+                closed,
+            ],
+            ..Default::default()
+        };
+
+        let rows = overview(&user_data, &context);
+        assert_eq!(
+            rows.iter().map(|row| row.year).collect::<Vec<_>>(),
+            vec![2022, 2023]
+        );
+
+        // No 2022 rate
+        assert!(rows[0].figures.is_err());
+        assert_eq!(rows[0].completeness, Some(4));
+
+        let figures = rows[1].figures.as_ref().unwrap();
+        assert_eq!(figures.accounts, 2);
+        // 7000 GBP and 4000 GBP at 0.80 GBP to the dollar
+        assert_eq!(figures.aggregate_max_usd, Money::usd(dec!(13750)));
+        assert!(figures.filing_required());
+        // 3 of checking's 12 months, and 1 of the 3 months savings was open
+        assert_eq!(rows[1].completeness, Some(26));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_household() -> Result<()> {
+        let context = ReportContext::new(
+            Facts {
+                years: vec![AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![ExchangeRate::new("GBP".to_string(), 0.80).unwrap()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            None,
+        );
+        let filer = |handle: &str| Filer {
+            name: handle.to_string(),
+            handle: handle.to_string(),
+            tin: None,
+            address: None,
+        };
+        let mut joint = account("joint", &[("2023-06-30", dec!(8000))]);
+        joint.owners = vec!["alex".to_string(), "sam".to_string()];
+        let mut sams = account("sams", &[("2023-06-30", dec!(4000))]);
+        sams.owners = vec!["sam".to_string()];
+        let user_data = UserData {
+            filers: vec![filer("alex"), filer("sam")],
+            // Without owners, so the first filer's
+            accounts: vec![account("alexs", &[("2023-06-30", dec!(2000))]), joint, sams],
+            ..Default::default()
+        };
+
+        let rows = household(&user_data, &context)?;
+        assert_eq!(rows.len(), 1);
+        let by_filer: Vec<(&str, usize, Money)> = rows[0]
+            .filers
+            .iter()
+            .map(|(handle, figures)| {
+                let figures = figures.as_ref().unwrap();
+                (
+                    handle.as_str(),
+                    figures.accounts,
+                    figures.aggregate_max_usd.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            by_filer,
+            vec![
+                ("alex", 2, Money::usd(dec!(12500))),
+                ("sam", 2, Money::usd(dec!(15000)))
+            ]
+        );
+        // The joint account is counted once
+        let household = rows[0].household.as_ref().unwrap();
+        assert_eq!(household.accounts, 3);
+        assert_eq!(household.aggregate_max_usd, Money::usd(dec!(17500)));
+
+        // The first filer's own report is unchanged
+        assert_eq!(
+            figures(2023, &user_data, &context)?.aggregate_max_usd,
+            Money::usd(dec!(12500))
+        );
+
+        Ok(())
+    }
+}
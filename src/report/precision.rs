@@ -0,0 +1,129 @@
+use anyhow::{anyhow, Result};
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::Report;
+use crate::money::Money;
+
+/// Largest difference between the filed figure and a recomputation that isn't flagged: a cent
+pub const PRECISION_TOLERANCE: Decimal = Decimal::from_parts(1, 0, 0, false, 2);
+
+/// An account's conversion to USD computed three ways, to check the filed figure doesn't depend on how it was
+/// computed: as filed, in `Decimal` at the rate as stored; in `f64`, as the tool converted before amounts were
+/// decimals; and in `Decimal` at the rate as written, e.g. exactly 0.85 rather than the nearest `f64` to it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PrecisionCheck {
+    pub account: String,
+    pub max_balance: Money,
+    pub exchange_rate: f64,
+    /// The filed figure
+    pub decimal_usd: Money,
+    pub float_usd: Money,
+    pub exact_usd: Money,
+}
+
+impl PrecisionCheck {
+    /// The larger of the two recomputations' differences from the filed figure
+    pub fn difference(&self) -> Decimal {
+        (self.decimal_usd.amount - self.float_usd.amount)
+            .abs()
+            .max((self.decimal_usd.amount - self.exact_usd.amount).abs())
+    }
+
+    pub fn exceeds_tolerance(&self) -> bool {
+        self.difference() > PRECISION_TOLERANCE
+    }
+}
+
+/// Recomputes every converted account's maximum in USD, for the accounts that weren't already in USD
+pub fn precision_checks(report: &Report) -> Result<Vec<PrecisionCheck>> {
+    report
+        .accounts
+        .iter()
+        .filter_map(|account| account.exchange_rate.map(|rate| (account, rate)))
+        .map(|(account, rate)| {
+            let amount = account.max_balance.amount;
+            let float = amount
+                .to_f64()
+                .map(|amount| amount / rate)
+                .and_then(Decimal::from_f64)
+                .ok_or_else(|| {
+                    anyhow!("{}: {} can't be converted as f64", account.handle, amount)
+                })?;
+            let exact_rate: Decimal = rate
+                .to_string()
+                .parse()
+                .map_err(|err| anyhow!("{}: rate {}: {}", account.handle, rate, err))?;
+            let exact = amount
+                .checked_div(exact_rate)
+                .ok_or_else(|| anyhow!("{}: {} is too large to convert", account.handle, amount))?;
+
+            Ok(PrecisionCheck {
+                account: account.handle.clone(),
+                max_balance: account.max_balance.clone(),
+                exchange_rate: rate,
+                decimal_usd: account.max_balance_usd.clone(),
+                float_usd: Money::usd(float).round(),
+                exact_usd: Money::usd(exact).round(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Account, BalanceRecord, UserData};
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use crate::report_context::ReportContext;
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_precision_checks() -> Result<()> {
+        let account = |handle: &str, currency: &str, amount: Decimal| Account {
+            handle: handle.to_string(),
+            currency_code: currency.to_string(),
+            balances: vec![BalanceRecord {
+                date: NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(),
+                amount,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let user_data = UserData {
+            accounts: vec![
+                account("savings", "EUR", dec!(1234567.89)),
+                account("brokerage", "USD", dec!(500)),
+            ],
+            ..Default::default()
+        };
+        let facts = Facts {
+            years: vec![AnnualFact {
+                year: 2023,
+                exchange_rates: vec![ExchangeRate::new("EUR".to_string(), 0.923).unwrap()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let report = Report::generate(2023, &user_data, &ReportContext::new(facts, None))?;
+
+        // USD accounts aren't converted, so there's nothing to check
+        let checks = precision_checks(&report)?;
+        assert_eq!(checks.len(), 1);
+        let check = &checks[0];
+        assert_eq!(check.account, "savings");
+        assert_eq!(check.decimal_usd, Money::usd(dec!(1337560.01)));
+        assert_eq!(check.float_usd, check.decimal_usd);
+        assert_eq!(check.exact_usd, check.decimal_usd);
+        assert!(!check.exceeds_tolerance());
+
+        let mut drifted = check.clone();
+        drifted.float_usd = Money::usd(dec!(1337560.03));
+        assert_eq!(drifted.difference(), dec!(0.02));
+        assert!(drifted.exceeds_tolerance());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,273 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use super::Report;
+use crate::money::Money;
+
+/// A frozen copy of a year's computed report, written when the year is closed.
+///
+/// The content hash lets later runs detect whether the underlying data for a closed (and presumably filed) year
+/// has since changed.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct YearSnapshot {
+    pub year: i32,
+    pub content_hash: String,
+    pub closed_at: DateTime<Utc>,
+    pub report: Report,
+}
+
+impl YearSnapshot {
+    pub fn new(report: Report) -> Result<Self> {
+        Ok(Self {
+            year: report.year,
+            content_hash: content_hash(&report)?,
+            closed_at: Utc::now(),
+            report,
+        })
+    }
+
+    /// Checks whether a freshly computed report still matches the frozen one, comparing only the figures
+    /// `content_hash` covers
+    pub fn matches(&self, report: &Report) -> Result<bool> {
+        Ok(content_hash(&self.report)? == content_hash(report)?)
+    }
+
+    /// Whether the stored hash is still that of the frozen report, i.e. the snapshot hasn't been edited
+    fn is_intact(&self) -> Result<bool> {
+        Ok(self.content_hash == content_hash(&self.report)?)
+    }
+}
+
+/// SHA-256 of the report's figures, hex encoded: each account's handle, maximum value in its currency and in USD,
+/// currency and exchange rate. Only changes to those change the hash, not notes, metadata or fields later versions
+/// add to reports.
+pub fn content_hash(report: &Report) -> Result<String> {
+    #[derive(Serialize)]
+    struct AccountFigures<'a> {
+        handle: &'a str,
+        max_balance: &'a Money,
+        max_balance_usd: &'a Money,
+        currency_code: &'a str,
+        exchange_rate: Option<f64>,
+    }
+    #[derive(Serialize)]
+    struct Figures<'a> {
+        year: i32,
+        accounts: Vec<AccountFigures<'a>>,
+    }
+
+    let figures = Figures {
+        year: report.year,
+        accounts: report
+            .accounts
+            .iter()
+            .map(|account| AccountFigures {
+                handle: &account.handle,
+                max_balance: &account.max_balance,
+                max_balance_usd: &account.max_balance_usd,
+                currency_code: &account.currency_code,
+                exchange_rate: account.exchange_rate,
+            })
+            .collect(),
+    };
+    let serialized = serde_yaml::to_string(&figures)?;
+    Ok(hex::encode(Sha256::digest(serialized.as_bytes())))
+}
+
+/// Reads and writes year snapshots under `.fbar_prep/snapshots` in the data directory
+pub struct SnapshotStore {
+    dir: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(base_path: &Path) -> Self {
+        Self {
+            dir: base_path.join(".fbar_prep").join("snapshots"),
+        }
+    }
+
+    /// Freezes the report for its year. Fails if the year has already been closed.
+    pub fn close_year(&self, report: Report) -> Result<YearSnapshot> {
+        let path = self.snapshot_path(report.year);
+        if path.exists() {
+            bail!("Year {} has already been closed ({:?})", report.year, path);
+        }
+
+        let snapshot = YearSnapshot::new(report)?;
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(&path, serde_yaml::to_string(&snapshot)?)?;
+
+        Ok(snapshot)
+    }
+
+    pub fn load(&self, year: i32) -> Result<Option<YearSnapshot>> {
+        let path = self.snapshot_path(year);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let contents = std::fs::read_to_string(path)?;
+        let snapshot: YearSnapshot = serde_yaml::from_str(&contents)?;
+
+        if !snapshot.is_intact()? {
+            bail!(
+                "Snapshot for {} has been modified since it was written",
+                year
+            );
+        }
+
+        Ok(Some(snapshot))
+    }
+
+    /// Returns the closed years, oldest first
+    pub fn closed_years(&self) -> Result<Vec<i32>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut years = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("yml") {
+                continue;
+            }
+            if let Some(year) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse().ok())
+            {
+                years.push(year);
+            }
+        }
+        years.sort();
+
+        Ok(years)
+    }
+
+    fn snapshot_path(&self, year: i32) -> PathBuf {
+        self.dir.join(format!("{}.yml", year))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::AccountReport;
+    use crate::report_context::RateSource;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use tempfile::TempDir;
+
+    fn create_test_report(max_balance: Decimal) -> Report {
+        Report {
+            year: 2023,
+            metadata: None,
+            summary: None,
+            accounts: vec![AccountReport {
+                handle: "savings".to_string(),
+                provider: "example_bank".to_string(),
+                currency_code: "eur".to_string(),
+                max_balance: Money::new(max_balance, "eur"),
+                max_balance_usd: Money::usd(max_balance / dec!(0.8)),
+                max_balance_date: None,
+                rate_source: RateSource::IrsProvided,
+                rate_pinned: false,
+                exchange_rate: Some(0.8),
+                usd_price: None,
+                other_maximum: None,
+                open_period: None,
+                notes: vec![],
+                entity: None,
+                signature_authority: None,
+                joint_owner: None,
+                interpolation: None,
+            }],
+            overridden_rates: Vec::new(),
+            provenance: vec![],
+            excluded: vec![],
+            entities: vec![],
+            joint_owners: vec![],
+            spousal_filing: None,
+        }
+    }
+
+    #[test]
+    fn test_close_and_load_year() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let store = SnapshotStore::new(temp_dir.path());
+
+        assert!(store.load(2023)?.is_none());
+        assert!(store.closed_years()?.is_empty());
+
+        let snapshot = store.close_year(create_test_report(dec!(1600)))?;
+        assert_eq!(snapshot.year, 2023);
+        assert_eq!(snapshot.content_hash.len(), 64);
+
+        let loaded = store.load(2023)?.unwrap();
+        assert_eq!(loaded.content_hash, snapshot.content_hash);
+        assert_eq!(loaded.report, snapshot.report);
+        assert_eq!(store.closed_years()?, vec![2023]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_close_year_twice() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let store = SnapshotStore::new(temp_dir.path());
+
+        store.close_year(create_test_report(dec!(1600)))?;
+        let result = store.close_year(create_test_report(dec!(1600)));
+
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("already been closed"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detects_changed_data() -> Result<()> {
+        let snapshot = YearSnapshot::new(create_test_report(dec!(1600)))?;
+
+        assert!(snapshot.matches(&create_test_report(dec!(1600)))?);
+        assert!(!snapshot.matches(&create_test_report(dec!(1700)))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ignores_notes() -> Result<()> {
+        let snapshot = YearSnapshot::new(create_test_report(dec!(1600)))?;
+
+        let mut report = create_test_report(dec!(1600));
+        report.accounts[0].notes = vec!["Reworded note".to_string()];
+        report.accounts[0].max_balance_date = "2023-06-30".parse().ok();
+        assert!(snapshot.matches(&report)?);
+
+        report.accounts[0].exchange_rate = Some(0.81);
+        assert!(!snapshot.matches(&report)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detects_edited_snapshot() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let store = SnapshotStore::new(temp_dir.path());
+        store.close_year(create_test_report(dec!(1600)))?;
+
+        let path = temp_dir.path().join(".fbar_prep/snapshots/2023.yml");
+        let contents = std::fs::read_to_string(&path)?;
+        std::fs::write(&path, contents.replace("1600", "16"))?;
+
+        assert!(store.load(2023).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::Report;
+use crate::data::UserData;
+
+/// Filers with a financial interest in at least this many accounts may report just the number of accounts
+pub const MANY_ACCOUNTS_THRESHOLD: usize = 25;
+
+/// How a filer with 25 or more accounts chooses to file them
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ManyAccountsMode {
+    /// Report every account individually, as for fewer accounts
+    Itemized,
+    /// Report only the number of accounts, keeping records of each
+    Summary,
+}
+
+/// The figures filed instead of individual accounts
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct AccountsSummary {
+    /// Accounts the filer has a financial interest in. Accounts they only have signature authority over aren't
+    /// counted, and are still itemized in Part IV.
+    pub accounts: usize,
+    /// Accounts held directly, counted in Part II
+    pub separately_owned: usize,
+    /// Accounts held through entities, counted in Part V
+    pub held_through_entities: usize,
+}
+
+impl AccountsSummary {
+    pub fn for_report(report: &Report) -> Self {
+        let accounts = report
+            .accounts
+            .iter()
+            .filter(|account| account.signature_authority.is_none())
+            .count();
+        let held_through_entities = report
+            .accounts
+            .iter()
+            .filter(|account| account.entity.is_some())
+            .count();
+        Self {
+            accounts,
+            separately_owned: accounts - held_through_entities,
+            held_through_entities,
+        }
+    }
+
+    pub fn qualifies(&self) -> bool {
+        self.accounts >= MANY_ACCOUNTS_THRESHOLD
+    }
+}
+
+/// Points out when the filer's choice of how to file many accounts is missing or doesn't apply to the report
+pub fn notice(report: &Report, user_data: &UserData) -> Option<String> {
+    let summary = AccountsSummary::for_report(report);
+    match (user_data.many_accounts, summary.qualifies()) {
+        (None, true) => Some(format!(
+            "{} has {} reportable accounts. With {} or more you may file only their number and keep records of \
+             each; set `many_accounts: summary` or `many_accounts: itemized` in data.yml to choose",
+            report.year, summary.accounts, MANY_ACCOUNTS_THRESHOLD
+        )),
+        (Some(ManyAccountsMode::Summary), false) => Some(format!(
+            "`many_accounts: summary` only applies with {} or more accounts, and {} has {}; they are itemized",
+            MANY_ACCOUNTS_THRESHOLD, report.year, summary.accounts
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Account, BalanceRecord};
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use crate::report_context::ReportContext;
+    use anyhow::Result;
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    fn create_test_user_data(accounts: usize) -> UserData {
+        UserData {
+            accounts: (0..accounts)
+                .map(|index| Account {
+                    handle: format!("account_{}", index),
+                    provider: "example_bank".to_string(),
+                    currency_code: "EUR".to_string(),
+                    balances: vec![BalanceRecord {
+                        date: NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(),
+                        amount: dec!(100),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn create_test_context() -> ReportContext {
+        ReportContext::new(
+            Facts {
+                years: vec![AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![ExchangeRate::new("EUR".to_string(), 0.80).unwrap()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            None,
+        )
+    }
+
+    #[test]
+    fn test_summary_mode() -> Result<()> {
+        let mut user_data = create_test_user_data(25);
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        assert!(report.summary.is_none());
+        assert!(notice(&report, &user_data)
+            .unwrap()
+            .starts_with("2023 has 25 reportable accounts"));
+
+        user_data.many_accounts = Some(ManyAccountsMode::Summary);
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        assert_eq!(
+            report.summary,
+            Some(AccountsSummary {
+                accounts: 25,
+                separately_owned: 25,
+                held_through_entities: 0,
+            })
+        );
+        // The accounts are still listed, as the filer's records
+        assert_eq!(report.accounts.len(), 25);
+        assert_eq!(notice(&report, &user_data), None);
+
+        user_data.many_accounts = Some(ManyAccountsMode::Itemized);
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        assert!(report.summary.is_none());
+        assert_eq!(notice(&report, &user_data), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_below_threshold() -> Result<()> {
+        let mut user_data = create_test_user_data(24);
+        user_data.many_accounts = Some(ManyAccountsMode::Summary);
+
+        let report = Report::generate(2023, &user_data, &create_test_context())?;
+        assert!(report.summary.is_none());
+        assert!(notice(&report, &user_data)
+            .unwrap()
+            .contains("2023 has 24; they are itemized"));
+
+        Ok(())
+    }
+}
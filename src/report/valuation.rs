@@ -0,0 +1,298 @@
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::OpenPeriod;
+use crate::data::Account;
+use crate::money::Money;
+use crate::report_context::ReportContext;
+
+/// Name of the valuation used for accounts that don't name one
+pub const BALANCE_VALUATION: &str = "balances";
+/// Name of the valuation used by multi-currency wallet accounts
+pub const WALLET_VALUATION: &str = "wallet";
+/// Name of the valuation used by insurance and annuity accounts
+pub const SURRENDER_VALUATION: &str = "surrender_value";
+
+/// The value of an account on a given date, usually in the account's currency. Values in another currency are
+/// converted to the account's by the report.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ValuePoint {
+    pub date: NaiveDate,
+    pub value: Money,
+}
+
+/// Maps an account's raw records to the series of values the report takes its maximum over.
+///
+/// Plain bank accounts are valued by their balance records. Accounts whose value has to be derived (holdings priced
+/// from a price series, a surrender value computed from an insurance wrapper's terms) can implement this trait in
+/// their own code, register it with `ReportContext::with_valuation`, and select it with the account's `valuation`
+/// field.
+pub trait Valuation {
+    /// Name accounts use to select this valuation
+    fn name(&self) -> &str;
+
+    /// The account's known values within the open period. The context gives access to the year's exchange rates.
+    fn value_series(
+        &self,
+        account: &Account,
+        period: &OpenPeriod,
+        context: &ReportContext,
+    ) -> Result<Vec<ValuePoint>>;
+}
+
+/// Values an account by its balance records, inline and imported
+pub struct BalanceValuation;
+
+impl Valuation for BalanceValuation {
+    fn name(&self) -> &str {
+        BALANCE_VALUATION
+    }
+
+    fn value_series(
+        &self,
+        account: &Account,
+        period: &OpenPeriod,
+        _context: &ReportContext,
+    ) -> Result<Vec<ValuePoint>> {
+        Ok(account
+            .balances_in_year(period.start.year())
+            .filter(|record| period.contains(record.date))
+            .map(|record| ValuePoint {
+                date: record.date,
+                value: Money::new(
+                    record.amount,
+                    record.currency.as_deref().unwrap_or(&account.currency_code),
+                ),
+            })
+            .collect())
+    }
+}
+
+/// Values multi-currency wallets, which hold a balance in each of several currencies at once.
+///
+/// Balance records carry the currency of the sub-balance they give, defaulting to the account's. On each date with a
+/// record, every currency's latest sub-balance is converted to USD at the year's rate and the total converted to the
+/// account's currency. A sub-balance is carried forward until its currency's next record, so a currency that's been
+/// emptied needs a zero record.
+pub struct WalletValuation;
+
+impl Valuation for WalletValuation {
+    fn name(&self) -> &str {
+        WALLET_VALUATION
+    }
+
+    fn value_series(
+        &self,
+        account: &Account,
+        period: &OpenPeriod,
+        context: &ReportContext,
+    ) -> Result<Vec<ValuePoint>> {
+        let year = period.start.year();
+        let mut records: Vec<_> = account
+            .balances_in_year(year)
+            .filter(|record| period.contains(record.date))
+            .collect();
+        records.sort_by_key(|record| record.date);
+
+        let mut sub_balances: BTreeMap<String, Money> = BTreeMap::new();
+        let mut series: Vec<ValuePoint> = Vec::new();
+        for record in records {
+            let currency = record
+                .currency
+                .as_deref()
+                .unwrap_or(&account.currency_code)
+                .to_lowercase();
+            sub_balances.insert(currency.clone(), Money::new(record.amount, currency));
+
+            let total_usd = sub_balances
+                .values()
+                .try_fold(Money::usd(Decimal::ZERO), |total, balance| {
+                    total.checked_add(&context.convert_to_usd(year, balance)?)
+                })?;
+            let value = context.convert_from_usd(year, &account.currency_code, &total_usd)?;
+
+            // Later records on the same date replace the total computed so far
+            if series.last().is_some_and(|point| point.date == record.date) {
+                series.pop();
+            }
+            series.push(ValuePoint {
+                date: record.date,
+                value,
+            });
+        }
+
+        Ok(series)
+    }
+}
+
+/// Values insurance policies and annuities by their cash surrender values. Statements usually give one a year, so
+/// an interpolation policy is often needed to carry the last value into the start of the year.
+pub struct SurrenderValuation;
+
+impl Valuation for SurrenderValuation {
+    fn name(&self) -> &str {
+        SURRENDER_VALUATION
+    }
+
+    fn value_series(
+        &self,
+        account: &Account,
+        period: &OpenPeriod,
+        _context: &ReportContext,
+    ) -> Result<Vec<ValuePoint>> {
+        Ok(account
+            .surrender_values
+            .iter()
+            .filter(|record| period.contains(record.date))
+            .map(|record| ValuePoint {
+                date: record.date,
+                value: Money::new(record.surrender_value, &account.currency_code),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{AccountKind, BalanceRecord, SurrenderValueRecord};
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use rust_decimal_macros::dec;
+
+    fn record(date: &str, amount: Decimal, currency: &str) -> BalanceRecord {
+        BalanceRecord {
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            amount,
+            currency: Some(currency.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_balance_valuation() -> Result<()> {
+        let account = Account {
+            handle: "savings".to_string(),
+            currency_code: "EUR".to_string(),
+            balances: vec![
+                BalanceRecord {
+                    date: NaiveDate::from_ymd_opt(2022, 12, 31).unwrap(),
+                    amount: dec!(100),
+                    ..Default::default()
+                },
+                BalanceRecord {
+                    date: NaiveDate::from_ymd_opt(2023, 1, 31).unwrap(),
+                    amount: dec!(200),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let period = OpenPeriod::for_account(&account, 2023).unwrap();
+
+        let series = BalanceValuation.value_series(
+            &account,
+            &period,
+            &ReportContext::new(Facts::empty(), None),
+        )?;
+        assert_eq!(
+            series,
+            vec![ValuePoint {
+                date: NaiveDate::from_ymd_opt(2023, 1, 31).unwrap(),
+                value: Money::new(dec!(200), "eur"),
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wallet_valuation() -> Result<()> {
+        let context = ReportContext::new(
+            Facts {
+                years: vec![AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![
+                        ExchangeRate::new("EUR".to_string(), 0.80).unwrap(),
+                        ExchangeRate::new("GBP".to_string(), 0.50).unwrap(),
+                    ],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            None,
+        );
+        let account = Account {
+            handle: "wallet".to_string(),
+            currency_code: "USD".to_string(),
+            kind: AccountKind::Wallet,
+            balances: vec![
+                record("2023-03-31", dec!(400), "gbp"),
+                record("2023-01-31", dec!(800), "eur"),
+                record("2023-03-31", dec!(100), "usd"),
+                record("2023-06-30", dec!(0), "eur"),
+            ],
+            ..Default::default()
+        };
+        let period = OpenPeriod::for_account(&account, 2023).unwrap();
+
+        let series = WalletValuation.value_series(&account, &period, &context)?;
+        let values: Vec<(String, Money)> = series
+            .into_iter()
+            .map(|point| (point.date.to_string(), point.value))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                // 800 EUR
+                ("2023-01-31".to_string(), Money::usd(dec!(1000))),
+                // 800 EUR carried forward, 400 GBP and 100 USD
+                ("2023-03-31".to_string(), Money::usd(dec!(1900))),
+                // EUR emptied
+                ("2023-06-30".to_string(), Money::usd(dec!(900))),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_surrender_valuation() -> Result<()> {
+        let account = Account {
+            handle: "policy".to_string(),
+            currency_code: "EUR".to_string(),
+            kind: AccountKind::Insurance,
+            opening_date: NaiveDate::from_ymd_opt(2023, 3, 1),
+            surrender_values: vec![
+                SurrenderValueRecord {
+                    date: NaiveDate::from_ymd_opt(2022, 12, 31).unwrap(),
+                    surrender_value: dec!(900),
+                },
+                SurrenderValueRecord {
+                    date: NaiveDate::from_ymd_opt(2023, 12, 31).unwrap(),
+                    surrender_value: dec!(1000),
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(account.valuation_name(), SURRENDER_VALUATION);
+        let period = OpenPeriod::for_account(&account, 2023).unwrap();
+
+        let series = SurrenderValuation.value_series(
+            &account,
+            &period,
+            &ReportContext::new(Facts::empty(), None),
+        )?;
+        assert_eq!(
+            series,
+            vec![ValuePoint {
+                date: NaiveDate::from_ymd_opt(2023, 12, 31).unwrap(),
+                value: Money::new(dec!(1000), "eur"),
+            }]
+        );
+
+        Ok(())
+    }
+}
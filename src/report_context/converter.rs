@@ -1,15 +1,59 @@
 use crate::facts::ExchangeRate;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::ops::Deref;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RateSource {
     UserProvided,
     IrsProvided,
+    /// Supplied by the named rate set, e.g. ECB reference rates
+    RateSet(String),
+    /// The amount was already in USD
+    NotConverted,
+    /// No source had a rate for the report's year, so the rate for the nearest year that has one was used, as the
+    /// run opted into with `RateFallback::NearestYear`
+    NearestYear {
+        year: i32,
+        source: Box<RateSource>,
+    },
+    /// The daily rate on the date of the maximum, from a price series imported for the currency, as filed under
+    /// `MaximumPolicy::MaxOfConverted`
+    DailyRate {
+        date: NaiveDate,
+    },
+    /// The USD value or rate given on the balance record for the date of the maximum, e.g. the provider's own USD
+    /// equivalent on a statement
+    RecordProvided {
+        date: NaiveDate,
+    },
+}
+
+impl fmt::Display for RateSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateSource::UserProvided => write!(f, "user-provided rate"),
+            RateSource::IrsProvided => write!(f, "IRS rate"),
+            RateSource::RateSet(name) => write!(f, "{} rate", name),
+            RateSource::NotConverted => write!(f, "no conversion"),
+            RateSource::NearestYear { year, source } => {
+                write!(f, "{} for {}, the nearest year with one", source, year)
+            }
+            RateSource::DailyRate { date } => write!(f, "daily rate for {}", date),
+            RateSource::RecordProvided { date } => {
+                write!(f, "USD value on the record for {}", date)
+            }
+        }
+    }
 }
 
 pub struct Converter {
     exchange_rate: ExchangeRate,
     source: RateSource,
+    /// Whether the source was pinned to the currency, rather than being the first in precedence with a rate
+    pinned: bool,
 }
 
 /***
@@ -29,12 +73,23 @@ impl Converter {
         Self {
             exchange_rate,
             source,
+            pinned: false,
         }
     }
 
+    /// Records that the source was pinned to the currency
+    pub fn pinned(mut self) -> Self {
+        self.pinned = true;
+        self
+    }
+
     pub fn source(&self) -> &RateSource {
         &self.source
     }
+
+    pub fn is_pinned(&self) -> bool {
+        self.pinned
+    }
 }
 
 impl Deref for Converter {
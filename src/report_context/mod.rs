@@ -1,12 +1,76 @@
 pub mod converter;
+pub mod rate_checks;
 pub use self::converter::{Converter, RateSource};
-use anyhow::{bail, Result};
+pub use self::rate_checks::RateCheckConfig;
+use anyhow::{anyhow, bail, Result};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
 
-use crate::facts::Facts;
+use crate::data::Account;
+use crate::facts::rate_set::{IRS_RATES, USER_RATES};
+use crate::facts::{CurrencyDefinition, ExchangeRate, Facts, RateKind, RateSet};
+use crate::money::{Money, USD};
+use crate::progress::Progress;
+use crate::report::cache::ReportCache;
+use crate::report::valuation::{BalanceValuation, SurrenderValuation, Valuation, WalletValuation};
+
+/// What to do when no rate source has a currency's rate for the report's year
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RateFallback {
+    /// Fail, so a missing rate is never silently papered over
+    #[default]
+    None,
+    /// Use the rate for the nearest year that has one, the earlier year on a tie, and say so in the report
+    NearestYear,
+}
+
+impl RateFallback {
+    fn is_none(&self) -> bool {
+        *self == RateFallback::None
+    }
+}
+
+impl FromStr for RateFallback {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "none" => Ok(Self::None),
+            "nearest-year" => Ok(Self::NearestYear),
+            _ => Err(format!(
+                "unknown rate fallback {:?}, expected `none` or `nearest-year`",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for RateFallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateFallback::None => write!(f, "none"),
+            RateFallback::NearestYear => write!(f, "nearest-year"),
+        }
+    }
+}
 
 pub struct ReportContext {
     facts: Facts,
     extensions: Facts,
+    rate_sets: Vec<RateSet>,
+    /// Names of the rate sources to consult, in order
+    precedence: Vec<String>,
+    /// Names of the rate sources pinned to currencies, by lowercase currency code
+    rate_pins: BTreeMap<String, String>,
+    rate_fallback: RateFallback,
+    valuations: Vec<Box<dyn Valuation>>,
+    progress: Progress,
+    cache: Option<ReportCache>,
 }
 
 impl ReportContext {
@@ -14,21 +78,172 @@ impl ReportContext {
         Self {
             facts,
             extensions: extensions.into().unwrap_or_else(Facts::empty),
+            rate_sets: Vec::new(),
+            precedence: vec![USER_RATES.to_string(), IRS_RATES.to_string()],
+            rate_pins: BTreeMap::new(),
+            rate_fallback: RateFallback::None,
+            valuations: vec![
+                Box::new(BalanceValuation),
+                Box::new(WalletValuation),
+                Box::new(SurrenderValuation),
+            ],
+            progress: Progress::hidden(),
+            cache: None,
+        }
+    }
+
+    /// Shows progress through each report's accounts
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    /// Reuses accounts' maxima computed by earlier runs, recomputing only those whose inputs changed
+    pub fn with_cache(mut self, cache: ReportCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn cache(&self) -> Option<&ReportCache> {
+        self.cache.as_ref()
+    }
+
+    /// Registers a valuation that accounts can select by name. A valuation registered later replaces an earlier
+    /// one with the same name.
+    pub fn with_valuation(mut self, valuation: Box<dyn Valuation>) -> Self {
+        self.valuations.push(valuation);
+        self
+    }
+
+    /// The valuation named by the account, or the default for its kind
+    pub fn valuation_for(&self, account: &Account) -> Result<&dyn Valuation> {
+        let name = account.valuation_name();
+
+        match self
+            .valuations
+            .iter()
+            .rev()
+            .find(|valuation| valuation.name() == name)
+        {
+            Some(valuation) => Ok(valuation.as_ref()),
+            None => bail!(
+                "Unknown valuation {:?} for account {}",
+                name,
+                account.handle
+            ),
+        }
+    }
+
+    /// Adds named rate sets. Unless a precedence order is given, they are consulted after the user's and the IRS
+    /// rates, in the order provided.
+    pub fn with_rate_sets(mut self, rate_sets: Vec<RateSet>) -> Self {
+        self.precedence
+            .extend(rate_sets.iter().map(|rate_set| rate_set.name.clone()));
+        self.rate_sets.extend(rate_sets);
+        self
+    }
+
+    /// Sets the order in which rate sources are consulted, by name (`user`, `irs`, or a rate set name). Sources left
+    /// out of the order are not used.
+    pub fn with_precedence(mut self, precedence: Vec<String>) -> Result<Self> {
+        let precedence: Vec<String> = precedence.iter().map(|name| name.to_lowercase()).collect();
+
+        for (index, name) in precedence.iter().enumerate() {
+            if self.rate_source(name).is_none() {
+                bail!("Unknown rate source {:?} in rate precedence", name);
+            }
+            if precedence[..index].contains(name) {
+                bail!("Rate source {:?} appears twice in rate precedence", name);
+            }
         }
+
+        self.precedence = precedence;
+        Ok(self)
+    }
+
+    /// Pins currencies to rate sources by name (`user`, `irs`, or a rate set name), e.g. a broker's year-end rates for
+    /// ILS. A pinned currency is only looked up in its source, whatever the precedence, so a source can be pinned even
+    /// when the precedence leaves it out.
+    pub fn with_rate_pins(mut self, pins: &BTreeMap<String, String>) -> Result<Self> {
+        for (currency, name) in pins {
+            let name = name.to_lowercase();
+            if self.rate_source(&name).is_none() {
+                bail!(
+                    "Unknown rate source {:?} pinned for {}",
+                    name,
+                    currency.to_uppercase()
+                );
+            }
+            self.rate_pins.insert(currency.to_lowercase(), name);
+        }
+        Ok(self)
+    }
+
+    /// The name of the rate source pinned to the currency, if any
+    pub fn rate_pin(&self, currency_code: &str) -> Option<&str> {
+        self.rate_pins
+            .get(&currency_code.to_lowercase())
+            .map(String::as_str)
+    }
+
+    /// Sets what happens when no source has a rate for the year. Falling back is opt-in, for a single run.
+    pub fn with_rate_fallback(mut self, rate_fallback: RateFallback) -> Self {
+        self.rate_fallback = rate_fallback;
+        self
+    }
+
+    /// Converts an amount from its currency to USD for a specific year
+    ///
+    /// # Arguments
+    /// * `year` - The year for which to perform the conversion
+    /// * `amount` - The amount in its source currency (e.g., EUR, CHF)
+    ///
+    /// # Returns
+    /// * `Result<Money, anyhow::Error>` - The converted amount in USD
+    pub fn convert_to_usd(&self, year: i32, amount: &Money) -> Result<Money> {
+        self.convert_to_usd_with(year, amount, RateKind::YearEnd)
+    }
+
+    /// Converts an amount to USD for a specific year at the rate of the given kind, e.g. the yearly average to
+    /// translate income for Form 1040 rather than the year-end rate the FBAR is filed at
+    pub fn convert_to_usd_with(&self, year: i32, amount: &Money, kind: RateKind) -> Result<Money> {
+        self.find_rate(year, &amount.currency, kind)?
+            .convert_to_usd(amount)
     }
 
-    /// Converts an amount from a source currency to USD for a specific year
+    /// Converts many amounts to USD for a specific year, looking up each currency's rate only once
     ///
     /// # Arguments
     /// * `year` - The year for which to perform the conversion
-    /// * `source_currency` - The currency code to convert from (e.g., "EUR", "CHF")
-    /// * `amount` - The amount in the source currency
+    /// * `amounts` - Pairs of currency code and amount in that currency
     ///
     /// # Returns
-    /// * `Result<f64, anyhow::Error>` - The converted amount in USD
-    pub fn convert_to_usd(&self, year: i32, source_currency: &str, amount: f64) -> Result<f64> {
-        self.find_exchange_rate(year, source_currency)
-            .map(|rate| rate.convert_to_usd(amount))
+    /// * `Vec<Result<Money, anyhow::Error>>` - The converted amounts in USD, in the order given. Each fails on its own,
+    ///   e.g. for a currency without a rate, without failing the rest.
+    pub fn convert_many<C: AsRef<str>>(
+        &self,
+        year: i32,
+        amounts: impl IntoIterator<Item = (C, Decimal)>,
+    ) -> Vec<Result<Money>> {
+        let mut rates: HashMap<String, Result<Converter, String>> = HashMap::new();
+        amounts
+            .into_iter()
+            .map(|(currency, amount)| {
+                let currency = currency.as_ref().to_lowercase();
+                let rate = rates.entry(currency.clone()).or_insert_with(|| {
+                    self.find_exchange_rate(year, &currency)
+                        .map_err(|err| err.to_string())
+                });
+                match rate {
+                    Ok(rate) => rate.convert_to_usd(&Money::new(amount, currency)),
+                    Err(message) => Err(anyhow!("{}", message)),
+                }
+            })
+            .collect()
     }
 
     /// Converts an amount from USD to a target currency for a specific year
@@ -39,30 +254,190 @@ impl ReportContext {
     /// * `amount` - The amount in USD
     ///
     /// # Returns
-    /// * `Result<f64, anyhow::Error>` - The converted amount in the target currency
-    pub fn convert_from_usd(&self, year: i32, target_currency: &str, amount: f64) -> Result<f64> {
-        self.find_exchange_rate(year, target_currency)
-            .map(|rate| rate.convert_from_usd(amount))
+    /// * `Result<Money, anyhow::Error>` - The converted amount in the target currency
+    pub fn convert_from_usd(
+        &self,
+        year: i32,
+        target_currency: &str,
+        amount: &Money,
+    ) -> Result<Money> {
+        Ok(self
+            .find_exchange_rate(year, target_currency)?
+            .convert_from_usd_unrounded(amount)?
+            .round_to(self.minor_units(target_currency)))
     }
 
-    // Helper method to find the appropriate exchange rate
-    fn find_exchange_rate(&self, year: i32, currency_code: &str) -> Result<Converter> {
+    /// The currency defined in `fact_extensions` with the code, if any
+    pub fn currency(&self, code: &str) -> Option<&CurrencyDefinition> {
+        self.extensions.currency(code)
+    }
+
+    /// Decimal places amounts in the currency are rounded and written to, including currencies defined in
+    /// `fact_extensions`
+    pub fn minor_units(&self, code: &str) -> u32 {
+        self.extensions.minor_units(code)
+    }
+
+    /// Hash of the bundled IRS facts, identifying the dataset reports were generated with
+    pub fn facts_hash(&self) -> Result<String> {
+        self.facts.content_hash()
+    }
+
+    /// Hash of every rate the context can consult and the order it consults them in
+    pub fn inputs_hash(&self) -> Result<String> {
+        #[derive(Serialize)]
+        struct Inputs<'a> {
+            facts: &'a Facts,
+            extensions: &'a Facts,
+            rate_sets: Vec<(&'a str, &'a Facts)>,
+            precedence: &'a [String],
+            #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+            rate_pins: &'a BTreeMap<String, String>,
+            #[serde(skip_serializing_if = "RateFallback::is_none")]
+            rate_fallback: RateFallback,
+        }
+
+        let serialized = serde_yaml::to_string(&Inputs {
+            facts: &self.facts,
+            extensions: &self.extensions,
+            rate_sets: self
+                .rate_sets
+                .iter()
+                .map(|rate_set| (rate_set.name.as_str(), &rate_set.facts))
+                .collect(),
+            precedence: &self.precedence,
+            rate_pins: &self.rate_pins,
+            rate_fallback: self.rate_fallback,
+        })?;
+        Ok(hex::encode(Sha256::digest(serialized.as_bytes())))
+    }
+
+    /// The bundled IRS rate for a currency and year, whatever the precedence
+    pub fn irs_rate(&self, year: i32, currency_code: &str) -> Option<&ExchangeRate> {
+        self.facts.get_exchange_rate(year, currency_code)
+    }
+
+    /// Finds the year-end exchange rate for a currency and year from the source pinned to the currency, or else the
+    /// highest-precedence source that has one. Under the nearest-year fallback, a currency no source has a rate for in
+    /// the year gets the nearest year's rate instead.
+    pub fn find_exchange_rate(&self, year: i32, currency_code: &str) -> Result<Converter> {
+        self.find_rate(year, currency_code, RateKind::YearEnd)
+    }
+
+    /// Finds the rate of the kind for a currency and year, from the same sources in the same order as
+    /// `find_exchange_rate`
+    pub fn find_rate(&self, year: i32, currency_code: &str, kind: RateKind) -> Result<Converter> {
         let lookup_code = currency_code.to_lowercase();
 
-        // First check extensions, then fall back to facts
-        if let Some(rate) = self
-            .extensions
-            .get_exchange_rate(year, lookup_code.to_string())
-        {
-            Ok(Converter::new(rate.clone(), RateSource::UserProvided))
-        } else if let Some(rate) = self.facts.get_exchange_rate(year, lookup_code.clone()) {
-            Ok(Converter::new(rate.clone(), RateSource::IrsProvided))
-        } else {
-            bail!(
-                "No exchange rate found for {} in year {}",
+        // Amounts already in USD (e.g. crypto priced in USD) don't need a rate
+        if lookup_code == USD {
+            return Ok(Converter::new(
+                ExchangeRate {
+                    currency_code: lookup_code,
+                    rate: 1.0,
+                },
+                RateSource::NotConverted,
+            ));
+        }
+
+        // A pinned currency is only looked up in its source; the rest check each source in order of precedence
+        let pin = self.rate_pins.get(&lookup_code);
+        let sources = pin.map_or(self.precedence.as_slice(), std::slice::from_ref);
+        let mark_pin = |converter: Converter| match pin {
+            Some(_) => converter.pinned(),
+            None => converter,
+        };
+        for name in sources {
+            if let Some((facts, source)) = self.rate_source(name) {
+                if let Some(rate) = facts.get_rate(year, lookup_code.clone(), kind) {
+                    return Ok(mark_pin(Converter::new(rate.clone(), source)));
+                }
+            }
+        }
+
+        if self.rate_fallback == RateFallback::NearestYear {
+            if let Some(found) = self.nearest_year_rate(year, &lookup_code, kind, sources) {
+                let converter = mark_pin(found);
+                tracing::warn!(
+                    "No {} {} rate for {}; falling back to the {}",
+                    currency_code.to_uppercase(),
+                    kind,
+                    year,
+                    converter.source()
+                );
+                return Ok(converter);
+            }
+        }
+
+        // Errors for the year-end rate read as they always have, since most runs only ever need it
+        let kind = match kind {
+            RateKind::YearEnd => String::new(),
+            kind => format!("{} ", kind),
+        };
+        match pin {
+            Some(name) => bail!(
+                "No {}exchange rate found for {} in year {} from {:?}, the rate source pinned to it",
+                kind,
+                currency_code,
+                year,
+                name
+            ),
+            None => bail!(
+                "No {}exchange rate found for {} in year {}",
+                kind,
                 currency_code,
                 year
+            ),
+        }
+    }
+
+    /// The currency's rate from the year closest to `year` that any of the sources has one for, the earlier year on a
+    /// tie, and the highest-precedence source for that year
+    fn nearest_year_rate(
+        &self,
+        year: i32,
+        currency_code: &str,
+        kind: RateKind,
+        sources: &[String],
+    ) -> Option<Converter> {
+        let mut nearest: Option<(i32, &ExchangeRate, RateSource)> = None;
+        for name in sources {
+            let Some((facts, source)) = self.rate_source(name) else {
+                continue;
+            };
+            for fact in &facts.years {
+                let Some(rate) = facts.get_rate(fact.year, currency_code, kind) else {
+                    continue;
+                };
+                let closer = nearest.as_ref().is_none_or(|(best, _, _)| {
+                    ((fact.year - year).abs(), fact.year) < ((best - year).abs(), *best)
+                });
+                if closer {
+                    nearest = Some((fact.year, rate, source.clone()));
+                }
+            }
+        }
+
+        nearest.map(|(found, rate, source)| {
+            Converter::new(
+                rate.clone(),
+                RateSource::NearestYear {
+                    year: found,
+                    source: Box::new(source),
+                },
             )
+        })
+    }
+
+    fn rate_source(&self, name: &str) -> Option<(&Facts, RateSource)> {
+        match name {
+            USER_RATES => Some((&self.extensions, RateSource::UserProvided)),
+            IRS_RATES => Some((&self.facts, RateSource::IrsProvided)),
+            _ => self
+                .rate_sets
+                .iter()
+                .find(|rate_set| rate_set.name == name)
+                .map(|rate_set| (&rate_set.facts, RateSource::RateSet(rate_set.name.clone()))),
         }
     }
 }
@@ -71,6 +446,7 @@ impl ReportContext {
 mod tests {
     use super::*;
     use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use rust_decimal_macros::dec;
 
     fn create_test_facts() -> Facts {
         Facts {
@@ -80,7 +456,9 @@ mod tests {
                     ExchangeRate::new("EUR".to_string(), 0.85).unwrap(),
                     ExchangeRate::new("CHF".to_string(), 0.90).unwrap(),
                 ],
+                ..Default::default()
             }],
+            ..Default::default()
         }
     }
 
@@ -92,7 +470,9 @@ mod tests {
                     ExchangeRate::new("EUR".to_string(), 0.80).unwrap(),
                     // CHF is not present in the extensions to test that the IRS rates are used
                 ],
+                ..Default::default()
             }],
+            ..Default::default()
         }
     }
 
@@ -103,12 +483,56 @@ mod tests {
         let context = ReportContext::new(facts, extensions);
 
         // Test EUR conversion
-        assert_eq!(context.convert_to_usd(2023, "EUR", 85.0)?, 106.25);
-        assert_eq!(context.convert_from_usd(2023, "EUR", 100.0)?, 80.0);
+        assert_eq!(
+            context.convert_to_usd(2023, &Money::new(dec!(85.0), "EUR"))?,
+            Money::usd(dec!(106.25))
+        );
+        assert_eq!(
+            context.convert_from_usd(2023, "EUR", &Money::usd(dec!(100.0)))?,
+            Money::new(dec!(80.0), "eur")
+        );
 
         // Test CHF conversion
-        assert_eq!(context.convert_to_usd(2023, "CHF", 90.0)?, 100.0);
-        assert_eq!(context.convert_from_usd(2023, "CHF", 100.0)?, 90.0);
+        assert_eq!(
+            context.convert_to_usd(2023, &Money::new(dec!(90.0), "CHF"))?,
+            Money::usd(dec!(100.0))
+        );
+        assert_eq!(
+            context.convert_from_usd(2023, "CHF", &Money::usd(dec!(100.0)))?,
+            Money::new(dec!(90.0), "chf")
+        );
+
+        // Only USD amounts can be converted from USD
+        assert!(context
+            .convert_from_usd(2023, "CHF", &Money::new(dec!(100.0), "EUR"))
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_many() -> Result<()> {
+        let context = ReportContext::new(create_test_facts(), create_test_fact_extensions());
+
+        let results = context.convert_many(
+            2023,
+            [
+                ("EUR", dec!(85.0)),
+                ("INVALID", dec!(100.0)),
+                ("chf", dec!(90.0)),
+                ("eur", dec!(8.5)),
+            ],
+        );
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap(), &Money::usd(dec!(106.25)));
+        // A currency without a rate fails on its own
+        assert!(results[1]
+            .as_ref()
+            .unwrap_err()
+            .to_string()
+            .contains("No exchange rate found"));
+        assert_eq!(results[2].as_ref().unwrap(), &Money::usd(dec!(100.0)));
+        assert_eq!(results[3].as_ref().unwrap(), &Money::usd(dec!(10.63)));
 
         Ok(())
     }
@@ -119,7 +543,7 @@ mod tests {
         let extensions = create_test_fact_extensions();
         let context = ReportContext::new(facts, extensions);
 
-        let result = context.convert_to_usd(2023, "INVALID", 100.0);
+        let result = context.convert_to_usd(2023, &Money::new(dec!(100.0), "INVALID"));
         assert!(result.is_err());
         assert!(result
             .unwrap_err()
@@ -133,7 +557,7 @@ mod tests {
         let extensions = create_test_facts();
         let context = ReportContext::new(facts, extensions);
 
-        let result = context.convert_to_usd(1999, "EUR", 100.0);
+        let result = context.convert_to_usd(1999, &Money::new(dec!(100.0), "EUR"));
         assert!(result.is_err());
         assert!(result
             .unwrap_err()
@@ -156,6 +580,185 @@ mod tests {
         let rate = context.find_exchange_rate(2023, "EUR")?;
         assert_eq!(rate.source(), &RateSource::UserProvided);
 
+        // USD amounts pass through unconverted
+        let rate = context.find_exchange_rate(2023, "USD")?;
+        assert_eq!(rate.source(), &RateSource::NotConverted);
+        assert_eq!(
+            rate.convert_to_usd(&Money::usd(dec!(12.34)))?,
+            Money::usd(dec!(12.34))
+        );
+
         Ok(())
     }
+
+    fn create_test_rate_sets() -> Vec<RateSet> {
+        vec![RateSet {
+            name: "ecb".to_string(),
+            facts: Facts {
+                years: vec![AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![
+                        ExchangeRate::new("EUR".to_string(), 0.90).unwrap(),
+                        ExchangeRate::new("JPY".to_string(), 140.0).unwrap(),
+                    ],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        }]
+    }
+
+    #[test]
+    fn test_rate_sets_default_precedence() -> Result<()> {
+        let context = ReportContext::new(create_test_facts(), create_test_fact_extensions())
+            .with_rate_sets(create_test_rate_sets());
+
+        // User and IRS rates still win by default
+        let rate = context.find_exchange_rate(2023, "EUR")?;
+        assert_eq!(rate.source(), &RateSource::UserProvided);
+        let rate = context.find_exchange_rate(2023, "CHF")?;
+        assert_eq!(rate.source(), &RateSource::IrsProvided);
+
+        // Rate sets fill in currencies the other sources lack
+        let rate = context.find_exchange_rate(2023, "JPY")?;
+        assert_eq!(rate.source(), &RateSource::RateSet("ecb".to_string()));
+        assert_eq!(rate.rate, 140.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_configured_precedence() -> Result<()> {
+        let context = ReportContext::new(create_test_facts(), create_test_fact_extensions())
+            .with_rate_sets(create_test_rate_sets())
+            .with_precedence(vec!["ECB".to_string(), "irs".to_string()])?;
+
+        let rate = context.find_exchange_rate(2023, "EUR")?;
+        assert_eq!(rate.source(), &RateSource::RateSet("ecb".to_string()));
+        assert_eq!(rate.rate, 0.90);
+
+        // Sources left out of the precedence are not consulted
+        let context = ReportContext::new(create_test_facts(), create_test_fact_extensions())
+            .with_rate_sets(create_test_rate_sets())
+            .with_precedence(vec!["user".to_string()])?;
+        assert!(context.find_exchange_rate(2023, "CHF").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_precedence() {
+        let context =
+            ReportContext::new(create_test_facts(), None).with_rate_sets(create_test_rate_sets());
+        let result = context.with_precedence(vec!["boe".to_string()]);
+        assert!(result.is_err());
+
+        let context = ReportContext::new(create_test_facts(), None);
+        let result = context.with_precedence(vec!["irs".to_string(), "IRS".to_string()]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_rate_pins() -> Result<()> {
+        let pins = BTreeMap::from([("EUR".to_string(), "ECB".to_string())]);
+        let context = ReportContext::new(create_test_facts(), create_test_fact_extensions())
+            .with_rate_sets(create_test_rate_sets())
+            .with_precedence(vec!["user".to_string(), "irs".to_string()])?
+            .with_rate_pins(&pins)?;
+
+        // The pinned source wins for its currency, even though the precedence leaves it out
+        let rate = context.find_exchange_rate(2023, "eur")?;
+        assert_eq!(rate.source(), &RateSource::RateSet("ecb".to_string()));
+        assert_eq!(rate.rate, 0.90);
+        assert!(rate.is_pinned());
+        assert_eq!(context.rate_pin("EUR"), Some("ecb"));
+        // Other currencies follow the precedence
+        let rate = context.find_exchange_rate(2023, "CHF")?;
+        assert_eq!(rate.source(), &RateSource::IrsProvided);
+        assert!(!rate.is_pinned());
+
+        // A pinned currency isn't looked up anywhere else
+        let pins = BTreeMap::from([("CHF".to_string(), "ecb".to_string())]);
+        let pinned = ReportContext::new(create_test_facts(), None)
+            .with_rate_sets(create_test_rate_sets())
+            .with_rate_pins(&pins)?;
+        let Err(err) = pinned.find_exchange_rate(2023, "CHF") else {
+            panic!("CHF has no rate in the source pinned to it");
+        };
+        assert!(err.to_string().contains("the rate source pinned to it"));
+        assert_ne!(
+            pinned.inputs_hash()?,
+            ReportContext::new(create_test_facts(), None)
+                .with_rate_sets(create_test_rate_sets())
+                .inputs_hash()?
+        );
+
+        let pins = BTreeMap::from([("EUR".to_string(), "boe".to_string())]);
+        assert!(ReportContext::new(create_test_facts(), None)
+            .with_rate_pins(&pins)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_nearest_year_fallback() -> Result<()> {
+        let facts = Facts {
+            years: vec![
+                AnnualFact {
+                    year: 2021,
+                    exchange_rates: vec![ExchangeRate::new("EUR".to_string(), 0.82).unwrap()],
+                    ..Default::default()
+                },
+                AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![ExchangeRate::new("EUR".to_string(), 0.85).unwrap()],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Without opting in, a missing year fails
+        let context = ReportContext::new(facts.clone(), None);
+        assert!(context.find_exchange_rate(2022, "EUR").is_err());
+
+        let context = context.with_rate_fallback(RateFallback::NearestYear);
+        // Equally near years: the earlier one is used
+        let rate = context.find_exchange_rate(2022, "EUR")?;
+        assert_eq!(rate.rate, 0.82);
+        assert_eq!(
+            rate.source(),
+            &RateSource::NearestYear {
+                year: 2021,
+                source: Box::new(RateSource::IrsProvided)
+            }
+        );
+        assert_eq!(
+            rate.source().to_string(),
+            "IRS rate for 2021, the nearest year with one"
+        );
+        assert_eq!(context.find_exchange_rate(2025, "EUR")?.rate, 0.85);
+        // Years with a rate of their own are unaffected
+        assert_eq!(
+            context.find_exchange_rate(2023, "EUR")?.source(),
+            &RateSource::IrsProvided
+        );
+        // No year at all still fails
+        assert!(context.find_exchange_rate(2022, "CHF").is_err());
+
+        assert_ne!(
+            ReportContext::new(facts.clone(), None).inputs_hash()?,
+            context.inputs_hash()?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_rate_fallback() {
+        assert_eq!("nearest-year".parse(), Ok(RateFallback::NearestYear));
+        assert_eq!("none".parse(), Ok(RateFallback::None));
+        assert!("latest".parse::<RateFallback>().is_err());
+    }
 }
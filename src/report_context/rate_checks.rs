@@ -0,0 +1,190 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::ReportContext;
+use crate::facts::{ExchangeRate, Facts};
+
+/// Thresholds for flagging suspicious user-supplied exchange rates, as percentages
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+#[serde(default)]
+pub struct RateCheckConfig {
+    /// How far a user-provided rate may deviate from the IRS rate for the same year and currency
+    pub max_override_deviation: f64,
+    /// How far a user-provided rate may move from the previous year's rate for the same currency
+    pub max_yearly_change: f64,
+}
+
+impl Default for RateCheckConfig {
+    fn default() -> Self {
+        Self {
+            max_override_deviation: 10.0,
+            max_yearly_change: 25.0,
+        }
+    }
+}
+
+/***
+ * Sanity checks for user-supplied rates (fact extensions and named rate sets).
+ *
+ * A typo in a rate silently corrupts every USD figure derived from it, so rates that stray too far from the IRS rate
+ * or jump implausibly from one year to the next are flagged for the user to double check. The bundled IRS rates are
+ * trusted and not checked.
+ */
+impl ReportContext {
+    pub fn check_rates(&self, config: &RateCheckConfig) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        for (year, rate) in all_rates(&self.extensions) {
+            if let Some(irs_rate) = self
+                .facts
+                .get_exchange_rate(year, rate.currency_code.clone())
+            {
+                let deviation = percent_change(irs_rate.rate, rate.rate);
+                if deviation.abs() > config.max_override_deviation {
+                    warnings.push(format!(
+                        "User-provided {} rate for {} ({}) differs from the IRS rate ({}) by {:.1}%",
+                        rate.currency_code.to_uppercase(),
+                        year,
+                        rate.rate,
+                        irs_rate.rate,
+                        deviation
+                    ));
+                }
+            }
+
+            // The previous year may only be covered by the IRS rates
+            let previous = self
+                .extensions
+                .get_exchange_rate(year - 1, rate.currency_code.clone())
+                .or_else(|| {
+                    self.facts
+                        .get_exchange_rate(year - 1, rate.currency_code.clone())
+                });
+            warnings.extend(check_yearly_change(
+                "User-provided",
+                year,
+                rate,
+                previous,
+                config,
+            ));
+        }
+
+        for rate_set in &self.rate_sets {
+            for (year, rate) in all_rates(&rate_set.facts) {
+                let previous = rate_set
+                    .facts
+                    .get_exchange_rate(year - 1, rate.currency_code.clone());
+                let label = format!("Rate set {:?}", rate_set.name);
+                warnings.extend(check_yearly_change(&label, year, rate, previous, config));
+            }
+        }
+
+        warnings
+    }
+}
+
+fn all_rates(facts: &Facts) -> impl Iterator<Item = (i32, &ExchangeRate)> {
+    facts.years.iter().flat_map(|annual_fact| {
+        annual_fact
+            .exchange_rates
+            .iter()
+            .map(move |rate| (annual_fact.year, rate))
+    })
+}
+
+fn check_yearly_change(
+    label: &str,
+    year: i32,
+    rate: &ExchangeRate,
+    previous: Option<&ExchangeRate>,
+    config: &RateCheckConfig,
+) -> Option<String> {
+    let previous = previous?;
+    let change = percent_change(previous.rate, rate.rate);
+
+    (change.abs() > config.max_yearly_change).then(|| {
+        format!(
+            "{} {} rate for {} ({}) moved {:.1}% from {} ({})",
+            label,
+            rate.currency_code.to_uppercase(),
+            year,
+            rate.rate,
+            change,
+            year - 1,
+            previous.rate
+        )
+    })
+}
+
+fn percent_change(from: f64, to: f64) -> f64 {
+    (to - from) / from * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::facts::{AnnualFact, RateSet};
+
+    fn create_test_facts(rates: &[(i32, f64)]) -> Facts {
+        Facts {
+            years: rates
+                .iter()
+                .map(|(year, rate)| AnnualFact {
+                    year: *year,
+                    exchange_rates: vec![ExchangeRate::new("EUR".to_string(), *rate).unwrap()],
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_plausible_rates() {
+        let facts = create_test_facts(&[(2022, 0.95), (2023, 0.92)]);
+        let extensions = create_test_facts(&[(2023, 0.93)]);
+        let context = ReportContext::new(facts, extensions);
+
+        assert!(context.check_rates(&RateCheckConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn test_override_deviates_from_irs() {
+        let facts = create_test_facts(&[(2022, 0.95), (2023, 0.92)]);
+        // Decimal point slip: 9.2 instead of 0.92
+        let extensions = create_test_facts(&[(2023, 9.2)]);
+        let context = ReportContext::new(facts, extensions);
+
+        let warnings = context.check_rates(&RateCheckConfig::default());
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].contains("differs from the IRS rate (0.92) by 900.0%"));
+        assert!(warnings[1].contains("moved 868.4% from 2022 (0.95)"));
+    }
+
+    #[test]
+    fn test_configurable_thresholds() {
+        let facts = create_test_facts(&[(2023, 0.92)]);
+        let extensions = create_test_facts(&[(2023, 0.98)]);
+        let context = ReportContext::new(facts, extensions);
+
+        assert!(context.check_rates(&RateCheckConfig::default()).is_empty());
+
+        let strict_config = RateCheckConfig {
+            max_override_deviation: 5.0,
+            ..RateCheckConfig::default()
+        };
+        assert_eq!(context.check_rates(&strict_config).len(), 1);
+    }
+
+    #[test]
+    fn test_rate_set_yearly_change() {
+        let context = ReportContext::new(Facts::empty(), None).with_rate_sets(vec![RateSet {
+            name: "ecb".to_string(),
+            facts: create_test_facts(&[(2022, 0.95), (2023, 0.50)]),
+        }]);
+
+        let warnings = context.check_rates(&RateCheckConfig::default());
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("Rate set \"ecb\" EUR rate for 2023"));
+    }
+}
@@ -0,0 +1,368 @@
+//! The records bundle kept for the five years FinCEN requires a filer to retain the records behind an FBAR: the filing
+//! itself, the accounts it covers, their statements, the rates they were converted at and how each figure was
+//! computed, in one zip with an index.
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::data::{Account, Entity, Filing, Provider, UserData};
+use crate::output::{self, Artifact, Layout, Manifest};
+use crate::package;
+use crate::report::Report;
+use crate::report_context::{RateSource, ReportContext};
+use crate::statements::StatementFile;
+
+/// Years records have to be kept for, from the FBAR's due date
+pub const RETENTION_YEARS: i32 = 5;
+/// Name bundles are recorded under in the output directory's manifest and layout
+pub const RECORDS_FORMAT: &str = "records";
+/// Name of the bundle's index, the last file in the archive
+pub const RECORDS_INDEX: &str = "index.yml";
+
+/// What's in a records bundle and how long to keep it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RecordsIndex {
+    pub year: i32,
+    pub report_id: String,
+    pub generated_at: DateTime<Utc>,
+    /// Five years after the FBAR's due date
+    pub retain_until: NaiveDate,
+    pub files: Vec<IndexEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IndexEntry {
+    pub name: String,
+    pub description: String,
+    /// SHA-256 of the file, hex encoded
+    pub sha256: String,
+}
+
+/// An account's details as they stood for the year, with only that year's records
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RetainedAccount {
+    pub account: Account,
+    pub provider: Option<Provider>,
+    pub entity: Option<Entity>,
+}
+
+/// A rate the year's accounts were converted at
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RetainedRate {
+    pub currency_code: String,
+    /// Units of the currency per USD
+    pub rate: f64,
+    pub source: RateSource,
+    /// The IRS rate for the year, for comparison when another source was used
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub irs_rate: Option<f64>,
+    pub accounts: Vec<String>,
+}
+
+/// The files of a records bundle, in archive order: the filing package under `filing/`, the accounts, the rates, each
+/// account's statements under `statements/`, and last the index
+pub fn records_files(
+    report: &Report,
+    user_data: &UserData,
+    context: &ReportContext,
+    generated_at: DateTime<Utc>,
+) -> Result<Vec<(String, String, Vec<u8>)>> {
+    let year = report.year;
+    let handles: Vec<&str> = report
+        .accounts
+        .iter()
+        .map(|account| account.handle.as_str())
+        .chain(
+            report
+                .excluded
+                .iter()
+                .map(|excluded| excluded.handle.as_str()),
+        )
+        .collect();
+    let accounts: Vec<&Account> = user_data
+        .accounts
+        .iter()
+        .filter(|account| handles.contains(&account.handle.as_str()))
+        .collect();
+
+    let mut files = Vec::new();
+    for (name, contents) in package::package_files(report, user_data, generated_at)? {
+        let description = match name.split('-').next() {
+            Some("fbar") => "The FinCEN XML batch filed",
+            Some("worksheet") => "PDF worksheet of the filing",
+            Some("report") => "The computed report, as JSON",
+            Some("audit") => "How each account's maximum and its conversion to USD were computed",
+            Some("provenance") => "Hashes of the inputs and facts, and the statements imported",
+            _ => "The filing package's manifest",
+        };
+        files.push((
+            format!("filing/{}", name),
+            description.to_string(),
+            contents,
+        ));
+    }
+
+    let retained: Vec<RetainedAccount> = accounts
+        .iter()
+        .map(|account| RetainedAccount {
+            account: records_for_year(account, year),
+            provider: user_data
+                .providers
+                .iter()
+                .find(|provider| provider.handle == account.provider)
+                .cloned(),
+            entity: account.entity.as_ref().and_then(|handle| {
+                user_data
+                    .entities
+                    .iter()
+                    .find(|entity| entity.handle == *handle)
+                    .cloned()
+            }),
+        })
+        .collect();
+    files.push((
+        format!("accounts-{}.yml", year),
+        "Details of each account reported or excluded, its provider and entity, with the year's records".to_string(),
+        serde_yaml::to_string(&retained)?.into_bytes(),
+    ));
+
+    files.push((
+        format!("rates-{}.yml", year),
+        "The exchange rate each currency was converted at, its source, and the IRS rate"
+            .to_string(),
+        serde_yaml::to_string(&rates(report, context))?.into_bytes(),
+    ));
+
+    for account in &accounts {
+        let imports: Vec<_> = account
+            .imports
+            .iter()
+            .filter(|import| {
+                import
+                    .balances
+                    .iter()
+                    .any(|record| record.date.year() == year)
+            })
+            .cloned()
+            .collect();
+        if imports.is_empty() {
+            continue;
+        }
+        let statements = StatementFile::new(&account.handle, imports);
+        files.push((
+            format!("statements/{}.yml", account.handle),
+            format!(
+                "Statements imported for {} with records in {}, with their checksums",
+                account.handle, year
+            ),
+            serde_yaml::to_string(&statements)?.into_bytes(),
+        ));
+    }
+
+    let retain_until = Filing::due_date(year)
+        .and_then(|due| due.with_year(due.year() + RETENTION_YEARS))
+        .ok_or_else(|| anyhow!("No due date for {}", year))?;
+    let index = RecordsIndex {
+        year,
+        report_id: output::report_metadata(report)?.report_id.clone(),
+        generated_at,
+        retain_until,
+        files: files
+            .iter()
+            .map(|(name, description, contents)| IndexEntry {
+                name: name.clone(),
+                description: description.clone(),
+                sha256: hex::encode(Sha256::digest(contents)),
+            })
+            .collect(),
+    };
+    files.push((
+        RECORDS_INDEX.to_string(),
+        "This index".to_string(),
+        serde_yaml::to_string(&index)?.into_bytes(),
+    ));
+
+    Ok(files)
+}
+
+/// Builds the records bundle for a report and writes it to its place in the layout under `out`, named after the year,
+/// the build time and the hash of its index, and recorded in the output directory's manifest
+pub fn write_records(
+    out: &Path,
+    layout: &Layout,
+    report: &Report,
+    user_data: &UserData,
+    context: &ReportContext,
+    generated_at: DateTime<Utc>,
+) -> Result<Artifact> {
+    let files: Vec<(String, Vec<u8>)> = records_files(report, user_data, context, generated_at)?
+        .into_iter()
+        .map(|(name, _, contents)| (name, contents))
+        .collect();
+    let (_, index) = files.last().context("Records bundle has no index")?;
+    let stamp = hex::encode(Sha256::digest(index));
+    let archive = package::zip_files(&files, generated_at)?;
+
+    let filer = output::filer_handle(user_data);
+    let relative = layout
+        .directory(report.year, filer, RECORDS_FORMAT)
+        .join(format!(
+            "fbar-records-{}-{}-{}.zip",
+            report.year,
+            generated_at.format("%Y%m%dT%H%M%SZ"),
+            &stamp[..12]
+        ));
+    let artifact = output::write_artifact(out, relative, &archive, report, filer, RECORDS_FORMAT)?;
+
+    let mut manifest = Manifest::load(out)?;
+    manifest.record(artifact.clone());
+    manifest.save(out)?;
+    Ok(artifact)
+}
+
+fn records_for_year(account: &Account, year: i32) -> Account {
+    let mut account = account.clone();
+    account.balances.retain(|record| record.date.year() == year);
+    account.holdings.retain(|record| record.date.year() == year);
+    account
+        .surrender_values
+        .retain(|record| record.date.year() == year);
+    account
+}
+
+fn rates(report: &Report, context: &ReportContext) -> Vec<RetainedRate> {
+    let mut by_currency: BTreeMap<&str, RetainedRate> = BTreeMap::new();
+    for account in &report.accounts {
+        let Some(rate) = account.exchange_rate else {
+            continue;
+        };
+        by_currency
+            .entry(account.currency_code.as_str())
+            .or_insert_with(|| RetainedRate {
+                currency_code: account.currency_code.clone(),
+                rate,
+                source: account.rate_source.clone(),
+                irs_rate: context
+                    .irs_rate(report.year, &account.currency_code)
+                    .map(|irs| irs.rate),
+                accounts: Vec::new(),
+            })
+            .accounts
+            .push(account.handle.clone());
+    }
+    by_currency.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Address, BalanceRecord};
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use crate::statements::{Provenance, StatementImport};
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+    use std::io::Read;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_write_records() -> Result<()> {
+        let dir = TempDir::new()?;
+        let balance = |date: &str| BalanceRecord {
+            date: date.parse().unwrap(),
+            amount: dec!(100),
+            ..Default::default()
+        };
+        let user_data = UserData {
+            providers: vec![Provider {
+                handle: "example_bank".to_string(),
+                name: "Example Bank".to_string(),
+                address: Address::Freeform("1 Bank Street, London".to_string()),
+                country: Some("GB".to_string()),
+                ..Default::default()
+            }],
+            accounts: vec![Account {
+                handle: "example_checking".to_string(),
+                provider: "example_bank".to_string(),
+                currency_code: "EUR".to_string(),
+                balances: vec![balance("2022-12-31"), balance("2023-06-30")],
+                imports: vec![StatementImport {
+                    provenance: Provenance {
+                        source_file: "june.csv".to_string(),
+                        imported_at: Utc.with_ymd_and_hms(2023, 7, 1, 0, 0, 0).unwrap(),
+                        sha256: "abc".to_string(),
+                        profile: None,
+                    },
+                    balances: vec![balance("2023-06-30")],
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let context = ReportContext::new(
+            Facts {
+                years: vec![AnnualFact {
+                    year: 2023,
+                    exchange_rates: vec![ExchangeRate::new("EUR".to_string(), 0.80).unwrap()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            None,
+        );
+        let report = Report::generate(2023, &user_data, &context)?;
+        let generated_at = Utc.with_ymd_and_hms(2024, 3, 15, 9, 30, 0).unwrap();
+
+        let artifact = write_records(
+            dir.path(),
+            &Layout::default(),
+            &report,
+            &user_data,
+            &context,
+            generated_at,
+        )?;
+        assert!(artifact
+            .path
+            .starts_with("2023/default/records/fbar-records-2023-20240315T093000Z-"));
+
+        let file = std::fs::File::open(dir.path().join(&artifact.path))?;
+        let mut archive = zip::ZipArchive::new(file)?;
+        let mut contents = String::new();
+        archive
+            .by_name(RECORDS_INDEX)?
+            .read_to_string(&mut contents)?;
+        let index: RecordsIndex = serde_yaml::from_str(&contents)?;
+        assert_eq!(index.retain_until, "2029-04-15".parse::<NaiveDate>()?);
+        let names: Vec<&str> = index.files.iter().map(|file| file.name.as_str()).collect();
+        assert!(names.contains(&"filing/fbar-2023.xml"));
+        assert!(names.contains(&"filing/audit-trail-2023.yml"));
+        assert!(names.contains(&"statements/example_checking.yml"));
+
+        contents.clear();
+        archive
+            .by_name("accounts-2023.yml")?
+            .read_to_string(&mut contents)?;
+        let accounts: Vec<RetainedAccount> = serde_yaml::from_str(&contents)?;
+        assert_eq!(accounts[0].account.balances, vec![balance("2023-06-30")]);
+        assert_eq!(
+            accounts[0]
+                .provider
+                .as_ref()
+                .map(|provider| provider.name.as_str()),
+            Some("Example Bank")
+        );
+
+        contents.clear();
+        archive
+            .by_name("rates-2023.yml")?
+            .read_to_string(&mut contents)?;
+        let rates: Vec<RetainedRate> = serde_yaml::from_str(&contents)?;
+        assert_eq!(rates[0].rate, 0.80);
+        assert_eq!(rates[0].accounts, vec!["example_checking"]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::data::{Account, BalanceRecord};
+
+/// Days before the closing date a record can be and still count as the final statement
+pub const FINAL_STATEMENT_DAYS: i64 = 31;
+
+/// What to check for an account that closed during the year, driven by its `closing_date` and the records on file
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ClosureChecklist {
+    pub handle: String,
+    pub closing_date: NaiveDate,
+    /// Date of the last record of any kind on or before the closing date
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_record: Option<NaiveDate>,
+    /// The last balance on or before the closing date
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_balance: Option<BalanceRecord>,
+    /// Whether a record falls within `FINAL_STATEMENT_DAYS` of the closing date
+    pub has_final_statement: bool,
+}
+
+impl ClosureChecklist {
+    /// The checklist for an account that closed during `year`, or `None` if it didn't
+    pub fn for_account(account: &Account, year: i32) -> Option<Self> {
+        let closing_date = account
+            .closing_date
+            .filter(|closed| closed.year() == year)?;
+        let last_record = account
+            .record_dates()
+            .filter(|date| *date <= closing_date)
+            .max();
+        let last_balance = account
+            .balances
+            .iter()
+            .chain(
+                account
+                    .imports
+                    .iter()
+                    .flat_map(|import| import.balances.iter()),
+            )
+            .filter(|record| record.date <= closing_date)
+            .max_by_key(|record| record.date)
+            .cloned();
+
+        Some(Self {
+            handle: account.handle.clone(),
+            closing_date,
+            last_record,
+            last_balance,
+            has_final_statement: last_record
+                .is_some_and(|date| (closing_date - date).num_days() <= FINAL_STATEMENT_DAYS),
+        })
+    }
+
+    /// The checklist's entries, as lines for the review screen
+    pub fn items(&self, currency_code: &str) -> Vec<String> {
+        let year = self.closing_date.year();
+        let mut items = Vec::new();
+
+        items.push(match (self.has_final_statement, self.last_record) {
+            (true, Some(date)) => format!("Closure: final statement on record, dated {}", date),
+            (_, Some(date)) => format!(
+                "Closure: final statement needed; the last record is from {}, {} days before the account closed on {}",
+                date,
+                (self.closing_date - date).num_days(),
+                self.closing_date
+            ),
+            (_, None) => format!(
+                "Closure: final statement needed; nothing is on record before the account closed on {}",
+                self.closing_date
+            ),
+        });
+
+        match &self.last_balance {
+            Some(balance) => {
+                let mut item = format!(
+                    "Closure: last balance on record is {} {} on {}",
+                    balance.amount,
+                    balance
+                        .currency
+                        .as_deref()
+                        .unwrap_or(currency_code)
+                        .to_uppercase(),
+                    balance.date
+                );
+                if !balance.amount.is_zero() {
+                    item.push_str("; the final statement should show the funds leaving");
+                }
+                items.push(item);
+            }
+            None => items.push("Closure: no balance on record".to_string()),
+        }
+
+        items.push(format!(
+            "Closure: still reportable for {}, with its maximum up to {}; not reportable from {} on",
+            year,
+            self.closing_date,
+            year + 1
+        ));
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    fn balance(date: &str, amount: Decimal) -> BalanceRecord {
+        BalanceRecord {
+            date: date.parse().unwrap(),
+            amount,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_closure_checklist() {
+        let mut account = Account {
+            handle: "savings".to_string(),
+            currency_code: "EUR".to_string(),
+            closing_date: NaiveDate::from_ymd_opt(2023, 5, 31),
+            balances: vec![
+                balance("2023-03-31", dec!(400)),
+                // After the closing date, so not the last balance
+                balance("2023-08-31", dec!(9999)),
+            ],
+            ..Default::default()
+        };
+        assert!(ClosureChecklist::for_account(&account, 2022).is_none());
+
+        let checklist = ClosureChecklist::for_account(&account, 2023).unwrap();
+        assert!(!checklist.has_final_statement);
+        assert_eq!(
+            checklist.items("eur"),
+            vec![
+                "Closure: final statement needed; the last record is from 2023-03-31, 61 days before the account \
+                 closed on 2023-05-31",
+                "Closure: last balance on record is 400 EUR on 2023-03-31; the final statement should show the funds \
+                 leaving",
+                "Closure: still reportable for 2023, with its maximum up to 2023-05-31; not reportable from 2024 on",
+            ]
+        );
+
+        account.balances.push(balance("2023-05-31", dec!(0)));
+        let checklist = ClosureChecklist::for_account(&account, 2023).unwrap();
+        assert!(checklist.has_final_statement);
+        assert_eq!(
+            checklist.items("eur")[..2],
+            [
+                "Closure: final statement on record, dated 2023-05-31",
+                "Closure: last balance on record is 0 EUR on 2023-05-31",
+            ]
+        );
+    }
+}
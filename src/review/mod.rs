@@ -0,0 +1,244 @@
+pub mod closure;
+pub mod tui;
+pub use self::closure::ClosureChecklist;
+pub use self::tui::ReviewApp;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::money::Money;
+use crate::report::{AccountReport, Report};
+
+/// Records which accounts in a year's report the user has checked over
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReviewState {
+    pub year: i32,
+    #[serde(default)]
+    pub accounts: BTreeMap<String, ReviewMark>,
+}
+
+/// Who signed off on which figure, and when. If the computed maximum changes afterwards the account needs reviewing
+/// again.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReviewMark {
+    /// Name of whoever reviewed the account; marks from before `reviews.yml` don't have one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: DateTime<Utc>,
+    pub max_balance_usd: Money,
+}
+
+impl ReviewState {
+    pub fn new(year: i32) -> Self {
+        Self {
+            year,
+            accounts: BTreeMap::new(),
+        }
+    }
+
+    /// Whether the account has been reviewed and its maximum hasn't changed since
+    pub fn is_reviewed(&self, account: &AccountReport) -> bool {
+        self.accounts
+            .get(&account.handle)
+            .is_some_and(|mark| mark.max_balance_usd == account.max_balance_usd)
+    }
+
+    pub fn mark(&mut self, account: &AccountReport, reviewed_by: Option<&str>) {
+        self.accounts.insert(
+            account.handle.clone(),
+            ReviewMark {
+                reviewed_by: reviewed_by.map(str::to_string),
+                reviewed_at: Utc::now(),
+                max_balance_usd: account.max_balance_usd.clone(),
+            },
+        );
+    }
+
+    pub fn unmark(&mut self, handle: &str) {
+        self.accounts.remove(handle);
+    }
+
+    /// Accounts in the report still waiting to be reviewed
+    pub fn unreviewed<'a>(&self, report: &'a Report) -> Vec<&'a AccountReport> {
+        report
+            .accounts
+            .iter()
+            .filter(|account| !self.is_reviewed(account))
+            .collect()
+    }
+}
+
+/// The sign-offs of every year, as kept in `reviews.yml`
+type Reviews = BTreeMap<i32, BTreeMap<String, ReviewMark>>;
+
+/// Reads and writes sign-offs in `reviews.yml` in the data directory, next to `data.yml`, so they're kept with the
+/// data rather than with tool state. Review state from before then, under `.fbar_prep/review`, is still read for years
+/// `reviews.yml` doesn't have, and moves into it on the next save.
+pub struct ReviewStore {
+    path: PathBuf,
+    legacy_dir: PathBuf,
+}
+
+impl ReviewStore {
+    pub fn new(base_path: &Path) -> Self {
+        Self {
+            path: base_path.join("reviews.yml"),
+            legacy_dir: base_path.join(".fbar_prep").join("review"),
+        }
+    }
+
+    /// Loads the year's review state, starting afresh if nothing has been reviewed yet
+    pub fn load(&self, year: i32) -> Result<ReviewState> {
+        if let Some(accounts) = self.load_all()?.remove(&year) {
+            return Ok(ReviewState { year, accounts });
+        }
+
+        let legacy_path = self.legacy_dir.join(format!("{}.yml", year));
+        if !legacy_path.exists() {
+            return Ok(ReviewState::new(year));
+        }
+        let contents = std::fs::read_to_string(&legacy_path)?;
+        serde_yaml::from_str(&contents)
+            .with_context(|| format!("Invalid YAML in {:?}", legacy_path))
+    }
+
+    /// Saves the year's review state, leaving other years' as they are
+    pub fn save(&self, state: &ReviewState) -> Result<()> {
+        let mut reviews = self.load_all()?;
+        if state.accounts.is_empty() {
+            reviews.remove(&state.year);
+        } else {
+            reviews.insert(state.year, state.accounts.clone());
+        }
+        std::fs::write(&self.path, serde_yaml::to_string(&reviews)?)?;
+        Ok(())
+    }
+
+    fn load_all(&self) -> Result<Reviews> {
+        if !self.path.exists() {
+            return Ok(Reviews::new());
+        }
+        let contents = std::fs::read_to_string(&self.path)?;
+        serde_yaml::from_str(&contents).with_context(|| format!("Invalid YAML in {:?}", self.path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report_context::RateSource;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use tempfile::TempDir;
+
+    fn create_test_account(handle: &str, max_balance_usd: Decimal) -> AccountReport {
+        AccountReport {
+            handle: handle.to_string(),
+            provider: "example_bank".to_string(),
+            currency_code: "gbp".to_string(),
+            max_balance: Money::new(max_balance_usd * dec!(0.8), "gbp"),
+            max_balance_usd: Money::usd(max_balance_usd),
+            max_balance_date: None,
+            rate_source: RateSource::IrsProvided,
+            rate_pinned: false,
+            exchange_rate: Some(0.8),
+            usd_price: None,
+            other_maximum: None,
+            open_period: None,
+            notes: vec![],
+            entity: None,
+            signature_authority: None,
+            joint_owner: None,
+            interpolation: None,
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let store = ReviewStore::new(temp_dir.path());
+
+        let mut state = store.load(2023)?;
+        assert!(state.accounts.is_empty());
+
+        state.mark(&create_test_account("checking", dec!(1000)), Some("alex"));
+        store.save(&state)?;
+
+        let loaded = store.load(2023)?;
+        assert_eq!(loaded, state);
+        assert_eq!(
+            loaded.accounts["checking"].reviewed_by.as_deref(),
+            Some("alex")
+        );
+        assert!(store.load(2024)?.accounts.is_empty());
+
+        // Other years are kept
+        let mut next = ReviewState::new(2024);
+        next.mark(&create_test_account("savings", dec!(500)), None);
+        store.save(&next)?;
+        assert_eq!(store.load(2023)?, state);
+        let contents = std::fs::read_to_string(temp_dir.path().join("reviews.yml"))?;
+        assert!(contents.starts_with("2023:\n  checking:\n    reviewed_by: alex\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_state() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let legacy_dir = temp_dir.path().join(".fbar_prep/review");
+        std::fs::create_dir_all(&legacy_dir)?;
+        let mut state = ReviewState::new(2023);
+        state.mark(&create_test_account("checking", dec!(1000)), None);
+        std::fs::write(legacy_dir.join("2023.yml"), serde_yaml::to_string(&state)?)?;
+
+        let store = ReviewStore::new(temp_dir.path());
+        let mut loaded = store.load(2023)?;
+        assert_eq!(loaded, state);
+
+        // Saving moves it into reviews.yml, which wins from then on
+        loaded.unmark("checking");
+        loaded.mark(&create_test_account("savings", dec!(500)), Some("sam"));
+        store.save(&loaded)?;
+        assert_eq!(store.load(2023)?, loaded);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_changed_figure_needs_review_again() {
+        let mut state = ReviewState::new(2023);
+        state.mark(&create_test_account("checking", dec!(1000)), None);
+
+        assert!(state.is_reviewed(&create_test_account("checking", dec!(1000))));
+        assert!(!state.is_reviewed(&create_test_account("checking", dec!(1200))));
+
+        let report = Report {
+            year: 2023,
+            metadata: None,
+            summary: None,
+            accounts: vec![
+                create_test_account("checking", dec!(1000)),
+                create_test_account("savings", dec!(500)),
+            ],
+            overridden_rates: Vec::new(),
+            provenance: vec![],
+            excluded: vec![],
+            entities: vec![],
+            joint_owners: vec![],
+            spousal_filing: None,
+        };
+        let unreviewed: Vec<_> = state
+            .unreviewed(&report)
+            .iter()
+            .map(|account| account.handle.as_str())
+            .collect();
+        assert_eq!(unreviewed, vec!["savings"]);
+
+        state.unmark("checking");
+        assert_eq!(state.unreviewed(&report).len(), 2);
+    }
+}
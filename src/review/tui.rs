@@ -0,0 +1,357 @@
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use super::{ClosureChecklist, ReviewState, ReviewStore};
+use crate::data::UserData;
+use crate::fincen;
+use crate::money::format::format_conversion;
+use crate::report::Report;
+
+/// Interactive checklist of a year's accounts: their computed maxima, any validation warnings, and whether the user
+/// has reviewed them. Review marks are saved as soon as they're toggled.
+pub struct ReviewApp {
+    report: Report,
+    /// Validation issues, report notes and the closure checklist of accounts that closed during the year, per account
+    /// in report order
+    warnings: Vec<Vec<String>>,
+    /// Decimal places of each account's currency, in report order, including currencies defined in `fact_extensions`
+    minor_units: Vec<u32>,
+    state: ReviewState,
+    /// Who marks are signed off by, the first filer unless given
+    reviewer: Option<String>,
+    table_state: TableState,
+}
+
+impl ReviewApp {
+    pub fn new(report: Report, user_data: &UserData, state: ReviewState) -> Self {
+        let issues = fincen::validate_report(&report, user_data);
+        let warnings = report
+            .accounts
+            .iter()
+            .map(|account| {
+                let closure = user_data
+                    .accounts
+                    .iter()
+                    .find(|own| own.handle == account.handle)
+                    .and_then(|own| ClosureChecklist::for_account(own, report.year))
+                    .map(|checklist| checklist.items(&account.currency_code))
+                    .unwrap_or_default();
+                issues
+                    .iter()
+                    .filter(|issue| issue.account == account.handle)
+                    .map(|issue| issue.to_string())
+                    .chain(account.notes.iter().cloned())
+                    .chain(closure)
+                    .collect()
+            })
+            .collect();
+        let minor_units = report
+            .accounts
+            .iter()
+            .map(|account| user_data.minor_units(&account.currency_code))
+            .collect();
+
+        let mut table_state = TableState::default();
+        if !report.accounts.is_empty() {
+            table_state.select(Some(0));
+        }
+
+        Self {
+            report,
+            warnings,
+            minor_units,
+            state,
+            reviewer: user_data.filers.first().map(|filer| filer.name.clone()),
+            table_state,
+        }
+    }
+
+    /// Signs marks off in someone else's name, e.g. an accountant's
+    pub fn reviewer(mut self, name: impl Into<String>) -> Self {
+        self.reviewer = Some(name.into());
+        self
+    }
+
+    pub fn state(&self) -> &ReviewState {
+        &self.state
+    }
+
+    pub fn next(&mut self) {
+        if let Some(selected) = self.table_state.selected() {
+            self.table_state
+                .select(Some((selected + 1).min(self.report.accounts.len() - 1)));
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if let Some(selected) = self.table_state.selected() {
+            self.table_state.select(Some(selected.saturating_sub(1)));
+        }
+    }
+
+    /// Marks the selected account as reviewed, or clears its mark if it already was
+    pub fn toggle_selected(&mut self) {
+        let Some(account) = self
+            .table_state
+            .selected()
+            .and_then(|selected| self.report.accounts.get(selected))
+        else {
+            return;
+        };
+
+        if self.state.is_reviewed(account) {
+            self.state.unmark(&account.handle);
+        } else {
+            self.state.mark(account, self.reviewer.as_deref());
+        }
+    }
+
+    /// Runs the review screen until the user quits, saving every change to the store
+    pub fn run(mut self, store: &ReviewStore) -> Result<ReviewState> {
+        let mut terminal = ratatui::init();
+        let result = self.event_loop(&mut terminal, store);
+        ratatui::restore();
+
+        result.map(|()| self.state)
+    }
+
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal, store: &ReviewStore) -> Result<()> {
+        loop {
+            terminal.draw(|frame| self.render(frame))?;
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Down | KeyCode::Char('j') => self.next(),
+                KeyCode::Up | KeyCode::Char('k') => self.previous(),
+                KeyCode::Char(' ') | KeyCode::Enter => {
+                    self.toggle_selected();
+                    store.save(&self.state)?;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn render(&mut self, frame: &mut Frame) {
+        let [table_area, details_area, help_area] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(8),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let rows = self
+            .report
+            .accounts
+            .iter()
+            .zip(&self.warnings)
+            .zip(&self.minor_units)
+            .map(|((account, warnings), minor_units)| {
+                Row::new(vec![
+                    if self.state.is_reviewed(account) {
+                        "[x]".to_string()
+                    } else {
+                        "[ ]".to_string()
+                    },
+                    account.handle.clone(),
+                    account.provider.clone(),
+                    account.max_balance.format_to(*minor_units),
+                    account.max_balance_usd.format(),
+                    warnings.len().to_string(),
+                ])
+            });
+        let reviewed = self.report.accounts.len() - self.state.unreviewed(&self.report).len();
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(3),
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+                Constraint::Length(20),
+                Constraint::Length(20),
+                Constraint::Length(8),
+            ],
+        )
+        .header(
+            Row::new(vec![
+                "",
+                "Account",
+                "Provider",
+                "Maximum",
+                "Maximum (USD)",
+                "Warnings",
+            ])
+            .style(Style::new().add_modifier(Modifier::BOLD)),
+        )
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            "Review {} ({}/{} reviewed)",
+            self.report.year,
+            reviewed,
+            self.report.accounts.len()
+        )));
+        frame.render_stateful_widget(table, table_area, &mut self.table_state);
+
+        let details: Vec<Line> = match self.table_state.selected() {
+            Some(selected) => {
+                let account = &self.report.accounts[selected];
+                let mut details = vec![Line::from(format!(
+                    "Maximum: {} ({}){}",
+                    format_conversion(
+                        &account.max_balance,
+                        self.minor_units[selected],
+                        &account.max_balance_usd,
+                        account.exchange_rate
+                    ),
+                    account.rate_source,
+                    account
+                        .max_balance_date
+                        .map(|date| format!(", on {}", date))
+                        .unwrap_or_default()
+                ))];
+                if let Some(mark) = self
+                    .state
+                    .accounts
+                    .get(&account.handle)
+                    .filter(|_| self.state.is_reviewed(account))
+                {
+                    let date = mark.reviewed_at.date_naive();
+                    details.push(Line::from(match &mark.reviewed_by {
+                        Some(reviewer) => format!("Reviewed by {} on {}", reviewer, date),
+                        None => format!("Reviewed on {}", date),
+                    }));
+                }
+                if self.warnings[selected].is_empty() {
+                    details.push(Line::from("No warnings"));
+                }
+                details.extend(
+                    self.warnings[selected]
+                        .iter()
+                        .map(|warning| Line::from(warning.as_str())),
+                );
+                details
+            }
+            None => vec![Line::from("No accounts to review")],
+        };
+        frame.render_widget(
+            Paragraph::new(details).wrap(Wrap { trim: false }).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Warnings and notes"),
+            ),
+            details_area,
+        );
+
+        frame.render_widget(
+            Line::from("↑/↓ select   space mark reviewed   q quit"),
+            help_area,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+    use crate::report::AccountReport;
+    use crate::report_context::RateSource;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+    use rust_decimal_macros::dec;
+
+    fn create_test_app() -> ReviewApp {
+        let account = |handle: &str| AccountReport {
+            handle: handle.to_string(),
+            provider: "example_bank".to_string(),
+            currency_code: "gbp".to_string(),
+            max_balance: Money::new(dec!(800), "gbp"),
+            max_balance_usd: Money::usd(dec!(1000)),
+            max_balance_date: None,
+            rate_source: RateSource::IrsProvided,
+            rate_pinned: false,
+            exchange_rate: Some(0.8),
+            usd_price: None,
+            other_maximum: None,
+            open_period: None,
+            notes: vec![],
+            entity: None,
+            signature_authority: None,
+            joint_owner: None,
+            interpolation: None,
+        };
+        let report = Report {
+            year: 2023,
+            metadata: None,
+            summary: None,
+            accounts: vec![account("checking"), account("savings")],
+            overridden_rates: Vec::new(),
+            provenance: vec![],
+            excluded: vec![],
+            entities: vec![],
+            joint_owners: vec![],
+            spousal_filing: None,
+        };
+
+        ReviewApp::new(report, &UserData::default(), ReviewState::new(2023))
+            .reviewer("Alex Example")
+    }
+
+    #[test]
+    fn test_toggle_and_navigate() {
+        let mut app = create_test_app();
+
+        app.toggle_selected();
+        assert_eq!(
+            app.state().accounts["checking"].reviewed_by.as_deref(),
+            Some("Alex Example")
+        );
+
+        app.next();
+        app.next();
+        app.toggle_selected();
+        assert!(app.state().accounts.contains_key("savings"));
+
+        app.previous();
+        app.toggle_selected();
+        assert!(!app.state().accounts.contains_key("checking"));
+    }
+
+    #[test]
+    fn test_render() -> Result<()> {
+        let mut app = create_test_app();
+        app.toggle_selected();
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 20))?;
+        terminal.draw(|frame| app.render(frame))?;
+
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("Review 2023 (1/2 reviewed)"));
+        assert!(screen.contains("[x]"));
+        assert!(screen.contains("Reviewed by Alex Example on "));
+        assert!(screen.contains("£800.00"));
+        assert!(screen.contains("$1,000.00"));
+        assert!(screen.contains("Maximum: £800.00 at 0.8 GBP per USD = $1,000.00 (IRS rate)"));
+        // No providers are configured, so validation flags the selected account's provider
+        assert!(screen.contains("unknown provider"));
+
+        Ok(())
+    }
+}
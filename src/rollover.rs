@@ -0,0 +1,133 @@
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
+
+use crate::data::UserData;
+use crate::report::OpenPeriod;
+
+/// Header written to each statement skeleton, matching what `import` expects
+pub const STATEMENT_HEADER: &str = "date,amount\n";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct RolloverSummary {
+    pub created: Vec<PathBuf>,
+    /// Skeletons that already existed and were left untouched
+    pub skipped: Vec<PathBuf>,
+}
+
+/// Directory holding statement files for a year that are waiting to be imported
+pub fn inbox_dir(base_path: &Path, year: i32) -> PathBuf {
+    base_path.join("inbox").join(year.to_string())
+}
+
+/// Carries last year's account set forward, writing an empty statement skeleton under `inbox/<to_year>` for every
+/// account that was reported in `from_year` and is still open in `to_year`.
+///
+/// Existing files are never overwritten, so rolling over twice is safe.
+pub fn rollover(
+    base_path: &Path,
+    user_data: &UserData,
+    from_year: i32,
+    to_year: i32,
+) -> Result<RolloverSummary> {
+    if to_year <= from_year {
+        bail!(
+            "Can't roll over from {} to {}: the new year must come later",
+            from_year,
+            to_year
+        );
+    }
+
+    let dir = inbox_dir(base_path, to_year);
+    let mut summary = RolloverSummary::default();
+
+    for account in user_data.accounts.iter().filter(|account| {
+        !account.exclude_from_fbar
+            && OpenPeriod::for_account(account, from_year).is_some()
+            && OpenPeriod::for_account(account, to_year).is_some()
+    }) {
+        let path = dir.join(format!("{}.csv", account.handle));
+        if path.exists() {
+            summary.skipped.push(path);
+            continue;
+        }
+
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(&path, STATEMENT_HEADER)?;
+        summary.created.push(path);
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Account;
+    use chrono::NaiveDate;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn create_test_user_data() -> UserData {
+        UserData {
+            accounts: vec![
+                Account {
+                    handle: "checking".to_string(),
+                    provider: "example_bank".to_string(),
+                    currency_code: "gbp".to_string(),
+                    ..Default::default()
+                },
+                Account {
+                    handle: "closed_in_2023".to_string(),
+                    provider: "example_bank".to_string(),
+                    currency_code: "gbp".to_string(),
+                    closing_date: NaiveDate::from_ymd_opt(2023, 8, 1),
+                    ..Default::default()
+                },
+                Account {
+                    handle: "opened_in_2024".to_string(),
+                    provider: "example_bank".to_string(),
+                    currency_code: "gbp".to_string(),
+                    opening_date: NaiveDate::from_ymd_opt(2024, 2, 1),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_rollover() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+
+        let summary = rollover(temp_dir.path(), &create_test_user_data(), 2023, 2024)?;
+
+        let expected = temp_dir.path().join("inbox/2024/checking.csv");
+        assert_eq!(summary.created, vec![expected.clone()]);
+        assert!(summary.skipped.is_empty());
+        assert_eq!(fs::read_to_string(expected)?, STATEMENT_HEADER);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rollover_keeps_existing_files() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("inbox/2024/checking.csv");
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(&path, "date,amount\n2024-01-31,100\n")?;
+
+        let summary = rollover(temp_dir.path(), &create_test_user_data(), 2023, 2024)?;
+
+        assert!(summary.created.is_empty());
+        assert_eq!(summary.skipped, vec![path.clone()]);
+        assert_eq!(fs::read_to_string(path)?, "date,amount\n2024-01-31,100\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rollover_backwards() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(rollover(temp_dir.path(), &create_test_user_data(), 2024, 2023).is_err());
+    }
+}
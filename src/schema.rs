@@ -0,0 +1,83 @@
+use schemars::{schema_for, Schema};
+use std::str::FromStr;
+
+use crate::data::UserData;
+use crate::statements::StatementFile;
+
+/// Files a JSON Schema can be exported for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SchemaFile {
+    /// The data directory's `data.yml`
+    Data,
+    /// Imported statement files under `statements/`
+    Statements,
+}
+
+impl FromStr for SchemaFile {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "data" => Ok(SchemaFile::Data),
+            "statements" => Ok(SchemaFile::Statements),
+            _ => Err(format!(
+                "unknown file {:?}, expected `data` or `statements`",
+                value
+            )),
+        }
+    }
+}
+
+/// JSON Schema for a file, generated from the types it's loaded into. Editors with YAML language support can use it
+/// for completion and validation.
+pub fn schema(file: SchemaFile) -> Schema {
+    match file {
+        SchemaFile::Data => schema_for!(UserData),
+        SchemaFile::Statements => schema_for!(StatementFile),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn test_data_schema() {
+        let schema = schema(SchemaFile::Data).to_value();
+
+        assert_eq!(schema["title"], "UserData");
+        let required = schema["required"].as_array().unwrap();
+        assert!(required.contains(&Value::from("providers")));
+        assert!(!required.contains(&Value::from("accounts")));
+
+        // Fields only filled in while loading aren't part of the file
+        assert!(schema["properties"].get("rate_sets").is_none());
+        let account = &schema["$defs"]["Account"]["properties"];
+        assert!(account.get("imports").is_none());
+        assert_eq!(
+            schema["$defs"]["AccountKind"]["oneOf"][1]["const"],
+            "crypto_exchange"
+        );
+        assert_eq!(
+            account["balances"]["items"]["$ref"],
+            "#/$defs/BalanceRecord"
+        );
+    }
+
+    #[test]
+    fn test_statements_schema() {
+        let schema = schema(SchemaFile::Statements).to_value();
+
+        // Provenance is flattened into each import
+        let import = &schema["$defs"]["StatementImport"]["properties"];
+        assert!(import.get("sha256").is_some());
+        assert!(import.get("provenance").is_none());
+    }
+
+    #[test]
+    fn test_parse_schema_file() {
+        assert_eq!("statements".parse(), Ok(SchemaFile::Statements));
+        assert!("report".parse::<SchemaFile>().is_err());
+    }
+}
@@ -0,0 +1,193 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::io::Read;
+use std::path::Path;
+
+use super::importer::StatementImporter;
+use crate::data::BalanceRecord;
+use crate::parsing::ParseContext;
+
+/// Imports statements in the plain `date,amount` CSV layout, with an optional `currency` column
+pub struct CsvImporter;
+
+impl StatementImporter for CsvImporter {
+    fn name(&self) -> &str {
+        "csv"
+    }
+
+    fn detect(&self, file_name: &str, _contents: &[u8]) -> bool {
+        Path::new(file_name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+    }
+
+    fn parse(
+        &self,
+        contents: &[u8],
+        source: &str,
+        context: &ParseContext,
+    ) -> Result<Vec<BalanceRecord>> {
+        parse_balances(contents, source, context)
+    }
+}
+
+#[derive(Deserialize)]
+struct RawBalance {
+    date: String,
+    amount: String,
+    #[serde(default)]
+    currency: Option<String>,
+}
+
+/// Parses a balance statement in CSV form, with `date` and `amount` columns. A `currency` column marks balances
+/// given in a currency other than the account's.
+///
+/// Dates may be ISO or numeric in the context's date order. Rows with unparseable or ambiguous dates are reported
+/// through the parse context, naming the line, and skipped when it is lenient. Amounts are read in the context's
+/// number format, or else the one the file's amounts decide, e.g. `1.234,56`; ones that could be read either way are
+/// reported.
+pub fn parse_balances(
+    reader: impl Read,
+    source: &str,
+    context: &ParseContext,
+) -> Result<Vec<BalanceRecord>> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+
+    let mut rows = Vec::new();
+    for (index, record) in csv_reader.deserialize().enumerate() {
+        // Data rows start on the line after the header
+        let location = format!("{} line {}", source, index + 2);
+        let raw: RawBalance = record.with_context(|| format!("Invalid row in {}", location))?;
+        rows.push((location, raw));
+    }
+    let number_format = context.number_format_for(rows.iter().map(|(_, raw)| raw.amount.as_str()));
+
+    let mut balances = Vec::new();
+    for (location, raw) in rows {
+        if let Some(date) = context.parse_date(&raw.date, &location)? {
+            balances.push(BalanceRecord {
+                date,
+                amount: context.parse_amount(&raw.amount, number_format, &location)?,
+                currency: raw
+                    .currency
+                    .filter(|currency| !currency.is_empty())
+                    .map(|currency| currency.to_lowercase()),
+                ..Default::default()
+            });
+        }
+    }
+
+    Ok(balances)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::DateOrder;
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_parse_balances() -> Result<()> {
+        let balances = parse_balances(
+            "date, amount\n2023-01-31, 10.5\n2023-02-28,-3\n".as_bytes(),
+            "test.csv",
+            &ParseContext::default(),
+        )?;
+
+        assert_eq!(balances.len(), 2);
+        assert_eq!(
+            balances[0].date,
+            NaiveDate::from_ymd_opt(2023, 1, 31).unwrap()
+        );
+        assert_eq!(balances[0].amount, dec!(10.5));
+        assert_eq!(balances[1].amount, dec!(-3));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_currency_column() -> Result<()> {
+        let balances = parse_balances(
+            "date,amount,currency\n2023-01-31,10.5,GBP\n2023-02-28,12,\n".as_bytes(),
+            "test.csv",
+            &ParseContext::default(),
+        )?;
+
+        assert_eq!(balances[0].currency.as_deref(), Some("gbp"));
+        assert_eq!(balances[1].currency, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_european_amounts() -> Result<()> {
+        // `12,50` shows the file uses a `,` decimal separator, deciding `1.234` too
+        let balances = parse_balances(
+            "date,amount\n2023-01-31,\"1.234\"\n2023-02-28,\"12,50\"\n".as_bytes(),
+            "test.csv",
+            &ParseContext::default(),
+        )?;
+        assert_eq!(balances[0].amount, dec!(1234));
+        assert_eq!(balances[1].amount, dec!(12.50));
+
+        // Nothing decides `1.234`, so it's read with a `.` decimal separator and reported
+        let context = ParseContext::default();
+        let balances = parse_balances(
+            "date,amount\n2023-01-31,1.234\n2023-02-28,100\n".as_bytes(),
+            "test.csv",
+            &context,
+        )?;
+        assert_eq!(balances[0].amount, dec!(1.234));
+        assert!(context.warnings()[0].starts_with("\"1.234\" in test.csv line 2: ambiguous amount"));
+        assert!(parse_balances(
+            "date,amount\n2023-01-31,1.234\n".as_bytes(),
+            "test.csv",
+            &ParseContext::strict(),
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_missing_column() {
+        let result = parse_balances(
+            "date\n2023-01-31\n".as_bytes(),
+            "test.csv",
+            &ParseContext::default(),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_unparseable_dates() -> Result<()> {
+        let statement = "date,amount\n31/01/2023,10\n03/04/2023,20\nsometime,30\n";
+
+        let lenient = ParseContext::default();
+        let balances = parse_balances(statement.as_bytes(), "test.csv", &lenient)?;
+        assert_eq!(balances.len(), 1);
+        assert_eq!(
+            balances[0].date,
+            NaiveDate::from_ymd_opt(2023, 1, 31).unwrap()
+        );
+        assert_eq!(lenient.warnings().len(), 2);
+        assert!(lenient.warnings()[0].starts_with("\"03/04/2023\" in test.csv line 3: ambiguous"));
+        assert!(lenient.warnings()[1].starts_with("\"sometime\" in test.csv line 4"));
+
+        let day_first = ParseContext::default().with_date_order(Some(DateOrder::DayFirst));
+        let balances = parse_balances(statement.as_bytes(), "test.csv", &day_first)?;
+        assert_eq!(
+            balances[1].date,
+            NaiveDate::from_ymd_opt(2023, 4, 3).unwrap()
+        );
+
+        let strict = ParseContext::strict();
+        assert!(parse_balances(statement.as_bytes(), "test.csv", &strict).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,337 @@
+use anyhow::{Context, Result};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::path::Path;
+
+use super::importer::StatementImporter;
+use super::ledger::{self, Transaction};
+use crate::data::{AccountKind, BalanceRecord};
+use crate::diagnostics::codes;
+use crate::parsing::ParseContext;
+
+/// Imports GoCardless (formerly Nordigen) bank account data, reconstructing balances from the booked transactions.
+///
+/// The file is the account's `transactions` response with the `balances` response merged into the same object, so
+/// the reconstruction has a balance to anchor to.
+pub struct GoCardlessImporter;
+
+impl StatementImporter for GoCardlessImporter {
+    fn name(&self) -> &str {
+        "gocardless"
+    }
+
+    fn detect(&self, file_name: &str, contents: &[u8]) -> bool {
+        let is_json = Path::new(file_name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let contents = String::from_utf8_lossy(contents);
+
+        is_json && contents.contains("\"transactions\"") && contents.contains("\"booked\"")
+    }
+
+    fn parse(
+        &self,
+        contents: &[u8],
+        source: &str,
+        context: &ParseContext,
+    ) -> Result<Vec<BalanceRecord>> {
+        parse_balances(contents, source, context)
+    }
+
+    /// GoCardless only connects to bank accounts
+    fn account_kind(&self, _contents: &[u8]) -> Option<AccountKind> {
+        Some(AccountKind::Bank)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccountData {
+    #[serde(default)]
+    balances: Vec<RawBalance>,
+    transactions: RawTransactions,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawBalance {
+    balance_amount: RawAmount,
+    balance_type: String,
+    #[serde(default)]
+    reference_date: Option<String>,
+}
+
+/// Pending transactions aren't on the ledger yet, so only the booked ones are read
+#[derive(Deserialize)]
+struct RawTransactions {
+    booked: Vec<RawTransaction>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawTransaction {
+    #[serde(default)]
+    booking_date: Option<String>,
+    #[serde(default)]
+    value_date: Option<String>,
+    transaction_amount: RawAmount,
+    #[serde(default)]
+    creditor_name: Option<String>,
+    #[serde(default)]
+    creditor_account: Option<RawAccount>,
+    #[serde(default)]
+    debtor_name: Option<String>,
+    #[serde(default)]
+    debtor_account: Option<RawAccount>,
+    #[serde(default)]
+    remittance_information_unstructured: Option<String>,
+    #[serde(default)]
+    remittance_information_unstructured_array: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawAccount {
+    #[serde(default)]
+    iban: Option<String>,
+    #[serde(default)]
+    bban: Option<String>,
+}
+
+/// Amounts are strings in the API, e.g. `"-12.30"`
+#[derive(Deserialize)]
+struct RawAmount {
+    amount: String,
+}
+
+impl RawAmount {
+    fn parse(&self, source: &str) -> Result<Decimal> {
+        self.amount
+            .parse()
+            .with_context(|| format!("Invalid amount {:?} in {}", self.amount, source))
+    }
+}
+
+/// Reconstructs end-of-day balances from the booked transactions, anchored to the booked balances that have a
+/// reference date. A `closingBooked` or `interimBooked` balance is the balance at the end of its date, and an
+/// `openingBooked` balance the balance at the end of the day before. Available and expected balances include pending
+/// transactions or credit limits, so they're ignored.
+pub fn parse_balances(
+    contents: &[u8],
+    source: &str,
+    context: &ParseContext,
+) -> Result<Vec<BalanceRecord>> {
+    let data = parse_account_data(contents, source)?;
+    let transactions = booked_transactions(&data, source, context)?;
+
+    let mut known = Vec::new();
+    for raw in &data.balances {
+        let days_before = match raw.balance_type.as_str() {
+            "closingBooked" | "interimBooked" => 0,
+            "openingBooked" => 1,
+            _ => continue,
+        };
+        let Some(date) = &raw.reference_date else {
+            continue;
+        };
+        let location = format!("{} {} balance", source, raw.balance_type);
+        let Some(date) = context.parse_date(date, &location)? else {
+            continue;
+        };
+        known.push(BalanceRecord {
+            date: date - chrono::Days::new(days_before),
+            amount: raw.balance_amount.parse(source)?,
+            ..Default::default()
+        });
+    }
+
+    ledger::reconstruct(&transactions, &known, source, context)
+}
+
+/// The booked transactions, with the other side of each: the creditor for payments out and the debtor for payments in
+pub fn parse_transactions(
+    contents: &[u8],
+    source: &str,
+    context: &ParseContext,
+) -> Result<Vec<Transaction>> {
+    booked_transactions(&parse_account_data(contents, source)?, source, context)
+}
+
+fn parse_account_data(contents: &[u8], source: &str) -> Result<AccountData> {
+    serde_json::from_slice(contents)
+        .with_context(|| format!("Invalid GoCardless account data in {}", source))
+}
+
+fn booked_transactions(
+    data: &AccountData,
+    source: &str,
+    context: &ParseContext,
+) -> Result<Vec<Transaction>> {
+    let mut transactions = Vec::new();
+    for (index, raw) in data.transactions.booked.iter().enumerate() {
+        let location = format!("{} transaction {}", source, index + 1);
+        let Some(date) = raw.booking_date.as_ref().or(raw.value_date.as_ref()) else {
+            context.issue_at(
+                codes::INVALID_DATE,
+                &location,
+                format!("No booking or value date for {}, skipping it", location),
+            )?;
+            continue;
+        };
+        let Some(date) = context.parse_date(date, &location)? else {
+            continue;
+        };
+
+        let amount = raw.transaction_amount.parse(source)?;
+        let (name, account) = if amount.is_sign_negative() {
+            (&raw.creditor_name, &raw.creditor_account)
+        } else {
+            (&raw.debtor_name, &raw.debtor_account)
+        };
+        let description = raw.remittance_information_unstructured.clone().or_else(|| {
+            (!raw.remittance_information_unstructured_array.is_empty())
+                .then(|| raw.remittance_information_unstructured_array.join(" "))
+        });
+        transactions.push(Transaction {
+            date,
+            amount,
+            counterparty_name: name.clone(),
+            counterparty_account: account
+                .as_ref()
+                .and_then(|account| account.iban.clone().or_else(|| account.bban.clone())),
+            description,
+        });
+    }
+    Ok(transactions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    const ACCOUNT_DATA: &str = r#"{
+        "balances": [
+            {
+                "balanceAmount": {"amount": "1200.00", "currency": "EUR"},
+                "balanceType": "closingBooked",
+                "referenceDate": "2023-03-31"
+            },
+            {
+                "balanceAmount": {"amount": "5000.00", "currency": "EUR"},
+                "balanceType": "interimAvailable",
+                "referenceDate": "2023-03-31"
+            },
+            {
+                "balanceAmount": {"amount": "1000.00", "currency": "EUR"},
+                "balanceType": "openingBooked",
+                "referenceDate": "2023-03-01"
+            }
+        ],
+        "transactions": {
+            "booked": [
+                {
+                    "bookingDate": "2023-03-20",
+                    "transactionAmount": {"amount": "-300.00", "currency": "EUR"},
+                    "creditorName": "Jane Doe",
+                    "creditorAccount": {"iban": "DE89370400440532013000"},
+                    "remittanceInformationUnstructuredArray": ["Savings", "March"]
+                },
+                {"bookingDate": "2023-03-02", "transactionAmount": {"amount": "450.00", "currency": "EUR"}},
+                {"valueDate": "2023-03-02", "transactionAmount": {"amount": "50.00", "currency": "EUR"}}
+            ],
+            "pending": [
+                {"valueDate": "2023-04-01", "transactionAmount": {"amount": "-999.00", "currency": "EUR"}}
+            ]
+        }
+    }"#;
+
+    fn date(value: &str) -> NaiveDate {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_balances() -> Result<()> {
+        let context = ParseContext::default();
+        let balances = parse_balances(ACCOUNT_DATA.as_bytes(), "data.json", &context)?;
+
+        let series: Vec<_> = balances
+            .iter()
+            .map(|balance| (balance.date, balance.amount))
+            .collect();
+        assert_eq!(
+            series,
+            vec![
+                (date("2023-02-28"), dec!(1000)),
+                (date("2023-03-02"), dec!(1500)),
+                (date("2023-03-20"), dec!(1200)),
+                (date("2023-03-31"), dec!(1200)),
+            ]
+        );
+        assert!(context.warnings().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_transactions() -> Result<()> {
+        let transactions = parse_transactions(
+            ACCOUNT_DATA.as_bytes(),
+            "data.json",
+            &ParseContext::default(),
+        )?;
+
+        assert_eq!(transactions.len(), 3);
+        assert_eq!(
+            transactions[0].counterparty_name.as_deref(),
+            Some("Jane Doe")
+        );
+        assert_eq!(
+            transactions[0].counterparty_account.as_deref(),
+            Some("DE89370400440532013000")
+        );
+        assert_eq!(
+            transactions[0].description.as_deref(),
+            Some("Savings March")
+        );
+        assert_eq!(transactions[1].counterparty_name, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect() {
+        let importer = GoCardlessImporter;
+        assert!(importer.detect("account.json", ACCOUNT_DATA.as_bytes()));
+        assert!(!importer.detect("account.json", b"{\"balances\": []}"));
+        assert!(!importer.detect("account.csv", ACCOUNT_DATA.as_bytes()));
+    }
+
+    #[test]
+    fn test_missing_transaction() -> Result<()> {
+        // Dropping the 450.00 deposit leaves the opening balance inconsistent with the closing one
+        let data = ACCOUNT_DATA.replace(
+            r#"{"bookingDate": "2023-03-02", "transactionAmount": {"amount": "450.00", "currency": "EUR"}},"#,
+            "",
+        );
+
+        let context = ParseContext::default();
+        parse_balances(data.as_bytes(), "data.json", &context)?;
+        assert_eq!(context.warnings().len(), 1);
+        assert!(context.warnings()[0].contains("1000.00 on 2023-02-28"));
+
+        assert!(parse_balances(data.as_bytes(), "data.json", &ParseContext::strict()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_needs_balance() {
+        let data = r#"{"transactions": {"booked": [
+            {"bookingDate": "2023-03-02", "transactionAmount": {"amount": "450.00"}}
+        ]}}"#;
+        assert!(parse_balances(data.as_bytes(), "data.json", &ParseContext::default()).is_err());
+    }
+}
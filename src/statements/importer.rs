@@ -0,0 +1,185 @@
+use anyhow::{bail, Result};
+
+use super::csv::CsvImporter;
+use super::gocardless::GoCardlessImporter;
+use super::mt940::Mt940Importer;
+use super::ofx::OfxImporter;
+use super::xlsx::XlsxImporter;
+use crate::data::{AccountKind, BalanceRecord};
+use crate::parsing::ParseContext;
+
+/// Turns a statement file in some bank- or format-specific shape into balance records.
+///
+/// Implement this in your own crate to support a bank's export format, and add it to an `ImporterRegistry` to make
+/// it available to `StatementStore::import`.
+pub trait StatementImporter {
+    /// Short name used to pick the importer explicitly, e.g. `csv`
+    fn name(&self) -> &str;
+
+    /// Whether this importer recognises the statement, judged from its file name and contents
+    fn detect(&self, file_name: &str, contents: &[u8]) -> bool;
+
+    /// Parses the statement, reporting recoverable problems through the parse context
+    fn parse(
+        &self,
+        contents: &[u8],
+        source: &str,
+        context: &ParseContext,
+    ) -> Result<Vec<BalanceRecord>>;
+
+    /// The kind of account the statement is for, when its format says
+    fn account_kind(&self, _contents: &[u8]) -> Option<AccountKind> {
+        None
+    }
+}
+
+/// The importers available to `import`. The default registry holds the built-in CSV, OFX, MT940, GoCardless and
+/// Excel importers.
+pub struct ImporterRegistry {
+    importers: Vec<Box<dyn StatementImporter>>,
+}
+
+impl Default for ImporterRegistry {
+    fn default() -> Self {
+        Self::empty()
+            .with(Box::new(CsvImporter))
+            .with(Box::new(OfxImporter))
+            .with(Box::new(Mt940Importer))
+            .with(Box::new(GoCardlessImporter))
+            .with(Box::new(XlsxImporter::default()))
+    }
+}
+
+impl ImporterRegistry {
+    pub fn empty() -> Self {
+        Self {
+            importers: Vec::new(),
+        }
+    }
+
+    /// Adds an importer. Importers registered later take priority when detecting a statement's format, so custom
+    /// importers can claim files the built-in ones would otherwise pick up.
+    pub fn with(mut self, importer: Box<dyn StatementImporter>) -> Self {
+        self.importers.push(importer);
+        self
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.importers
+            .iter()
+            .map(|importer| importer.name())
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn StatementImporter> {
+        self.importers
+            .iter()
+            .rev()
+            .find(|importer| importer.name() == name)
+            .map(|importer| importer.as_ref())
+    }
+
+    pub fn detect(&self, file_name: &str, contents: &[u8]) -> Option<&dyn StatementImporter> {
+        self.importers
+            .iter()
+            .rev()
+            .find(|importer| importer.detect(file_name, contents))
+            .map(|importer| importer.as_ref())
+    }
+
+    /// Picks the importer named by `format`, or detects one when no format is given
+    pub fn resolve(
+        &self,
+        format: Option<&str>,
+        file_name: &str,
+        contents: &[u8],
+    ) -> Result<&dyn StatementImporter> {
+        let importer = match format {
+            Some(format) => self.get(format),
+            None => self.detect(file_name, contents),
+        };
+
+        match (importer, format) {
+            (Some(importer), _) => Ok(importer),
+            (None, Some(format)) => bail!(
+                "Unknown statement format {:?} (available: {})",
+                format,
+                self.names().join(", ")
+            ),
+            (None, None) => bail!(
+                "Could not detect the format of {}; pass one of: {}",
+                file_name,
+                self.names().join(", ")
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    /// Claims any `.txt` file and returns a single fixed record
+    struct FixedImporter;
+
+    impl StatementImporter for FixedImporter {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        fn detect(&self, file_name: &str, _contents: &[u8]) -> bool {
+            file_name.ends_with(".txt")
+        }
+
+        fn parse(
+            &self,
+            _contents: &[u8],
+            _source: &str,
+            _context: &ParseContext,
+        ) -> Result<Vec<BalanceRecord>> {
+            Ok(vec![BalanceRecord {
+                date: NaiveDate::from_ymd_opt(2023, 1, 31).unwrap(),
+                amount: Decimal::ONE,
+                ..Default::default()
+            }])
+        }
+    }
+
+    #[test]
+    fn test_default_importers() -> Result<()> {
+        let registry = ImporterRegistry::default();
+        assert_eq!(
+            registry.names(),
+            vec!["csv", "ofx", "mt940", "gocardless", "xlsx"]
+        );
+
+        assert_eq!(registry.resolve(None, "jan.csv", b"")?.name(), "csv");
+        assert_eq!(registry.resolve(None, "jan.qfx", b"")?.name(), "ofx");
+        assert_eq!(registry.resolve(None, "jan.sta", b"")?.name(), "mt940");
+        assert_eq!(registry.resolve(None, "jan.XLSX", b"")?.name(), "xlsx");
+        assert_eq!(registry.resolve(Some("csv"), "jan.txt", b"")?.name(), "csv");
+
+        assert!(registry.resolve(None, "jan.txt", b"").is_err());
+        assert!(registry.resolve(Some("qif"), "jan.qif", b"").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_importer() -> Result<()> {
+        let registry = ImporterRegistry::default().with(Box::new(FixedImporter));
+
+        let importer = registry.resolve(None, "jan.txt", b"anything")?;
+        assert_eq!(importer.name(), "fixed");
+        assert_eq!(
+            importer
+                .parse(b"anything", "jan.txt", &ParseContext::default())?
+                .len(),
+            1
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+use anyhow::{bail, Result};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound::{Excluded, Included};
+
+use crate::data::BalanceRecord;
+use crate::diagnostics::codes;
+use crate::parsing::ParseContext;
+
+/// Longest stretch without a transaction that isn't flagged as a possible gap in the ledger
+pub const MAX_GAP_DAYS: i64 = 45;
+
+/// A booked transaction from a bank's ledger: money in is positive, money out negative
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Transaction {
+    pub date: NaiveDate,
+    pub amount: Decimal,
+    /// Name of whoever the money came from or went to, when the export gives it
+    pub counterparty_name: Option<String>,
+    /// IBAN or account number of the other side of the transaction, when the export gives it
+    pub counterparty_account: Option<String>,
+    /// Reference or remittance text
+    pub description: Option<String>,
+}
+
+/// Derives end-of-day balances from a ledger of transactions, for banks whose exports have no running balance.
+///
+/// The latest of the known balances anchors the series, and every day with transactions or a known balance gets a
+/// record of the balance at its close, worked forwards and backwards from the anchor. Balances are unchanged on the days between, so the
+/// records capture every maximum. Known balances are end-of-day figures; the others are checked against the
+/// reconstruction, and a mismatch, which means transactions are missing or wrong, is reported through the parse
+/// context, as is any stretch of more than `MAX_GAP_DAYS` without transactions. Fails without a known balance.
+pub fn reconstruct(
+    transactions: &[Transaction],
+    known: &[BalanceRecord],
+    source: &str,
+    context: &ParseContext,
+) -> Result<Vec<BalanceRecord>> {
+    let Some(anchor) = known.iter().max_by_key(|balance| balance.date) else {
+        bail!(
+            "{} has transactions but no balance to reconstruct balances from",
+            source
+        );
+    };
+
+    let mut daily: BTreeMap<NaiveDate, Decimal> = BTreeMap::new();
+    for transaction in transactions {
+        *daily.entry(transaction.date).or_default() += transaction.amount;
+    }
+
+    let dates: Vec<NaiveDate> = daily.keys().copied().collect();
+    for pair in dates.windows(2) {
+        if (pair[1] - pair[0]).num_days() > MAX_GAP_DAYS {
+            context.issue_at(
+                codes::TRANSACTION_GAP,
+                source,
+                format!(
+                    "No transactions in {} between {} and {}; check the export covers that period",
+                    source, pair[0], pair[1]
+                ),
+            )?;
+        }
+    }
+
+    // Each day's net movement is applied at its close, so a day's balance includes its own transactions
+    let balance_at = |date: NaiveDate| -> Decimal {
+        if date >= anchor.date {
+            let since: Decimal = daily
+                .range((Excluded(anchor.date), Included(date)))
+                .map(|(_, net)| net)
+                .sum();
+            anchor.amount + since
+        } else {
+            let until: Decimal = daily
+                .range((Excluded(date), Included(anchor.date)))
+                .map(|(_, net)| net)
+                .sum();
+            anchor.amount - until
+        }
+    };
+
+    for balance in known {
+        let reconstructed = balance_at(balance.date);
+        if reconstructed != balance.amount {
+            context.issue_at(
+                codes::BALANCE_MISMATCH,
+                source,
+                format!(
+                    "{} gives a balance of {} on {} but its transactions give {}; transactions may be missing \
+                     between then and {}",
+                    source, balance.amount, balance.date, reconstructed, anchor.date
+                ),
+            )?;
+        }
+    }
+
+    let mut days: BTreeSet<NaiveDate> = dates.into_iter().collect();
+    days.extend(known.iter().map(|balance| balance.date));
+    Ok(days
+        .into_iter()
+        .map(|date| BalanceRecord {
+            date,
+            amount: balance_at(date),
+            ..Default::default()
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn transaction(date: &str, amount: Decimal) -> Transaction {
+        Transaction {
+            date: date.parse().unwrap(),
+            amount,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_reconstruct() -> Result<()> {
+        let transactions = vec![
+            transaction("2023-01-10", dec!(100)),
+            transaction("2023-01-10", dec!(-40)),
+            transaction("2023-01-20", dec!(25)),
+            transaction("2023-02-05", dec!(-500)),
+        ];
+        let known = vec![BalanceRecord {
+            date: "2023-01-31".parse()?,
+            amount: dec!(1085),
+            ..Default::default()
+        }];
+
+        let context = ParseContext::default();
+        let balances = reconstruct(&transactions, &known, "ledger", &context)?;
+        let series: Vec<_> = balances
+            .iter()
+            .map(|balance| (balance.date.to_string(), balance.amount))
+            .collect();
+        assert_eq!(
+            series,
+            vec![
+                ("2023-01-10".to_string(), dec!(1060)),
+                ("2023-01-20".to_string(), dec!(1085)),
+                ("2023-01-31".to_string(), dec!(1085)),
+                ("2023-02-05".to_string(), dec!(585)),
+            ]
+        );
+        assert!(context.warnings().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reconstruct_flags_gaps() -> Result<()> {
+        let transactions = vec![
+            transaction("2023-01-10", dec!(100)),
+            transaction("2023-04-10", dec!(100)),
+        ];
+        let known = vec![BalanceRecord {
+            date: "2023-04-10".parse()?,
+            amount: dec!(300),
+            ..Default::default()
+        }];
+
+        let context = ParseContext::default();
+        reconstruct(&transactions, &known, "ledger", &context)?;
+        assert_eq!(context.warnings().len(), 1);
+        assert!(context.warnings()[0].contains("between 2023-01-10 and 2023-04-10"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+//! Finds the account in `data.yml` a statement is for from the identifiers it gives, so statements can be imported
+//! without naming the account.
+
+use anyhow::{bail, Result};
+
+use crate::data::{Account, IdentifierKind, UserData};
+
+/// The account with an identifier matching the statement's account ID. When the number matches more than one, e.g.
+/// the same account number at two banks, the statement's bank ID is checked against the BICs of the accounts'
+/// providers, and against BICs given as account identifiers.
+pub fn match_account<'a>(
+    user_data: &'a UserData,
+    account_id: &str,
+    bank_id: Option<&str>,
+) -> Result<&'a Account> {
+    let account_id = compact(account_id);
+    let candidates: Vec<&Account> = user_data
+        .accounts
+        .iter()
+        .filter(|account| {
+            identifiers(account)
+                .filter(|(kind, _)| *kind != Some(IdentifierKind::Bic))
+                .any(|(_, identifier)| same_account(&compact(identifier), &account_id))
+        })
+        .collect();
+
+    let matched = match candidates.as_slice() {
+        [] => bail!("No account in data.yml has the identifier {}", account_id),
+        [account] => return Ok(account),
+        _ => match bank_id.and_then(|id| IdentifierKind::Bic.normalize(id).ok()) {
+            Some(bic) => candidates
+                .iter()
+                .copied()
+                .filter(|account| {
+                    bics(user_data, account).any(|known| same_institution(&known, &bic))
+                })
+                .collect(),
+            None => Vec::new(),
+        },
+    };
+    match matched.as_slice() {
+        [account] => Ok(account),
+        _ => bail!(
+            "{} is the identifier of more than one account ({}), and the statement's bank ID {} doesn't tell them \
+             apart; give their providers a bic, or name the account to import into",
+            account_id,
+            candidates
+                .iter()
+                .map(|account| account.handle.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            bank_id.unwrap_or("(none)")
+        ),
+    }
+}
+
+fn identifiers(account: &Account) -> impl Iterator<Item = (Option<IdentifierKind>, &String)> {
+    [
+        (account.primary_identifier_kind(), &account.identifier1),
+        (account.secondary_identifier_kind(), &account.identifier2),
+    ]
+    .into_iter()
+    .filter_map(|(kind, identifier)| identifier.as_ref().map(|identifier| (kind, identifier)))
+}
+
+/// The BICs known for an account: its provider's, and any given as one of its identifiers
+fn bics<'a>(user_data: &'a UserData, account: &'a Account) -> impl Iterator<Item = String> + 'a {
+    let provider = user_data
+        .providers
+        .iter()
+        .find(|provider| provider.handle == account.provider)
+        .and_then(|provider| provider.bic.clone());
+    identifiers(account)
+        .filter(|(kind, _)| *kind == Some(IdentifierKind::Bic))
+        .map(|(_, identifier)| compact(identifier))
+        .chain(provider)
+}
+
+/// Whether an identifier is the statement's account ID, or ends with it, as an IBAN ends with the account number
+fn same_account(identifier: &str, account_id: &str) -> bool {
+    identifier == account_id || (account_id.len() >= 6 && identifier.ends_with(account_id))
+}
+
+/// BICs name the same institution when their first 8 characters match; the last 3, when given, are the branch
+fn same_institution(a: &str, b: &str) -> bool {
+    a.get(..8)
+        .is_some_and(|institution| Some(institution) == b.get(..8))
+}
+
+fn compact(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Address, Provider};
+    use crate::statements::mt940;
+
+    fn provider(handle: &str, bic: Option<&str>) -> Provider {
+        Provider {
+            name: handle.to_string(),
+            handle: handle.to_string(),
+            address: Address::Freeform("1 Bank Street".to_string()),
+            country: Some("GB".to_string()),
+            bic: bic.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn account(handle: &str, provider: &str, identifier: &str) -> Account {
+        Account {
+            handle: handle.to_string(),
+            provider: provider.to_string(),
+            identifier1_kind: Some(IdentifierKind::AccountNumber),
+            identifier1: Some(identifier.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_match_account() -> Result<()> {
+        let mut user_data = UserData {
+            providers: vec![
+                provider("barclays", Some("BARCGB22")),
+                provider("hsbc", None),
+            ],
+            accounts: vec![
+                account("current", "barclays", "12345678"),
+                account("savings", "hsbc", "12345678"),
+                account("isa", "hsbc", "87654321"),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(match_account(&user_data, "8765 4321", None)?.handle, "isa");
+        assert!(match_account(&user_data, "11111111", None).is_err());
+
+        // The same number at two banks needs the bank's BIC
+        let err = match_account(&user_data, "12345678", None).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("more than one account (current, savings)"));
+        assert_eq!(
+            match_account(&user_data, "12345678", Some("BARCGB22XXX"))?.handle,
+            "current"
+        );
+        assert!(match_account(&user_data, "12345678", Some("MIDLGB22")).is_err());
+
+        // A BIC given as an account identifier counts too
+        user_data.accounts[1].identifier2_kind = Some(IdentifierKind::Bic);
+        user_data.accounts[1].identifier2 = Some("MIDLGB22".to_string());
+        assert_eq!(
+            match_account(&user_data, "12345678", Some("MIDLGB22"))?.handle,
+            "savings"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_match_mt940_statement() -> Result<()> {
+        let user_data = UserData {
+            providers: vec![
+                provider("barclays", Some("BARCGB22")),
+                provider("hsbc", Some("MIDLGB22")),
+            ],
+            accounts: vec![
+                account("current", "barclays", "12345678"),
+                account("savings", "hsbc", "12345678"),
+            ],
+            ..Default::default()
+        };
+
+        let statement =
+            "{1:F01MIDLGB22AXXX0000000000}{4:\n:20:STMT\n:25:12345678\n:62F:C230131GBP100,00\n-}";
+        let account = match_account(
+            &user_data,
+            mt940::account_id(statement).unwrap(),
+            mt940::bank_id(statement).as_deref(),
+        )?;
+        assert_eq!(account.handle, "savings");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1001 @@
+pub mod csv;
+pub mod gocardless;
+pub mod importer;
+pub mod ledger;
+pub mod matching;
+pub mod mt940;
+pub mod normalize;
+pub mod ofx;
+pub mod profile;
+pub mod series;
+pub mod sheets;
+pub mod xlsx;
+pub use self::importer::{ImporterRegistry, StatementImporter};
+pub use self::normalize::NormalizeResult;
+pub use self::profile::ImportProfile;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Datelike, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use crate::data::{AccountKind, BalanceRecord};
+use crate::parsing::ParseContext;
+
+/// Where a batch of balance records came from, so every figure can be traced back to the statement it was
+/// derived from.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct Provenance {
+    pub source_file: String,
+    pub imported_at: DateTime<Utc>,
+    pub sha256: String,
+    /// Import profile the amounts were normalized with, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct StatementImport {
+    #[serde(flatten)]
+    pub provenance: Provenance,
+    /// Listed, or in a compressed file as a `series::BalanceSeries`
+    #[serde(deserialize_with = "series::deserialize_balances")]
+    #[schemars(with = "series::StoredBalances")]
+    pub balances: Vec<BalanceRecord>,
+}
+
+/// Statements imported for a single account. New imports are added to `statements/<account handle>.yml`, and
+/// `StatementStore::normalize` splits them into one `statements/<account handle>.<year>.yml` per year.
+///
+/// Each import records where its balances came from. Files are checked with `validate` as they're read and written.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct StatementFile {
+    /// Handle of the account in `data.yml`
+    pub account: String,
+    /// Currency of the records that don't give their own, when it isn't the account's, e.g. a file written by
+    /// another tool for statements in GBP. Moved onto the records when the file is read, so files the store writes
+    /// give each record's currency on the record.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    #[serde(default)]
+    pub imports: Vec<StatementImport>,
+    /// Whether the file writes its imports' records as series, as `statements compress` leaves them. Kept when the
+    /// file is read and written back.
+    #[serde(skip)]
+    pub compressed: bool,
+}
+
+impl StatementFile {
+    pub fn new(account: &str, imports: Vec<StatementImport>) -> Self {
+        Self {
+            account: account.to_string(),
+            currency: None,
+            imports,
+            compressed: false,
+        }
+    }
+
+    /// Reads a statement file in either form, noting whether it was compressed
+    fn from_yaml(value: serde_yaml::Value, context: &ParseContext, source: &str) -> Result<Self> {
+        let compressed = value
+            .get("imports")
+            .and_then(|imports| imports.as_sequence())
+            .is_some_and(|imports| {
+                imports.iter().any(|import| {
+                    import
+                        .get("balances")
+                        .is_some_and(|balances| balances.is_mapping())
+                })
+            });
+        let mut file: StatementFile = context.deserialize(value, source)?;
+        file.compressed = compressed;
+        Ok(file)
+    }
+
+    /// The file as YAML, with each import's records as a series when the file is compressed and they can be one
+    fn to_yaml(&self) -> Result<String> {
+        let mut value = serde_yaml::to_value(self)?;
+        if self.compressed {
+            let imports = value
+                .get_mut("imports")
+                .and_then(|imports| imports.as_sequence_mut());
+            for (import, stored) in imports.into_iter().flatten().zip(&self.imports) {
+                if let Some(series) = series::BalanceSeries::compress(&stored.balances) {
+                    import["balances"] = serde_yaml::to_value(series)?;
+                }
+            }
+        }
+        Ok(serde_yaml::to_string(&value)?)
+    }
+
+    /// Checks the file is one the store can use: an account handle, currency codes, SHA-256 checksums and source
+    /// files for every import, no records marked `manual`, which belong in `data.yml`, and for a yearly file, only
+    /// records from its year
+    pub fn validate(&self, year: Option<i32>) -> Result<()> {
+        if self.account.trim().is_empty() || self.account.contains(['/', '\\']) {
+            bail!("Invalid account handle {:?}", self.account);
+        }
+        if let Some(currency) = &self.currency {
+            check_currency_code(currency)?;
+        }
+        for import in &self.imports {
+            let provenance = &import.provenance;
+            if provenance.source_file.trim().is_empty() {
+                bail!("Import from {} has no source_file", provenance.imported_at);
+            }
+            if provenance.sha256.len() != 64
+                || !provenance.sha256.chars().all(|c| c.is_ascii_hexdigit())
+            {
+                bail!(
+                    "Import of {} has an invalid sha256 {:?}; expected 64 hex digits",
+                    provenance.source_file,
+                    provenance.sha256
+                );
+            }
+            for record in &import.balances {
+                if let Some(currency) = &record.currency {
+                    check_currency_code(currency)?;
+                }
+                record
+                    .usd_equivalent()
+                    .with_context(|| format!("Import of {}", provenance.source_file))?;
+                if record.manual {
+                    bail!(
+                        "Record on {} imported from {} is marked manual; balances entered by hand belong in \
+                         data.yml",
+                        record.date,
+                        provenance.source_file
+                    );
+                }
+                if let Some(year) = year.filter(|year| record.date.year() != *year) {
+                    bail!(
+                        "Record on {} imported from {} is in the {} file",
+                        record.date,
+                        provenance.source_file,
+                        year
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the file's `currency` onto the records that don't give their own
+    fn resolve_currency(&mut self) {
+        let Some(currency) = self.currency.take() else {
+            return;
+        };
+        let currency = currency.to_lowercase();
+        for record in self
+            .imports
+            .iter_mut()
+            .flat_map(|import| import.balances.iter_mut())
+        {
+            record.currency.get_or_insert_with(|| currency.clone());
+        }
+    }
+}
+
+fn check_currency_code(currency: &str) -> Result<()> {
+    if currency.is_empty() || !currency.chars().all(|c| c.is_ascii_alphanumeric()) {
+        bail!("Invalid currency code {:?}", currency);
+    }
+    Ok(())
+}
+
+/// Outcome of importing a statement
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportResult {
+    /// The import as recorded, holding only the records that weren't already present. Nothing is recorded when
+    /// there are no new records.
+    pub import: StatementImport,
+    /// Records skipped because the account already had a record with the same date, amount and currency, or the
+    /// statement gave it twice
+    pub duplicates: Vec<BalanceRecord>,
+    /// Name of the importer that parsed the statement, e.g. `csv`
+    pub format: String,
+    /// The kind of account the statement is for, when its format says
+    pub account_kind: Option<AccountKind>,
+}
+
+pub struct StatementStore {
+    dir: PathBuf,
+    importers: ImporterRegistry,
+}
+
+impl StatementStore {
+    pub fn new(base_path: &Path) -> Self {
+        Self {
+            dir: base_path.join("statements"),
+            importers: ImporterRegistry::default(),
+        }
+    }
+
+    /// Replaces the built-in importers, e.g. with a registry that includes bank-specific ones
+    pub fn with_importers(mut self, importers: ImporterRegistry) -> Self {
+        self.importers = importers;
+        self
+    }
+
+    /// Loads every statement file in the store, ordered by account handle. Accounts normalized into yearly files
+    /// have one entry per file.
+    pub fn load_all(&self, context: &ParseContext) -> Result<Vec<StatementFile>> {
+        Ok(self
+            .load_files(context)?
+            .into_iter()
+            .map(|(_, file)| file)
+            .collect())
+    }
+
+    /// Every statement file with the path it was read from, ordered by account handle and then path
+    fn load_files(&self, context: &ParseContext) -> Result<Vec<(PathBuf, StatementFile)>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) == Some("yml") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        // Parsed in parallel, as a data directory can have hundreds of statement files
+        let loaded = context.parse_parallel(paths, |path, context| {
+            let file = load_statement_file(&path, context);
+            (path, file)
+        });
+        let mut files = Vec::new();
+        let mut errors = Vec::new();
+        for (path, file) in loaded {
+            match file {
+                Ok(file) => files.push((path, file)),
+                Err(err) => errors.push(err),
+            }
+        }
+        if errors.len() > 1 {
+            let errors: Vec<String> = errors.iter().map(|err| format!("  {:#}", err)).collect();
+            bail!(
+                "{} statement files couldn't be loaded:\n{}",
+                errors.len(),
+                errors.join("\n")
+            );
+        }
+        if let Some(err) = errors.pop() {
+            return Err(err);
+        }
+
+        files.sort_by(|(a_path, a), (b_path, b)| {
+            a.account.cmp(&b.account).then_with(|| a_path.cmp(b_path))
+        });
+
+        Ok(files)
+    }
+
+    /// Imports a statement for an account, recording its checksum and import time alongside the records
+    ///
+    /// The statement is parsed by the importer named by `format`, or by whichever registered importer recognises it.
+    /// Records already imported for the account are skipped, so re-importing an updated export from the bank only
+    /// adds what's new. Amounts are normalized by the import profile, if given, before they're compared.
+    pub fn import(
+        &self,
+        account: &str,
+        statement_path: &Path,
+        format: Option<&str>,
+        profile: Option<&ImportProfile>,
+        context: &ParseContext,
+    ) -> Result<ImportResult> {
+        let _span =
+            tracing::info_span!("import", account, path = %statement_path.display()).entered();
+        let (mut file, result) = self.prepare(account, statement_path, format, profile, context)?;
+
+        if !result.import.balances.is_empty() {
+            file.imports.push(result.import.clone());
+            self.save(&file)?;
+        }
+
+        tracing::info!(
+            new = result.import.balances.len(),
+            duplicates = result.duplicates.len(),
+            "Imported statement"
+        );
+        Ok(result)
+    }
+
+    /// Parses a statement and checks it against the account's records exactly as `import` would, without writing
+    /// anything
+    pub fn preview(
+        &self,
+        account: &str,
+        statement_path: &Path,
+        format: Option<&str>,
+        profile: Option<&ImportProfile>,
+        context: &ParseContext,
+    ) -> Result<ImportResult> {
+        let _span =
+            tracing::info_span!("preview", account, path = %statement_path.display()).entered();
+        let (_, result) = self.prepare(account, statement_path, format, profile, context)?;
+        Ok(result)
+    }
+
+    /// The import a statement would make, and the account's statement file it would be added to
+    fn prepare(
+        &self,
+        account: &str,
+        statement_path: &Path,
+        format: Option<&str>,
+        profile: Option<&ImportProfile>,
+        context: &ParseContext,
+    ) -> Result<(StatementFile, ImportResult)> {
+        let source_file = match statement_path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name.to_string(),
+            None => bail!("Invalid statement path {:?}", statement_path),
+        };
+
+        let bytes = std::fs::read(statement_path)
+            .with_context(|| format!("Could not read statement {:?}", statement_path))?;
+        self.prepare_contents(account, source_file, &bytes, format, profile, context)
+    }
+
+    /// Imports a statement that isn't read from a file, e.g. one fetched from a spreadsheet service, under the given
+    /// source name. Otherwise the same as `import`.
+    pub fn import_contents(
+        &self,
+        account: &str,
+        source_file: &str,
+        bytes: &[u8],
+        format: Option<&str>,
+        profile: Option<&ImportProfile>,
+        context: &ParseContext,
+    ) -> Result<ImportResult> {
+        let _span = tracing::info_span!("import", account, source = source_file).entered();
+        let (mut file, result) = self.prepare_contents(
+            account,
+            source_file.to_string(),
+            bytes,
+            format,
+            profile,
+            context,
+        )?;
+
+        if !result.import.balances.is_empty() {
+            file.imports.push(result.import.clone());
+            self.save(&file)?;
+        }
+        Ok(result)
+    }
+
+    fn prepare_contents(
+        &self,
+        account: &str,
+        source_file: String,
+        bytes: &[u8],
+        format: Option<&str>,
+        profile: Option<&ImportProfile>,
+        context: &ParseContext,
+    ) -> Result<(StatementFile, ImportResult)> {
+        let importer = self.importers.resolve(format, &source_file, bytes)?;
+        let number_format = profile
+            .map(|profile| profile.number_format())
+            .transpose()?
+            .flatten();
+        let parsed = context.with_amounts_in(number_format, |context| {
+            importer.parse(bytes, &source_file, context)
+        })?;
+        let parsed = match profile {
+            Some(profile) => profile.apply(parsed),
+            None => parsed,
+        };
+
+        let file = self
+            .load(account)?
+            .unwrap_or_else(|| StatementFile::new(account, Vec::new()));
+        let yearly_files = self.load_yearly(account)?;
+
+        let mut balances: Vec<BalanceRecord> = Vec::new();
+        let mut duplicates = Vec::new();
+        for record in parsed {
+            let already_present = file
+                .imports
+                .iter()
+                .chain(yearly_files.iter().flat_map(|file| file.imports.iter()))
+                .flat_map(|import| import.balances.iter())
+                .chain(balances.iter())
+                .any(|existing| *existing == record);
+
+            if already_present {
+                duplicates.push(record);
+            } else {
+                balances.push(record);
+            }
+        }
+
+        let import = StatementImport {
+            provenance: Provenance {
+                source_file,
+                imported_at: Utc::now(),
+                sha256: hex::encode(Sha256::digest(bytes)),
+                profile: profile.map(|profile| profile.name.clone()),
+            },
+            balances,
+        };
+        let result = ImportResult {
+            import,
+            duplicates,
+            format: importer.name().to_string(),
+            account_kind: importer.account_kind(bytes),
+        };
+        Ok((file, result))
+    }
+
+    fn load(&self, account: &str) -> Result<Option<StatementFile>> {
+        let path = self.file_path(account);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let contents = std::fs::read_to_string(&path)?;
+        let value: serde_yaml::Value = serde_yaml::from_str(&contents)?;
+        let mut file =
+            StatementFile::from_yaml(value, &ParseContext::default(), &path.display().to_string())?;
+        file.validate(None)
+            .with_context(|| format!("Invalid statement file {:?}", path))?;
+        file.resolve_currency();
+        Ok(Some(file))
+    }
+
+    /// The account's normalized yearly files, so new imports aren't compared against its latest imports alone
+    fn load_yearly(&self, account: &str) -> Result<Vec<StatementFile>> {
+        let prefix = format!("{}.", account);
+        let mut files = Vec::new();
+        if !self.dir.exists() {
+            return Ok(files);
+        }
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let is_yearly = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(&prefix)?.strip_suffix(".yml"))
+                .is_some_and(|year| year.parse::<i32>().is_ok());
+            if is_yearly {
+                files.push(load_statement_file(&path, &ParseContext::default())?);
+            }
+        }
+        Ok(files)
+    }
+
+    /// Rewrites every statement file with its imports' records as series, or as lists when `compressed` is false,
+    /// returning the files changed. Records that can't be a series, e.g. ones with their own USD value, stay listed.
+    pub fn set_compressed(&self, compressed: bool, context: &ParseContext) -> Result<Vec<PathBuf>> {
+        let mut changed = Vec::new();
+        for (path, mut file) in self.load_files(context)? {
+            if file.compressed != compressed {
+                file.compressed = compressed;
+                self.save_to(&path, &file)?;
+                changed.push(path);
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Writes a statement file, replacing any existing file for the same account
+    pub fn save(&self, file: &StatementFile) -> Result<()> {
+        self.save_to(&self.file_path(&file.account), file)
+    }
+
+    fn save_to(&self, path: &Path, file: &StatementFile) -> Result<()> {
+        file.validate(file_year(path, &file.account))
+            .with_context(|| format!("Not writing invalid statement file {:?}", path))?;
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(path, file.to_yaml()?)?;
+        Ok(())
+    }
+
+    fn file_path(&self, account: &str) -> PathBuf {
+        self.dir.join(format!("{}.yml", account))
+    }
+
+    fn year_file_path(&self, account: &str, year: i32) -> PathBuf {
+        self.dir.join(format!("{}.{}.yml", account, year))
+    }
+}
+
+/// Reads, checks and validates one statement file, naming it in any error
+fn load_statement_file(path: &Path, context: &ParseContext) -> Result<StatementFile> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Can't read statement file {:?}", path))?;
+    let value: serde_yaml::Value = serde_yaml::from_str(&contents)
+        .with_context(|| format!("Invalid statement file {:?}", path))?;
+    let mut file = StatementFile::from_yaml(value, context, &path.display().to_string())?;
+    file.validate(file_year(path, &file.account))
+        .with_context(|| format!("Invalid statement file {:?}", path))?;
+    file.resolve_currency();
+    Ok(file)
+}
+
+/// The year of a yearly statement file, from its name `<account handle>.<year>.yml`
+fn file_year(path: &Path, account: &str) -> Option<i32> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix(account)?
+        .strip_prefix('.')?
+        .strip_suffix(".yml")?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const STATEMENT: &str = "date,amount\n2023-01-31,1000.00\n2023-02-28,1250.50\n";
+
+    #[test]
+    fn test_import_records_provenance() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let statement_path = temp_dir.path().join("january.csv");
+        fs::write(&statement_path, STATEMENT)?;
+
+        let store = StatementStore::new(temp_dir.path());
+        let import = store
+            .import(
+                "checking",
+                &statement_path,
+                None,
+                None,
+                &ParseContext::default(),
+            )?
+            .import;
+
+        assert_eq!(import.provenance.source_file, "january.csv");
+        // SHA-256 of the raw statement bytes
+        assert_eq!(
+            import.provenance.sha256,
+            hex::encode(Sha256::digest(STATEMENT.as_bytes()))
+        );
+        assert_eq!(import.balances.len(), 2);
+        assert_eq!(import.balances[1].amount, dec!(1250.50));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_imports_are_persisted() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let statement_path = temp_dir.path().join("january.csv");
+        fs::write(&statement_path, STATEMENT)?;
+
+        let store = StatementStore::new(temp_dir.path());
+        let first = store
+            .import(
+                "checking",
+                &statement_path,
+                None,
+                None,
+                &ParseContext::default(),
+            )?
+            .import;
+        store.import(
+            "savings",
+            &statement_path,
+            None,
+            None,
+            &ParseContext::default(),
+        )?;
+
+        let files = store.load_all(&ParseContext::default())?;
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].account, "checking");
+        assert_eq!(files[0].imports, vec![first]);
+        assert_eq!(files[1].account, "savings");
+
+        Ok(())
+    }
+
+    const SHA256: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    #[test]
+    fn test_statement_file_round_trip() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let store = StatementStore::new(temp_dir.path());
+        let yaml = format!(
+            "account: girokonto\n\
+             currency: GBP\n\
+             imports:\n\
+             - source_file: export.csv\n  \
+               imported_at: 2024-01-05T10:00:00Z\n  \
+               sha256: {}\n  \
+               balances:\n  \
+               - date: 2023-01-31\n    \
+                 amount: 1000.00\n  \
+               - date: 2023-02-28\n    \
+                 amount: 1250.50\n    \
+                 currency: eur\n",
+            SHA256
+        );
+        fs::create_dir_all(temp_dir.path().join("statements"))?;
+        fs::write(temp_dir.path().join("statements/girokonto.yml"), yaml)?;
+
+        // The file's currency applies to the records without their own
+        let files = store.load_all(&ParseContext::strict())?;
+        let file = &files[0];
+        assert_eq!(file.currency, None);
+        let currencies: Vec<_> = file.imports[0]
+            .balances
+            .iter()
+            .map(|record| record.currency.as_deref())
+            .collect();
+        assert_eq!(currencies, vec![Some("gbp"), Some("eur")]);
+
+        // Written and read back unchanged
+        store.save(file)?;
+        assert_eq!(store.load_all(&ParseContext::strict())?, files);
+        let written = fs::read_to_string(temp_dir.path().join("statements/girokonto.yml"))?;
+        assert_eq!(serde_yaml::from_str::<StatementFile>(&written)?, *file);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_many_files() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path().join("statements");
+        fs::create_dir_all(&dir)?;
+        for index in 0..100 {
+            let mut file = StatementFile::new(&format!("account{:03}", index), Vec::new());
+            if index % 10 == 0 {
+                file.currency = Some("eur".to_string());
+            }
+            fs::write(
+                dir.join(format!("account{:03}.yml", index)),
+                format!("{}extra: true\n", serde_yaml::to_string(&file)?),
+            )?;
+        }
+
+        let context = ParseContext::default();
+        let files = StatementStore::new(temp_dir.path()).load_all(&context)?;
+        assert_eq!(files.len(), 100);
+        assert_eq!(files[42].account, "account042");
+        // Each file's unknown key is reported, in the files' order
+        let warnings = context.warnings();
+        assert_eq!(warnings.len(), 100);
+        assert!(warnings[42].contains("account042.yml"));
+
+        // Every file that fails is named
+        fs::write(dir.join("account007.yml"), "account: [")?;
+        fs::write(
+            dir.join("account070.yml"),
+            "account: account070\ncurrency: \"€\"\n",
+        )?;
+        let err = StatementStore::new(temp_dir.path())
+            .load_all(&ParseContext::default())
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("2 statement files couldn't be loaded"));
+        assert!(err.contains("account007.yml"));
+        assert!(err.contains("account070.yml\": Invalid currency code"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_statement_file() {
+        let import = |date: &str, sha256: &str| StatementImport {
+            provenance: Provenance {
+                source_file: "export.csv".to_string(),
+                imported_at: Utc::now(),
+                sha256: sha256.to_string(),
+                profile: None,
+            },
+            balances: vec![BalanceRecord {
+                date: date.parse().unwrap(),
+                amount: dec!(100),
+                ..Default::default()
+            }],
+        };
+
+        let file = StatementFile::new("girokonto", vec![import("2023-01-31", SHA256)]);
+        assert!(file.validate(None).is_ok());
+        assert!(file.validate(Some(2023)).is_ok());
+        assert!(file
+            .validate(Some(2024))
+            .unwrap_err()
+            .to_string()
+            .contains("is in the 2024 file"));
+
+        let file = StatementFile::new("girokonto", vec![import("2023-01-31", "abc123")]);
+        assert!(file
+            .validate(None)
+            .unwrap_err()
+            .to_string()
+            .contains("invalid sha256"));
+
+        let mut file = StatementFile::new("girokonto", vec![import("2023-01-31", SHA256)]);
+        file.imports[0].balances[0].manual = true;
+        assert!(file
+            .validate(None)
+            .unwrap_err()
+            .to_string()
+            .contains("belong in data.yml"));
+
+        let mut file = StatementFile::new("girokonto", Vec::new());
+        file.currency = Some("€".to_string());
+        assert!(file.validate(None).is_err());
+        assert!(StatementFile::new("../girokonto", Vec::new())
+            .validate(None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_import_detects_format() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let statement_path = temp_dir.path().join("january.qfx");
+        fs::write(
+            &statement_path,
+            "<OFX><LEDGERBAL><BALAMT>99.10<DTASOF>20230131</LEDGERBAL></OFX>",
+        )?;
+
+        let store = StatementStore::new(temp_dir.path());
+        let import = store
+            .import(
+                "checking",
+                &statement_path,
+                None,
+                None,
+                &ParseContext::default(),
+            )?
+            .import;
+        assert_eq!(import.balances[0].amount, dec!(99.10));
+
+        // An explicit format overrides detection, so the OFX markup is read as a CSV header with no rows
+        let result = store.import(
+            "checking",
+            &statement_path,
+            Some("csv"),
+            None,
+            &ParseContext::default(),
+        )?;
+        assert!(result.import.balances.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compressed_store() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let statement_path = temp_dir.path().join("january.csv");
+        fs::write(&statement_path, STATEMENT)?;
+        let store = StatementStore::new(temp_dir.path());
+        let context = ParseContext::default();
+        store.import("checking", &statement_path, None, None, &context)?;
+        let before = store.load_all(&context)?;
+
+        let changed = store.set_compressed(true, &context)?;
+        let path = temp_dir.path().join("statements/checking.yml");
+        assert_eq!(changed, vec![path.clone()]);
+        assert!(fs::read_to_string(&path)?.contains("steps: 0:1000 28:250.5"));
+        let loaded = store.load_all(&context)?;
+        assert!(loaded[0].compressed);
+        assert_eq!(loaded[0].imports, before[0].imports);
+
+        // New imports and normalizing keep the account's files compressed
+        let statement_path = temp_dir.path().join("march.csv");
+        fs::write(&statement_path, "date,amount\n2023-03-31,1300\n")?;
+        store.import("checking", &statement_path, None, None, &context)?;
+        assert!(fs::read_to_string(&path)?.contains("steps: 0:1300"));
+        store.normalize(&context)?;
+        let yearly = temp_dir.path().join("statements/checking.2023.yml");
+        assert!(fs::read_to_string(&yearly)?.contains("steps:"));
+
+        assert_eq!(store.set_compressed(false, &context)?, vec![yearly.clone()]);
+        assert!(!fs::read_to_string(&yearly)?.contains("steps:"));
+        assert_eq!(store.load_all(&context)?[0].imports.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_with_profile() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let statement_path = temp_dir.path().join("pence.csv");
+        fs::write(&statement_path, "date,amount\n2023-01-31,125050\n")?;
+        let profile = ImportProfile {
+            name: "pence".to_string(),
+            divide_by: Some(dec!(100)),
+            decimal_places: Some(2),
+            sheet: None,
+            decimal_separator: None,
+            thousands_separator: None,
+        };
+
+        let store = StatementStore::new(temp_dir.path());
+        let import = store
+            .import(
+                "checking",
+                &statement_path,
+                None,
+                Some(&profile),
+                &ParseContext::default(),
+            )?
+            .import;
+        assert_eq!(import.balances[0].amount, dec!(1250.50));
+        assert_eq!(import.provenance.profile.as_deref(), Some("pence"));
+
+        // The same record already imported in major units is a duplicate once scaled
+        let statement_path = temp_dir.path().join("pounds.csv");
+        fs::write(&statement_path, "date,amount\n2023-01-31,1250.5\n")?;
+        let result = store.import(
+            "checking",
+            &statement_path,
+            None,
+            None,
+            &ParseContext::default(),
+        )?;
+        assert_eq!(result.duplicates.len(), 1);
+
+        // A profile's decimal separator decides amounts the file alone can't
+        let statement_path = temp_dir.path().join("euros.csv");
+        fs::write(&statement_path, "date,amount\n2023-02-28,2.500\n")?;
+        let profile = ImportProfile {
+            name: "euros".to_string(),
+            divide_by: None,
+            decimal_places: None,
+            sheet: None,
+            decimal_separator: Some(','),
+            thousands_separator: None,
+        };
+        let context = ParseContext::default();
+        let import = store
+            .import("checking", &statement_path, None, Some(&profile), &context)?
+            .import;
+        assert_eq!(import.balances[0].amount, dec!(2500));
+        assert!(context.warnings().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reimport_skips_duplicates() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let statement_path = temp_dir.path().join("export.csv");
+        fs::write(&statement_path, STATEMENT)?;
+
+        let store = StatementStore::new(temp_dir.path());
+        let first = store.import(
+            "checking",
+            &statement_path,
+            None,
+            None,
+            &ParseContext::default(),
+        )?;
+        assert!(first.duplicates.is_empty());
+
+        // Re-importing the same file adds nothing
+        let again = store.import(
+            "checking",
+            &statement_path,
+            None,
+            None,
+            &ParseContext::default(),
+        )?;
+        assert!(again.import.balances.is_empty());
+        assert_eq!(again.duplicates.len(), 2);
+
+        // An updated export only adds its new record
+        fs::write(
+            &statement_path,
+            format!("{}2023-03-31,1300.00\n", STATEMENT),
+        )?;
+        let updated = store.import(
+            "checking",
+            &statement_path,
+            None,
+            None,
+            &ParseContext::default(),
+        )?;
+        assert_eq!(updated.import.balances.len(), 1);
+        assert_eq!(updated.duplicates.len(), 2);
+
+        let files = store.load_all(&ParseContext::default())?;
+        assert_eq!(files[0].imports.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_validates_yearly_files() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let statement_path = temp_dir.path().join("export.csv");
+        fs::write(&statement_path, STATEMENT)?;
+        let store = StatementStore::new(temp_dir.path());
+        let context = ParseContext::default();
+        store.import("checking", &statement_path, None, None, &context)?;
+        store.normalize(&context)?;
+
+        // A record in the 2023 file dated in another year fails the import, as it would loading the store
+        let yearly = temp_dir.path().join("statements/checking.2023.yml");
+        fs::write(
+            &yearly,
+            fs::read_to_string(&yearly)?.replace("2023-02-28", "2024-02-28"),
+        )?;
+        let err = store
+            .import("checking", &statement_path, None, None, &context)
+            .unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("Invalid statement file"),
+            "{:#}",
+            err
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_preview_writes_nothing() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let statement_path = temp_dir.path().join("export.csv");
+        fs::write(&statement_path, STATEMENT)?;
+
+        let store = StatementStore::new(temp_dir.path());
+        let preview = store.preview(
+            "checking",
+            &statement_path,
+            None,
+            None,
+            &ParseContext::default(),
+        )?;
+        assert_eq!(preview.import.balances.len(), 2);
+        assert_eq!(preview.format, "csv");
+        assert!(store.load_all(&ParseContext::default())?.is_empty());
+
+        // Previewing after an import shows the records it would skip
+        store.import(
+            "checking",
+            &statement_path,
+            None,
+            None,
+            &ParseContext::default(),
+        )?;
+        let preview = store.preview(
+            "checking",
+            &statement_path,
+            None,
+            None,
+            &ParseContext::default(),
+        )?;
+        assert!(preview.import.balances.is_empty());
+        assert_eq!(preview.duplicates[0].amount, dec!(1000.00));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_invalid_statement() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let statement_path = temp_dir.path().join("broken.csv");
+        fs::write(&statement_path, "date,amount\nnot-a-date,12\n")?;
+
+        let store = StatementStore::new(temp_dir.path());
+        assert!(store
+            .import(
+                "checking",
+                &statement_path,
+                None,
+                None,
+                &ParseContext::strict()
+            )
+            .is_err());
+        // Nothing is written for a failed import
+        assert!(store.load_all(&ParseContext::default())?.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::path::Path;
+
+use super::importer::StatementImporter;
+use crate::data::{AccountKind, BalanceRecord};
+use crate::diagnostics::codes;
+use crate::parsing::ParseContext;
+
+/// Imports the closing balances from SWIFT MT940 customer statements, as many European banks export them
+pub struct Mt940Importer;
+
+impl StatementImporter for Mt940Importer {
+    fn name(&self) -> &str {
+        "mt940"
+    }
+
+    fn detect(&self, file_name: &str, contents: &[u8]) -> bool {
+        let extension = Path::new(file_name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+        let statement = String::from_utf8_lossy(contents);
+
+        matches!(extension.as_deref(), Some("sta" | "mt940"))
+            || (statement.contains(":25:") && statement.contains(":62F:"))
+    }
+
+    fn parse(
+        &self,
+        contents: &[u8],
+        source: &str,
+        context: &ParseContext,
+    ) -> Result<Vec<BalanceRecord>> {
+        parse_balances(&String::from_utf8_lossy(contents), source, context)
+    }
+
+    /// MT940 statements are only sent for bank accounts
+    fn account_kind(&self, _contents: &[u8]) -> Option<AccountKind> {
+        Some(AccountKind::Bank)
+    }
+}
+
+/// Reads every closing balance in the statement, final (`:62F:`) or intermediate (`:62M:`), as a balance record
+/// dated by its booking date
+pub fn parse_balances(
+    statement: &str,
+    source: &str,
+    context: &ParseContext,
+) -> Result<Vec<BalanceRecord>> {
+    let mut balances = Vec::new();
+
+    for (tag, value) in fields(statement) {
+        if tag != "62F" && tag != "62M" {
+            continue;
+        }
+        // D or C, then YYMMDD, a 3-letter currency and the amount with a decimal comma, e.g. C230131EUR1250,50
+        let (sign, rest) = match value.split_at_checked(1) {
+            Some(("D", rest)) => (Decimal::NEGATIVE_ONE, rest),
+            Some(("C", rest)) => (Decimal::ONE, rest),
+            _ => anyhow::bail!("Invalid closing balance {:?} in {}", value, source),
+        };
+        let (date, rest) = rest
+            .split_at_checked(6)
+            .with_context(|| format!("Invalid closing balance {:?} in {}", value, source))?;
+        let amount = rest.get(3..).unwrap_or_default().replace(',', ".");
+        let amount: Decimal = amount
+            .parse()
+            .with_context(|| format!("Invalid balance amount {:?} in {}", amount, source))?;
+
+        match NaiveDate::parse_from_str(date, "%y%m%d") {
+            Ok(date) => balances.push(BalanceRecord {
+                date,
+                amount: sign * amount,
+                ..Default::default()
+            }),
+            Err(_) => context.issue_at(
+                codes::INVALID_DATE,
+                source,
+                format!(
+                    "Unparseable date {:?} in {}, skipping balance",
+                    date, source
+                ),
+            )?,
+        }
+    }
+
+    Ok(balances)
+}
+
+/// The account the statement is for, from its `:25:` field: an IBAN, or an account number after the bank's code
+pub fn account_id(statement: &str) -> Option<&str> {
+    let account = account_field(statement)?;
+    let id = account.rsplit_once('/').map_or(account, |(_, id)| id);
+    Some(id.trim()).filter(|id| !id.is_empty())
+}
+
+/// The bank holding the account: the bank code before the account number in the `:25:` field, or failing that the
+/// BIC of the logical terminal in the message's basic header block, e.g. `{1:F01DEUTDEFFAXXX...}`
+pub fn bank_id(statement: &str) -> Option<String> {
+    if let Some((bank, _)) = account_field(statement).and_then(|account| account.split_once('/')) {
+        return Some(bank.trim().to_string()).filter(|bank| !bank.is_empty());
+    }
+    // The terminal address is the 8-character BIC, a terminal code and a 3-character branch
+    let (_, header) = statement.split_once("{1:F01")?;
+    let terminal = header.get(..12)?;
+    Some(format!("{}{}", &terminal[..8], &terminal[9..]))
+}
+
+fn account_field(statement: &str) -> Option<&str> {
+    fields(statement).find_map(|(tag, value)| (tag == "25").then_some(value))
+}
+
+/// The statement's fields as tag and first line of value, e.g. `("62F", "C230131EUR1250,50")`
+fn fields(statement: &str) -> impl Iterator<Item = (&str, &str)> {
+    statement.lines().filter_map(|line| {
+        let (tag, value) = line.trim_start().strip_prefix(':')?.split_once(':')?;
+        tag.chars()
+            .all(|c| c.is_ascii_alphanumeric())
+            .then(|| (tag, value.trim()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    const STATEMENT: &str = "{1:F01DEUTDEFFAXXX0000000000}{2:I940DEUTDEFFXXXXN}{4:
+:20:STMT2301
+:25:DEUTDEFF/0532013000
+:28C:1/1
+:60F:C221231EUR1000,00
+:61:2301150115D250,00NTRFNONREF
+:86:Rent
+:62M:C230115EUR750,00
+:60M:C230115EUR750,00
+:62F:D230131EUR12,5
+-}";
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let balances = parse_balances(STATEMENT, "jan.sta", &ParseContext::default())?;
+
+        assert_eq!(balances.len(), 2);
+        assert_eq!(
+            balances[0].date,
+            NaiveDate::from_ymd_opt(2023, 1, 15).unwrap()
+        );
+        assert_eq!(balances[0].amount, dec!(750.00));
+        assert_eq!(
+            balances[1].date,
+            NaiveDate::from_ymd_opt(2023, 1, 31).unwrap()
+        );
+        assert_eq!(balances[1].amount, dec!(-12.5));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unparseable_date() -> Result<()> {
+        let statement = ":25:DE89370400440532013000\n:62F:C231399EUR1,00";
+
+        let lenient = ParseContext::default();
+        assert!(parse_balances(statement, "jan.sta", &lenient)?.is_empty());
+        assert_eq!(lenient.warnings().len(), 1);
+        assert!(parse_balances(statement, "jan.sta", &ParseContext::strict()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_account_and_bank() {
+        assert_eq!(account_id(STATEMENT), Some("0532013000"));
+        assert_eq!(bank_id(STATEMENT).as_deref(), Some("DEUTDEFF"));
+
+        // Without a bank code the BIC comes from the header
+        let statement = STATEMENT.replace(":25:DEUTDEFF/0532013000", ":25:DE89370400440532013000");
+        assert_eq!(account_id(&statement), Some("DE89370400440532013000"));
+        assert_eq!(bank_id(&statement).as_deref(), Some("DEUTDEFFXXX"));
+
+        assert!(Mt940Importer.detect("jan.sta", b""));
+        assert!(Mt940Importer.detect("jan.txt", STATEMENT.as_bytes()));
+        assert!(!Mt940Importer.detect("jan.txt", b"date,amount"));
+    }
+}
@@ -0,0 +1,218 @@
+use anyhow::Result;
+use chrono::Datelike;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use super::{StatementFile, StatementImport, StatementStore};
+use crate::data::BalanceRecord;
+use crate::parsing::ParseContext;
+
+/// What normalizing an account's statements changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct NormalizeResult {
+    pub account: String,
+    /// Years the account now has a statement file for
+    pub years: Vec<i32>,
+    /// Balance records kept
+    pub records: usize,
+    /// Records dropped because an earlier import already had the same date, amount and currency
+    pub duplicates_removed: usize,
+    /// Imports of the same statement (by checksum and import profile) folded into their first import
+    pub imports_merged: usize,
+    /// Imports dropped because none of their records were left
+    pub imports_removed: usize,
+    /// Files replaced by the yearly files, e.g. the account's unsplit `<account handle>.yml`
+    pub files_removed: Vec<PathBuf>,
+}
+
+impl StatementStore {
+    /// Rewrites every account's statements as one file per year, `statements/<account handle>.<year>.yml`.
+    ///
+    /// Imports are kept in the order they were made, each holding its records for the year sorted by date and
+    /// currency. Records an earlier import already has are dropped, repeat imports of the same statement are folded
+    /// into the first, and imports left without records are dropped. Running it again changes nothing. An account's
+    /// yearly files are compressed if any of the files they replace was.
+    pub fn normalize(&self, context: &ParseContext) -> Result<Vec<NormalizeResult>> {
+        let _span = tracing::info_span!("normalize").entered();
+        let mut by_account: BTreeMap<String, Vec<(PathBuf, StatementFile)>> = BTreeMap::new();
+        for (path, file) in self.load_files(context)? {
+            by_account
+                .entry(file.account.clone())
+                .or_default()
+                .push((path, file));
+        }
+
+        let mut results = Vec::new();
+        for (account, files) in by_account {
+            let (paths, files): (Vec<PathBuf>, Vec<StatementFile>) = files.into_iter().unzip();
+            let compressed = files.iter().any(|file| file.compressed);
+            let mut imports: Vec<StatementImport> =
+                files.into_iter().flat_map(|file| file.imports).collect();
+            imports.sort_by_key(|import| import.provenance.imported_at);
+
+            let mut result = NormalizeResult {
+                account: account.clone(),
+                years: Vec::new(),
+                records: 0,
+                duplicates_removed: 0,
+                imports_merged: 0,
+                imports_removed: 0,
+                files_removed: Vec::new(),
+            };
+
+            let mut by_year: BTreeMap<i32, Vec<StatementImport>> = BTreeMap::new();
+            for import in imports {
+                let mut kept: Vec<&BalanceRecord> = Vec::new();
+                for record in &import.balances {
+                    let already_present = by_year
+                        .get(&record.date.year())
+                        .into_iter()
+                        .flatten()
+                        .flat_map(|import| import.balances.iter())
+                        .chain(kept.iter().copied())
+                        .any(|existing| existing == record);
+                    if already_present {
+                        result.duplicates_removed += 1;
+                    } else {
+                        kept.push(record);
+                    }
+                }
+                if kept.is_empty() {
+                    result.imports_removed += 1;
+                    continue;
+                }
+
+                let mut years: BTreeMap<i32, Vec<BalanceRecord>> = BTreeMap::new();
+                for record in kept {
+                    years
+                        .entry(record.date.year())
+                        .or_default()
+                        .push(record.clone());
+                }
+                let mut merged = false;
+                for (year, balances) in years {
+                    let year_imports = by_year.entry(year).or_default();
+                    match year_imports.iter_mut().find(|existing| {
+                        existing.provenance.sha256 == import.provenance.sha256
+                            && existing.provenance.profile == import.provenance.profile
+                    }) {
+                        Some(existing) => {
+                            existing.balances.extend(balances);
+                            merged = true;
+                        }
+                        None => year_imports.push(StatementImport {
+                            provenance: import.provenance.clone(),
+                            balances,
+                        }),
+                    }
+                }
+                if merged {
+                    result.imports_merged += 1;
+                }
+            }
+
+            for (year, mut imports) in by_year {
+                for import in &mut imports {
+                    import.balances.sort_by(|a, b| {
+                        a.date
+                            .cmp(&b.date)
+                            .then_with(|| a.currency.cmp(&b.currency))
+                    });
+                    result.records += import.balances.len();
+                }
+                self.save_to(
+                    &self.year_file_path(&account, year),
+                    &StatementFile {
+                        compressed,
+                        ..StatementFile::new(&account, imports)
+                    },
+                )?;
+                result.years.push(year);
+            }
+
+            // Only once every yearly file is written, so an interrupted run loses nothing
+            for path in paths {
+                let is_yearly = result
+                    .years
+                    .iter()
+                    .any(|year| path == self.year_file_path(&account, *year));
+                if !is_yearly {
+                    std::fs::remove_file(&path)?;
+                    result.files_removed.push(path);
+                }
+            }
+
+            tracing::info!(
+                account,
+                records = result.records,
+                duplicates = result.duplicates_removed,
+                "Normalized statements"
+            );
+            results.push(result);
+        }
+
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_normalize() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let store = StatementStore::new(temp_dir.path());
+        let context = ParseContext::default();
+
+        let statement_path = temp_dir.path().join("export.csv");
+        fs::write(
+            &statement_path,
+            "date,amount\n2023-01-31,1000.00\n2022-12-31,900.00\n",
+        )?;
+        store.import("checking", &statement_path, None, None, &context)?;
+        fs::write(&statement_path, "date,amount\n2023-02-28,1100.00\n")?;
+        store.import("checking", &statement_path, None, None, &context)?;
+
+        // A record hand-copied into a later import, as happens when files are edited directly
+        let mut file = store.load("checking")?.unwrap();
+        let copied = file.imports[0].balances[0].clone();
+        file.imports[1].balances.insert(0, copied);
+        store.save(&file)?;
+
+        let results = store.normalize(&context)?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].years, vec![2022, 2023]);
+        assert_eq!(results[0].records, 3);
+        assert_eq!(results[0].duplicates_removed, 1);
+        assert_eq!(results[0].imports_merged, 0);
+        assert_eq!(
+            results[0].files_removed,
+            vec![temp_dir.path().join("statements").join("checking.yml")]
+        );
+
+        let files = store.load_all(&context)?;
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].imports.len(), 1);
+        assert_eq!(files[1].imports.len(), 2);
+        assert_eq!(
+            files[1].imports[0].balances[0].date.to_string(),
+            "2023-01-31"
+        );
+
+        // Records already in the yearly files are still recognised on import
+        let again = store.import("checking", &statement_path, None, None, &context)?;
+        assert_eq!(again.duplicates.len(), 1);
+
+        // Normalizing again changes nothing
+        let results = store.normalize(&context)?;
+        assert_eq!(results[0].records, 3);
+        assert_eq!(results[0].duplicates_removed, 0);
+        assert!(results[0].files_removed.is_empty());
+        assert_eq!(store.load_all(&context)?, files);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::path::Path;
+
+use super::importer::StatementImporter;
+use super::ledger::Transaction;
+use crate::data::{AccountKind, BalanceRecord};
+use crate::diagnostics::codes;
+use crate::parsing::ParseContext;
+
+/// Imports the ledger balances from OFX/QFX downloads, in either the SGML (1.x) or XML (2.x) flavour
+pub struct OfxImporter;
+
+impl StatementImporter for OfxImporter {
+    fn name(&self) -> &str {
+        "ofx"
+    }
+
+    fn detect(&self, file_name: &str, contents: &[u8]) -> bool {
+        let extension = Path::new(file_name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+
+        matches!(extension.as_deref(), Some("ofx" | "qfx"))
+            || contents.starts_with(b"OFXHEADER")
+            || String::from_utf8_lossy(contents).contains("<OFX>")
+    }
+
+    fn parse(
+        &self,
+        contents: &[u8],
+        source: &str,
+        context: &ParseContext,
+    ) -> Result<Vec<BalanceRecord>> {
+        parse_balances(&String::from_utf8_lossy(contents), source, context)
+    }
+
+    /// Investment statements are for securities accounts; bank and credit card statements for bank accounts
+    fn account_kind(&self, contents: &[u8]) -> Option<AccountKind> {
+        let statement = String::from_utf8_lossy(contents);
+        if statement.contains("<INVACCTFROM>") {
+            Some(AccountKind::Securities)
+        } else if statement.contains("<BANKACCTFROM>") || statement.contains("<CCACCTFROM>") {
+            Some(AccountKind::Bank)
+        } else {
+            None
+        }
+    }
+}
+
+/// Reads every `<LEDGERBAL>` aggregate in the statement as a balance record dated by its `<DTASOF>`
+pub fn parse_balances(
+    statement: &str,
+    source: &str,
+    context: &ParseContext,
+) -> Result<Vec<BalanceRecord>> {
+    let mut balances = Vec::new();
+
+    for aggregate in statement.split("<LEDGERBAL>").skip(1) {
+        let aggregate = aggregate.split("</LEDGERBAL>").next().unwrap_or(aggregate);
+
+        let amount = tag_value(aggregate, "BALAMT")
+            .with_context(|| format!("Ledger balance without <BALAMT> in {}", source))?;
+        let amount: Decimal = amount
+            .parse()
+            .with_context(|| format!("Invalid balance amount {:?} in {}", amount, source))?;
+
+        let date = tag_value(aggregate, "DTASOF")
+            .with_context(|| format!("Ledger balance without <DTASOF> in {}", source))?;
+        match parse_ofx_date(date) {
+            Some(date) => balances.push(BalanceRecord {
+                date,
+                amount,
+                ..Default::default()
+            }),
+            None => context.issue_at(
+                codes::INVALID_DATE,
+                source,
+                format!(
+                    "Unparseable date {:?} in {}, skipping balance",
+                    date, source
+                ),
+            )?,
+        }
+    }
+
+    Ok(balances)
+}
+
+/// Reads every `<STMTTRN>` in the statement, with the payee's name, the memo and any account the money went to
+pub fn parse_transactions(
+    statement: &str,
+    source: &str,
+    context: &ParseContext,
+) -> Result<Vec<Transaction>> {
+    let mut transactions = Vec::new();
+
+    for aggregate in statement.split("<STMTTRN>").skip(1) {
+        let aggregate = aggregate.split("</STMTTRN>").next().unwrap_or(aggregate);
+
+        let amount = tag_value(aggregate, "TRNAMT")
+            .with_context(|| format!("Transaction without <TRNAMT> in {}", source))?;
+        let amount: Decimal = amount
+            .parse()
+            .with_context(|| format!("Invalid transaction amount {:?} in {}", amount, source))?;
+
+        let date = tag_value(aggregate, "DTPOSTED")
+            .with_context(|| format!("Transaction without <DTPOSTED> in {}", source))?;
+        let Some(date) = parse_ofx_date(date) else {
+            context.issue_at(
+                codes::INVALID_DATE,
+                source,
+                format!(
+                    "Unparseable date {:?} in {}, skipping transaction",
+                    date, source
+                ),
+            )?;
+            continue;
+        };
+
+        let text = |tag: &str| {
+            tag_value(aggregate, tag)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let counterparty_account = aggregate
+            .split_once("<BANKACCTTO>")
+            .and_then(|(_, account)| tag_value(account, "ACCTID"))
+            .map(str::to_string);
+        transactions.push(Transaction {
+            date,
+            amount,
+            counterparty_name: text("NAME").or_else(|| text("PAYEE")),
+            counterparty_account,
+            description: text("MEMO"),
+        });
+    }
+
+    Ok(transactions)
+}
+
+/// The ID of the account the statement is for
+pub fn account_id(statement: &str) -> Option<&str> {
+    statement_account(statement)
+        .and_then(|account| tag_value(account, "ACCTID"))
+        .filter(|id| !id.is_empty())
+}
+
+/// The ID of the bank holding the account the statement is for: a routing number for US banks, and often a BIC or
+/// national bank code elsewhere. Credit card statements don't give one.
+pub fn bank_id(statement: &str) -> Option<&str> {
+    let (_, account) = statement.split_once("<BANKACCTFROM>")?;
+    tag_value(account, "BANKID").filter(|id| !id.is_empty())
+}
+
+/// The statement from its account aggregate on
+fn statement_account(statement: &str) -> Option<&str> {
+    statement
+        .split_once("<BANKACCTFROM>")
+        .or_else(|| statement.split_once("<CCACCTFROM>"))
+        .map(|(_, account)| account)
+}
+
+/// OFX dates are YYYYMMDD, optionally followed by a time and timezone
+fn parse_ofx_date(value: &str) -> Option<NaiveDate> {
+    value
+        .get(..8)
+        .and_then(|day| NaiveDate::parse_from_str(day, "%Y%m%d").ok())
+}
+
+/// Value of an element, which in SGML OFX runs to the next tag or line end as closing tags are optional
+fn tag_value<'a>(aggregate: &'a str, tag: &str) -> Option<&'a str> {
+    let start = aggregate.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let rest = &aggregate[start..];
+    let end = rest.find(['<', '\n', '\r']).unwrap_or(rest.len());
+
+    Some(rest[..end].trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    const SGML_STATEMENT: &str = "OFXHEADER:100
+DATA:OFXSGML
+
+<OFX>
+<BANKMSGSRSV1><STMTTRNRS><STMTRS>
+<CURDEF>GBP
+<BANKACCTFROM>
+<BANKID>123456
+<ACCTID>12345678
+<ACCTTYPE>CHECKING
+</BANKACCTFROM>
+<BANKTRANLIST>
+<STMTTRN>
+<TRNTYPE>XFER
+<DTPOSTED>20230115
+<TRNAMT>-500.00
+<NAME>J DOE SAVINGS
+<MEMO>Monthly savings
+<BANKACCTTO>
+<BANKID>654321
+<ACCTID>87654321
+<ACCTTYPE>SAVINGS
+</BANKACCTTO>
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>CREDIT
+<DTPOSTED>20230128
+<TRNAMT>2000.00
+<NAME>ACME LTD
+</STMTTRN>
+</BANKTRANLIST>
+<LEDGERBAL>
+<BALAMT>1250.50
+<DTASOF>20230131120000[0:GMT]
+</LEDGERBAL>
+<AVAILBAL>
+<BALAMT>1200.00
+<DTASOF>20230131
+</AVAILBAL>
+</STMTRS></STMTTRNRS></BANKMSGSRSV1>
+</OFX>
+";
+
+    #[test]
+    fn test_parse_sgml() -> Result<()> {
+        let balances = parse_balances(SGML_STATEMENT, "jan.ofx", &ParseContext::default())?;
+
+        assert_eq!(balances.len(), 1);
+        assert_eq!(
+            balances[0].date,
+            NaiveDate::from_ymd_opt(2023, 1, 31).unwrap()
+        );
+        assert_eq!(balances[0].amount, dec!(1250.50));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_xml() -> Result<()> {
+        let statement = "<?xml version=\"1.0\"?><OFX><LEDGERBAL><BALAMT>-12.00</BALAMT><DTASOF>20230228</DTASOF></LEDGERBAL></OFX>";
+        let balances = parse_balances(statement, "feb.ofx", &ParseContext::default())?;
+
+        assert_eq!(balances.len(), 1);
+        assert_eq!(balances[0].amount, dec!(-12.00));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unparseable_date() -> Result<()> {
+        let statement = "<OFX><LEDGERBAL><BALAMT>1<DTASOF>2023-01-31</LEDGERBAL></OFX>";
+
+        let lenient = ParseContext::default();
+        assert!(parse_balances(statement, "jan.ofx", &lenient)?.is_empty());
+        assert_eq!(lenient.warnings().len(), 1);
+
+        assert!(parse_balances(statement, "jan.ofx", &ParseContext::strict()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_transactions() -> Result<()> {
+        let transactions = parse_transactions(SGML_STATEMENT, "jan.ofx", &ParseContext::default())?;
+
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].amount, dec!(-500.00));
+        assert_eq!(
+            transactions[0].counterparty_name.as_deref(),
+            Some("J DOE SAVINGS")
+        );
+        assert_eq!(
+            transactions[0].counterparty_account.as_deref(),
+            Some("87654321")
+        );
+        assert_eq!(
+            transactions[0].description.as_deref(),
+            Some("Monthly savings")
+        );
+        assert_eq!(transactions[1].counterparty_account, None);
+        assert_eq!(account_id(SGML_STATEMENT), Some("12345678"));
+        assert_eq!(bank_id(SGML_STATEMENT), Some("123456"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect() {
+        assert!(OfxImporter.detect("jan.QFX", b""));
+        assert!(OfxImporter.detect("download", SGML_STATEMENT.as_bytes()));
+        assert!(!OfxImporter.detect("jan.csv", b"date,amount\n"));
+    }
+}
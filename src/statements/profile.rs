@@ -0,0 +1,220 @@
+use anyhow::{bail, Context, Result};
+use rust_decimal::{Decimal, RoundingStrategy};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::data::BalanceRecord;
+use crate::parsing::NumberFormat;
+
+/// How to normalize the amounts in a bank's exports as they're imported, so records land in the store in the
+/// account's currency units with a consistent precision
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct ImportProfile {
+    pub name: String,
+    /// Divide amounts by this, e.g. 100 for exports in minor units like pence or cents
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub divide_by: Option<Decimal>,
+    /// Round amounts to this many decimal places, with halves rounded away from zero, e.g. 2 for exports with
+    /// four or more
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decimal_places: Option<u32>,
+    /// Sheet to read Excel statements from, when the statement isn't on the first sheet with a header row
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sheet: Option<String>,
+    /// Decimal separator of the exports' amounts, `.` or `,`, for banks writing amounts like `1.234,56`. Detected
+    /// from the amounts when neither separator is given, with a warning for amounts like `1.234` that could be read
+    /// either way.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decimal_separator: Option<char>,
+    /// Separator between groups of three digits, when it isn't the other of `.` and `,`, e.g. a space
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thousands_separator: Option<char>,
+}
+
+impl ImportProfile {
+    /// Scales and then rounds each record's amount
+    pub fn apply(&self, records: Vec<BalanceRecord>) -> Vec<BalanceRecord> {
+        records
+            .into_iter()
+            .map(|mut record| {
+                if let Some(divisor) = self.divide_by {
+                    record.amount /= divisor;
+                }
+                if let Some(places) = self.decimal_places {
+                    record.amount = record
+                        .amount
+                        .round_dp_with_strategy(places, RoundingStrategy::MidpointAwayFromZero);
+                }
+                record.amount = record.amount.normalize();
+                record
+            })
+            .collect()
+    }
+
+    /// The format the profile's exports write amounts in, if it gives either separator
+    pub fn number_format(&self) -> Result<Option<NumberFormat>> {
+        if self.decimal_separator.is_none() && self.thousands_separator.is_none() {
+            return Ok(None);
+        }
+        NumberFormat::new(self.decimal_separator, self.thousands_separator)
+            .map(Some)
+            .map_err(|err| anyhow::anyhow!("Import profile {}: {}", self.name, err))
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        self.number_format()?;
+        if self
+            .divide_by
+            .is_some_and(|divisor| divisor <= Decimal::ZERO)
+        {
+            bail!(
+                "Import profile {} has divide_by {}, which must be positive",
+                self.name,
+                self.divide_by.unwrap_or_default()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The import profile to use for an account: the one named, or else the one the account names in `data.yml`.
+///
+/// Only the profiles and the accounts' choices are read from `data.yml`, so statements can be imported before the
+/// rest of the file is complete. Fails if a profile is named that `data.yml` doesn't define.
+pub fn profile_for(
+    base_path: &Path,
+    account: &str,
+    name: Option<&str>,
+) -> Result<Option<ImportProfile>> {
+    #[derive(Deserialize, Default)]
+    struct Settings {
+        #[serde(default)]
+        import_profiles: Vec<ImportProfile>,
+        #[serde(default)]
+        accounts: Vec<AccountSettings>,
+    }
+
+    #[derive(Deserialize)]
+    struct AccountSettings {
+        handle: String,
+        #[serde(default)]
+        import_profile: Option<String>,
+    }
+
+    let path = base_path.join("data.yml");
+    let settings: Settings = if path.exists() {
+        let contents = std::fs::read_to_string(&path)?;
+        serde_yaml::from_str(&contents).with_context(|| format!("Invalid {:?}", path))?
+    } else {
+        Settings::default()
+    };
+
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => match settings
+            .accounts
+            .into_iter()
+            .find(|settings| settings.handle == account)
+            .and_then(|settings| settings.import_profile)
+        {
+            Some(name) => name,
+            None => return Ok(None),
+        },
+    };
+
+    match settings
+        .import_profiles
+        .into_iter()
+        .find(|profile| profile.name == name)
+    {
+        Some(profile) => {
+            profile.validate()?;
+            Ok(Some(profile))
+        }
+        None => bail!(
+            "Unknown import profile {:?}; define it under import_profiles in data.yml",
+            name
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn record(amount: Decimal) -> BalanceRecord {
+        BalanceRecord {
+            date: NaiveDate::from_ymd_opt(2023, 1, 31).unwrap(),
+            amount,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let profile = ImportProfile {
+            name: "pence".to_string(),
+            divide_by: Some(dec!(100)),
+            decimal_places: None,
+            sheet: None,
+            decimal_separator: None,
+            thousands_separator: None,
+        };
+        let amounts: Vec<Decimal> = profile
+            .apply(vec![record(dec!(125050)), record(dec!(-7))])
+            .into_iter()
+            .map(|record| record.amount)
+            .collect();
+        assert_eq!(amounts, vec![dec!(1250.50), dec!(-0.07)]);
+
+        let profile = ImportProfile {
+            name: "precise".to_string(),
+            divide_by: None,
+            decimal_places: Some(2),
+            sheet: None,
+            decimal_separator: None,
+            thousands_separator: None,
+        };
+        let amounts: Vec<Decimal> = profile
+            .apply(vec![record(dec!(1250.5049)), record(dec!(0.125))])
+            .into_iter()
+            .map(|record| record.amount)
+            .collect();
+        assert_eq!(amounts, vec![dec!(1250.50), dec!(0.13)]);
+    }
+
+    #[test]
+    fn test_profile_for() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        assert_eq!(profile_for(temp_dir.path(), "checking", None)?, None);
+
+        fs::write(
+            temp_dir.path().join("data.yml"),
+            r#"
+import_profiles:
+  - name: pence
+    divide_by: 100
+  - name: broken
+    divide_by: 0
+accounts:
+  - handle: checking
+    import_profile: pence
+  - handle: savings
+"#,
+        )?;
+
+        let profile = profile_for(temp_dir.path(), "checking", None)?.unwrap();
+        assert_eq!(profile.divide_by, Some(dec!(100)));
+        assert_eq!(profile_for(temp_dir.path(), "savings", None)?, None);
+        assert!(profile_for(temp_dir.path(), "savings", Some("pence"))?.is_some());
+        assert!(profile_for(temp_dir.path(), "savings", Some("cents")).is_err());
+        assert!(profile_for(temp_dir.path(), "savings", Some("broken")).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+//! A compact form for the balance records of a statement import, for stores of daily balances across many accounts
+//! and years. The records are written as steps from one to the next, with runs of equal steps, e.g. a balance that
+//! stays the same for a month, written once:
+//!
+//! ```yaml
+//! balances:
+//!   start: 2023-01-01
+//!   steps: 0:1000 1:0*30 1:-12.5
+//! ```
+//!
+//! Each step is the days since the previous record and the change in amount, followed by `*` and how many times it
+//! repeats. The first is from `start` and zero. Files are still YAML, and are read the same in either form.
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{Days, NaiveDate};
+use rust_decimal::Decimal;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::str::FromStr;
+
+use crate::data::BalanceRecord;
+
+/// Records as a statement file stores them: listed, or as a series of steps
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub(crate) enum StoredBalances {
+    Records(Vec<BalanceRecord>),
+    Series(BalanceSeries),
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct BalanceSeries {
+    pub start: NaiveDate,
+    /// Currency of every record, when it isn't the account's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    /// `<days>:<change>[*<repeats>]` for each run of records, separated by spaces
+    pub steps: String,
+}
+
+impl BalanceSeries {
+    /// The series for the records, if they can be written as one: plain records in date order, all in the same
+    /// currency, without `usd_value`, `usd_rate` or a note
+    pub fn compress(records: &[BalanceRecord]) -> Option<Self> {
+        let first = records.first()?;
+        let plain = records.iter().all(|record| {
+            record.currency == first.currency
+                && !record.manual
+                && record.note.is_none()
+                && record.usd_value.is_none()
+                && record.usd_rate.is_none()
+        });
+        if !plain || records.windows(2).any(|pair| pair[1].date < pair[0].date) {
+            return None;
+        }
+
+        let mut runs: Vec<(u64, Decimal, usize)> = Vec::new();
+        let mut previous = (first.date, Decimal::ZERO);
+        for record in records {
+            let days = (record.date - previous.0).num_days() as u64;
+            let change = record.amount - previous.1;
+            match runs.last_mut() {
+                Some((run_days, run_change, count))
+                    if *run_days == days && *run_change == change =>
+                {
+                    *count += 1
+                }
+                _ => runs.push((days, change, 1)),
+            }
+            previous = (record.date, record.amount);
+        }
+
+        let steps: Vec<String> = runs
+            .into_iter()
+            .map(|(days, change, count)| match count {
+                1 => format!("{}:{}", days, change.normalize()),
+                _ => format!("{}:{}*{}", days, change.normalize(), count),
+            })
+            .collect();
+        let series = Self {
+            start: first.date,
+            currency: first.currency.clone(),
+            steps: steps.join(" "),
+        };
+        // Only a series that reads back as the same records is used
+        (series.records().ok()? == records).then_some(series)
+    }
+
+    /// The records the series stands for
+    pub fn records(&self) -> Result<Vec<BalanceRecord>> {
+        let mut records = Vec::new();
+        let mut date = self.start;
+        let mut amount = Decimal::ZERO;
+        for step in self.steps.split_whitespace() {
+            let (step, count) = match step.split_once('*') {
+                Some((step, count)) => {
+                    (step, count.parse::<usize>().ok().filter(|count| *count > 0))
+                }
+                None => (step, Some(1)),
+            };
+            let parsed = step.split_once(':').and_then(|(days, change)| {
+                Some((days.parse::<u64>().ok()?, Decimal::from_str(change).ok()?))
+            });
+            let (Some((days, change)), Some(count)) = (parsed, count) else {
+                bail!(
+                    "Invalid step {:?} in balance series from {}",
+                    step,
+                    self.start
+                );
+            };
+            for _ in 0..count {
+                date = date.checked_add_days(Days::new(days)).ok_or_else(|| {
+                    anyhow!("Balance series from {} runs past the last date", self.start)
+                })?;
+                amount += change;
+                records.push(BalanceRecord {
+                    date,
+                    amount,
+                    currency: self.currency.clone(),
+                    ..Default::default()
+                });
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// Reads an import's `balances` in either form
+pub(crate) fn deserialize_balances<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<BalanceRecord>, D::Error> {
+    match StoredBalances::deserialize(deserializer)? {
+        StoredBalances::Records(records) => Ok(records),
+        StoredBalances::Series(series) => series.records().map_err(serde::de::Error::custom),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn record(date: &str, amount: Decimal) -> BalanceRecord {
+        BalanceRecord {
+            date: date.parse().unwrap(),
+            amount,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_compress() -> Result<()> {
+        let mut records: Vec<BalanceRecord> = (1..=31)
+            .map(|day| record(&format!("2023-01-{:02}", day), dec!(1000.00)))
+            .collect();
+        records.push(record("2023-02-01", dec!(987.50)));
+        records.push(record("2023-02-04", dec!(1087.50)));
+
+        let series = BalanceSeries::compress(&records).unwrap();
+        assert_eq!(series.start, "2023-01-01".parse()?);
+        assert_eq!(series.steps, "0:1000 1:0*30 1:-12.5 3:100");
+        assert_eq!(series.records()?, records);
+
+        // Records in other currencies, or with their own USD values, stay listed
+        records[3].currency = Some("gbp".to_string());
+        assert_eq!(BalanceSeries::compress(&records), None);
+        records[3].currency = None;
+        records[3].usd_value = Some(dec!(1100));
+        assert_eq!(BalanceSeries::compress(&records), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_steps() {
+        let series = BalanceSeries {
+            start: "2023-01-01".parse().unwrap(),
+            currency: None,
+            steps: "0:1000 1:x".to_string(),
+        };
+        assert!(series.records().is_err());
+    }
+}
@@ -0,0 +1,125 @@
+//! Balances kept in a shared Google Sheet, e.g. by a couple tracking their accounts together, pulled into the
+//! statement store like any imported statement. Which sheet and range holds each account's balances is configured
+//! under `google_sheets` in `data.yml`; pulling them needs the `google-sheets` feature.
+
+#[cfg(feature = "google-sheets")]
+mod pull;
+#[cfg(feature = "google-sheets")]
+pub use self::pull::{pull, values_to_csv, SheetsClient};
+
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Environment variable naming the service account key file when `data.yml` doesn't, as Google's own tools use
+pub const CREDENTIALS_ENV: &str = "GOOGLE_APPLICATION_CREDENTIALS";
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Default)]
+pub struct GoogleSheets {
+    /// Service account key file (JSON), relative to the data directory. The sheets have to be shared with the
+    /// service account's email address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credentials: Option<PathBuf>,
+    #[serde(default)]
+    pub sources: Vec<SheetSource>,
+}
+
+/// Where an account's balances are kept: a range with a header row of `date` and `amount` (and optionally
+/// `currency`) columns, like a CSV statement
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct SheetSource {
+    /// Handle of the account the balances are for
+    pub account: String,
+    /// The ID in the sheet's URL, between `/d/` and `/edit`
+    pub spreadsheet_id: String,
+    /// A range in A1 notation, including the sheet's name, e.g. `Balances!A1:C`
+    pub range: String,
+}
+
+impl SheetSource {
+    /// Name recorded as the source file of the balances pulled from the sheet
+    pub fn source_name(&self) -> String {
+        format!("google-sheets {} {}", self.spreadsheet_id, self.range)
+    }
+}
+
+impl GoogleSheets {
+    /// The service account key file: the one `data.yml` names, relative to the data directory, or else the one in
+    /// `GOOGLE_APPLICATION_CREDENTIALS`
+    pub fn credentials_path(&self, base_path: &Path) -> Option<PathBuf> {
+        self.credentials
+            .as_ref()
+            .map(|path| base_path.join(path))
+            .or_else(|| std::env::var_os(CREDENTIALS_ENV).map(PathBuf::from))
+    }
+
+    /// The sources for the given accounts, or every source when none are given
+    pub fn sources_for(&self, accounts: &[String]) -> Vec<&SheetSource> {
+        self.sources
+            .iter()
+            .filter(|source| accounts.is_empty() || accounts.contains(&source.account))
+            .collect()
+    }
+}
+
+/// The `google_sheets` settings from `data.yml`, read without loading the rest of the file, like import profiles
+pub fn settings(base_path: &Path) -> Result<GoogleSheets> {
+    #[derive(Deserialize, Default)]
+    struct Settings {
+        #[serde(default)]
+        google_sheets: Option<GoogleSheets>,
+    }
+
+    let path = base_path.join("data.yml");
+    if !path.exists() {
+        return Ok(GoogleSheets::default());
+    }
+    let contents = std::fs::read_to_string(&path)?;
+    let settings: Settings =
+        serde_yaml::from_str(&contents).with_context(|| format!("Invalid {:?}", path))?;
+    Ok(settings.google_sheets.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_settings() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        assert_eq!(settings(temp_dir.path())?, GoogleSheets::default());
+
+        fs::write(
+            temp_dir.path().join("data.yml"),
+            r#"
+providers: []
+google_sheets:
+  credentials: keys/service-account.json
+  sources:
+    - account: joint_savings
+      spreadsheet_id: 1AbCdEf
+      range: "Balances!A1:C"
+    - account: current
+      spreadsheet_id: 1AbCdEf
+      range: "'Current account'!A:B"
+"#,
+        )?;
+        let sheets = settings(temp_dir.path())?;
+        assert_eq!(
+            sheets.credentials_path(temp_dir.path()),
+            Some(temp_dir.path().join("keys/service-account.json"))
+        );
+        assert_eq!(sheets.sources_for(&[]).len(), 2);
+        let sources = sheets.sources_for(&["current".to_string()]);
+        assert_eq!(sources.len(), 1);
+        assert_eq!(
+            sources[0].source_name(),
+            "google-sheets 1AbCdEf 'Current account'!A:B"
+        );
+
+        Ok(())
+    }
+}
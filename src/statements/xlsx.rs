@@ -0,0 +1,467 @@
+use anyhow::{bail, Context, Result};
+use calamine::{Data, DataType, Dimensions, Range, Reader, Xlsx};
+use rust_decimal::Decimal;
+use std::io::Cursor;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::importer::StatementImporter;
+use crate::data::BalanceRecord;
+use crate::parsing::ParseContext;
+
+/// Rows searched for the header before giving up on a sheet, enough for a bank's title and account details
+const HEADER_SEARCH_ROWS: usize = 20;
+
+/// Imports statements exported as Excel workbooks, laid out like CSV statements: a header row with `date` and
+/// `amount` columns and an optional `currency` column.
+///
+/// Title rows above the header are skipped, and a header cell merged across several rows or columns counts in each
+/// of them, so a `Date` merged down beside a two-row header is still found. Without a sheet named, the first sheet
+/// with a header row is read.
+#[derive(Debug, Clone, Default)]
+pub struct XlsxImporter {
+    sheet: Option<String>,
+}
+
+impl XlsxImporter {
+    /// Reads only the named sheet
+    pub fn sheet(name: impl Into<String>) -> Self {
+        Self {
+            sheet: Some(name.into()),
+        }
+    }
+}
+
+impl StatementImporter for XlsxImporter {
+    fn name(&self) -> &str {
+        "xlsx"
+    }
+
+    fn detect(&self, file_name: &str, _contents: &[u8]) -> bool {
+        Path::new(file_name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("xlsx") || ext.eq_ignore_ascii_case("xlsm"))
+    }
+
+    fn parse(
+        &self,
+        contents: &[u8],
+        source: &str,
+        context: &ParseContext,
+    ) -> Result<Vec<BalanceRecord>> {
+        let mut workbook = Xlsx::new(Cursor::new(contents))
+            .with_context(|| format!("Invalid Excel workbook {}", source))?;
+        workbook
+            .load_merged_regions()
+            .with_context(|| format!("Invalid merged cells in {}", source))?;
+
+        let sheet_names = workbook.sheet_names();
+        let sheets = match &self.sheet {
+            Some(name) if sheet_names.contains(name) => vec![name.clone()],
+            Some(name) => bail!(
+                "No sheet {:?} in {} (sheets: {})",
+                name,
+                source,
+                sheet_names.join(", ")
+            ),
+            None => sheet_names,
+        };
+
+        for sheet in &sheets {
+            let range = workbook
+                .worksheet_range(sheet)
+                .with_context(|| format!("Could not read sheet {:?} of {}", sheet, source))?;
+            let merged: Vec<Dimensions> = workbook
+                .merged_regions_by_sheet(sheet)
+                .into_iter()
+                .map(|(_, _, dimensions)| *dimensions)
+                .collect();
+
+            if let Some(header) = find_header(&range, &merged) {
+                let location = format!("{} sheet {:?}", source, sheet);
+                return parse_rows(&range, &header, &location, context);
+            }
+        }
+
+        match &self.sheet {
+            Some(sheet) => bail!(
+                "Sheet {:?} of {} has no header row with date and amount columns",
+                sheet,
+                source
+            ),
+            None => bail!(
+                "No sheet of {} has a header row with date and amount columns",
+                source
+            ),
+        }
+    }
+}
+
+/// Where a sheet's header row is, and the columns it names, as offsets into the sheet's range
+#[derive(Debug, PartialEq)]
+struct Header {
+    row: usize,
+    date: usize,
+    amount: usize,
+    currency: Option<usize>,
+}
+
+/// The first row with `date` and `amount` headings, reading each merged cell's value into every cell it covers
+fn find_header(range: &Range<Data>, merged: &[Dimensions]) -> Option<Header> {
+    let (start_row, start_column) = range.start()?;
+
+    for (row, cells) in range.rows().take(HEADER_SEARCH_ROWS).enumerate() {
+        let heading = |column: usize| {
+            let position = (start_row + row as u32, start_column + column as u32);
+            let cell = match merged
+                .iter()
+                .find(|region| region.contains(position.0, position.1))
+            {
+                Some(region) => range.get_value(region.start).unwrap_or(&cells[column]),
+                None => &cells[column],
+            };
+            cell.get_string().map(|value| value.trim().to_lowercase())
+        };
+        let find =
+            |name: &str| (0..cells.len()).find(|column| heading(*column).as_deref() == Some(name));
+
+        if let (Some(date), Some(amount)) = (find("date"), find("amount")) {
+            return Some(Header {
+                row,
+                date,
+                amount,
+                currency: find("currency"),
+            });
+        }
+    }
+    None
+}
+
+fn parse_rows(
+    range: &Range<Data>,
+    header: &Header,
+    location: &str,
+    context: &ParseContext,
+) -> Result<Vec<BalanceRecord>> {
+    let first_row = range.start().map_or(0, |(row, _)| row as usize);
+    // Number cells need no format; amounts written as text are read like CSV ones
+    let number_format = context.number_format_for(
+        range
+            .rows()
+            .skip(header.row + 1)
+            .filter_map(|cells| cells[header.amount].get_string()),
+    );
+
+    let mut balances = Vec::new();
+    for (index, cells) in range.rows().enumerate().skip(header.row + 1) {
+        let date_cell = &cells[header.date];
+        let amount_cell = &cells[header.amount];
+        if date_cell.is_empty() && amount_cell.is_empty() {
+            continue;
+        }
+        // Rows are numbered from 1, as Excel shows them
+        let location = format!("{} row {}", location, first_row + index + 1);
+
+        let date = match date_cell {
+            Data::DateTime(_) | Data::DateTimeIso(_) => date_cell.as_date(),
+            _ => context.parse_date(&date_cell.to_string(), &location)?,
+        };
+        let Some(date) = date else {
+            continue;
+        };
+
+        let amount = match amount_cell {
+            Data::Int(value) => Some(Decimal::from(*value)),
+            // Written the shortest way that reads back as the same float, so 0.1 stays 0.1
+            Data::Float(value) => Decimal::from_str(&value.to_string()).ok(),
+            Data::String(value) => Some(context.parse_amount(value, number_format, &location)?),
+            _ => None,
+        };
+        let Some(amount) = amount else {
+            bail!(
+                "Invalid amount {:?} in {}",
+                amount_cell.to_string(),
+                location
+            );
+        };
+
+        balances.push(BalanceRecord {
+            date,
+            amount,
+            currency: header
+                .currency
+                .and_then(|column| cells[column].get_string())
+                .map(str::trim)
+                .filter(|currency| !currency.is_empty())
+                .map(str::to_lowercase),
+            ..Default::default()
+        });
+    }
+
+    Ok(balances)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    /// A cell of a test sheet: text, a number, or a number formatted as a date
+    enum Cell {
+        Text(&'static str),
+        Number(f64),
+        Date(u32),
+    }
+
+    /// A test sheet's name, rows of cells, and merged ranges like `A1:C1`
+    type Sheet<'a> = (&'a str, Vec<Vec<Option<Cell>>>, Vec<&'a str>);
+
+    /// Builds a workbook with the given sheets
+    fn workbook(sheets: &[Sheet]) -> Vec<u8> {
+        let mut buffer = Cursor::new(Vec::new());
+        let mut zip = zip::ZipWriter::new(&mut buffer);
+        let options = SimpleFileOptions::default();
+        let mut add = |name: &str, contents: String| {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        };
+
+        let overrides: String = (1..=sheets.len())
+            .map(|n| {
+                format!(
+                    r#"<Override PartName="/xl/worksheets/sheet{n}.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>"#
+                )
+            })
+            .collect();
+        add(
+            "[Content_Types].xml",
+            format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?><Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/><Override PartName="/xl/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml"/>{overrides}</Types>"#
+            ),
+        );
+        add(
+            "_rels/.rels",
+            r#"<?xml version="1.0" encoding="UTF-8"?><Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/></Relationships>"#.to_string(),
+        );
+
+        let sheet_entries: String = sheets
+            .iter()
+            .enumerate()
+            .map(|(index, (name, _, _))| {
+                format!(
+                    r#"<sheet name="{name}" sheetId="{n}" r:id="rId{n}"/>"#,
+                    n = index + 1
+                )
+            })
+            .collect();
+        add(
+            "xl/workbook.xml",
+            format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?><workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets>{sheet_entries}</sheets></workbook>"#
+            ),
+        );
+        let relationships: String = (1..=sheets.len())
+            .map(|n| {
+                format!(
+                    r#"<Relationship Id="rId{n}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet{n}.xml"/>"#
+                )
+            })
+            .collect();
+        add(
+            "xl/_rels/workbook.xml.rels",
+            format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?><Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">{relationships}<Relationship Id="rIdStyles" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/></Relationships>"#
+            ),
+        );
+        // Style 1 is the built-in short date format
+        add(
+            "xl/styles.xml",
+            r#"<?xml version="1.0" encoding="UTF-8"?><styleSheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><cellXfs count="2"><xf numFmtId="0"/><xf numFmtId="14" applyNumberFormat="1"/></cellXfs></styleSheet>"#.to_string(),
+        );
+
+        for (index, (_, rows, merged)) in sheets.iter().enumerate() {
+            let mut data = String::new();
+            for (row, cells) in rows.iter().enumerate() {
+                data.push_str(&format!(r#"<row r="{}">"#, row + 1));
+                for (column, cell) in cells.iter().enumerate() {
+                    let reference = format!("{}{}", (b'A' + column as u8) as char, row + 1);
+                    match cell {
+                        Some(Cell::Text(text)) => data.push_str(&format!(
+                            r#"<c r="{reference}" t="inlineStr"><is><t>{text}</t></is></c>"#
+                        )),
+                        Some(Cell::Number(number)) => {
+                            data.push_str(&format!(r#"<c r="{reference}"><v>{number}</v></c>"#))
+                        }
+                        Some(Cell::Date(serial)) => data
+                            .push_str(&format!(r#"<c r="{reference}" s="1"><v>{serial}</v></c>"#)),
+                        None => {}
+                    }
+                }
+                data.push_str("</row>");
+            }
+            let merge_cells = if merged.is_empty() {
+                String::new()
+            } else {
+                let cells: String = merged
+                    .iter()
+                    .map(|range| format!(r#"<mergeCell ref="{range}"/>"#))
+                    .collect();
+                format!(
+                    r#"<mergeCells count="{}">{cells}</mergeCells>"#,
+                    merged.len()
+                )
+            };
+            add(
+                &format!("xl/worksheets/sheet{}.xml", index + 1),
+                format!(
+                    r#"<?xml version="1.0" encoding="UTF-8"?><worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>{data}</sheetData>{merge_cells}</worksheet>"#
+                ),
+            );
+        }
+
+        zip.finish().unwrap();
+        buffer.into_inner()
+    }
+
+    fn text(value: &'static str) -> Option<Cell> {
+        Some(Cell::Text(value))
+    }
+
+    fn number(value: f64) -> Option<Cell> {
+        Some(Cell::Number(value))
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_parse_statement() -> Result<()> {
+        let contents = workbook(&[(
+            "Statement",
+            vec![
+                vec![text("Date"), text("Amount"), text("Currency")],
+                // 44957 is 2023-01-31
+                vec![Some(Cell::Date(44957)), number(10.5), None],
+                vec![text("2023-02-28"), text("-3"), text("GBP")],
+                vec![],
+                vec![text("2023-03-31"), number(0.1), None],
+            ],
+            vec![],
+        )]);
+
+        let balances =
+            XlsxImporter::default().parse(&contents, "jan.xlsx", &ParseContext::default())?;
+
+        assert_eq!(balances.len(), 3);
+        assert_eq!(balances[0].date, date(2023, 1, 31));
+        assert_eq!(balances[0].amount, dec!(10.5));
+        assert_eq!(balances[0].currency, None);
+        assert_eq!(balances[1].date, date(2023, 2, 28));
+        assert_eq!(balances[1].amount, dec!(-3));
+        assert_eq!(balances[1].currency.as_deref(), Some("gbp"));
+        assert_eq!(balances[2].amount, dec!(0.1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merged_header() -> Result<()> {
+        // A merged title row, then a two-row header with `Date` merged down beside `Balance` over `Amount`
+        let contents = workbook(&[(
+            "Export",
+            vec![
+                vec![text("Example Bank statement"), None],
+                vec![text("Date"), text("Balance")],
+                vec![None, text("Amount")],
+                vec![text("2023-01-31"), number(125.25)],
+                vec![text("Closing balance"), number(125.25)],
+            ],
+            vec!["A1:B1", "A2:A3"],
+        )]);
+
+        let context = ParseContext::default();
+        let balances = XlsxImporter::default().parse(&contents, "jan.xlsx", &context)?;
+
+        assert_eq!(balances.len(), 1);
+        assert_eq!(balances[0].date, date(2023, 1, 31));
+        assert_eq!(balances[0].amount, dec!(125.25));
+        assert_eq!(context.warnings().len(), 1);
+        assert!(context.warnings()[0]
+            .starts_with("\"Closing balance\" in jan.xlsx sheet \"Export\" row 5"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sheet_selection() -> Result<()> {
+        let contents = workbook(&[
+            (
+                "Summary",
+                vec![vec![text("Account"), text("Example")]],
+                vec![],
+            ),
+            (
+                "EUR",
+                vec![
+                    vec![text("date"), text("amount")],
+                    vec![text("2023-01-31"), number(1.0)],
+                ],
+                vec![],
+            ),
+            (
+                "GBP",
+                vec![
+                    vec![text("date"), text("amount")],
+                    vec![text("2023-01-31"), number(2.0)],
+                ],
+                vec![],
+            ),
+        ]);
+        let context = ParseContext::default();
+
+        // The first sheet with a header row
+        let balances = XlsxImporter::default().parse(&contents, "jan.xlsx", &context)?;
+        assert_eq!(balances[0].amount, dec!(1));
+
+        let balances = XlsxImporter::sheet("GBP").parse(&contents, "jan.xlsx", &context)?;
+        assert_eq!(balances[0].amount, dec!(2));
+
+        let error = XlsxImporter::sheet("USD")
+            .parse(&contents, "jan.xlsx", &context)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No sheet \"USD\" in jan.xlsx (sheets: Summary, EUR, GBP)"
+        );
+        assert!(XlsxImporter::sheet("Summary")
+            .parse(&contents, "jan.xlsx", &context)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_amount() {
+        let contents = workbook(&[(
+            "Statement",
+            vec![
+                vec![text("date"), text("amount")],
+                vec![text("2023-01-31"), text("n/a")],
+            ],
+            vec![],
+        )]);
+
+        let error = XlsxImporter::default()
+            .parse(&contents, "jan.xlsx", &ParseContext::default())
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid amount \"n/a\" in jan.xlsx sheet \"Statement\" row 2"
+        );
+    }
+}
@@ -0,0 +1,599 @@
+//! The report as an Excel workbook, the form most accountants ask for: a summary sheet for the year listing every
+//! account, and a detail sheet for each account with its figures, notes and the year's balance records.
+//!
+//! The workbook is written as SpreadsheetML by hand, like the PDF worksheet, and its entries carry a fixed timestamp,
+//! so the same report always gives the same bytes.
+
+use anyhow::Result;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::io::Write;
+use std::str::FromStr;
+use zip::write::SimpleFileOptions;
+
+use crate::data::{Provider, UserData};
+use crate::fincen;
+use crate::report::overview::FILING_THRESHOLD_USD;
+use crate::report::{AccountReport, Report};
+
+/// Longest sheet name Excel accepts
+const MAX_SHEET_NAME: usize = 31;
+
+/// Style indexes into the `cellXfs` of `styles.xml`
+const DATE_STYLE: usize = 1;
+const HEADING_STYLE: usize = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Cell {
+    Text(String),
+    Heading(String),
+    Number(Decimal),
+    Date(NaiveDate),
+    Empty,
+}
+
+impl Cell {
+    fn text(value: impl Into<String>) -> Self {
+        Cell::Text(value.into())
+    }
+
+    fn heading(value: impl Into<String>) -> Self {
+        Cell::Heading(value.into())
+    }
+}
+
+struct Sheet {
+    name: String,
+    rows: Vec<Vec<Cell>>,
+}
+
+/// Renders the report as an `.xlsx` workbook
+pub fn report_workbook(report: &Report, user_data: &UserData) -> Result<Vec<u8>> {
+    let mut taken = vec![summary_sheet_name(report)];
+    let details: Vec<Sheet> = report
+        .accounts
+        .iter()
+        .map(|account_report| {
+            let name = sheet_name(&account_report.handle, &taken);
+            taken.push(name.clone());
+            detail_sheet(name, account_report, report.year, user_data)
+        })
+        .collect();
+
+    let mut sheets = vec![summary_sheet(report, user_data, &details)?];
+    sheets.extend(details);
+    write_workbook(&sheets)
+}
+
+fn summary_sheet_name(report: &Report) -> String {
+    format!("FBAR {}", report.year)
+}
+
+fn summary_sheet(report: &Report, user_data: &UserData, details: &[Sheet]) -> Result<Sheet> {
+    let aggregate = report.aggregate_max_usd()?;
+    let mut rows = vec![vec![Cell::heading(format!(
+        "FBAR summary for {}",
+        report.year
+    ))]];
+    if let Some(filer) = user_data.primary_filer() {
+        rows.push(vec![Cell::text("Filer"), Cell::text(&filer.name)]);
+    }
+    if let Some(metadata) = &report.metadata {
+        rows.push(vec![
+            Cell::text("Report ID"),
+            Cell::text(&metadata.report_id),
+        ]);
+    }
+    rows.push(vec![
+        Cell::text("Accounts"),
+        Cell::Number(report.accounts.len().into()),
+    ]);
+    rows.push(vec![
+        Cell::text("Aggregate maximum (USD)"),
+        Cell::Number(aggregate.amount),
+    ]);
+    rows.push(vec![
+        Cell::text("FBAR required"),
+        Cell::text(if aggregate.amount > FILING_THRESHOLD_USD {
+            "Yes"
+        } else {
+            "No"
+        }),
+    ]);
+    rows.push(Vec::new());
+
+    rows.push(
+        [
+            "Account",
+            "Institution",
+            "Part",
+            "Currency",
+            "Maximum value",
+            "Rate (per USD)",
+            "Maximum value (USD)",
+            "Filed as (USD)",
+            "Maximum on",
+            "Rate source",
+            "Counted from",
+            "Counted to",
+            "Detail sheet",
+        ]
+        .into_iter()
+        .map(Cell::heading)
+        .collect(),
+    );
+    for (account_report, detail) in report.accounts.iter().zip(details) {
+        let (from, to) = match account_report.open_period {
+            Some(period) => (Cell::Date(period.start), Cell::Date(period.end)),
+            None => (Cell::Empty, Cell::Empty),
+        };
+        rows.push(vec![
+            Cell::text(&account_report.handle),
+            Cell::text(provider_name(account_report, user_data)),
+            Cell::text(account_report.part()),
+            Cell::text(account_report.currency_code.to_uppercase()),
+            Cell::Number(account_report.max_balance.amount),
+            rate_cell(account_report.exchange_rate),
+            Cell::Number(account_report.max_balance_usd.amount),
+            Cell::Number(account_report.filed_value()?),
+            account_report
+                .max_balance_date
+                .map_or(Cell::Empty, Cell::Date),
+            Cell::text(account_report.rate_source.to_string()),
+            from,
+            to,
+            Cell::text(&detail.name),
+        ]);
+    }
+
+    if !report.excluded.is_empty() {
+        rows.push(Vec::new());
+        rows.push(vec![Cell::heading("Left out of the FBAR")]);
+        rows.push(vec![
+            Cell::heading("Account"),
+            Cell::heading("Institution"),
+            Cell::heading("Reason"),
+        ]);
+        for excluded in &report.excluded {
+            rows.push(vec![
+                Cell::text(&excluded.handle),
+                Cell::text(&excluded.provider),
+                Cell::text(&excluded.reason),
+            ]);
+        }
+    }
+
+    Ok(Sheet {
+        name: summary_sheet_name(report),
+        rows,
+    })
+}
+
+fn detail_sheet(
+    name: String,
+    account_report: &AccountReport,
+    year: i32,
+    user_data: &UserData,
+) -> Sheet {
+    let account = user_data
+        .accounts
+        .iter()
+        .find(|account| account.handle == account_report.handle);
+
+    let mut rows = vec![
+        vec![Cell::heading(&account_report.handle)],
+        vec![
+            Cell::text("Institution"),
+            Cell::text(provider_name(account_report, user_data)),
+        ],
+        vec![Cell::text("Part"), Cell::text(account_report.part())],
+        vec![
+            Cell::text("Account number"),
+            account
+                .and_then(fincen::account_number)
+                .map_or(Cell::Empty, Cell::Text),
+        ],
+        vec![
+            Cell::text("Currency"),
+            Cell::text(account_report.currency_code.to_uppercase()),
+        ],
+        vec![
+            Cell::text("Maximum value"),
+            Cell::Number(account_report.max_balance.amount),
+        ],
+        vec![
+            Cell::text("Rate (per USD)"),
+            rate_cell(account_report.exchange_rate),
+        ],
+        vec![
+            Cell::text("Rate source"),
+            Cell::text(account_report.rate_source.to_string()),
+        ],
+        vec![
+            Cell::text("Maximum value (USD)"),
+            Cell::Number(account_report.max_balance_usd.amount),
+        ],
+        vec![
+            Cell::text("Maximum on"),
+            account_report
+                .max_balance_date
+                .map_or(Cell::Empty, Cell::Date),
+        ],
+    ];
+    if let Some(period) = account_report.open_period {
+        rows.push(vec![Cell::text("Counted from"), Cell::Date(period.start)]);
+        rows.push(vec![Cell::text("Counted to"), Cell::Date(period.end)]);
+    }
+    if let Some(entity) = &account_report.entity {
+        rows.push(vec![Cell::text("Held through"), Cell::text(entity)]);
+    }
+    if let Some(authority) = &account_report.signature_authority {
+        rows.push(vec![Cell::text("Owner"), Cell::text(&authority.owner)]);
+        rows.push(vec![
+            Cell::text("Filer's title"),
+            Cell::text(&authority.title),
+        ]);
+    }
+    if let Some(owner) = &account_report.joint_owner {
+        rows.push(vec![Cell::text("Joint owner"), Cell::text(owner)]);
+    }
+
+    if !account_report.notes.is_empty() {
+        rows.push(Vec::new());
+        rows.push(vec![Cell::heading("Notes")]);
+        rows.extend(
+            account_report
+                .notes
+                .iter()
+                .map(|note| vec![Cell::text(note)]),
+        );
+    }
+
+    let mut records: Vec<_> = account
+        .map(|account| account.balances_in_year(year).collect())
+        .unwrap_or_default();
+    records.sort_by_key(|record| record.date);
+    rows.push(Vec::new());
+    rows.push(vec![
+        Cell::heading("Date"),
+        Cell::heading("Balance"),
+        Cell::heading("Currency"),
+        Cell::heading("Entered by hand"),
+    ]);
+    for record in records {
+        rows.push(vec![
+            Cell::Date(record.date),
+            Cell::Number(record.amount),
+            Cell::text(
+                record
+                    .currency
+                    .as_deref()
+                    .unwrap_or(&account_report.currency_code)
+                    .to_uppercase(),
+            ),
+            match record.manual {
+                true => Cell::text(record.note.as_deref().unwrap_or_default()),
+                false => Cell::Empty,
+            },
+        ]);
+    }
+
+    Sheet { name, rows }
+}
+
+/// The rate as a number, written the shortest way that reads back as the same float, as in the report
+fn rate_cell(rate: Option<f64>) -> Cell {
+    rate.and_then(|rate| Decimal::from_str(&rate.to_string()).ok())
+        .map_or(Cell::Empty, Cell::Number)
+}
+
+fn provider_name(account_report: &AccountReport, user_data: &UserData) -> String {
+    user_data
+        .providers
+        .iter()
+        .find(|provider| provider.handle == account_report.provider)
+        .map_or_else(
+            || account_report.provider.clone(),
+            Provider::institution_name,
+        )
+}
+
+/// A name Excel accepts for the sheet, unlike any already `taken`: without the characters it forbids, at most 31
+/// characters, and numbered when it would clash
+fn sheet_name(handle: &str, taken: &[String]) -> String {
+    let cleaned: String = handle
+        .chars()
+        .filter(|c| !matches!(c, '[' | ']' | ':' | '*' | '?' | '/' | '\\'))
+        .take(MAX_SHEET_NAME)
+        .collect();
+    let cleaned = if cleaned.is_empty() {
+        "Account".to_string()
+    } else {
+        cleaned
+    };
+
+    let clashes = |name: &str| taken.iter().any(|taken| taken.eq_ignore_ascii_case(name));
+    if !clashes(&cleaned) {
+        return cleaned;
+    }
+    (2..)
+        .map(|n| {
+            let suffix = format!(" ({})", n);
+            let stem: String = cleaned
+                .chars()
+                .take(MAX_SHEET_NAME - suffix.len())
+                .collect();
+            stem + &suffix
+        })
+        .find(|name| !clashes(name))
+        .unwrap_or(cleaned)
+}
+
+fn write_workbook(sheets: &[Sheet]) -> Result<Vec<u8>> {
+    let mut files = vec![
+        (
+            "[Content_Types].xml".to_string(),
+            content_types(sheets.len()),
+        ),
+        ("_rels/.rels".to_string(), ROOT_RELATIONSHIPS.to_string()),
+        ("xl/workbook.xml".to_string(), workbook_xml(sheets)),
+        (
+            "xl/_rels/workbook.xml.rels".to_string(),
+            workbook_relationships(sheets.len()),
+        ),
+        ("xl/styles.xml".to_string(), STYLES.to_string()),
+    ];
+    for (index, sheet) in sheets.iter().enumerate() {
+        files.push((
+            format!("xl/worksheets/sheet{}.xml", index + 1),
+            sheet_xml(sheet),
+        ));
+    }
+
+    // The default options date entries 1980-01-01, so the bytes depend only on the contents
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let mut archive = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, contents) in files {
+        archive.start_file(name, options)?;
+        archive.write_all(contents.as_bytes())?;
+    }
+    Ok(archive.finish()?.into_inner())
+}
+
+const XML_HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#;
+
+const ROOT_RELATIONSHIPS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/></Relationships>"#;
+
+/// A default style, one for dates (the built-in short date format, 14) and one for bold headings
+const STYLES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><styleSheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><fonts count="2"><font><sz val="11"/><name val="Calibri"/></font><font><b/><sz val="11"/><name val="Calibri"/></font></fonts><fills count="2"><fill><patternFill patternType="none"/></fill><fill><patternFill patternType="gray125"/></fill></fills><borders count="1"><border><left/><right/><top/><bottom/><diagonal/></border></borders><cellStyleXfs count="1"><xf numFmtId="0" fontId="0" fillId="0" borderId="0"/></cellStyleXfs><cellXfs count="3"><xf numFmtId="0" fontId="0" fillId="0" borderId="0" xfId="0"/><xf numFmtId="14" fontId="0" fillId="0" borderId="0" xfId="0" applyNumberFormat="1"/><xf numFmtId="0" fontId="1" fillId="0" borderId="0" xfId="0" applyFont="1"/></cellXfs></styleSheet>"#;
+
+fn content_types(sheets: usize) -> String {
+    let overrides: String = (1..=sheets)
+        .map(|n| {
+            format!(
+                r#"<Override PartName="/xl/worksheets/sheet{}.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>"#,
+                n
+            )
+        })
+        .collect();
+    format!(
+        r#"{}<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/><Override PartName="/xl/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml"/>{}</Types>"#,
+        XML_HEADER, overrides
+    )
+}
+
+fn workbook_xml(sheets: &[Sheet]) -> String {
+    let entries: String = sheets
+        .iter()
+        .enumerate()
+        .map(|(index, sheet)| {
+            format!(
+                r#"<sheet name="{}" sheetId="{n}" r:id="rId{n}"/>"#,
+                escape(&sheet.name),
+                n = index + 1
+            )
+        })
+        .collect();
+    format!(
+        r#"{}<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets>{}</sheets></workbook>"#,
+        XML_HEADER, entries
+    )
+}
+
+fn workbook_relationships(sheets: usize) -> String {
+    let relationships: String = (1..=sheets)
+        .map(|n| {
+            format!(
+                r#"<Relationship Id="rId{n}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet{n}.xml"/>"#
+            )
+        })
+        .collect();
+    format!(
+        r#"{}<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">{}<Relationship Id="rId{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/></Relationships>"#,
+        XML_HEADER,
+        relationships,
+        sheets + 1
+    )
+}
+
+fn sheet_xml(sheet: &Sheet) -> String {
+    let mut data = String::new();
+    for (row, cells) in sheet.rows.iter().enumerate() {
+        data.push_str(&format!(r#"<row r="{}">"#, row + 1));
+        for (column, cell) in cells.iter().enumerate() {
+            let reference = format!("{}{}", column_name(column), row + 1);
+            match cell {
+                Cell::Text(text) => data.push_str(&inline_string(&reference, text, None)),
+                Cell::Heading(text) => {
+                    data.push_str(&inline_string(&reference, text, Some(HEADING_STYLE)))
+                }
+                Cell::Number(number) => {
+                    data.push_str(&format!(r#"<c r="{}"><v>{}</v></c>"#, reference, number))
+                }
+                Cell::Date(date) => data.push_str(&format!(
+                    r#"<c r="{}" s="{}"><v>{}</v></c>"#,
+                    reference,
+                    DATE_STYLE,
+                    serial_date(*date)
+                )),
+                Cell::Empty => {}
+            }
+        }
+        data.push_str("</row>");
+    }
+    format!(
+        r#"{}<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>{}</sheetData></worksheet>"#,
+        XML_HEADER, data
+    )
+}
+
+fn inline_string(reference: &str, text: &str, style: Option<usize>) -> String {
+    let style = style.map_or(String::new(), |style| format!(r#" s="{}""#, style));
+    format!(
+        r#"<c r="{}"{} t="inlineStr"><is><t xml:space="preserve">{}</t></is></c>"#,
+        reference,
+        style,
+        escape(text)
+    )
+}
+
+/// Column letters for a zero-based index: A to Z, then AA, AB and so on
+fn column_name(index: usize) -> String {
+    let mut name = Vec::new();
+    let mut remaining = index + 1;
+    while remaining > 0 {
+        let digit = (remaining - 1) % 26;
+        name.push(b'A' + digit as u8);
+        remaining = (remaining - 1) / 26;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap_or_default()
+}
+
+/// Days since Excel's epoch, 1899-12-30 in its 1900 date system
+fn serial_date(date: NaiveDate) -> i64 {
+    let epoch = NaiveDate::from_ymd_opt(1899, 12, 30).unwrap_or_default();
+    (date - epoch).num_days()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Account, Address, BalanceRecord, Provider};
+    use crate::facts::{AnnualFact, ExchangeRate, Facts};
+    use crate::report_context::ReportContext;
+    use calamine::{Data, Reader, Xlsx};
+    use rust_decimal_macros::dec;
+    use std::io::Cursor;
+
+    fn account(handle: &str, amount: Decimal) -> Account {
+        Account {
+            handle: handle.to_string(),
+            provider: "example_bank".to_string(),
+            currency_code: "EUR".to_string(),
+            balances: vec![
+                BalanceRecord {
+                    date: NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(),
+                    amount,
+                    ..Default::default()
+                },
+                BalanceRecord {
+                    date: NaiveDate::from_ymd_opt(2023, 1, 31).unwrap(),
+                    amount: dec!(100),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_report_workbook() -> Result<()> {
+        let user_data = UserData {
+            providers: vec![Provider {
+                handle: "example_bank".to_string(),
+                name: "Example Bank & Trust".to_string(),
+                address: Address::from("1 Bank Street, Zurich, Switzerland"),
+                ..Default::default()
+            }],
+            accounts: vec![
+                account("savings", dec!(8000)),
+                account("joint/holiday", dec!(400.5)),
+            ],
+            ..Default::default()
+        };
+        let facts = Facts {
+            years: vec![AnnualFact {
+                year: 2023,
+                exchange_rates: vec![ExchangeRate::new("EUR".to_string(), 0.8).unwrap()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let report = Report::generate(2023, &user_data, &ReportContext::new(facts, None))?;
+
+        let bytes = report_workbook(&report, &user_data)?;
+        assert_eq!(bytes, report_workbook(&report, &user_data)?);
+
+        let mut workbook = Xlsx::new(Cursor::new(bytes))?;
+        assert_eq!(
+            workbook.sheet_names(),
+            vec!["FBAR 2023", "jointholiday", "savings"]
+        );
+
+        let summary = workbook.worksheet_range("FBAR 2023")?;
+        assert_eq!(summary.get_value((3, 1)), Some(&Data::Float(10500.63)));
+        assert_eq!(
+            summary.get_value((4, 1)),
+            Some(&Data::String("Yes".to_string()))
+        );
+        assert_eq!(
+            summary.get_value((7, 1)),
+            Some(&Data::String("Example Bank & Trust".to_string()))
+        );
+        assert_eq!(summary.get_value((8, 7)), Some(&Data::Float(10000.0)));
+        assert!(matches!(summary.get_value((8, 8)), Some(Data::DateTime(_))));
+        assert_eq!(
+            summary.get_value((7, 12)),
+            Some(&Data::String("jointholiday".to_string()))
+        );
+
+        let detail = workbook.worksheet_range("savings")?;
+        let rows: Vec<_> = detail.rows().collect();
+        let records = rows
+            .iter()
+            .position(|row| row[0] == Data::String("Date".to_string()))
+            .unwrap();
+        assert_eq!(rows.len(), records + 3);
+        assert!(matches!(rows[records + 1][0], Data::DateTime(_)));
+        assert_eq!(rows[records + 1][1], Data::Float(100.0));
+        assert_eq!(rows[records + 2][1], Data::Float(8000.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sheet_name() {
+        let taken = vec!["FBAR 2023".to_string(), "savings".to_string()];
+        assert_eq!(sheet_name("current", &taken), "current");
+        assert_eq!(sheet_name("Savings", &taken), "Savings (2)");
+        assert_eq!(sheet_name("a/b:c", &taken), "abc");
+        assert_eq!(sheet_name(&"x".repeat(40), &taken).len(), MAX_SHEET_NAME);
+    }
+
+    #[test]
+    fn test_column_name() {
+        assert_eq!(column_name(0), "A");
+        assert_eq!(column_name(25), "Z");
+        assert_eq!(column_name(26), "AA");
+        assert_eq!(column_name(701), "ZZ");
+        assert_eq!(column_name(702), "AAA");
+    }
+}
@@ -0,0 +1,318 @@
+//! Conformance tests for the FinCEN XML export. Each test vector is an anonymized copy of a synthetic data directory,
+//! exported with a fixed signing date and compared against the expected batch in `tests/snapshots`. Every batch is
+//! also checked structurally: well-formed, in the `fc2` namespace, with elements only where the BSA E-Filing schema
+//! puts them and `SeqNum`s numbering them in document order. That structure is hand-written, not FinCEN's XSD, which
+//! isn't vendored; validating against the XSD is still to be done. After an intended change to the export, review and
+//! accept the new batches with `cargo insta review`.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+use fbar_prep::anonymize::write_anonymized;
+use fbar_prep::app::{App, AppConfig};
+use fbar_prep::data::{Filing, LateFilingReason, UserData};
+use fbar_prep::examples::{self, Example, EXAMPLE_YEAR};
+use fbar_prep::fincen::{fbar_xml, SchemaVersion};
+use fbar_prep::report::Report;
+
+/// Every vector is signed on the same day, so the batches only change when the export does
+const SIGNED_ON: (i32, u32, u32) = (2024, 3, 15);
+
+/// The elements each element may contain, in the order the schema requires, as transcribed from the BSA E-Filing
+/// documentation. Elements not listed hold text.
+const STRUCTURE: &[(&str, &[&str])] = &[
+    ("EFilingBatchXML", &["FormTypeCode", "Activity"]),
+    (
+        "Activity",
+        &[
+            "ApprovalOfficialSignatureDateText",
+            "Party",
+            "Account",
+            "ForeignAccountActivity",
+            "ActivityNarrativeInformation",
+        ],
+    ),
+    (
+        "Party",
+        &[
+            "ActivityPartyTypeCode",
+            "FilerFinancialInterest25ForeignAccountIndicator",
+            "FinancialInterest25ForeignAccountCount",
+            "FilerTypeIndividualIndicator",
+            "PartyName",
+            "Address",
+            "PartyIdentification",
+            "PartyOccupationBusiness",
+        ],
+    ),
+    (
+        "PartyName",
+        &[
+            "PartyNameTypeCode",
+            "RawEntityIndividualLastName",
+            "RawIndividualFirstName",
+            "RawPartyFullName",
+        ],
+    ),
+    (
+        "Address",
+        &[
+            "RawCityText",
+            "RawCountryCodeText",
+            "RawStateCodeText",
+            "RawStreetAddress1Text",
+            "RawZIPCode",
+        ],
+    ),
+    (
+        "PartyIdentification",
+        &[
+            "PartyIdentificationNumberText",
+            "PartyIdentificationTypeCode",
+        ],
+    ),
+    ("PartyOccupationBusiness", &["OccupationBusinessText"]),
+    (
+        "Account",
+        &[
+            "AccountMaximumValueAmountText",
+            "AccountNumberText",
+            "AccountTypeCode",
+            "OtherAccountTypeText",
+            "Party",
+            "PartyAccountAssociation",
+        ],
+    ),
+    (
+        "PartyAccountAssociation",
+        &["PartyAccountAssociationTypeCode"],
+    ),
+    (
+        "ForeignAccountActivity",
+        &["ReportCalendarYearText", "LateFilingReasonCode"],
+    ),
+    (
+        "ActivityNarrativeInformation",
+        &["ActivityNarrativeSequenceNumber", "ActivityNarrativeText"],
+    ),
+];
+
+/// Elements numbered with a `SeqNum`
+const SEQUENCED: &[&str] = &[
+    "Activity",
+    "Party",
+    "PartyName",
+    "Address",
+    "PartyIdentification",
+    "PartyOccupationBusiness",
+    "Account",
+    "PartyAccountAssociation",
+    "ForeignAccountActivity",
+    "ActivityNarrativeInformation",
+];
+
+/// Loads an anonymized copy of the data directory and generates its report
+fn anonymized_report(source: &Path, year: i32) -> (UserData, Report) {
+    let dir = tempfile::TempDir::new().unwrap();
+    write_anonymized(source, dir.path()).unwrap();
+    let mut config = AppConfig::new(dir.path().to_path_buf());
+    config.cache = false;
+    let app = App::new(config);
+    let (user_data, context) = app.data().unwrap();
+    let report = Report::generate(year, user_data, context).unwrap();
+    (user_data.clone(), report)
+}
+
+fn example_vector(example: Example) -> (UserData, Report) {
+    let dir = tempfile::TempDir::new().unwrap();
+    examples::write_example(dir.path(), example).unwrap();
+    anonymized_report(dir.path(), EXAMPLE_YEAR)
+}
+
+fn fixture_vector(name: &str, year: i32) -> (UserData, Report) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    anonymized_report(&path, year)
+}
+
+fn export(user_data: &UserData, report: &Report) -> String {
+    let (year, month, day) = SIGNED_ON;
+    let xml = fbar_xml(
+        report,
+        user_data,
+        NaiveDate::from_ymd_opt(year, month, day).unwrap(),
+    )
+    .unwrap();
+    check_structure(&xml);
+    xml
+}
+
+/// Checks the batch against `STRUCTURE` and `SEQUENCED`, panicking with the path of the first element out of place
+fn check_structure(xml: &str) {
+    let structure: HashMap<&str, &[&str]> = STRUCTURE.iter().copied().collect();
+    let mut reader = Reader::from_str(xml);
+    // Open elements, with the position in their parent's list of children the last child was at
+    let mut open: Vec<(String, usize)> = Vec::new();
+    let mut sequence = 0;
+    let mut activities = 0;
+    let mut activity_count = None;
+
+    loop {
+        let event = reader
+            .read_event()
+            .unwrap_or_else(|err| panic!("Malformed XML: {}", err));
+        match event {
+            Event::Start(start) => {
+                let name = String::from_utf8(start.name().as_ref().to_vec()).unwrap();
+                let local = name
+                    .strip_prefix("fc2:")
+                    .unwrap_or_else(|| panic!("{} isn't in the fc2 namespace", name))
+                    .to_string();
+                let path = open
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .chain([local.as_str()])
+                    .collect::<Vec<_>>()
+                    .join("/");
+
+                match open.last_mut() {
+                    None => assert_eq!(local, "EFilingBatchXML", "root element"),
+                    Some((parent, position)) => {
+                        let children = structure
+                            .get(parent.as_str())
+                            .unwrap_or_else(|| panic!("{} holds text, not elements", parent));
+                        let index = children
+                            .iter()
+                            .position(|child| *child == local)
+                            .unwrap_or_else(|| panic!("{} isn't allowed there", path));
+                        assert!(index >= *position, "{} is out of order", path);
+                        *position = index;
+                    }
+                }
+
+                let attributes: HashMap<String, String> = start
+                    .attributes()
+                    .map(|attribute| {
+                        let attribute = attribute.unwrap();
+                        (
+                            String::from_utf8(attribute.key.as_ref().to_vec()).unwrap(),
+                            attribute
+                                .decode_and_unescape_value(&reader)
+                                .unwrap()
+                                .into_owned(),
+                        )
+                    })
+                    .collect();
+                if SEQUENCED.contains(&local.as_str()) {
+                    sequence += 1;
+                    assert_eq!(
+                        attributes.get("SeqNum"),
+                        Some(&sequence.to_string()),
+                        "SeqNum of {}",
+                        path
+                    );
+                }
+                if local == "EFilingBatchXML" {
+                    assert_eq!(
+                        attributes.get("xmlns:fc2").map(String::as_str),
+                        Some("www.fincen.gov/base")
+                    );
+                    activity_count = attributes.get("ActivityCount").cloned();
+                }
+                if local == "Activity" {
+                    activities += 1;
+                }
+                open.push((local, 0));
+            }
+            Event::Text(text) => {
+                let text = text.unescape().unwrap();
+                if let Some((parent, _)) = open.last() {
+                    assert!(
+                        text.trim().is_empty() || !structure.contains_key(parent.as_str()),
+                        "{} holds elements, not text",
+                        parent
+                    );
+                    if parent == "AccountMaximumValueAmountText" {
+                        assert!(
+                            text.parse::<u64>().is_ok(),
+                            "{:?} isn't a whole dollar amount",
+                            text
+                        );
+                    }
+                }
+            }
+            Event::End(end) => {
+                let name = String::from_utf8(end.name().as_ref().to_vec()).unwrap();
+                let (local, _) = open.pop().unwrap();
+                assert_eq!(name, format!("fc2:{}", local));
+            }
+            Event::Empty(empty) => panic!(
+                "Empty element {}",
+                String::from_utf8_lossy(empty.name().as_ref())
+            ),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    assert!(open.is_empty(), "unclosed elements {:?}", open);
+    assert_eq!(activity_count, Some(activities.to_string()));
+}
+
+macro_rules! assert_xml_snapshot {
+    ($name:expr, $xml:expr) => {
+        insta::assert_snapshot!($name.to_string(), $xml);
+    };
+}
+
+#[test]
+fn basic() {
+    let (user_data, report) = fixture_vector("basic", 2023);
+    assert_xml_snapshot!("basic_2023", export(&user_data, &report));
+}
+
+#[test]
+fn examples() {
+    for example in Example::ALL {
+        let (user_data, report) = example_vector(example);
+        assert_xml_snapshot!(example.name(), export(&user_data, &report));
+    }
+}
+
+#[test]
+fn late_filing() {
+    let (mut user_data, report) = example_vector(Example::SingleFiler);
+    user_data.filings = vec![Filing {
+        year: EXAMPLE_YEAR,
+        filed_on: NaiveDate::from_ymd_opt(EXAMPLE_YEAR + 2, 2, 1),
+        late_reason: Some(LateFilingReason::Other),
+        explanation: Some("Moved abroad & missed the deadline.".to_string()),
+    }];
+    for version in SchemaVersion::ALL {
+        user_data.fincen_schema_version = Some(version);
+        assert_xml_snapshot!(
+            format!("late_filing_{}", version),
+            export(&user_data, &report)
+        );
+    }
+}
+
+#[test]
+fn structure_check_catches_misplaced_elements() {
+    let (user_data, report) = fixture_vector("basic", 2023);
+    let xml = export(&user_data, &report);
+    let misplaced = xml
+        .replacen("<fc2:FormTypeCode>FBARX</fc2:FormTypeCode>", "", 1)
+        .replacen(
+            "</fc2:EFilingBatchXML>",
+            "<fc2:FormTypeCode>FBARX</fc2:FormTypeCode>\n</fc2:EFilingBatchXML>",
+            1,
+        );
+    let result = std::panic::catch_unwind(|| check_structure(&misplaced));
+    assert!(result.is_err());
+}
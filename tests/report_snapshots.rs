@@ -0,0 +1,117 @@
+//! Golden-file tests for report output. Each directory under `tests/fixtures` is a synthetic data directory; its
+//! report is compared against the snapshot in `tests/snapshots`. After an intended change to report math or
+//! formatting, review and accept the new snapshots with `cargo insta review`. The examples `fbar_prep example`
+//! writes are snapshotted the same way, from a temporary directory.
+
+use std::path::Path;
+
+use fbar_prep::app::{App, AppConfig};
+use fbar_prep::examples::{self, Example, EXAMPLE_YEAR};
+use fbar_prep::report::Report;
+
+/// Generates a fixture's report the way the CLI does, with the bundled facts and the fixture's own rates and prices
+fn fixture_report(name: &str, year: i32) -> Report {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    data_dir_report(&path, name, year)
+}
+
+/// Generates an example's report from a freshly written copy of it
+fn example_report(example: Example) -> Report {
+    let dir = tempfile::TempDir::new().unwrap();
+    examples::write_example(dir.path(), example).unwrap();
+    data_dir_report(dir.path(), example.name(), EXAMPLE_YEAR)
+}
+
+fn data_dir_report(path: &Path, name: &str, year: i32) -> Report {
+    let mut config = AppConfig::new(path.to_path_buf());
+    // Keep the fixtures free of tool state
+    config.cache = false;
+    let app = App::new(config);
+    let (user_data, context) = app
+        .data()
+        .unwrap_or_else(|err| panic!("Could not load fixture {}: {:#}", name, err));
+    assert_eq!(
+        app.parse_context().warnings(),
+        Vec::<String>::new(),
+        "{}",
+        name
+    );
+
+    Report::generate(year, user_data, context).unwrap()
+}
+
+/// Snapshots a fixture's report. The metadata holds the crate version and input hashes, so it's left out to keep
+/// snapshots stable across releases; `verify` covers it instead.
+macro_rules! assert_report_snapshot {
+    ($name:expr, $year:expr) => {
+        insta::assert_yaml_snapshot!(
+            format!("{}_{}", $name, $year),
+            fixture_report($name, $year),
+            { ".metadata" => "[metadata]" }
+        );
+    };
+    ($example:expr) => {
+        insta::assert_yaml_snapshot!(
+            format!("example_{}", $example.name()),
+            example_report($example),
+            { ".metadata" => "[metadata]" }
+        );
+    };
+}
+
+#[test]
+fn basic() {
+    assert_report_snapshot!("basic", 2023);
+}
+
+#[test]
+fn partial_year() {
+    assert_report_snapshot!("partial_year", 2023);
+}
+
+#[test]
+fn imported_statements() {
+    assert_report_snapshot!("imported_statements", 2023);
+}
+
+#[test]
+fn wallet() {
+    assert_report_snapshot!("wallet", 2023);
+}
+
+#[test]
+fn crypto() {
+    assert_report_snapshot!("crypto", 2023);
+}
+
+#[test]
+fn rate_sets() {
+    assert_report_snapshot!("rate_sets", 2023);
+}
+
+#[test]
+fn example_single_filer() {
+    assert_report_snapshot!(Example::SingleFiler);
+}
+
+#[test]
+fn example_joint_couple() {
+    assert_report_snapshot!(Example::JointCouple);
+}
+
+#[test]
+fn example_many_accounts() {
+    assert_report_snapshot!(Example::ManyAccounts);
+}
+
+#[test]
+fn example_entity_held() {
+    assert_report_snapshot!(Example::EntityHeld);
+}
+
+#[test]
+fn example_signature_authority() {
+    assert_report_snapshot!(Example::SignatureAuthority);
+}